@@ -3,6 +3,7 @@
  * Run this after circuit compilation and setup
  */
 
+const crypto = require('crypto');
 const fs = require('fs');
 const path = require('path');
 
@@ -53,49 +54,61 @@ async function loadVerificationKey() {
   console.log('='.repeat(60));
   console.log(`
 // Verification key constants (generated from circuit)
-const VK_ALPHA_G1: [u8; 64] = [
+const VK_ALPHA_G1: G1Point = G1Point::new([
     // X coordinate (32 bytes)
     ${g1ToBytes(alpha_g1[0]).join(', ')},
     // Y coordinate (32 bytes)
     ${g1ToBytes(alpha_g1[1]).join(', ')},
-];
+]);
 
-const VK_BETA_G2: [u8; 128] = [
-    // X1 coordinate (32 bytes)
-    ${g2ToBytes(beta_g2[0][0]).join(', ')},
-    // X2 coordinate (32 bytes)
+const VK_BETA_G2: G2Point = G2Point::new([
+    // X.c1 (32 bytes)
     ${g2ToBytes(beta_g2[0][1]).join(', ')},
-    // Y1 coordinate (32 bytes)
-    ${g2ToBytes(beta_g2[1][0]).join(', ')},
-    // Y2 coordinate (32 bytes)
+    // X.c0 (32 bytes)
+    ${g2ToBytes(beta_g2[0][0]).join(', ')},
+    // Y.c1 (32 bytes)
     ${g2ToBytes(beta_g2[1][1]).join(', ')},
-];
+    // Y.c0 (32 bytes)
+    ${g2ToBytes(beta_g2[1][0]).join(', ')},
+]);
 
-const VK_GAMMA_G2: [u8; 128] = [
-    ${g2ToBytes(gamma_g2[0][0]).join(', ')},
+const VK_GAMMA_G2: G2Point = G2Point::new([
     ${g2ToBytes(gamma_g2[0][1]).join(', ')},
-    ${g2ToBytes(gamma_g2[1][0]).join(', ')},
+    ${g2ToBytes(gamma_g2[0][0]).join(', ')},
     ${g2ToBytes(gamma_g2[1][1]).join(', ')},
-];
+    ${g2ToBytes(gamma_g2[1][0]).join(', ')},
+]);
 
-const VK_DELTA_G2: [u8; 128] = [
-    ${g2ToBytes(delta_g2[0][0]).join(', ')},
+const VK_DELTA_G2: G2Point = G2Point::new([
     ${g2ToBytes(delta_g2[0][1]).join(', ')},
-    ${g2ToBytes(delta_g2[1][0]).join(', ')},
+    ${g2ToBytes(delta_g2[0][0]).join(', ')},
     ${g2ToBytes(delta_g2[1][1]).join(', ')},
-];
+    ${g2ToBytes(delta_g2[1][0]).join(', ')},
+]);
 
-const VK_IC: [[u8; 64]; ${ic.length}] = [
-${ic.map(point => `    [
+const VK_IC: [G1Point; ${ic.length}] = [
+${ic.map(point => `    G1Point::new([
         ${g1ToBytes(point[0]).join(', ')},
         ${g1ToBytes(point[1]).join(', ')},
-    ]`).join(',\n')}
+    ])`).join(',\n')}
 ];
   `);
 
+  // Public signal order, used to pin the IC layout on-chain
+  const circuitInfoPath = path.join(__dirname, 'build', 'circuit_info.json');
+  const circuitInfo = JSON.parse(fs.readFileSync(circuitInfoPath, 'utf8'));
+  const signalNames = circuitInfo.publicInputs
+    .sort((a, b) => a.index - b.index)
+    .map(input => input.name);
+  if (signalNames.length !== ic.length - 1) {
+    console.error(`circuit_info.json lists ${signalNames.length} public inputs but the key has ${ic.length - 1}`);
+    process.exit(1);
+  }
+  console.log(`\nSignal layout: ${signalNames.join(', ')}`);
+
   // Save to file
   const outputPath = path.join(__dirname, 'build', 'vkey_constants.rs');
-  const rustCode = generateRustCode(alpha_g1, beta_g2, gamma_g2, delta_g2, ic);
+  const rustCode = generateRustCode(alpha_g1, beta_g2, gamma_g2, delta_g2, ic, signalNames);
   fs.writeFileSync(outputPath, rustCode);
   console.log(`\nRust code saved to: ${outputPath}`);
 }
@@ -110,46 +123,73 @@ function g1ToBytes(coordinate) {
   return bytes;
 }
 
+/**
+ * One Fq2 limb; snarkjs lists each G2 coordinate as [c0, c1], the syscalls
+ * take c1 first (see contracts/src/point.rs), so callers emit [1] then [0]
+ */
 function g2ToBytes(coordinate) {
   return g1ToBytes(coordinate);
 }
 
-function generateRustCode(alpha_g1, beta_g2, gamma_g2, delta_g2, ic) {
+/**
+ * SHA256 over each signal name as u32 little-endian length followed by its
+ * UTF-8 bytes; must match signal_layout_hash() in contracts/src/lib.rs
+ */
+function signalLayoutHash(names) {
+  const hash = crypto.createHash('sha256');
+  for (const name of names) {
+    const bytes = Buffer.from(name, 'utf8');
+    const len = Buffer.alloc(4);
+    len.writeUInt32LE(bytes.length);
+    hash.update(len);
+    hash.update(bytes);
+  }
+  return [...hash.digest()].map(b => `0x${b.toString(16).padStart(2, '0')}`);
+}
+
+function generateRustCode(alpha_g1, beta_g2, gamma_g2, delta_g2, ic, signalNames) {
   return `// Auto-generated verification key constants
 // Generated from circuit compilation
 // DO NOT EDIT MANUALLY
 
-pub const VK_ALPHA_G1: [u8; 64] = [
+use crate::point::{G1Point, G2Point};
+
+pub const VK_ALPHA_G1: G1Point = G1Point::new([
     ${g1ToBytes(alpha_g1[0]).join(', ')},
     ${g1ToBytes(alpha_g1[1]).join(', ')},
-];
+]);
 
-pub const VK_BETA_G2: [u8; 128] = [
-    ${g2ToBytes(beta_g2[0][0]).join(', ')},
+pub const VK_BETA_G2: G2Point = G2Point::new([
     ${g2ToBytes(beta_g2[0][1]).join(', ')},
-    ${g2ToBytes(beta_g2[1][0]).join(', ')},
+    ${g2ToBytes(beta_g2[0][0]).join(', ')},
     ${g2ToBytes(beta_g2[1][1]).join(', ')},
-];
+    ${g2ToBytes(beta_g2[1][0]).join(', ')},
+]);
 
-pub const VK_GAMMA_G2: [u8; 128] = [
-    ${g2ToBytes(gamma_g2[0][0]).join(', ')},
+pub const VK_GAMMA_G2: G2Point = G2Point::new([
     ${g2ToBytes(gamma_g2[0][1]).join(', ')},
-    ${g2ToBytes(gamma_g2[1][0]).join(', ')},
+    ${g2ToBytes(gamma_g2[0][0]).join(', ')},
     ${g2ToBytes(gamma_g2[1][1]).join(', ')},
-];
+    ${g2ToBytes(gamma_g2[1][0]).join(', ')},
+]);
 
-pub const VK_DELTA_G2: [u8; 128] = [
-    ${g2ToBytes(delta_g2[0][0]).join(', ')},
+pub const VK_DELTA_G2: G2Point = G2Point::new([
     ${g2ToBytes(delta_g2[0][1]).join(', ')},
-    ${g2ToBytes(delta_g2[1][0]).join(', ')},
+    ${g2ToBytes(delta_g2[0][0]).join(', ')},
     ${g2ToBytes(delta_g2[1][1]).join(', ')},
-];
+    ${g2ToBytes(delta_g2[1][0]).join(', ')},
+]);
 
-pub const VK_IC: [[u8; 64]; ${ic.length}] = [
-${ic.map(point => `    [
+pub const VK_IC: [G1Point; ${ic.length}] = [
+${ic.map(point => `    G1Point::new([
         ${g1ToBytes(point[0]).join(', ')},
         ${g1ToBytes(point[1]).join(', ')},
-    ]`).join(',\n')}
+    ])`).join(',\n')}
+];
+
+/// Signal layout hash (${signalNames.join(', ')})
+pub const VK_SIGNAL_LAYOUT_HASH: [u8; 32] = [
+    ${signalLayoutHash(signalNames).join(', ')},
 ];
 `;
 }
@@ -0,0 +1,78 @@
+//! Off-chain timing of the program's verification code
+//!
+//! Runs the single and batch pairing checks natively, with the
+//! alt_bn128 syscalls served by their host implementations, over the same
+//! fixture statements `bench-cu` measures on-chain, for batch sizes 1 to
+//! `MAX_BATCH_SIZE`. Wall-clock time here tracks changes to the curve
+//! arithmetic the program asks for; compute units come from `bench-cu`.
+//!
+//! ```bash
+//! cargo bench --bench verify
+//! ```
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use x402_zk_verifier::{
+    batch_verifier::{verify_batch_pairing, MAX_BATCH_SIZE},
+    endian::Be32,
+    inline_key::{process_verify_with_inline_key, InlineKeySource, InlineVerificationKey},
+    test_utils::{fixture_key, fixture_proof},
+    transcript::HashBackend,
+    Groth16Proof, PaymentPublicInputs,
+};
+
+/// `count` distinct payment statements with fixture proofs against `key`
+fn statements(
+    key: &InlineVerificationKey,
+    count: usize,
+) -> Vec<(Groth16Proof, PaymentPublicInputs)> {
+    (0..count)
+        .map(|i| {
+            let inputs = PaymentPublicInputs {
+                min_amount: 1_000_000 + i as u64,
+                recipient_pubkey: [4u8; 32],
+                max_block_age: 60,
+                current_time: 1_700_000_000,
+            };
+            let proof = fixture_proof(key, &inputs.to_scalars().map(Be32::from_u64));
+            (proof, inputs)
+        })
+        .collect()
+}
+
+fn bench_verify(c: &mut Criterion) {
+    let key = fixture_key(PaymentPublicInputs::SIGNAL_LAYOUT.len());
+    let source = InlineKeySource::Inline(key.clone());
+    let mut group = c.benchmark_group("verify");
+    for size in 1..=MAX_BATCH_SIZE {
+        let batch = statements(&key, size);
+        group.throughput(Throughput::Elements(size as u64));
+
+        let signals: Vec<Vec<Be32>> = batch
+            .iter()
+            .map(|(_, inputs)| inputs.to_scalars().map(Be32::from_u64).to_vec())
+            .collect();
+        group.bench_with_input(BenchmarkId::new("single", size), &batch, |b, batch| {
+            b.iter(|| {
+                for ((proof, _), inputs) in batch.iter().zip(&signals) {
+                    process_verify_with_inline_key(&[], &source, proof, inputs).unwrap();
+                }
+            })
+        });
+
+        let proofs: Vec<Groth16Proof> = batch.iter().map(|(proof, _)| proof.clone()).collect();
+        let inputs: Vec<PaymentPublicInputs> =
+            batch.iter().map(|(_, inputs)| inputs.clone()).collect();
+        let neg_alpha = key.alpha_g1.neg();
+        group.bench_with_input(BenchmarkId::new("batch", size), &size, |b, _| {
+            b.iter(|| {
+                let vk = key.as_key();
+                verify_batch_pairing(&vk, &neg_alpha, HashBackend::Keccak256, &proofs, &inputs)
+                    .unwrap();
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_verify);
+criterion_main!(benches);
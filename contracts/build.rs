@@ -0,0 +1,77 @@
+// Build provenance for `GetVersion`
+// Writes `$OUT_DIR/version.rs` with the git commit the program was built from
+// and the SHA-256 of the payment circuit's verification_key.json, so a
+// deployed binary can be matched to a circuit build. `X402_GIT_COMMIT`
+// overrides the commit for builds outside a git checkout, e.g. from a source
+// tarball in a pinned build container.
+//
+// Without the exported key the hash is all zeros and a warning is printed;
+// with the `require-real-vkey` feature the build fails instead.
+
+use std::{env, fs, path::Path, process::Command};
+
+use sha2::{Digest, Sha256};
+
+fn main() {
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let manifest_dir = Path::new(&manifest_dir);
+    let vkey_path = manifest_dir.join("../circuits/build/verification_key.json");
+    println!("cargo:rerun-if-changed={}", vkey_path.display());
+    println!("cargo:rerun-if-env-changed=X402_GIT_COMMIT");
+
+    let vkey_sha256: [u8; 32] = match fs::read(&vkey_path) {
+        Ok(json) => Sha256::digest(json).into(),
+        Err(e) if env::var_os("CARGO_FEATURE_REQUIRE_REAL_VKEY").is_some() => {
+            panic!("{}: {} (export the circuit key first)", vkey_path.display(), e)
+        }
+        Err(_) => {
+            println!(
+                "cargo:warning={} not found; GetVersion reports a zero key hash",
+                vkey_path.display()
+            );
+            [0u8; 32]
+        }
+    };
+
+    let git_commit = env::var("X402_GIT_COMMIT")
+        .ok()
+        .or_else(|| git(manifest_dir, &["rev-parse", "HEAD"]))
+        .unwrap_or_else(|| "unknown".to_string());
+    // Rebuild when HEAD moves, whether it is detached or on a branch
+    for file in ["HEAD", "packed-refs"] {
+        if let Some(path) = git(manifest_dir, &["rev-parse", "--git-path", file]) {
+            rerun_if_exists(&manifest_dir.join(path));
+        }
+    }
+    if let Some(branch) = git(manifest_dir, &["symbolic-ref", "-q", "HEAD"]) {
+        if let Some(path) = git(manifest_dir, &["rev-parse", "--git-path", &branch]) {
+            rerun_if_exists(&manifest_dir.join(path));
+        }
+    }
+
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("version.rs");
+    fs::write(
+        out,
+        format!(
+            "pub const GIT_COMMIT: &str = {git_commit:?};\n\
+             pub const VKEY_SHA256: [u8; 32] = {vkey_sha256:?};\n"
+        ),
+    )
+    .unwrap();
+}
+
+/// Trimmed stdout of a successful git command run in `dir`
+fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).current_dir(dir).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8(output.stdout).ok()?.trim().to_string())
+}
+
+/// Cargo reruns a build script on every build when a watched path is missing
+fn rerun_if_exists(path: &Path) {
+    if path.exists() {
+        println!("cargo:rerun-if-changed={}", path.display());
+    }
+}
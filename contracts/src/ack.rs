@@ -0,0 +1,253 @@
+// Recipient acknowledgement of payment terms
+// The recipient signs keccak(invoice_id || min_amount || valid_until) with the
+// ed25519 key in `recipient_pubkey`; the transaction carries that signature in
+// an ed25519 program instruction, which this program finds through the
+// instructions sysvar.
+
+use solana_program::{
+    account_info::AccountInfo,
+    ed25519_program,
+    entrypoint::ProgramResult,
+    keccak, msg,
+    pubkey::Pubkey,
+    sysvar::{
+        clock::Clock,
+        instructions::{load_current_index_checked, load_instruction_at_checked},
+        Sysvar,
+    },
+};
+
+use crate::{
+    events::{ProofVerified, VerifierEvent},
+    layout::VERIFY_PROOF_WITH_ACK,
+    state::{DeniedRecipient, PreparedVk, VerifierConfig},
+    verify_groth16, CircuitId, Groth16Proof, PaymentPublicInputsV3, VerifierError,
+    INVOICE_PAYMENT_VK,
+};
+
+/// Start of the signature offsets in ed25519 instruction data, after the
+/// signature count and a padding byte
+const SIGNATURE_OFFSETS_START: usize = 2;
+
+/// Serialized size of one `Ed25519SignatureOffsets` entry
+const SIGNATURE_OFFSETS_SIZE: usize = 14;
+
+/// Offset index meaning "the ed25519 instruction itself"
+const CURRENT_INSTRUCTION: u16 = u16::MAX;
+
+/// Message the recipient signs to accept a payment's terms
+pub fn recipient_ack_message(public_inputs: &PaymentPublicInputsV3) -> [u8; 32] {
+    keccak::hashv(&[
+        &public_inputs.invoice_id,
+        &public_inputs.min_amount.to_le_bytes(),
+        &public_inputs.valid_until.to_le_bytes(),
+    ])
+    .to_bytes()
+}
+
+/// Verify an invoice-bound proof whose terms the recipient signed
+///
+/// Runs the same statement checks as `VerifyAndRecord` without recording a
+/// receipt, and additionally requires an ed25519 instruction in the same
+/// transaction with the recipient's signature over `recipient_ack_message`.
+///
+/// Accounts expected:
+/// 0. `[]` Config PDA: ["config"]
+/// 1. `[]` Instructions sysvar
+/// 2. `[]` Denylist marker PDA: ["denied", recipient_pubkey]
+/// 3. `[]` Prepared key PDA: ["prepared-vk", 2]
+pub fn process_verify_proof_with_ack(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    proof: &Groth16Proof,
+    public_inputs: &PaymentPublicInputsV3,
+) -> ProgramResult {
+    let layout = VERIFY_PROOF_WITH_ACK;
+    layout.check(accounts)?;
+    let config_account = &accounts[layout.config];
+    let instructions_sysvar = &accounts[layout.instructions_sysvar];
+    let marker_account = &accounts[layout.marker];
+    let prepared_account = &accounts[layout.prepared_key];
+    PreparedVk::load_active(prepared_account, program_id, CircuitId::InvoicePayment)?;
+
+    check_recipient_ack(
+        instructions_sysvar,
+        &public_inputs.recipient_pubkey,
+        &recipient_ack_message(public_inputs),
+    )?;
+
+    INVOICE_PAYMENT_VK.check_signal_layout(PaymentPublicInputsV3::SIGNAL_LAYOUT)?;
+    let config = VerifierConfig::load(config_account, program_id)?;
+    let now = Clock::get()?.unix_timestamp;
+    public_inputs.validate(&config.input_policy, now)?;
+    public_inputs.check_expiry(now)?;
+    if !config.accepts_recipient_root(&public_inputs.recipient_set_root, now) {
+        return Err(VerifierError::RecipientRootMismatch.into());
+    }
+    DeniedRecipient::check(marker_account, program_id, &public_inputs.recipient_pubkey)?;
+
+    verify_groth16(&INVOICE_PAYMENT_VK, proof, &public_inputs.to_scalars())?;
+
+    VerifierEvent::ProofVerified(ProofVerified {
+        proof_hash: proof.hash(public_inputs),
+        recipient: public_inputs.recipient_pubkey,
+        min_amount: public_inputs.min_amount,
+    })
+    .emit();
+
+    msg!("✓ Recipient-acknowledged payment proof verified");
+    Ok(())
+}
+
+/// Require an ed25519 instruction in this transaction carrying `signer`'s
+/// signature over `message`
+///
+/// The ed25519 program has already checked the signature itself; this checks
+/// that it is the right key over the right message, read from that same
+/// instruction's data.
+pub fn check_recipient_ack(
+    instructions_sysvar: &AccountInfo,
+    signer: &[u8; 32],
+    message: &[u8; 32],
+) -> ProgramResult {
+    let current = load_current_index_checked(instructions_sysvar)?;
+
+    let mut found_ed25519 = false;
+    for index in 0..current {
+        let instruction = load_instruction_at_checked(index as usize, instructions_sysvar)?;
+        if instruction.program_id != ed25519_program::id() {
+            continue;
+        }
+        found_ed25519 = true;
+        if ed25519_data_signs(&instruction.data, index, signer, message) {
+            return Ok(());
+        }
+    }
+
+    if found_ed25519 {
+        msg!("No ed25519 signature by the recipient over the payment terms");
+        Err(VerifierError::RecipientAckMismatch.into())
+    } else {
+        Err(VerifierError::MissingRecipientAck.into())
+    }
+}
+
+/// Whether ed25519 instruction `data`, at transaction index `index`, holds a
+/// signature by `signer` over `message`
+///
+/// Offsets may only point into the ed25519 instruction's own data; otherwise a
+/// transaction could pair a verified signature with a public key or message
+/// planted in some other instruction.
+pub fn ed25519_data_signs(data: &[u8], index: u16, signer: &[u8; 32], message: &[u8; 32]) -> bool {
+    let count = match data.first() {
+        Some(count) => *count as usize,
+        None => return false,
+    };
+
+    (0..count).any(|i| {
+        let start = SIGNATURE_OFFSETS_START + i * SIGNATURE_OFFSETS_SIZE;
+        let offsets = match data.get(start..start + SIGNATURE_OFFSETS_SIZE) {
+            Some(offsets) => offsets,
+            None => return false,
+        };
+        let field = |k: usize| u16::from_le_bytes([offsets[2 * k], offsets[2 * k + 1]]);
+        let signature_offset = field(0);
+        let public_key_offset = field(2);
+        let message_offset = field(4);
+        let message_size = field(5);
+
+        // Instruction indices of the signature, public key and message
+        let own = |ix: u16| ix == CURRENT_INSTRUCTION || ix == index;
+        if ![field(1), field(3), field(6)].into_iter().all(own) {
+            return false;
+        }
+        if message_size as usize != message.len() {
+            return false;
+        }
+
+        let slice = |offset: u16, len: usize| data.get(offset as usize..offset as usize + len);
+        slice(signature_offset, 64).is_some()
+            && slice(public_key_offset, 32) == Some(&signer[..])
+            && slice(message_offset, 32) == Some(&message[..])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// ed25519 instruction data for one signature, laid out as the SDK does:
+    /// offsets, then public key, signature and message
+    fn ed25519_data(signer: &[u8; 32], message: &[u8; 32], instruction_index: u16) -> Vec<u8> {
+        let public_key_offset = 16u16;
+        let signature_offset = public_key_offset + 32;
+        let message_offset = signature_offset + 64;
+
+        let mut data = vec![1, 0];
+        for field in [
+            signature_offset,
+            instruction_index,
+            public_key_offset,
+            instruction_index,
+            message_offset,
+            32,
+            instruction_index,
+        ] {
+            data.extend_from_slice(&field.to_le_bytes());
+        }
+        data.extend_from_slice(signer);
+        data.extend_from_slice(&[7u8; 64]);
+        data.extend_from_slice(message);
+        data
+    }
+
+    #[test]
+    fn test_ack_message_binds_terms() {
+        let inputs = PaymentPublicInputsV3 {
+            min_amount: 1000000,
+            recipient_pubkey: [4u8; 32],
+            max_block_age: 60,
+            current_time: 1700000000,
+            invoice_id: [0xaa; 32],
+            valid_until: 1700000600,
+            recipient_set_root: [0x5e; 32],
+        };
+        let message = recipient_ack_message(&inputs);
+
+        let cheaper = PaymentPublicInputsV3 {
+            min_amount: 999999,
+            ..inputs
+        };
+        assert_ne!(recipient_ack_message(&cheaper), message);
+
+        // Fields outside the terms do not change what the recipient signs
+        let later = PaymentPublicInputsV3 {
+            current_time: 1700000001,
+            ..cheaper
+        };
+        assert_eq!(recipient_ack_message(&later), recipient_ack_message(&cheaper));
+    }
+
+    #[test]
+    fn test_ed25519_offsets_validated() {
+        let signer = [4u8; 32];
+        let message = [9u8; 32];
+
+        assert!(ed25519_data_signs(&ed25519_data(&signer, &message, u16::MAX), 0, &signer, &message));
+        // Its own index is as good as the "current instruction" marker
+        assert!(ed25519_data_signs(&ed25519_data(&signer, &message, 2), 2, &signer, &message));
+
+        // Offsets into another instruction are refused
+        assert!(!ed25519_data_signs(&ed25519_data(&signer, &message, 1), 2, &signer, &message));
+
+        // Wrong key or message
+        assert!(!ed25519_data_signs(&ed25519_data(&[5u8; 32], &message, u16::MAX), 0, &signer, &message));
+        assert!(!ed25519_data_signs(&ed25519_data(&signer, &[8u8; 32], u16::MAX), 0, &signer, &message));
+
+        // Truncated data and out-of-range offsets are refused, not panicked on
+        let data = ed25519_data(&signer, &message, u16::MAX);
+        assert!(!ed25519_data_signs(&data[..data.len() - 1], 0, &signer, &message));
+        assert!(!ed25519_data_signs(&data[..10], 0, &signer, &message));
+        assert!(!ed25519_data_signs(&[], 0, &signer, &message));
+    }
+}
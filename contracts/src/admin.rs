@@ -0,0 +1,1280 @@
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    log::sol_log_64,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar::{clock::Clock, Sysvar},
+};
+
+use crate::{
+    audit::{self, AUDIT_LOG_LEN},
+    events::{ConfigUpdated, VerifierEvent},
+    field,
+    layout::{
+        check_no_extra_accounts, next_system_program, optional_system_program, AdminConfigAccounts,
+        AdminPreparedKeyAccounts, PrepareVerificationKeyAccounts, SetAdminSignersAccounts,
+        ACCEPT_ADMIN, ADMIN_CONFIG, ADMIN_CONFIG_GROWING, ADMIN_PREPARED_KEY, ALLOW_RECIPIENT,
+        DENY_RECIPIENT, INITIALIZE_AUDIT_LOG, INITIALIZE_CONFIG, INITIALIZE_POOL,
+        PREPARE_VERIFICATION_KEY, RELAYER_LIST, REMOVE_RELAYER, SET_ADMIN_SIGNERS,
+    },
+    merkle::MerkleTree,
+    pda, relayers,
+    state::{
+        AdminSigners, DeniedRecipient, InputPolicy, PreparedVk, ShieldedPool, StateAccount,
+        VerifierConfig, AUDIT_LOG_SEED, CONFIG_SEED, DENIED_RECIPIENT_SEED,
+        MIN_SCRATCH_TTL_SLOTS, POOL_SEED, PREPARED_VK_SEED, RELAYER_LIST_SEED,
+    },
+    utils::{create_pda_account, grow_program_account},
+    CircuitId, G1Point, HashBackend, VerifierError,
+};
+
+/// Create the config PDA with the signer as admin
+///
+/// Accounts expected:
+/// 0. `[signer, writable]` Admin, funds the config rent
+/// 1. `[writable]` Config PDA: ["config"]
+/// 2. `[]` System program
+pub fn process_initialize_config(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    recipient_root_grace_seconds: i64,
+) -> ProgramResult {
+    let layout = INITIALIZE_CONFIG;
+    layout.check(accounts)?;
+    let admin = &accounts[layout.admin];
+    let config_account = &accounts[layout.config];
+    let system_program = &accounts[layout.system_program];
+
+    if recipient_root_grace_seconds < 0 {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let (config_address, bump) = pda::config_address(program_id);
+    if config_address != *config_account.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if !config_account.data_is_empty() {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+
+    create_pda_account(
+        admin,
+        config_account,
+        system_program,
+        program_id,
+        VerifierConfig::LEN,
+        &[CONFIG_SEED, &[bump]],
+    )?;
+
+    let config = VerifierConfig {
+        admin: *admin.key,
+        pending_admin: Pubkey::default(),
+        recipient_set_root: [0u8; 32],
+        previous_recipient_set_root: [0u8; 32],
+        previous_root_valid_until: 0,
+        recipient_root_grace_seconds,
+        epoch_length_seconds: 0,
+        input_policy: InputPolicy::default(),
+        max_receipt_age_slots: 0,
+        bump,
+        audit_enabled: false,
+        max_key_deprecation_slots: 0,
+        admin_signers: AdminSigners::default(),
+        allow_invoice_overpayment: false,
+        max_verifications_per_window: 0,
+        window_slots: 0,
+        finality_slots: 0,
+        scratch_ttl_slots: 0,
+        track_relayer: false,
+    };
+    config.pack(&mut config_account.data.borrow_mut()[..])?;
+
+    VerifierEvent::ConfigUpdated(ConfigUpdated {
+        config: *config_account.key,
+        admin: config.admin,
+    })
+    .emit();
+
+    msg!("Verifier config initialized");
+    Ok(())
+}
+
+/// Rotate the approved recipient set root
+///
+/// Accounts expected:
+/// 0. `[signer]` Admin
+/// 1. `[writable]` Config PDA
+pub fn process_set_recipient_root(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    root: [u8; 32],
+) -> ProgramResult {
+    let layout = ADMIN_CONFIG;
+    layout.check(accounts)?;
+    let admin = &accounts[layout.admin];
+    let config_account = &accounts[layout.config];
+
+    let mut config = VerifierConfig::load(config_account, program_id)?;
+    check_admin(&config, admin)?;
+
+    let clock = Clock::get()?;
+    config.rotate_recipient_root(root, clock.unix_timestamp);
+    config.store(&mut config_account.data.borrow_mut()[..])?;
+
+    VerifierEvent::ConfigUpdated(ConfigUpdated {
+        config: *config_account.key,
+        admin: config.admin,
+    })
+    .emit();
+
+    msg!("Recipient set root updated");
+    Ok(())
+}
+
+/// Set the subscription billing epoch length
+///
+/// Accounts expected:
+/// 0. `[signer]` Admin
+/// 1. `[writable]` Config PDA
+pub fn process_set_epoch_length(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    epoch_length_seconds: i64,
+) -> ProgramResult {
+    let layout = ADMIN_CONFIG;
+    layout.check(accounts)?;
+    let admin = &accounts[layout.admin];
+    let config_account = &accounts[layout.config];
+
+    let mut config = VerifierConfig::load(config_account, program_id)?;
+    check_admin(&config, admin)?;
+
+    if epoch_length_seconds < 0 {
+        return Err(ProgramError::InvalidArgument);
+    }
+    config.epoch_length_seconds = epoch_length_seconds;
+    config.store(&mut config_account.data.borrow_mut()[..])?;
+
+    VerifierEvent::ConfigUpdated(ConfigUpdated {
+        config: *config_account.key,
+        admin: config.admin,
+    })
+    .emit();
+
+    msg!("Subscription epoch length updated");
+    Ok(())
+}
+
+/// Replace the public input policy
+///
+/// Accounts expected:
+/// 0. `[signer]` Admin
+/// 1. `[writable]` Config PDA
+pub fn process_set_input_policy(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    policy: InputPolicy,
+) -> ProgramResult {
+    let layout = ADMIN_CONFIG;
+    layout.check(accounts)?;
+    let admin = &accounts[layout.admin];
+    let config_account = &accounts[layout.config];
+
+    let mut config = VerifierConfig::load(config_account, program_id)?;
+    check_admin(&config, admin)?;
+
+    config.input_policy = policy;
+    config.store(&mut config_account.data.borrow_mut()[..])?;
+
+    VerifierEvent::ConfigUpdated(ConfigUpdated {
+        config: *config_account.key,
+        admin: config.admin,
+    })
+    .emit();
+
+    msg!("Input policy updated");
+    Ok(())
+}
+
+/// Set how many slots receipts stay fresh for; 0 means they never go stale
+///
+/// Accounts expected:
+/// 0. `[signer]` Admin
+/// 1. `[writable]` Config PDA
+pub fn process_set_max_receipt_age(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    max_receipt_age_slots: u64,
+) -> ProgramResult {
+    let layout = ADMIN_CONFIG;
+    layout.check(accounts)?;
+    let admin = &accounts[layout.admin];
+    let config_account = &accounts[layout.config];
+
+    let mut config = VerifierConfig::load(config_account, program_id)?;
+    check_admin(&config, admin)?;
+
+    config.max_receipt_age_slots = max_receipt_age_slots;
+    config.store(&mut config_account.data.borrow_mut()[..])?;
+
+    VerifierEvent::ConfigUpdated(ConfigUpdated {
+        config: *config_account.key,
+        admin: config.admin,
+    })
+    .emit();
+
+    msg!("Max receipt age set (slots):");
+    sol_log_64(max_receipt_age_slots, 0, 0, 0, 0);
+    Ok(())
+}
+
+/// Start a two-step admin transfer
+///
+/// Nothing changes hands until the proposed key signs `AcceptAdmin`, so a
+/// mistyped key can be withdrawn with `CancelProposedAdmin` instead of
+/// locking the config.
+///
+/// Accounts expected:
+/// 0. `[signer]` Admin
+/// 1. `[writable]` Config PDA
+/// 2. `[signer]` Admin co-signers, as many as the config's threshold needs
+pub fn process_propose_admin(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    new_admin: Pubkey,
+) -> ProgramResult {
+    let layout = ADMIN_CONFIG;
+    layout.check_leading(accounts)?;
+    let admin = &accounts[layout.admin];
+    let config_account = &accounts[layout.config];
+
+    let mut config = VerifierConfig::load(config_account, program_id)?;
+    check_admin(&config, admin)?;
+    config.admin_signers.check(accounts)?;
+    check_co_signers(
+        &accounts[AdminConfigAccounts::LEN..],
+        &[&config.admin_signers],
+    )?;
+
+    if new_admin == Pubkey::default() {
+        return Err(ProgramError::InvalidArgument);
+    }
+    config.pending_admin = new_admin;
+    config.store(&mut config_account.data.borrow_mut()[..])?;
+
+    VerifierEvent::ConfigUpdated(ConfigUpdated {
+        config: *config_account.key,
+        admin: config.admin,
+    })
+    .emit();
+
+    msg!("Admin transfer proposed to:");
+    new_admin.log();
+    Ok(())
+}
+
+/// Complete a pending admin transfer
+///
+/// A PDA admin signs this through `invoke_signed` from its owning program.
+///
+/// Accounts expected:
+/// 0. `[signer]` Pending admin
+/// 1. `[writable]` Config PDA
+pub fn process_accept_admin(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let layout = ACCEPT_ADMIN;
+    layout.check(accounts)?;
+    let new_admin = &accounts[layout.pending_admin];
+    let config_account = &accounts[layout.config];
+
+    let mut config = VerifierConfig::load(config_account, program_id)?;
+    if config.pending_admin == Pubkey::default() {
+        return Err(VerifierError::NoPendingAdmin.into());
+    }
+    if config.pending_admin != *new_admin.key {
+        return Err(VerifierError::Unauthorized.into());
+    }
+
+    config.admin = config.pending_admin;
+    config.pending_admin = Pubkey::default();
+    config.store(&mut config_account.data.borrow_mut()[..])?;
+
+    VerifierEvent::ConfigUpdated(ConfigUpdated {
+        config: *config_account.key,
+        admin: config.admin,
+    })
+    .emit();
+
+    msg!("Admin transferred to:");
+    config.admin.log();
+    Ok(())
+}
+
+/// Withdraw a pending admin proposal
+///
+/// Accounts expected:
+/// 0. `[signer]` Admin
+/// 1. `[writable]` Config PDA
+pub fn process_cancel_proposed_admin(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let layout = ADMIN_CONFIG;
+    layout.check(accounts)?;
+    let admin = &accounts[layout.admin];
+    let config_account = &accounts[layout.config];
+
+    let mut config = VerifierConfig::load(config_account, program_id)?;
+    check_admin(&config, admin)?;
+
+    if config.pending_admin == Pubkey::default() {
+        return Err(VerifierError::NoPendingAdmin.into());
+    }
+    config.pending_admin = Pubkey::default();
+    config.store(&mut config_account.data.borrow_mut()[..])?;
+
+    VerifierEvent::ConfigUpdated(ConfigUpdated {
+        config: *config_account.key,
+        admin: config.admin,
+    })
+    .emit();
+
+    msg!("Proposed admin cancelled");
+    Ok(())
+}
+
+/// Validate a compiled verification key and store its derived values
+///
+/// Verification then only compares the stored key hash against the compiled
+/// key instead of re-deriving anything. Preparing again overwrites the
+/// account, which is how a prepared key is refreshed after a key upgrade; the
+/// deprecation window and any revocation are kept, and an older account is
+/// grown to hold them. A revoked key cannot be prepared again, so reinstating
+/// a revoked circuit takes a build with a new key.
+///
+/// A key whose beta, gamma or delta is the G2 identity, as a zeroed
+/// placeholder's are, is refused with `InvalidVerificationKey`.
+///
+/// The alt_bn128 syscalls cannot compute a GT element, so `alpha_beta` is only
+/// checked to be a well-formed, non-zero Fq12 encoding, not to equal e(alpha, beta).
+///
+/// Accounts expected:
+/// 0. `[signer, writable]` Admin, funds the prepared key rent
+/// 1. `[]` Config PDA
+/// 2. `[writable]` Prepared key PDA: ["prepared-vk", circuit id]
+/// 3. `[]` System program
+/// 4. `[signer]` Admin co-signers, as many as the config's threshold needs
+pub fn process_prepare_verification_key(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    circuit: CircuitId,
+    alpha_beta: Option<[u8; 384]>,
+) -> ProgramResult {
+    let layout = PREPARE_VERIFICATION_KEY;
+    layout.check_leading(accounts)?;
+    let admin = &accounts[layout.admin];
+    let config_account = &accounts[layout.config];
+    let prepared_account = &accounts[layout.prepared_key];
+    let system_program = &accounts[layout.system_program];
+
+    let config = VerifierConfig::load(config_account, program_id)?;
+    check_admin(&config, admin)?;
+    config.admin_signers.check(accounts)?;
+    check_co_signers(
+        &accounts[PrepareVerificationKeyAccounts::LEN..],
+        &[&config.admin_signers],
+    )?;
+
+    let vk = circuit.verification_key();
+    vk.check_g2_not_identity()?;
+    if !vk.is_canonical() {
+        return Err(VerifierError::NonCanonicalEncoding.into());
+    }
+    if vk.alpha_g1 == G1Point::IDENTITY || vk.ic.is_empty() {
+        msg!("Verification key is degenerate");
+        return Err(ProgramError::InvalidArgument);
+    }
+    if let Some(alpha_beta) = &alpha_beta {
+        if *alpha_beta == [0u8; 384] || !field::all_limbs_canonical(alpha_beta) {
+            msg!("e(alpha, beta) is not a valid GT encoding");
+            return Err(ProgramError::InvalidArgument);
+        }
+    }
+
+    let circuit_seed = [circuit as u8];
+    let (prepared_address, bump) = pda::vkey_address(program_id, circuit);
+    if prepared_address != *prepared_account.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    let vk_hash = vk.hash();
+    let (previous_vk_hash, deprecated_until_slot, revoked_vk_hash, hash_backend) =
+        if prepared_account.data_is_empty() {
+            create_pda_account(
+                admin,
+                prepared_account,
+                system_program,
+                program_id,
+                PreparedVk::LEN,
+                &[PREPARED_VK_SEED, &circuit_seed, &[bump]],
+            )?;
+            ([0u8; 32], 0, [0u8; 32], HashBackend::default())
+        } else {
+            let existing = PreparedVk::load_any_version(prepared_account, program_id, circuit)?;
+            if existing.is_revoked(&vk_hash) {
+                msg!("Verification key was revoked; prepare a new key instead");
+                return Err(VerifierError::KeyRevoked.into());
+            }
+            if prepared_account.data_len() < PreparedVk::LEN {
+                grow_program_account(admin, prepared_account, system_program, PreparedVk::LEN)?;
+            }
+            (
+                existing.previous_vk_hash,
+                existing.deprecated_until_slot,
+                existing.revoked_vk_hash,
+                existing.hash_backend,
+            )
+        };
+
+    let prepared = PreparedVk {
+        circuit,
+        vk_hash,
+        neg_alpha_g1: vk.alpha_g1.neg(),
+        alpha_beta: alpha_beta.unwrap_or([0u8; 384]),
+        bump,
+        previous_vk_hash,
+        deprecated_until_slot,
+        revoked_vk_hash,
+        hash_backend,
+    };
+    prepared.pack(&mut prepared_account.data.borrow_mut()[..])?;
+
+    msg!("Verification key prepared for circuit:");
+    sol_log_64(circuit as u64, 0, 0, 0, 0);
+    Ok(())
+}
+
+/// Create the empty audit log
+///
+/// Accounts expected:
+/// 0. `[signer, writable]` Admin, funds the audit log rent
+/// 1. `[]` Config PDA
+/// 2. `[writable]` Audit log PDA: ["audit-log"]
+/// 3. `[]` System program
+pub fn process_initialize_audit_log(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let layout = INITIALIZE_AUDIT_LOG;
+    layout.check(accounts)?;
+    let admin = &accounts[layout.admin];
+    let config_account = &accounts[layout.config];
+    let log_account = &accounts[layout.audit_log];
+    let system_program = &accounts[layout.system_program];
+
+    let config = VerifierConfig::load(config_account, program_id)?;
+    check_admin(&config, admin)?;
+
+    let (log_address, bump) = pda::audit_log_address(program_id);
+    if log_address != *log_account.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if !log_account.data_is_empty() {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+
+    create_pda_account(
+        admin,
+        log_account,
+        system_program,
+        program_id,
+        AUDIT_LOG_LEN,
+        &[AUDIT_LOG_SEED, &[bump]],
+    )?;
+    audit::initialize(&mut log_account.data.borrow_mut(), bump)?;
+
+    msg!("Audit log initialized");
+    Ok(())
+}
+
+/// Turn appending to the audit log on or off
+///
+/// Configs created before `audit_enabled` existed are one byte short; enabling
+/// auditing grows them, with the admin paying the extra rent.
+///
+/// Accounts expected:
+/// 0. `[signer, writable]` Admin
+/// 1. `[writable]` Config PDA
+/// 2. `[]` System program, only when enabling on a version 1 config
+pub fn process_set_audit_enabled(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    enabled: bool,
+) -> ProgramResult {
+    let layout = ADMIN_CONFIG_GROWING;
+    layout.check(accounts)?;
+    let admin = &accounts[layout.admin];
+    let config_account = &accounts[layout.config];
+    let system_program = optional_system_program(accounts, layout.system_program)?;
+
+    let mut config = VerifierConfig::load(config_account, program_id)?;
+    check_admin(&config, admin)?;
+
+    if enabled && config_account.data_len() < VerifierConfig::LEN_V2 {
+        let system_program = system_program.ok_or(ProgramError::NotEnoughAccountKeys)?;
+        grow_program_account(admin, config_account, system_program, VerifierConfig::LEN)?;
+    }
+    config.audit_enabled = enabled;
+    config.store(&mut config_account.data.borrow_mut()[..])?;
+
+    VerifierEvent::ConfigUpdated(ConfigUpdated {
+        config: *config_account.key,
+        admin: config.admin,
+    })
+    .emit();
+
+    msg!("Audit log enabled:");
+    sol_log_64(enabled as u64, 0, 0, 0, 0);
+    Ok(())
+}
+
+/// Create the shielded pool with an empty commitment tree keeping the last
+/// `root_history_size` roots
+///
+/// Accounts expected:
+/// 0. `[signer, writable]` Admin, funds the pool rent
+/// 1. `[]` Config PDA
+/// 2. `[writable]` Pool PDA: ["pool"]
+/// 3. `[]` System program
+pub fn process_initialize_pool(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    root_history_size: u32,
+) -> ProgramResult {
+    let layout = INITIALIZE_POOL;
+    layout.check(accounts)?;
+    let admin = &accounts[layout.admin];
+    let config_account = &accounts[layout.config];
+    let pool_account = &accounts[layout.pool];
+    let system_program = &accounts[layout.system_program];
+
+    let config = VerifierConfig::load(config_account, program_id)?;
+    check_admin(&config, admin)?;
+    let tree = MerkleTree::with_root_history(root_history_size)?;
+
+    let (pool_address, bump) = pda::pool_address(program_id);
+    if pool_address != *pool_account.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if !pool_account.data_is_empty() {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+
+    create_pda_account(
+        admin,
+        pool_account,
+        system_program,
+        program_id,
+        ShieldedPool::LEN,
+        &[POOL_SEED, &[bump]],
+    )?;
+    ShieldedPool { tree, bump }.pack(&mut pool_account.data.borrow_mut()[..])?;
+
+    msg!("Shielded pool initialized (root history size):");
+    sol_log_64(root_history_size as u64, 0, 0, 0, 0);
+    Ok(())
+}
+
+/// Set the longest deprecation window `DeprecatePreviousKey` may open
+///
+/// Configs created before the limit existed are grown to hold it, with the
+/// admin paying the extra rent.
+///
+/// Accounts expected:
+/// 0. `[signer, writable]` Admin
+/// 1. `[writable]` Config PDA
+/// 2. `[]` System program, only when growing a version 1 or 2 config
+pub fn process_set_max_key_deprecation(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    max_key_deprecation_slots: u64,
+) -> ProgramResult {
+    let layout = ADMIN_CONFIG_GROWING;
+    layout.check(accounts)?;
+    let admin = &accounts[layout.admin];
+    let config_account = &accounts[layout.config];
+    let system_program = optional_system_program(accounts, layout.system_program)?;
+
+    let mut config = VerifierConfig::load(config_account, program_id)?;
+    check_admin(&config, admin)?;
+
+    if max_key_deprecation_slots > 0 && config_account.data_len() < VerifierConfig::LEN {
+        let system_program = system_program.ok_or(ProgramError::NotEnoughAccountKeys)?;
+        grow_program_account(admin, config_account, system_program, VerifierConfig::LEN)?;
+    }
+    config.max_key_deprecation_slots = max_key_deprecation_slots;
+    config.store(&mut config_account.data.borrow_mut()[..])?;
+
+    VerifierEvent::ConfigUpdated(ConfigUpdated {
+        config: *config_account.key,
+        admin: config.admin,
+    })
+    .emit();
+
+    msg!("Max key deprecation window set (slots):");
+    sol_log_64(max_key_deprecation_slots, 0, 0, 0, 0);
+    Ok(())
+}
+
+/// Keep accepting proofs against a circuit's previous key until
+/// `deprecated_until_slot`
+///
+/// The previous key is the one compiled in by `vkey_previous.rs`; the window
+/// records its hash so it lapses if that key is replaced. The prepared key
+/// must already be refreshed for the current key, and the previous key must
+/// not be the revoked one.
+///
+/// Accounts expected:
+/// 0. `[signer, writable]` Admin
+/// 1. `[]` Config PDA
+/// 2. `[writable]` Prepared key PDA: ["prepared-vk", circuit id]
+/// 3. `[]` System program, only when growing a prepared key older than version 4
+/// 4. `[signer]` Admin co-signers, as many as the config's threshold needs
+pub fn process_deprecate_previous_key(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    circuit: CircuitId,
+    deprecated_until_slot: u64,
+) -> ProgramResult {
+    let layout = ADMIN_PREPARED_KEY;
+    layout.check_leading(accounts)?;
+    let admin = &accounts[layout.admin];
+    let config_account = &accounts[layout.config];
+    let prepared_account = &accounts[layout.prepared_key];
+    let account_info_iter = &mut accounts[AdminPreparedKeyAccounts::LEN..].iter();
+    let system_program = next_system_program(account_info_iter);
+
+    let config = VerifierConfig::load(config_account, program_id)?;
+    check_admin(&config, admin)?;
+    config.admin_signers.check(accounts)?;
+    check_co_signers(account_info_iter.as_slice(), &[&config.admin_signers])?;
+
+    let slot = Clock::get()?.slot;
+    if deprecated_until_slot > slot.saturating_add(config.max_key_deprecation_slots) {
+        return Err(VerifierError::DeprecationWindowTooLong.into());
+    }
+    let previous = circuit.previous_verification_key().ok_or_else(|| {
+        msg!("Circuit has no previous verification key");
+        ProgramError::InvalidArgument
+    })?;
+
+    let mut prepared = PreparedVk::load(prepared_account, program_id, circuit)?;
+    prepared.check_fresh(&circuit.verification_key().hash())?;
+    if prepared.is_revoked(&previous.hash()) {
+        return Err(VerifierError::KeyRevoked.into());
+    }
+    if prepared_account.data_len() < PreparedVk::LEN {
+        let system_program = system_program.ok_or(ProgramError::NotEnoughAccountKeys)?;
+        grow_program_account(admin, prepared_account, system_program, PreparedVk::LEN)?;
+    }
+    prepared.previous_vk_hash = previous.hash();
+    prepared.deprecated_until_slot = deprecated_until_slot;
+    prepared.pack(&mut prepared_account.data.borrow_mut()[..])?;
+
+    msg!("Previous key accepted until slot (circuit, slot):");
+    sol_log_64(circuit as u64, deprecated_until_slot, 0, 0, 0);
+    Ok(())
+}
+
+/// Stop a circuit's prepared key from verifying anything, effective at once
+///
+/// For a compromised trusted setup: unlike a key upgrade this needs no
+/// replacement key and no co-signers, only the admin. Any deprecation window
+/// is closed with it. Preparing a different key for the circuit, which does
+/// need the co-signers, reinstates it; the revoked key stays refused, as the
+/// current key and as the previous one.
+///
+/// Verify instructions only see the revocation when passed the prepared key
+/// account, so integrators relying on it must always pass that account.
+///
+/// Accounts expected:
+/// 0. `[signer, writable]` Admin
+/// 1. `[]` Config PDA
+/// 2. `[writable]` Prepared key PDA: ["prepared-vk", circuit id]
+/// 3. `[]` System program, only when growing a prepared key older than version 4
+pub fn process_revoke_verification_key(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    circuit: CircuitId,
+) -> ProgramResult {
+    let layout = ADMIN_PREPARED_KEY;
+    layout.check_leading(accounts)?;
+    let admin = &accounts[layout.admin];
+    let config_account = &accounts[layout.config];
+    let prepared_account = &accounts[layout.prepared_key];
+    let account_info_iter = &mut accounts[AdminPreparedKeyAccounts::LEN..].iter();
+    let system_program = next_system_program(account_info_iter);
+    check_no_extra_accounts(account_info_iter.as_slice())?;
+
+    let config = VerifierConfig::load(config_account, program_id)?;
+    check_admin(&config, admin)?;
+
+    let mut prepared = PreparedVk::load(prepared_account, program_id, circuit)?;
+    if prepared_account.data_len() < PreparedVk::LEN {
+        let system_program = system_program.ok_or(ProgramError::NotEnoughAccountKeys)?;
+        grow_program_account(admin, prepared_account, system_program, PreparedVk::LEN)?;
+    }
+    prepared.revoked_vk_hash = prepared.vk_hash;
+    prepared.deprecated_until_slot = 0;
+    prepared.pack(&mut prepared_account.data.borrow_mut()[..])?;
+
+    msg!("Verification key revoked for circuit:");
+    sol_log_64(circuit as u64, 0, 0, 0, 0);
+    Ok(())
+}
+
+/// Select the hash of a circuit's batch transcripts
+///
+/// Relayers read the backend from the prepared key to derive the batch
+/// coefficients the program checks.
+///
+/// Accounts expected:
+/// 0. `[signer, writable]` Admin
+/// 1. `[]` Config PDA
+/// 2. `[writable]` Prepared key PDA: ["prepared-vk", circuit id]
+/// 3. `[]` System program, only when growing a prepared key older than version 4
+/// 4. `[signer]` Admin co-signers, as many as the config's threshold needs
+pub fn process_set_hash_backend(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    circuit: CircuitId,
+    backend: HashBackend,
+) -> ProgramResult {
+    let layout = ADMIN_PREPARED_KEY;
+    layout.check_leading(accounts)?;
+    let admin = &accounts[layout.admin];
+    let config_account = &accounts[layout.config];
+    let prepared_account = &accounts[layout.prepared_key];
+    let account_info_iter = &mut accounts[AdminPreparedKeyAccounts::LEN..].iter();
+    let system_program = next_system_program(account_info_iter);
+
+    let config = VerifierConfig::load(config_account, program_id)?;
+    check_admin(&config, admin)?;
+    config.admin_signers.check(accounts)?;
+    check_co_signers(account_info_iter.as_slice(), &[&config.admin_signers])?;
+
+    let mut prepared = PreparedVk::load(prepared_account, program_id, circuit)?;
+    if prepared_account.data_len() < PreparedVk::LEN {
+        let system_program = system_program.ok_or(ProgramError::NotEnoughAccountKeys)?;
+        grow_program_account(admin, prepared_account, system_program, PreparedVk::LEN)?;
+    }
+    prepared.hash_backend = backend;
+    prepared.pack(&mut prepared_account.data.borrow_mut()[..])?;
+
+    msg!("Hash backend set (circuit, backend):");
+    sol_log_64(circuit as u64, backend as u64, 0, 0, 0);
+    Ok(())
+}
+
+/// Create the empty relayer allowlist
+///
+/// Accounts expected:
+/// 0. `[signer, writable]` Admin, funds the list rent
+/// 1. `[]` Config PDA
+/// 2. `[writable]` Relayer list PDA: ["relayers"]
+/// 3. `[]` System program
+pub fn process_initialize_relayer_list(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let layout = RELAYER_LIST;
+    layout.check(accounts)?;
+    let admin = &accounts[layout.admin];
+    let config_account = &accounts[layout.config];
+    let list_account = &accounts[layout.relayer_list];
+    let system_program = &accounts[layout.system_program];
+
+    let config = VerifierConfig::load(config_account, program_id)?;
+    check_admin(&config, admin)?;
+
+    let (list_address, bump) = pda::relayer_list_address(program_id);
+    if list_address != *list_account.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if !list_account.data_is_empty() {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+
+    create_pda_account(
+        admin,
+        list_account,
+        system_program,
+        program_id,
+        relayers::relayer_list_len(relayers::RELAYER_LIST_INITIAL_SLOTS),
+        &[RELAYER_LIST_SEED, &[bump]],
+    )?;
+    relayers::initialize(&mut list_account.data.borrow_mut(), bump)?;
+
+    msg!("Relayer list initialized");
+    Ok(())
+}
+
+/// Add `relayer` to the allowlist, growing a full list
+///
+/// The list grows by `RELAYER_LIST_GROWTH_SLOTS` zeroed slots at a time, with
+/// the admin paying the extra rent, and never past `MAX_RELAYERS`.
+///
+/// Accounts expected:
+/// 0. `[signer, writable]` Admin, funds growing the list
+/// 1. `[]` Config PDA
+/// 2. `[writable]` Relayer list PDA
+/// 3. `[]` System program
+pub fn process_add_relayer(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    relayer: &Pubkey,
+) -> ProgramResult {
+    let layout = RELAYER_LIST;
+    layout.check(accounts)?;
+    let admin = &accounts[layout.admin];
+    let config_account = &accounts[layout.config];
+    let list_account = &accounts[layout.relayer_list];
+    let system_program = &accounts[layout.system_program];
+
+    let config = VerifierConfig::load(config_account, program_id)?;
+    check_admin(&config, admin)?;
+    relayers::check_address(program_id, list_account)?;
+
+    if relayers::contains(&list_account.data.borrow(), relayer)? {
+        msg!("Relayer already listed");
+        return Err(ProgramError::InvalidArgument);
+    }
+    let len = relayers::len_for_one_more(&list_account.data.borrow())?;
+    if len > list_account.data_len() {
+        grow_program_account(admin, list_account, system_program, len)?;
+        msg!("Relayer list grown to (bytes):");
+        sol_log_64(len as u64, 0, 0, 0, 0);
+    }
+    relayers::add(&mut list_account.data.borrow_mut(), relayer)?;
+
+    msg!("Relayer added");
+    Ok(())
+}
+
+/// Take `relayer` off the allowlist
+///
+/// Accounts expected:
+/// 0. `[signer]` Admin
+/// 1. `[]` Config PDA
+/// 2. `[writable]` Relayer list PDA
+pub fn process_remove_relayer(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    relayer: &Pubkey,
+) -> ProgramResult {
+    let layout = REMOVE_RELAYER;
+    layout.check(accounts)?;
+    let admin = &accounts[layout.admin];
+    let config_account = &accounts[layout.config];
+    let list_account = &accounts[layout.relayer_list];
+
+    let config = VerifierConfig::load(config_account, program_id)?;
+    check_admin(&config, admin)?;
+    relayers::check_address(program_id, list_account)?;
+
+    if !relayers::remove(&mut list_account.data.borrow_mut(), relayer)? {
+        msg!("Relayer not listed");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    msg!("Relayer removed");
+    Ok(())
+}
+
+/// Replace the co-signers destructive admin instructions need
+///
+/// Both the current set's threshold and the new one's must be met, so a set
+/// nobody can sign for is never installed. A threshold of 0 with no signers
+/// hands those instructions back to the admin alone.
+///
+/// Accounts expected:
+/// 0. `[signer, writable]` Admin, funds growing an older config
+/// 1. `[writable]` Config PDA
+/// 2. `[]` System program
+/// 3. `[signer]` Co-signers from the current and new sets
+pub fn process_set_admin_signers(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    threshold: u8,
+    signers: &[Pubkey],
+) -> ProgramResult {
+    let layout = SET_ADMIN_SIGNERS;
+    layout.check_leading(accounts)?;
+    let admin = &accounts[layout.admin];
+    let config_account = &accounts[layout.config];
+    let system_program = &accounts[layout.system_program];
+
+    let mut config = VerifierConfig::load(config_account, program_id)?;
+    check_admin(&config, admin)?;
+    config.admin_signers.check(accounts)?;
+
+    let admin_signers = AdminSigners::new(threshold, signers)?;
+    admin_signers.check(accounts)?;
+    check_co_signers(
+        &accounts[SetAdminSignersAccounts::LEN..],
+        &[&config.admin_signers, &admin_signers],
+    )?;
+
+    if config_account.data_len() < VerifierConfig::LEN {
+        grow_program_account(admin, config_account, system_program, VerifierConfig::LEN)?;
+    }
+    config.admin_signers = admin_signers;
+    config.store(&mut config_account.data.borrow_mut()[..])?;
+
+    VerifierEvent::ConfigUpdated(ConfigUpdated {
+        config: *config_account.key,
+        admin: config.admin,
+    })
+    .emit();
+
+    msg!("Admin signers set (threshold, signers):");
+    sol_log_64(threshold as u64, signers.len() as u64, 0, 0, 0);
+    Ok(())
+}
+
+/// Let an invoice with an active receipt be paid again
+///
+/// Configs created before `allow_invoice_overpayment` existed are one byte
+/// short; allowing overpayment grows them, with the admin paying the extra
+/// rent.
+///
+/// Accounts expected:
+/// 0. `[signer, writable]` Admin
+/// 1. `[writable]` Config PDA
+/// 2. `[]` System program, only when allowing on a config older than version 5
+pub fn process_set_invoice_overpayment(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    allowed: bool,
+) -> ProgramResult {
+    let layout = ADMIN_CONFIG_GROWING;
+    layout.check(accounts)?;
+    let admin = &accounts[layout.admin];
+    let config_account = &accounts[layout.config];
+    let system_program = optional_system_program(accounts, layout.system_program)?;
+
+    let mut config = VerifierConfig::load(config_account, program_id)?;
+    check_admin(&config, admin)?;
+
+    if allowed && config_account.data_len() < VerifierConfig::LEN {
+        let system_program = system_program.ok_or(ProgramError::NotEnoughAccountKeys)?;
+        grow_program_account(admin, config_account, system_program, VerifierConfig::LEN)?;
+    }
+    config.allow_invoice_overpayment = allowed;
+    config.store(&mut config_account.data.borrow_mut()[..])?;
+
+    VerifierEvent::ConfigUpdated(ConfigUpdated {
+        config: *config_account.key,
+        admin: config.admin,
+    })
+    .emit();
+
+    msg!("Invoice overpayment allowed:");
+    sol_log_64(allowed as u64, 0, 0, 0, 0);
+    Ok(())
+}
+
+/// Limit the payments recorded per recipient and window
+///
+/// Configs created before rate limiting are 16 bytes short; setting a limit
+/// grows them, with the admin paying the extra rent. A limit needs a window
+/// of at least one slot.
+///
+/// Accounts expected:
+/// 0. `[signer, writable]` Admin
+/// 1. `[writable]` Config PDA
+/// 2. `[]` System program, only when setting a limit on a config older than
+///    version 6
+pub fn process_set_rate_limit(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    max_verifications_per_window: u64,
+    window_slots: u64,
+) -> ProgramResult {
+    let layout = ADMIN_CONFIG_GROWING;
+    layout.check(accounts)?;
+    let admin = &accounts[layout.admin];
+    let config_account = &accounts[layout.config];
+    let system_program = optional_system_program(accounts, layout.system_program)?;
+
+    let mut config = VerifierConfig::load(config_account, program_id)?;
+    check_admin(&config, admin)?;
+
+    if max_verifications_per_window > 0 && window_slots == 0 {
+        msg!("A rate limit needs a window of at least one slot");
+        return Err(ProgramError::InvalidArgument);
+    }
+    if max_verifications_per_window > 0 && config_account.data_len() < VerifierConfig::LEN {
+        let system_program = system_program.ok_or(ProgramError::NotEnoughAccountKeys)?;
+        grow_program_account(admin, config_account, system_program, VerifierConfig::LEN)?;
+    }
+    config.max_verifications_per_window = max_verifications_per_window;
+    config.window_slots = window_slots;
+    config.store(&mut config_account.data.borrow_mut()[..])?;
+
+    VerifierEvent::ConfigUpdated(ConfigUpdated {
+        config: *config_account.key,
+        admin: config.admin,
+    })
+    .emit();
+
+    msg!("Rate limit set (verifications, window slots):");
+    sol_log_64(max_verifications_per_window, window_slots, 0, 0, 0);
+    Ok(())
+}
+
+/// Set how many slots must pass after a receipt's slot before
+/// `FinalizeReceipt` accepts it; 0 restores `DEFAULT_FINALITY_SLOTS`
+///
+/// Configs created before receipt finality are 8 bytes short; setting a depth
+/// grows them, with the admin paying the extra rent.
+///
+/// Accounts expected:
+/// 0. `[signer, writable]` Admin
+/// 1. `[writable]` Config PDA
+/// 2. `[]` System program, only when setting a depth on a config older than
+///    version 7
+pub fn process_set_finality_slots(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    finality_slots: u64,
+) -> ProgramResult {
+    let layout = ADMIN_CONFIG_GROWING;
+    layout.check(accounts)?;
+    let admin = &accounts[layout.admin];
+    let config_account = &accounts[layout.config];
+    let system_program = optional_system_program(accounts, layout.system_program)?;
+
+    let mut config = VerifierConfig::load(config_account, program_id)?;
+    check_admin(&config, admin)?;
+
+    if finality_slots > 0 && config_account.data_len() < VerifierConfig::LEN_V7 {
+        let system_program = system_program.ok_or(ProgramError::NotEnoughAccountKeys)?;
+        grow_program_account(admin, config_account, system_program, VerifierConfig::LEN)?;
+    }
+    config.finality_slots = finality_slots;
+    config.store(&mut config_account.data.borrow_mut()[..])?;
+
+    VerifierEvent::ConfigUpdated(ConfigUpdated {
+        config: *config_account.key,
+        admin: config.admin,
+    })
+    .emit();
+
+    msg!("Finality depth set (slots):");
+    sol_log_64(finality_slots, 0, 0, 0, 0);
+    Ok(())
+}
+
+/// Set how many slots after its last write a batch buffer may be reaped by
+/// anyone; 0 restores `DEFAULT_SCRATCH_TTL_SLOTS`
+///
+/// A TTL below `MIN_SCRATCH_TTL_SLOTS` is refused, so reapers cannot be let
+/// at buffers still being uploaded. Configs created before the scratch TTL
+/// are 8 bytes short; setting one grows them, with the admin paying the extra
+/// rent.
+///
+/// Accounts expected:
+/// 0. `[signer, writable]` Admin
+/// 1. `[writable]` Config PDA
+/// 2. `[]` System program, only when setting a TTL on a config older than
+///    version 8
+pub fn process_set_scratch_ttl(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    scratch_ttl_slots: u64,
+) -> ProgramResult {
+    let layout = ADMIN_CONFIG_GROWING;
+    layout.check(accounts)?;
+    let admin = &accounts[layout.admin];
+    let config_account = &accounts[layout.config];
+    let system_program = optional_system_program(accounts, layout.system_program)?;
+
+    let mut config = VerifierConfig::load(config_account, program_id)?;
+    check_admin(&config, admin)?;
+
+    if scratch_ttl_slots != 0 && scratch_ttl_slots < MIN_SCRATCH_TTL_SLOTS {
+        msg!("Scratch TTL below the minimum");
+        return Err(ProgramError::InvalidArgument);
+    }
+    if scratch_ttl_slots > 0 && config_account.data_len() < VerifierConfig::LEN_V8 {
+        let system_program = system_program.ok_or(ProgramError::NotEnoughAccountKeys)?;
+        grow_program_account(admin, config_account, system_program, VerifierConfig::LEN_V8)?;
+    }
+    config.scratch_ttl_slots = scratch_ttl_slots;
+    config.store(&mut config_account.data.borrow_mut()[..])?;
+
+    VerifierEvent::ConfigUpdated(ConfigUpdated {
+        config: *config_account.key,
+        admin: config.admin,
+    })
+    .emit();
+
+    msg!("Scratch TTL set (slots):");
+    sol_log_64(scratch_ttl_slots, 0, 0, 0, 0);
+    Ok(())
+}
+
+/// Record the relayer and its declared compute unit price in each
+/// `VerifyAndRecord` receipt from now on
+///
+/// Configs created before `track_relayer` are one byte short; enabling it
+/// grows them, with the admin paying the extra rent.
+///
+/// Accounts expected:
+/// 0. `[signer, writable]` Admin
+/// 1. `[writable]` Config PDA
+/// 2. `[]` System program, only when enabling on a config older than version
+///    9
+pub fn process_set_track_relayer(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    track_relayer: bool,
+) -> ProgramResult {
+    let layout = ADMIN_CONFIG_GROWING;
+    layout.check(accounts)?;
+    let admin = &accounts[layout.admin];
+    let config_account = &accounts[layout.config];
+    let system_program = optional_system_program(accounts, layout.system_program)?;
+
+    let mut config = VerifierConfig::load(config_account, program_id)?;
+    check_admin(&config, admin)?;
+
+    if track_relayer && config_account.data_len() < VerifierConfig::LEN {
+        let system_program = system_program.ok_or(ProgramError::NotEnoughAccountKeys)?;
+        grow_program_account(admin, config_account, system_program, VerifierConfig::LEN)?;
+    }
+    config.track_relayer = track_relayer;
+    config.store(&mut config_account.data.borrow_mut()[..])?;
+
+    VerifierEvent::ConfigUpdated(ConfigUpdated {
+        config: *config_account.key,
+        admin: config.admin,
+    })
+    .emit();
+
+    msg!("Relayer tracking enabled:");
+    sol_log_64(track_relayer as u64, 0, 0, 0, 0);
+    Ok(())
+}
+
+/// Deny payments to `recipient` by creating its denylist marker
+///
+/// Accounts expected:
+/// 0. `[signer, writable]` Admin, funds the marker rent
+/// 1. `[]` Config PDA
+/// 2. `[writable]` Denylist marker PDA: ["denied", recipient]
+/// 3. `[]` System program
+pub fn process_deny_recipient(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    recipient: [u8; 32],
+) -> ProgramResult {
+    let layout = DENY_RECIPIENT;
+    layout.check(accounts)?;
+    let admin = &accounts[layout.admin];
+    let config_account = &accounts[layout.config];
+    let marker_account = &accounts[layout.marker];
+    let system_program = &accounts[layout.system_program];
+
+    let config = VerifierConfig::load(config_account, program_id)?;
+    check_admin(&config, admin)?;
+
+    let (marker_address, bump) = pda::denied_recipient_address(program_id, &recipient);
+    if marker_address != *marker_account.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if !marker_account.data_is_empty() {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+
+    create_pda_account(
+        admin,
+        marker_account,
+        system_program,
+        program_id,
+        DeniedRecipient::LEN,
+        &[DENIED_RECIPIENT_SEED, &recipient, &[bump]],
+    )?;
+    DeniedRecipient { recipient, bump }.pack(&mut marker_account.data.borrow_mut()[..])?;
+
+    msg!("Recipient denied");
+    Ok(())
+}
+
+/// Lift a denial by closing the recipient's marker, returning its rent to
+/// the admin
+///
+/// Accounts expected:
+/// 0. `[signer, writable]` Admin, receives the marker rent
+/// 1. `[]` Config PDA
+/// 2. `[writable]` Denylist marker PDA: ["denied", recipient]
+pub fn process_allow_recipient(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    recipient: [u8; 32],
+) -> ProgramResult {
+    let layout = ALLOW_RECIPIENT;
+    layout.check(accounts)?;
+    let admin = &accounts[layout.admin];
+    let config_account = &accounts[layout.config];
+    let marker_account = &accounts[layout.marker];
+
+    let config = VerifierConfig::load(config_account, program_id)?;
+    check_admin(&config, admin)?;
+
+    let (marker_address, _) = pda::denied_recipient_address(program_id, &recipient);
+    if marker_address != *marker_account.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if marker_account.owner != program_id {
+        msg!("Recipient not denied");
+        return Err(ProgramError::InvalidArgument);
+    }
+    DeniedRecipient::unpack(&marker_account.data.borrow())?;
+
+    // Emptied rather than only drained, so verifications later in the same
+    // transaction already read the recipient as allowed
+    let lamports = marker_account.lamports();
+    **admin.lamports.borrow_mut() += lamports;
+    **marker_account.lamports.borrow_mut() = 0;
+    marker_account.realloc(0, false)?;
+
+    msg!("Recipient allowed");
+    Ok(())
+}
+
+/// Require every account after an instruction's own to be a co-signer that
+/// signed and that one of `sets` lists
+fn check_co_signers(co_signers: &[AccountInfo], sets: &[&AdminSigners]) -> ProgramResult {
+    for account in co_signers {
+        let listed = sets.iter().any(|set| set.signers().contains(account.key));
+        if !account.is_signer || !listed {
+            msg!("Unexpected account among the co-signers:");
+            account.key.log();
+            return Err(VerifierError::UnexpectedAccount.into());
+        }
+    }
+    Ok(())
+}
+
+/// Require `admin` to be the config admin and to have signed
+fn check_admin(config: &VerifierConfig, admin: &AccountInfo) -> ProgramResult {
+    if !admin.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if config.admin != *admin.key {
+        return Err(VerifierError::Unauthorized.into());
+    }
+    Ok(())
+}
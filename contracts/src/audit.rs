@@ -0,0 +1,290 @@
+// Ring buffer of recent verifications
+// The audit log is a singleton PDA ["audit-log"] holding the last
+// `AUDIT_LOG_CAPACITY` verifications, so the trail can be read with one
+// account fetch instead of an indexer. Layout: the two-byte `StateAccount`
+// header, an `AuditLogHeader`, then fixed-width `AuditEntry` slots. An append
+// writes one slot at `head`, so it costs the same however full the log is.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+use crate::{
+    state::{VerifierConfig, ACCOUNT_HEADER_LEN, AUDIT_LOG_SEED},
+    VerifierError,
+};
+
+/// Entries kept before the oldest is overwritten
+pub const AUDIT_LOG_CAPACITY: usize = 64;
+
+/// First byte of the audit log, distinct from every `StateAccount` discriminator
+pub const AUDIT_LOG_DISCRIMINATOR: u8 = 7;
+
+/// Layout version of the audit log
+pub const AUDIT_LOG_VERSION: u8 = 1;
+
+/// One audited verification
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AuditEntry {
+    /// `Groth16Proof::hash` of the proof and its public inputs
+    pub proof_hash: [u8; 32],
+    pub recipient: [u8; 32],
+    pub min_amount: u64,
+    pub slot: u64,
+    /// The verification's error in `ProgramError`'s u64 encoding, 0 when it passed
+    pub outcome: u64,
+}
+
+impl AuditEntry {
+    pub const LEN: usize = 32 + 32 + 8 + 8 + 8;
+
+    pub fn passed(&self) -> bool {
+        self.outcome == 0
+    }
+}
+
+/// Ring buffer position, stored between the account header and the entries
+#[derive(BorshSerialize, BorshDeserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct AuditLogHeader {
+    /// Slot the next entry is written to
+    pub head: u32,
+    /// Entries ever appended; the log holds the last `AUDIT_LOG_CAPACITY` of them
+    pub total: u64,
+    pub bump: u8,
+}
+
+impl AuditLogHeader {
+    pub const LEN: usize = 4 + 8 + 1;
+}
+
+/// Offset of the first entry slot
+const ENTRIES_OFFSET: usize = ACCOUNT_HEADER_LEN + AuditLogHeader::LEN;
+
+/// Full audit log account size
+pub const AUDIT_LOG_LEN: usize = ENTRIES_OFFSET + AUDIT_LOG_CAPACITY * AuditEntry::LEN;
+
+/// Write an empty log into account data of exactly `AUDIT_LOG_LEN` bytes
+pub fn initialize(dst: &mut [u8], bump: u8) -> Result<(), VerifierError> {
+    if dst.len() != AUDIT_LOG_LEN {
+        return Err(VerifierError::InvalidAccountLength);
+    }
+    dst.fill(0);
+    dst[0] = AUDIT_LOG_DISCRIMINATOR;
+    dst[1] = AUDIT_LOG_VERSION;
+    write_header(
+        dst,
+        &AuditLogHeader {
+            head: 0,
+            total: 0,
+            bump,
+        },
+    )
+}
+
+/// Read the ring buffer position, checking the account header the way
+/// `StateAccount::unpack` does
+pub fn read_header(src: &[u8]) -> Result<AuditLogHeader, VerifierError> {
+    let header = match src {
+        [AUDIT_LOG_DISCRIMINATOR, AUDIT_LOG_VERSION, ..] if src.len() == AUDIT_LOG_LEN => {
+            AuditLogHeader::try_from_slice(&src[ACCOUNT_HEADER_LEN..ENTRIES_OFFSET])
+                .map_err(|_| VerifierError::MalformedAccountData)?
+        }
+        [AUDIT_LOG_DISCRIMINATOR, AUDIT_LOG_VERSION, ..] => {
+            return Err(VerifierError::InvalidAccountLength)
+        }
+        [AUDIT_LOG_DISCRIMINATOR, _, ..] => return Err(VerifierError::UnsupportedAccountVersion),
+        [_, _, ..] => return Err(VerifierError::WrongAccountDiscriminator),
+        _ => return Err(VerifierError::InvalidAccountLength),
+    };
+    if header.head as usize >= AUDIT_LOG_CAPACITY {
+        return Err(VerifierError::MalformedAccountData);
+    }
+    Ok(header)
+}
+
+/// Write `entry` at the head, overwriting the oldest entry once the log is full
+pub fn append(data: &mut [u8], entry: &AuditEntry) -> Result<(), VerifierError> {
+    let mut header = read_header(data)?;
+    let start = entry_offset(header.head as usize);
+    entry
+        .serialize(&mut &mut data[start..start + AuditEntry::LEN])
+        .map_err(|_| VerifierError::InvalidAccountLength)?;
+
+    header.head = ((header.head as usize + 1) % AUDIT_LOG_CAPACITY) as u32;
+    header.total = header.total.saturating_add(1);
+    write_header(data, &header)
+}
+
+/// Entries in the log, oldest first
+pub fn read_entries(data: &[u8]) -> Result<Vec<AuditEntry>, VerifierError> {
+    let header = read_header(data)?;
+    let len = header.total.min(AUDIT_LOG_CAPACITY as u64) as usize;
+    // Until the log wraps the oldest entry is in slot 0; after, it is the next
+    // one to be overwritten
+    let oldest = if len < AUDIT_LOG_CAPACITY {
+        0
+    } else {
+        header.head as usize
+    };
+
+    (0..len)
+        .map(|i| {
+            let start = entry_offset((oldest + i) % AUDIT_LOG_CAPACITY);
+            AuditEntry::try_from_slice(&data[start..start + AuditEntry::LEN])
+                .map_err(|_| VerifierError::MalformedAccountData)
+        })
+        .collect()
+}
+
+/// The audit log to append to, or `None` when no log was passed or the
+/// config has auditing off
+///
+/// A passed account that is not the program's audit log is rejected rather
+/// than skipped, auditing on or off, so a misconfigured client does not
+/// silently lose entries and no other account passes for the log.
+pub fn active_log<'b, 'a>(
+    program_id: &Pubkey,
+    config: &VerifierConfig,
+    log_account: Option<&'b AccountInfo<'a>>,
+) -> Result<Option<&'b AccountInfo<'a>>, ProgramError> {
+    let Some(log_account) = log_account else {
+        return Ok(None);
+    };
+    if log_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let header = read_header(&log_account.data.borrow())?;
+    let address = Pubkey::create_program_address(&[AUDIT_LOG_SEED, &[header.bump]], program_id)?;
+    if address != *log_account.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if !config.audit_enabled {
+        return Ok(None);
+    }
+    Ok(Some(log_account))
+}
+
+/// Append `entry` to a log returned by `active_log`
+pub fn record(log_account: &AccountInfo, entry: &AuditEntry) -> ProgramResult {
+    append(&mut log_account.data.borrow_mut(), entry)?;
+    Ok(())
+}
+
+/// `AuditEntry::outcome` of a verification's result
+pub fn outcome(result: &ProgramResult) -> u64 {
+    match result {
+        Ok(()) => 0,
+        Err(e) => u64::from(e.clone()),
+    }
+}
+
+fn entry_offset(slot: usize) -> usize {
+    ENTRIES_OFFSET + slot * AuditEntry::LEN
+}
+
+fn write_header(dst: &mut [u8], header: &AuditLogHeader) -> Result<(), VerifierError> {
+    header
+        .serialize(&mut &mut dst[ACCOUNT_HEADER_LEN..ENTRIES_OFFSET])
+        .map_err(|_| VerifierError::InvalidAccountLength)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log() -> Vec<u8> {
+        let mut data = vec![0u8; AUDIT_LOG_LEN];
+        initialize(&mut data, 254).unwrap();
+        data
+    }
+
+    fn entry(n: u64) -> AuditEntry {
+        AuditEntry {
+            proof_hash: [n as u8; 32],
+            recipient: [4u8; 32],
+            min_amount: 1000000 + n,
+            slot: 100 + n,
+            outcome: n % 3,
+        }
+    }
+
+    #[test]
+    fn test_entries_in_order_before_wrap() {
+        let mut data = log();
+        assert_eq!(read_entries(&data), Ok(vec![]));
+
+        for n in 0..5 {
+            append(&mut data, &entry(n)).unwrap();
+        }
+        assert_eq!(read_entries(&data), Ok((0..5).map(entry).collect()));
+
+        let header = read_header(&data).unwrap();
+        assert_eq!((header.head, header.total, header.bump), (5, 5, 254));
+        assert_eq!(AuditEntry::LEN, entry(0).try_to_vec().unwrap().len());
+        assert_eq!(AUDIT_LOG_LEN, 2 + 13 + 64 * 88);
+    }
+
+    #[test]
+    fn test_wrap_around_keeps_latest() {
+        let mut data = log();
+        let capacity = AUDIT_LOG_CAPACITY as u64;
+
+        // Exactly full: nothing overwritten yet, head back at slot 0
+        for n in 0..capacity {
+            append(&mut data, &entry(n)).unwrap();
+        }
+        assert_eq!(read_header(&data).unwrap().head, 0);
+        assert_eq!(read_entries(&data), Ok((0..capacity).map(entry).collect()));
+
+        // Seven more overwrite the seven oldest
+        for n in capacity..capacity + 7 {
+            append(&mut data, &entry(n)).unwrap();
+        }
+        let header = read_header(&data).unwrap();
+        assert_eq!((header.head, header.total), (7, capacity + 7));
+        assert_eq!(read_entries(&data), Ok((7..capacity + 7).map(entry).collect()));
+
+        // Several times round
+        for n in capacity + 7..5 * capacity + 1 {
+            append(&mut data, &entry(n)).unwrap();
+        }
+        let entries = read_entries(&data).unwrap();
+        assert_eq!(entries.len(), AUDIT_LOG_CAPACITY);
+        assert_eq!(entries[0], entry(4 * capacity + 1));
+        assert_eq!(entries[AUDIT_LOG_CAPACITY - 1], entry(5 * capacity));
+    }
+
+    #[test]
+    fn test_header_rejections() {
+        let data = log();
+
+        let mut receipt = data.clone();
+        receipt[0] = 2;
+        assert_eq!(read_entries(&receipt), Err(VerifierError::WrongAccountDiscriminator));
+
+        let mut newer = data.clone();
+        newer[1] = 2;
+        assert_eq!(read_entries(&newer), Err(VerifierError::UnsupportedAccountVersion));
+
+        assert_eq!(
+            read_entries(&data[..data.len() - 1]),
+            Err(VerifierError::InvalidAccountLength)
+        );
+        assert_eq!(read_entries(&[7]), Err(VerifierError::InvalidAccountLength));
+
+        // A head outside the buffer would index past the entries
+        let mut bad_head = data.clone();
+        bad_head[2..6].copy_from_slice(&(AUDIT_LOG_CAPACITY as u32).to_le_bytes());
+        assert_eq!(
+            append(&mut bad_head, &entry(0)),
+            Err(VerifierError::MalformedAccountData)
+        );
+
+        assert_eq!(
+            initialize(&mut [0u8; 16], 255),
+            Err(VerifierError::InvalidAccountLength)
+        );
+    }
+}
@@ -0,0 +1,290 @@
+// Batch receipts
+// `VerifyBatchWithReceipt` records one `BatchReceipt` for a whole verified
+// batch instead of a receipt per proof. The receipt holds a keccak Merkle root
+// over the batch's proof hashes in batch order: leaves are keccak(0x00 ||
+// proof_hash) and nodes keccak(0x01 || left || right), so a leaf never passes
+// for a node. A level of odd width carries its last node up unchanged, so a
+// path has one sibling per level where the node has one, and the leaf count
+// fixes which levels those are.
+
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    keccak, msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar::{clock::Clock, Sysvar},
+};
+
+use crate::{
+    batch_verifier::{batch_transcript_hash, batch_verify_proofs, BatchVerificationRequest},
+    events::{BatchReceiptCreated, VerifierEvent},
+    layout::{VERIFY_BATCH_INCLUSION, VERIFY_BATCH_WITH_RECEIPT},
+    pda,
+    state::{BatchReceipt, StateAccount, BATCH_RECEIPT_SEED},
+    utils::create_pda_account,
+    VerifierError,
+};
+
+const LEAF_PREFIX: u8 = 0;
+const NODE_PREFIX: u8 = 1;
+
+/// Leaf of the receipt tree for the proof with `proof_hash`
+pub fn leaf_hash(proof_hash: &[u8; 32]) -> [u8; 32] {
+    keccak::hashv(&[&[LEAF_PREFIX], proof_hash]).to_bytes()
+}
+
+fn node_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    keccak::hashv(&[&[NODE_PREFIX], left, right]).to_bytes()
+}
+
+/// Merkle root over `proof_hashes`, in order; zero for an empty batch
+pub fn receipt_root(proof_hashes: &[[u8; 32]]) -> [u8; 32] {
+    let mut level: Vec<[u8; 32]> = proof_hashes.iter().map(leaf_hash).collect();
+    if level.is_empty() {
+        return [0u8; 32];
+    }
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => node_hash(left, right),
+                [last] => *last,
+                _ => unreachable!(),
+            })
+            .collect();
+    }
+    level[0]
+}
+
+/// Siblings from the leaf up proving that `proof_hashes[index]` is under
+/// `receipt_root(proof_hashes)`; `None` when `index` is out of range
+pub fn inclusion_proof(proof_hashes: &[[u8; 32]], index: usize) -> Option<Vec<[u8; 32]>> {
+    if index >= proof_hashes.len() {
+        return None;
+    }
+    let mut level: Vec<[u8; 32]> = proof_hashes.iter().map(leaf_hash).collect();
+    let mut index = index;
+    let mut siblings = Vec::new();
+    while level.len() > 1 {
+        if let Some(sibling) = level.get(index ^ 1) {
+            siblings.push(*sibling);
+        }
+        level = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => node_hash(left, right),
+                [last] => *last,
+                _ => unreachable!(),
+            })
+            .collect();
+        index /= 2;
+    }
+    Some(siblings)
+}
+
+/// Whether `siblings` leads from `proof_hash` at `index` to `root` in a tree
+/// of `count` leaves
+///
+/// Every sibling must be used, so a path for a different tree shape fails.
+pub fn verify_inclusion(
+    root: &[u8; 32],
+    count: usize,
+    proof_hash: &[u8; 32],
+    index: usize,
+    siblings: &[[u8; 32]],
+) -> bool {
+    if index >= count {
+        return false;
+    }
+    let mut node = leaf_hash(proof_hash);
+    let mut siblings = siblings.iter();
+    let (mut index, mut width) = (index, count);
+    while width > 1 {
+        // The last node of an odd-width level has no sibling
+        if (index ^ 1) < width {
+            let Some(sibling) = siblings.next() else {
+                return false;
+            };
+            node = if index % 2 == 0 {
+                node_hash(&node, sibling)
+            } else {
+                node_hash(sibling, &node)
+            };
+        }
+        index /= 2;
+        width = width.div_ceil(2);
+    }
+    siblings.next().is_none() && node == *root
+}
+
+/// Verify a batch, then record one `BatchReceipt` for it
+///
+/// The receipt's address is derived from `batch_transcript_hash` of the
+/// statements, so a batch is recorded at most once whatever proofs carry it.
+/// Duplicates must be rejected: a receipt counts each statement it covers.
+///
+/// Accounts expected:
+/// 0. `[signer, writable]` Relayer funding the receipt rent
+/// 1. `[writable]` Batch receipt PDA: ["batch-receipt", transcript_hash]
+/// 2. `[]` System program
+/// 3. `[]` Prepared key PDA: ["prepared-vk", circuit_id], as for `VerifyBatch`
+/// 4.. `[]` Denylist marker PDA of each proof's recipient, in batch order
+pub fn process_verify_batch_with_receipt(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    request: &BatchVerificationRequest,
+) -> ProgramResult {
+    let layout = VERIFY_BATCH_WITH_RECEIPT;
+    layout.check_leading(accounts)?;
+    let relayer = &accounts[layout.relayer];
+    let receipt_account = &accounts[layout.receipt];
+    let system_program = &accounts[layout.system_program];
+    if request.allow_duplicates {
+        msg!("A batch receipt requires distinct proofs");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let transcript_hash = batch_transcript_hash(&request.public_inputs);
+    let (address, bump) = pda::batch_receipt_address(program_id, &transcript_hash);
+    if address != *receipt_account.key {
+        msg!("Batch receipt account does not match the batch");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if !receipt_account.data_is_empty() {
+        return Err(VerifierError::ReceiptAlreadyExists.into());
+    }
+
+    // `batch_verify_proofs` reads the prepared key and the markers after it
+    batch_verify_proofs(program_id, &accounts[layout.prepared_key..], request)?;
+
+    let proof_hashes: Vec<[u8; 32]> = request
+        .proofs
+        .iter()
+        .zip(&request.public_inputs)
+        .map(|(proof, inputs)| proof.hash(inputs))
+        .collect();
+    let receipt = BatchReceipt {
+        transcript_hash,
+        count: proof_hashes.len() as u8,
+        receipt_root: receipt_root(&proof_hashes),
+        slot: Clock::get()?.slot,
+        relayer: *relayer.key,
+        bump,
+    };
+
+    create_pda_account(
+        relayer,
+        receipt_account,
+        system_program,
+        program_id,
+        BatchReceipt::LEN,
+        &[BATCH_RECEIPT_SEED, &transcript_hash, &[bump]],
+    )?;
+    receipt.pack(&mut receipt_account.data.borrow_mut()[..])?;
+
+    VerifierEvent::BatchReceiptCreated(BatchReceiptCreated {
+        receipt: *receipt_account.key,
+        transcript_hash,
+        receipt_root: receipt.receipt_root,
+        num_proofs: receipt.count as u32,
+    })
+    .emit();
+
+    msg!("✓ Batch receipt recorded");
+    Ok(())
+}
+
+/// Succeed only if the proof with `proof_hash` is member `index` of the batch
+/// a `BatchReceipt` records
+///
+/// Read-only, so a program can gate on a batched payment through CPI.
+///
+/// Accounts expected:
+/// 0. `[]` Batch receipt PDA: ["batch-receipt", transcript_hash]
+pub fn process_verify_batch_inclusion(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    proof_hash: &[u8; 32],
+    index: u8,
+    siblings: &[[u8; 32]],
+) -> ProgramResult {
+    let layout = VERIFY_BATCH_INCLUSION;
+    layout.check(accounts)?;
+    let receipt_account = &accounts[layout.receipt];
+
+    let receipt = BatchReceipt::load(receipt_account, program_id)?;
+    if !verify_inclusion(
+        &receipt.receipt_root,
+        receipt.count as usize,
+        proof_hash,
+        index as usize,
+        siblings,
+    ) {
+        return Err(VerifierError::ProofNotInBatch.into());
+    }
+
+    msg!("✓ Proof included in batch");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hashes(count: u8) -> Vec<[u8; 32]> {
+        (1..=count).map(|i| [i; 32]).collect()
+    }
+
+    #[test]
+    fn test_receipt_root_shape() {
+        let h = hashes(3);
+        assert_eq!(receipt_root(&h[..1]), leaf_hash(&h[0]));
+        let pair = node_hash(&leaf_hash(&h[0]), &leaf_hash(&h[1]));
+        assert_eq!(receipt_root(&h[..2]), pair);
+        // The odd leaf is carried up, not hashed with itself
+        assert_eq!(receipt_root(&h), node_hash(&pair, &leaf_hash(&h[2])));
+        assert_eq!(receipt_root(&[]), [0u8; 32]);
+    }
+
+    #[test]
+    fn test_every_member_included() {
+        for count in 1..=16u8 {
+            let h = hashes(count);
+            let root = receipt_root(&h);
+            for (index, hash) in h.iter().enumerate() {
+                let siblings = inclusion_proof(&h, index).unwrap();
+                assert!(verify_inclusion(&root, h.len(), hash, index, &siblings));
+            }
+            assert_eq!(inclusion_proof(&h, h.len()), None);
+        }
+    }
+
+    #[test]
+    fn test_non_members_rejected() {
+        let h = hashes(5);
+        let root = receipt_root(&h);
+        let siblings = inclusion_proof(&h, 2).unwrap();
+        assert!(verify_inclusion(&root, 5, &h[2], 2, &siblings));
+
+        // A hash outside the batch
+        assert!(!verify_inclusion(&root, 5, &[9u8; 32], 2, &siblings));
+        // A member at the wrong position
+        assert!(!verify_inclusion(&root, 5, &h[2], 3, &siblings));
+        assert!(!verify_inclusion(&root, 5, &h[2], 5, &siblings));
+        // A path with a sibling missing or extra
+        assert!(!verify_inclusion(&root, 5, &h[2], 2, &siblings[1..]));
+        let mut extra = siblings.clone();
+        extra.push([0u8; 32]);
+        assert!(!verify_inclusion(&root, 5, &h[2], 2, &extra));
+        // The same path against another tree shape
+        assert!(!verify_inclusion(&root, 4, &h[2], 2, &siblings));
+
+        // An inner node does not pass for a leaf of a smaller tree
+        let root = receipt_root(&h[..4]);
+        let left = node_hash(&leaf_hash(&h[0]), &leaf_hash(&h[1]));
+        let right = node_hash(&leaf_hash(&h[2]), &leaf_hash(&h[3]));
+        assert_eq!(node_hash(&left, &right), root);
+        assert!(!verify_inclusion(&root, 2, &left, 0, &[right]));
+    }
+}
@@ -1,27 +1,165 @@
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
-    account_info::AccountInfo,
-    alt_bn128::{alt_bn128_addition, alt_bn128_pairing, ALT_BN128_PAIRING_OUTPUT_LEN},
+    account_info::{next_account_info, AccountInfo},
     entrypoint::ProgramResult,
+    log::sol_log_64,
     msg,
+    program::set_return_data,
     program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar::{clock::Clock, Sysvar},
 };
 
-use crate::{Groth16Proof, PaymentPublicInputs};
+use crate::{
+    add_input_term,
+    audit::{self, AuditEntry},
+    events::{BatchVerified, VerifierEvent},
+    field::Scalar,
+    layout::{
+        check_no_extra_accounts, VerifyBatchAccounts, VerifyBatchSoftAccounts, ABORT_BUFFER,
+        REAP_STALE, VERIFY_BATCH, VERIFY_BATCH_BUFFERED, VERIFY_BATCH_SOFT, WRITE_BATCH_BUFFER,
+    },
+    pairing::PairingVec,
+    pda,
+    receipt::verify_and_record,
+    state::{
+        BatchBuffer, DeniedRecipient, PreparedVk, StateAccount, VerifierConfig, BATCH_BUFFER_SEED,
+    },
+    transcript::{HashBackend, Transcript},
+    utils::{create_pda_account, decode_exact},
+    CircuitId, G1Point, G2Point, Groth16Proof, PaymentPublicInputs, PaymentPublicInputsV3,
+    SerializedLen, Statement, ValidatedKeys, VerificationKey, VerifierError, INVOICE_PAYMENT_VK,
+};
+
+/// Most proofs accepted by the aggregated batch path
+pub const MAX_BATCH_SIZE: usize = 16;
+
+/// Most proofs accepted by `VerifyBatchSoft`
+///
+/// Every proof pays for its own 4-pair pairing and receipt, so this is bounded
+/// by the transaction compute budget rather than by the aggregation.
+pub const MAX_SOFT_BATCH_SIZE: usize = 4;
+
+/// Largest Borsh-encoded `BatchVerificationRequest` a batch buffer holds
+pub const MAX_BATCH_BUFFER_LEN: usize = BatchVerificationRequest::MAX_LEN;
+
+/// Positions of the recipient limbs in `PaymentPublicInputs::to_scalars`
+const RECIPIENT_SCALARS: [usize; 2] = [1, 2];
 
 /// Batch verification of multiple Groth16 proofs
 /// More efficient than verifying individually
-#[derive(BorshSerialize, BorshDeserialize, Debug)]
+///
+/// The proofs must be in canonical order; see `canonicalize`.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
 pub struct BatchVerificationRequest {
     pub proofs: Vec<Groth16Proof>,
     pub public_inputs: Vec<PaymentPublicInputs>,
+    /// Skip the duplicate check; only for pure verification where nothing is
+    /// counted per proof
+    pub allow_duplicates: bool,
+    /// Circuit every proof in the batch belongs to; its key must take the
+    /// layout of `public_inputs`
+    pub circuit_id: CircuitId,
+}
+
+impl BatchVerificationRequest {
+    /// Encoded length of a request of `num_proofs` proofs: two u32 vector
+    /// lengths, the duplicates flag and the circuit id, plus each proof and
+    /// its inputs
+    pub const fn len_for(num_proofs: usize) -> usize {
+        4 + 4 + 1 + 1 + num_proofs * (Groth16Proof::MAX_LEN + PaymentPublicInputs::MAX_LEN)
+    }
+
+    /// Sort the batch into canonical order, by `Groth16Proof::hash` ascending
+    ///
+    /// `batch_verify_proofs` takes a batch in this order only, so a set of
+    /// proofs has one encoding, one set of coefficients and one batch receipt
+    /// address, whichever relayer submits it. The order is by the keccak256
+    /// proof hash even for a circuit whose prepared key selects another
+    /// backend; the backend applies to the transcripts only. A request whose
+    /// proof and input counts differ is left as it is, for the program to
+    /// refuse.
+    pub fn canonicalize(&mut self) {
+        if self.proofs.len() != self.public_inputs.len() {
+            return;
+        }
+        let mut entries: Vec<([u8; 32], Groth16Proof, PaymentPublicInputs)> = self
+            .proofs
+            .drain(..)
+            .zip(self.public_inputs.drain(..))
+            .map(|(proof, inputs)| (proof.hash(&inputs), proof, inputs))
+            .collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        for (_, proof, inputs) in entries {
+            self.proofs.push(proof);
+            self.public_inputs.push(inputs);
+        }
+    }
+}
+
+impl SerializedLen for BatchVerificationRequest {
+    const MAX_LEN: usize = Self::len_for(MAX_BATCH_SIZE);
+
+    fn serialized_len(&self) -> usize {
+        Self::len_for(0)
+            + self.proofs.len() * Groth16Proof::MAX_LEN
+            + self.public_inputs.len() * PaymentPublicInputs::MAX_LEN
+    }
+}
+
+/// Details of a `PublicInputCountMismatch`, set as Borsh return data
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PublicInputCountMismatch {
+    /// Scalars the batch circuit's key takes, one per IC point after IC[0]
+    pub expected: u32,
+    /// Scalars the offending statement expands to
+    pub got: u32,
+    /// Batch position of the first statement that does not fit
+    pub index: u32,
+}
+
+impl PublicInputCountMismatch {
+    /// The first of `counts`, each statement's scalar count in batch order,
+    /// that a key of `ic_len` IC points does not take
+    pub fn find(ic_len: usize, counts: impl IntoIterator<Item = usize>) -> Option<Self> {
+        let expected = ic_len.saturating_sub(1);
+        counts
+            .into_iter()
+            .enumerate()
+            .find(|(_, got)| *got != expected)
+            .map(|(index, got)| PublicInputCountMismatch {
+                expected: expected as u32,
+                got: got as u32,
+                index: index as u32,
+            })
+    }
+}
+
+/// Key of the circuit a batch declares, checked against the signal layout of
+/// the statements the batch carries
+///
+/// A batch has one circuit, so its key's IC length and layout hash are
+/// checked once, before any proof is touched; statements of another input
+/// version fail with `PublicInputLayoutMismatch` instead of being aggregated
+/// against incompatible IC points.
+pub fn batch_verification_key(
+    circuit: CircuitId,
+    layout: &[&str],
+) -> Result<&'static VerificationKey<'static>, VerifierError> {
+    let vk = circuit.verification_key();
+    vk.check_signal_layout(layout)?;
+    Ok(vk)
 }
 
 /// Verify multiple proofs in a single batch
 /// Uses aggregated pairing to reduce compute cost
+///
+/// `accounts` holds the batch circuit's prepared key, then the denylist
+/// marker of each proof's recipient in batch order; a revoked key fails the
+/// batch with `KeyRevoked`, and its hash backend derives the coefficients.
 pub fn batch_verify_proofs(
-    _program_id: &solana_program::pubkey::Pubkey,
-    _accounts: &[AccountInfo],
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
     request: &BatchVerificationRequest,
 ) -> ProgramResult {
     if request.proofs.len() != request.public_inputs.len() {
@@ -34,65 +172,101 @@ pub fn batch_verify_proofs(
         return Err(ProgramError::InvalidArgument);
     }
 
-    let num_proofs = request.proofs.len();
-    msg!("Batch verifying {} proofs", num_proofs);
-
-    // For batch verification, we need to:
-    // 1. Generate random coefficients (using Fiat-Shamir)
-    // 2. Aggregate proofs: A_agg = sum(r_i * A_i)
-    // 3. Aggregate B's: B_agg = sum(r_i * B_i)
-    // 4. Aggregate C's: C_agg = sum(r_i * C_i)
-    // 5. Single pairing check
-
-    // Generate pseudo-random coefficients using Fiat-Shamir
-    let coefficients = generate_batch_coefficients(num_proofs, &request.proofs)?;
+    if request.proofs.len() > MAX_BATCH_SIZE {
+        return Err(VerifierError::BatchTooLarge.into());
+    }
 
-    // Aggregate A points
-    let a_agg = aggregate_g1_points(
-        &request.proofs.iter().map(|p| &p.a[..]).collect::<Vec<_>>(),
-        &coefficients,
+    let vk = batch_verification_key(request.circuit_id, PaymentPublicInputs::SIGNAL_LAYOUT)?;
+    check_public_input_counts(vk.ic, &request.public_inputs)?;
+    if !request.allow_duplicates {
+        check_distinct(&request.proofs, &request.public_inputs)?;
+    }
+    check_canonical_order(&request.proofs, &request.public_inputs)?;
+    let layout = VERIFY_BATCH;
+    layout.check_leading(accounts)?;
+    let prepared_account = &accounts[layout.prepared_key];
+    let prepared = PreparedVk::load_active(prepared_account, program_id, request.circuit_id)?;
+    check_recipient_markers(
+        program_id,
+        &accounts[VerifyBatchAccounts::LEN..],
+        &request.public_inputs,
     )?;
 
-    msg!("✓ A points aggregated");
+    let num_proofs = request.proofs.len();
+    msg!("Batch verifying proofs:");
+    sol_log_64(num_proofs as u64, request.circuit_id as u64, 0, 0, 0);
 
-    // Aggregate B points
-    let b_agg = aggregate_g2_points(
-        &request.proofs.iter().map(|p| &p.b[..]).collect::<Vec<_>>(),
-        &coefficients,
+    for proof in &request.proofs {
+        proof.check_canonical()?;
+    }
+    vk.validate()?;
+    verify_batch_pairing(
+        vk,
+        &prepared.neg_alpha_g1,
+        prepared.hash_backend,
+        &request.proofs,
+        &request.public_inputs,
     )?;
 
-    msg!("✓ B points aggregated");
+    msg!("✓ Batch verification successful");
+    VerifierEvent::BatchVerified(BatchVerified {
+        num_proofs: num_proofs as u32,
+    })
+    .emit();
+    Ok(())
+}
+
+/// Check every proof of a batch against `vk` with one pairing product
+///
+/// With r_i from `generate_batch_coefficients` under `backend`, checks
+///
+/// prod e(r_i * A_i, B_i) =
+///     e(sum(r_i) * alpha, beta) * e(sum(r_i * P_i), gamma) * e(sum(r_i * C_i), delta)
+///
+/// which every batch of valid proofs passes. The r_i are fixed only once
+/// every proof is, so a batch holding an invalid proof passes with negligible
+/// probability. Proofs sharing a B share its pair, so a batch costs one pair
+/// per distinct B plus three. `neg_alpha` is the prepared key's -alpha, and
+/// callers have checked the encodings and counts.
+pub fn verify_batch_pairing(
+    vk: &VerificationKey,
+    neg_alpha: &G1Point,
+    backend: HashBackend,
+    proofs: &[Groth16Proof],
+    public_inputs: &[PaymentPublicInputs],
+) -> ProgramResult {
+    let coefficients = generate_batch_coefficients(backend, proofs, public_inputs);
 
-    // Aggregate C points
+    let mut a_terms: Vec<(G1Point, G2Point)> = Vec::with_capacity(proofs.len());
+    for (proof, coefficient) in proofs.iter().zip(&coefficients) {
+        let a = proof.a.mul(coefficient)?;
+        match a_terms.iter_mut().find(|(_, b)| *b == proof.b) {
+            Some((sum, _)) => *sum = sum.add(&a)?,
+            None => a_terms.push((a, proof.b)),
+        }
+    }
     let c_agg = aggregate_g1_points(
-        &request.proofs.iter().map(|p| &p.c[..]).collect::<Vec<_>>(),
+        &proofs.iter().map(|p| &p.c).collect::<Vec<_>>(),
         &coefficients,
     )?;
+    let input_points = batch_public_input_points(vk.ic, public_inputs)?;
+    let input_agg = aggregate_g1_points(&input_points.iter().collect::<Vec<_>>(), &coefficients)?;
+    let neg_alpha_agg = aggregate_g1_points(&vec![neg_alpha; proofs.len()], &coefficients)?;
 
-    msg!("✓ C points aggregated");
-
-    // Now perform single pairing check on aggregated values
-    // This is much cheaper than num_proofs individual pairings
-    let mut pairing_input = Vec::with_capacity(384);
-    pairing_input.extend_from_slice(&a_agg);
-    pairing_input.extend_from_slice(&b_agg);
-
-    // Add remaining pairing elements (verification key components)
-    // ... (similar to individual verification)
-
-    let mut pairing_result = [0u8; ALT_BN128_PAIRING_OUTPUT_LEN];
-    alt_bn128_pairing(&pairing_input, &mut pairing_result).map_err(|e| {
-        msg!("Batch pairing failed: {:?}", e);
-        ProgramError::InvalidArgument
+    let mut pairing = PairingVec::with_capacity(a_terms.len() + 3);
+    for (a, b) in &a_terms {
+        pairing.add_pair(a, b);
+    }
+    pairing
+        .add_negated_pair(&input_agg, &vk.gamma_g2)
+        .add_negated_pair(&c_agg, &vk.delta_g2)
+        .add_pair(&neg_alpha_agg, &vk.beta_g2);
+    let verified = pairing.check().map_err(|e| {
+        msg!("Batch pairing failed");
+        e
     })?;
 
-    let expected = [
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        0, 1,
-    ];
-
-    if pairing_result == expected {
-        msg!("✓ Batch verification successful for {} proofs", num_proofs);
+    if verified {
         Ok(())
     } else {
         msg!("✗ Batch verification failed");
@@ -100,72 +274,588 @@ pub fn batch_verify_proofs(
     }
 }
 
-/// Generate pseudo-random coefficients for batch verification
-/// Uses Fiat-Shamir heuristic for non-interactivity
-fn generate_batch_coefficients(
-    num_proofs: usize,
-    proofs: &[Groth16Proof],
-) -> Result<Vec<[u8; 32]>, ProgramError> {
-    let mut coefficients = Vec::with_capacity(num_proofs);
+/// Share of a stale buffer's rent paid to whoever reaps it, in basis points
+pub const REAP_BOUNTY_BPS: u64 = 100;
+
+/// Lamports of a `lamports` buffer that `ReapStale` pays the reaper
+///
+/// A fixed share of what the buffer holds, so topping a buffer up to inflate
+/// the bounty costs the reaper more than it pays.
+pub fn reap_bounty(lamports: u64) -> u64 {
+    (lamports as u128 * REAP_BOUNTY_BPS as u128 / 10_000) as u64
+}
+
+/// Write a chunk of a Borsh-encoded `BatchVerificationRequest` into a buffer
+///
+/// Batches too large for one transaction's instruction data are uploaded in
+/// chunks and verified with `process_verify_batch_buffered`. The first write
+/// creates the buffer with a `BatchBuffer` header and room for `total_len`
+/// bytes; later writes ignore it. `offset` counts from the end of the header,
+/// and every write records its slot there so an upload in progress is not
+/// reaped.
+///
+/// Accounts expected:
+/// 0. `[signer, writable]` Authority, funds the buffer rent
+/// 1. `[writable]` Buffer PDA: ["batch-buffer", authority, nonce (u64 LE)]
+/// 2. `[]` System program
+pub fn process_write_batch_buffer(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    nonce: u64,
+    total_len: u32,
+    offset: u32,
+    data: &[u8],
+) -> ProgramResult {
+    let layout = WRITE_BATCH_BUFFER;
+    layout.check(accounts)?;
+    let authority = &accounts[layout.authority];
+    let buffer_account = &accounts[layout.buffer];
+    let system_program = &accounts[layout.system_program];
+
+    let nonce_bytes = nonce.to_le_bytes();
+    let (buffer_address, bump) = pda::batch_buffer_address(program_id, authority.key, nonce);
+    if buffer_address != *buffer_account.key {
+        msg!("Buffer account does not match authority and nonce");
+        return Err(ProgramError::InvalidSeeds);
+    }
 
-    // Simple deterministic generation (in production, use proper hash)
-    for i in 0..num_proofs {
-        let mut coeff = [0u8; 32];
-        // Use proof data to generate coefficient
-        let hash_input = [&proofs[i].a[..], &[i as u8]].concat();
-        // In production: use SHA256 or similar
-        coeff[..hash_input.len().min(32)].copy_from_slice(&hash_input[..hash_input.len().min(32)]);
-        coefficients.push(coeff);
+    if buffer_account.data_is_empty() {
+        if total_len as usize > MAX_BATCH_BUFFER_LEN {
+            return Err(VerifierError::BatchTooLarge.into());
+        }
+        create_pda_account(
+            authority,
+            buffer_account,
+            system_program,
+            program_id,
+            BatchBuffer::LEN + total_len as usize,
+            &[BATCH_BUFFER_SEED, authority.key.as_ref(), &nonce_bytes, &[bump]],
+        )?;
+    } else if buffer_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    } else {
+        // A buffer staged before the header was added must be aborted
+        BatchBuffer::split(&buffer_account.data.borrow())?;
     }
 
-    Ok(coefficients)
+    let header = BatchBuffer {
+        authority: *authority.key,
+        last_write_slot: Clock::get()?.slot,
+    };
+    let mut buffer = buffer_account.data.borrow_mut();
+    let (header_data, staged) = buffer.split_at_mut(BatchBuffer::LEN);
+    header.pack(header_data)?;
+    let start = offset as usize;
+    let end = start
+        .checked_add(data.len())
+        .filter(|end| *end <= staged.len())
+        .ok_or(ProgramError::InvalidArgument)?;
+    staged[start..end].copy_from_slice(data);
+
+    msg!("Wrote batch buffer bytes (len, offset):");
+    sol_log_64(data.len() as u64, offset as u64, 0, 0, 0);
+    Ok(())
 }
 
-/// Aggregate G1 points with coefficients
-fn aggregate_g1_points(
-    points: &[&[u8]],
-    coefficients: &[[u8; 32]],
-) -> Result<[u8; 64], ProgramError> {
-    if points.len() != coefficients.len() {
+/// Verify the batch staged in a buffer, then close the buffer to the authority
+///
+/// Accounts expected:
+/// 0. `[signer, writable]` Authority, receives the buffer rent
+/// 1. `[writable]` Buffer PDA: ["batch-buffer", authority, nonce (u64 LE)]
+/// 2. `[]` Prepared key PDA: ["prepared-vk", circuit_id], as for
+///    `VerifyBatch`
+/// 3.. `[]` Denylist marker PDA of each proof's recipient, in batch order
+pub fn process_verify_batch_buffered(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    nonce: u64,
+) -> ProgramResult {
+    let layout = VERIFY_BATCH_BUFFERED;
+    layout.check_leading(accounts)?;
+    let authority = &accounts[layout.authority];
+    let buffer_account = &accounts[layout.buffer];
+    // `batch_verify_proofs` reads the prepared key and the markers after it
+    let prepared_accounts = &accounts[layout.prepared_key..];
+
+    let (buffer_address, _) = pda::batch_buffer_address(program_id, authority.key, nonce);
+    if buffer_address != *buffer_account.key {
+        msg!("Buffer account does not match authority and nonce");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if buffer_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    // The buffer is allocated at the staged length, so a tail is junk written
+    // after the request
+    let request = {
+        let data = buffer_account.data.borrow();
+        let (_, staged) = BatchBuffer::split(&data)?;
+        decode_exact::<BatchVerificationRequest>(staged)
+            .map_err(|_| ProgramError::InvalidAccountData)?
+    };
+    batch_verify_proofs(program_id, prepared_accounts, &request)?;
+
+    close_buffer(buffer_account, authority);
+    Ok(())
+}
+
+/// Close a batch buffer without verifying it, refunding its rent to the
+/// authority
+///
+/// Works on any buffer of the authority, including one staged before buffers
+/// had a header.
+///
+/// Accounts expected:
+/// 0. `[signer, writable]` Authority, receives the buffer rent
+/// 1. `[writable]` Buffer PDA: ["batch-buffer", authority, nonce (u64 LE)]
+pub fn process_abort_buffer(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    nonce: u64,
+) -> ProgramResult {
+    let layout = ABORT_BUFFER;
+    layout.check(accounts)?;
+    let authority = &accounts[layout.authority];
+    let buffer_account = &accounts[layout.buffer];
+
+    let (buffer_address, _) = pda::batch_buffer_address(program_id, authority.key, nonce);
+    if buffer_address != *buffer_account.key {
+        msg!("Buffer account does not match authority and nonce");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if buffer_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    close_buffer(buffer_account, authority);
+    msg!("✓ Batch buffer aborted");
+    Ok(())
+}
+
+/// Close a batch buffer abandoned for the config's scratch TTL, paying the
+/// reaper `reap_bounty` of its rent and refunding the rest to the authority
+/// recorded in it
+///
+/// The TTL runs from the buffer's last write and never falls below
+/// `MIN_SCRATCH_TTL_SLOTS`, so a buffer still being uploaded is safe.
+///
+/// Accounts expected:
+/// 0. `[signer, writable]` Reaper, receives the bounty
+/// 1. `[writable]` Buffer PDA: ["batch-buffer", authority, nonce (u64 LE)]
+/// 2. `[writable]` Authority recorded in the buffer, receives the rest
+/// 3. `[]` Config PDA
+pub fn process_reap_stale(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    nonce: u64,
+) -> ProgramResult {
+    let layout = REAP_STALE;
+    layout.check(accounts)?;
+    let reaper = &accounts[layout.reaper];
+    let buffer_account = &accounts[layout.buffer];
+    let authority = &accounts[layout.authority];
+    let config_account = &accounts[layout.config];
+
+    let (buffer_address, _) = pda::batch_buffer_address(program_id, authority.key, nonce);
+    if buffer_address != *buffer_account.key {
+        msg!("Buffer account does not match authority and nonce");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if buffer_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let config = VerifierConfig::load(config_account, program_id)?;
+
+    let (header, _) = BatchBuffer::split(&buffer_account.data.borrow())?;
+    if header.authority != *authority.key {
         return Err(ProgramError::InvalidArgument);
     }
+    if !config.scratch_stale_at(header.last_write_slot, Clock::get()?.slot) {
+        return Err(VerifierError::ScratchNotStale.into());
+    }
 
-    if points.is_empty() {
+    let bounty = reap_bounty(buffer_account.lamports());
+    **buffer_account.lamports.borrow_mut() -= bounty;
+    **reaper.lamports.borrow_mut() += bounty;
+    close_buffer(buffer_account, authority);
+
+    msg!("✓ Stale batch buffer reaped");
+    Ok(())
+}
+
+/// Move all of a buffer's lamports to `recipient` and zero its data
+fn close_buffer(buffer_account: &AccountInfo, recipient: &AccountInfo) {
+    let lamports = buffer_account.lamports();
+    **recipient.lamports.borrow_mut() += lamports;
+    **buffer_account.lamports.borrow_mut() = 0;
+    buffer_account.data.borrow_mut().fill(0);
+}
+
+/// Verify each invoice-bound proof on its own and record receipts for those that pass
+///
+/// Writes per-proof results as return data (see `soft_batch_return_data`)
+/// and succeeds when at least `min_valid` proofs passed.
+///
+/// Accounts expected:
+/// 0. `[signer, writable]` Payer funding the receipt rents
+/// 1. `[]` Config PDA: ["config"]
+/// 2. `[]` System program
+/// 3. `[]` Prepared key PDA: ["prepared-vk", 2]; a revoked key fails the whole
+///    batch with `KeyRevoked`
+/// 4.. `[writable]` Receipt PDA of each proof, in order: ["receipt", proof_hash],
+///    each followed by its invoice index PDA ["invoice", invoice_id] unless
+///    the invoice id is zero, then by its recipient's denylist marker PDA
+///    ["denied", recipient_pubkey]; a denied recipient fails its proof alone.
+///    When the config sets a rate limit, the marker is followed by the
+///    recipient's stats PDA ["stats", recipient_pubkey], writable, and a
+///    recipient past its limit fails its remaining proofs with `RateLimited`
+/// 4 + n.. `[writable]` (optional) Audit log PDA: ["audit-log"]; every proof's
+///    outcome is appended unless the batch falls below `min_valid`
+pub fn process_verify_batch_soft(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    proofs: &[Groth16Proof],
+    public_inputs: &[PaymentPublicInputsV3],
+    min_valid: u8,
+) -> ProgramResult {
+    if proofs.len() != public_inputs.len() {
+        msg!("Mismatched proof and input counts");
         return Err(ProgramError::InvalidArgument);
     }
+    if proofs.len() > MAX_SOFT_BATCH_SIZE {
+        return Err(VerifierError::BatchTooLarge.into());
+    }
+    INVOICE_PAYMENT_VK.check_signal_layout(PaymentPublicInputsV3::SIGNAL_LAYOUT)?;
 
-    // Start with first point (identity would be better, but we don't have it)
-    let mut result = [0u8; 64];
-    result.copy_from_slice(points[0]);
+    let layout = VERIFY_BATCH_SOFT;
+    layout.check_leading(accounts)?;
+    let payer = &accounts[layout.payer];
+    let config_account = &accounts[layout.config];
+    let system_program = &accounts[layout.system_program];
+    let prepared_account = &accounts[layout.prepared_key];
+    PreparedVk::load_active(prepared_account, program_id, CircuitId::InvoicePayment)?;
+    let account_info_iter = &mut accounts[VerifyBatchSoftAccounts::LEN..].iter();
 
-    // Add remaining points
-    for i in 1..points.len() {
-        // Scalar multiply: temp = coefficient[i] * points[i]
-        let mut multiplication_input = Vec::with_capacity(96);
-        multiplication_input.extend_from_slice(points[i]);
-        multiplication_input.extend_from_slice(&coefficients[i]);
+    let config = VerifierConfig::load(config_account, program_id)?;
+    let clock = Clock::get()?;
 
-        let mut temp = [0u8; 64];
-        solana_program::alt_bn128::alt_bn128_multiplication(&multiplication_input, &mut temp)
-            .map_err(|_| ProgramError::InvalidArgument)?;
+    let mut keys = ValidatedKeys::default();
+    let mut results = Vec::with_capacity(proofs.len());
+    for (i, (proof, inputs)) in proofs.iter().zip(public_inputs).enumerate() {
+        let receipt_account = next_account_info(account_info_iter)?;
+        let index_account = if inputs.invoice_id == [0u8; 32] {
+            None
+        } else {
+            Some(next_account_info(account_info_iter)?)
+        };
+        let marker_account = next_account_info(account_info_iter)?;
+        let stats_account = if config.max_verifications_per_window > 0 {
+            Some(next_account_info(account_info_iter)?)
+        } else {
+            None
+        };
+        let result = verify_and_record(
+            program_id,
+            payer,
+            receipt_account,
+            index_account,
+            marker_account,
+            stats_account,
+            system_program,
+            &config,
+            &clock,
+            &mut keys,
+            proof,
+            inputs,
+            false,
+            Pubkey::default(),
+            0,
+            false,
+        );
+        if let Err(e) = &result {
+            msg!("Proof rejected (index, error):");
+            sol_log_64(i as u64, u64::from(e.clone()), 0, 0, 0);
+        }
+        results.push(result);
+    }
+    let log_account = audit::active_log(program_id, &config, account_info_iter.next())?;
+    check_no_extra_accounts(account_info_iter.as_slice())?;
 
-        // Add to result: result = result + temp
-        let mut addition_input = Vec::with_capacity(128);
-        addition_input.extend_from_slice(&result);
-        addition_input.extend_from_slice(&temp);
+    if let Some(log_account) = log_account {
+        for ((proof, inputs), result) in proofs.iter().zip(public_inputs).zip(&results) {
+            audit::record(
+                log_account,
+                &AuditEntry {
+                    proof_hash: proof.hash(inputs),
+                    recipient: inputs.recipient_pubkey,
+                    min_amount: inputs.min_amount,
+                    slot: clock.slot,
+                    outcome: audit::outcome(result),
+                },
+            )?;
+        }
+    }
 
-        alt_bn128_addition(&addition_input, &mut result)
-            .map_err(|_| ProgramError::InvalidArgument)?;
+    set_return_data(&soft_batch_return_data(&results));
+
+    let passed = results.iter().filter(|result| result.is_ok()).count();
+    msg!("Proofs passed (passed, total):");
+    sol_log_64(passed as u64, results.len() as u64, 0, 0, 0);
+    if passed < min_valid as usize {
+        return Err(VerifierError::BatchBelowThreshold.into());
     }
+    Ok(())
+}
 
-    Ok(result)
+/// Return data of a soft batch: `result_bitmap` of the outcomes, then each
+/// proof's error as a little-endian u64 in `ProgramError`'s encoding, 0 for a
+/// proof that passed
+///
+/// The codes let a relayer tell a malformed point (`AltBn128InvalidInput`)
+/// from a proof that decoded but failed the pairing (`InvalidArgument`).
+pub fn soft_batch_return_data(results: &[ProgramResult]) -> Vec<u8> {
+    let passed: Vec<bool> = results.iter().map(|result| result.is_ok()).collect();
+    let mut data = result_bitmap(&passed);
+    for result in results {
+        data.extend_from_slice(&audit::outcome(result).to_le_bytes());
+    }
+    data
+}
+
+/// Per-proof results of a soft batch of `count` proofs from its return data
+///
+/// The runtime strips trailing zero bytes from return data, so missing bytes
+/// read as zero.
+pub fn decode_soft_batch_results(data: &[u8], count: usize) -> Vec<ProgramResult> {
+    let codes_start = (count + 7) / 8;
+    let mut padded = data.to_vec();
+    padded.resize(codes_start + 8 * count, 0);
+
+    padded[codes_start..]
+        .chunks_exact(8)
+        .map(|code| match u64::from_le_bytes(code.try_into().unwrap()) {
+            0 => Ok(()),
+            code => Err(ProgramError::from(code)),
+        })
+        .collect()
+}
+
+/// Pack per-proof results into bytes, bit i (least significant first) for proof i
+pub fn result_bitmap(results: &[bool]) -> Vec<u8> {
+    let mut bitmap = vec![0u8; (results.len() + 7) / 8];
+    for (i, passed) in results.iter().enumerate() {
+        if *passed {
+            bitmap[i / 8] |= 1 << (i % 8);
+        }
+    }
+    bitmap
+}
+
+/// Refuse a batch unless every statement expands to exactly as many scalars
+/// as the key's `ic` takes, setting the first that does not as return data
+///
+/// The public input points pair scalars with IC points one to one, so a
+/// statement of another width would be folded against the wrong points.
+fn check_public_input_counts(
+    ic: &[G1Point],
+    public_inputs: &[PaymentPublicInputs],
+) -> ProgramResult {
+    let counts = public_inputs.iter().map(|inputs| inputs.to_scalars().len());
+    if let Some(mismatch) = PublicInputCountMismatch::find(ic.len(), counts) {
+        msg!("Public input count mismatch (expected, got, index):");
+        sol_log_64(mismatch.expected as u64, mismatch.got as u64, mismatch.index as u64, 0, 0);
+        set_return_data(&mismatch.try_to_vec()?);
+        return Err(VerifierError::PublicInputCountMismatch.into());
+    }
+    Ok(())
+}
+
+/// Check the denylist marker of every statement's recipient, one per
+/// statement in batch order, refusing any account after the last
+///
+/// A batch to many recipients passes a marker for each, so a denied
+/// recipient cannot be slipped into a batch the way a lone proof cannot.
+fn check_recipient_markers(
+    program_id: &Pubkey,
+    markers: &[AccountInfo],
+    public_inputs: &[PaymentPublicInputs],
+) -> ProgramResult {
+    if markers.len() < public_inputs.len() {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    let (markers, extra) = markers.split_at(public_inputs.len());
+    check_no_extra_accounts(extra)?;
+    for (marker, inputs) in markers.iter().zip(public_inputs) {
+        DeniedRecipient::check(marker, program_id, &inputs.recipient_pubkey)?;
+    }
+    Ok(())
+}
+
+/// Reject a batch containing the same statement more than once, under any
+/// proof
+///
+/// Sorts the proof hashes in a stack array, so the check is O(n log n)
+/// without a heap map. Callers must have bounded the batch by `MAX_BATCH_SIZE`.
+fn check_distinct(
+    proofs: &[Groth16Proof],
+    public_inputs: &[PaymentPublicInputs],
+) -> Result<(), VerifierError> {
+    let mut hashes = [[0u8; 32]; MAX_BATCH_SIZE];
+    let hashes = &mut hashes[..proofs.len()];
+    for (hash, (proof, inputs)) in hashes.iter_mut().zip(proofs.iter().zip(public_inputs)) {
+        *hash = proof.hash(inputs);
+    }
+    check_distinct_hashes(hashes)
 }
 
-/// Aggregate G2 points with coefficients
-fn aggregate_g2_points(
-    points: &[&[u8]],
-    coefficients: &[[u8; 32]],
-) -> Result<[u8; 128], ProgramError> {
+/// Reject a batch whose keccak256 proof hashes do not ascend, as
+/// `canonicalize` sorts them whatever the circuit's hash backend
+///
+/// Equal neighbours pass, as a batch allowing duplicates may repeat an entry;
+/// `check_distinct` refuses them otherwise.
+fn check_canonical_order(
+    proofs: &[Groth16Proof],
+    public_inputs: &[PaymentPublicInputs],
+) -> Result<(), VerifierError> {
+    let mut previous: Option<[u8; 32]> = None;
+    for (proof, inputs) in proofs.iter().zip(public_inputs) {
+        let hash = proof.hash(inputs);
+        if previous.map_or(false, |previous| previous > hash) {
+            msg!("Batch proofs not sorted by proof hash");
+            return Err(VerifierError::BatchNotCanonical);
+        }
+        previous = Some(hash);
+    }
+    Ok(())
+}
+
+/// Reject a repeated proof hash; at most `MAX_BATCH_SIZE` hashes
+fn check_distinct_hashes(hashes: &[[u8; 32]]) -> Result<(), VerifierError> {
+    let mut sorted = [[0u8; 32]; MAX_BATCH_SIZE];
+    let sorted = &mut sorted[..hashes.len()];
+    sorted.copy_from_slice(hashes);
+    sorted.sort_unstable();
+    if sorted.windows(2).any(|pair| pair[0] == pair[1]) {
+        return Err(VerifierError::DuplicateProofInBatch);
+    }
+    Ok(())
+}
+
+/// Public input point of every statement in a batch
+///
+/// Relayers often batch many payments to one recipient. A statement identical
+/// to an earlier one reuses its point outright, and the recipient limb terms
+/// are computed once per distinct recipient. The result is identical to calling
+/// `compute_public_input_point` per statement.
+fn batch_public_input_points(
+    ic: &[G1Point],
+    inputs: &[PaymentPublicInputs],
+) -> Result<Vec<G1Point>, ProgramError> {
+    let mut points: Vec<G1Point> = Vec::with_capacity(inputs.len());
+    let mut recipient_terms: Vec<([u8; 32], G1Point)> = Vec::new();
+
+    for (i, input) in inputs.iter().enumerate() {
+        if let Some(j) = inputs[..i].iter().position(|earlier| earlier == input) {
+            points.push(points[j]);
+            continue;
+        }
+
+        let scalars = input.to_scalars();
+        let recipient_term = match recipient_terms
+            .iter()
+            .find(|(recipient, _)| *recipient == input.recipient_pubkey)
+        {
+            Some((_, term)) => *term,
+            None => {
+                let mut term = G1Point::IDENTITY;
+                for k in RECIPIENT_SCALARS {
+                    term = add_input_term(term, &ic[k + 1], scalars[k].into())?;
+                }
+                recipient_terms.push((input.recipient_pubkey, term));
+                term
+            }
+        };
+
+        let mut point = ic[0].add(&recipient_term)?;
+        for (k, &scalar) in scalars.iter().enumerate() {
+            if !RECIPIENT_SCALARS.contains(&k) {
+                point = add_input_term(point, &ic[k + 1], scalar.into())?;
+            }
+        }
+        points.push(point);
+    }
+
+    Ok(points)
+}
+
+/// Domain of the batch coefficient transcript
+const BATCH_COEFFICIENTS_DOMAIN: &str = "x402-zk-verifier/batch-coefficients";
+
+/// Domain of the transcript naming a batch receipt
+const BATCH_RECEIPT_DOMAIN: &str = "x402-zk-verifier/batch-receipt";
+
+/// Derive one random coefficient per proof (Fiat-Shamir)
+///
+/// Every proof and every statement is absorbed before the first coefficient
+/// is squeezed, so no coefficient can be predicted while choosing the batch.
+/// The transcript is the batch count as a u64, then the label "proof" and A,
+/// B and C in syscall encoding for each proof, then the label
+/// "public-inputs" and the Borsh statement for each, under the domain
+/// "x402-zk-verifier/batch-coefficients"; see `transcript` for the encoding.
+pub fn generate_batch_coefficients(
+    backend: HashBackend,
+    proofs: &[Groth16Proof],
+    public_inputs: &[PaymentPublicInputs],
+) -> Vec<Scalar> {
+    let mut transcript =
+        batch_transcript(BATCH_COEFFICIENTS_DOMAIN, backend, proofs, public_inputs);
+    (0..proofs.len()).map(|_| transcript.challenge_scalar()).collect()
+}
+
+/// Identifier of a batch: the seed of its `BatchReceipt` PDA
+///
+/// Absorbs the statement hash of every entry in batch order under its own
+/// domain, always with keccak256. The proofs do not enter it, so the same
+/// statements resubmitted with re-randomized proofs name the same receipt.
+pub fn batch_transcript_hash(public_inputs: &[PaymentPublicInputs]) -> [u8; 32] {
+    let mut transcript = Transcript::new(BATCH_RECEIPT_DOMAIN);
+    transcript.append_u64(public_inputs.len() as u64);
+    for inputs in public_inputs {
+        transcript.append_label("statement");
+        transcript.append_bytes(&inputs.statement_hash());
+    }
+    transcript.challenge_bytes()
+}
+
+fn batch_transcript(
+    domain: &str,
+    backend: HashBackend,
+    proofs: &[Groth16Proof],
+    public_inputs: &[PaymentPublicInputs],
+) -> Transcript {
+    let mut transcript = Transcript::with_backend(domain, backend);
+    transcript.append_u64(proofs.len() as u64);
+    for proof in proofs {
+        transcript.append_label("proof");
+        transcript.append_bytes(&proof.a.to_bytes());
+        transcript.append_bytes(&proof.b.to_bytes());
+        transcript.append_bytes(&proof.c.to_bytes());
+    }
+    for inputs in public_inputs {
+        transcript.append_label("public-inputs");
+        transcript.append_bytes(&inputs.try_to_vec().unwrap());
+    }
+    transcript
+}
+
+/// sum(coefficients[i] * points[i]), one syscall multiplication per point
+///
+/// Fails with `InvalidArgument` on an empty or mismatched input.
+pub fn aggregate_g1_points(
+    points: &[&G1Point],
+    coefficients: &[Scalar],
+) -> Result<G1Point, ProgramError> {
     if points.len() != coefficients.len() {
         return Err(ProgramError::InvalidArgument);
     }
@@ -174,13 +864,10 @@ fn aggregate_g2_points(
         return Err(ProgramError::InvalidArgument);
     }
 
-    // G2 points are 128 bytes
-    let mut result = [0u8; 128];
-    result.copy_from_slice(points[0]);
-
-    // Note: G2 operations are not directly supported by alt_bn128
-    // In practice, batch verification for Groth16 typically only aggregates G1 points
-    // This is a simplified version
+    let mut result = G1Point::IDENTITY;
+    for (point, coefficient) in points.iter().zip(coefficients) {
+        result = result.add(&point.mul(coefficient)?)?;
+    }
 
     Ok(result)
 }
@@ -188,23 +875,332 @@ fn aggregate_g2_points(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{compute_public_input_point, PaymentPublicInputsV2, PAYMENT_VK};
+
+    #[test]
+    fn test_reap_bounty() {
+        assert_eq!(reap_bounty(2_000_000), 20_000);
+        // Rounded down, and never more than the buffer holds
+        assert_eq!(reap_bounty(99), 0);
+        assert_eq!(reap_bounty(u64::MAX), 184_467_440_737_095_516);
+    }
 
     #[test]
     fn test_coefficient_generation() {
         let proofs = vec![
             Groth16Proof {
-                a: [1u8; 64],
-                b: [2u8; 128],
-                c: [3u8; 64],
+                a: G1Point::new([1u8; 64]),
+                b: G2Point::new([2u8; 128]),
+                c: G1Point::new([3u8; 64]),
             },
             Groth16Proof {
-                a: [4u8; 64],
-                b: [5u8; 128],
-                c: [6u8; 64],
+                a: G1Point::new([4u8; 64]),
+                b: G2Point::new([5u8; 128]),
+                c: G1Point::new([6u8; 64]),
             },
         ];
+        let batch = vec![inputs(1000000, 4), inputs(2000000, 5)];
+
+        // Changing these values changes which batches a verifier accepts
+        let coeffs = generate_batch_coefficients(HashBackend::Keccak256, &proofs, &batch);
+        assert_eq!(
+            coeffs.iter().map(|c| c.to_syscall()).collect::<Vec<_>>(),
+            vec![
+                [
+                    0x26, 0xcc, 0xb6, 0x82, 0x54, 0x9b, 0xd8, 0x91,
+                    0xb3, 0x32, 0x90, 0xd8, 0xd4, 0x9c, 0x8e, 0x4b,
+                    0xd3, 0xd5, 0xb2, 0x4c, 0x8b, 0xcd, 0xcb, 0x7d,
+                    0xea, 0xd7, 0x22, 0x97, 0xf8, 0xb0, 0x9f, 0xf6,
+                ],
+                [
+                    0x1d, 0x34, 0x59, 0x41, 0x14, 0xa1, 0x27, 0x5c,
+                    0x0e, 0x31, 0x1c, 0x00, 0x6a, 0x53, 0xdf, 0x6d,
+                    0x99, 0xee, 0x8a, 0xbf, 0xa3, 0xa3, 0xc6, 0xdb,
+                    0xea, 0xf0, 0x3f, 0x85, 0x0a, 0x8a, 0xe1, 0x3d,
+                ],
+            ]
+        );
+
+        // Every coefficient depends on every statement, not just its own proof
+        let other_batch = vec![inputs(1000000, 4), inputs(2000001, 5)];
+        let other = generate_batch_coefficients(HashBackend::Keccak256, &proofs, &other_batch);
+        assert_ne!(other[0], coeffs[0]);
+        assert_ne!(other[1], coeffs[1]);
+    }
+
+    #[test]
+    fn test_batch_transcript_hash_pinned() {
+        let batch = vec![inputs(1000000, 4), inputs(2000000, 5)];
+
+        // Batch receipts live at this hash; changing it moves every receipt
+        assert_eq!(
+            batch_transcript_hash(&batch),
+            [
+                0x6e, 0xf8, 0xd4, 0x50, 0xbf, 0x54, 0x2d, 0x0c,
+                0x5f, 0xc5, 0x31, 0x27, 0x0b, 0x4d, 0x39, 0x94,
+                0x0e, 0x6b, 0x5b, 0x92, 0x7b, 0x8f, 0xb3, 0xe7,
+                0x30, 0xe8, 0x26, 0xa4, 0x69, 0xf3, 0x40, 0x48,
+            ]
+        );
+
+        // The order of the batch is part of its identity
+        let reversed_batch: Vec<_> = batch.iter().rev().cloned().collect();
+        assert_ne!(
+            batch_transcript_hash(&reversed_batch),
+            batch_transcript_hash(&batch)
+        );
+    }
+
+    fn batch_proof(seed: u8) -> Groth16Proof {
+        Groth16Proof {
+            a: G1Point::new([seed; 64]),
+            b: G2Point::new([2u8; 128]),
+            c: G1Point::new([3u8; 64]),
+        }
+    }
+
+    #[test]
+    fn test_duplicate_proofs_rejected() {
+        let proofs: Vec<Groth16Proof> = (1..=5).map(batch_proof).collect();
+        let batch: Vec<PaymentPublicInputs> = (0..5).map(|i| inputs(1000000 + i, 4)).collect();
+        assert_eq!(check_distinct(&proofs, &batch), Ok(()));
+
+        // Statement of the first entry at the last position, under another proof
+        let mut first_last = batch.clone();
+        first_last[4] = inputs(1000000, 4);
+        assert_eq!(
+            check_distinct(&proofs, &first_last),
+            Err(VerifierError::DuplicateProofInBatch)
+        );
+
+        // Adjacent duplicates at the start and at the end
+        let mut leading = batch.clone();
+        leading[1] = inputs(1000000, 4);
+        assert_eq!(
+            check_distinct(&proofs, &leading),
+            Err(VerifierError::DuplicateProofInBatch)
+        );
+        let mut trailing = batch.clone();
+        trailing[3] = inputs(1000004, 4);
+        assert_eq!(
+            check_distinct(&proofs, &trailing),
+            Err(VerifierError::DuplicateProofInBatch)
+        );
+
+        // The same proof for a different statement is a different entry
+        let same_proof: Vec<Groth16Proof> = (0..5).map(|_| batch_proof(1)).collect();
+        assert_eq!(check_distinct(&same_proof, &batch), Ok(()));
+    }
+
+    #[test]
+    fn test_batch_order_canonical() {
+        let mut request = BatchVerificationRequest {
+            proofs: (1..=5).map(batch_proof).collect(),
+            public_inputs: (0..5).map(|i| inputs(1000000 + i, 4)).collect(),
+            allow_duplicates: false,
+            circuit_id: CircuitId::Payment,
+        };
+        request.canonicalize();
+        let hashes: Vec<[u8; 32]> = request
+            .proofs
+            .iter()
+            .zip(&request.public_inputs)
+            .map(|(proof, inputs)| proof.hash(inputs))
+            .collect();
+        assert!(hashes.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(
+            check_canonical_order(&request.proofs, &request.public_inputs),
+            Ok(())
+        );
+
+        // Any other order of the same set sorts back to the same request
+        let mut reversed = request.clone();
+        reversed.proofs.reverse();
+        reversed.public_inputs.reverse();
+        let not_canonical: ProgramError = VerifierError::BatchNotCanonical.into();
+        let program_id = Pubkey::new_unique();
+        assert_eq!(
+            batch_verify_proofs(&program_id, &[], &reversed),
+            Err(not_canonical.clone())
+        );
+        reversed.canonicalize();
+        assert_eq!(
+            reversed.try_to_vec().unwrap(),
+            request.try_to_vec().unwrap()
+        );
+        assert_ne!(
+            batch_verify_proofs(&program_id, &[], &request),
+            Err(not_canonical)
+        );
+    }
+
+    #[test]
+    fn test_allow_duplicates_skips_check() {
+        let request = |allow_duplicates| BatchVerificationRequest {
+            proofs: vec![batch_proof(1), batch_proof(1)],
+            public_inputs: vec![inputs(1000000, 4), inputs(1000000, 4)],
+            allow_duplicates,
+            circuit_id: CircuitId::Payment,
+        };
+        let duplicate: ProgramError = VerifierError::DuplicateProofInBatch.into();
+
+        let program_id = Pubkey::new_unique();
+        assert_eq!(
+            batch_verify_proofs(&program_id, &[], &request(false)),
+            Err(duplicate.clone())
+        );
+        // Proceeds past the duplicate check to the prepared key, which this
+        // request leaves out
+        assert_ne!(
+            batch_verify_proofs(&program_id, &[], &request(true)),
+            Err(duplicate)
+        );
+    }
+
+    #[test]
+    fn test_batch_circuit_must_match_inputs() {
+        let mismatch: ProgramError = VerifierError::PublicInputLayoutMismatch.into();
+        let request = |circuit_id| BatchVerificationRequest {
+            proofs: vec![batch_proof(1), batch_proof(2)],
+            public_inputs: vec![inputs(1000000, 4), inputs(2000000, 4)],
+            allow_duplicates: false,
+            circuit_id,
+        };
+
+        let program_id = Pubkey::new_unique();
+        for circuit_id in [CircuitId::TokenPayment, CircuitId::Withdraw] {
+            assert_eq!(
+                batch_verify_proofs(&program_id, &[], &request(circuit_id)),
+                Err(mismatch.clone())
+            );
+        }
+        assert_ne!(
+            batch_verify_proofs(&program_id, &[], &request(CircuitId::Payment)),
+            Err(mismatch)
+        );
+
+        // Token payment statements against the SOL payment circuit, and back
+        assert_eq!(
+            batch_verification_key(CircuitId::Payment, PaymentPublicInputsV2::SIGNAL_LAYOUT)
+                .err(),
+            Some(VerifierError::PublicInputLayoutMismatch)
+        );
+        assert!(batch_verification_key(
+            CircuitId::TokenPayment,
+            PaymentPublicInputsV2::SIGNAL_LAYOUT
+        )
+        .is_ok());
+        assert_eq!(
+            batch_verification_key(CircuitId::TokenPayment, PaymentPublicInputs::SIGNAL_LAYOUT)
+                .err(),
+            Some(VerifierError::PublicInputLayoutMismatch)
+        );
+    }
+
+    #[test]
+    fn test_public_input_count_mismatch_names_first_offender() {
+        // A key of 5 IC points takes 4 scalars
+        assert_eq!(PublicInputCountMismatch::find(5, [4, 4, 4]), None);
+        assert_eq!(
+            PublicInputCountMismatch::find(5, [4, 4, 5, 3]),
+            Some(PublicInputCountMismatch {
+                expected: 4,
+                got: 5,
+                index: 2,
+            })
+        );
+        assert_eq!(
+            PublicInputCountMismatch::find(5, [4, 3, 4]),
+            Some(PublicInputCountMismatch {
+                expected: 4,
+                got: 3,
+                index: 1,
+            })
+        );
+
+        let batch = [inputs(1000000, 4), inputs(2000000, 4)];
+        let mismatch: ProgramError = VerifierError::PublicInputCountMismatch.into();
+        assert_eq!(check_public_input_counts(PAYMENT_VK.ic, &batch), Ok(()));
+        for ic_len in [PAYMENT_VK.ic.len() - 1, PAYMENT_VK.ic.len() + 1] {
+            let ic = vec![G1Point::IDENTITY; ic_len];
+            assert_eq!(check_public_input_counts(&ic, &batch), Err(mismatch.clone()));
+        }
+    }
+
+    #[test]
+    fn test_result_bitmap() {
+        assert_eq!(result_bitmap(&[]), Vec::<u8>::new());
+        assert_eq!(result_bitmap(&[true, false, true, true]), vec![0b1101]);
+
+        let mut results = [true; 10];
+        results[9] = false;
+        assert_eq!(result_bitmap(&results), vec![0xff, 0b01]);
+    }
+
+    #[test]
+    fn test_soft_batch_return_data_round_trip() {
+        let results = [
+            Ok(()),
+            Err(VerifierError::AltBn128InvalidInput.into()),
+            Err(ProgramError::InvalidArgument),
+            // Custom(0) has its own encoding, distinct from success
+            Err(VerifierError::NonCanonicalEncoding.into()),
+            Ok(()),
+        ];
+        let data = soft_batch_return_data(&results);
+        assert_eq!(data.len(), 1 + 8 * results.len());
+        assert_eq!(data[0], 0b10001);
+        assert_eq!(&data[1 + 8..1 + 16], &29u64.to_le_bytes());
+        assert_eq!(decode_soft_batch_results(&data, results.len()), results);
+
+        // As recorded by the runtime, without the trailing zero code
+        let stripped = &data[..data.len() - 8];
+        assert_eq!(decode_soft_batch_results(stripped, results.len()), results);
+        assert_eq!(decode_soft_batch_results(&[], 2), vec![Ok(()), Ok(())]);
+    }
+
+    /// IC points k*G for k = 1..=6, so every term is distinct
+    fn distinct_ic() -> Vec<G1Point> {
+        let mut generator = [0u8; 64];
+        generator[31] = 1;
+        generator[63] = 2;
+        let g = G1Point::new(generator);
+
+        let mut ic = vec![g];
+        for _ in 1..6 {
+            let next = ic.last().unwrap().add(&g).unwrap();
+            ic.push(next);
+        }
+        ic
+    }
+
+    fn inputs(min_amount: u64, recipient: u8) -> PaymentPublicInputs {
+        PaymentPublicInputs {
+            min_amount,
+            recipient_pubkey: [recipient; 32],
+            max_block_age: 60,
+            current_time: 1700000000,
+        }
+    }
+
+    #[test]
+    fn test_shared_inputs_match_naive_path() {
+        let ic = distinct_ic();
+        let batch = vec![
+            inputs(1000000, 4),
+            inputs(1000000, 4), // identical statement
+            inputs(2000000, 4), // same recipient, different amount
+            inputs(1000000, 5), // unique recipient
+            inputs(1, 0),       // all-zero recipient
+            inputs(3000000, 4),
+            inputs(1, 0),
+        ];
 
-        let coeffs = generate_batch_coefficients(2, &proofs).unwrap();
-        assert_eq!(coeffs.len(), 2);
+        let shared = batch_public_input_points(&ic, &batch).unwrap();
+        let naive: Vec<G1Point> = batch
+            .iter()
+            .map(|input| compute_public_input_point(&ic, &input.to_scalars()).unwrap())
+            .collect();
+        assert_eq!(shared, naive);
     }
 }
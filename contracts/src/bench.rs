@@ -0,0 +1,129 @@
+// Compute unit benchmark rows
+// Enabled with the `client` feature. The `bench-cu` binary measures each
+// verification path at every batch size it takes and prints one row per
+// measurement as CSV; `parse_cu_csv` reads that output back, so a CI job can
+// compare two runs without a CSV library. Units are those the transaction
+// consumed, read from the bank's transaction metadata.
+
+use crate::client::ClientError;
+
+/// Header line of the CSV `to_cu_csv` writes
+pub const CU_CSV_HEADER: &str = "path,batch_size,total_units,units_per_proof,accepted";
+
+/// Verification path a row measures
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BenchPath {
+    /// One `VerifyWithInlineKey` per proof
+    Single,
+    /// `VerifyBatch`: one aggregated pairing for the whole batch
+    Batch,
+    /// `VerifyBatchSoft`: one pairing and one receipt per proof
+    SoftBatch,
+}
+
+impl BenchPath {
+    pub const ALL: [BenchPath; 3] = [BenchPath::Single, BenchPath::Batch, BenchPath::SoftBatch];
+
+    /// Name of the path in the CSV
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BenchPath::Single => "single",
+            BenchPath::Batch => "batch",
+            BenchPath::SoftBatch => "soft_batch",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|path| path.as_str() == name)
+    }
+}
+
+/// Compute units one path used for one batch size
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CuRow {
+    pub path: BenchPath,
+    pub batch_size: usize,
+    /// Units of every transaction the measurement sent, added up
+    pub total_units: u64,
+    /// Whether the program accepted the proofs; a rejected batch stops at the
+    /// failing check, so its units are a lower bound
+    pub accepted: bool,
+}
+
+impl CuRow {
+    /// `total_units` spread over the batch, rounded down
+    pub fn units_per_proof(&self) -> u64 {
+        self.total_units / self.batch_size.max(1) as u64
+    }
+
+    fn to_csv_line(&self) -> String {
+        format!(
+            "{},{},{},{},{}",
+            self.path.as_str(),
+            self.batch_size,
+            self.total_units,
+            self.units_per_proof(),
+            self.accepted
+        )
+    }
+}
+
+/// `rows` as CSV under `CU_CSV_HEADER`, one line each
+pub fn to_cu_csv(rows: &[CuRow]) -> String {
+    let mut csv = String::from(CU_CSV_HEADER);
+    csv.push('\n');
+    for row in rows {
+        csv.push_str(&row.to_csv_line());
+        csv.push('\n');
+    }
+    csv
+}
+
+/// Read back the CSV `to_cu_csv` writes
+///
+/// The header must match `CU_CSV_HEADER`; blank lines are skipped, and
+/// `units_per_proof` must agree with the row's total and batch size.
+///
+/// ```
+/// use x402_zk_verifier::bench::{parse_cu_csv, to_cu_csv, BenchPath, CuRow};
+///
+/// let rows = vec![
+///     CuRow { path: BenchPath::Single, batch_size: 2, total_units: 201_000, accepted: true },
+///     CuRow { path: BenchPath::Batch, batch_size: 2, total_units: 150_000, accepted: false },
+/// ];
+/// let csv = to_cu_csv(&rows);
+/// assert_eq!(parse_cu_csv(&csv).unwrap(), rows);
+///
+/// let csv = "path,batch_size,total_units,units_per_proof,accepted\n\
+///            soft_batch,4,400000,100000,true\n";
+/// let row = &parse_cu_csv(csv).unwrap()[0];
+/// assert_eq!(row.path, BenchPath::SoftBatch);
+/// assert_eq!(row.units_per_proof(), 100_000);
+///
+/// assert!(parse_cu_csv("batch_size,path\n").is_err());
+/// ```
+pub fn parse_cu_csv(csv: &str) -> Result<Vec<CuRow>, ClientError> {
+    let mut lines = csv.lines().filter(|line| !line.trim().is_empty());
+    if lines.next().map(str::trim) != Some(CU_CSV_HEADER) {
+        return Err(ClientError::InvalidField("benchmark CSV header"));
+    }
+    lines.map(parse_row).collect()
+}
+
+fn parse_row(line: &str) -> Result<CuRow, ClientError> {
+    let malformed = || ClientError::MalformedPayload("benchmark CSV row");
+    let fields: Vec<&str> = line.trim().split(',').collect();
+    let [path, batch_size, total_units, units_per_proof, accepted] = fields[..] else {
+        return Err(malformed());
+    };
+    let row = CuRow {
+        path: BenchPath::from_name(path).ok_or_else(malformed)?,
+        batch_size: batch_size.parse().map_err(|_| malformed())?,
+        total_units: total_units.parse().map_err(|_| malformed())?,
+        accepted: accepted.parse().map_err(|_| malformed())?,
+    };
+    if row.batch_size == 0 || units_per_proof.parse::<u64>() != Ok(row.units_per_proof()) {
+        return Err(malformed());
+    }
+    Ok(row)
+}
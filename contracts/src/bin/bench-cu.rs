@@ -0,0 +1,216 @@
+// Compute units per proof of each verification path, as CSV
+// Loads the release program into a `ProgramTest` bank, so the units are the
+// ones a cluster charges. Build it first, then run from contracts/:
+//
+//   cargo build-bpf --features bench-keys
+//   cargo run -p x402-zk-verifier --bin bench-cu --features prove-tests,bench-keys > cu.csv
+//
+// Every path gets the same payment statements with fixture proofs from
+// `test_utils`, for batch sizes 1 to MAX_BATCH_SIZE. The single path sends one
+// `VerifyWithInlineKey` per proof against a fixture key of the payment
+// circuit's shape. `VerifyBatch` and `VerifyBatchSoft` (1 to
+// MAX_SOFT_BATCH_SIZE) take the registered keys only, which `bench-keys`
+// replaces with the fixture keys of the payment and invoice payment circuits,
+// so every path verifies valid proofs to the end. A row that still says
+// `accepted = false` stopped at a failing check and its units are a lower
+// bound. Batches too large for one transaction are measured as sent in one;
+// on a cluster they go through the batch buffer instead.
+// `bench::parse_cu_csv` reads the output back.
+
+use borsh::BorshSerialize;
+use solana_program_test::{ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::Account, clock::Clock, instruction::Instruction, pubkey::Pubkey, signature::Signer,
+    transaction::Transaction,
+};
+use x402_zk_verifier::{
+    batch_verifier::{BatchVerificationRequest, MAX_BATCH_SIZE, MAX_SOFT_BATCH_SIZE},
+    bench::{to_cu_csv, BenchPath, CuRow},
+    client,
+    endian::Be32,
+    inline_key::InlineVerificationKey,
+    test_utils::{add_config, add_prepared_keys, fixture_key, fixture_proof, FIXTURE_KEY_ACCOUNT},
+    CircuitId, Groth16Proof, PaymentPublicInputs, PaymentPublicInputsV3,
+};
+
+/// The most a transaction may use, which the largest batches need
+const COMPUTE_UNIT_LIMIT: u64 = 1_400_000;
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::default();
+    program_test.prefer_bpf(true);
+    program_test.add_program("x402_zk_verifier", program_id, None);
+    program_test.set_compute_max_units(COMPUTE_UNIT_LIMIT);
+
+    let key = fixture_key(PaymentPublicInputs::SIGNAL_LAYOUT.len());
+    let invoice_key = fixture_key(PaymentPublicInputsV3::SIGNAL_LAYOUT.len());
+    program_test.add_account(
+        FIXTURE_KEY_ACCOUNT,
+        Account {
+            lamports: 1_000_000_000,
+            data: key.try_to_vec().unwrap(),
+            ..Account::default()
+        },
+    );
+    add_config(&mut program_test, &program_id, Pubkey::new_unique(), 0);
+    add_prepared_keys(&mut program_test, &program_id);
+    let mut context = program_test.start_with_context().await;
+    let now = context
+        .banks_client
+        .get_sysvar::<Clock>()
+        .await
+        .unwrap()
+        .unix_timestamp;
+
+    let mut rows = Vec::new();
+    for size in 1..=MAX_BATCH_SIZE {
+        let statements = payment_statements(&key, size, now);
+
+        let mut total_units = 0;
+        let mut accepted = true;
+        for (proof, inputs) in &statements {
+            let instruction = client::verify_with_key_account_instruction(
+                &program_id,
+                &FIXTURE_KEY_ACCOUNT,
+                &key,
+                proof.clone(),
+                inputs.to_scalars().map(Be32::from_u64).to_vec(),
+            );
+            let (units, ok) = measure(&mut context, instruction).await;
+            total_units += units;
+            accepted &= ok;
+        }
+        rows.push(CuRow {
+            path: BenchPath::Single,
+            batch_size: size,
+            total_units,
+            accepted,
+        });
+
+        let instruction = batch_instruction(&program_id, &statements);
+        let (total_units, accepted) = measure(&mut context, instruction).await;
+        rows.push(CuRow {
+            path: BenchPath::Batch,
+            batch_size: size,
+            total_units,
+            accepted,
+        });
+
+        if size <= MAX_SOFT_BATCH_SIZE {
+            let payer = context.payer.pubkey();
+            let instruction =
+                soft_batch_instruction(&program_id, &payer, &invoice_key, &statements);
+            let (total_units, accepted) = measure(&mut context, instruction).await;
+            rows.push(CuRow {
+                path: BenchPath::SoftBatch,
+                batch_size: size,
+                total_units,
+                accepted,
+            });
+        }
+    }
+
+    for row in rows.iter().filter(|row| !row.accepted) {
+        eprintln!(
+            "{} of {}: rejected, units are a lower bound",
+            row.path.as_str(),
+            row.batch_size
+        );
+    }
+    print!("{}", to_cu_csv(&rows));
+}
+
+/// `count` distinct payment statements, each with a fixture proof against `key`
+fn payment_statements(
+    key: &InlineVerificationKey,
+    count: usize,
+    now: i64,
+) -> Vec<(Groth16Proof, PaymentPublicInputs)> {
+    (0..count)
+        .map(|i| {
+            let inputs = PaymentPublicInputs {
+                min_amount: 1_000_000 + i as u64,
+                recipient_pubkey: [4u8; 32],
+                max_block_age: 60,
+                current_time: now,
+            };
+            let proof = fixture_proof(key, &inputs.to_scalars().map(Be32::from_u64));
+            (proof, inputs)
+        })
+        .collect()
+}
+
+/// `VerifyBatch` of `statements` in canonical order
+fn batch_instruction(
+    program_id: &Pubkey,
+    statements: &[(Groth16Proof, PaymentPublicInputs)],
+) -> Instruction {
+    let request = BatchVerificationRequest {
+        proofs: statements.iter().map(|(proof, _)| proof.clone()).collect(),
+        public_inputs: statements
+            .iter()
+            .map(|(_, inputs)| inputs.clone())
+            .collect(),
+        allow_duplicates: false,
+        circuit_id: CircuitId::Payment,
+    };
+    client::verify_batch_instruction(program_id, request)
+}
+
+/// `VerifyBatchSoft` of `statements`, each paying a fresh invoice with a
+/// fixture proof against `invoice_key`, that needs every proof to pass
+///
+/// Invoice ids carry the batch size, so no batch reuses the receipt or the
+/// invoice index of a smaller one.
+fn soft_batch_instruction(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    invoice_key: &InlineVerificationKey,
+    statements: &[(Groth16Proof, PaymentPublicInputs)],
+) -> Instruction {
+    let mut proofs = Vec::new();
+    let mut public_inputs = Vec::new();
+    for (i, (_, inputs)) in statements.iter().enumerate() {
+        let mut invoice_id = [0u8; 32];
+        invoice_id[0] = statements.len() as u8;
+        invoice_id[1] = i as u8 + 1;
+        let inputs = PaymentPublicInputsV3 {
+            min_amount: inputs.min_amount,
+            recipient_pubkey: inputs.recipient_pubkey,
+            max_block_age: inputs.max_block_age,
+            current_time: inputs.current_time,
+            invoice_id,
+            valid_until: 0,
+            recipient_set_root: [0u8; 32],
+        };
+        let proof = fixture_proof(invoice_key, &inputs.to_scalars().map(Be32::from_u64));
+        proofs.push(proof);
+        public_inputs.push(inputs);
+    }
+    client::verify_batch_soft_instruction(
+        program_id,
+        payer,
+        proofs,
+        public_inputs,
+        statements.len() as u8,
+    )
+}
+
+/// Units the transaction of `instruction` consumed, and whether it succeeded
+async fn measure(context: &mut ProgramTestContext, instruction: Instruction) -> (u64, bool) {
+    let blockhash = context.get_new_latest_blockhash().await.unwrap();
+    let mut transaction =
+        Transaction::new_with_payer(&[instruction], Some(&context.payer.pubkey()));
+    transaction.sign(&[&context.payer], blockhash);
+    let result = context
+        .banks_client
+        .process_transaction_with_metadata(transaction)
+        .await
+        .unwrap();
+    let units = result
+        .metadata
+        .map_or(0, |metadata| metadata.compute_units_consumed);
+    (units, result.result.is_ok())
+}
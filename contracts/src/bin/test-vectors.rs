@@ -0,0 +1,10 @@
+// Dump `test_vectors::vectors()` as JSON for SDKs outside Rust
+//
+//   cargo run --features test_vectors --bin test-vectors > vectors.json
+
+use x402_zk_verifier::test_vectors;
+
+fn main() {
+    let json = test_vectors::vectors().to_json();
+    println!("{}", serde_json::to_string_pretty(&json).unwrap());
+}
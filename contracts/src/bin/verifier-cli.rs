@@ -0,0 +1,597 @@
+// Command-line access to the verifier for operators
+//
+//   verifier-cli verify-offchain --circuit <name> --proof <file> --public <file>
+//   verifier-cli build-ix        --kind <payment|subscription> --program-id <key>
+//                                --proof <file> --inputs <file> [--payer <key>]
+//   verifier-cli submit          <build-ix options> --keypair <file> [--skip-preflight]
+//                                [--max-retries <n>]
+//   verifier-cli estimate-cu     <build-ix options> --keypair <file>
+//   verifier-cli decode-receipt  --address <key> [--program-id <key>]
+//
+// Proofs are read as snarkjs proof.json or as hex (`a:b:c`, or the 256 proof
+// bytes). Public inputs are read as snarkjs public.json (verify-offchain only),
+// the `PaymentPublicInputs` display form, or hex of their Borsh encoding.
+// RPC commands use --url, else $SOLANA_RPC_URL, else devnet.
+
+use std::{collections::HashMap, process::ExitCode, str::FromStr, time::Duration};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde_json::Value;
+use solana_client::{
+    client_error::ClientError as RpcError, nonblocking::rpc_client::RpcClient,
+    rpc_config::RpcSendTransactionConfig,
+};
+use solana_sdk::{
+    account::from_account,
+    clock::Clock,
+    commitment_config::{CommitmentConfig, CommitmentLevel},
+    hash::Hash,
+    instruction::{Instruction, InstructionError},
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Keypair, Signature, Signer},
+    sysvar,
+    transaction::{Transaction, TransactionError},
+};
+use x402_zk_verifier::{
+    client::{
+        bn128_syscalls_enabled, check_transaction_size, decode_hex, preflight, submit_with_retry,
+        verify_offchain, verify_proof_instruction, verify_subscription_instruction, AccountFetcher,
+        ClientError, FetchedAccount, PreflightCheck, PreflightOutcome, PublicSignals, RpcLike,
+        SubmitOptions, SubmitOutcome,
+    },
+    snarkjs,
+    state::{PaymentReceipt, StateAccount},
+    CircuitId, CommittedPaymentPublicInputs, G1Point, G2Point, Groth16Proof, PaymentPublicInputs,
+    PaymentPublicInputsV2, PaymentPublicInputsV3, RefundPublicInputs, SplitPaymentPublicInputs,
+    SubscriptionPublicInputs, VerifierError, WithdrawPublicInputs,
+};
+
+const DEFAULT_RPC_URL: &str = "https://api.devnet.solana.com";
+
+/// How often `confirm` polls a sent transaction's status
+const CONFIRM_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    let Some(command) = args.next() else {
+        eprintln!(
+            "usage: verifier-cli \
+             <verify-offchain|build-ix|submit|estimate-cu|decode-receipt> [--option value]..."
+        );
+        return ExitCode::FAILURE;
+    };
+    let result = match Options::parse(args) {
+        Ok(opts) => run(&command, &opts).await,
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(command: &str, opts: &Options) -> Result<(), String> {
+    match command {
+        "verify-offchain" => verify_offchain_command(opts),
+        "build-ix" => {
+            let payer = match opts.get("payer") {
+                Some(payer) => parse_pubkey(payer)?,
+                None => Pubkey::default(),
+            };
+            print_instruction(&build_instruction(opts, &payer)?);
+            Ok(())
+        }
+        "submit" => submit(opts).await,
+        "estimate-cu" => estimate_cu(opts).await,
+        "decode-receipt" => decode_receipt(opts).await,
+        other => Err(format!("unknown command {}", other)),
+    }
+}
+
+/// `--name value` pairs and bare `--flag`s
+#[derive(Debug, Default)]
+struct Options {
+    values: HashMap<String, String>,
+    flags: Vec<String>,
+}
+
+impl Options {
+    fn parse(args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut opts = Options::default();
+        let mut args = args.peekable();
+        while let Some(arg) = args.next() {
+            let name = arg
+                .strip_prefix("--")
+                .ok_or_else(|| format!("unexpected argument {}", arg))?;
+            match args.next_if(|next| !next.starts_with("--")) {
+                Some(value) => {
+                    opts.values.insert(name.to_string(), value);
+                }
+                None => opts.flags.push(name.to_string()),
+            }
+        }
+        Ok(opts)
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(String::as_str)
+    }
+
+    fn required(&self, name: &str) -> Result<&str, String> {
+        self.get(name).ok_or_else(|| format!("missing --{}", name))
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.flags.iter().any(|flag| flag == name)
+    }
+
+    fn read(&self, name: &str) -> Result<String, String> {
+        let path = self.required(name)?;
+        std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    fn rpc(&self) -> RpcClient {
+        let url = self
+            .get("url")
+            .map(str::to_string)
+            .or_else(|| std::env::var("SOLANA_RPC_URL").ok())
+            .unwrap_or_else(|| DEFAULT_RPC_URL.to_string());
+        RpcClient::new_with_commitment(url, CommitmentConfig::confirmed())
+    }
+
+    fn keypair(&self) -> Result<Keypair, String> {
+        let path = self.required("keypair")?;
+        read_keypair_file(path).map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+fn verify_offchain_command(opts: &Options) -> Result<(), String> {
+    let circuit = parse_circuit(opts.required("circuit")?)?;
+    let proof = parse_proof(&opts.read("proof")?)?;
+    let scalars = parse_scalars(circuit, &opts.read("public")?)?;
+
+    match verify_offchain(circuit, &proof, &scalars) {
+        Ok(()) => {
+            println!("valid");
+            Ok(())
+        }
+        Err(e) => Err(format!("invalid: {}", describe_program_error(&e))),
+    }
+}
+
+fn build_instruction(opts: &Options, payer: &Pubkey) -> Result<Instruction, String> {
+    let program_id = parse_pubkey(opts.required("program-id")?)?;
+    let proof = parse_proof(&opts.read("proof")?)?;
+    let inputs = opts.read("inputs")?;
+
+    match opts.required("kind")? {
+        "payment" => Ok(verify_proof_instruction(
+            &program_id,
+            proof,
+            parse_payment_inputs(&inputs)?,
+        )),
+        "subscription" => {
+            if *payer == Pubkey::default() {
+                return Err("subscription instructions need --payer or --keypair".into());
+            }
+            Ok(verify_subscription_instruction(
+                &program_id,
+                payer,
+                proof,
+                parse_borsh_hex(&inputs)?,
+            ))
+        }
+        other => Err(format!("unknown instruction kind {}", other)),
+    }
+}
+
+fn print_instruction(instruction: &Instruction) {
+    println!("program: {}", instruction.program_id);
+    for (i, meta) in instruction.accounts.iter().enumerate() {
+        let mode = match (meta.is_signer, meta.is_writable) {
+            (true, true) => "signer, writable",
+            (true, false) => "signer",
+            (false, true) => "writable",
+            (false, false) => "readonly",
+        };
+        println!("account {}: {} [{}]", i, meta.pubkey, mode);
+    }
+    println!("data: {}", hex(&instruction.data));
+}
+
+/// Subscription proofs go through `client::preflight` first, which reports
+/// what the program would reject without paying for a transaction; other
+/// proofs only through its check that the cluster has the curve syscalls
+async fn run_preflight(opts: &Options, rpc: &RpcClient) -> Result<(), String> {
+    if opts.required("kind")? != "subscription" {
+        return match bn128_syscalls_enabled(&Rpc(rpc)).await {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(format!(
+                "preflight {:?} failed: {}",
+                PreflightCheck::Bn128Syscalls,
+                describe_program_error(&VerifierError::SyscallUnavailable.into())
+            )),
+            Err(e) => Err(e.to_string()),
+        };
+    }
+    let program_id = parse_pubkey(opts.required("program-id")?)?;
+    let proof = parse_proof(&opts.read("proof")?)?;
+    let inputs: SubscriptionPublicInputs = parse_borsh_hex(&opts.read("inputs")?)?;
+
+    let report = preflight(&Rpc(rpc), &program_id, &proof, &inputs)
+        .await
+        .map_err(|e| e.to_string())?;
+    for (check, outcome) in &report.checks {
+        let outcome = match outcome {
+            PreflightOutcome::Passed => "passed".to_string(),
+            PreflightOutcome::Skipped => "skipped".to_string(),
+            PreflightOutcome::Failed(e) => describe_program_error(e),
+        };
+        println!("preflight {:?}: {}", check, outcome);
+    }
+    match report.first_failure() {
+        Some((check, e)) => Err(format!(
+            "preflight {:?} failed: {}",
+            check,
+            describe_program_error(e)
+        )),
+        None => Ok(()),
+    }
+}
+
+async fn signed_transaction(opts: &Options, rpc: &RpcClient) -> Result<Transaction, String> {
+    let keypair = opts.keypair()?;
+    let instruction = build_instruction(opts, &keypair.pubkey())?;
+    check_transaction_size(std::slice::from_ref(&instruction), &keypair.pubkey())
+        .map_err(|e| e.to_string())?;
+    let blockhash = rpc.get_latest_blockhash().await.map_err(|e| e.to_string())?;
+    Ok(Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&keypair.pubkey()),
+        &[&keypair],
+        blockhash,
+    ))
+}
+
+async fn submit(opts: &Options) -> Result<(), String> {
+    let rpc = opts.rpc();
+    if !opts.flag("skip-preflight") {
+        run_preflight(opts, &rpc).await?;
+    }
+    let keypair = opts.keypair()?;
+    let instruction = build_instruction(opts, &keypair.pubkey())?;
+    let mut submit_opts = SubmitOptions::default();
+    if let Some(max_retries) = opts.get("max-retries") {
+        submit_opts.max_retries = max_retries
+            .parse()
+            .map_err(|_| format!("invalid --max-retries {}", max_retries))?;
+    }
+
+    let outcome = submit_with_retry(&Rpc(&rpc), &[instruction], &keypair, &submit_opts)
+        .await
+        .map_err(|e| e.to_string())?;
+    match outcome {
+        SubmitOutcome::Confirmed(signature) => {
+            println!("confirmed: {}", signature);
+            Ok(())
+        }
+        SubmitOutcome::AlreadyProcessed(signature) => {
+            println!("already processed: {}", signature);
+            Ok(())
+        }
+        SubmitOutcome::ProofRejected(code) => Err(format!("rejected: {}", describe_custom(code))),
+        SubmitOutcome::Expired => Err("blockhash expired on every attempt".into()),
+    }
+}
+
+async fn estimate_cu(opts: &Options) -> Result<(), String> {
+    let rpc = opts.rpc();
+    let transaction = signed_transaction(opts, &rpc).await?;
+    let simulation = rpc
+        .simulate_transaction(&transaction)
+        .await
+        .map_err(|e| describe_rpc_error(&e))?
+        .value;
+
+    if let Some(units) = simulation.units_consumed {
+        println!("compute units: {}", units);
+    }
+    match simulation.err {
+        Some(e) => Err(format!("simulation failed: {}", describe_transaction_error(&e))),
+        None => Ok(()),
+    }
+}
+
+async fn decode_receipt(opts: &Options) -> Result<(), String> {
+    let address = parse_pubkey(opts.required("address")?)?;
+    let account = opts
+        .rpc()
+        .get_account(&address)
+        .await
+        .map_err(|e| describe_rpc_error(&e))?;
+    if let Some(program_id) = opts.get("program-id") {
+        if account.owner != parse_pubkey(program_id)? {
+            return Err(format!("{} is owned by {}", address, account.owner));
+        }
+    }
+
+    let receipt = PaymentReceipt::unpack(&account.data)
+        .map_err(|e| format!("not a payment receipt: {:?}", e))?;
+    println!("proof_hash: {}", hex(&receipt.proof_hash));
+    println!("recipient: {}", Pubkey::new_from_array(receipt.recipient));
+    println!("min_amount: {}", receipt.min_amount);
+    println!("invoice_id: {}", hex(&receipt.invoice_id));
+    println!("payer: {}", receipt.payer);
+    println!("status: {:?}", receipt.status);
+    println!("slot: {}", receipt.slot);
+    println!("finalized: {}", receipt.finalized);
+    Ok(())
+}
+
+/// An RPC node as seen by `client::preflight`
+struct Rpc<'a>(&'a RpcClient);
+
+impl AccountFetcher for Rpc<'_> {
+    async fn get_account(&self, address: &Pubkey) -> Result<Option<FetchedAccount>, ClientError> {
+        let account = self
+            .0
+            .get_account_with_commitment(address, self.0.commitment())
+            .await
+            .map_err(|e| ClientError::Rpc(e.to_string()))?
+            .value;
+        Ok(account.map(|account| FetchedAccount {
+            owner: account.owner,
+            lamports: account.lamports,
+            data: account.data,
+        }))
+    }
+
+    async fn get_clock(&self) -> Result<Clock, ClientError> {
+        let account = self
+            .0
+            .get_account(&sysvar::clock::id())
+            .await
+            .map_err(|e| ClientError::Rpc(e.to_string()))?;
+        from_account(&account).ok_or_else(|| ClientError::Rpc("malformed clock sysvar".into()))
+    }
+}
+
+impl RpcLike for Rpc<'_> {
+    async fn get_latest_blockhash(&self) -> Result<(Hash, u64), ClientError> {
+        self.0
+            .get_latest_blockhash_with_commitment(self.0.commitment())
+            .await
+            .map_err(|e| ClientError::Rpc(e.to_string()))
+    }
+
+    async fn simulate(
+        &self,
+        transaction: &Transaction,
+    ) -> Result<Result<u64, TransactionError>, ClientError> {
+        let simulation = self
+            .0
+            .simulate_transaction(transaction)
+            .await
+            .map_err(|e| ClientError::Rpc(e.to_string()))?
+            .value;
+        Ok(match simulation.err {
+            Some(e) => Err(e),
+            None => Ok(simulation.units_consumed.unwrap_or_default()),
+        })
+    }
+
+    async fn send(
+        &self,
+        transaction: &Transaction,
+    ) -> Result<Result<(), TransactionError>, ClientError> {
+        let config = RpcSendTransactionConfig {
+            skip_preflight: true,
+            ..RpcSendTransactionConfig::default()
+        };
+        match self.0.send_transaction_with_config(transaction, config).await {
+            Ok(_) => Ok(Ok(())),
+            Err(e) => match e.get_transaction_error() {
+                Some(e) => Ok(Err(e)),
+                None => Err(ClientError::Rpc(e.to_string())),
+            },
+        }
+    }
+
+    async fn confirm(
+        &self,
+        signature: &Signature,
+        level: CommitmentLevel,
+        last_valid_block_height: u64,
+    ) -> Result<Option<Result<(), TransactionError>>, ClientError> {
+        let commitment = CommitmentConfig { commitment: level };
+        loop {
+            // Height first: a status read after the blockhash expired is final
+            let block_height = self
+                .0
+                .get_block_height()
+                .await
+                .map_err(|e| ClientError::Rpc(e.to_string()))?;
+            let status = self
+                .0
+                .get_signature_status_with_commitment(signature, commitment)
+                .await
+                .map_err(|e| ClientError::Rpc(e.to_string()))?;
+            if status.is_some() {
+                return Ok(status);
+            }
+            if block_height > last_valid_block_height {
+                return Ok(None);
+            }
+            tokio::time::sleep(CONFIRM_POLL_INTERVAL).await;
+        }
+    }
+}
+
+/// `VerifierError` names for the program's custom codes
+fn describe_program_error(error: &solana_sdk::program_error::ProgramError) -> String {
+    use solana_sdk::program_error::ProgramError;
+
+    match error {
+        ProgramError::Custom(code) => describe_custom(*code),
+        other => other.to_string(),
+    }
+}
+
+fn describe_custom(code: u32) -> String {
+    match VerifierError::try_from(code) {
+        Ok(error) => format!("{:?} ({})", error, error),
+        Err(code) => format!("custom program error {}", code),
+    }
+}
+
+fn describe_transaction_error(error: &TransactionError) -> String {
+    match error {
+        TransactionError::InstructionError(index, InstructionError::Custom(code)) => {
+            format!("instruction {}: {}", index, describe_custom(*code))
+        }
+        other => other.to_string(),
+    }
+}
+
+fn describe_rpc_error(error: &RpcError) -> String {
+    match error.get_transaction_error() {
+        Some(e) => describe_transaction_error(&e),
+        None => error.to_string(),
+    }
+}
+
+fn parse_pubkey(s: &str) -> Result<Pubkey, String> {
+    Pubkey::from_str(s).map_err(|_| format!("invalid public key {}", s))
+}
+
+fn parse_circuit(name: &str) -> Result<CircuitId, String> {
+    Ok(match name {
+        "payment" => CircuitId::Payment,
+        "token-payment" => CircuitId::TokenPayment,
+        "invoice-payment" => CircuitId::InvoicePayment,
+        "split-payment" => CircuitId::SplitPayment,
+        "refund" => CircuitId::Refund,
+        "subscription" => CircuitId::Subscription,
+        "withdraw" => CircuitId::Withdraw,
+        "committed-payment" => CircuitId::CommittedPayment,
+        other => return Err(format!("unknown circuit {}", other)),
+    })
+}
+
+/// snarkjs proof.json, `a:b:c` hex, or the 256 proof bytes in hex
+fn parse_proof(text: &str) -> Result<Groth16Proof, String> {
+    let text = text.trim();
+    if text.starts_with('{') {
+        let json: Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        return snarkjs::parse_proof(&json).map_err(|e| e.to_string());
+    }
+    if text.contains(':') {
+        return text.parse().map_err(|e: ClientError| e.to_string());
+    }
+    parse_borsh_hex(text)
+}
+
+fn parse_borsh_hex<T: BorshDeserialize>(text: &str) -> Result<T, String> {
+    let bytes = decode_hex(text.trim()).ok_or("invalid hex")?;
+    T::try_from_slice(&bytes).map_err(|e| format!("malformed payload: {}", e))
+}
+
+fn parse_payment_inputs(text: &str) -> Result<PaymentPublicInputs, String> {
+    let text = text.trim();
+    if text.contains('=') {
+        return text.parse().map_err(|e: ClientError| e.to_string());
+    }
+    parse_borsh_hex(text)
+}
+
+/// snarkjs public.json, or the circuit's typed public inputs
+fn parse_scalars(circuit: CircuitId, text: &str) -> Result<Vec<u64>, String> {
+    let text = text.trim();
+    if text.starts_with('[') {
+        let json: Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        return snarkjs::parse_public_signals(&json).map_err(|e| e.to_string());
+    }
+
+    fn typed<T: PublicSignals + BorshDeserialize>(text: &str) -> Result<Vec<u64>, String> {
+        parse_borsh_hex::<T>(text).map(|inputs| inputs.signals())
+    }
+    match circuit {
+        CircuitId::Payment => parse_payment_inputs(text).map(|inputs| inputs.signals()),
+        CircuitId::TokenPayment => typed::<PaymentPublicInputsV2>(text),
+        CircuitId::InvoicePayment => typed::<PaymentPublicInputsV3>(text),
+        CircuitId::SplitPayment => typed::<SplitPaymentPublicInputs>(text),
+        CircuitId::Refund => typed::<RefundPublicInputs>(text),
+        CircuitId::Subscription => typed::<SubscriptionPublicInputs>(text),
+        CircuitId::Withdraw => typed::<WithdrawPublicInputs>(text),
+        CircuitId::CommittedPayment => typed::<CommittedPaymentPublicInputs>(text),
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_options_parse() {
+        let opts = options(&["--circuit", "payment", "--skip-preflight", "--url", "x"]).unwrap();
+        assert_eq!(opts.get("circuit"), Some("payment"));
+        assert_eq!(opts.get("url"), Some("x"));
+        assert!(opts.flag("skip-preflight"));
+        assert_eq!(opts.required("proof").unwrap_err(), "missing --proof");
+        assert!(options(&["payment"]).is_err());
+    }
+
+    #[test]
+    fn test_proof_formats_agree() {
+        let proof = Groth16Proof {
+            a: G1Point::new([1u8; 64]),
+            b: G2Point::new([2u8; 128]),
+            c: G1Point::new([3u8; 64]),
+        };
+        let from_display = parse_proof(&proof.to_string()).unwrap();
+        let bytes = proof.try_to_vec().unwrap();
+        let from_bytes = parse_proof(&format!("0x{}", hex(&bytes))).unwrap();
+        assert_eq!(from_display.b, proof.b);
+        assert_eq!(from_bytes.b, proof.b);
+        assert_eq!(from_bytes.c, proof.c);
+    }
+
+    #[test]
+    fn test_public_json_scalars() {
+        let public_json = r#"["1000000", "4", "5", "60", "1700000000"]"#;
+        let scalars = parse_scalars(CircuitId::Payment, public_json);
+        assert_eq!(scalars, Ok(vec![1000000, 4, 5, 60, 1700000000]));
+
+        let too_large = parse_scalars(CircuitId::Payment, r#"["18446744073709551616"]"#);
+        let expected = ClientError::MalformedPayload("public signal").to_string();
+        assert_eq!(too_large.unwrap_err(), expected);
+    }
+
+    #[test]
+    fn test_custom_errors_named() {
+        assert_eq!(
+            describe_custom(VerifierError::NullifierAlreadySpent as u32),
+            "NullifierAlreadySpent (Nullifier already spent)"
+        );
+        assert_eq!(describe_custom(9999), "custom program error 9999");
+        assert_eq!(
+            describe_transaction_error(&TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(VerifierError::EpochMismatch as u32)
+            )),
+            "instruction 0: EpochMismatch (Proof epoch is outside the accepted window)"
+        );
+    }
+}
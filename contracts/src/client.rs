@@ -0,0 +1,3532 @@
+// Off-chain helpers for relayers, gateways and wallets
+// Enabled with the `client` feature; everything here must produce exactly the
+// same bytes as the on-chain program.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    future::Future,
+    ops::Range,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        RwLock,
+    },
+    time::Duration,
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    account_info::AccountInfo,
+    clock::Clock,
+    feature::Feature,
+    instruction::{AccountMeta, Instruction},
+    keccak,
+    message::Message,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_program, sysvar,
+};
+use thiserror::Error;
+
+use crate::{
+    batch_receipt,
+    batch_verifier::{batch_transcript_hash, BatchVerificationRequest, MAX_BATCH_SIZE},
+    commitment::AmountOpening,
+    endian::Be32,
+    envelope::ProofEnvelope,
+    field::Scalar,
+    inline_key::{InlineKeySource, InlineVerificationKey},
+    layout,
+    state::{
+        DeniedRecipient, InvoiceIndex, PaymentReceipt, PreparedVk, SpentNullifier, StateAccount,
+        VerifierConfig,
+    },
+    syscall_probe::ALT_BN128_FEATURE_ID,
+    verify_groth16, CircuitId, CommittedPaymentPublicInputs, G1Point, G2Point, Groth16Proof,
+    HashBackend, PaymentPublicInputs, PaymentPublicInputsV2, PaymentPublicInputsV3,
+    RefundPublicInputs, SplitPaymentPublicInputs, SerializedLen, Statement,
+    SubscriptionPublicInputs, VerificationKey, VerifierError, VerifierInstruction,
+    WithdrawPublicInputs,
+};
+
+#[cfg(feature = "rpc")]
+mod diagnose;
+#[cfg(feature = "rpc")]
+mod finality;
+mod metrics;
+#[cfg(feature = "rpc")]
+mod submit;
+pub use crate::pda;
+#[cfg(feature = "prometheus")]
+pub use metrics::PrometheusMetrics;
+#[cfg(feature = "metrics")]
+pub use metrics::{error_code, NoopMetrics, VerifierMetrics, OFFCHAIN_LABEL};
+#[cfg(all(feature = "rpc", feature = "metrics"))]
+pub use submit::submit_with_retry_with_metrics;
+#[cfg(feature = "rpc")]
+pub use diagnose::{diagnose_failure, Diagnosis, FailureCause, FailureReport};
+#[cfg(feature = "rpc")]
+pub use finality::{await_receipt_finality, FinalityOptions, FinalityOutcome, FinalityRpc};
+#[cfg(feature = "rpc")]
+pub use submit::{
+    submit_with_retry, RpcLike, SubmitOptions, SubmitOutcome, MAX_COMPUTE_UNIT_LIMIT,
+};
+
+#[cfg(not(feature = "metrics"))]
+use metrics::{error_code, NoopMetrics, VerifierMetrics, OFFCHAIN_LABEL};
+
+/// HTTP header carrying the hex-encoded Borsh proof
+pub const PROOF_HEADER: &str = "X-ZK-PROOF";
+
+/// HTTP header carrying the hex-encoded Borsh public inputs
+pub const PUBLIC_INPUTS_HEADER: &str = "X-ZK-PUBLIC-INPUTS";
+
+/// HTTP header carrying the hex-encoded invoice id the proof is bound to
+pub const INVOICE_ID_HEADER: &str = "X-ZK-INVOICE-ID";
+
+/// Errors produced by client-side helpers
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ClientError {
+    #[error("Invalid hex encoding in {0}")]
+    InvalidHex(&'static str),
+
+    #[error("Malformed {0} payload")]
+    MalformedPayload(&'static str),
+
+    #[error("Invoice id header does not match the proof's public inputs")]
+    InvoiceMismatch,
+
+    #[error("RPC request failed: {0}")]
+    Rpc(String),
+
+    #[error("Wrong length for {0}")]
+    InvalidLength(&'static str),
+
+    #[error("Missing or unknown field in {0}")]
+    InvalidField(&'static str),
+
+    #[error("Transaction failed: {0}")]
+    TransactionFailed(String),
+
+    #[error("Transaction needs {needed} bytes, over the {limit}-byte limit; {suggestion}")]
+    TransactionTooLarge {
+        needed: usize,
+        limit: usize,
+        suggestion: SizeSuggestion,
+    },
+}
+
+/// Compute the canonical proof hash off-chain
+///
+/// Identical to `Groth16Proof::hash`, which the program uses for nullifier,
+/// receipt and event identifiers.
+pub fn proof_hash(proof: &Groth16Proof, public_inputs: &PaymentPublicInputs) -> [u8; 32] {
+    proof.hash(public_inputs)
+}
+
+/// Compute the aggregated batch path's random coefficients off-chain
+///
+/// Identical to the program's Fiat-Shamir derivation, so a relayer can
+/// reproduce exactly what a batch will be checked against.
+pub fn batch_coefficients(
+    proofs: &[Groth16Proof],
+    public_inputs: &[PaymentPublicInputs],
+) -> Vec<[u8; 32]> {
+    batch_coefficients_with(HashBackend::Keccak256, proofs, public_inputs)
+}
+
+/// `batch_coefficients` under `backend`, as read with `hash_backend`
+pub fn batch_coefficients_with(
+    backend: HashBackend,
+    proofs: &[Groth16Proof],
+    public_inputs: &[PaymentPublicInputs],
+) -> Vec<[u8; 32]> {
+    crate::batch_verifier::generate_batch_coefficients(backend, proofs, public_inputs)
+        .into_iter()
+        .map(Scalar::to_syscall)
+        .collect()
+}
+
+/// x402 payment headers for an invoice-bound proof
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaymentHeaders {
+    pub proof: String,
+    pub public_inputs: String,
+    pub invoice_id: String,
+}
+
+impl PaymentHeaders {
+    /// Header name/value pairs, ready to attach to a request
+    pub fn to_pairs(&self) -> [(&'static str, &str); 3] {
+        [
+            (PROOF_HEADER, &self.proof),
+            (PUBLIC_INPUTS_HEADER, &self.public_inputs),
+            (INVOICE_ID_HEADER, &self.invoice_id),
+        ]
+    }
+}
+
+/// Encode a proof and its invoice-bound public inputs as x402 headers
+pub fn encode_payment_headers(
+    proof: &Groth16Proof,
+    public_inputs: &PaymentPublicInputsV3,
+) -> PaymentHeaders {
+    PaymentHeaders {
+        proof: to_hex(&proof.try_to_vec().unwrap()),
+        public_inputs: to_hex(&public_inputs.try_to_vec().unwrap()),
+        invoice_id: to_hex(&public_inputs.invoice_id),
+    }
+}
+
+/// Decode x402 headers, rejecting an invoice header that disagrees with the
+/// invoice id actually bound into the proof's public inputs
+pub fn decode_payment_headers(
+    headers: &PaymentHeaders,
+) -> Result<(Groth16Proof, PaymentPublicInputsV3), ClientError> {
+    let proof_bytes = from_hex(&headers.proof).ok_or(ClientError::InvalidHex(PROOF_HEADER))?;
+    let input_bytes = from_hex(&headers.public_inputs)
+        .ok_or(ClientError::InvalidHex(PUBLIC_INPUTS_HEADER))?;
+    let invoice_id =
+        from_hex(&headers.invoice_id).ok_or(ClientError::InvalidHex(INVOICE_ID_HEADER))?;
+
+    let proof = Groth16Proof::try_from_slice(&proof_bytes)
+        .map_err(|_| ClientError::MalformedPayload(PROOF_HEADER))?;
+    let public_inputs = PaymentPublicInputsV3::try_from_slice(&input_bytes)
+        .map_err(|_| ClientError::MalformedPayload(PUBLIC_INPUTS_HEADER))?;
+
+    if invoice_id != public_inputs.invoice_id {
+        return Err(ClientError::InvoiceMismatch);
+    }
+
+    Ok((proof, public_inputs))
+}
+
+/// What a gateway asks a payer to prove, the one source of both the statement
+/// the program checks and the prover's input
+///
+/// The statement is the invoice-bound `PaymentPublicInputsV3` that
+/// `VerifyAndRecord` takes; the circuit input is built from that statement, so
+/// the two cannot disagree on a signal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaymentIntent {
+    pub recipient: Pubkey,
+    pub min_amount: u64,
+    pub max_block_age: u64,
+    pub invoice_id: [u8; 32],
+    /// Unix timestamp after which the proof is rejected; 0 means no expiry
+    pub valid_until: i64,
+    /// Root of the recipient allowlist the config approves
+    pub recipient_set_root: [u8; 32],
+}
+
+impl PaymentIntent {
+    /// The statement of a proof generated at `clock_time`
+    pub fn to_public_inputs(&self, clock_time: i64) -> PaymentPublicInputsV3 {
+        PaymentPublicInputsV3 {
+            min_amount: self.min_amount,
+            recipient_pubkey: self.recipient.to_bytes(),
+            max_block_age: self.max_block_age,
+            current_time: clock_time,
+            invoice_id: self.invoice_id,
+            valid_until: self.valid_until,
+            recipient_set_root: self.recipient_set_root,
+        }
+    }
+
+    /// The public signals of `to_public_inputs(clock_time)` as a snarkjs
+    /// input.json object, each signal name mapped to its decimal scalar
+    ///
+    /// The prover adds the private signals before generating the witness.
+    pub fn to_circuit_inputs_json(&self, clock_time: i64) -> String {
+        let signals: Vec<String> = PaymentPublicInputsV3::SIGNAL_LAYOUT
+            .iter()
+            .zip(self.to_public_inputs(clock_time).to_scalars())
+            .map(|(name, scalar)| format!("\"{}\":\"{}\"", name, scalar))
+            .collect();
+        format!("{{{}}}", signals.join(","))
+    }
+}
+
+/// `VerifyProof` for a SOL payment proof with its config, prepared key and
+/// denylist marker PDAs derived
+pub fn verify_proof_instruction(
+    program_id: &Pubkey,
+    proof: Groth16Proof,
+    public_inputs: PaymentPublicInputs,
+) -> Instruction {
+    let accounts = verify_proof_metas(
+        program_id,
+        CircuitId::Payment,
+        &public_inputs.recipient_pubkey,
+    );
+    Instruction::new_with_borsh(
+        *program_id,
+        &VerifierInstruction::VerifyProof {
+            proof,
+            public_inputs,
+        },
+        accounts,
+    )
+}
+
+/// `VerifyProofV2` for a token payment proof with its config, prepared key
+/// and denylist marker PDAs derived
+pub fn verify_proof_v2_instruction(
+    program_id: &Pubkey,
+    proof: Groth16Proof,
+    public_inputs: PaymentPublicInputsV2,
+) -> Instruction {
+    let accounts = verify_proof_metas(
+        program_id,
+        CircuitId::TokenPayment,
+        &public_inputs.recipient_pubkey,
+    );
+    Instruction::new_with_borsh(
+        *program_id,
+        &VerifierInstruction::VerifyProofV2 {
+            proof,
+            public_inputs,
+        },
+        accounts,
+    )
+}
+
+/// `VerifyEnvelope`, or `VerifyEnvelopeWithMaxSkew` given a skew bound, with
+/// the PDAs of the circuit and recipient the envelope decodes to derived
+pub fn verify_envelope_instruction(
+    program_id: &Pubkey,
+    envelope: ProofEnvelope,
+    max_time_skew_seconds: Option<u64>,
+) -> Result<Instruction, ClientError> {
+    let (_, inputs) = envelope
+        .decode()
+        .map_err(|_| ClientError::MalformedPayload("envelope"))?;
+    let accounts = verify_proof_metas(program_id, inputs.circuit, &inputs.recipient_pubkey);
+    let instruction = match max_time_skew_seconds {
+        Some(max_time_skew_seconds) => VerifierInstruction::VerifyEnvelopeWithMaxSkew {
+            envelope,
+            max_time_skew_seconds,
+        },
+        None => VerifierInstruction::VerifyEnvelope { envelope },
+    };
+    Ok(Instruction::new_with_borsh(
+        *program_id,
+        &instruction,
+        accounts,
+    ))
+}
+
+/// Metas of the `VerifyProof` layout for a statement of `circuit` paying
+/// `recipient`
+fn verify_proof_metas(
+    program_id: &Pubkey,
+    circuit: CircuitId,
+    recipient: &[u8; 32],
+) -> Vec<AccountMeta> {
+    let (config, _) = pda::config_address(program_id);
+    let (prepared, _) = pda::vkey_address(program_id, circuit);
+    let (marker, _) = pda::denied_recipient_address(program_id, recipient);
+    let layout = layout::VERIFY_PROOF;
+    layout.metas(&[
+        (layout.system_program, system_program::id()),
+        (layout.config, config),
+        (layout.prepared_key, prepared),
+        (layout.marker, marker),
+    ])
+}
+
+/// `VerifyAndRecord` with its config, receipt, denylist marker, prepared key
+/// and, for a
+/// nonzero invoice id, invoice index PDAs derived; records no stats, so it
+/// fails under a rate-limiting config, and declares no compute unit price
+pub fn verify_and_record_instruction(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    proof: Groth16Proof,
+    public_inputs: PaymentPublicInputsV3,
+) -> Instruction {
+    let (config, _) = pda::config_address(program_id);
+    let (receipt, _) = pda::receipt_address(program_id, &proof.hash(&public_inputs));
+    let (marker, _) = pda::denied_recipient_address(program_id, &public_inputs.recipient_pubkey);
+    let (prepared, _) = pda::vkey_address(program_id, CircuitId::InvoicePayment);
+    let layout = layout::VERIFY_AND_RECORD;
+    let mut accounts = layout.metas(&[
+        (layout.payer, *payer),
+        (layout.config, config),
+        (layout.receipt, receipt),
+        (layout.system_program, system_program::id()),
+        (layout.marker, marker),
+        (layout.prepared_key, prepared),
+    ]);
+    if public_inputs.invoice_id != [0u8; 32] {
+        let (index, _) = pda::invoice_address(program_id, &public_inputs.invoice_id);
+        accounts.push(AccountMeta::new(index, false));
+    }
+    Instruction::new_with_borsh(
+        *program_id,
+        &VerifierInstruction::VerifyAndRecord {
+            proof,
+            public_inputs,
+            update_stats: false,
+            dry_run: false,
+            consumable: false,
+            delegate: None,
+            compute_unit_price: 0,
+        },
+        accounts,
+    )
+}
+
+/// `VerifyBatchSoft` paid by `payer`, each proof's receipt, invoice index and
+/// denylist marker PDAs derived; like `verify_and_record_instruction`, it
+/// passes no stats PDAs and so fails under a rate-limiting config
+pub fn verify_batch_soft_instruction(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    proofs: Vec<Groth16Proof>,
+    public_inputs: Vec<PaymentPublicInputsV3>,
+    min_valid: u8,
+) -> Instruction {
+    let (config, _) = pda::config_address(program_id);
+    let (prepared, _) = pda::vkey_address(program_id, CircuitId::InvoicePayment);
+    let layout = layout::VERIFY_BATCH_SOFT;
+    let mut accounts = layout.metas(&[
+        (layout.payer, *payer),
+        (layout.config, config),
+        (layout.system_program, system_program::id()),
+        (layout.prepared_key, prepared),
+    ]);
+    for (proof, inputs) in proofs.iter().zip(&public_inputs) {
+        let (receipt, _) = pda::receipt_address(program_id, &proof.hash(inputs));
+        accounts.push(AccountMeta::new(receipt, false));
+        if inputs.invoice_id != [0u8; 32] {
+            let (index, _) = pda::invoice_address(program_id, &inputs.invoice_id);
+            accounts.push(AccountMeta::new(index, false));
+        }
+        let (marker, _) = pda::denied_recipient_address(program_id, &inputs.recipient_pubkey);
+        accounts.push(AccountMeta::new_readonly(marker, false));
+    }
+    Instruction::new_with_borsh(
+        *program_id,
+        &VerifierInstruction::VerifyBatchSoft {
+            proofs,
+            public_inputs,
+            min_valid,
+        },
+        accounts,
+    )
+}
+
+/// `VerifyProofWithAck` with its config, denylist marker and prepared key
+/// PDAs derived; the transaction must also carry the recipient's ed25519
+/// signature instruction
+pub fn verify_proof_with_ack_instruction(
+    program_id: &Pubkey,
+    proof: Groth16Proof,
+    public_inputs: PaymentPublicInputsV3,
+) -> Instruction {
+    let (config, _) = pda::config_address(program_id);
+    let (marker, _) = pda::denied_recipient_address(program_id, &public_inputs.recipient_pubkey);
+    let (prepared, _) = pda::vkey_address(program_id, CircuitId::InvoicePayment);
+    let layout = layout::VERIFY_PROOF_WITH_ACK;
+    Instruction::new_with_borsh(
+        *program_id,
+        &VerifierInstruction::VerifyProofWithAck {
+            proof,
+            public_inputs,
+        },
+        layout.metas(&[
+            (layout.config, config),
+            (layout.instructions_sysvar, sysvar::instructions::id()),
+            (layout.marker, marker),
+            (layout.prepared_key, prepared),
+        ]),
+    )
+}
+
+/// `TouchReceipt` refreshing the receipt of `receipt_proof_hash` with a new
+/// proof, signed by `recipient`
+pub fn touch_receipt_instruction(
+    program_id: &Pubkey,
+    recipient: &Pubkey,
+    receipt_proof_hash: [u8; 32],
+    proof: Groth16Proof,
+    public_inputs: PaymentPublicInputsV3,
+) -> Instruction {
+    let (config, _) = pda::config_address(program_id);
+    let (receipt, _) = pda::receipt_address(program_id, &receipt_proof_hash);
+    let (marker, _) = pda::denied_recipient_address(program_id, &public_inputs.recipient_pubkey);
+    let (prepared, _) = pda::vkey_address(program_id, CircuitId::InvoicePayment);
+    let layout = layout::TOUCH_RECEIPT;
+    Instruction::new_with_borsh(
+        *program_id,
+        &VerifierInstruction::TouchReceipt {
+            receipt_proof_hash,
+            proof,
+            public_inputs,
+        },
+        layout.metas(&[
+            (layout.recipient, *recipient),
+            (layout.config, config),
+            (layout.receipt, receipt),
+            (layout.marker, marker),
+            (layout.prepared_key, prepared),
+        ]),
+    )
+}
+
+/// `VerifyRefund` with its receipt and prepared key PDAs derived and, given
+/// the receipt's invoice id, the invoice index PDA to mark refunded
+pub fn verify_refund_instruction(
+    program_id: &Pubkey,
+    proof: Groth16Proof,
+    public_inputs: RefundPublicInputs,
+    invoice_id: Option<[u8; 32]>,
+) -> Instruction {
+    let (receipt, _) = pda::receipt_address(program_id, &public_inputs.receipt_proof_hash);
+    let (prepared, _) = pda::vkey_address(program_id, CircuitId::Refund);
+    let layout = layout::VERIFY_REFUND;
+    let mut keys = vec![(layout.receipt, receipt), (layout.prepared_key, prepared)];
+    if let Some(invoice_id) = invoice_id {
+        let (index, _) = pda::invoice_address(program_id, &invoice_id);
+        keys.push((layout.invoice_index, index));
+    }
+    Instruction::new_with_borsh(
+        *program_id,
+        &VerifierInstruction::VerifyRefund {
+            proof,
+            public_inputs,
+        },
+        layout.metas(&keys),
+    )
+}
+
+/// `VerifySubscription` with its config, nullifier, denylist marker and
+/// prepared key PDAs derived
+pub fn verify_subscription_instruction(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    proof: Groth16Proof,
+    public_inputs: SubscriptionPublicInputs,
+) -> Instruction {
+    let (config, _) = pda::config_address(program_id);
+    let (nullifier, _) = SpentNullifier::address(
+        program_id,
+        CircuitId::Subscription,
+        &public_inputs.nullifier,
+        public_inputs.epoch,
+    );
+    let legacy_nullifier =
+        SpentNullifier::legacy_address(program_id, &public_inputs.nullifier, public_inputs.epoch);
+    let (marker, _) = pda::denied_recipient_address(program_id, &public_inputs.recipient_pubkey);
+    let (prepared, _) = pda::vkey_address(program_id, CircuitId::Subscription);
+    let layout = layout::VERIFY_SUBSCRIPTION;
+    Instruction::new_with_borsh(
+        *program_id,
+        &VerifierInstruction::VerifySubscription {
+            proof,
+            public_inputs,
+            dry_run: false,
+        },
+        layout.metas(&[
+            (layout.payer, *payer),
+            (layout.config, config),
+            (layout.nullifier, nullifier),
+            (layout.system_program, system_program::id()),
+            (layout.marker, marker),
+            (layout.legacy_nullifier, legacy_nullifier),
+            (layout.prepared_key, prepared),
+        ]),
+    )
+}
+
+/// `Deposit` into the shielded pool with its PDA derived
+pub fn deposit_instruction(
+    program_id: &Pubkey,
+    depositor: &Pubkey,
+    commitment: [u8; 32],
+    amount: u64,
+) -> Instruction {
+    let (pool, _) = pda::pool_address(program_id);
+    let layout = layout::DEPOSIT;
+    Instruction::new_with_borsh(
+        *program_id,
+        &VerifierInstruction::Deposit { commitment, amount },
+        layout.metas(&[
+            (layout.depositor, *depositor),
+            (layout.pool, pool),
+            (layout.system_program, system_program::id()),
+        ]),
+    )
+}
+
+/// `Withdraw` from the shielded pool with its pool, nullifier, denylist
+/// marker and prepared key PDAs derived
+pub fn withdraw_instruction(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    proof: Groth16Proof,
+    public_inputs: WithdrawPublicInputs,
+) -> Instruction {
+    let (pool, _) = pda::pool_address(program_id);
+    let (nullifier, _) =
+        SpentNullifier::address(program_id, CircuitId::Withdraw, &public_inputs.nullifier, 0);
+    let recipient = Pubkey::new_from_array(public_inputs.recipient);
+    let (marker, _) = pda::denied_recipient_address(program_id, &public_inputs.recipient);
+    let (prepared, _) = pda::vkey_address(program_id, CircuitId::Withdraw);
+    let layout = layout::WITHDRAW;
+    Instruction::new_with_borsh(
+        *program_id,
+        &VerifierInstruction::Withdraw {
+            proof,
+            public_inputs,
+        },
+        layout.metas(&[
+            (layout.payer, *payer),
+            (layout.pool, pool),
+            (layout.nullifier, nullifier),
+            (layout.recipient, recipient),
+            (layout.system_program, system_program::id()),
+            (layout.marker, marker),
+            (layout.prepared_key, prepared),
+        ]),
+    )
+}
+
+/// Token accounts settling both legs of a split payment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitSettlement {
+    /// Payer's token account, debited for both legs
+    pub source: Pubkey,
+    /// Token account of each recipient, in leg order
+    pub destinations: [Pubkey; 2],
+    pub mint: Pubkey,
+    /// Owner of the mint: `spl_token::id()` or `spl_token_2022::id()`
+    pub token_program: Pubkey,
+    /// Extra accounts of the mint's transfer hook, from
+    /// `resolve_transfer_hook_accounts`; empty for mints without one
+    pub hook_accounts: Vec<AccountMeta>,
+}
+
+/// `VerifySplitPayment`, settling both legs when `settlement` is given
+pub fn verify_split_payment_instruction(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    proof: Groth16Proof,
+    public_inputs: SplitPaymentPublicInputs,
+    settlement: Option<&SplitSettlement>,
+    dry_run: bool,
+) -> Instruction {
+    let (receipt, _) = pda::receipt_address(program_id, &proof.hash(&public_inputs));
+    let [first_marker, second_marker] = public_inputs
+        .recipients
+        .map(|recipient| pda::denied_recipient_address(program_id, &recipient).0);
+    let (prepared, _) = pda::vkey_address(program_id, CircuitId::SplitPayment);
+    let layout = layout::VERIFY_SPLIT_PAYMENT;
+    let mut keys = vec![
+        (layout.payer, *payer),
+        (layout.receipt, receipt),
+        (layout.system_program, system_program::id()),
+        (layout.first_marker, first_marker),
+        (layout.second_marker, second_marker),
+        (layout.prepared_key, prepared),
+    ];
+    if let Some(settlement) = settlement {
+        keys.extend([
+            (layout.source, settlement.source),
+            (layout.first_destination, settlement.destinations[0]),
+            (layout.second_destination, settlement.destinations[1]),
+            (layout.token_program, settlement.token_program),
+            (layout.mint, settlement.mint),
+        ]);
+    }
+    let mut accounts = layout.metas(&keys);
+    if let Some(settlement) = settlement {
+        accounts.extend(settlement.hook_accounts.iter().cloned());
+    }
+    Instruction::new_with_borsh(
+        *program_id,
+        &VerifierInstruction::VerifySplitPayment {
+            proof,
+            public_inputs,
+            settle: settlement.is_some(),
+            dry_run,
+        },
+        accounts,
+    )
+}
+
+/// Resolve the extra accounts a Token-2022 transfer hook needs to settle both
+/// legs of `public_inputs`
+///
+/// `fetch_account_data` returns an account's data, or `None` if it does not
+/// exist. Each leg is resolved as the `transfer_checked` the program makes,
+/// and the accounts are merged, writable if either leg writes them. Mints
+/// without a hook resolve to nothing.
+pub async fn resolve_transfer_hook_accounts<F, Fut>(
+    payer: &Pubkey,
+    settlement: &SplitSettlement,
+    public_inputs: &SplitPaymentPublicInputs,
+    decimals: u8,
+    fetch_account_data: F,
+) -> Result<Vec<AccountMeta>, ClientError>
+where
+    F: Fn(Pubkey) -> Fut,
+    Fut: Future<Output = Result<Option<Vec<u8>>, Box<dyn std::error::Error + Send + Sync>>>,
+{
+    let mut hook_accounts: Vec<AccountMeta> = Vec::new();
+    for leg in 0..2 {
+        let transfer =
+            spl_token_2022::offchain::create_transfer_checked_instruction_with_extra_metas(
+                &settlement.token_program,
+                &settlement.source,
+                &settlement.mint,
+                &settlement.destinations[leg],
+                payer,
+                &[],
+                public_inputs.min_amounts[leg],
+                decimals,
+                &fetch_account_data,
+            )
+            .await
+            .map_err(|e| ClientError::Rpc(e.to_string()))?;
+        // Source, mint, destination and authority come first
+        for meta in transfer.accounts.into_iter().skip(4) {
+            match hook_accounts.iter_mut().find(|known| known.pubkey == meta.pubkey) {
+                Some(known) => known.is_writable |= meta.is_writable,
+                None => hook_accounts.push(meta),
+            }
+        }
+    }
+    Ok(hook_accounts)
+}
+
+/// `VerifyWithInlineKey` with the key carried in the instruction
+pub fn verify_with_inline_key_instruction(
+    program_id: &Pubkey,
+    vk: InlineVerificationKey,
+    proof: Groth16Proof,
+    public_inputs: Vec<Be32>,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &VerifierInstruction::VerifyWithInlineKey {
+            key: InlineKeySource::Inline(vk),
+            proof,
+            public_inputs,
+        },
+        vec![],
+    )
+}
+
+/// `VerifyWithInlineKey` against `vk` as written to `key_account`, pinned by
+/// its hash
+pub fn verify_with_key_account_instruction(
+    program_id: &Pubkey,
+    key_account: &Pubkey,
+    vk: &InlineVerificationKey,
+    proof: Groth16Proof,
+    public_inputs: Vec<Be32>,
+) -> Instruction {
+    let layout = layout::VERIFY_WITH_INLINE_KEY;
+    Instruction::new_with_borsh(
+        *program_id,
+        &VerifierInstruction::VerifyWithInlineKey {
+            key: InlineKeySource::Account { key_hash: vk.hash() },
+            proof,
+            public_inputs,
+        },
+        layout.metas(&[(layout.key_account, *key_account)]),
+    )
+}
+
+/// `VerifyBatch` with its prepared key and denylist marker PDAs derived, the
+/// request put in canonical order first
+pub fn verify_batch_instruction(
+    program_id: &Pubkey,
+    mut request: BatchVerificationRequest,
+) -> Instruction {
+    request.canonicalize();
+    let accounts = verify_batch_accounts(program_id, &request);
+    Instruction::new_with_borsh(
+        *program_id,
+        &VerifierInstruction::VerifyBatch { request },
+        accounts,
+    )
+}
+
+/// Metas of `VerifyBatch`: the request's prepared key, then the statements'
+/// denylist markers
+fn verify_batch_accounts(
+    program_id: &Pubkey,
+    request: &BatchVerificationRequest,
+) -> Vec<AccountMeta> {
+    let (prepared, _) = pda::vkey_address(program_id, request.circuit_id);
+    let layout = layout::VERIFY_BATCH;
+    let mut accounts = layout.metas(&[(layout.prepared_key, prepared)]);
+    accounts.extend(recipient_marker_metas(program_id, &request.public_inputs));
+    accounts
+}
+
+/// `VerifyBatchWithReceipt` with its batch receipt, prepared key and
+/// denylist marker PDAs derived, the request put in canonical order first
+///
+/// The same proofs in any order make the same instruction, so relayers
+/// submitting one set land on one batch receipt.
+pub fn verify_batch_with_receipt_instruction(
+    program_id: &Pubkey,
+    relayer: &Pubkey,
+    mut request: BatchVerificationRequest,
+) -> Instruction {
+    request.canonicalize();
+    let transcript_hash = batch_transcript_hash(&request.public_inputs);
+    let (receipt, _) = pda::batch_receipt_address(program_id, &transcript_hash);
+    let (prepared, _) = pda::vkey_address(program_id, request.circuit_id);
+    let layout = layout::VERIFY_BATCH_WITH_RECEIPT;
+    let mut accounts = layout.metas(&[
+        (layout.relayer, *relayer),
+        (layout.receipt, receipt),
+        (layout.system_program, system_program::id()),
+        (layout.prepared_key, prepared),
+    ]);
+    accounts.extend(recipient_marker_metas(program_id, &request.public_inputs));
+    Instruction::new_with_borsh(
+        *program_id,
+        &VerifierInstruction::VerifyBatchWithReceipt { request },
+        accounts,
+    )
+}
+
+/// Everything `VerifyBatchInclusion` needs to show one proof is in a batch
+/// recorded by `VerifyBatchWithReceipt`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchInclusionProof {
+    /// Seed of the batch receipt PDA
+    pub transcript_hash: [u8; 32],
+    pub proof_hash: [u8; 32],
+    pub index: u8,
+    /// Merkle path from the proof's leaf to the receipt root
+    pub siblings: Vec<[u8; 32]>,
+}
+
+/// Inclusion proof for entry `index` of a batch submitted with
+/// `VerifyBatchWithReceipt`; `None` when `index` is out of range
+///
+/// `index` counts in the canonical order the batch was submitted in, which is
+/// the order of `request` once canonicalized.
+pub fn batch_inclusion_proof(
+    request: &BatchVerificationRequest,
+    index: usize,
+) -> Option<BatchInclusionProof> {
+    let mut request = request.clone();
+    request.canonicalize();
+    let proof_hashes: Vec<[u8; 32]> = request
+        .proofs
+        .iter()
+        .zip(&request.public_inputs)
+        .map(|(proof, inputs)| proof.hash(inputs))
+        .collect();
+    let siblings = batch_receipt::inclusion_proof(&proof_hashes, index)?;
+    Some(BatchInclusionProof {
+        transcript_hash: batch_transcript_hash(&request.public_inputs),
+        proof_hash: proof_hashes[index],
+        index: u8::try_from(index).ok()?,
+        siblings,
+    })
+}
+
+/// `VerifyBatchInclusion` for `inclusion` with its batch receipt PDA derived
+pub fn verify_batch_inclusion_instruction(
+    program_id: &Pubkey,
+    inclusion: BatchInclusionProof,
+) -> Instruction {
+    let (receipt, _) = pda::batch_receipt_address(program_id, &inclusion.transcript_hash);
+    let layout = layout::VERIFY_BATCH_INCLUSION;
+    Instruction::new_with_borsh(
+        *program_id,
+        &VerifierInstruction::VerifyBatchInclusion {
+            proof_hash: inclusion.proof_hash,
+            index: inclusion.index,
+            siblings: inclusion.siblings,
+        },
+        layout.metas(&[(layout.receipt, receipt)]),
+    )
+}
+
+/// Metas of the denylist marker of each statement's recipient, in order, as
+/// the batch instructions take them after the prepared key
+fn recipient_marker_metas<'a>(
+    program_id: &'a Pubkey,
+    public_inputs: &'a [PaymentPublicInputs],
+) -> impl Iterator<Item = AccountMeta> + 'a {
+    public_inputs.iter().map(move |inputs| {
+        let (marker, _) = pda::denied_recipient_address(program_id, &inputs.recipient_pubkey);
+        AccountMeta::new_readonly(marker, false)
+    })
+}
+
+/// `ConsumeReceipt` for the receipt of `receipt_proof_hash`, signed by
+/// `consumer`, its recipient or delegate
+pub fn consume_receipt_instruction(
+    program_id: &Pubkey,
+    consumer: &Pubkey,
+    receipt_proof_hash: [u8; 32],
+) -> Instruction {
+    let (receipt, _) = pda::receipt_address(program_id, &receipt_proof_hash);
+    let layout = layout::CONSUME_RECEIPT;
+    Instruction::new_with_borsh(
+        *program_id,
+        &VerifierInstruction::ConsumeReceipt { receipt_proof_hash },
+        layout.metas(&[(layout.consumer, *consumer), (layout.receipt, receipt)]),
+    )
+}
+
+/// `ClaimRelayerRebate` paying `relayer` `amount` lamports out of
+/// `merchant`'s rebate deposit for the receipt of `receipt_proof_hash`
+pub fn claim_relayer_rebate_instruction(
+    program_id: &Pubkey,
+    merchant: &Pubkey,
+    relayer: &Pubkey,
+    receipt_proof_hash: [u8; 32],
+    amount: u64,
+) -> Instruction {
+    let (deposit, _) = pda::rebate_deposit_address(program_id, merchant);
+    let (receipt, _) = pda::receipt_address(program_id, &receipt_proof_hash);
+    let layout = layout::CLAIM_RELAYER_REBATE;
+    Instruction::new_with_borsh(
+        *program_id,
+        &VerifierInstruction::ClaimRelayerRebate {
+            receipt_proof_hash,
+            amount,
+        },
+        layout.metas(&[
+            (layout.merchant, *merchant),
+            (layout.deposit, deposit),
+            (layout.relayer, *relayer),
+            (layout.receipt, receipt),
+            (layout.system_program, system_program::id()),
+        ]),
+    )
+}
+
+/// `FinalizeReceipt` for the receipt of `receipt_proof_hash`, paid by
+/// `payer` should the receipt need to grow
+pub fn finalize_receipt_instruction(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    receipt_proof_hash: [u8; 32],
+) -> Instruction {
+    let (config, _) = pda::config_address(program_id);
+    let (receipt, _) = pda::receipt_address(program_id, &receipt_proof_hash);
+    let layout = layout::FINALIZE_RECEIPT;
+    Instruction::new_with_borsh(
+        *program_id,
+        &VerifierInstruction::FinalizeReceipt { receipt_proof_hash },
+        layout.metas(&[
+            (layout.caller, *payer),
+            (layout.config, config),
+            (layout.receipt, receipt),
+            (layout.system_program, system_program::id()),
+        ]),
+    )
+}
+
+/// `GetReceiptStatus` for the receipt of `receipt_proof_hash`, whose
+/// `cpi::ReceiptStatus` a simulation returns
+pub fn get_receipt_status_instruction(
+    program_id: &Pubkey,
+    receipt_proof_hash: [u8; 32],
+    allow_missing: bool,
+) -> Instruction {
+    let (receipt, _) = pda::receipt_address(program_id, &receipt_proof_hash);
+    let layout = layout::GET_RECEIPT_STATUS;
+    Instruction::new_with_borsh(
+        *program_id,
+        &VerifierInstruction::GetReceiptStatus {
+            receipt_proof_hash,
+            allow_missing,
+        },
+        layout.metas(&[(layout.receipt, receipt)]),
+    )
+}
+
+/// `AbortBuffer` for the batch buffer of `authority` at `nonce`
+pub fn abort_buffer_instruction(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    nonce: u64,
+) -> Instruction {
+    let (buffer, _) = pda::batch_buffer_address(program_id, authority, nonce);
+    let layout = layout::ABORT_BUFFER;
+    Instruction::new_with_borsh(
+        *program_id,
+        &VerifierInstruction::AbortBuffer { nonce },
+        layout.metas(&[(layout.authority, *authority), (layout.buffer, buffer)]),
+    )
+}
+
+/// `ReapStale` for the batch buffer of `authority` at `nonce`, paying the
+/// bounty to `reaper`
+pub fn reap_stale_instruction(
+    program_id: &Pubkey,
+    reaper: &Pubkey,
+    authority: &Pubkey,
+    nonce: u64,
+) -> Instruction {
+    let (buffer, _) = pda::batch_buffer_address(program_id, authority, nonce);
+    let (config, _) = pda::config_address(program_id);
+    let layout = layout::REAP_STALE;
+    Instruction::new_with_borsh(
+        *program_id,
+        &VerifierInstruction::ReapStale { nonce },
+        layout.metas(&[
+            (layout.reaper, *reaper),
+            (layout.buffer, buffer),
+            (layout.authority, *authority),
+            (layout.config, config),
+        ]),
+    )
+}
+
+/// `MigrateReceipt` for the receipt of `receipt_proof_hash`, paid by
+/// `caller` and reimbursed from the config
+pub fn migrate_receipt_instruction(
+    program_id: &Pubkey,
+    caller: &Pubkey,
+    receipt_proof_hash: [u8; 32],
+) -> Instruction {
+    let (config, _) = pda::config_address(program_id);
+    let (receipt, _) = pda::receipt_address(program_id, &receipt_proof_hash);
+    let layout = layout::MIGRATE_RECEIPT;
+    Instruction::new_with_borsh(
+        *program_id,
+        &VerifierInstruction::MigrateReceipt { receipt_proof_hash },
+        layout.metas(&[
+            (layout.caller, *caller),
+            (layout.config, config),
+            (layout.receipt, receipt),
+            (layout.system_program, system_program::id()),
+        ]),
+    )
+}
+
+/// `VerifyWithOpening` with its config, receipt, denylist marker and prepared
+/// key PDAs derived; `opening` reveals the amount, `None` keeps it hidden
+pub fn verify_with_opening_instruction(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    proof: Groth16Proof,
+    public_inputs: CommittedPaymentPublicInputs,
+    opening: Option<AmountOpening>,
+) -> Instruction {
+    let (config, _) = pda::config_address(program_id);
+    let (receipt, _) = pda::receipt_address(program_id, &proof.hash(&public_inputs));
+    let (marker, _) = pda::denied_recipient_address(program_id, &public_inputs.recipient_pubkey);
+    let (prepared, _) = pda::vkey_address(program_id, CircuitId::CommittedPayment);
+    let layout = layout::VERIFY_WITH_OPENING;
+    Instruction::new_with_borsh(
+        *program_id,
+        &VerifierInstruction::VerifyWithOpening {
+            proof,
+            public_inputs,
+            opening,
+        },
+        layout.metas(&[
+            (layout.payer, *payer),
+            (layout.config, config),
+            (layout.receipt, receipt),
+            (layout.system_program, system_program::id()),
+            (layout.marker, marker),
+            (layout.prepared_key, prepared),
+        ]),
+    )
+}
+
+/// `instruction`, one of the admin instructions, signed by `admin` with the
+/// config and the PDA it names derived and `co_signers` after its own
+/// accounts
+///
+/// The system program is passed wherever the instruction may create or grow
+/// an account. Panics on any other instruction, and on co-signers for an
+/// instruction that takes none.
+pub fn admin_instruction(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    instruction: VerifierInstruction,
+    co_signers: &[Pubkey],
+) -> Instruction {
+    use VerifierInstruction::*;
+    let (config, _) = pda::config_address(program_id);
+    let system = system_program::id();
+    let mut accounts = match &instruction {
+        InitializeConfig { .. } => {
+            let layout = layout::INITIALIZE_CONFIG;
+            layout.metas(&[
+                (layout.admin, *admin),
+                (layout.config, config),
+                (layout.system_program, system),
+            ])
+        }
+        SetRecipientRoot { .. }
+        | SetEpochLength { .. }
+        | SetInputPolicy { .. }
+        | SetMaxReceiptAge { .. }
+        | ProposeAdmin { .. }
+        | CancelProposedAdmin => {
+            let layout = layout::ADMIN_CONFIG;
+            layout.metas(&[(layout.admin, *admin), (layout.config, config)])
+        }
+        AcceptAdmin => {
+            let layout = layout::ACCEPT_ADMIN;
+            layout.metas(&[(layout.pending_admin, *admin), (layout.config, config)])
+        }
+        SetAuditEnabled { .. }
+        | SetMaxKeyDeprecation { .. }
+        | SetInvoiceOverpayment { .. }
+        | SetRateLimit { .. }
+        | SetFinalitySlots { .. }
+        | SetScratchTtl { .. }
+        | SetTrackRelayer { .. } => {
+            let layout = layout::ADMIN_CONFIG_GROWING;
+            layout.metas(&[
+                (layout.admin, *admin),
+                (layout.config, config),
+                (layout.system_program, system),
+            ])
+        }
+        SetAdminSigners { .. } => {
+            let layout = layout::SET_ADMIN_SIGNERS;
+            layout.metas(&[
+                (layout.admin, *admin),
+                (layout.config, config),
+                (layout.system_program, system),
+            ])
+        }
+        PrepareVerificationKey { circuit, .. } => {
+            let (prepared, _) = pda::vkey_address(program_id, *circuit);
+            let layout = layout::PREPARE_VERIFICATION_KEY;
+            layout.metas(&[
+                (layout.admin, *admin),
+                (layout.config, config),
+                (layout.prepared_key, prepared),
+                (layout.system_program, system),
+            ])
+        }
+        DeprecatePreviousKey { circuit, .. }
+        | SetHashBackend { circuit, .. }
+        | RevokeVerificationKey { circuit } => {
+            let (prepared, _) = pda::vkey_address(program_id, *circuit);
+            let layout = layout::ADMIN_PREPARED_KEY;
+            let mut accounts = layout.metas(&[
+                (layout.admin, *admin),
+                (layout.config, config),
+                (layout.prepared_key, prepared),
+            ]);
+            // Grows a prepared key written before version 4
+            accounts.push(AccountMeta::new_readonly(system, false));
+            accounts
+        }
+        InitializeAuditLog => {
+            let (log, _) = pda::audit_log_address(program_id);
+            let layout = layout::INITIALIZE_AUDIT_LOG;
+            layout.metas(&[
+                (layout.admin, *admin),
+                (layout.config, config),
+                (layout.audit_log, log),
+                (layout.system_program, system),
+            ])
+        }
+        InitializePool { .. } => {
+            let (pool, _) = pda::pool_address(program_id);
+            let layout = layout::INITIALIZE_POOL;
+            layout.metas(&[
+                (layout.admin, *admin),
+                (layout.config, config),
+                (layout.pool, pool),
+                (layout.system_program, system),
+            ])
+        }
+        InitializeRelayerList | AddRelayer { .. } => {
+            let (list, _) = pda::relayer_list_address(program_id);
+            let layout = layout::RELAYER_LIST;
+            layout.metas(&[
+                (layout.admin, *admin),
+                (layout.config, config),
+                (layout.relayer_list, list),
+                (layout.system_program, system),
+            ])
+        }
+        RemoveRelayer { .. } => {
+            let (list, _) = pda::relayer_list_address(program_id);
+            let layout = layout::REMOVE_RELAYER;
+            layout.metas(&[
+                (layout.admin, *admin),
+                (layout.config, config),
+                (layout.relayer_list, list),
+            ])
+        }
+        DenyRecipient { recipient } => {
+            let (marker, _) = pda::denied_recipient_address(program_id, recipient);
+            let layout = layout::DENY_RECIPIENT;
+            layout.metas(&[
+                (layout.admin, *admin),
+                (layout.config, config),
+                (layout.marker, marker),
+                (layout.system_program, system),
+            ])
+        }
+        AllowRecipient { recipient } => {
+            let (marker, _) = pda::denied_recipient_address(program_id, recipient);
+            let layout = layout::ALLOW_RECIPIENT;
+            layout.metas(&[
+                (layout.admin, *admin),
+                (layout.config, config),
+                (layout.marker, marker),
+            ])
+        }
+        _ => panic!("not an admin instruction"),
+    };
+    let takes_co_signers = matches!(
+        instruction,
+        ProposeAdmin { .. }
+            | SetAdminSigners { .. }
+            | PrepareVerificationKey { .. }
+            | DeprecatePreviousKey { .. }
+            | SetHashBackend { .. }
+    );
+    assert!(
+        takes_co_signers || co_signers.is_empty(),
+        "instruction takes no co-signers"
+    );
+    for co_signer in co_signers {
+        accounts.push(AccountMeta::new_readonly(*co_signer, true));
+    }
+    Instruction::new_with_borsh(*program_id, &instruction, accounts)
+}
+
+/// Run the program's pairing check for `circuit` locally
+///
+/// Covers the proof alone; statement checks that need accounts or the clock
+/// are `preflight`'s job.
+pub fn verify_offchain(
+    circuit: CircuitId,
+    proof: &Groth16Proof,
+    scalars: &[u64],
+) -> Result<(), ProgramError> {
+    verify_with_key(circuit.verification_key(), proof, scalars)
+}
+
+/// `verify_offchain` against a key supplied at runtime, e.g. from
+/// `snarkjs::parse_verification_key`
+pub fn verify_with_key(
+    vk: &VerificationKey,
+    proof: &Groth16Proof,
+    scalars: &[u64],
+) -> Result<(), ProgramError> {
+    verify_groth16(vk, proof, scalars)
+}
+
+/// Largest serialized transaction the cluster accepts
+pub const MAX_TRANSACTION_SIZE: usize = 1232;
+
+/// Knobs for `plan_batches`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchPlanOptions {
+    pub program_id: Pubkey,
+    /// Fee payer and signer of every planned transaction
+    pub authority: Pubkey,
+    pub max_transaction_size: usize,
+    pub compute_unit_limit: u64,
+    /// Estimated cost of a batch regardless of its size (the pairing)
+    pub base_compute_units: u64,
+    /// Estimated cost of each proof in a batch (aggregation and IC terms)
+    pub compute_units_per_proof: u64,
+    /// Nonce of the first buffer PDA; each buffered plan takes the next one
+    pub first_buffer_nonce: u64,
+}
+
+impl BatchPlanOptions {
+    /// Cluster limits and rough compute estimates; calibrate the estimates
+    /// against a deployed build before relying on them near the limit
+    pub fn new(program_id: Pubkey, authority: Pubkey) -> Self {
+        Self {
+            program_id,
+            authority,
+            max_transaction_size: MAX_TRANSACTION_SIZE,
+            compute_unit_limit: 1_400_000,
+            base_compute_units: 120_000,
+            compute_units_per_proof: 40_000,
+            first_buffer_nonce: 0,
+        }
+    }
+
+    fn estimated_compute_units(&self, num_proofs: usize) -> u64 {
+        self.base_compute_units + self.compute_units_per_proof * num_proofs as u64
+    }
+}
+
+/// How a planned batch reaches the program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchStrategy {
+    /// One `VerifyBatch` transaction carrying the proofs as instruction data
+    Inline,
+    /// `WriteBatchBuffer` chunks followed by `VerifyBatchBuffered`
+    Buffered { buffer: Pubkey, nonce: u64 },
+}
+
+/// One batch of a plan and the instructions that verify it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchPlan {
+    /// Indices of the batch's proofs in the planned slice; the request
+    /// carries them in canonical order
+    pub proofs: Range<usize>,
+    pub strategy: BatchStrategy,
+    /// Send each in its own transaction, in order
+    pub instructions: Vec<Instruction>,
+}
+
+/// Split pending proofs into batches the program can verify
+///
+/// Proofs are packed greedily, in order, up to `MAX_BATCH_SIZE` per batch and
+/// under the compute estimate; each batch is then sorted into the canonical
+/// order the program requires. Batches are sent inline and also bounded by
+/// the transaction size, unless a single proof does not fit inline, in which
+/// case the batch is uploaded through a buffer PDA instead.
+///
+/// # Panics
+///
+/// If `max_transaction_size` cannot hold even a one-byte buffer write.
+pub fn plan_batches(
+    proofs: &[(Groth16Proof, PaymentPublicInputs)],
+    opts: BatchPlanOptions,
+) -> Vec<BatchPlan> {
+    plan_batches_metered(proofs, opts, &NoopMetrics)
+}
+
+/// `plan_batches`, counting each planned batch and its proofs
+#[cfg(feature = "metrics")]
+pub fn plan_batches_with_metrics(
+    proofs: &[(Groth16Proof, PaymentPublicInputs)],
+    opts: BatchPlanOptions,
+    metrics: &impl VerifierMetrics,
+) -> Vec<BatchPlan> {
+    plan_batches_metered(proofs, opts, metrics)
+}
+
+fn plan_batches_metered(
+    proofs: &[(Groth16Proof, PaymentPublicInputs)],
+    opts: BatchPlanOptions,
+    metrics: &impl VerifierMetrics,
+) -> Vec<BatchPlan> {
+    let mut plans = Vec::new();
+    let mut nonce = opts.first_buffer_nonce;
+    let mut start = 0;
+
+    while start < proofs.len() {
+        let inline =
+            inline_transaction_size(&proofs[start..start + 1], &opts) <= opts.max_transaction_size;
+
+        let mut end = start + 1;
+        while end < proofs.len() {
+            let extended = end + 1 - start;
+            if extended > MAX_BATCH_SIZE
+                || opts.estimated_compute_units(extended) > opts.compute_unit_limit
+                || (inline
+                    && inline_transaction_size(&proofs[start..=end], &opts)
+                        > opts.max_transaction_size)
+            {
+                break;
+            }
+            end += 1;
+        }
+
+        let request = batch_request(&proofs[start..end]);
+        let plan = if inline {
+            metrics.batch_planned("VerifyBatch", end - start);
+            BatchPlan {
+                proofs: start..end,
+                strategy: BatchStrategy::Inline,
+                instructions: vec![verify_batch_instruction(&opts.program_id, request)],
+            }
+        } else {
+            metrics.batch_planned("VerifyBatchBuffered", end - start);
+            let plan = buffered_plan(start..end, &request, nonce, &opts);
+            nonce += 1;
+            plan
+        };
+        plans.push(plan);
+        start = end;
+    }
+
+    plans
+}
+
+fn batch_request(proofs: &[(Groth16Proof, PaymentPublicInputs)]) -> BatchVerificationRequest {
+    let mut request = BatchVerificationRequest {
+        proofs: proofs.iter().map(|(proof, _)| proof.clone()).collect(),
+        public_inputs: proofs.iter().map(|(_, inputs)| inputs.clone()).collect(),
+        allow_duplicates: false,
+        circuit_id: CircuitId::Payment,
+    };
+    request.canonicalize();
+    request
+}
+
+/// Size of the `VerifyBatch` transaction for `proofs`, from the encoded
+/// length alone
+///
+/// A proof to a recipient not yet in the batch adds its marker's key, so the
+/// size depends on the proofs and not only on their number.
+fn inline_transaction_size(
+    proofs: &[(Groth16Proof, PaymentPublicInputs)],
+    opts: &BatchPlanOptions,
+) -> usize {
+    let request = batch_request(proofs);
+    let empty = Instruction::new_with_bytes(
+        opts.program_id,
+        &[],
+        verify_batch_accounts(&opts.program_id, &request),
+    );
+    // The variant tag, then the request
+    let data_len = 1 + BatchVerificationRequest::len_for(proofs.len());
+    transaction_size_with_data(&empty, &opts.authority, data_len)
+}
+
+fn buffered_plan(
+    proofs: Range<usize>,
+    request: &BatchVerificationRequest,
+    nonce: u64,
+    opts: &BatchPlanOptions,
+) -> BatchPlan {
+    let (buffer, _) = pda::batch_buffer_address(&opts.program_id, &opts.authority, nonce);
+    let total_len = request.serialized_len();
+    let layout = layout::WRITE_BATCH_BUFFER;
+    let write = |offset: usize, data: &[u8]| {
+        Instruction::new_with_borsh(
+            opts.program_id,
+            &VerifierInstruction::WriteBatchBuffer {
+                nonce,
+                total_len: total_len as u32,
+                offset: offset as u32,
+                data: data.to_vec(),
+            },
+            layout.metas(&[
+                (layout.authority, opts.authority),
+                (layout.buffer, buffer),
+                (layout.system_program, system_program::id()),
+            ]),
+        )
+    };
+
+    // Longest chunk whose write fits; the data's length prefix grows with it
+    let empty = write(0, &[]);
+    let overhead = transaction_size(&empty, &opts.authority);
+    let chunk_len = (1..=opts.max_transaction_size.saturating_sub(overhead))
+        .rev()
+        .find(|len| {
+            let size = transaction_size_with_data(&empty, &opts.authority, empty.data.len() + len);
+            size <= opts.max_transaction_size
+        })
+        .expect("transaction size too small for a buffer write");
+
+    let mut instructions: Vec<Instruction> = request
+        .try_to_vec()
+        .unwrap()
+        .chunks(chunk_len)
+        .enumerate()
+        .map(|(i, chunk)| write(i * chunk_len, chunk))
+        .collect();
+    let (prepared, _) = pda::vkey_address(&opts.program_id, request.circuit_id);
+    let layout = layout::VERIFY_BATCH_BUFFERED;
+    let mut accounts = layout.metas(&[
+        (layout.authority, opts.authority),
+        (layout.buffer, buffer),
+        (layout.prepared_key, prepared),
+    ]);
+    accounts.extend(recipient_marker_metas(&opts.program_id, &request.public_inputs));
+    instructions.push(Instruction::new_with_borsh(
+        opts.program_id,
+        &VerifierInstruction::VerifyBatchBuffered { nonce },
+        accounts,
+    ));
+
+    BatchPlan {
+        proofs,
+        strategy: BatchStrategy::Buffered { buffer, nonce },
+        instructions,
+    }
+}
+
+/// What to change about a transaction too large to send
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SizeSuggestion {
+    /// Upload the batch through a buffer PDA, as `plan_batches` does for a
+    /// batch that outgrows one transaction
+    BufferedBatch,
+    /// Send the other instructions, such as settlement transfers or a second
+    /// verification, in transactions of their own
+    SeparateInstructions,
+}
+
+impl fmt::Display for SizeSuggestion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            SizeSuggestion::BufferedBatch => "upload the batch through a buffer PDA instead",
+            SizeSuggestion::SeparateInstructions => "send the other instructions separately",
+        })
+    }
+}
+
+/// Serialized size of a transaction holding `instructions`, paid by `payer`
+///
+/// Exact: the signature count comes from the signers the instructions' account
+/// metas name, and the message is serialized as the cluster receives it.
+pub fn serialized_transaction_size(instructions: &[Instruction], payer: &Pubkey) -> usize {
+    let message = Message::new(instructions, Some(payer));
+    let signatures = message.header.num_required_signatures as usize;
+    // Compact-u16 signature count, then the signatures
+    short_vec_len(signatures) + 64 * signatures + message.serialize().len()
+}
+
+/// Check that a transaction holding `instructions`, paid by `payer`, fits
+/// `MAX_TRANSACTION_SIZE`, returning its size
+///
+/// The instruction builders cannot know what else a transaction will carry,
+/// so call this on the final list. A transaction that does not fit fails with
+/// `TransactionTooLarge` rather than with the RPC node's generic error; the
+/// suggestion is a buffered upload when one of `instructions` is a
+/// `VerifyBatch`, and otherwise to split the instructions up.
+pub fn check_transaction_size(
+    instructions: &[Instruction],
+    payer: &Pubkey,
+) -> Result<usize, ClientError> {
+    let needed = serialized_transaction_size(instructions, payer);
+    if needed <= MAX_TRANSACTION_SIZE {
+        return Ok(needed);
+    }
+    let batched = instructions.iter().any(|instruction| {
+        matches!(
+            VerifierInstruction::try_from_slice(&instruction.data),
+            Ok(VerifierInstruction::VerifyBatch { .. })
+        )
+    });
+    Err(ClientError::TransactionTooLarge {
+        needed,
+        limit: MAX_TRANSACTION_SIZE,
+        suggestion: if batched {
+            SizeSuggestion::BufferedBatch
+        } else {
+            SizeSuggestion::SeparateInstructions
+        },
+    })
+}
+
+/// Serialized size of a transaction holding only `instruction`, paid by `payer`
+fn transaction_size(instruction: &Instruction, payer: &Pubkey) -> usize {
+    serialized_transaction_size(&[instruction.clone()], payer)
+}
+
+/// `transaction_size` with the data of `instruction` replaced by `data_len`
+/// bytes
+fn transaction_size_with_data(instruction: &Instruction, payer: &Pubkey, data_len: usize) -> usize {
+    let data = instruction.data.len();
+    let without_data = transaction_size(instruction, payer) - short_vec_len(data) - data;
+    without_data + short_vec_len(data_len) + data_len
+}
+
+/// Bytes of the compact-u16 length prefix of a `len`-element vector
+fn short_vec_len(len: usize) -> usize {
+    match len {
+        0..=0x7f => 1,
+        0x80..=0x3fff => 2,
+        _ => 3,
+    }
+}
+
+/// An account as returned by an RPC node
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchedAccount {
+    pub owner: Pubkey,
+    pub lamports: u64,
+    pub data: Vec<u8>,
+}
+
+/// The reads `preflight` needs from a cluster
+///
+/// Kept this small so an RPC client, a test harness or a cache can stand in.
+pub trait AccountFetcher {
+    /// Fetch an account, `None` if it does not exist
+    fn get_account(
+        &self,
+        address: &Pubkey,
+    ) -> impl Future<Output = Result<Option<FetchedAccount>, ClientError>>;
+
+    /// Fetch the cluster clock
+    fn get_clock(&self) -> impl Future<Output = Result<Clock, ClientError>>;
+}
+
+/// A check `VerifySubscription` makes before accepting a proof
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreflightCheck {
+    /// The cluster has activated the alt_bn128 syscalls
+    Bn128Syscalls,
+    /// The compiled key matches the circuit's signal layout
+    SignalLayout,
+    /// The prepared key PDA is current and its key not revoked
+    PreparedKey,
+    /// The nullifier PDA has not been created for this epoch
+    NullifierUnspent,
+    /// The config PDA exists and is owned by the program
+    Config,
+    /// The proof's epoch is the current or the previous one
+    Epoch,
+    /// The recipient has no denylist marker
+    RecipientAllowed,
+    /// The pairing check passes
+    Proof,
+}
+
+/// What a check came to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreflightOutcome {
+    Passed,
+    /// Failed with the error the program would return
+    Failed(ProgramError),
+    /// Not run because a check it depends on failed
+    Skipped,
+}
+
+/// Every check `VerifySubscription` would make, in the order it makes them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreflightReport {
+    pub checks: Vec<(PreflightCheck, PreflightOutcome)>,
+}
+
+impl PreflightReport {
+    /// Whether the instruction would get through every check
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|(_, outcome)| *outcome == PreflightOutcome::Passed)
+    }
+
+    /// The failure the program would actually report: the first one it reaches
+    pub fn first_failure(&self) -> Option<(PreflightCheck, &ProgramError)> {
+        self.checks.iter().find_map(|(check, outcome)| match outcome {
+            PreflightOutcome::Failed(error) => Some((*check, error)),
+            _ => None,
+        })
+    }
+
+    /// Outcome of one check
+    pub fn outcome(&self, check: PreflightCheck) -> Option<&PreflightOutcome> {
+        self.checks
+            .iter()
+            .find(|(c, _)| *c == check)
+            .map(|(_, outcome)| outcome)
+    }
+}
+
+/// Run `VerifySubscription`'s checks off-chain against current cluster state
+///
+/// Fetches the alt_bn128 feature gate, the config, nullifier and denylist
+/// marker PDAs and the clock, then applies the same code the processor runs,
+/// so a relayer learns about a cluster without the curve syscalls, a spent
+/// nullifier, a stale epoch or a denied recipient before paying for a
+/// transaction. Every independent check runs even after an earlier one fails;
+/// `first_failure` is what the program would return.
+pub async fn preflight(
+    rpc: &impl AccountFetcher,
+    program_id: &Pubkey,
+    proof: &Groth16Proof,
+    public_inputs: &SubscriptionPublicInputs,
+) -> Result<PreflightReport, ClientError> {
+    preflight_metered(rpc, program_id, proof, public_inputs, &NoopMetrics).await
+}
+
+/// `preflight`, counting the failure the program would report by its code
+#[cfg(feature = "metrics")]
+pub async fn preflight_with_metrics(
+    rpc: &impl AccountFetcher,
+    program_id: &Pubkey,
+    proof: &Groth16Proof,
+    public_inputs: &SubscriptionPublicInputs,
+    metrics: &impl VerifierMetrics,
+) -> Result<PreflightReport, ClientError> {
+    preflight_metered(rpc, program_id, proof, public_inputs, metrics).await
+}
+
+async fn preflight_metered(
+    rpc: &impl AccountFetcher,
+    program_id: &Pubkey,
+    proof: &Groth16Proof,
+    public_inputs: &SubscriptionPublicInputs,
+    metrics: &impl VerifierMetrics,
+) -> Result<PreflightReport, ClientError> {
+    let mut checks = Vec::with_capacity(8);
+    let outcome = |result: Result<(), ProgramError>| match result {
+        Ok(()) => PreflightOutcome::Passed,
+        Err(error) => PreflightOutcome::Failed(error),
+    };
+
+    checks.push((
+        PreflightCheck::Bn128Syscalls,
+        outcome(if bn128_syscalls_enabled(rpc).await? {
+            Ok(())
+        } else {
+            Err(VerifierError::SyscallUnavailable.into())
+        }),
+    ));
+
+    let vk = CircuitId::Subscription.verification_key();
+    checks.push((
+        PreflightCheck::SignalLayout,
+        outcome(
+            vk.check_signal_layout(SubscriptionPublicInputs::SIGNAL_LAYOUT)
+                .map_err(Into::into),
+        ),
+    ));
+
+    let (prepared_address, _) = pda::vkey_address(program_id, CircuitId::Subscription);
+    let prepared = rpc.get_account(&prepared_address).await?;
+    checks.push((
+        PreflightCheck::PreparedKey,
+        outcome(with_account_info(prepared, &prepared_address, |info| {
+            PreparedVk::load_active(info, program_id, CircuitId::Subscription).map(|_| ())
+        })),
+    ));
+
+    let (nullifier_address, _) = SpentNullifier::address(
+        program_id,
+        CircuitId::Subscription,
+        &public_inputs.nullifier,
+        public_inputs.epoch,
+    );
+    let legacy_address =
+        SpentNullifier::legacy_address(program_id, &public_inputs.nullifier, public_inputs.epoch);
+    let mut spent = false;
+    for address in [nullifier_address, legacy_address] {
+        spent |= rpc
+            .get_account(&address)
+            .await?
+            .is_some_and(|account| !account.data.is_empty());
+    }
+    checks.push((
+        PreflightCheck::NullifierUnspent,
+        outcome(if spent {
+            Err(VerifierError::NullifierAlreadySpent.into())
+        } else {
+            Ok(())
+        }),
+    ));
+
+    let (config_address, _) = pda::config_address(program_id);
+    let config = load_config(rpc.get_account(&config_address).await?, &config_address, program_id);
+    let clock = rpc.get_clock().await?;
+    match config {
+        Ok(config) => {
+            checks.push((PreflightCheck::Config, PreflightOutcome::Passed));
+            checks.push((
+                PreflightCheck::Epoch,
+                outcome(
+                    config
+                        .check_epoch(public_inputs.epoch, clock.unix_timestamp)
+                        .map_err(Into::into),
+                ),
+            ));
+        }
+        Err(error) => {
+            checks.push((PreflightCheck::Config, PreflightOutcome::Failed(error)));
+            checks.push((PreflightCheck::Epoch, PreflightOutcome::Skipped));
+        }
+    }
+
+    let (marker_address, _) =
+        pda::denied_recipient_address(program_id, &public_inputs.recipient_pubkey);
+    let marker = rpc.get_account(&marker_address).await?;
+    checks.push((
+        PreflightCheck::RecipientAllowed,
+        outcome(with_account_info(marker, &marker_address, |info| {
+            DeniedRecipient::check(info, program_id, &public_inputs.recipient_pubkey)
+        })),
+    ));
+
+    checks.push((
+        PreflightCheck::Proof,
+        outcome(verify_groth16(vk, proof, &public_inputs.to_scalars())),
+    ));
+
+    let report = PreflightReport { checks };
+    if let Some((_, error)) = report.first_failure() {
+        metrics.failure("VerifySubscription", error_code(error));
+    }
+    Ok(report)
+}
+
+/// Whether the cluster behind `rpc` has activated the alt_bn128 syscalls
+///
+/// Reads the feature gate's account; the program probes the syscalls
+/// themselves and fails with `SyscallUnavailable` where this is false.
+pub async fn bn128_syscalls_enabled(rpc: &impl AccountFetcher) -> Result<bool, ClientError> {
+    let account = rpc.get_account(&ALT_BN128_FEATURE_ID).await?;
+    Ok(feature_active(account))
+}
+
+/// Hash backend the prepared key of `circuit` selects, read as the program
+/// reads it; keccak256 while the key is not prepared
+///
+/// Relayers pass it to `batch_coefficients_with`.
+pub async fn hash_backend(
+    rpc: &impl AccountFetcher,
+    program_id: &Pubkey,
+    circuit: CircuitId,
+) -> Result<HashBackend, ClientError> {
+    let (address, _) = pda::vkey_address(program_id, circuit);
+    let account = rpc.get_account(&address).await?;
+    with_account_info(account, &address, |info| {
+        PreparedVk::hash_backend_of(Some(info), program_id, circuit)
+    })
+    .map_err(|_| ClientError::MalformedPayload("prepared key"))
+}
+
+/// Whether a fetched feature gate account records its activation
+fn feature_active(account: Option<FetchedAccount>) -> bool {
+    with_account_info(account, &ALT_BN128_FEATURE_ID, Feature::from_account_info)
+        .is_ok_and(|feature| feature.activated_at.is_some())
+}
+
+/// Load the config through `VerifierConfig::load`, exactly as the program does
+fn load_config(
+    account: Option<FetchedAccount>,
+    address: &Pubkey,
+    program_id: &Pubkey,
+) -> Result<VerifierConfig, ProgramError> {
+    with_account_info(account, address, |info| VerifierConfig::load(info, program_id))
+}
+
+/// Run `f` on a fetched account as the program would see it
+///
+/// A missing account is presented as the empty, system-owned account the
+/// program would be handed.
+fn with_account_info<T>(
+    account: Option<FetchedAccount>,
+    address: &Pubkey,
+    f: impl FnOnce(&AccountInfo) -> T,
+) -> T {
+    let FetchedAccount {
+        owner,
+        mut lamports,
+        mut data,
+    } = account.unwrap_or(FetchedAccount {
+        owner: system_program::id(),
+        lamports: 0,
+        data: Vec::new(),
+    });
+    let info = AccountInfo::new(
+        address,
+        false,
+        false,
+        &mut lamports,
+        &mut data,
+        &owner,
+        false,
+        0,
+    );
+    f(&info)
+}
+
+/// Look up the receipt recorded for `invoice_id` through its index PDA
+///
+/// Returns the receipt's address and contents, or `None` when the invoice has
+/// not been paid. After overpayment the index points at the latest receipt.
+pub async fn find_receipt_by_invoice(
+    rpc: &impl AccountFetcher,
+    program_id: &Pubkey,
+    invoice_id: &[u8; 32],
+) -> Result<Option<(Pubkey, PaymentReceipt)>, ClientError> {
+    let (index_address, _) = InvoiceIndex::address(program_id, invoice_id);
+    let Some(index) =
+        fetch_state::<InvoiceIndex>(rpc, program_id, &index_address, "invoice index").await?
+    else {
+        return Ok(None);
+    };
+    let receipt = fetch_state::<PaymentReceipt>(rpc, program_id, &index.receipt, "receipt")
+        .await?
+        .ok_or(ClientError::MalformedPayload("invoice index"))?;
+    Ok(Some((index.receipt, receipt)))
+}
+
+/// Fetch and unpack a program account, `None` if it does not exist
+async fn fetch_state<T: StateAccount>(
+    rpc: &impl AccountFetcher,
+    program_id: &Pubkey,
+    address: &Pubkey,
+    name: &'static str,
+) -> Result<Option<T>, ClientError> {
+    match rpc.get_account(address).await? {
+        Some(account) if !account.data.is_empty() => {
+            if account.owner != *program_id {
+                return Err(ClientError::MalformedPayload(name));
+            }
+            T::unpack(&account.data)
+                .map(Some)
+                .map_err(|_| ClientError::MalformedPayload(name))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Public inputs of any circuit, as hashed and as fed to the pairing
+pub trait PublicSignals: Statement {
+    fn signals(&self) -> Vec<u64>;
+}
+
+macro_rules! impl_public_signals {
+    ($($inputs:ty),*) => {
+        $(impl PublicSignals for $inputs {
+            fn signals(&self) -> Vec<u64> {
+                self.to_scalars().to_vec()
+            }
+        })*
+    };
+}
+
+impl_public_signals!(
+    PaymentPublicInputs,
+    PaymentPublicInputsV2,
+    PaymentPublicInputsV3,
+    SplitPaymentPublicInputs,
+    RefundPublicInputs,
+    SubscriptionPublicInputs,
+    WithdrawPublicInputs,
+    CommittedPaymentPublicInputs
+);
+
+/// Hit and miss counts of a `VerificationCache`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl CacheStats {
+    /// Fraction of lookups answered from the cache; 0 before any lookup
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            total => self.hits as f64 / total as f64,
+        }
+    }
+}
+
+/// Source of the current time for client-side expiry
+///
+/// `std::time` panics on `wasm32-unknown-unknown`, so browser builds pass a
+/// source backed by `Date.now()` instead.
+pub trait TimeSource: Send + Sync {
+    /// Time since a fixed origin; only differences between readings are used
+    fn now(&self) -> Duration;
+}
+
+impl<F: Fn() -> Duration + Send + Sync> TimeSource for F {
+    fn now(&self) -> Duration {
+        self()
+    }
+}
+
+/// Monotonic time since construction
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone, Copy)]
+pub struct MonotonicTime(Instant);
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for MonotonicTime {
+    fn default() -> Self {
+        Self(Instant::now())
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl TimeSource for MonotonicTime {
+    fn now(&self) -> Duration {
+        self.0.elapsed()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct CachedOutcome {
+    valid: bool,
+    verified_at: Duration,
+}
+
+/// Off-chain verification results, shared across request handlers
+///
+/// Retries of the same HTTP request carry the same proof; this answers them
+/// without another pairing. Entries are keyed on the verification key hash and
+/// `Groth16Proof::hash`, so any change to the key, proof or public inputs is a
+/// miss. Both outcomes are cached. An entry older than `max_age` counts as a
+/// miss and is verified again, which bounds how long a time-dependent
+/// statement (`current_time`, `valid_until`) can be served from the cache.
+pub struct VerificationCache {
+    capacity: usize,
+    max_age: Duration,
+    time: Box<dyn TimeSource>,
+    metrics: Box<dyn VerifierMetrics>,
+    entries: RwLock<HashMap<[u8; 32], CachedOutcome>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl fmt::Debug for VerificationCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VerificationCache")
+            .field("capacity", &self.capacity)
+            .field("max_age", &self.max_age)
+            .field("len", &self.len())
+            .field("stats", &self.stats())
+            .finish_non_exhaustive()
+    }
+}
+
+impl VerificationCache {
+    /// A cache timed by `MonotonicTime`
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn new(capacity: usize, max_age: Duration) -> Self {
+        Self::with_time_source(capacity, max_age, MonotonicTime::default())
+    }
+
+    /// A cache timed by `time`, for targets without a monotonic clock
+    pub fn with_time_source(
+        capacity: usize,
+        max_age: Duration,
+        time: impl TimeSource + 'static,
+    ) -> Self {
+        Self {
+            capacity,
+            max_age,
+            time: Box::new(time),
+            metrics: Box::new(NoopMetrics),
+            entries: RwLock::new(HashMap::with_capacity(capacity)),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Count lookups, and the verifications misses run, in `metrics`
+    ///
+    /// Verifications are labeled `OFFCHAIN_LABEL`.
+    #[cfg(feature = "metrics")]
+    pub fn with_metrics(mut self, metrics: impl VerifierMetrics + 'static) -> Self {
+        self.metrics = Box::new(metrics);
+        self
+    }
+
+    /// Whether `proof` verifies against `vk`, from the cache when possible
+    pub fn verify_cached<I: PublicSignals>(
+        &self,
+        vk: &VerificationKey,
+        proof: &Groth16Proof,
+        public_inputs: &I,
+    ) -> bool {
+        let key = keccak::hashv(&[&vk.hash(), &proof.hash(public_inputs)]).to_bytes();
+        self.get_or_verify(key, self.time.now(), || {
+            match verify_groth16(vk, proof, &public_inputs.signals()) {
+                Ok(()) => {
+                    self.metrics.proof_verified(OFFCHAIN_LABEL);
+                    true
+                }
+                Err(error) => {
+                    self.metrics.failure(OFFCHAIN_LABEL, error_code(&error));
+                    false
+                }
+            }
+        })
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Cached outcome of `key` at `now`, or `verify`'s, which is then cached
+    ///
+    /// Concurrent misses on one key may each verify; they store the same outcome.
+    fn get_or_verify(&self, key: [u8; 32], now: Duration, verify: impl FnOnce() -> bool) -> bool {
+        let fresh = |entry: &CachedOutcome| now.saturating_sub(entry.verified_at) <= self.max_age;
+
+        if let Some(entry) = self.entries.read().unwrap().get(&key).filter(|entry| fresh(entry)) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            self.metrics.cache_lookup(true);
+            return entry.valid;
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        self.metrics.cache_lookup(false);
+
+        // Verify without holding the lock; a pairing takes milliseconds
+        let valid = verify();
+        if self.capacity == 0 {
+            return valid;
+        }
+
+        let mut entries = self.entries.write().unwrap();
+        if entries.len() >= self.capacity && !entries.contains_key(&key) {
+            entries.retain(|_, entry| fresh(entry));
+            if entries.len() >= self.capacity {
+                let oldest = entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.verified_at)
+                    .map(|(key, _)| *key);
+                if let Some(oldest) = oldest {
+                    entries.remove(&oldest);
+                }
+            }
+        }
+        entries.insert(
+            key,
+            CachedOutcome {
+                valid,
+                verified_at: now,
+            },
+        );
+        valid
+    }
+}
+
+/// Lowercase hex of the point's 64 bytes
+impl fmt::Display for G1Point {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&to_hex(&self.to_bytes()))
+    }
+}
+
+/// Hex of the point's 64 bytes, with or without a 0x prefix, in any case
+impl FromStr for G1Point {
+    type Err = ClientError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_hex_array(s, "G1 point").map(Self::new)
+    }
+}
+
+/// Lowercase hex of the point's 128 bytes
+impl fmt::Display for G2Point {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&to_hex(&self.to_bytes()))
+    }
+}
+
+/// Hex of the point's 128 bytes, with or without a 0x prefix, in any case
+impl FromStr for G2Point {
+    type Err = ClientError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_hex_array(s, "G2 point").map(Self::new)
+    }
+}
+
+/// `a:b:c`, each point in hex
+impl fmt::Display for Groth16Proof {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.a, self.b, self.c)
+    }
+}
+
+impl FromStr for Groth16Proof {
+    type Err = ClientError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut points = s.trim().split(':');
+        let mut next = || points.next().ok_or(ClientError::InvalidField("proof"));
+        let proof = Groth16Proof {
+            a: next()?.parse()?,
+            b: next()?.parse()?,
+            c: next()?.parse()?,
+        };
+        if points.next().is_some() {
+            return Err(ClientError::InvalidField("proof"));
+        }
+        Ok(proof)
+    }
+}
+
+/// `min_amount=.. recipient=.. max_block_age=.. current_time=..`, with the
+/// recipient in base58
+impl fmt::Display for PaymentPublicInputs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "min_amount={} recipient={} max_block_age={} current_time={}",
+            self.min_amount,
+            Pubkey::new_from_array(self.recipient_pubkey),
+            self.max_block_age,
+            self.current_time
+        )
+    }
+}
+
+/// The `Display` form; the recipient may also be given in hex
+impl FromStr for PaymentPublicInputs {
+    type Err = ClientError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        const NAME: &str = "public inputs";
+        let mut fields = HashMap::new();
+        for field in s.split_whitespace() {
+            let (name, value) = field.split_once('=').ok_or(ClientError::InvalidField(NAME))?;
+            if fields.insert(name, value).is_some() {
+                return Err(ClientError::InvalidField(NAME));
+            }
+        }
+        let mut take = |name: &str| fields.remove(name).ok_or(ClientError::InvalidField(NAME));
+        let number = |value: &str| value.parse().map_err(|_| ClientError::MalformedPayload(NAME));
+
+        let public_inputs = PaymentPublicInputs {
+            min_amount: number(take("min_amount")?)?,
+            recipient_pubkey: parse_pubkey(take("recipient")?)?,
+            max_block_age: number(take("max_block_age")?)?,
+            current_time: take("current_time")?
+                .parse()
+                .map_err(|_| ClientError::MalformedPayload(NAME))?,
+        };
+        if !fields.is_empty() {
+            return Err(ClientError::InvalidField(NAME));
+        }
+        Ok(public_inputs)
+    }
+}
+
+/// A 32-byte key in base58, or in hex when 0x-prefixed or 64 digits long
+pub(crate) fn parse_pubkey(s: &str) -> Result<[u8; 32], ClientError> {
+    if s.starts_with("0x") || s.starts_with("0X") || s.len() == 64 {
+        return parse_hex_array(s, "recipient");
+    }
+    Pubkey::from_str(s)
+        .map(|key| key.to_bytes())
+        .map_err(|_| ClientError::MalformedPayload("recipient"))
+}
+
+/// Exactly `N` bytes of hex, optionally 0x-prefixed, in either case
+pub(crate) fn parse_hex_array<const N: usize>(
+    s: &str,
+    name: &'static str,
+) -> Result<[u8; N], ClientError> {
+    let bytes = decode_hex(s).ok_or(ClientError::InvalidHex(name))?;
+    bytes.try_into().map_err(|_| ClientError::InvalidLength(name))
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Bytes of hex, optionally 0x-prefixed, in either case
+pub fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    from_hex(s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")).unwrap_or(s))
+}
+
+fn from_hex(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::{envelope::PROOF_VERSION_V1, state::InputPolicy};
+
+    fn sample() -> (Groth16Proof, PaymentPublicInputsV3) {
+        (
+            Groth16Proof {
+                a: G1Point::new([1u8; 64]),
+                b: G2Point::new([2u8; 128]),
+                c: G1Point::new([3u8; 64]),
+            },
+            PaymentPublicInputsV3 {
+                min_amount: 1000000,
+                recipient_pubkey: [4u8; 32],
+                max_block_age: 60,
+                current_time: 1700000000,
+                invoice_id: [0xaa; 32],
+                valid_until: 0,
+                recipient_set_root: [0x5e; 32],
+            },
+        )
+    }
+
+    #[test]
+    fn test_feature_active() {
+        let feature = |owner: Pubkey, data: Vec<u8>| {
+            Some(FetchedAccount {
+                owner,
+                lamports: 1,
+                data,
+            })
+        };
+        // bincode's `Option<u64>`: a tag, then the activation slot
+        let activated = [&[1u8][..], &42u64.to_le_bytes()].concat();
+        assert!(feature_active(feature(solana_program::feature::id(), activated.clone())));
+
+        // Missing, still pending, or not a feature account at all
+        assert!(!feature_active(None));
+        assert!(!feature_active(feature(solana_program::feature::id(), vec![0u8; 9])));
+        assert!(!feature_active(feature(Pubkey::new_unique(), activated)));
+    }
+
+    #[test]
+    fn test_payment_headers_round_trip() {
+        let (proof, public_inputs) = sample();
+        let headers = encode_payment_headers(&proof, &public_inputs);
+        assert_eq!(headers.invoice_id, "aa".repeat(32));
+
+        let (decoded_proof, decoded_inputs) = decode_payment_headers(&headers).unwrap();
+        assert_eq!(decoded_proof.a, proof.a);
+        assert_eq!(decoded_inputs.invoice_id, public_inputs.invoice_id);
+    }
+
+    #[test]
+    fn test_payment_headers_reject_swapped_invoice() {
+        let (proof, public_inputs) = sample();
+        let mut headers = encode_payment_headers(&proof, &public_inputs);
+        headers.invoice_id = "bb".repeat(32);
+
+        assert_eq!(
+            decode_payment_headers(&headers).unwrap_err(),
+            ClientError::InvoiceMismatch
+        );
+    }
+
+    #[test]
+    fn test_batch_coefficients_reproducible() {
+        let (proof, _) = sample();
+        let public_inputs = PaymentPublicInputs {
+            min_amount: 1000000,
+            recipient_pubkey: [4u8; 32],
+            max_block_age: 60,
+            current_time: 1700000000,
+        };
+        let proofs = [proof];
+        let inputs = [public_inputs];
+
+        let coefficients = batch_coefficients(&proofs, &inputs);
+        assert_eq!(coefficients.len(), 1);
+        assert_eq!(coefficients, batch_coefficients(&proofs, &inputs));
+        assert!(coefficients[0] < crate::field::FR_MODULUS);
+    }
+
+    #[test]
+    fn test_batch_inclusion_proofs() {
+        let request = batch_request(&pending(5));
+        let hashes: Vec<[u8; 32]> = request
+            .proofs
+            .iter()
+            .zip(&request.public_inputs)
+            .map(|(proof, inputs)| proof_hash(proof, inputs))
+            .collect();
+        let root = batch_receipt::receipt_root(&hashes);
+
+        for (index, hash) in hashes.iter().enumerate() {
+            let inclusion = batch_inclusion_proof(&request, index).unwrap();
+            assert_eq!(inclusion.proof_hash, *hash);
+            assert_eq!(inclusion.index as usize, index);
+            assert_eq!(
+                inclusion.transcript_hash,
+                batch_transcript_hash(&request.public_inputs)
+            );
+            assert!(batch_receipt::verify_inclusion(
+                &root,
+                hashes.len(),
+                hash,
+                index,
+                &inclusion.siblings
+            ));
+        }
+        assert_eq!(batch_inclusion_proof(&request, 5), None);
+    }
+
+    #[test]
+    fn test_batch_receipt_address_independent_of_order() {
+        let program_id = Pubkey::new_unique();
+        let relayer = Pubkey::new_unique();
+        let mut proofs = pending(4);
+        let forward =
+            verify_batch_with_receipt_instruction(&program_id, &relayer, batch_request(&proofs));
+        proofs.reverse();
+        let unsorted = BatchVerificationRequest {
+            proofs: proofs.iter().map(|(proof, _)| proof.clone()).collect(),
+            public_inputs: proofs.iter().map(|(_, inputs)| inputs.clone()).collect(),
+            allow_duplicates: false,
+            circuit_id: CircuitId::Payment,
+        };
+        let reversed = verify_batch_with_receipt_instruction(&program_id, &relayer, unsorted);
+
+        let receipt = layout::VERIFY_BATCH_WITH_RECEIPT.receipt;
+        assert_eq!(forward.accounts[receipt], reversed.accounts[receipt]);
+        assert_eq!(forward.data, reversed.data);
+        assert_eq!(
+            batch_inclusion_proof(&batch_request(&proofs), 0),
+            batch_inclusion_proof(&batch_request(&pending(4)), 0)
+        );
+
+        // Other proofs of the same statements name the same receipt
+        let mut reproved = batch_request(&pending(4));
+        for proof in &mut reproved.proofs {
+            proof.c = G1Point::new([9u8; 64]);
+        }
+        let reproved = verify_batch_with_receipt_instruction(&program_id, &relayer, reproved);
+        assert_eq!(forward.accounts[receipt], reproved.accounts[receipt]);
+    }
+
+    fn pending(count: usize) -> Vec<(Groth16Proof, PaymentPublicInputs)> {
+        (0..count)
+            .map(|i| {
+                (
+                    sample().0,
+                    PaymentPublicInputs {
+                        min_amount: 1000000 + i as u64,
+                        recipient_pubkey: [4u8; 32],
+                        max_block_age: 60,
+                        current_time: 1700000000,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    fn options() -> BatchPlanOptions {
+        BatchPlanOptions::new(Pubkey::new_unique(), Pubkey::new_unique())
+    }
+
+    fn batch_sizes(plans: &[BatchPlan]) -> Vec<usize> {
+        plans.iter().map(|plan| plan.proofs.len()).collect()
+    }
+
+    #[test]
+    fn test_plan_batches_transaction_size_boundary() {
+        let proofs = pending(7);
+        let mut opts = options();
+
+        // Sized from the encoded length, as the real transactions come out
+        for n in 1..=proofs.len() {
+            let instruction =
+                verify_batch_instruction(&opts.program_id, batch_request(&proofs[..n]));
+            let size = transaction_size(&instruction, &opts.authority);
+            assert_eq!(inline_transaction_size(&proofs[..n], &opts), size);
+        }
+
+        // Exactly three proofs fit: batches of three
+        opts.max_transaction_size = inline_transaction_size(&proofs[..3], &opts);
+        let plans = plan_batches(&proofs, opts.clone());
+        assert_eq!(batch_sizes(&plans), vec![3, 3, 1]);
+        assert!(plans.iter().all(|plan| plan.strategy == BatchStrategy::Inline));
+        for plan in &plans {
+            assert_eq!(plan.instructions.len(), 1);
+            let size = transaction_size(&plan.instructions[0], &opts.authority);
+            assert!(size <= opts.max_transaction_size);
+        }
+
+        // One byte less and the third no longer fits
+        opts.max_transaction_size -= 1;
+        let plans = plan_batches(&proofs, opts);
+        assert_eq!(batch_sizes(&plans), vec![2, 2, 2, 1]);
+        assert_eq!(plans[3].proofs, 6..7);
+    }
+
+    #[test]
+    fn test_plan_batches_compute_boundary() {
+        let proofs = pending(5);
+        let mut opts = options();
+        opts.max_transaction_size = usize::MAX;
+
+        opts.compute_unit_limit = opts.estimated_compute_units(2);
+        assert_eq!(batch_sizes(&plan_batches(&proofs, opts.clone())), vec![2, 2, 1]);
+
+        opts.compute_unit_limit -= 1;
+        assert_eq!(batch_sizes(&plan_batches(&proofs, opts.clone())), vec![1; 5]);
+
+        // The batch size cap applies even with unlimited compute
+        opts.compute_unit_limit = u64::MAX;
+        let proofs = pending(MAX_BATCH_SIZE + 1);
+        assert_eq!(
+            batch_sizes(&plan_batches(&proofs, opts)),
+            vec![MAX_BATCH_SIZE, 1]
+        );
+    }
+
+    #[test]
+    fn test_plan_batches_switches_to_buffer() {
+        let proofs = pending(5);
+        let mut opts = options();
+        opts.first_buffer_nonce = 7;
+
+        // A single proof still fits inline
+        opts.max_transaction_size = inline_transaction_size(&proofs[..1], &opts);
+        let plans = plan_batches(&proofs, opts.clone());
+        assert_eq!(batch_sizes(&plans), vec![1; 5]);
+        assert!(plans.iter().all(|plan| plan.strategy == BatchStrategy::Inline));
+
+        // One byte less and batches are uploaded instead, no longer bounded
+        // by the transaction size
+        opts.max_transaction_size -= 1;
+        opts.compute_unit_limit = opts.estimated_compute_units(3);
+        let plans = plan_batches(&proofs, opts.clone());
+        assert_eq!(batch_sizes(&plans), vec![3, 2]);
+
+        for (i, plan) in plans.iter().enumerate() {
+            let nonce = 7 + i as u64;
+            let (buffer, _) = pda::batch_buffer_address(&opts.program_id, &opts.authority, nonce);
+            assert_eq!(plan.strategy, BatchStrategy::Buffered { buffer, nonce });
+
+            // Every transaction fits, and the chunks reassemble the request
+            let (verify, writes) = plan.instructions.split_last().unwrap();
+            let mut uploaded = Vec::new();
+            for write in writes {
+                assert!(transaction_size(write, &opts.authority) <= opts.max_transaction_size);
+                match VerifierInstruction::try_from_slice(&write.data).unwrap() {
+                    VerifierInstruction::WriteBatchBuffer { offset, data, .. } => {
+                        assert_eq!(offset as usize, uploaded.len());
+                        uploaded.extend(data);
+                    }
+                    other => panic!("unexpected instruction {:?}", other),
+                }
+            }
+            // Every chunk but the last fills its transaction
+            for write in &writes[..writes.len() - 1] {
+                assert_eq!(transaction_size(write, &opts.authority), opts.max_transaction_size);
+            }
+            assert_eq!(
+                uploaded,
+                batch_request(&proofs[plan.proofs.clone()]).try_to_vec().unwrap()
+            );
+            assert!(matches!(
+                VerifierInstruction::try_from_slice(&verify.data).unwrap(),
+                VerifierInstruction::VerifyBatchBuffered { nonce: n } if n == nonce
+            ));
+        }
+    }
+
+    fn split_payment(program_id: &Pubkey, payer: &Pubkey, hooks: usize) -> Instruction {
+        let public_inputs = SplitPaymentPublicInputs {
+            recipients: [[1u8; 32], [2u8; 32]],
+            min_amounts: [900000, 100000],
+            max_block_age: 60,
+            current_time: 1700000000,
+            mint: [3u8; 32],
+        };
+        let settlement = SplitSettlement {
+            source: Pubkey::new_unique(),
+            destinations: [Pubkey::new_unique(), Pubkey::new_unique()],
+            mint: Pubkey::new_unique(),
+            token_program: spl_token::id(),
+            hook_accounts: (0..hooks)
+                .map(|_| AccountMeta::new_readonly(Pubkey::new_unique(), false))
+                .collect(),
+        };
+        let (proof, _) = sample();
+        verify_split_payment_instruction(
+            program_id,
+            payer,
+            proof,
+            public_inputs,
+            Some(&settlement),
+            false,
+        )
+    }
+
+    /// Hand `instruction`'s metas to the processor-side check of the layout
+    /// `layout::instruction_specs` gives its decoded variant, as the runtime
+    /// would, then compare each meta's flags with the layout, the keys at
+    /// `expected` and the accounts after the layout with `trailing`
+    ///
+    /// Returns the variant's tag, so a test can tell which variants it built.
+    fn assert_matches_layout(
+        instruction: &Instruction,
+        expected: &[(usize, Pubkey)],
+        trailing: &[AccountMeta],
+    ) -> u8 {
+        let decoded = VerifierInstruction::try_from_slice(&instruction.data).unwrap();
+        let specs = layout::instruction_specs(&decoded);
+        let split = instruction.accounts.len() - trailing.len();
+        assert_eq!(&instruction.accounts[split..], trailing);
+
+        let owner = system_program::id();
+        let mut lamports = vec![0u64; split];
+        let mut data = vec![Vec::<u8>::new(); split];
+        let mut infos: Vec<AccountInfo> = instruction.accounts[..split]
+            .iter()
+            .zip(lamports.iter_mut().zip(data.iter_mut()))
+            .map(|(meta, (lamports, data))| {
+                AccountInfo::new(
+                    &meta.pubkey,
+                    meta.is_signer,
+                    meta.is_writable,
+                    lamports,
+                    data,
+                    &owner,
+                    false,
+                    0,
+                )
+            })
+            .collect();
+        assert_eq!(layout::check_accounts(specs, &infos), Ok(()));
+        for (meta, spec) in instruction.accounts.iter().zip(specs) {
+            assert_eq!(meta.is_signer, spec.signer, "{}", spec.name);
+            assert_eq!(meta.is_writable, spec.writable, "{}", spec.name);
+        }
+        for (index, key) in expected {
+            assert_eq!(
+                instruction.accounts[*index].pubkey, *key,
+                "{}",
+                specs[*index].name
+            );
+        }
+
+        // Without the signatures the check fails
+        for info in &mut infos {
+            info.is_signer = false;
+        }
+        if specs.iter().any(|spec| spec.signer) {
+            assert_eq!(
+                layout::check_accounts(specs, &infos),
+                Err(ProgramError::MissingRequiredSignature)
+            );
+        }
+        instruction.data[0]
+    }
+
+    #[test]
+    fn test_builders_match_account_layouts() {
+        let program_id = Pubkey::new_unique();
+        let payer = Pubkey::new_unique();
+        let (proof, public_inputs) = sample();
+        let (config, _) = pda::config_address(&program_id);
+        let receipt_hash = proof.hash(&public_inputs);
+        let (receipt, _) = pda::receipt_address(&program_id, &receipt_hash);
+        let (marker, _) = pda::denied_recipient_address(&program_id, &[4u8; 32]);
+        let (index, _) = pda::invoice_address(&program_id, &public_inputs.invoice_id);
+        let mut covered = BTreeSet::new();
+
+        let layout = layout::VERIFY_PROOF;
+        let (prepared, _) = pda::vkey_address(&program_id, CircuitId::Payment);
+        let v1 = pending(1).remove(0).1;
+        let verify_proof_keys = [
+            (layout.system_program, system_program::id()),
+            (layout.config, config),
+            (layout.prepared_key, prepared),
+            (layout.marker, marker),
+        ];
+        covered.insert(assert_matches_layout(
+            &verify_proof_instruction(&program_id, proof.clone(), v1.clone()),
+            &verify_proof_keys,
+            &[],
+        ));
+        for max_time_skew_seconds in [None, Some(30)] {
+            let envelope = ProofEnvelope::v1(&proof, &v1);
+            let instruction =
+                verify_envelope_instruction(&program_id, envelope, max_time_skew_seconds).unwrap();
+            covered.insert(assert_matches_layout(&instruction, &verify_proof_keys, &[]));
+        }
+        let token = PaymentPublicInputsV2 {
+            min_amount: 1000000,
+            recipient_pubkey: [4u8; 32],
+            max_block_age: 60,
+            current_time: 1700000000,
+            mint: [3u8; 32],
+            token_decimals_checked_amount: 1000000,
+        };
+        let (token_prepared, _) = pda::vkey_address(&program_id, CircuitId::TokenPayment);
+        let envelope = ProofEnvelope::v2(&proof, &token);
+        assert_matches_layout(
+            &verify_envelope_instruction(&program_id, envelope, None).unwrap(),
+            &[(layout.prepared_key, token_prepared)],
+            &[],
+        );
+        covered.insert(assert_matches_layout(
+            &verify_proof_v2_instruction(&program_id, proof.clone(), token),
+            &[
+                (layout.prepared_key, token_prepared),
+                (layout.marker, marker),
+            ],
+            &[],
+        ));
+        let malformed = ProofEnvelope {
+            version: PROOF_VERSION_V1,
+            payload: vec![0u8; 3],
+        };
+        assert_eq!(
+            verify_envelope_instruction(&program_id, malformed, None),
+            Err(ClientError::MalformedPayload("envelope"))
+        );
+
+        // The invoice index comes straight after the layout
+        let layout = layout::VERIFY_AND_RECORD;
+        covered.insert(assert_matches_layout(
+            &verify_and_record_instruction(
+                &program_id,
+                &payer,
+                proof.clone(),
+                public_inputs.clone(),
+            ),
+            &[
+                (layout.payer, payer),
+                (layout.config, config),
+                (layout.receipt, receipt),
+            ],
+            &[AccountMeta::new(index, false)],
+        ));
+
+        // Each proof's receipt, invoice index and marker follow the layout
+        let layout = layout::VERIFY_BATCH_SOFT;
+        covered.insert(assert_matches_layout(
+            &verify_batch_soft_instruction(
+                &program_id,
+                &payer,
+                vec![proof.clone()],
+                vec![public_inputs.clone()],
+                1,
+            ),
+            &[(layout.payer, payer), (layout.config, config)],
+            &[
+                AccountMeta::new(receipt, false),
+                AccountMeta::new(index, false),
+                AccountMeta::new_readonly(marker, false),
+            ],
+        ));
+
+        let layout = layout::VERIFY_PROOF_WITH_ACK;
+        covered.insert(assert_matches_layout(
+            &verify_proof_with_ack_instruction(&program_id, proof.clone(), public_inputs.clone()),
+            &[
+                (layout.config, config),
+                (layout.instructions_sysvar, sysvar::instructions::id()),
+                (layout.marker, marker),
+            ],
+            &[],
+        ));
+
+        let layout = layout::TOUCH_RECEIPT;
+        let recipient = Pubkey::new_from_array([4u8; 32]);
+        covered.insert(assert_matches_layout(
+            &touch_receipt_instruction(
+                &program_id,
+                &recipient,
+                receipt_hash,
+                proof.clone(),
+                public_inputs.clone(),
+            ),
+            &[
+                (layout.recipient, recipient),
+                (layout.receipt, receipt),
+                (layout.marker, marker),
+            ],
+            &[],
+        ));
+
+        // The invoice index is optional
+        let layout = layout::VERIFY_REFUND;
+        let refund = || RefundPublicInputs {
+            receipt_proof_hash: receipt_hash,
+            refund_recipient: [6u8; 32],
+        };
+        covered.insert(assert_matches_layout(
+            &verify_refund_instruction(
+                &program_id,
+                proof.clone(),
+                refund(),
+                Some(public_inputs.invoice_id),
+            ),
+            &[(layout.receipt, receipt), (layout.invoice_index, index)],
+            &[],
+        ));
+        let unindexed = verify_refund_instruction(&program_id, proof.clone(), refund(), None);
+        assert_matches_layout(&unindexed, &[(layout.receipt, receipt)], &[]);
+        assert_eq!(unindexed.accounts.len(), layout.invoice_index);
+
+        let layout = layout::VERIFY_SUBSCRIPTION;
+        let subscription = SubscriptionPublicInputs {
+            min_amount: 1000000,
+            recipient_pubkey: [4u8; 32],
+            epoch: 7,
+            nullifier: [9u8; 32],
+        };
+        let (nullifier, _) =
+            SpentNullifier::address(&program_id, CircuitId::Subscription, &[9u8; 32], 7);
+        covered.insert(assert_matches_layout(
+            &verify_subscription_instruction(&program_id, &payer, proof.clone(), subscription),
+            &[
+                (layout.payer, payer),
+                (layout.config, config),
+                (layout.nullifier, nullifier),
+            ],
+            &[],
+        ));
+
+        let layout = layout::DEPOSIT;
+        covered.insert(assert_matches_layout(
+            &deposit_instruction(&program_id, &payer, [5u8; 32], 1000),
+            &[
+                (layout.depositor, payer),
+                (layout.pool, pda::pool_address(&program_id).0),
+            ],
+            &[],
+        ));
+
+        let layout = layout::WITHDRAW;
+        let withdrawal = WithdrawPublicInputs {
+            root: [1u8; 32],
+            nullifier: [2u8; 32],
+            recipient: [3u8; 32],
+            amount: 1000,
+            change_commitment: [0u8; 32],
+        };
+        covered.insert(assert_matches_layout(
+            &withdraw_instruction(&program_id, &payer, proof.clone(), withdrawal),
+            &[
+                (layout.payer, payer),
+                (layout.recipient, Pubkey::new_from_array([3u8; 32])),
+            ],
+            &[],
+        ));
+
+        // Settlement accounts are optional, hook accounts follow them
+        let layout = layout::VERIFY_SPLIT_PAYMENT;
+        let split = split_payment(&program_id, &payer, 2);
+        let hooks = split.accounts[layout::VerifySplitPaymentAccounts::LEN..].to_vec();
+        assert_eq!(hooks.len(), 2);
+        covered.insert(assert_matches_layout(
+            &split,
+            &[
+                (layout.payer, payer),
+                (layout.token_program, spl_token::id()),
+            ],
+            &hooks,
+        ));
+        let unsettled = verify_split_payment_instruction(
+            &program_id,
+            &payer,
+            proof.clone(),
+            SplitPaymentPublicInputs {
+                recipients: [[1u8; 32], [2u8; 32]],
+                min_amounts: [900000, 100000],
+                max_block_age: 60,
+                current_time: 1700000000,
+                mint: [3u8; 32],
+            },
+            None,
+            false,
+        );
+        let (second_marker, _) = pda::denied_recipient_address(&program_id, &[2u8; 32]);
+        assert_matches_layout(&unsettled, &[(layout.second_marker, second_marker)], &[]);
+        assert_eq!(unsettled.accounts.len(), layout.second_marker + 1);
+
+        // The batches' denylist markers follow their layouts
+        let request = batch_request(&pending(2));
+        let markers: Vec<AccountMeta> =
+            recipient_marker_metas(&program_id, &request.public_inputs).collect();
+        let layout = layout::VERIFY_BATCH;
+        covered.insert(assert_matches_layout(
+            &verify_batch_instruction(&program_id, request.clone()),
+            &[(layout.prepared_key, prepared)],
+            &markers,
+        ));
+        let layout = layout::VERIFY_BATCH_WITH_RECEIPT;
+        covered.insert(assert_matches_layout(
+            &verify_batch_with_receipt_instruction(&program_id, &payer, request.clone()),
+            &[(layout.relayer, payer), (layout.prepared_key, prepared)],
+            &markers,
+        ));
+        let layout = layout::VERIFY_BATCH_INCLUSION;
+        let inclusion = batch_inclusion_proof(&request, 1).unwrap();
+        let (recorded, _) = pda::batch_receipt_address(&program_id, &inclusion.transcript_hash);
+        covered.insert(assert_matches_layout(
+            &verify_batch_inclusion_instruction(&program_id, inclusion),
+            &[(layout.receipt, recorded)],
+            &[],
+        ));
+
+        let opts = options();
+        let (buffer, _) = pda::batch_buffer_address(&opts.program_id, &opts.authority, 3);
+        let plan = buffered_plan(0..2, &request, 3, &opts);
+        let layout = layout::WRITE_BATCH_BUFFER;
+        covered.insert(assert_matches_layout(
+            &plan.instructions[0],
+            &[(layout.authority, opts.authority), (layout.buffer, buffer)],
+            &[],
+        ));
+        let layout = layout::VERIFY_BATCH_BUFFERED;
+        let buffered_markers: Vec<AccountMeta> =
+            recipient_marker_metas(&opts.program_id, &request.public_inputs).collect();
+        covered.insert(assert_matches_layout(
+            plan.instructions.last().unwrap(),
+            &[(layout.authority, opts.authority), (layout.buffer, buffer)],
+            &buffered_markers,
+        ));
+
+        let (buffer, _) = pda::batch_buffer_address(&program_id, &payer, 3);
+        let layout = layout::ABORT_BUFFER;
+        covered.insert(assert_matches_layout(
+            &abort_buffer_instruction(&program_id, &payer, 3),
+            &[(layout.authority, payer), (layout.buffer, buffer)],
+            &[],
+        ));
+        let reaper = Pubkey::new_unique();
+        let layout = layout::REAP_STALE;
+        covered.insert(assert_matches_layout(
+            &reap_stale_instruction(&program_id, &reaper, &payer, 3),
+            &[
+                (layout.reaper, reaper),
+                (layout.buffer, buffer),
+                (layout.authority, payer),
+            ],
+            &[],
+        ));
+
+        let layout = layout::CONSUME_RECEIPT;
+        covered.insert(assert_matches_layout(
+            &consume_receipt_instruction(&program_id, &payer, receipt_hash),
+            &[(layout.consumer, payer), (layout.receipt, receipt)],
+            &[],
+        ));
+        let layout = layout::FINALIZE_RECEIPT;
+        covered.insert(assert_matches_layout(
+            &finalize_receipt_instruction(&program_id, &payer, receipt_hash),
+            &[(layout.caller, payer), (layout.receipt, receipt)],
+            &[],
+        ));
+        let layout = layout::MIGRATE_RECEIPT;
+        covered.insert(assert_matches_layout(
+            &migrate_receipt_instruction(&program_id, &payer, receipt_hash),
+            &[
+                (layout.caller, payer),
+                (layout.config, config),
+                (layout.receipt, receipt),
+            ],
+            &[],
+        ));
+        let layout = layout::GET_RECEIPT_STATUS;
+        covered.insert(assert_matches_layout(
+            &get_receipt_status_instruction(&program_id, receipt_hash, false),
+            &[(layout.receipt, receipt)],
+            &[],
+        ));
+        let relayer = Pubkey::new_unique();
+        let (deposit, _) = pda::rebate_deposit_address(&program_id, &payer);
+        let layout = layout::CLAIM_RELAYER_REBATE;
+        covered.insert(assert_matches_layout(
+            &claim_relayer_rebate_instruction(&program_id, &payer, &relayer, receipt_hash, 5000),
+            &[
+                (layout.merchant, payer),
+                (layout.deposit, deposit),
+                (layout.relayer, relayer),
+                (layout.receipt, receipt),
+            ],
+            &[],
+        ));
+
+        let layout = layout::VERIFY_WITH_OPENING;
+        let committed = CommittedPaymentPublicInputs {
+            min_amount: 1000000,
+            recipient_pubkey: [4u8; 32],
+            max_block_age: 60,
+            current_time: 1700000000,
+            amount_commitment: [0u8; 32],
+        };
+        covered.insert(assert_matches_layout(
+            &verify_with_opening_instruction(&program_id, &payer, proof.clone(), committed, None),
+            &[(layout.payer, payer), (layout.marker, marker)],
+            &[],
+        ));
+
+        // The key account is passed only when the key is not inline
+        let layout = layout::VERIFY_WITH_INLINE_KEY;
+        let vk = InlineVerificationKey {
+            alpha_g1: G1Point::new([1u8; 64]),
+            beta_g2: G2Point::new([2u8; 128]),
+            gamma_g2: G2Point::new([2u8; 128]),
+            delta_g2: G2Point::new([2u8; 128]),
+            ic: vec![G1Point::new([1u8; 64]); 2],
+        };
+        let key_account = Pubkey::new_unique();
+        covered.insert(assert_matches_layout(
+            &verify_with_key_account_instruction(
+                &program_id,
+                &key_account,
+                &vk,
+                proof.clone(),
+                vec![],
+            ),
+            &[(layout.key_account, key_account)],
+            &[],
+        ));
+        let inline = verify_with_inline_key_instruction(&program_id, vk, proof, vec![]);
+        assert_matches_layout(&inline, &[], &[]);
+        assert!(inline.accounts.is_empty());
+
+        // Every admin instruction has the admin and the config first; the
+        // prepared key instructions pass the system program after their
+        // layout, and the co-signers come last
+        let admin = Pubkey::new_unique();
+        let co_signer = Pubkey::new_unique();
+        let co_signers = [AccountMeta::new_readonly(co_signer, true)];
+        let system = AccountMeta::new_readonly(system_program::id(), false);
+        let with_system = [system.clone(), co_signers[0].clone()];
+        let admin_instructions: [(VerifierInstruction, &[AccountMeta]); 27] = [
+            (
+                VerifierInstruction::InitializeConfig {
+                    recipient_root_grace_seconds: 60,
+                },
+                &[],
+            ),
+            (
+                VerifierInstruction::SetRecipientRoot { root: [1u8; 32] },
+                &[],
+            ),
+            (
+                VerifierInstruction::SetEpochLength {
+                    epoch_length_seconds: 60,
+                },
+                &[],
+            ),
+            (
+                VerifierInstruction::SetInputPolicy {
+                    policy: InputPolicy::default(),
+                },
+                &[],
+            ),
+            (
+                VerifierInstruction::ProposeAdmin { new_admin: payer },
+                &co_signers,
+            ),
+            (VerifierInstruction::AcceptAdmin, &[]),
+            (VerifierInstruction::CancelProposedAdmin, &[]),
+            (
+                VerifierInstruction::PrepareVerificationKey {
+                    circuit: CircuitId::Payment,
+                    alpha_beta: None,
+                },
+                &co_signers,
+            ),
+            (
+                VerifierInstruction::SetMaxReceiptAge {
+                    max_receipt_age_slots: 100,
+                },
+                &[],
+            ),
+            (VerifierInstruction::InitializeAuditLog, &[]),
+            (VerifierInstruction::SetAuditEnabled { enabled: true }, &[]),
+            (
+                VerifierInstruction::InitializePool {
+                    root_history_size: 8,
+                },
+                &[],
+            ),
+            (
+                VerifierInstruction::SetMaxKeyDeprecation {
+                    max_key_deprecation_slots: 100,
+                },
+                &[],
+            ),
+            (
+                VerifierInstruction::DeprecatePreviousKey {
+                    circuit: CircuitId::Payment,
+                    deprecated_until_slot: 100,
+                },
+                &with_system,
+            ),
+            (VerifierInstruction::InitializeRelayerList, &[]),
+            (VerifierInstruction::AddRelayer { relayer }, &[]),
+            (VerifierInstruction::RemoveRelayer { relayer }, &[]),
+            (
+                VerifierInstruction::SetAdminSigners {
+                    threshold: 1,
+                    signers: vec![co_signer],
+                },
+                &co_signers,
+            ),
+            (
+                VerifierInstruction::SetInvoiceOverpayment { allowed: true },
+                &[],
+            ),
+            (
+                VerifierInstruction::RevokeVerificationKey {
+                    circuit: CircuitId::Payment,
+                },
+                &with_system[..1],
+            ),
+            (
+                VerifierInstruction::DenyRecipient {
+                    recipient: [4u8; 32],
+                },
+                &[],
+            ),
+            (
+                VerifierInstruction::AllowRecipient {
+                    recipient: [4u8; 32],
+                },
+                &[],
+            ),
+            (
+                VerifierInstruction::SetRateLimit {
+                    max_verifications_per_window: 10,
+                    window_slots: 100,
+                },
+                &[],
+            ),
+            (
+                VerifierInstruction::SetFinalitySlots { finality_slots: 32 },
+                &[],
+            ),
+            (
+                VerifierInstruction::SetHashBackend {
+                    circuit: CircuitId::Payment,
+                    backend: HashBackend::Sha256,
+                },
+                &with_system,
+            ),
+            (
+                VerifierInstruction::SetScratchTtl {
+                    scratch_ttl_slots: 100,
+                },
+                &[],
+            ),
+            (
+                VerifierInstruction::SetTrackRelayer {
+                    track_relayer: true,
+                },
+                &[],
+            ),
+        ];
+        for (instruction, trailing) in admin_instructions {
+            let signers: Vec<Pubkey> = trailing
+                .iter()
+                .filter(|meta| meta.is_signer)
+                .map(|meta| meta.pubkey)
+                .collect();
+            let built = admin_instruction(&program_id, &admin, instruction, &signers);
+            covered.insert(assert_matches_layout(
+                &built,
+                &[(0, admin), (1, config)],
+                trailing,
+            ));
+        }
+
+        // `GetVersion` takes no accounts, and no builder
+        let get_version =
+            Instruction::new_with_borsh(program_id, &VerifierInstruction::GetVersion, vec![]);
+        covered.insert(assert_matches_layout(&get_version, &[], &[]));
+
+        // Every variant was built and checked
+        assert_eq!(covered, (0..55).collect());
+    }
+
+    #[test]
+    #[should_panic(expected = "instruction takes no co-signers")]
+    fn test_admin_instruction_rejects_stray_co_signers() {
+        let program_id = Pubkey::new_unique();
+        admin_instruction(
+            &program_id,
+            &Pubkey::new_unique(),
+            VerifierInstruction::SetRecipientRoot { root: [1u8; 32] },
+            &[Pubkey::new_unique()],
+        );
+    }
+
+    #[test]
+    fn test_check_transaction_size_suggestions() {
+        let opts = options();
+        let payer = opts.authority;
+
+        let fits = verify_batch_instruction(&opts.program_id, batch_request(&pending(1)));
+        let size = serialized_transaction_size(&[fits.clone()], &payer);
+        assert_eq!(check_transaction_size(&[fits], &payer), Ok(size));
+
+        // Too many proofs inline: upload them instead
+        let batch =
+            verify_batch_instruction(&opts.program_id, batch_request(&pending(MAX_BATCH_SIZE)));
+        let needed = serialized_transaction_size(&[batch.clone()], &payer);
+        assert!(needed > MAX_TRANSACTION_SIZE);
+        let error = check_transaction_size(&[batch], &payer).unwrap_err();
+        assert_eq!(
+            error,
+            ClientError::TransactionTooLarge {
+                needed,
+                limit: MAX_TRANSACTION_SIZE,
+                suggestion: SizeSuggestion::BufferedBatch,
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "Transaction needs {} bytes, over the 1232-byte limit; \
+                 upload the batch through a buffer PDA instead",
+                needed
+            )
+        );
+
+        // Settled proofs that fit one at a time but not together
+        let split = split_payment(&opts.program_id, &payer, 8);
+        assert!(check_transaction_size(&[split.clone()], &payer).is_ok());
+        let both = [split, split_payment(&opts.program_id, &payer, 8)];
+        assert!(matches!(
+            check_transaction_size(&both, &payer),
+            Err(ClientError::TransactionTooLarge {
+                suggestion: SizeSuggestion::SeparateInstructions,
+                ..
+            })
+        ));
+    }
+
+    #[cfg(feature = "rpc")]
+    #[test]
+    fn test_transaction_size_matches_wire_size() {
+        use solana_sdk::{
+            compute_budget::ComputeBudgetInstruction,
+            hash::Hash,
+            packet::{Packet, PACKET_DATA_SIZE},
+            signature::{Keypair, Signer},
+            transaction::Transaction,
+        };
+
+        assert_eq!(MAX_TRANSACTION_SIZE, PACKET_DATA_SIZE);
+        let program_id = Pubkey::new_unique();
+        let payer = Keypair::new();
+        let owner = Keypair::new();
+        let (proof, _) = sample();
+        let public_inputs = pending(1).remove(0).1;
+        let verify = verify_proof_instruction(&program_id, proof, public_inputs);
+        let budget = [
+            ComputeBudgetInstruction::set_compute_unit_limit(400_000),
+            ComputeBudgetInstruction::set_compute_unit_price(10_000),
+        ];
+        // A transfer signed by a second key, ahead of a settled split payment
+        let transfer = spl_token::instruction::transfer(
+            &spl_token::id(),
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &owner.pubkey(),
+            &[],
+            1,
+        )
+        .unwrap();
+        let split = |hooks| split_payment(&program_id, &payer.pubkey(), hooks);
+        let batch = |n| {
+            Instruction::new_with_borsh(
+                program_id,
+                &VerifierInstruction::VerifyBatch {
+                    request: batch_request(&pending(n)),
+                },
+                vec![],
+            )
+        };
+
+        let scenarios: Vec<(Vec<Instruction>, Vec<&Keypair>)> = vec![
+            (vec![verify.clone()], vec![]),
+            ([&budget[..], &[verify]].concat(), vec![]),
+            ([&budget[..], &[transfer.clone(), split(0)]].concat(), vec![&owner]),
+            (vec![transfer, split(3)], vec![&owner]),
+            (vec![split(8), split(8)], vec![]),
+            (vec![batch(1)], vec![]),
+            (vec![batch(3)], vec![]),
+            ([&budget[..], &[batch(3)]].concat(), vec![]),
+            (vec![batch(MAX_BATCH_SIZE)], vec![]),
+        ];
+        let mut too_large = 0;
+        for (instructions, signers) in scenarios {
+            let mut keys = vec![&payer];
+            keys.extend(signers);
+            let transaction = Transaction::new_signed_with_payer(
+                &instructions,
+                Some(&payer.pubkey()),
+                &keys,
+                Hash::new_unique(),
+            );
+            // A packet refuses a transaction over the limit
+            let wire = Packet::from_data(None, &transaction).ok().map(|packet| packet.meta().size);
+            let checked = check_transaction_size(&instructions, &payer.pubkey());
+            assert_eq!(checked.clone().ok(), wire);
+            if let Err(ClientError::TransactionTooLarge { needed, .. }) = checked {
+                assert_eq!(needed, serialized_transaction_size(&instructions, &payer.pubkey()));
+                let message_len = transaction.message_data().len();
+                assert_eq!(needed, 1 + 64 * transaction.signatures.len() + message_len);
+                too_large += 1;
+            }
+        }
+        assert_eq!(too_large, 2);
+    }
+
+    #[test]
+    fn test_verification_cache_hits_and_expiry() {
+        let cache = VerificationCache::new(4, Duration::from_secs(60));
+        let start = Duration::ZERO;
+        let key = [1u8; 32];
+
+        assert!(cache.get_or_verify(key, start, || true));
+        // Answered from the cache, even though verifying again would now fail
+        assert!(cache.get_or_verify(key, start + Duration::from_secs(60), || false));
+        assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 1 });
+
+        // Past max-age the statement is verified again
+        assert!(!cache.get_or_verify(key, start + Duration::from_secs(61), || false));
+        assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 2 });
+        assert_eq!(cache.stats().hit_rate(), 1.0 / 3.0);
+
+        // Failures are cached too
+        assert!(!cache.get_or_verify(key, start + Duration::from_secs(62), || true));
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_verification_cache_capacity() {
+        let cache = VerificationCache::new(2, Duration::from_secs(60));
+        let start = Duration::ZERO;
+        let at = |secs| start + Duration::from_secs(secs);
+
+        cache.get_or_verify([1u8; 32], at(0), || true);
+        cache.get_or_verify([2u8; 32], at(1), || true);
+        cache.get_or_verify([3u8; 32], at(2), || true);
+        assert_eq!(cache.len(), 2);
+
+        // The oldest entry made room
+        assert!(!cache.get_or_verify([1u8; 32], at(3), || false));
+        assert!(cache.get_or_verify([3u8; 32], at(3), || false));
+
+        let disabled = VerificationCache::new(0, Duration::from_secs(60));
+        assert!(disabled.get_or_verify([1u8; 32], start, || true));
+        assert!(disabled.is_empty());
+    }
+
+    #[test]
+    fn test_verification_cache_concurrent() {
+        let cache = VerificationCache::new(16, Duration::from_secs(60));
+        let verifications = AtomicU64::new(0);
+        let start = Duration::ZERO;
+
+        std::thread::scope(|scope| {
+            for thread in 0..8u8 {
+                let (cache, verifications) = (&cache, &verifications);
+                scope.spawn(move || {
+                    for i in 0..100u8 {
+                        let key = [i % 4; 32];
+                        let valid = cache.get_or_verify(key, start, || {
+                            verifications.fetch_add(1, Ordering::Relaxed);
+                            key[0] % 2 == 0
+                        });
+                        assert_eq!(valid, key[0] % 2 == 0, "thread {}", thread);
+                    }
+                });
+            }
+        });
+
+        let stats = cache.stats();
+        assert_eq!(stats.hits + stats.misses, 800);
+        assert_eq!(stats.misses, verifications.load(Ordering::Relaxed));
+        // Each key misses at most once per thread racing on it
+        assert!(stats.misses >= 4 && stats.misses <= 32);
+        assert_eq!(cache.len(), 4);
+    }
+
+    #[test]
+    fn test_verification_cache_time_source() {
+        let clock = std::sync::Arc::new(AtomicU64::new(0));
+        let time = {
+            let clock = clock.clone();
+            move || Duration::from_secs(clock.load(Ordering::Relaxed))
+        };
+        let cache = VerificationCache::with_time_source(8, Duration::from_secs(60), time);
+        let (proof, public_inputs) = sample();
+        let vk = CircuitId::InvoicePayment.verification_key();
+
+        assert!(!cache.verify_cached(vk, &proof, &public_inputs));
+        clock.store(60, Ordering::Relaxed);
+        assert!(!cache.verify_cached(vk, &proof, &public_inputs));
+        assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 1 });
+
+        clock.store(61, Ordering::Relaxed);
+        assert!(!cache.verify_cached(vk, &proof, &public_inputs));
+        assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 2 });
+    }
+
+    #[test]
+    fn test_verification_cache_keys_on_statement() {
+        let cache = VerificationCache::new(8, Duration::from_secs(60));
+        let (proof, public_inputs) = sample();
+        let vk = CircuitId::InvoicePayment.verification_key();
+
+        // The sample proof fails verification, so the outcome itself is stable
+        assert!(!cache.verify_cached(vk, &proof, &public_inputs));
+        assert!(!cache.verify_cached(vk, &proof, &public_inputs));
+        assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 1 });
+
+        let later = PaymentPublicInputsV3 {
+            current_time: public_inputs.current_time + 1,
+            ..public_inputs
+        };
+        assert!(!cache.verify_cached(vk, &proof, &later));
+        assert!(!cache.verify_cached(CircuitId::Payment.verification_key(), &proof, &later));
+        assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 3 });
+    }
+
+    #[test]
+    fn test_proof_display_round_trip() {
+        let (proof, _) = sample();
+        let text = proof.to_string();
+        assert_eq!(
+            text,
+            format!("{}:{}:{}", "01".repeat(64), "02".repeat(128), "03".repeat(64))
+        );
+        assert_eq!(text.parse::<Groth16Proof>().unwrap().try_to_vec(), proof.try_to_vec());
+
+        // 0x prefixes and any case
+        let prefixed = format!(
+            "0x{}:0X{}:{}",
+            "AB".repeat(64),
+            "cD".repeat(128),
+            "ef".repeat(64)
+        );
+        let parsed: Groth16Proof = prefixed.parse().unwrap();
+        assert_eq!(parsed.a, G1Point::new([0xab; 64]));
+        assert_eq!(parsed.b, G2Point::new([0xcd; 128]));
+        assert_eq!(parsed.c, G1Point::new([0xef; 64]));
+    }
+
+    #[test]
+    fn test_hex_parsers_reject_malformed() {
+        let a = "01".repeat(64);
+        let b = "02".repeat(128);
+
+        // Odd length, bad digits, non-ASCII
+        assert_eq!("0x123".parse::<G1Point>(), Err(ClientError::InvalidHex("G1 point")));
+        assert_eq!(
+            format!("{}zz", &a[2..]).parse::<G1Point>(),
+            Err(ClientError::InvalidHex("G1 point"))
+        );
+        assert_eq!(
+            format!("{}é", &a[2..]).parse::<G1Point>(),
+            Err(ClientError::InvalidHex("G1 point"))
+        );
+
+        // Whole bytes, wrong count, including a G1 point where B belongs
+        assert_eq!(a[2..].parse::<G1Point>(), Err(ClientError::InvalidLength("G1 point")));
+        assert_eq!(
+            format!("{}00", a).parse::<G1Point>(),
+            Err(ClientError::InvalidLength("G1 point"))
+        );
+        assert_eq!(
+            format!("{}:{}:{}", a, a, a).parse::<Groth16Proof>().err(),
+            Some(ClientError::InvalidLength("G2 point"))
+        );
+        assert_eq!("".parse::<G1Point>(), Err(ClientError::InvalidLength("G1 point")));
+
+        // Wrong number of points
+        assert_eq!(
+            format!("{}:{}", a, b).parse::<Groth16Proof>().err(),
+            Some(ClientError::InvalidField("proof"))
+        );
+        assert_eq!(
+            format!("{}:{}:{}:{}", a, b, a, a).parse::<Groth16Proof>().err(),
+            Some(ClientError::InvalidField("proof"))
+        );
+    }
+
+    #[test]
+    fn test_public_inputs_display_round_trip() {
+        let public_inputs = PaymentPublicInputs {
+            min_amount: 1000000,
+            recipient_pubkey: [4u8; 32],
+            max_block_age: 60,
+            current_time: -1,
+        };
+        let recipient = Pubkey::new_from_array([4u8; 32]).to_string();
+        let text = public_inputs.to_string();
+        assert_eq!(
+            text,
+            format!(
+                "min_amount=1000000 recipient={} max_block_age=60 current_time=-1",
+                recipient
+            )
+        );
+        assert_eq!(text.parse(), Ok(public_inputs.clone()));
+
+        // Any field order; the recipient in hex
+        let hex = format!(
+            "current_time=-1 max_block_age=60 recipient=0x{} min_amount=1000000",
+            "04".repeat(32)
+        );
+        assert_eq!(hex.parse(), Ok(public_inputs));
+
+        let name = "public inputs";
+        for malformed in [
+            "min_amount=1 max_block_age=60 current_time=0".to_string(),
+            format!("min_amount=1 recipient={} max_block_age=60 current_time=0 x=1", recipient),
+            format!("min_amount=1 min_amount=1 recipient={} current_time=0", recipient),
+            format!("min_amount {} max_block_age=60 current_time=0", recipient),
+        ] {
+            assert_eq!(
+                malformed.parse::<PaymentPublicInputs>(),
+                Err(ClientError::InvalidField(name)),
+                "{}",
+                malformed
+            );
+        }
+        assert_eq!(
+            format!("min_amount=-1 recipient={} max_block_age=60 current_time=0", recipient)
+                .parse::<PaymentPublicInputs>(),
+            Err(ClientError::MalformedPayload(name))
+        );
+        assert_eq!(
+            "min_amount=1 recipient=0x0404 max_block_age=60 current_time=0"
+                .parse::<PaymentPublicInputs>(),
+            Err(ClientError::InvalidLength("recipient"))
+        );
+    }
+
+    #[test]
+    fn test_payment_intent_signals() {
+        let (_, public_inputs) = sample();
+        let intent = PaymentIntent {
+            recipient: Pubkey::new_from_array([4u8; 32]),
+            min_amount: 1000000,
+            max_block_age: 60,
+            invoice_id: [0xaa; 32],
+            valid_until: 0,
+            recipient_set_root: [0x5e; 32],
+        };
+        assert_eq!(
+            intent.to_public_inputs(1700000000).try_to_vec().unwrap(),
+            public_inputs.try_to_vec().unwrap()
+        );
+
+        // Signal names in IC order, 32-byte values as four u64 limbs
+        let recipient = "289360691352306692";
+        let invoice = "12297829382473034410";
+        let root = "6799976246779207262";
+        let expected = format!(
+            concat!(
+                r#"{{"minAmount":"1000000","recipientPubKeyX":"{0}","recipientPubKeyY":"{0}","#,
+                r#""maxBlockAge":"60","currentTime":"1700000000","invoiceId0":"{1}","#,
+                r#""invoiceId1":"{1}","invoiceId2":"{1}","invoiceId3":"{1}","validUntil":"0","#,
+                r#""recipientSetRoot0":"{2}","recipientSetRoot1":"{2}","#,
+                r#""recipientSetRoot2":"{2}","recipientSetRoot3":"{2}"}}"#
+            ),
+            recipient, invoice, root
+        );
+        assert_eq!(intent.to_circuit_inputs_json(1700000000), expected);
+    }
+}
@@ -0,0 +1,304 @@
+// Reading a failed transaction's metadata
+// A failed verification reaches support as a transaction status, its logs and
+// its return data. `diagnose_failure` turns those into a `Diagnosis`: which
+// instruction failed and with what, as a `VerifierError` where the code is
+// one, what the program left in return data about it, any numbered
+// diagnostic a `diagnostics` build logged, and a one-line summary.
+
+use std::fmt;
+
+use borsh::BorshDeserialize;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{instruction::InstructionError, transaction::TransactionError};
+use solana_transaction_status::TransactionStatusMeta;
+
+use crate::{batch_verifier::PublicInputCountMismatch, VerifierError};
+
+/// Prefix of the lines `diagnostics::report` logs
+const DIAGNOSTIC_LOG_PREFIX: &str = "Program log: Diagnostic ";
+
+/// Why a transaction failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FailureCause {
+    /// One of the program's own errors, the `AltBn128*` syscall failures
+    /// among them
+    Verifier(VerifierError),
+    /// A custom code this client does not know, from a newer program or
+    /// another program in the transaction
+    UnknownCustom(u32),
+    /// A builtin error; `InvalidArgument` is a proof that does not verify
+    Instruction(InstructionError),
+    /// The transaction failed before any instruction ran, as for an expired
+    /// blockhash or an unfunded fee payer
+    Transaction(TransactionError),
+}
+
+impl FailureCause {
+    /// The cause of `error`, and the index of the instruction that failed if
+    /// one did
+    pub fn from_transaction_error(error: &TransactionError) -> (Option<u8>, Self) {
+        match error {
+            TransactionError::InstructionError(index, InstructionError::Custom(code)) => {
+                let cause = match VerifierError::try_from(*code) {
+                    Ok(error) => FailureCause::Verifier(error),
+                    Err(code) => FailureCause::UnknownCustom(code),
+                };
+                (Some(*index), cause)
+            }
+            TransactionError::InstructionError(index, error) => {
+                (Some(*index), FailureCause::Instruction(error.clone()))
+            }
+            other => (None, FailureCause::Transaction(other.clone())),
+        }
+    }
+
+    /// Whether an alt_bn128 syscall refused its input
+    pub fn is_syscall_failure(&self) -> bool {
+        matches!(
+            self,
+            FailureCause::Verifier(
+                VerifierError::AltBn128InvalidInput
+                    | VerifierError::AltBn128GroupError
+                    | VerifierError::AltBn128SliceOutOfBounds
+                    | VerifierError::AltBn128ResultConversion
+                    | VerifierError::AltBn128ProjectiveToG1
+                    | VerifierError::AltBn128Unexpected
+            )
+        )
+    }
+}
+
+impl fmt::Display for FailureCause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FailureCause::Verifier(error) if self.is_syscall_failure() => {
+                write!(
+                    f,
+                    "{:?} ({}, code {}; alt_bn128 syscall)",
+                    error, error, *error as u32
+                )
+            }
+            FailureCause::Verifier(error) => {
+                write!(f, "{:?} ({}, code {})", error, error, *error as u32)
+            }
+            FailureCause::UnknownCustom(code) => write!(f, "custom program error {}", code),
+            FailureCause::Instruction(error @ InstructionError::InvalidArgument) => {
+                write!(
+                    f,
+                    "{}; for a verify instruction, the proof did not verify",
+                    error
+                )
+            }
+            FailureCause::Instruction(error) => write!(f, "{}", error),
+            FailureCause::Transaction(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+/// Return data left by the failed transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FailureReport {
+    /// The batch statement whose input count the batch key does not take
+    PublicInputCount(PublicInputCountMismatch),
+    /// Return data this client does not decode, as the `DiagnosticsReport`
+    /// of a `diagnostics` build, whose log line `Diagnosis` reads instead
+    Raw { program_id: Pubkey, data: Vec<u8> },
+}
+
+/// A failed transaction, read back
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnosis {
+    /// Index of the failing instruction; `None` when the transaction failed
+    /// as a whole, or did not fail
+    pub instruction_index: Option<u8>,
+    /// `None` for a transaction that succeeded
+    pub cause: Option<FailureCause>,
+    pub report: Option<FailureReport>,
+    /// Number and message of the `Diagnostic` a `diagnostics` build logged
+    pub diagnostic: Option<(u8, String)>,
+    /// Everything above in one line
+    pub summary: String,
+}
+
+/// Read the failure out of a transaction's status metadata
+///
+/// Works on the metadata of any transaction, failed or not; nothing is
+/// fetched.
+pub fn diagnose_failure(meta: &TransactionStatusMeta) -> Diagnosis {
+    let (instruction_index, cause) = match &meta.status {
+        Ok(()) => (None, None),
+        Err(error) => {
+            let (index, cause) = FailureCause::from_transaction_error(error);
+            (index, Some(cause))
+        }
+    };
+
+    let report = meta.return_data.as_ref().and_then(|return_data| {
+        if return_data.data.is_empty() {
+            return None;
+        }
+        let decoded = match &cause {
+            Some(FailureCause::Verifier(VerifierError::PublicInputCountMismatch)) => {
+                PublicInputCountMismatch::try_from_slice(&return_data.data)
+                    .ok()
+                    .map(FailureReport::PublicInputCount)
+            }
+            _ => None,
+        };
+        Some(decoded.unwrap_or_else(|| FailureReport::Raw {
+            program_id: return_data.program_id,
+            data: return_data.data.clone(),
+        }))
+    });
+
+    let diagnostic = meta.log_messages.iter().flatten().find_map(|line| {
+        let rest = line.strip_prefix(DIAGNOSTIC_LOG_PREFIX)?;
+        let (number, _) = rest.split_once(':')?;
+        Some((
+            number.parse().ok()?,
+            line["Program log: ".len()..].to_string(),
+        ))
+    });
+
+    let mut summary = match (&cause, instruction_index) {
+        (None, _) => "transaction succeeded".to_string(),
+        (Some(cause), Some(index)) => format!("instruction {} failed: {}", index, cause),
+        (Some(cause), None) => format!("transaction failed: {}", cause),
+    };
+    if let Some(FailureReport::PublicInputCount(mismatch)) = &report {
+        summary.push_str(&format!(
+            "; batch statement {} has {} public inputs, the key takes {}",
+            mismatch.index, mismatch.got, mismatch.expected
+        ));
+    }
+    if let Some((_, message)) = &diagnostic {
+        summary.push_str("; ");
+        summary.push_str(message);
+    }
+
+    Diagnosis {
+        instruction_index,
+        cause,
+        report,
+        diagnostic,
+        summary,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use borsh::BorshSerialize;
+    use solana_sdk::transaction_context::TransactionReturnData;
+
+    use super::*;
+
+    fn failed(error: TransactionError, logs: &[&str]) -> TransactionStatusMeta {
+        TransactionStatusMeta {
+            status: Err(error),
+            log_messages: Some(logs.iter().map(|line| line.to_string()).collect()),
+            ..TransactionStatusMeta::default()
+        }
+    }
+
+    fn custom(code: u32) -> TransactionError {
+        TransactionError::InstructionError(1, InstructionError::Custom(code))
+    }
+
+    #[test]
+    fn test_custom_codes_map_to_verifier_errors() {
+        let diagnosis = diagnose_failure(&failed(custom(VerifierError::KeyRevoked as u32), &[]));
+        assert_eq!(diagnosis.instruction_index, Some(1));
+        assert_eq!(
+            diagnosis.cause,
+            Some(FailureCause::Verifier(VerifierError::KeyRevoked))
+        );
+        assert!(diagnosis
+            .summary
+            .starts_with("instruction 1 failed: KeyRevoked"));
+
+        // The syscall sub-codes are named as such
+        let diagnosis = diagnose_failure(&failed(custom(30), &[]));
+        let cause = diagnosis.cause.unwrap();
+        assert_eq!(
+            cause,
+            FailureCause::Verifier(VerifierError::AltBn128GroupError)
+        );
+        assert!(cause.is_syscall_failure());
+        assert!(diagnosis.summary.ends_with("alt_bn128 syscall)"));
+
+        let diagnosis = diagnose_failure(&failed(custom(9999), &[]));
+        assert_eq!(diagnosis.cause, Some(FailureCause::UnknownCustom(9999)));
+    }
+
+    #[test]
+    fn test_builtin_and_transaction_errors() {
+        let invalid = TransactionError::InstructionError(0, InstructionError::InvalidArgument);
+        let diagnosis = diagnose_failure(&failed(invalid, &[]));
+        assert_eq!(
+            diagnosis.cause,
+            Some(FailureCause::Instruction(InstructionError::InvalidArgument))
+        );
+        assert!(diagnosis.summary.contains("the proof did not verify"));
+
+        let diagnosis = diagnose_failure(&failed(TransactionError::BlockhashNotFound, &[]));
+        assert_eq!(diagnosis.instruction_index, None);
+        assert_eq!(
+            diagnosis.cause,
+            Some(FailureCause::Transaction(
+                TransactionError::BlockhashNotFound
+            ))
+        );
+
+        let succeeded = diagnose_failure(&TransactionStatusMeta::default());
+        assert_eq!(succeeded.cause, None);
+        assert_eq!(succeeded.summary, "transaction succeeded");
+    }
+
+    #[test]
+    fn test_reports_and_diagnostics_extracted() {
+        let program_id = Pubkey::new_unique();
+        let mismatch = PublicInputCountMismatch {
+            expected: 5,
+            got: 8,
+            index: 2,
+        };
+        let mut meta = failed(custom(VerifierError::PublicInputCountMismatch as u32), &[]);
+        meta.return_data = Some(TransactionReturnData {
+            program_id,
+            data: mismatch.try_to_vec().unwrap(),
+        });
+        let diagnosis = diagnose_failure(&meta);
+        assert_eq!(
+            diagnosis.report,
+            Some(FailureReport::PublicInputCount(mismatch))
+        );
+        assert!(diagnosis
+            .summary
+            .ends_with("batch statement 2 has 8 public inputs, the key takes 5"));
+
+        // A diagnostics build's report stays raw; its log line is read instead
+        let invalid = TransactionError::InstructionError(0, InstructionError::InvalidArgument);
+        let mut meta = failed(
+            invalid,
+            &[
+                "Program 11111111111111111111111111111111 invoke [1]",
+                "Program log: Diagnostic 5: proof B is not on the twist; check the Fq2 limb order",
+                "Program log: 0x5, 0x6553f100, 0x0, 0x0, 0x0",
+            ],
+        );
+        meta.return_data = Some(TransactionReturnData {
+            program_id,
+            data: vec![5; 42],
+        });
+        let diagnosis = diagnose_failure(&meta);
+        assert_eq!(
+            diagnosis.diagnostic,
+            Some((
+                5,
+                "Diagnostic 5: proof B is not on the twist; check the Fq2 limb order".to_string()
+            ))
+        );
+        assert!(matches!(diagnosis.report, Some(FailureReport::Raw { .. })));
+        assert!(diagnosis.summary.ends_with("check the Fq2 limb order"));
+    }
+}
@@ -0,0 +1,313 @@
+// Waiting out a receipt's finality
+// A receipt recorded in a slot that is later rolled back disappears with it,
+// so a service caching "paid" should wait for `PaymentReceipt::finalized`.
+// `await_receipt_finality` polls the receipt at `finalized` commitment until
+// the finalized slot reaches the receipt's slot plus the config's finality
+// depth, then submits `FinalizeReceipt` with `submit_with_retry`.
+
+use std::{future::Future, time::Duration};
+
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{commitment_config::CommitmentLevel, signature::Signer};
+
+use super::{
+    finalize_receipt_instruction, load_config,
+    submit::{submit_with_retry, RpcLike, SubmitOptions, SubmitOutcome},
+    ClientError, FetchedAccount,
+};
+use crate::{
+    pda,
+    state::{PaymentReceipt, StateAccount},
+};
+
+/// The reads `await_receipt_finality` makes on top of `RpcLike`
+pub trait FinalityRpc: RpcLike {
+    /// Fetch an account as of `level`, `None` if it does not exist there
+    fn get_account_at(
+        &self,
+        address: &Pubkey,
+        level: CommitmentLevel,
+    ) -> impl Future<Output = Result<Option<FetchedAccount>, ClientError>>;
+
+    /// The latest slot to reach `level`
+    fn get_slot(&self, level: CommitmentLevel) -> impl Future<Output = Result<u64, ClientError>>;
+
+    /// Wait `duration` between polls
+    fn pause(&self, duration: Duration) -> impl Future<Output = ()>;
+}
+
+/// Knobs for `await_receipt_finality`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FinalityOptions {
+    /// Wait between polls
+    pub poll_interval: Duration,
+    /// Polls before giving up
+    pub max_polls: u32,
+    /// How the `FinalizeReceipt` transaction is sent
+    pub submit: SubmitOptions,
+}
+
+impl Default for FinalityOptions {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(2),
+            max_polls: 60,
+            submit: SubmitOptions::default(),
+        }
+    }
+}
+
+/// How waiting for a receipt's finality ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FinalityOutcome {
+    /// `FinalizeReceipt` was sent; `Confirmed` means the receipt is final
+    Submitted(SubmitOutcome),
+    /// The receipt was already marked final
+    AlreadyFinal,
+    /// The receipt is gone even at `processed`: its slot was rolled back, or
+    /// it was never recorded
+    Vanished,
+    /// The polls ran out before the receipt's slot was deep enough
+    TimedOut,
+}
+
+/// Wait until the receipt at `receipt_address` can no longer be rolled back,
+/// then mark it final, paid for by `signer`
+pub async fn await_receipt_finality(
+    rpc: &impl FinalityRpc,
+    signer: &impl Signer,
+    program_id: &Pubkey,
+    receipt_address: &Pubkey,
+    opts: &FinalityOptions,
+) -> Result<FinalityOutcome, ClientError> {
+    let (config_address, _) = pda::config_address(program_id);
+    for poll in 0..opts.max_polls {
+        if poll > 0 {
+            rpc.pause(opts.poll_interval).await;
+        }
+
+        let Some(account) = rpc.get_account_at(receipt_address, CommitmentLevel::Finalized).await?
+        else {
+            // Not rooted yet; only a receipt the node still sees is worth waiting for
+            let seen = rpc.get_account_at(receipt_address, CommitmentLevel::Processed).await?;
+            if seen.is_none() {
+                return Ok(FinalityOutcome::Vanished);
+            }
+            continue;
+        };
+        if account.owner != *program_id {
+            return Err(ClientError::MalformedPayload("receipt"));
+        }
+        let receipt = PaymentReceipt::unpack(&account.data)
+            .map_err(|_| ClientError::MalformedPayload("receipt"))?;
+        if receipt.finalized {
+            return Ok(FinalityOutcome::AlreadyFinal);
+        }
+
+        let config = rpc.get_account_at(&config_address, CommitmentLevel::Finalized).await?;
+        let config = load_config(config, &config_address, program_id)
+            .map_err(|_| ClientError::MalformedPayload("config"))?;
+        let slot = rpc.get_slot(CommitmentLevel::Finalized).await?;
+        if config.receipt_final_at(receipt.slot, slot) {
+            let instruction =
+                finalize_receipt_instruction(program_id, &signer.pubkey(), receipt.proof_hash);
+            let outcome = submit_with_retry(rpc, &[instruction], signer, &opts.submit).await?;
+            return Ok(FinalityOutcome::Submitted(outcome));
+        }
+    }
+    Ok(FinalityOutcome::TimedOut)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use borsh::BorshSerialize;
+    use solana_sdk::{
+        hash::Hash,
+        signature::{Keypair, Signature},
+        transaction::{Transaction, TransactionError},
+    };
+
+    use super::*;
+    use crate::{
+        state::{
+            AdminSigners, InputPolicy, ReceiptStatus, VerifierConfig, DEFAULT_FINALITY_SLOTS,
+        },
+        VerifierInstruction,
+    };
+
+    /// Cluster whose finalized slot advances by 10 each poll; the receipt is
+    /// rooted from `rooted_from` and `None` means it was rolled back
+    struct MockCluster {
+        program_id: Pubkey,
+        receipt: Option<PaymentReceipt>,
+        rooted_from: u64,
+        slot: RefCell<u64>,
+        sent: RefCell<Vec<Transaction>>,
+    }
+
+    impl MockCluster {
+        fn new(receipt: Option<PaymentReceipt>) -> Self {
+            Self {
+                program_id: Pubkey::new_unique(),
+                receipt,
+                rooted_from: 110,
+                slot: RefCell::new(100),
+                sent: RefCell::new(Vec::new()),
+            }
+        }
+
+        fn owned(&self, data: Vec<u8>) -> Option<FetchedAccount> {
+            Some(FetchedAccount {
+                owner: self.program_id,
+                lamports: 1,
+                data,
+            })
+        }
+    }
+
+    impl RpcLike for MockCluster {
+        async fn get_latest_blockhash(&self) -> Result<(Hash, u64), ClientError> {
+            Ok((Hash::new_unique(), 1000))
+        }
+
+        async fn simulate(
+            &self,
+            _transaction: &Transaction,
+        ) -> Result<Result<u64, TransactionError>, ClientError> {
+            Ok(Ok(10_000))
+        }
+
+        async fn send(
+            &self,
+            transaction: &Transaction,
+        ) -> Result<Result<(), TransactionError>, ClientError> {
+            self.sent.borrow_mut().push(transaction.clone());
+            Ok(Ok(()))
+        }
+
+        async fn confirm(
+            &self,
+            _signature: &Signature,
+            _level: CommitmentLevel,
+            _last_valid_block_height: u64,
+        ) -> Result<Option<Result<(), TransactionError>>, ClientError> {
+            Ok(Some(Ok(())))
+        }
+    }
+
+    impl FinalityRpc for MockCluster {
+        async fn get_account_at(
+            &self,
+            address: &Pubkey,
+            level: CommitmentLevel,
+        ) -> Result<Option<FetchedAccount>, ClientError> {
+            if *address == pda::config_address(&self.program_id).0 {
+                return Ok(self.owned(config(&self.program_id).to_account_data()));
+            }
+            let rooted = *self.slot.borrow() >= self.rooted_from;
+            Ok(match &self.receipt {
+                Some(receipt) if rooted || level != CommitmentLevel::Finalized => {
+                    self.owned(receipt.to_account_data())
+                }
+                _ => None,
+            })
+        }
+
+        async fn get_slot(&self, _level: CommitmentLevel) -> Result<u64, ClientError> {
+            Ok(*self.slot.borrow())
+        }
+
+        async fn pause(&self, _duration: Duration) {
+            *self.slot.borrow_mut() += 10;
+        }
+    }
+
+    fn config(program_id: &Pubkey) -> VerifierConfig {
+        VerifierConfig {
+            admin: Pubkey::new_unique(),
+            pending_admin: Pubkey::default(),
+            recipient_set_root: [0u8; 32],
+            previous_recipient_set_root: [0u8; 32],
+            previous_root_valid_until: 0,
+            recipient_root_grace_seconds: 0,
+            epoch_length_seconds: 0,
+            input_policy: InputPolicy::default(),
+            max_receipt_age_slots: 0,
+            bump: pda::config_address(program_id).1,
+            audit_enabled: false,
+            max_key_deprecation_slots: 0,
+            admin_signers: AdminSigners::default(),
+            allow_invoice_overpayment: false,
+            max_verifications_per_window: 0,
+            window_slots: 0,
+            finality_slots: 0,
+            scratch_ttl_slots: 0,
+            track_relayer: false,
+        }
+    }
+
+    fn receipt(finalized: bool) -> PaymentReceipt {
+        PaymentReceipt {
+            proof_hash: [7u8; 32],
+            recipient: [4u8; 32],
+            min_amount: 1000,
+            invoice_id: [0u8; 32],
+            payer: Pubkey::new_unique(),
+            status: ReceiptStatus::Active,
+            slot: 100,
+            bump: 255,
+            consumable: false,
+            delegate: Pubkey::default(),
+            finalized,
+            revealed_amount: 0,
+            relayer: Pubkey::default(),
+            compute_unit_price: 0,
+            rebate_claimed: false,
+        }
+    }
+
+    async fn wait(rpc: &MockCluster, max_polls: u32) -> FinalityOutcome {
+        let opts = FinalityOptions {
+            max_polls,
+            ..FinalityOptions::default()
+        };
+        let address = pda::receipt_address(&rpc.program_id, &[7u8; 32]).0;
+        await_receipt_finality(rpc, &Keypair::new(), &rpc.program_id, &address, &opts)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_finalizes_once_deep_enough() {
+        let rpc = MockCluster::new(Some(receipt(false)));
+        let outcome = wait(&rpc, 10).await;
+
+        let sent = rpc.sent.borrow();
+        assert_eq!(sent.len(), 1);
+        let confirmed = SubmitOutcome::Confirmed(sent[0].signatures[0]);
+        assert_eq!(outcome, FinalityOutcome::Submitted(confirmed));
+        // Sent at the first poll with the finalized slot past 100 plus the depth
+        assert_eq!(*rpc.slot.borrow(), 100 + DEFAULT_FINALITY_SLOTS.div_ceil(10) * 10);
+        let finalize = sent[0].message.instructions.last().unwrap();
+        let expected = VerifierInstruction::FinalizeReceipt {
+            receipt_proof_hash: [7u8; 32],
+        };
+        assert_eq!(finalize.data, expected.try_to_vec().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_finality_wait_ends_without_sending() {
+        // Too few polls to reach the depth, already final, rolled back
+        for (receipt, max_polls, expected) in [
+            (Some(receipt(false)), 2, FinalityOutcome::TimedOut),
+            (Some(receipt(true)), 10, FinalityOutcome::AlreadyFinal),
+            (None, 10, FinalityOutcome::Vanished),
+        ] {
+            let rpc = MockCluster::new(receipt);
+            assert_eq!(wait(&rpc, max_polls).await, expected);
+            assert!(rpc.sent.borrow().is_empty());
+        }
+    }
+}
@@ -0,0 +1,326 @@
+// Counters for relayer operators
+// `submit_with_retry`, `plan_batches`, `preflight` and `VerificationCache`
+// report what they do to a `VerifierMetrics`. Each counter is labeled with the
+// `VerifierInstruction` it concerns and, for failures, the program's custom
+// error code (a `VerifierError` for this program). Every method defaults to a
+// no-op, and without the `metrics` feature the helpers only ever report to
+// `NoopMetrics`, so the calls compile away. The `prometheus` feature adds
+// `PrometheusMetrics`, which registers the counters with a registry.
+
+use std::sync::Arc;
+
+use solana_program::program_error::ProgramError;
+
+/// Label of verifications the cache runs, which belong to no instruction
+pub const OFFCHAIN_LABEL: &str = "offchain";
+
+/// Where the client helpers report what they did
+///
+/// Implementations are shared across request handlers, so they count through
+/// `&self`.
+pub trait VerifierMetrics: Send + Sync {
+    /// A proof verified: `instruction` confirmed on chain, or a pairing the
+    /// cache ran passed
+    fn proof_verified(&self, _instruction: &str) {}
+
+    /// A batch of `proofs` proofs was planned for `instruction`
+    fn batch_planned(&self, _instruction: &str, _proofs: usize) {}
+
+    /// A simulation of `instruction` consumed `units` compute units
+    fn compute_units(&self, _instruction: &str, _units: u64) {}
+
+    /// `instruction` failed, or would fail, with custom error `code`; `None`
+    /// for failures that are not the program's, such as an expired blockhash
+    fn failure(&self, _instruction: &str, _code: Option<u32>) {}
+
+    /// A `VerificationCache` lookup was answered from the cache, or missed
+    fn cache_lookup(&self, _hit: bool) {}
+}
+
+/// Counts nothing
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopMetrics;
+
+impl VerifierMetrics for NoopMetrics {}
+
+/// One set of counters shared, e.g. by a cache and the submitting task
+impl<M: VerifierMetrics + ?Sized> VerifierMetrics for Arc<M> {
+    fn proof_verified(&self, instruction: &str) {
+        (**self).proof_verified(instruction)
+    }
+
+    fn batch_planned(&self, instruction: &str, proofs: usize) {
+        (**self).batch_planned(instruction, proofs)
+    }
+
+    fn compute_units(&self, instruction: &str, units: u64) {
+        (**self).compute_units(instruction, units)
+    }
+
+    fn failure(&self, instruction: &str, code: Option<u32>) {
+        (**self).failure(instruction, code)
+    }
+
+    fn cache_lookup(&self, hit: bool) {
+        (**self).cache_lookup(hit)
+    }
+}
+
+/// The custom error code of `error`, if it has one
+pub fn error_code(error: &ProgramError) -> Option<u32> {
+    match error {
+        ProgramError::Custom(code) => Some(*code),
+        _ => None,
+    }
+}
+
+#[cfg(feature = "prometheus")]
+pub use self::prometheus_metrics::PrometheusMetrics;
+
+#[cfg(feature = "prometheus")]
+mod prometheus_metrics {
+    use prometheus::{IntCounterVec, Opts, Registry};
+
+    use super::VerifierMetrics;
+
+    /// `VerifierMetrics` as Prometheus counters, all prefixed `x402_zk_verifier_`
+    #[derive(Debug, Clone)]
+    pub struct PrometheusMetrics {
+        proofs_verified: IntCounterVec,
+        batches_planned: IntCounterVec,
+        batch_proofs: IntCounterVec,
+        compute_units: IntCounterVec,
+        failures: IntCounterVec,
+        cache_lookups: IntCounterVec,
+    }
+
+    impl PrometheusMetrics {
+        /// Create the counters and register them with `registry`
+        pub fn register(registry: &Registry) -> prometheus::Result<Self> {
+            let counter = |name: &str, help: &str, labels: &[&str]| {
+                let counter = IntCounterVec::new(
+                    Opts::new(name, help).namespace("x402_zk_verifier"),
+                    labels,
+                )?;
+                registry.register(Box::new(counter.clone()))?;
+                Ok::<_, prometheus::Error>(counter)
+            };
+            Ok(Self {
+                proofs_verified: counter(
+                    "proofs_verified_total",
+                    "Proofs verified on chain or by the cache",
+                    &["instruction"],
+                )?,
+                batches_planned: counter(
+                    "batches_planned_total",
+                    "Batches planned for submission",
+                    &["instruction"],
+                )?,
+                batch_proofs: counter(
+                    "batch_proofs_total",
+                    "Proofs in planned batches",
+                    &["instruction"],
+                )?,
+                compute_units: counter(
+                    "compute_units_total",
+                    "Compute units consumed in simulations",
+                    &["instruction"],
+                )?,
+                failures: counter(
+                    "failures_total",
+                    "Failures by custom error code, or \"none\"",
+                    &["instruction", "code"],
+                )?,
+                cache_lookups: counter(
+                    "cache_lookups_total",
+                    "Verification cache lookups by result",
+                    &["result"],
+                )?,
+            })
+        }
+    }
+
+    impl VerifierMetrics for PrometheusMetrics {
+        fn proof_verified(&self, instruction: &str) {
+            self.proofs_verified.with_label_values(&[instruction]).inc();
+        }
+
+        fn batch_planned(&self, instruction: &str, proofs: usize) {
+            self.batches_planned.with_label_values(&[instruction]).inc();
+            self.batch_proofs.with_label_values(&[instruction]).inc_by(proofs as u64);
+        }
+
+        fn compute_units(&self, instruction: &str, units: u64) {
+            self.compute_units.with_label_values(&[instruction]).inc_by(units);
+        }
+
+        fn failure(&self, instruction: &str, code: Option<u32>) {
+            let code = code.map_or_else(|| "none".to_string(), |code| code.to_string());
+            self.failures.with_label_values(&[instruction, &code]).inc();
+        }
+
+        fn cache_lookup(&self, hit: bool) {
+            let result = if hit { "hit" } else { "miss" };
+            self.cache_lookups.with_label_values(&[result]).inc();
+        }
+    }
+}
+
+/// Counters kept in a map, keyed `name/label,label`
+#[cfg(all(test, feature = "metrics"))]
+#[derive(Debug, Default)]
+pub(crate) struct RecordingMetrics {
+    counts: std::sync::Mutex<std::collections::HashMap<String, u64>>,
+}
+
+#[cfg(all(test, feature = "metrics"))]
+impl RecordingMetrics {
+    pub(crate) fn count(&self, key: &str) -> u64 {
+        self.counts.lock().unwrap().get(key).copied().unwrap_or(0)
+    }
+
+    fn add(&self, key: String, n: u64) {
+        *self.counts.lock().unwrap().entry(key).or_default() += n;
+    }
+}
+
+#[cfg(all(test, feature = "metrics"))]
+impl VerifierMetrics for RecordingMetrics {
+    fn proof_verified(&self, instruction: &str) {
+        self.add(format!("proofs_verified/{instruction}"), 1);
+    }
+
+    fn batch_planned(&self, instruction: &str, proofs: usize) {
+        self.add(format!("batches_planned/{instruction}"), 1);
+        self.add(format!("batch_proofs/{instruction}"), proofs as u64);
+    }
+
+    fn compute_units(&self, instruction: &str, units: u64) {
+        self.add(format!("compute_units/{instruction}"), units);
+    }
+
+    fn failure(&self, instruction: &str, code: Option<u32>) {
+        self.add(format!("failures/{instruction},{code:?}"), 1);
+    }
+
+    fn cache_lookup(&self, hit: bool) {
+        self.add(format!("cache_lookups/{hit}"), 1);
+    }
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use std::{future::Future, time::Duration};
+
+    use solana_program::{clock::Clock, pubkey::Pubkey};
+
+    use super::*;
+    use crate::{
+        client::{
+            plan_batches_with_metrics, preflight_with_metrics, AccountFetcher, BatchPlanOptions,
+            ClientError, FetchedAccount, VerificationCache,
+        },
+        CircuitId, G1Point, G2Point, Groth16Proof, PaymentPublicInputs, SubscriptionPublicInputs,
+        VerifierError,
+    };
+
+    /// Rejected before the pairing, with a known code
+    fn non_canonical_proof() -> Groth16Proof {
+        Groth16Proof {
+            a: G1Point::new([0xff; 64]),
+            b: G2Point::new([0u8; 128]),
+            c: G1Point::new([0u8; 64]),
+        }
+    }
+
+    fn payment_inputs(min_amount: u64) -> PaymentPublicInputs {
+        PaymentPublicInputs {
+            min_amount,
+            recipient_pubkey: [4u8; 32],
+            max_block_age: 60,
+            current_time: 1700000000,
+        }
+    }
+
+    #[test]
+    fn test_plan_batches_counts_batches_and_proofs() {
+        let proofs: Vec<_> = (0..5)
+            .map(|n| (non_canonical_proof(), payment_inputs(1000000 + n)))
+            .collect();
+        let opts = BatchPlanOptions::new(Pubkey::new_unique(), Pubkey::new_unique());
+        let metrics = RecordingMetrics::default();
+        let plans = plan_batches_with_metrics(&proofs, opts, &metrics);
+
+        assert_eq!(metrics.count("batches_planned/VerifyBatch"), plans.len() as u64);
+        assert_eq!(metrics.count("batch_proofs/VerifyBatch"), 5);
+        assert_eq!(metrics.count("batches_planned/VerifyBatchBuffered"), 0);
+    }
+
+    #[test]
+    fn test_cache_counts_lookups_and_verifications() {
+        let metrics = Arc::new(RecordingMetrics::default());
+        let cache = VerificationCache::with_time_source(4, Duration::from_secs(60), || {
+            Duration::ZERO
+        })
+        .with_metrics(metrics.clone());
+        let vk = CircuitId::Payment.verification_key();
+
+        // Verified once, then answered from the cache
+        for _ in 0..3 {
+            assert!(!cache.verify_cached(vk, &non_canonical_proof(), &payment_inputs(1000000)));
+        }
+        assert_eq!(metrics.count("cache_lookups/false"), 1);
+        assert_eq!(metrics.count("cache_lookups/true"), 2);
+        let code = VerifierError::NonCanonicalEncoding as u32;
+        assert_eq!(metrics.count(&format!("failures/offchain,Some({code})")), 1);
+        assert_eq!(metrics.count("proofs_verified/offchain"), 0);
+    }
+
+    /// A cluster with no accounts at all
+    struct EmptyCluster;
+
+    impl AccountFetcher for EmptyCluster {
+        fn get_account(
+            &self,
+            _address: &Pubkey,
+        ) -> impl Future<Output = Result<Option<FetchedAccount>, ClientError>> {
+            async { Ok(None) }
+        }
+
+        fn get_clock(&self) -> impl Future<Output = Result<Clock, ClientError>> {
+            async { Ok(Clock::default()) }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_preflight_counts_first_failure() {
+        let metrics = RecordingMetrics::default();
+        let inputs = SubscriptionPublicInputs {
+            min_amount: 1000000,
+            recipient_pubkey: [4u8; 32],
+            epoch: 0,
+            nullifier: [7u8; 32],
+        };
+        let program_id = Pubkey::new_unique();
+        let proof = non_canonical_proof();
+        let report = preflight_with_metrics(&EmptyCluster, &program_id, &proof, &inputs, &metrics)
+            .await
+            .unwrap();
+
+        // The missing config is what the program would report, and it has no
+        // custom code; the proof's own failure is not counted
+        assert_eq!(report.first_failure().map(|(_, error)| error_code(error)), Some(None));
+        assert_eq!(metrics.count("failures/VerifySubscription,None"), 1);
+        let code = VerifierError::NonCanonicalEncoding as u32;
+        assert_eq!(metrics.count(&format!("failures/VerifySubscription,Some({code})")), 0);
+    }
+
+    #[test]
+    fn test_error_code() {
+        assert_eq!(
+            error_code(&VerifierError::NullifierAlreadySpent.into()),
+            Some(VerifierError::NullifierAlreadySpent as u32)
+        );
+        assert_eq!(error_code(&ProgramError::InvalidSeeds), None);
+    }
+}
@@ -0,0 +1,579 @@
+// Transaction submission for relayers
+// `submit_with_retry` sizes the compute budget from a simulation, then sends
+// and confirms, re-signing against a fresh blockhash each time the previous
+// one expires and raising the priority fee per attempt. The cluster is reached
+// through `RpcLike`, so the retry logic runs against a mock in tests.
+
+use std::future::Future;
+
+#[cfg(feature = "metrics")]
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use solana_sdk::{
+    commitment_config::CommitmentLevel,
+    compute_budget::ComputeBudgetInstruction,
+    hash::Hash,
+    instruction::{Instruction, InstructionError},
+    signature::{Signature, Signer},
+    transaction::{Transaction, TransactionError},
+};
+
+use super::{
+    check_transaction_size,
+    metrics::{NoopMetrics, VerifierMetrics},
+    ClientError,
+};
+#[cfg(feature = "metrics")]
+use crate::VerifierInstruction;
+
+/// Compute unit limit of a transaction, and of the estimating simulation
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+
+/// Headroom over the simulated compute units, in percent
+const COMPUTE_UNIT_MARGIN_PERCENT: u64 = 10;
+
+/// The calls `submit_with_retry` makes to a cluster
+pub trait RpcLike {
+    /// Latest blockhash and the last block height it is valid at
+    fn get_latest_blockhash(&self) -> impl Future<Output = Result<(Hash, u64), ClientError>>;
+
+    /// Simulate `transaction`, returning the compute units it consumed
+    fn simulate(
+        &self,
+        transaction: &Transaction,
+    ) -> impl Future<Output = Result<Result<u64, TransactionError>, ClientError>>;
+
+    /// Send `transaction` without preflight; `Err` if the node refused it
+    fn send(
+        &self,
+        transaction: &Transaction,
+    ) -> impl Future<Output = Result<Result<(), TransactionError>, ClientError>>;
+
+    /// Wait for `signature` to reach `level`, returning its result, or `None`
+    /// once the block height passes `last_valid_block_height` without it
+    fn confirm(
+        &self,
+        signature: &Signature,
+        level: CommitmentLevel,
+        last_valid_block_height: u64,
+    ) -> impl Future<Output = Result<Option<Result<(), TransactionError>>, ClientError>>;
+}
+
+/// Knobs for `submit_with_retry`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubmitOptions {
+    /// Attempts after the first, each on a fresh blockhash
+    pub max_retries: u32,
+    /// Priority fee of each attempt in micro-lamports per compute unit; the
+    /// last entry repeats, and an empty schedule pays none
+    pub cu_price_schedule: Vec<u64>,
+    /// Commitment an attempt must reach to count as confirmed
+    pub confirm_level: CommitmentLevel,
+}
+
+impl Default for SubmitOptions {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            cu_price_schedule: vec![10_000, 50_000, 250_000, 1_000_000],
+            confirm_level: CommitmentLevel::Confirmed,
+        }
+    }
+}
+
+impl SubmitOptions {
+    fn cu_price(&self, attempt: usize) -> u64 {
+        let last = self.cu_price_schedule.len().saturating_sub(1);
+        self.cu_price_schedule.get(attempt.min(last)).copied().unwrap_or(0)
+    }
+}
+
+/// How a submission ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubmitOutcome {
+    Confirmed(Signature),
+    /// The node had already processed this exact transaction
+    AlreadyProcessed(Signature),
+    /// The program refused the transaction with this custom error code
+    /// (usually a `VerifierError`); retrying would not help
+    ProofRejected(u32),
+    /// Every attempt's blockhash expired before it landed
+    Expired,
+}
+
+/// Send `instructions` signed and paid for by `signer` until one attempt
+/// confirms, the program refuses them, or the retries run out
+///
+/// The compute unit limit comes from one simulation up front, which also
+/// catches a rejected proof before any fee is paid. A transaction too large
+/// to send fails with `ClientError::TransactionTooLarge` before any call.
+pub async fn submit_with_retry(
+    rpc: &impl RpcLike,
+    instructions: &[Instruction],
+    signer: &impl Signer,
+    opts: &SubmitOptions,
+) -> Result<SubmitOutcome, ClientError> {
+    submit(rpc, instructions, signer, opts, &NoopMetrics, "").await
+}
+
+/// `submit_with_retry`, counting the simulated compute units and the outcome
+///
+/// Counters are labeled with the variant name of the last of `instructions`
+/// that is a `VerifierInstruction`, or "unknown" when none is.
+#[cfg(feature = "metrics")]
+pub async fn submit_with_retry_with_metrics(
+    rpc: &impl RpcLike,
+    instructions: &[Instruction],
+    signer: &impl Signer,
+    opts: &SubmitOptions,
+    metrics: &impl VerifierMetrics,
+) -> Result<SubmitOutcome, ClientError> {
+    let label = instruction_label(instructions);
+    let outcome = submit(rpc, instructions, signer, opts, metrics, &label).await;
+    match &outcome {
+        Ok(SubmitOutcome::Confirmed(_)) => metrics.proof_verified(&label),
+        Ok(SubmitOutcome::ProofRejected(code)) => metrics.failure(&label, Some(*code)),
+        Ok(SubmitOutcome::Expired) | Err(ClientError::TransactionFailed(_)) => {
+            metrics.failure(&label, None)
+        }
+        // Landed by an earlier submission; RPC errors are not the program's
+        Ok(SubmitOutcome::AlreadyProcessed(_)) | Err(_) => {}
+    }
+    outcome
+}
+
+/// `submit_with_retry`, reporting the simulated compute units under `label`
+async fn submit(
+    rpc: &impl RpcLike,
+    instructions: &[Instruction],
+    signer: &impl Signer,
+    opts: &SubmitOptions,
+    metrics: &impl VerifierMetrics,
+    label: &str,
+) -> Result<SubmitOutcome, ClientError> {
+    // Every attempt is this size or smaller, so one that cannot fit is
+    // refused before the first RPC call
+    let max_cu_price = opts.cu_price_schedule.iter().copied().max().unwrap_or(0);
+    let largest = with_budget(instructions, MAX_COMPUTE_UNIT_LIMIT, max_cu_price);
+    check_transaction_size(&largest, &signer.pubkey())?;
+
+    let (blockhash, _) = rpc.get_latest_blockhash().await?;
+    let estimate = transaction(instructions, signer, MAX_COMPUTE_UNIT_LIMIT, 0, blockhash);
+    let units = match rpc.simulate(&estimate).await? {
+        Ok(units) => units,
+        Err(error) => return rejected(error),
+    };
+    metrics.compute_units(label, units);
+    let compute_unit_limit = (units + units * COMPUTE_UNIT_MARGIN_PERCENT / 100)
+        .min(MAX_COMPUTE_UNIT_LIMIT as u64) as u32;
+
+    for attempt in 0..=opts.max_retries as usize {
+        let (blockhash, last_valid_block_height) = rpc.get_latest_blockhash().await?;
+        let transaction = transaction(
+            instructions,
+            signer,
+            compute_unit_limit,
+            opts.cu_price(attempt),
+            blockhash,
+        );
+        let signature = transaction.signatures[0];
+
+        match rpc.send(&transaction).await? {
+            Ok(()) => {}
+            Err(TransactionError::AlreadyProcessed) => {
+                return Ok(SubmitOutcome::AlreadyProcessed(signature))
+            }
+            Err(TransactionError::BlockhashNotFound) => continue,
+            Err(error) => return rejected(error),
+        }
+        match rpc
+            .confirm(&signature, opts.confirm_level, last_valid_block_height)
+            .await?
+        {
+            Some(Ok(())) => return Ok(SubmitOutcome::Confirmed(signature)),
+            Some(Err(error)) => return rejected(error),
+            None => {}
+        }
+    }
+    Ok(SubmitOutcome::Expired)
+}
+
+/// Variant name of the last of `instructions` whose data is a
+/// `VerifierInstruction`, or "unknown"
+///
+/// Data only counts when it re-encodes to the same bytes, so another
+/// program's instruction that happens to parse is not taken for one.
+#[cfg(feature = "metrics")]
+fn instruction_label(instructions: &[Instruction]) -> String {
+    instructions
+        .iter()
+        .rev()
+        .find_map(|instruction| {
+            let decoded = VerifierInstruction::try_from_slice(&instruction.data).ok()?;
+            if decoded.try_to_vec().ok()? != instruction.data {
+                return None;
+            }
+            let name = format!("{:?}", decoded);
+            let end = name.find(|c: char| !c.is_alphanumeric()).unwrap_or(name.len());
+            Some(name[..end].to_string())
+        })
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn transaction(
+    instructions: &[Instruction],
+    signer: &impl Signer,
+    compute_unit_limit: u32,
+    cu_price: u64,
+    blockhash: Hash,
+) -> Transaction {
+    let with_budget = with_budget(instructions, compute_unit_limit, cu_price);
+    Transaction::new_signed_with_payer(&with_budget, Some(&signer.pubkey()), &[signer], blockhash)
+}
+
+/// `instructions` after the compute budget instructions of an attempt
+fn with_budget(
+    instructions: &[Instruction],
+    compute_unit_limit: u32,
+    cu_price: u64,
+) -> Vec<Instruction> {
+    let mut with_budget =
+        vec![ComputeBudgetInstruction::set_compute_unit_limit(compute_unit_limit)];
+    if cu_price > 0 {
+        with_budget.push(ComputeBudgetInstruction::set_compute_unit_price(cu_price));
+    }
+    with_budget.extend_from_slice(instructions);
+    with_budget
+}
+
+/// A program's custom error as `ProofRejected`, anything else as an error
+fn rejected(error: TransactionError) -> Result<SubmitOutcome, ClientError> {
+    match error {
+        TransactionError::InstructionError(_, InstructionError::Custom(code)) => {
+            Ok(SubmitOutcome::ProofRejected(code))
+        }
+        other => Err(ClientError::TransactionFailed(other.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, collections::VecDeque};
+
+    use solana_sdk::{instruction::AccountMeta, pubkey::Pubkey, signature::Keypair};
+
+    use super::*;
+    use crate::{
+        client::{serialized_transaction_size, SizeSuggestion, MAX_TRANSACTION_SIZE},
+        VerifierError,
+    };
+
+    /// Scripted cluster: each blockhash is fresh, and confirmations come
+    /// from `confirmations` in order
+    struct MockRpc {
+        simulation: Result<u64, TransactionError>,
+        send_results: RefCell<VecDeque<Result<(), TransactionError>>>,
+        confirmations: RefCell<VecDeque<Option<Result<(), TransactionError>>>>,
+        blockhashes: RefCell<u8>,
+        sent: RefCell<Vec<Transaction>>,
+    }
+
+    impl MockRpc {
+        fn new(confirmations: Vec<Option<Result<(), TransactionError>>>) -> Self {
+            Self {
+                simulation: Ok(200_000),
+                send_results: RefCell::new(VecDeque::new()),
+                confirmations: RefCell::new(confirmations.into()),
+                blockhashes: RefCell::new(0),
+                sent: RefCell::new(Vec::new()),
+            }
+        }
+    }
+
+    impl RpcLike for MockRpc {
+        async fn get_latest_blockhash(&self) -> Result<(Hash, u64), ClientError> {
+            let mut count = self.blockhashes.borrow_mut();
+            *count += 1;
+            Ok((Hash::new_from_array([*count; 32]), 100 * *count as u64))
+        }
+
+        async fn simulate(
+            &self,
+            _transaction: &Transaction,
+        ) -> Result<Result<u64, TransactionError>, ClientError> {
+            Ok(self.simulation.clone())
+        }
+
+        async fn send(
+            &self,
+            transaction: &Transaction,
+        ) -> Result<Result<(), TransactionError>, ClientError> {
+            self.sent.borrow_mut().push(transaction.clone());
+            Ok(self.send_results.borrow_mut().pop_front().unwrap_or(Ok(())))
+        }
+
+        async fn confirm(
+            &self,
+            _signature: &Signature,
+            _level: CommitmentLevel,
+            _last_valid_block_height: u64,
+        ) -> Result<Option<Result<(), TransactionError>>, ClientError> {
+            Ok(self.confirmations.borrow_mut().pop_front().unwrap_or(None))
+        }
+    }
+
+    fn instructions() -> Vec<Instruction> {
+        vec![Instruction::new_with_bytes(
+            Pubkey::new_unique(),
+            &[1, 2, 3],
+            vec![AccountMeta::new_readonly(Pubkey::new_unique(), false)],
+        )]
+    }
+
+    /// Compute budget instruction data of a sent transaction, in order
+    fn budget(transaction: &Transaction) -> Vec<Vec<u8>> {
+        let message = &transaction.message;
+        message
+            .instructions
+            .iter()
+            .filter(|ix| {
+                message.account_keys[ix.program_id_index as usize]
+                    == solana_sdk::compute_budget::id()
+            })
+            .map(|ix| ix.data.clone())
+            .collect()
+    }
+
+    fn cu_price(price: u64) -> Vec<u8> {
+        ComputeBudgetInstruction::set_compute_unit_price(price).data
+    }
+
+    #[tokio::test]
+    async fn test_confirmed_first_attempt() {
+        let rpc = MockRpc::new(vec![Some(Ok(()))]);
+        let signer = Keypair::new();
+        let outcome = submit_with_retry(&rpc, &instructions(), &signer, &SubmitOptions::default())
+            .await
+            .unwrap();
+
+        let sent = rpc.sent.borrow();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(outcome, SubmitOutcome::Confirmed(sent[0].signatures[0]));
+        // 200k simulated units plus the margin, at the first price step
+        assert_eq!(
+            budget(&sent[0]),
+            vec![
+                ComputeBudgetInstruction::set_compute_unit_limit(220_000).data,
+                cu_price(10_000),
+            ]
+        );
+        assert_eq!(sent[0].message.instructions.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_transaction_refused_before_any_call() {
+        let rpc = MockRpc::new(vec![Some(Ok(()))]);
+        let signer = Keypair::new();
+        let instruction = |len: usize| {
+            Instruction::new_with_bytes(Pubkey::new_unique(), &vec![0u8; len], vec![])
+        };
+        // The budget instructions at their largest count against the limit
+        let fits = with_budget(&[instruction(1000)], MAX_COMPUTE_UNIT_LIMIT, 1);
+        let size = serialized_transaction_size(&fits, &signer.pubkey());
+        let oversized = instruction(1000 + MAX_TRANSACTION_SIZE + 1 - size);
+
+        let outcome =
+            submit_with_retry(&rpc, &[oversized], &signer, &SubmitOptions::default()).await;
+        assert!(matches!(
+            outcome,
+            Err(ClientError::TransactionTooLarge {
+                needed,
+                limit: MAX_TRANSACTION_SIZE,
+                suggestion: SizeSuggestion::SeparateInstructions,
+            }) if needed == MAX_TRANSACTION_SIZE + 1
+        ));
+        assert_eq!(*rpc.blockhashes.borrow(), 0);
+        assert!(rpc.sent.borrow().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_expiry_refreshes_blockhash_and_escalates_fee() {
+        let rpc = MockRpc::new(vec![None, None, Some(Ok(()))]);
+        let signer = Keypair::new();
+        let opts = SubmitOptions {
+            max_retries: 5,
+            cu_price_schedule: vec![1_000, 2_000],
+            confirm_level: CommitmentLevel::Processed,
+        };
+        let outcome = submit_with_retry(&rpc, &instructions(), &signer, &opts)
+            .await
+            .unwrap();
+
+        let sent = rpc.sent.borrow();
+        assert_eq!(sent.len(), 3);
+        assert_eq!(outcome, SubmitOutcome::Confirmed(sent[2].signatures[0]));
+        // A new blockhash each attempt; the schedule's last step repeats
+        assert_ne!(sent[0].message.recent_blockhash, sent[1].message.recent_blockhash);
+        assert_ne!(sent[1].message.recent_blockhash, sent[2].message.recent_blockhash);
+        let prices: Vec<_> = sent.iter().map(|tx| budget(tx)[1].clone()).collect();
+        assert_eq!(prices, vec![cu_price(1_000), cu_price(2_000), cu_price(2_000)]);
+    }
+
+    #[tokio::test]
+    async fn test_retries_run_out() {
+        let rpc = MockRpc {
+            send_results: RefCell::new(vec![Err(TransactionError::BlockhashNotFound)].into()),
+            ..MockRpc::new(vec![])
+        };
+        let signer = Keypair::new();
+        let opts = SubmitOptions {
+            max_retries: 2,
+            cu_price_schedule: vec![],
+            confirm_level: CommitmentLevel::Confirmed,
+        };
+        let outcome = submit_with_retry(&rpc, &instructions(), &signer, &opts)
+            .await
+            .unwrap();
+
+        assert_eq!(outcome, SubmitOutcome::Expired);
+        let sent = rpc.sent.borrow();
+        assert_eq!(sent.len(), 3);
+        // No price step, no price instruction
+        assert!(sent.iter().all(|tx| budget(tx).len() == 1));
+    }
+
+    #[tokio::test]
+    async fn test_rejected_proof_not_retried() {
+        let custom = |error: VerifierError| {
+            TransactionError::InstructionError(2, InstructionError::Custom(error as u32))
+        };
+        let signer = Keypair::new();
+
+        // Caught by the estimating simulation, before anything is sent
+        let rpc = MockRpc {
+            simulation: Err(custom(VerifierError::NonCanonicalEncoding)),
+            ..MockRpc::new(vec![])
+        };
+        let outcome = submit_with_retry(&rpc, &instructions(), &signer, &SubmitOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            outcome,
+            SubmitOutcome::ProofRejected(VerifierError::NonCanonicalEncoding as u32)
+        );
+        assert!(rpc.sent.borrow().is_empty());
+
+        // Landed and failed, e.g. a nullifier spent since the simulation
+        let rpc = MockRpc::new(vec![Some(Err(custom(VerifierError::NullifierAlreadySpent)))]);
+        let outcome = submit_with_retry(&rpc, &instructions(), &signer, &SubmitOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            outcome,
+            SubmitOutcome::ProofRejected(VerifierError::NullifierAlreadySpent as u32)
+        );
+        assert_eq!(rpc.sent.borrow().len(), 1);
+
+        // Failures that are not the program's are errors
+        let rpc = MockRpc {
+            simulation: Err(TransactionError::InsufficientFundsForFee),
+            ..MockRpc::new(vec![])
+        };
+        let result =
+            submit_with_retry(&rpc, &instructions(), &signer, &SubmitOptions::default()).await;
+        assert!(matches!(result, Err(ClientError::TransactionFailed(_))));
+    }
+
+    #[cfg(feature = "metrics")]
+    #[tokio::test]
+    async fn test_metrics_labeled_by_instruction_and_code() {
+        use crate::{
+            client::{metrics::RecordingMetrics, verify_proof_instruction},
+            G1Point, G2Point, Groth16Proof, PaymentPublicInputs,
+        };
+
+        let verify = verify_proof_instruction(
+            &Pubkey::new_unique(),
+            Groth16Proof {
+                a: G1Point::new([1u8; 64]),
+                b: G2Point::new([2u8; 128]),
+                c: G1Point::new([3u8; 64]),
+            },
+            PaymentPublicInputs {
+                min_amount: 1000000,
+                recipient_pubkey: [4u8; 32],
+                max_block_age: 60,
+                current_time: 1700000000,
+            },
+        );
+        assert_eq!(instruction_label(&[verify.clone()]), "VerifyProof");
+        assert_eq!(instruction_label(&instructions()), "unknown");
+        let signer = Keypair::new();
+        let metrics = RecordingMetrics::default();
+
+        let rpc = MockRpc::new(vec![Some(Ok(()))]);
+        let submitted = [instructions(), vec![verify]].concat();
+        submit_with_retry_with_metrics(
+            &rpc,
+            &submitted,
+            &signer,
+            &SubmitOptions::default(),
+            &metrics,
+        )
+        .await
+        .unwrap();
+        assert_eq!(metrics.count("compute_units/VerifyProof"), 200_000);
+        assert_eq!(metrics.count("proofs_verified/VerifyProof"), 1);
+
+        let rpc = MockRpc {
+            simulation: Err(TransactionError::InstructionError(
+                1,
+                InstructionError::Custom(VerifierError::NonCanonicalEncoding as u32),
+            )),
+            ..MockRpc::new(vec![])
+        };
+        submit_with_retry_with_metrics(
+            &rpc,
+            &submitted,
+            &signer,
+            &SubmitOptions::default(),
+            &metrics,
+        )
+        .await
+        .unwrap();
+        let code = VerifierError::NonCanonicalEncoding as u32;
+        assert_eq!(metrics.count(&format!("failures/VerifyProof,Some({code})")), 1);
+        // Rejected by the simulation, so no compute units were counted for it
+        assert_eq!(metrics.count("compute_units/VerifyProof"), 200_000);
+
+        let rpc = MockRpc {
+            send_results: RefCell::new(vec![Err(TransactionError::BlockhashNotFound)].into()),
+            ..MockRpc::new(vec![])
+        };
+        let opts = SubmitOptions {
+            max_retries: 0,
+            ..SubmitOptions::default()
+        };
+        let outcome = submit_with_retry_with_metrics(&rpc, &submitted, &signer, &opts, &metrics)
+            .await
+            .unwrap();
+        assert_eq!(outcome, SubmitOutcome::Expired);
+        assert_eq!(metrics.count("failures/VerifyProof,None"), 1);
+        assert_eq!(metrics.count("proofs_verified/VerifyProof"), 1);
+    }
+
+    #[tokio::test]
+    async fn test_already_processed() {
+        let rpc = MockRpc {
+            send_results: RefCell::new(vec![Err(TransactionError::AlreadyProcessed)].into()),
+            ..MockRpc::new(vec![])
+        };
+        let signer = Keypair::new();
+        let outcome = submit_with_retry(&rpc, &instructions(), &signer, &SubmitOptions::default())
+            .await
+            .unwrap();
+        let sent = rpc.sent.borrow();
+        assert_eq!(outcome, SubmitOutcome::AlreadyProcessed(sent[0].signatures[0]));
+        assert_eq!(sent.len(), 1);
+    }
+}
@@ -0,0 +1,214 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar::{clock::Clock, Sysvar},
+};
+
+use crate::{
+    endian::Be32,
+    events::{ProofVerified, ReceiptCreated, VerifierEvent},
+    field::is_canonical_fr,
+    layout::VERIFY_WITH_OPENING,
+    merkle, pda,
+    state::{
+        DeniedRecipient, PaymentReceipt, PreparedVk, ReceiptStatus, StateAccount, VerifierConfig,
+        RECEIPT_SEED,
+    },
+    utils::create_pda_account,
+    verify_groth16, CircuitId, CommittedPaymentPublicInputs, Groth16Proof, VerificationKey,
+    VerifierError,
+};
+
+/// Commitment to `amount` under `blinding`: `Poseidon(amount, blinding)` with
+/// both as big-endian scalars, circomlib's `Poseidon(2)`
+pub fn amount_commitment(amount: u64, blinding: &[u8; 32]) -> Result<[u8; 32], VerifierError> {
+    merkle::poseidon(&Be32::from_u64(amount).to_syscall(), blinding)
+}
+
+/// Amount and blinding behind a `CommittedPaymentPublicInputs::amount_commitment`
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AmountOpening {
+    pub amount: u64,
+    /// Blinding scalar, big-endian and below the scalar field modulus
+    pub blinding: [u8; 32],
+}
+
+impl AmountOpening {
+    /// The opened amount, or `CommitmentMismatch` unless this opens
+    /// `commitment`
+    ///
+    /// A blinding at or above the modulus is refused rather than reduced, so
+    /// each commitment has exactly one accepted opening.
+    pub fn open(&self, commitment: &[u8; 32]) -> Result<u64, VerifierError> {
+        if !is_canonical_fr(&self.blinding)
+            || amount_commitment(self.amount, &self.blinding)? != *commitment
+        {
+            return Err(VerifierError::CommitmentMismatch);
+        }
+        Ok(self.amount)
+    }
+}
+
+/// Check `opening` against the statement's commitment, then verify `proof`
+/// against `vk`
+///
+/// Returns the revealed amount, 0 without an opening. The opening is checked
+/// first, as a Poseidon hash costs far less than the pairing.
+pub fn verify_with_opening(
+    vk: &VerificationKey,
+    proof: &Groth16Proof,
+    public_inputs: &CommittedPaymentPublicInputs,
+    opening: Option<&AmountOpening>,
+) -> Result<u64, ProgramError> {
+    let revealed_amount = match opening {
+        Some(opening) => opening.open(&public_inputs.amount_commitment)?,
+        None => 0,
+    };
+    verify_groth16(vk, proof, &public_inputs.to_scalars())?;
+    Ok(revealed_amount)
+}
+
+/// Verify a committed-amount payment proof and record a `PaymentReceipt` for
+/// it, revealing the amount when `opening` is given
+///
+/// Accounts expected:
+/// 0. `[signer, writable]` Payer funding the receipt rent
+/// 1. `[]` Config PDA: ["config"]
+/// 2. `[writable]` Receipt PDA: ["receipt", proof_hash]
+/// 3. `[]` System program
+/// 4. `[]` Denylist marker PDA: ["denied", recipient_pubkey]
+/// 5. `[]` Prepared key PDA: ["prepared-vk", 7]
+pub fn process_verify_with_opening(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    proof: &Groth16Proof,
+    public_inputs: &CommittedPaymentPublicInputs,
+    opening: Option<&AmountOpening>,
+) -> ProgramResult {
+    let vk = CircuitId::CommittedPayment.verification_key();
+    vk.check_signal_layout(CommittedPaymentPublicInputs::SIGNAL_LAYOUT)?;
+
+    let layout = VERIFY_WITH_OPENING;
+    layout.check(accounts)?;
+    let payer = &accounts[layout.payer];
+    let config_account = &accounts[layout.config];
+    let receipt_account = &accounts[layout.receipt];
+    let system_program = &accounts[layout.system_program];
+    let marker_account = &accounts[layout.marker];
+    let prepared_account = &accounts[layout.prepared_key];
+    PreparedVk::load_active(prepared_account, program_id, CircuitId::CommittedPayment)?;
+
+    let config = VerifierConfig::load(config_account, program_id)?;
+    let clock = Clock::get()?;
+    let proof_hash = proof.hash(public_inputs);
+    let (receipt_address, bump) = pda::receipt_address(program_id, &proof_hash);
+    if receipt_address != *receipt_account.key {
+        msg!("Receipt account does not match proof hash");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if !receipt_account.data_is_empty() {
+        return Err(VerifierError::ReceiptAlreadyExists.into());
+    }
+
+    public_inputs.validate(&config.input_policy, clock.unix_timestamp)?;
+    DeniedRecipient::check(marker_account, program_id, &public_inputs.recipient_pubkey)?;
+
+    let revealed_amount = verify_with_opening(vk, proof, public_inputs, opening)?;
+
+    create_pda_account(
+        payer,
+        receipt_account,
+        system_program,
+        program_id,
+        PaymentReceipt::LEN,
+        &[RECEIPT_SEED, &proof_hash, &[bump]],
+    )?;
+    PaymentReceipt {
+        proof_hash,
+        recipient: public_inputs.recipient_pubkey,
+        min_amount: public_inputs.min_amount,
+        invoice_id: [0u8; 32],
+        payer: *payer.key,
+        status: ReceiptStatus::Active,
+        slot: clock.slot,
+        bump,
+        consumable: false,
+        delegate: Pubkey::default(),
+        finalized: false,
+        revealed_amount,
+        relayer: Pubkey::default(),
+        compute_unit_price: 0,
+        rebate_claimed: false,
+    }
+    .pack(&mut receipt_account.data.borrow_mut()[..])?;
+
+    VerifierEvent::ProofVerified(ProofVerified {
+        proof_hash,
+        recipient: public_inputs.recipient_pubkey,
+        min_amount: public_inputs.min_amount,
+    })
+    .emit();
+    VerifierEvent::ReceiptCreated(ReceiptCreated {
+        receipt: *receipt_account.key,
+        proof_hash,
+        invoice_id: [0u8; 32],
+    })
+    .emit();
+
+    if opening.is_some() {
+        msg!("✓ Payment receipt recorded with its amount");
+    } else {
+        msg!("✓ Payment receipt recorded");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLINDING: [u8; 32] = Be32::from_u64(42).to_syscall();
+
+    /// circomlib `Poseidon(2)` of (1500000, 42)
+    const COMMITMENT: [u8; 32] = [
+        0x1b, 0x53, 0xb9, 0x90, 0xe8, 0x89, 0x05, 0xcf, 0x22, 0x77, 0x40, 0xbe, 0x5b, 0x6d, 0xb7,
+        0xa4, 0x72, 0xbf, 0x63, 0xb6, 0x77, 0x96, 0x7a, 0x11, 0x77, 0x31, 0x1c, 0x5a, 0x15, 0x15,
+        0x19, 0xe1,
+    ];
+
+    #[test]
+    fn test_amount_commitment_matches_circomlib() {
+        assert_eq!(amount_commitment(1500000, &BLINDING), Ok(COMMITMENT));
+        let opening = AmountOpening {
+            amount: 1500000,
+            blinding: BLINDING,
+        };
+        assert_eq!(opening.open(&COMMITMENT), Ok(1500000));
+    }
+
+    #[test]
+    fn test_wrong_openings_mismatch() {
+        let mut blinding = BLINDING;
+        blinding[31] ^= 1;
+        // The right blinding plus the modulus, which reduces to it
+        let unreduced = [
+            0x30, 0x64, 0x4e, 0x72, 0xe1, 0x31, 0xa0, 0x29, 0xb8, 0x50, 0x45, 0xb6, 0x81, 0x81,
+            0x58, 0x5d, 0x28, 0x33, 0xe8, 0x48, 0x79, 0xb9, 0x70, 0x91, 0x43, 0xe1, 0xf5, 0x93,
+            0xf0, 0x00, 0x00, 0x2b,
+        ];
+        for (amount, blinding) in [
+            (1500001, BLINDING),
+            (1500000, blinding),
+            (1500000, unreduced),
+            (0, [0u8; 32]),
+        ] {
+            let opening = AmountOpening { amount, blinding };
+            assert_eq!(opening.open(&COMMITMENT), Err(VerifierError::CommitmentMismatch));
+        }
+    }
+}
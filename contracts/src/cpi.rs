@@ -0,0 +1,297 @@
+// Typed CPI helpers for programs that gate on a verified payment
+// A consumer either verifies a proof inside its own instruction with
+// `verify_proof_cpi`, or accepts a receipt a client recorded earlier with
+// `VerifyAndRecord` through `read_receipt`. Both return a
+// `VerificationReceipt`. A consumer that only needs to know where a receipt
+// stands asks `GetReceiptStatus` through `get_receipt_status_cpi`, which
+// returns a versioned `ReceiptStatus` rather than our account layout. None
+// of them checks which program `verifier_program` is: the consumer must
+// compare its key against the verifier it trusts first.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    account_info::AccountInfo,
+    instruction::Instruction,
+    msg,
+    program::{get_return_data, invoke},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar::{clock::Clock, Sysvar},
+};
+
+use crate::{
+    layout::{GET_RECEIPT_STATUS, VERIFY_PROOF},
+    pda,
+    receipt::is_fresh,
+    state::{self, PaymentReceipt, StateAccount, VerifierConfig},
+    Groth16Proof, PaymentPublicInputs, VerifierError, VerifierInstruction,
+};
+
+/// What a payment proof established, set as return data by the stateless
+/// verify instructions
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct VerificationReceipt {
+    /// `Groth16Proof::hash` of the proof and its public inputs
+    pub proof_hash: [u8; 32],
+    pub recipient: [u8; 32],
+    pub min_amount: u64,
+}
+
+/// Layout version of the `ReceiptStatus` this build returns
+pub const RECEIPT_STATUS_VERSION: u8 = 1;
+
+/// Where a payment receipt stands, set as return data by `GetReceiptStatus`
+///
+/// Stable across receipt account versions: later layouts only append fields
+/// and bump `version`, so a consumer decoding a prefix keeps working.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ReceiptStatus {
+    /// `RECEIPT_STATUS_VERSION` of the verifier that answered
+    pub version: u8,
+    /// False only for a missing receipt asked about with `allow_missing`;
+    /// every other field is then zero
+    pub exists: bool,
+    /// Set by `FinalizeReceipt`; the receipt can no longer be rolled back
+    pub finalized: bool,
+    /// Spent by `ConsumeReceipt`
+    pub consumed: bool,
+    pub recipient: [u8; 32],
+    pub min_amount: u64,
+    /// Slot the receipt was recorded in, or last refreshed
+    pub slot: u64,
+}
+
+impl ReceiptStatus {
+    /// Status of `receipt`, or of a receipt that does not exist
+    pub fn of(receipt: Option<&PaymentReceipt>) -> Self {
+        match receipt {
+            Some(receipt) => Self {
+                version: RECEIPT_STATUS_VERSION,
+                exists: true,
+                finalized: receipt.finalized,
+                consumed: receipt.status == state::ReceiptStatus::Consumed,
+                recipient: receipt.recipient,
+                min_amount: receipt.min_amount,
+                slot: receipt.slot,
+            },
+            None => Self {
+                version: RECEIPT_STATUS_VERSION,
+                exists: false,
+                finalized: false,
+                consumed: false,
+                recipient: [0u8; 32],
+                min_amount: 0,
+                slot: 0,
+            },
+        }
+    }
+}
+
+/// Verifier accounts passed through by `verify_proof_cpi`
+pub struct VerifyCpiAccounts<'a, 'info> {
+    pub system_program: &'a AccountInfo<'info>,
+    /// Config PDA, whose input policy applies
+    pub config: &'a AccountInfo<'info>,
+    /// Prepared key PDA of the payment circuit, whose revocation applies
+    pub prepared_vk: &'a AccountInfo<'info>,
+    /// Denylist marker PDA of the proof's recipient
+    pub marker: &'a AccountInfo<'info>,
+}
+
+/// Verify a payment proof with a `VerifyProof` CPI and return what it proved
+///
+/// A rejected proof fails the CPI, and with it the calling instruction, with
+/// the verifier's error code.
+pub fn verify_proof_cpi<'info>(
+    verifier_program: &AccountInfo<'info>,
+    accounts: VerifyCpiAccounts<'_, 'info>,
+    proof: Groth16Proof,
+    public_inputs: PaymentPublicInputs,
+) -> Result<VerificationReceipt, ProgramError> {
+    let account_infos = [
+        accounts.system_program.clone(),
+        accounts.config.clone(),
+        accounts.prepared_vk.clone(),
+        accounts.marker.clone(),
+        verifier_program.clone(),
+    ];
+    let layout = VERIFY_PROOF;
+    let account_metas = layout.metas(&[
+        (layout.system_program, *accounts.system_program.key),
+        (layout.config, *accounts.config.key),
+        (layout.prepared_key, *accounts.prepared_vk.key),
+        (layout.marker, *accounts.marker.key),
+    ]);
+
+    let instruction = Instruction::new_with_borsh(
+        *verifier_program.key,
+        &VerifierInstruction::VerifyProof {
+            proof,
+            public_inputs,
+        },
+        account_metas,
+    );
+    invoke(&instruction, &account_infos)?;
+    decode_return_data(verifier_program.key, get_return_data())
+}
+
+/// Read the receipt `VerifyAndRecord` wrote for `proof_hash`, refusing one
+/// that is refunded, consumed or older than the config's receipt age limit
+pub fn read_receipt(
+    verifier_program: &AccountInfo,
+    config_account: &AccountInfo,
+    receipt_account: &AccountInfo,
+    proof_hash: &[u8; 32],
+) -> Result<VerificationReceipt, ProgramError> {
+    let verifier_id = verifier_program.key;
+    let (receipt_address, _) = pda::receipt_address(verifier_id, proof_hash);
+    if receipt_address != *receipt_account.key {
+        msg!("Receipt account does not match proof hash");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if receipt_account.owner != verifier_id || receipt_account.data_is_empty() {
+        return Err(VerifierError::ReceiptNotFound.into());
+    }
+
+    let receipt = PaymentReceipt::unpack(&receipt_account.data.borrow())?;
+    match receipt.status {
+        state::ReceiptStatus::Active => {}
+        state::ReceiptStatus::Refunded => return Err(VerifierError::ReceiptAlreadyRefunded.into()),
+        state::ReceiptStatus::Consumed => return Err(VerifierError::ReceiptAlreadyConsumed.into()),
+    }
+    let config = VerifierConfig::load(config_account, verifier_id)?;
+    if !is_fresh(&receipt, Clock::get()?.slot, &config) {
+        return Err(VerifierError::ProofExpired.into());
+    }
+
+    Ok(VerificationReceipt {
+        proof_hash: receipt.proof_hash,
+        recipient: receipt.recipient,
+        min_amount: receipt.min_amount,
+    })
+}
+
+/// Ask the verifier where the receipt for `proof_hash` stands with a
+/// `GetReceiptStatus` CPI
+///
+/// With `allow_missing` a receipt that was never recorded, or was closed,
+/// comes back with `exists` false; without it the CPI fails with
+/// `ReceiptNotFound`. Any other account than the receipt PDA fails it.
+pub fn get_receipt_status_cpi<'info>(
+    verifier_program: &AccountInfo<'info>,
+    receipt_account: &AccountInfo<'info>,
+    proof_hash: [u8; 32],
+    allow_missing: bool,
+) -> Result<ReceiptStatus, ProgramError> {
+    let layout = GET_RECEIPT_STATUS;
+    let instruction = Instruction::new_with_borsh(
+        *verifier_program.key,
+        &VerifierInstruction::GetReceiptStatus {
+            receipt_proof_hash: proof_hash,
+            allow_missing,
+        },
+        layout.metas(&[(layout.receipt, *receipt_account.key)]),
+    );
+    invoke(&instruction, &[receipt_account.clone(), verifier_program.clone()])?;
+    decode_return_data(verifier_program.key, get_return_data())
+}
+
+/// Decode the Borsh value the verifier left as return data
+fn decode_return_data<T: BorshDeserialize>(
+    verifier_id: &Pubkey,
+    return_data: Option<(Pubkey, Vec<u8>)>,
+) -> Result<T, ProgramError> {
+    match return_data {
+        Some((program_id, data)) if program_id == *verifier_id => {
+            T::try_from_slice(&data).map_err(|_| {
+                msg!("Malformed return data");
+                ProgramError::InvalidAccountData
+            })
+        }
+        _ => {
+            msg!("Verifier set no return data");
+            Err(ProgramError::InvalidAccountData)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn receipt() -> VerificationReceipt {
+        VerificationReceipt {
+            proof_hash: [1u8; 32],
+            recipient: [2u8; 32],
+            min_amount: 1000000,
+        }
+    }
+
+    #[test]
+    fn test_decode_return_data() {
+        let verifier_id = Pubkey::new_unique();
+        let data = receipt().try_to_vec().unwrap();
+        assert_eq!(data.len(), 72);
+        assert_eq!(
+            decode_return_data(&verifier_id, Some((verifier_id, data.clone()))),
+            Ok(receipt())
+        );
+
+        // Return data left by another program, or none at all, proves nothing
+        assert_eq!(
+            decode_return_data::<VerificationReceipt>(
+                &verifier_id,
+                Some((Pubkey::new_unique(), data.clone()))
+            ),
+            Err(ProgramError::InvalidAccountData)
+        );
+        assert_eq!(
+            decode_return_data::<VerificationReceipt>(&verifier_id, None),
+            Err(ProgramError::InvalidAccountData)
+        );
+
+        assert_eq!(
+            decode_return_data::<VerificationReceipt>(
+                &verifier_id,
+                Some((verifier_id, data[..71].to_vec()))
+            ),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn test_receipt_status_layout() {
+        let mut receipt = PaymentReceipt {
+            proof_hash: [1u8; 32],
+            recipient: [2u8; 32],
+            min_amount: 1000000,
+            invoice_id: [3u8; 32],
+            payer: Pubkey::new_unique(),
+            status: state::ReceiptStatus::Consumed,
+            slot: 500,
+            bump: 255,
+            consumable: true,
+            delegate: Pubkey::default(),
+            finalized: true,
+            revealed_amount: 0,
+            relayer: Pubkey::default(),
+            compute_unit_price: 0,
+            rebate_claimed: false,
+        };
+        let status = ReceiptStatus::of(Some(&receipt));
+        let mut expected = vec![RECEIPT_STATUS_VERSION, 1, 1, 1];
+        expected.extend_from_slice(&[2u8; 32]);
+        expected.extend_from_slice(&1000000u64.to_le_bytes());
+        expected.extend_from_slice(&500u64.to_le_bytes());
+        assert_eq!(status.try_to_vec().unwrap(), expected);
+
+        // A refunded receipt is not consumed
+        receipt.status = state::ReceiptStatus::Refunded;
+        assert!(!ReceiptStatus::of(Some(&receipt)).consumed);
+
+        let missing = ReceiptStatus::of(None);
+        let mut expected = vec![RECEIPT_STATUS_VERSION, 0, 0, 0];
+        expected.resize(expected.len() + 48, 0);
+        assert_eq!(missing.try_to_vec().unwrap(), expected);
+    }
+}
@@ -0,0 +1,285 @@
+// Numbered failure diagnostics for devnet builds
+// With the `diagnostics` feature, the payment verify instructions run the
+// cheap checks below before the pairing. The first one that fails is logged by
+// number and set as a `DiagnosticsReport` in return data. Verification then
+// goes on as usual, so a diagnostics build accepts and rejects exactly the
+// proofs any other build does, and a proof that passes replaces the report
+// with its receipt. The checks cost compute on every proof: never enable the
+// feature for a mainnet build.
+//
+// The checks run from the most basic cause to the least, so the number names
+// the actual mistake. A proof written with the wrong byte order, for example,
+// fails an encoding check before it could be taken for a proof against the
+// wrong key. Numbers are part of the report format and are never reused.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    log::sol_log_64,
+    msg,
+    program::set_return_data,
+    program_error::ProgramError,
+    sysvar::{clock::Clock, Sysvar},
+};
+
+use crate::{envelope::VerifiedInputs, CircuitId, Groth16Proof, VerificationKey};
+
+/// How far `current_time` may run ahead of the cluster clock, in seconds,
+/// before it is reported
+pub const MAX_CLOCK_LEAD_SECONDS: i64 = 60;
+
+/// A failed pre-pairing check, numbered in the order the checks run
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Diagnostic {
+    /// A coordinate of proof point A is not below the field modulus
+    NonCanonicalA = 1,
+    NonCanonicalB = 2,
+    NonCanonicalC = 3,
+    /// A is not on the curve, as when its limbs are little-endian
+    AOffCurve = 4,
+    /// B is not on the twist, as when its Fq2 limbs are in snarkjs order
+    BOffTwist = 5,
+    COffCurve = 6,
+    /// `current_time` is ahead of the cluster clock, as when it is in
+    /// milliseconds
+    TimestampInFuture = 7,
+    /// `current_time` is older than the proof's own `max_block_age` allows
+    TimestampStale = 8,
+    /// The compiled-in key has the wrong number of IC points for the
+    /// circuit's inputs, as when another circuit's constants were installed
+    IcLengthMismatch = 9,
+}
+
+impl Diagnostic {
+    /// What the failed check means, prefixed with its number
+    pub fn message(self) -> &'static str {
+        match self {
+            Diagnostic::NonCanonicalA => {
+                "Diagnostic 1: proof A has a limb >= p; limbs are big-endian"
+            }
+            Diagnostic::NonCanonicalB => {
+                "Diagnostic 2: proof B has a limb >= p; limbs are big-endian"
+            }
+            Diagnostic::NonCanonicalC => {
+                "Diagnostic 3: proof C has a limb >= p; limbs are big-endian"
+            }
+            Diagnostic::AOffCurve => "Diagnostic 4: proof A is not on the curve",
+            Diagnostic::BOffTwist => {
+                "Diagnostic 5: proof B is not on the twist; check the Fq2 limb order"
+            }
+            Diagnostic::COffCurve => "Diagnostic 6: proof C is not on the curve",
+            Diagnostic::TimestampInFuture => {
+                "Diagnostic 7: current_time is ahead of the cluster clock; it is in seconds"
+            }
+            Diagnostic::TimestampStale => "Diagnostic 8: current_time is older than max_block_age",
+            Diagnostic::IcLengthMismatch => {
+                "Diagnostic 9: key IC length does not fit the inputs; check the installed key"
+            }
+        }
+    }
+}
+
+/// Return data describing the first failed check
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DiagnosticsReport {
+    /// `Diagnostic` number
+    pub diagnostic: u8,
+    pub circuit: CircuitId,
+    /// `VerificationKey::hash` of the key the proof was checked against
+    pub vk_hash: [u8; 32],
+    /// Cluster clock the timestamp checks used
+    pub cluster_time: i64,
+}
+
+/// The first check `proof` and `inputs` fail against `vk` at cluster time
+/// `now`, if any
+pub fn diagnose(
+    vk: &VerificationKey,
+    proof: &Groth16Proof,
+    inputs: &VerifiedInputs,
+    now: i64,
+) -> Option<Diagnostic> {
+    if !proof.a.is_canonical() {
+        return Some(Diagnostic::NonCanonicalA);
+    }
+    if !proof.b.is_canonical() {
+        return Some(Diagnostic::NonCanonicalB);
+    }
+    if !proof.c.is_canonical() {
+        return Some(Diagnostic::NonCanonicalC);
+    }
+    if !proof.a.is_on_curve() {
+        return Some(Diagnostic::AOffCurve);
+    }
+    if proof.b.validate().is_err() {
+        return Some(Diagnostic::BOffTwist);
+    }
+    if !proof.c.is_on_curve() {
+        return Some(Diagnostic::COffCurve);
+    }
+
+    let age = now.saturating_sub(inputs.current_time);
+    if age < -MAX_CLOCK_LEAD_SECONDS {
+        return Some(Diagnostic::TimestampInFuture);
+    }
+    if age > 0 && age as u64 > inputs.max_block_age {
+        return Some(Diagnostic::TimestampStale);
+    }
+
+    if vk.ic.len() != inputs.scalars.len() + 1 {
+        return Some(Diagnostic::IcLengthMismatch);
+    }
+    None
+}
+
+/// Run `diagnose` against the cluster clock, logging and reporting the first
+/// failed check
+pub fn report(
+    vk: &VerificationKey,
+    proof: &Groth16Proof,
+    inputs: &VerifiedInputs,
+) -> Result<(), ProgramError> {
+    let now = Clock::get()?.unix_timestamp;
+    if let Some(diagnostic) = diagnose(vk, proof, inputs, now) {
+        msg!(diagnostic.message());
+        sol_log_64(diagnostic as u64, now as u64, 0, 0, 0);
+        #[cfg(not(feature = "privacy-strict"))]
+        log_statement!("Statement current time:", inputs.current_time as u64);
+        let report = DiagnosticsReport {
+            diagnostic: diagnostic as u8,
+            circuit: inputs.circuit,
+            vk_hash: vk.hash(),
+            cluster_time: now,
+        };
+        set_return_data(&report.try_to_vec()?);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{G1Point, G2Point, PaymentPublicInputs};
+
+    const NOW: i64 = 1700000000;
+
+    fn g1_generator() -> G1Point {
+        let mut point = [0u8; 64];
+        point[31] = 1;
+        point[63] = 2;
+        G1Point::new(point)
+    }
+
+    fn inputs(proof: &Groth16Proof, current_time: i64) -> VerifiedInputs {
+        VerifiedInputs::from_v1(
+            proof,
+            &PaymentPublicInputs {
+                min_amount: 1000000,
+                recipient_pubkey: [4u8; 32],
+                max_block_age: 60,
+                current_time,
+            },
+        )
+    }
+
+    #[test]
+    fn test_checks_run_in_numbered_order() {
+        // Start with every check failing and fix one cause at a time; each
+        // step must report the next number. Non-canonical limbs are also off
+        // the curve, so the encoding checks have to come first.
+        let check = |vk, proof: &Groth16Proof, current_time| {
+            diagnose(vk, proof, &inputs(proof, current_time), NOW).map(|d| d as u8)
+        };
+        let mut proof = Groth16Proof {
+            a: G1Point::new([0xff; 64]),
+            b: G2Point::new([0xff; 128]),
+            c: G1Point::new([0xff; 64]),
+        };
+        let wrong_key = CircuitId::TokenPayment.verification_key();
+        let off_curve = G1Point::new([0x01; 64]);
+
+        assert_eq!(check(wrong_key, &proof, NOW + 3600), Some(1));
+        proof.a = off_curve;
+        assert_eq!(check(wrong_key, &proof, NOW + 3600), Some(2));
+        proof.b = G2Point::new([0x01; 128]);
+        assert_eq!(check(wrong_key, &proof, NOW + 3600), Some(3));
+        proof.c = off_curve;
+        assert_eq!(check(wrong_key, &proof, NOW + 3600), Some(4));
+        proof.a = g1_generator();
+        assert_eq!(check(wrong_key, &proof, NOW + 3600), Some(5));
+        proof.b = G2Point::new([0u8; 128]);
+        assert_eq!(check(wrong_key, &proof, NOW + 3600), Some(6));
+        proof.c = g1_generator();
+        assert_eq!(check(wrong_key, &proof, NOW + 3600), Some(7));
+        assert_eq!(check(wrong_key, &proof, NOW - 3600), Some(8));
+        assert_eq!(check(wrong_key, &proof, NOW), Some(9));
+        assert_eq!(check(CircuitId::Payment.verification_key(), &proof, NOW), None);
+    }
+
+    #[test]
+    fn test_common_mistakes_map_to_their_diagnostic() {
+        let vk = CircuitId::Payment.verification_key();
+        let good = Groth16Proof {
+            a: g1_generator(),
+            b: G2Point::new([0u8; 128]),
+            c: g1_generator(),
+        };
+        assert_eq!(diagnose(vk, &good, &inputs(&good, NOW), NOW), None);
+
+        // The generator with its limbs written little-endian stays below p,
+        // so it is caught by the curve check
+        let mut little_endian = [0u8; 64];
+        little_endian[0] = 1;
+        little_endian[32] = 2;
+        let proof = Groth16Proof {
+            c: G1Point::new(little_endian),
+            ..good
+        };
+        assert_eq!(
+            diagnose(vk, &proof, &inputs(&proof, NOW), NOW),
+            Some(Diagnostic::COffCurve)
+        );
+
+        // A timestamp in milliseconds
+        assert_eq!(
+            diagnose(vk, &good, &inputs(&good, NOW * 1000), NOW),
+            Some(Diagnostic::TimestampInFuture)
+        );
+
+        // Clock drift within the allowance, and an age exactly at the limit
+        assert_eq!(
+            diagnose(vk, &good, &inputs(&good, NOW + MAX_CLOCK_LEAD_SECONDS), NOW),
+            None
+        );
+        assert_eq!(diagnose(vk, &good, &inputs(&good, NOW - 60), NOW), None);
+        assert_eq!(
+            diagnose(vk, &good, &inputs(&good, NOW - 61), NOW),
+            Some(Diagnostic::TimestampStale)
+        );
+
+        // Token payment constants installed as the payment key
+        assert_eq!(
+            diagnose(
+                CircuitId::TokenPayment.verification_key(),
+                &good,
+                &inputs(&good, NOW),
+                NOW
+            ),
+            Some(Diagnostic::IcLengthMismatch)
+        );
+    }
+
+    #[test]
+    fn test_report_layout() {
+        let report = DiagnosticsReport {
+            diagnostic: Diagnostic::TimestampStale as u8,
+            circuit: CircuitId::Payment,
+            vk_hash: [3u8; 32],
+            cluster_time: NOW,
+        };
+        let data = report.try_to_vec().unwrap();
+        assert_eq!(data.len(), 42);
+        assert_eq!(&data[..2], &[8, 0]);
+        assert_eq!(DiagnosticsReport::try_from_slice(&data).unwrap(), report);
+    }
+}
@@ -0,0 +1,168 @@
+// Byte order of 32-byte integers
+// Two encodings meet in this crate. Borsh, and every integer packed with
+// `to_le_bytes`, is little-endian. The alt_bn128 syscalls, the verification key
+// constants and the field helpers take big-endian field elements. `Be32` and
+// `Le32` record which one a value is in.
+//
+// Raw bytes enter through `from_syscall` or `from_borsh` and leave through
+// `to_syscall` or `to_borsh`, so each crossing names its side. Going from one
+// order to the other reverses the bytes, and `to_be` and `to_le` are the only
+// way to do it. Both types are transparent over their bytes, so wrapping them
+// changes no encoding.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+
+/// A 32-byte big-endian integer, as the alt_bn128 syscalls take field elements
+///
+/// Byte order makes the derived ordering numeric.
+#[derive(
+    BorshSerialize, BorshDeserialize, Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord,
+)]
+#[repr(transparent)]
+pub struct Be32([u8; 32]);
+
+impl Be32 {
+    pub const ZERO: Self = Self([0u8; 32]);
+
+    /// Wrap bytes already in syscall order
+    pub const fn from_syscall(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// Limb `index` of a big-endian encoding made of 32-byte limbs, such as a
+    /// point
+    ///
+    /// # Panics
+    ///
+    /// If the limb does not fit in `bytes`.
+    pub const fn from_limb<const N: usize>(bytes: &[u8; N], index: usize) -> Self {
+        let mut limb = [0u8; 32];
+        let mut i = 0;
+        while i < 32 {
+            limb[i] = bytes[32 * index + i];
+            i += 1;
+        }
+        Self(limb)
+    }
+
+    pub const fn from_u64(value: u64) -> Self {
+        let bytes = value.to_be_bytes();
+        let mut limb = [0u8; 32];
+        let mut i = 0;
+        while i < 8 {
+            limb[24 + i] = bytes[i];
+            i += 1;
+        }
+        Self(limb)
+    }
+
+    /// The bytes as the syscalls take them
+    pub const fn to_syscall(self) -> [u8; 32] {
+        self.0
+    }
+
+    /// The same integer, little-endian
+    pub fn to_le(self) -> Le32 {
+        let mut bytes = self.0;
+        bytes.reverse();
+        Le32(bytes)
+    }
+}
+
+/// A 32-byte little-endian integer, as Borsh and `to_le_bytes` lay them out
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct Le32([u8; 32]);
+
+impl Le32 {
+    /// Wrap bytes as Borsh encodes them
+    pub const fn from_borsh(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    pub const fn from_u64(value: u64) -> Self {
+        let bytes = value.to_le_bytes();
+        let mut limb = [0u8; 32];
+        let mut i = 0;
+        while i < 8 {
+            limb[i] = bytes[i];
+            i += 1;
+        }
+        Self(limb)
+    }
+
+    /// The bytes as Borsh encodes them
+    pub const fn to_borsh(self) -> [u8; 32] {
+        self.0
+    }
+
+    /// Little-endian u64 number `index` of the bytes, as public inputs split
+    /// 32-byte values into scalars
+    pub fn u64_limb(self, index: usize) -> u64 {
+        u64::from_le_bytes(self.0[8 * index..8 * (index + 1)].try_into().unwrap())
+    }
+
+    /// The same integer, big-endian
+    pub fn to_be(self) -> Be32 {
+        let mut bytes = self.0;
+        bytes.reverse();
+        Be32(bytes)
+    }
+}
+
+/// Concatenate big-endian limbs into an encoding of `N` bytes
+///
+/// # Panics
+///
+/// If `limbs` does not fill exactly `N` bytes.
+pub(crate) fn join_limbs<const N: usize>(limbs: &[Be32]) -> [u8; N] {
+    assert_eq!(limbs.len() * 32, N);
+    let mut bytes = [0u8; N];
+    for (chunk, limb) in bytes.chunks_exact_mut(32).zip(limbs) {
+        chunk.copy_from_slice(&limb.0);
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_u64_in_each_order() {
+        let value = 0x0102030405060708u64;
+        let be = Be32::from_u64(value);
+        let le = Le32::from_u64(value);
+        assert_eq!(be.to_syscall()[24..], value.to_be_bytes());
+        assert_eq!(le.to_borsh()[..8], value.to_le_bytes());
+        assert_eq!(le.try_to_vec().unwrap(), le.to_borsh());
+        assert_eq!(be.try_to_vec().unwrap(), be.to_syscall());
+
+        // Converting reverses, and round-trips
+        assert_eq!(be.to_le(), le);
+        assert_eq!(le.to_be(), be);
+        assert_eq!(be.to_le().to_be(), be);
+
+        let mut bytes = [0u8; 32];
+        bytes[8..16].copy_from_slice(&value.to_le_bytes());
+        assert_eq!(Le32::from_borsh(bytes).u64_limb(1), value);
+        assert_eq!(Le32::from_borsh(bytes).u64_limb(0), 0);
+    }
+
+    #[test]
+    fn test_order_is_numeric() {
+        assert!(Be32::from_u64(255) < Be32::from_u64(256));
+        assert!(Be32::from_u64(u64::MAX) < Be32::from_syscall([0xff; 32]));
+        assert_eq!(Be32::default(), Be32::ZERO);
+    }
+
+    #[test]
+    fn test_limbs() {
+        let mut bytes = [0u8; 64];
+        bytes[31] = 1;
+        bytes[63] = 2;
+        let limbs = [Be32::from_limb(&bytes, 0), Be32::from_limb(&bytes, 1)];
+        assert_eq!(limbs, [Be32::from_u64(1), Be32::from_u64(2)]);
+        assert_eq!(join_limbs::<64>(&limbs), bytes);
+    }
+}
@@ -0,0 +1,219 @@
+// Versioned proof envelope
+// `VerifyEnvelope` carries a version byte and an opaque payload, so new proof or
+// public input encodings can ship alongside old ones instead of replacing the
+// raw Borsh structs under every deployed client at once.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::program_error::ProgramError;
+
+use crate::{
+    utils::decode_exact, CircuitId, Groth16Proof, PaymentPublicInputs, PaymentPublicInputsV2,
+    SerializedLen, VerifierError,
+};
+
+/// Payload is Borsh `(Groth16Proof, PaymentPublicInputs)`, byte-for-byte the
+/// body of a `VerifyProof` instruction
+pub const PROOF_VERSION_V1: u8 = 1;
+
+/// Payload is Borsh `(Groth16Proof, PaymentPublicInputsV2)`, the body of a
+/// `VerifyProofV2` instruction
+pub const PROOF_VERSION_V2: u8 = 2;
+
+/// A proof and its public inputs in a versioned encoding
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ProofEnvelope {
+    pub version: u8,
+    pub payload: Vec<u8>,
+}
+
+impl ProofEnvelope {
+    /// Wrap a proof and SOL payment statement as a v1 envelope
+    pub fn v1(proof: &Groth16Proof, public_inputs: &PaymentPublicInputs) -> Self {
+        Self {
+            version: PROOF_VERSION_V1,
+            payload: (proof, public_inputs).try_to_vec().unwrap(),
+        }
+    }
+
+    /// Wrap a proof and token payment statement as a v2 envelope
+    pub fn v2(proof: &Groth16Proof, public_inputs: &PaymentPublicInputsV2) -> Self {
+        Self {
+            version: PROOF_VERSION_V2,
+            payload: (proof, public_inputs).try_to_vec().unwrap(),
+        }
+    }
+
+    /// Decode the payload with the decoder for its version
+    pub fn decode(&self) -> Result<(Groth16Proof, VerifiedInputs), ProgramError> {
+        match self.version {
+            PROOF_VERSION_V1 => decode_proof_v1(&self.payload),
+            PROOF_VERSION_V2 => decode_proof_v2(&self.payload),
+            _ => Err(VerifierError::UnsupportedProofVersion.into()),
+        }
+    }
+}
+
+impl SerializedLen for ProofEnvelope {
+    /// A v2 envelope, the longest payload any version decodes
+    const MAX_LEN: usize = 1 + 4 + Groth16Proof::MAX_LEN + PaymentPublicInputsV2::MAX_LEN;
+
+    fn serialized_len(&self) -> usize {
+        1 + 4 + self.payload.len()
+    }
+}
+
+/// Decode a v1 payload
+pub fn decode_proof_v1(payload: &[u8]) -> Result<(Groth16Proof, VerifiedInputs), ProgramError> {
+    let (proof, public_inputs) = decode_exact::<(Groth16Proof, PaymentPublicInputs)>(payload)?;
+    let inputs = VerifiedInputs::from_v1(&proof, &public_inputs);
+    Ok((proof, inputs))
+}
+
+/// Decode a v2 payload
+pub fn decode_proof_v2(payload: &[u8]) -> Result<(Groth16Proof, VerifiedInputs), ProgramError> {
+    let (proof, public_inputs) = decode_exact::<(Groth16Proof, PaymentPublicInputsV2)>(payload)?;
+    let inputs = VerifiedInputs::from_v2(&proof, &public_inputs);
+    Ok((proof, inputs))
+}
+
+/// A payment statement independent of the encoding it arrived in
+///
+/// Carries what verification needs: the circuit, its scalars in signal order,
+/// the fields the input policy checks, and the proof hash over the original
+/// encoding so events match those of the versioned instructions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifiedInputs {
+    pub circuit: CircuitId,
+    pub signal_layout: &'static [&'static str],
+    pub scalars: Vec<u64>,
+    pub min_amount: u64,
+    pub recipient_pubkey: [u8; 32],
+    pub max_block_age: u64,
+    pub current_time: i64,
+    pub proof_hash: [u8; 32],
+}
+
+impl VerifiedInputs {
+    pub fn from_v1(proof: &Groth16Proof, public_inputs: &PaymentPublicInputs) -> Self {
+        Self {
+            circuit: CircuitId::Payment,
+            signal_layout: PaymentPublicInputs::SIGNAL_LAYOUT,
+            scalars: public_inputs.to_scalars().to_vec(),
+            min_amount: public_inputs.min_amount,
+            recipient_pubkey: public_inputs.recipient_pubkey,
+            max_block_age: public_inputs.max_block_age,
+            current_time: public_inputs.current_time,
+            proof_hash: proof.hash(public_inputs),
+        }
+    }
+
+    pub fn from_v2(proof: &Groth16Proof, public_inputs: &PaymentPublicInputsV2) -> Self {
+        Self {
+            circuit: CircuitId::TokenPayment,
+            signal_layout: PaymentPublicInputsV2::SIGNAL_LAYOUT,
+            scalars: public_inputs.to_scalars().to_vec(),
+            min_amount: public_inputs.min_amount,
+            recipient_pubkey: public_inputs.recipient_pubkey,
+            max_block_age: public_inputs.max_block_age,
+            current_time: public_inputs.current_time,
+            proof_hash: proof.hash(public_inputs),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{G1Point, G2Point, VerifierInstruction};
+
+    fn proof() -> Groth16Proof {
+        Groth16Proof {
+            a: G1Point::new([1u8; 64]),
+            b: G2Point::new([2u8; 128]),
+            c: G1Point::new([3u8; 64]),
+        }
+    }
+
+    fn v1_inputs() -> PaymentPublicInputs {
+        PaymentPublicInputs {
+            min_amount: 1000000,
+            recipient_pubkey: [4u8; 32],
+            max_block_age: 60,
+            current_time: 1700000000,
+        }
+    }
+
+    #[test]
+    fn test_v1_payload_is_verify_proof_body() {
+        let instruction = VerifierInstruction::VerifyProof {
+            proof: proof(),
+            public_inputs: v1_inputs(),
+        }
+        .try_to_vec()
+        .unwrap();
+
+        let envelope = ProofEnvelope::v1(&proof(), &v1_inputs());
+        assert_eq!(envelope.payload, instruction[1..]);
+
+        // version || u32 LE payload length || payload
+        let bytes = envelope.try_to_vec().unwrap();
+        assert_eq!(bytes[..5], [1, 0x38, 0x01, 0, 0]);
+        assert_eq!(bytes[5..69], [1u8; 64]);
+        assert_eq!(bytes[69..197], [2u8; 128]);
+        assert_eq!(bytes[197..261], [3u8; 64]);
+        assert_eq!(bytes[261..269], 1000000u64.to_le_bytes());
+        assert_eq!(bytes[269..301], [4u8; 32]);
+        assert_eq!(bytes[301..309], 60u64.to_le_bytes());
+        assert_eq!(bytes[309..317], 1700000000i64.to_le_bytes());
+        assert_eq!(bytes.len(), 317);
+    }
+
+    #[test]
+    fn test_decoded_inputs_match_direct_path() {
+        let (decoded, inputs) = ProofEnvelope::v1(&proof(), &v1_inputs()).decode().unwrap();
+        assert_eq!(decoded.try_to_vec().unwrap(), proof().try_to_vec().unwrap());
+        assert_eq!(inputs, VerifiedInputs::from_v1(&proof(), &v1_inputs()));
+        assert_eq!(inputs.circuit, CircuitId::Payment);
+        assert_eq!(inputs.scalars, v1_inputs().to_scalars());
+        assert_eq!(inputs.proof_hash, proof().hash(&v1_inputs()));
+
+        let v2_inputs = PaymentPublicInputsV2 {
+            min_amount: 1000000,
+            recipient_pubkey: [4u8; 32],
+            max_block_age: 60,
+            current_time: 1700000000,
+            mint: [5u8; 32],
+            token_decimals_checked_amount: 1000000,
+        };
+        let (_, inputs) = ProofEnvelope::v2(&proof(), &v2_inputs).decode().unwrap();
+        assert_eq!(inputs.circuit, CircuitId::TokenPayment);
+        assert_eq!(inputs.scalars, v2_inputs.to_scalars());
+        assert_eq!(inputs.proof_hash, proof().hash(&v2_inputs));
+    }
+
+    #[test]
+    fn test_unknown_version_rejected() {
+        for version in [0, 3, u8::MAX] {
+            let envelope = ProofEnvelope {
+                version,
+                ..ProofEnvelope::v1(&proof(), &v1_inputs())
+            };
+            assert_eq!(
+                envelope.decode().unwrap_err(),
+                ProgramError::from(VerifierError::UnsupportedProofVersion)
+            );
+        }
+    }
+
+    #[test]
+    fn test_malformed_payload_rejected() {
+        let mut envelope = ProofEnvelope::v1(&proof(), &v1_inputs());
+        envelope.payload.push(0);
+        assert_eq!(envelope.decode().unwrap_err(), ProgramError::InvalidInstructionData);
+
+        // A v1 payload is not a valid v2 payload
+        envelope.payload.pop();
+        envelope.version = PROOF_VERSION_V2;
+        assert_eq!(envelope.decode().unwrap_err(), ProgramError::InvalidInstructionData);
+    }
+}
@@ -0,0 +1,498 @@
+use solana_program::{alt_bn128::AltBn128Error, program_error::ProgramError};
+use thiserror::Error;
+
+/// Errors returned by the verifier program
+///
+/// Surfaced to clients as `ProgramError::Custom(code)`; the discriminants are
+/// part of the public interface and must never be renumbered.
+///
+/// So is which error a statement with several problems gets. The payment
+/// verify instructions (`VerifyProof`, `VerifyProofV2` and the `VerifyEnvelope`
+/// variants) report the first failing check in this order:
+///
+/// 1. `PublicInputLayoutMismatch`: the compiled-in key's layout
+/// 2. The config account's own errors, then the input policy:
+///    `AmountBelowFloor`, `BlockAgeAboveCeiling`, `ClockSkewExceeded`, then
+///    `RecipientDenied` and the denylist marker's own errors
+/// 3. The prepared key account's own errors, then `KeyRevoked`, then
+///    `StalePreparedKey`
+/// 4. `TooManyPublicInputs` or `PublicInputLayoutMismatch` for the input
+///    count, then `NonCanonicalEncoding` or `InvalidG2Point` for the proof
+/// 5. `InvalidVerificationKey` for a key with an invalid point, then the
+///    pairing: an `AltBn128*` error, or `InvalidArgument` when the proof does
+///    not verify
+///
+/// An account past the four these instructions take fails them with
+/// `UnexpectedAccount` before any of these.
+///
+/// Steps 1 to 4, and the key check, make no alt_bn128 syscall, so a stale or malformed proof is
+/// rejected for a small fraction of the pairing's cost.
+#[derive(Error, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum VerifierError {
+    /// A proof coordinate is not a canonical (reduced) base field element
+    #[error("Non-canonical field element encoding")]
+    NonCanonicalEncoding = 0,
+
+    /// A receipt for this proof and statement has already been recorded
+    #[error("Payment receipt already exists")]
+    ReceiptAlreadyExists = 1,
+
+    /// The proof's `valid_until` deadline has passed
+    #[error("Proof has expired")]
+    ProofExpired = 2,
+
+    /// The proof's recipient set root is neither the current nor a grace-period root
+    #[error("Recipient set root is not approved")]
+    RecipientRootMismatch = 3,
+
+    /// The signer is not allowed to perform this operation
+    #[error("Unauthorized")]
+    Unauthorized = 4,
+
+    /// The verification key was exported for a different public signal ordering
+    #[error("Public input layout does not match the verification key")]
+    PublicInputLayoutMismatch = 5,
+
+    /// A settlement account does not belong to the proof's recipient, or a
+    /// settlement token account has the wrong mint
+    #[error("Invalid settlement token account")]
+    InvalidSettlementAccount = 6,
+
+    /// The referenced payment receipt account does not exist
+    #[error("Payment receipt not found")]
+    ReceiptNotFound = 7,
+
+    /// The payment receipt has already been refunded
+    #[error("Payment receipt already refunded")]
+    ReceiptAlreadyRefunded = 8,
+
+    /// The nullifier has already been spent for this epoch
+    #[error("Nullifier already spent")]
+    NullifierAlreadySpent = 9,
+
+    /// The proof's epoch is neither the current nor the previous billing epoch
+    #[error("Proof epoch is outside the accepted window")]
+    EpochMismatch = 10,
+
+    /// `min_amount` is below the configured floor
+    #[error("Minimum amount is below the configured floor")]
+    AmountBelowFloor = 11,
+
+    /// `max_block_age` is above the configured ceiling
+    #[error("Maximum block age is above the configured ceiling")]
+    BlockAgeAboveCeiling = 12,
+
+    /// `current_time` is further from the cluster clock than the configured skew
+    #[error("Proof time is outside the allowed clock skew")]
+    ClockSkewExceeded = 13,
+
+    /// A curve point encoding has the wrong number of bytes
+    #[error("Invalid curve point length")]
+    InvalidPointLength = 14,
+
+    /// The batch holds more proofs than its verification path accepts
+    #[error("Too many proofs in batch")]
+    BatchTooLarge = 15,
+
+    /// Fewer proofs in a soft batch passed than the caller required
+    #[error("Too few proofs in batch passed verification")]
+    BatchBelowThreshold = 16,
+
+    /// `AcceptAdmin` was sent while no admin transfer is pending
+    #[error("No admin transfer is pending")]
+    NoPendingAdmin = 17,
+
+    /// The proof envelope's version has no decoder in this program
+    #[error("Unsupported proof envelope version")]
+    UnsupportedProofVersion = 18,
+
+    /// The prepared verification key was derived from a different key, or
+    /// written before version 5 and not prepared again since
+    #[error("Prepared verification key is stale")]
+    StalePreparedKey = 19,
+
+    /// The same statement appears more than once in a batch
+    #[error("Duplicate proof in batch")]
+    DuplicateProofInBatch = 20,
+
+    /// The transaction has no ed25519 instruction acknowledging the payment
+    #[error("Missing recipient acknowledgement")]
+    MissingRecipientAck = 21,
+
+    /// No ed25519 instruction carries the recipient's signature over the terms
+    #[error("Recipient acknowledgement does not match the payment terms")]
+    RecipientAckMismatch = 22,
+
+    /// A proof presented for an existing receipt is for a different invoice,
+    /// recipient or a lower amount
+    #[error("Proof does not match the receipt's terms")]
+    ReceiptTermsMismatch = 23,
+
+    /// A G2 point's coordinates do not satisfy the twist curve equation,
+    /// typically Fq2 limbs in another library's order
+    #[error("G2 point is not on the curve")]
+    InvalidG2Point = 24,
+
+    /// The account holds a different account type than the one expected
+    #[error("Wrong account discriminator")]
+    WrongAccountDiscriminator = 25,
+
+    /// The account data is not the size its type and version call for
+    #[error("Invalid account data length")]
+    InvalidAccountLength = 26,
+
+    /// The account was written with a layout version this program cannot read
+    #[error("Unsupported account version")]
+    UnsupportedAccountVersion = 27,
+
+    /// The account body does not decode as its type
+    #[error("Malformed account data")]
+    MalformedAccountData = 28,
+
+    /// A curve syscall was given input of the wrong length, or a point that
+    /// does not decode onto the curve
+    #[error("Invalid alt_bn128 syscall input")]
+    AltBn128InvalidInput = 29,
+
+    /// A curve syscall rejected a point as outside the group
+    #[error("alt_bn128 group error")]
+    AltBn128GroupError = 30,
+
+    /// A curve syscall read outside its input
+    #[error("alt_bn128 slice out of bounds")]
+    AltBn128SliceOutOfBounds = 31,
+
+    /// A curve syscall could not convert its result to bytes
+    #[error("alt_bn128 result conversion failed")]
+    AltBn128ResultConversion = 32,
+
+    /// A curve syscall could not convert a projective point to affine
+    #[error("alt_bn128 projective to affine conversion failed")]
+    AltBn128ProjectiveToG1 = 33,
+
+    /// A curve syscall failed with an error code this program does not know
+    #[error("Unexpected alt_bn128 error")]
+    AltBn128Unexpected = 34,
+
+    /// Every leaf of the shielded pool's commitment tree is taken
+    #[error("Merkle tree is full")]
+    MerkleTreeFull = 35,
+
+    /// A deposit commitment is zero or not a canonical scalar field element
+    #[error("Invalid deposit commitment")]
+    InvalidCommitment = 36,
+
+    /// The Poseidon syscall rejected its input
+    #[error("Poseidon hash failed")]
+    PoseidonHashFailed = 37,
+
+    /// A withdrawal's root is not one of the pool's recent roots
+    #[error("Unknown Merkle root")]
+    UnknownMerkleRoot = 38,
+
+    /// A pool's root history size is zero or above `MAX_ROOT_HISTORY_SIZE`
+    #[error("Invalid root history size")]
+    InvalidRootHistorySize = 39,
+
+    /// A key deprecation window would end later than the config allows
+    #[error("Key deprecation window too long")]
+    DeprecationWindowTooLong = 40,
+
+    /// The relayer allowlist already holds `MAX_RELAYERS` relayers
+    #[error("Relayer list full")]
+    RelayerListFull = 41,
+
+    /// Fewer than the config's threshold of distinct admin signers signed a
+    /// destructive admin instruction
+    #[error("Admin signer threshold not met")]
+    AdminThresholdNotMet = 42,
+
+    /// An admin signer set with duplicate or zero keys, too many keys, or a
+    /// threshold it cannot meet
+    #[error("Invalid admin signer set")]
+    InvalidAdminSigners = 43,
+
+    /// The invoice already has an active receipt and the config does not
+    /// allow overpayment
+    #[error("Invoice already paid")]
+    InvoiceAlreadyPaid = 44,
+
+    /// More public inputs than `MAX_PUBLIC_INPUTS`, rejected before any
+    /// curve arithmetic
+    #[error("Too many public inputs")]
+    TooManyPublicInputs = 45,
+
+    /// The key account of `VerifyWithInlineKey` does not hold the key the
+    /// caller named
+    #[error("Inline key hash mismatch")]
+    InlineKeyHashMismatch = 46,
+
+    /// A `PairingBuilder` already holds `pairing::MAX_PAIRS` pairs
+    #[error("Too many pairing pairs")]
+    TooManyPairs = 47,
+
+    /// The circuit's key was revoked by `RevokeVerificationKey`; only a
+    /// different key, prepared by the admin threshold, verifies again
+    #[error("Verification key revoked")]
+    KeyRevoked = 48,
+
+    /// The proof pays a recipient the admin denied with `DenyRecipient`
+    #[error("Recipient denied")]
+    RecipientDenied = 49,
+
+    /// `VerifyBatchInclusion` was given a proof and path that do not lead to
+    /// the batch receipt's root
+    #[error("Proof not in batch")]
+    ProofNotInBatch = 50,
+
+    /// The recipient has had the config's maximum number of verifications in
+    /// the current window; nothing was recorded, so the payment can be
+    /// submitted again once the window rolls over
+    #[error("Recipient rate limited")]
+    RateLimited = 51,
+
+    /// The cluster has not activated the alt_bn128 syscalls, so no proof can
+    /// be verified on it
+    #[error("alt_bn128 syscalls unavailable")]
+    SyscallUnavailable = 52,
+
+    /// `ConsumeReceipt` was given a receipt that has already been consumed
+    #[error("Receipt already consumed")]
+    ReceiptAlreadyConsumed = 53,
+
+    /// `ConsumeReceipt` was given a receipt created without `consumable`
+    #[error("Receipt not consumable")]
+    ReceiptNotConsumable = 54,
+
+    /// A point of the verification key is the identity or off its curve, as
+    /// in a key still holding placeholder points
+    #[error("Invalid verification key")]
+    InvalidVerificationKey = 55,
+
+    /// `FinalizeReceipt` came before the config's finality depth had passed
+    /// since the receipt's slot
+    #[error("Receipt not yet final")]
+    ReceiptNotFinal = 56,
+
+    /// `ReapStale` came before the config's scratch TTL had passed since the
+    /// buffer's last write
+    #[error("Scratch account not yet stale")]
+    ScratchNotStale = 57,
+
+    /// `MigrateReceipt` was given a receipt already at the current layout
+    #[error("Receipt already migrated")]
+    ReceiptAlreadyMigrated = 58,
+
+    /// A `VerifyWithOpening` amount and blinding do not open the proof's
+    /// amount commitment
+    #[error("Amount commitment opening mismatch")]
+    CommitmentMismatch = 59,
+
+    /// A batch statement does not expand to as many scalars as the batch
+    /// circuit's key takes; the details are set as return data
+    #[error("Public input count does not match the batch circuit")]
+    PublicInputCountMismatch = 60,
+
+    /// An account was passed that the instruction does not take, an account
+    /// it writes was passed read-only, or one it only reads was passed
+    /// writable
+    #[error("Unexpected account")]
+    UnexpectedAccount = 61,
+
+    /// `ClaimRelayerRebate` was given a receipt whose rebate was already paid
+    #[error("Relayer rebate already claimed")]
+    RebateAlreadyClaimed = 62,
+
+    /// The relayer passed to `ClaimRelayerRebate` is not the one the receipt
+    /// recorded, or the receipt recorded none
+    #[error("Relayer does not match the receipt")]
+    RelayerMismatch = 63,
+
+    /// A batch's proofs are not in canonical order, by proof hash ascending
+    #[error("Batch is not in canonical order")]
+    BatchNotCanonical = 64,
+}
+
+impl TryFrom<u32> for VerifierError {
+    type Error = u32;
+
+    /// The variant behind a `ProgramError::Custom` code, or the code back if
+    /// no variant has it
+    fn try_from(code: u32) -> Result<Self, u32> {
+        use VerifierError::*;
+        const ALL: [VerifierError; 65] = [
+            NonCanonicalEncoding,
+            ReceiptAlreadyExists,
+            ProofExpired,
+            RecipientRootMismatch,
+            Unauthorized,
+            PublicInputLayoutMismatch,
+            InvalidSettlementAccount,
+            ReceiptNotFound,
+            ReceiptAlreadyRefunded,
+            NullifierAlreadySpent,
+            EpochMismatch,
+            AmountBelowFloor,
+            BlockAgeAboveCeiling,
+            ClockSkewExceeded,
+            InvalidPointLength,
+            BatchTooLarge,
+            BatchBelowThreshold,
+            NoPendingAdmin,
+            UnsupportedProofVersion,
+            StalePreparedKey,
+            DuplicateProofInBatch,
+            MissingRecipientAck,
+            RecipientAckMismatch,
+            ReceiptTermsMismatch,
+            InvalidG2Point,
+            WrongAccountDiscriminator,
+            InvalidAccountLength,
+            UnsupportedAccountVersion,
+            MalformedAccountData,
+            AltBn128InvalidInput,
+            AltBn128GroupError,
+            AltBn128SliceOutOfBounds,
+            AltBn128ResultConversion,
+            AltBn128ProjectiveToG1,
+            AltBn128Unexpected,
+            MerkleTreeFull,
+            InvalidCommitment,
+            PoseidonHashFailed,
+            UnknownMerkleRoot,
+            InvalidRootHistorySize,
+            DeprecationWindowTooLong,
+            RelayerListFull,
+            AdminThresholdNotMet,
+            InvalidAdminSigners,
+            InvoiceAlreadyPaid,
+            TooManyPublicInputs,
+            InlineKeyHashMismatch,
+            TooManyPairs,
+            KeyRevoked,
+            RecipientDenied,
+            ProofNotInBatch,
+            RateLimited,
+            SyscallUnavailable,
+            ReceiptAlreadyConsumed,
+            ReceiptNotConsumable,
+            InvalidVerificationKey,
+            ReceiptNotFinal,
+            ScratchNotStale,
+            ReceiptAlreadyMigrated,
+            CommitmentMismatch,
+            PublicInputCountMismatch,
+            UnexpectedAccount,
+            RebateAlreadyClaimed,
+            RelayerMismatch,
+            BatchNotCanonical,
+        ];
+        ALL.into_iter().find(|e| *e as u32 == code).ok_or(code)
+    }
+}
+
+impl From<AltBn128Error> for VerifierError {
+    fn from(e: AltBn128Error) -> Self {
+        match e {
+            AltBn128Error::InvalidInputData => VerifierError::AltBn128InvalidInput,
+            AltBn128Error::GroupError => VerifierError::AltBn128GroupError,
+            AltBn128Error::SliceOutOfBounds => VerifierError::AltBn128SliceOutOfBounds,
+            AltBn128Error::TryIntoVecError(_) => VerifierError::AltBn128ResultConversion,
+            AltBn128Error::ProjectiveToG1Failed => VerifierError::AltBn128ProjectiveToG1,
+            AltBn128Error::UnexpectedError => VerifierError::AltBn128Unexpected,
+        }
+    }
+}
+
+impl From<VerifierError> for ProgramError {
+    fn from(e: VerifierError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{field::Scalar, G1Point};
+    use solana_program::alt_bn128::{alt_bn128_pairing, ALT_BN128_PAIRING_OUTPUT_LEN};
+
+    /// Base field modulus, big-endian
+    const BN254_P: [u8; 32] = [
+        0x30, 0x64, 0x4e, 0x72, 0xe1, 0x31, 0xa0, 0x29, 0xb8, 0x50, 0x45, 0xb6, 0x81, 0x81, 0x58,
+        0x5d, 0x97, 0x81, 0x6a, 0x91, 0x68, 0x71, 0xca, 0x8d, 0x3c, 0x20, 0x8c, 0x16, 0xd8, 0x7c,
+        0xfd, 0x47,
+    ];
+
+    #[test]
+    fn test_alt_bn128_errors_distinct() {
+        let cases = [
+            (AltBn128Error::InvalidInputData, 29),
+            (AltBn128Error::GroupError, 30),
+            (AltBn128Error::SliceOutOfBounds, 31),
+            (AltBn128Error::TryIntoVecError(vec![1, 2]), 32),
+            (AltBn128Error::ProjectiveToG1Failed, 33),
+            (AltBn128Error::UnexpectedError, 34),
+        ];
+        for (error, code) in cases {
+            assert_eq!(
+                ProgramError::from(VerifierError::from(error.clone())),
+                ProgramError::Custom(code),
+                "{:?}",
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn test_error_codes_round_trip() {
+        for code in 0..65 {
+            assert_eq!(VerifierError::try_from(code).map(|e| e as u32), Ok(code));
+        }
+        assert_eq!(VerifierError::try_from(65), Err(65));
+        assert_eq!(VerifierError::try_from(u32::MAX), Err(u32::MAX));
+    }
+
+    #[test]
+    fn test_bad_syscall_inputs_reported() {
+        let invalid_input = ProgramError::from(VerifierError::AltBn128InvalidInput);
+
+        // (1, 1) is not on y^2 = x^3 + 3
+        let mut off_curve = [0u8; 64];
+        off_curve[31] = 1;
+        off_curve[63] = 1;
+        // The generator (1, 2) with x replaced by p, one past the largest
+        // field element
+        let mut generator = [0u8; 64];
+        generator[31] = 1;
+        generator[63] = 2;
+        let mut unreduced = generator;
+        unreduced[..32].copy_from_slice(&BN254_P);
+
+        let cases: [(&str, Result<G1Point, ProgramError>); 3] = [
+            ("off-curve addend", G1Point::new(off_curve).add(&G1Point::IDENTITY)),
+            ("off-curve multiplicand", G1Point::new(off_curve).mul(&Scalar::ZERO)),
+            ("unreduced coordinate", G1Point::new(unreduced).add(&G1Point::IDENTITY)),
+        ];
+        for (name, result) in cases {
+            assert_eq!(result, Err(invalid_input.clone()), "{}", name);
+        }
+
+        let mut output = [0u8; ALT_BN128_PAIRING_OUTPUT_LEN];
+        let short = alt_bn128_pairing(&[0u8; 191], &mut output);
+        assert_eq!(
+            short.err().map(|e| ProgramError::from(VerifierError::from(e))),
+            Some(invalid_input.clone())
+        );
+
+        // Generator paired with a G2 point off the twist
+        let mut pair = [0u8; 192];
+        pair[..64].copy_from_slice(&generator);
+        pair[64 + 31] = 1;
+        pair[64 + 127] = 1;
+        let off_twist = alt_bn128_pairing(&pair, &mut output);
+        assert_eq!(
+            off_twist.err().map(|e| ProgramError::from(VerifierError::from(e))),
+            Some(invalid_input)
+        );
+    }
+}
@@ -0,0 +1,426 @@
+// Structured events for indexers
+// Each event is logged with `sol_log_data` as one buffer: an 8-byte
+// discriminator (the first 8 bytes of sha256("event:<Name>")) followed by the
+// Borsh-encoded event body. Discriminators are part of the indexer interface and
+// must never change; add a new event instead of altering an existing body.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{log::sol_log_data, pubkey::Pubkey};
+
+use crate::CircuitId;
+
+pub const PROOF_VERIFIED_DISCRIMINATOR: [u8; 8] = [0xb5, 0x36, 0x94, 0xd3, 0xed, 0x49, 0x83, 0xe8];
+pub const BATCH_VERIFIED_DISCRIMINATOR: [u8; 8] = [0x58, 0x81, 0xc0, 0xc4, 0x40, 0x7c, 0xbc, 0xef];
+pub const RECEIPT_CREATED_DISCRIMINATOR: [u8; 8] = [0x35, 0xec, 0xce, 0x18, 0xc2, 0x0a, 0xd0, 0xa3];
+pub const NULLIFIER_CONSUMED_DISCRIMINATOR: [u8; 8] =
+    [0x74, 0x18, 0xaa, 0x34, 0xbb, 0x84, 0x4a, 0x35];
+pub const CONFIG_UPDATED_DISCRIMINATOR: [u8; 8] = [0x28, 0xf1, 0xe6, 0x7a, 0x0b, 0x13, 0xc6, 0xc2];
+pub const RECEIPT_REFUNDED_DISCRIMINATOR: [u8; 8] =
+    [0xbe, 0x35, 0xa8, 0xe5, 0xeb, 0xa5, 0xab, 0x60];
+pub const DRY_RUN_VERIFIED_DISCRIMINATOR: [u8; 8] =
+    [0xda, 0xd5, 0x40, 0x47, 0x68, 0xf3, 0x64, 0x7f];
+pub const DEPOSIT_COMMITTED_DISCRIMINATOR: [u8; 8] =
+    [0x0a, 0xef, 0x3e, 0x28, 0x5a, 0x2c, 0x0e, 0x10];
+pub const VERIFIED_WITH_PREVIOUS_KEY_DISCRIMINATOR: [u8; 8] =
+    [0x7e, 0xe2, 0x74, 0x8e, 0x00, 0x19, 0xaa, 0x69];
+pub const WITHDRAWN_DISCRIMINATOR: [u8; 8] = [0x14, 0x59, 0xdf, 0xc6, 0xc2, 0x7c, 0xdb, 0x0d];
+pub const BATCH_RECEIPT_CREATED_DISCRIMINATOR: [u8; 8] =
+    [0x47, 0xda, 0x94, 0x4f, 0x51, 0xcb, 0xe4, 0xff];
+pub const RECEIPT_CONSUMED_DISCRIMINATOR: [u8; 8] =
+    [0xdb, 0x43, 0x35, 0x3d, 0xd6, 0xa9, 0x48, 0x90];
+pub const RECEIPT_FINALIZED_DISCRIMINATOR: [u8; 8] =
+    [0x4e, 0x6f, 0x19, 0x9e, 0xc6, 0x05, 0xd0, 0x09];
+pub const RELAYER_REBATE_CLAIMED_DISCRIMINATOR: [u8; 8] =
+    [0x4c, 0x63, 0x34, 0x58, 0x7e, 0x54, 0x48, 0xc5];
+
+/// A single proof passed verification
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ProofVerified {
+    pub proof_hash: [u8; 32],
+    pub recipient: [u8; 32],
+    pub min_amount: u64,
+}
+
+/// A batch of proofs passed verification
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BatchVerified {
+    pub num_proofs: u32,
+}
+
+/// A payment receipt account was created
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ReceiptCreated {
+    pub receipt: Pubkey,
+    pub proof_hash: [u8; 32],
+    pub invoice_id: [u8; 32],
+}
+
+/// A nullifier was marked as spent
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct NullifierConsumed {
+    pub nullifier: [u8; 32],
+}
+
+/// The program config was created or modified
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ConfigUpdated {
+    pub config: Pubkey,
+    pub admin: Pubkey,
+}
+
+/// A payment receipt was marked refunded
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ReceiptRefunded {
+    pub receipt: Pubkey,
+    pub refund_recipient: [u8; 32],
+}
+
+/// A dry run passed every check without writing anything
+///
+/// Emitted instead of the events a real run would log, so indexers never see
+/// state that does not exist.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DryRunVerified {
+    /// Account the real run would have created
+    pub account: Pubkey,
+    /// The account data it would have held
+    pub data: Vec<u8>,
+}
+
+/// A commitment was appended to the shielded pool's tree
+///
+/// Wallets replay these to rebuild the tree and the Merkle paths of their notes.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DepositCommitted {
+    pub commitment: [u8; 32],
+    pub leaf_index: u64,
+    /// Root after the append
+    pub root: [u8; 32],
+}
+
+/// The proof of the preceding `ProofVerified` was accepted by its circuit's
+/// previous key, during that key's deprecation window
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct VerifiedWithPreviousKey {
+    pub proof_hash: [u8; 32],
+    pub circuit: CircuitId,
+    /// `VerificationKey::hash` of the previous key
+    pub vk_hash: [u8; 32],
+}
+
+/// Lamports left the shielded pool
+///
+/// A change note is also announced by its own `DepositCommitted`, so wallets
+/// rebuilding the tree need only those.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Withdrawn {
+    pub nullifier: [u8; 32],
+    pub recipient: [u8; 32],
+    pub amount: u64,
+    /// Leaf index of the change note, `None` when the note was spent in full
+    pub change_leaf_index: Option<u64>,
+}
+
+/// A batch receipt account was created for a verified batch
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BatchReceiptCreated {
+    pub receipt: Pubkey,
+    pub transcript_hash: [u8; 32],
+    /// Merkle root of the batch's proof hashes
+    pub receipt_root: [u8; 32],
+    pub num_proofs: u32,
+}
+
+/// A consumable receipt was spent
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ReceiptConsumed {
+    pub receipt: Pubkey,
+    pub proof_hash: [u8; 32],
+    /// The recipient or the receipt's delegate, whoever signed
+    pub consumer: Pubkey,
+}
+
+/// A receipt outlived the finality depth and was marked final
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ReceiptFinalized {
+    pub receipt: Pubkey,
+    pub proof_hash: [u8; 32],
+    /// The receipt's `slot` when it was finalized
+    pub slot: u64,
+}
+
+/// A recipient paid the relayer of a receipt its rebate
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RelayerRebateClaimed {
+    pub receipt: Pubkey,
+    pub relayer: Pubkey,
+    /// Lamports moved from the recipient's rebate deposit
+    pub amount: u64,
+}
+
+/// Events emitted by the verifier program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifierEvent {
+    ProofVerified(ProofVerified),
+    BatchVerified(BatchVerified),
+    ReceiptCreated(ReceiptCreated),
+    NullifierConsumed(NullifierConsumed),
+    ConfigUpdated(ConfigUpdated),
+    ReceiptRefunded(ReceiptRefunded),
+    DryRunVerified(DryRunVerified),
+    DepositCommitted(DepositCommitted),
+    VerifiedWithPreviousKey(VerifiedWithPreviousKey),
+    Withdrawn(Withdrawn),
+    BatchReceiptCreated(BatchReceiptCreated),
+    ReceiptConsumed(ReceiptConsumed),
+    ReceiptFinalized(ReceiptFinalized),
+    RelayerRebateClaimed(RelayerRebateClaimed),
+}
+
+impl VerifierEvent {
+    pub fn discriminator(&self) -> [u8; 8] {
+        match self {
+            VerifierEvent::ProofVerified(_) => PROOF_VERIFIED_DISCRIMINATOR,
+            VerifierEvent::BatchVerified(_) => BATCH_VERIFIED_DISCRIMINATOR,
+            VerifierEvent::ReceiptCreated(_) => RECEIPT_CREATED_DISCRIMINATOR,
+            VerifierEvent::NullifierConsumed(_) => NULLIFIER_CONSUMED_DISCRIMINATOR,
+            VerifierEvent::ConfigUpdated(_) => CONFIG_UPDATED_DISCRIMINATOR,
+            VerifierEvent::ReceiptRefunded(_) => RECEIPT_REFUNDED_DISCRIMINATOR,
+            VerifierEvent::DryRunVerified(_) => DRY_RUN_VERIFIED_DISCRIMINATOR,
+            VerifierEvent::DepositCommitted(_) => DEPOSIT_COMMITTED_DISCRIMINATOR,
+            VerifierEvent::VerifiedWithPreviousKey(_) => VERIFIED_WITH_PREVIOUS_KEY_DISCRIMINATOR,
+            VerifierEvent::Withdrawn(_) => WITHDRAWN_DISCRIMINATOR,
+            VerifierEvent::BatchReceiptCreated(_) => BATCH_RECEIPT_CREATED_DISCRIMINATOR,
+            VerifierEvent::ReceiptConsumed(_) => RECEIPT_CONSUMED_DISCRIMINATOR,
+            VerifierEvent::ReceiptFinalized(_) => RECEIPT_FINALIZED_DISCRIMINATOR,
+            VerifierEvent::RelayerRebateClaimed(_) => RELAYER_REBATE_CLAIMED_DISCRIMINATOR,
+        }
+    }
+
+    /// Discriminator followed by the Borsh-encoded body
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let body = match self {
+            VerifierEvent::ProofVerified(event) => event.try_to_vec(),
+            VerifierEvent::BatchVerified(event) => event.try_to_vec(),
+            VerifierEvent::ReceiptCreated(event) => event.try_to_vec(),
+            VerifierEvent::NullifierConsumed(event) => event.try_to_vec(),
+            VerifierEvent::ConfigUpdated(event) => event.try_to_vec(),
+            VerifierEvent::ReceiptRefunded(event) => event.try_to_vec(),
+            VerifierEvent::DryRunVerified(event) => event.try_to_vec(),
+            VerifierEvent::DepositCommitted(event) => event.try_to_vec(),
+            VerifierEvent::VerifiedWithPreviousKey(event) => event.try_to_vec(),
+            VerifierEvent::Withdrawn(event) => event.try_to_vec(),
+            VerifierEvent::BatchReceiptCreated(event) => event.try_to_vec(),
+            VerifierEvent::ReceiptConsumed(event) => event.try_to_vec(),
+            VerifierEvent::ReceiptFinalized(event) => event.try_to_vec(),
+            VerifierEvent::RelayerRebateClaimed(event) => event.try_to_vec(),
+        }
+        .unwrap();
+
+        let mut data = self.discriminator().to_vec();
+        data.extend_from_slice(&body);
+        data
+    }
+
+    /// Log the event as `Program data:` for indexers
+    pub fn emit(&self) {
+        sol_log_data(&[&self.to_bytes()]);
+    }
+}
+
+/// Decode one `Program data:` payload emitted by this program
+///
+/// Returns `None` for unknown discriminators or malformed bodies, so logs from
+/// other programs can be fed through without pre-filtering.
+#[cfg(feature = "client")]
+pub fn parse_event(data: &[u8]) -> Option<VerifierEvent> {
+    if data.len() < 8 {
+        return None;
+    }
+    let (discriminator, mut body) = data.split_at(8);
+    let discriminator: [u8; 8] = discriminator.try_into().ok()?;
+
+    let event = match discriminator {
+        PROOF_VERIFIED_DISCRIMINATOR => {
+            VerifierEvent::ProofVerified(ProofVerified::deserialize(&mut body).ok()?)
+        }
+        BATCH_VERIFIED_DISCRIMINATOR => {
+            VerifierEvent::BatchVerified(BatchVerified::deserialize(&mut body).ok()?)
+        }
+        RECEIPT_CREATED_DISCRIMINATOR => {
+            VerifierEvent::ReceiptCreated(ReceiptCreated::deserialize(&mut body).ok()?)
+        }
+        NULLIFIER_CONSUMED_DISCRIMINATOR => {
+            VerifierEvent::NullifierConsumed(NullifierConsumed::deserialize(&mut body).ok()?)
+        }
+        CONFIG_UPDATED_DISCRIMINATOR => {
+            VerifierEvent::ConfigUpdated(ConfigUpdated::deserialize(&mut body).ok()?)
+        }
+        RECEIPT_REFUNDED_DISCRIMINATOR => {
+            VerifierEvent::ReceiptRefunded(ReceiptRefunded::deserialize(&mut body).ok()?)
+        }
+        DRY_RUN_VERIFIED_DISCRIMINATOR => {
+            VerifierEvent::DryRunVerified(DryRunVerified::deserialize(&mut body).ok()?)
+        }
+        DEPOSIT_COMMITTED_DISCRIMINATOR => {
+            VerifierEvent::DepositCommitted(DepositCommitted::deserialize(&mut body).ok()?)
+        }
+        VERIFIED_WITH_PREVIOUS_KEY_DISCRIMINATOR => VerifierEvent::VerifiedWithPreviousKey(
+            VerifiedWithPreviousKey::deserialize(&mut body).ok()?,
+        ),
+        WITHDRAWN_DISCRIMINATOR => {
+            VerifierEvent::Withdrawn(Withdrawn::deserialize(&mut body).ok()?)
+        }
+        BATCH_RECEIPT_CREATED_DISCRIMINATOR => {
+            VerifierEvent::BatchReceiptCreated(BatchReceiptCreated::deserialize(&mut body).ok()?)
+        }
+        RECEIPT_CONSUMED_DISCRIMINATOR => {
+            VerifierEvent::ReceiptConsumed(ReceiptConsumed::deserialize(&mut body).ok()?)
+        }
+        RECEIPT_FINALIZED_DISCRIMINATOR => {
+            VerifierEvent::ReceiptFinalized(ReceiptFinalized::deserialize(&mut body).ok()?)
+        }
+        RELAYER_REBATE_CLAIMED_DISCRIMINATOR => VerifierEvent::RelayerRebateClaimed(
+            RelayerRebateClaimed::deserialize(&mut body).ok()?,
+        ),
+        _ => return None,
+    };
+
+    // Trailing bytes mean this is not one of our events
+    if !body.is_empty() {
+        return None;
+    }
+    Some(event)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_program::hash::hash;
+
+    #[test]
+    fn test_discriminators_pinned() {
+        let cases = [
+            ("ProofVerified", PROOF_VERIFIED_DISCRIMINATOR),
+            ("BatchVerified", BATCH_VERIFIED_DISCRIMINATOR),
+            ("ReceiptCreated", RECEIPT_CREATED_DISCRIMINATOR),
+            ("NullifierConsumed", NULLIFIER_CONSUMED_DISCRIMINATOR),
+            ("ConfigUpdated", CONFIG_UPDATED_DISCRIMINATOR),
+            ("ReceiptRefunded", RECEIPT_REFUNDED_DISCRIMINATOR),
+            ("DryRunVerified", DRY_RUN_VERIFIED_DISCRIMINATOR),
+            ("DepositCommitted", DEPOSIT_COMMITTED_DISCRIMINATOR),
+            ("VerifiedWithPreviousKey", VERIFIED_WITH_PREVIOUS_KEY_DISCRIMINATOR),
+            ("Withdrawn", WITHDRAWN_DISCRIMINATOR),
+            ("BatchReceiptCreated", BATCH_RECEIPT_CREATED_DISCRIMINATOR),
+            ("ReceiptConsumed", RECEIPT_CONSUMED_DISCRIMINATOR),
+            ("ReceiptFinalized", RECEIPT_FINALIZED_DISCRIMINATOR),
+            ("RelayerRebateClaimed", RELAYER_REBATE_CLAIMED_DISCRIMINATOR),
+        ];
+        for (name, discriminator) in cases {
+            let digest = hash(format!("event:{}", name).as_bytes()).to_bytes();
+            assert_eq!(&digest[..8], &discriminator, "{}", name);
+        }
+
+        // Literal values indexers hardcode
+        assert_eq!(
+            PROOF_VERIFIED_DISCRIMINATOR,
+            [0xb5, 0x36, 0x94, 0xd3, 0xed, 0x49, 0x83, 0xe8]
+        );
+        assert_eq!(
+            CONFIG_UPDATED_DISCRIMINATOR,
+            [0x28, 0xf1, 0xe6, 0x7a, 0x0b, 0x13, 0xc6, 0xc2]
+        );
+    }
+
+    #[test]
+    fn test_event_layout() {
+        let event = VerifierEvent::BatchVerified(BatchVerified { num_proofs: 3 });
+        let bytes = event.to_bytes();
+        assert_eq!(&bytes[..8], &BATCH_VERIFIED_DISCRIMINATOR);
+        assert_eq!(&bytes[8..], &[3, 0, 0, 0]);
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_parse_event_round_trip() {
+        let events = [
+            VerifierEvent::ProofVerified(ProofVerified {
+                proof_hash: [1u8; 32],
+                recipient: [2u8; 32],
+                min_amount: 1000000,
+            }),
+            VerifierEvent::BatchVerified(BatchVerified { num_proofs: 4 }),
+            VerifierEvent::ReceiptCreated(ReceiptCreated {
+                receipt: Pubkey::new_unique(),
+                proof_hash: [3u8; 32],
+                invoice_id: [4u8; 32],
+            }),
+            VerifierEvent::NullifierConsumed(NullifierConsumed {
+                nullifier: [5u8; 32],
+            }),
+            VerifierEvent::ConfigUpdated(ConfigUpdated {
+                config: Pubkey::new_unique(),
+                admin: Pubkey::new_unique(),
+            }),
+            VerifierEvent::ReceiptRefunded(ReceiptRefunded {
+                receipt: Pubkey::new_unique(),
+                refund_recipient: [6u8; 32],
+            }),
+            VerifierEvent::DryRunVerified(DryRunVerified {
+                account: Pubkey::new_unique(),
+                data: vec![2, 1, 7],
+            }),
+            VerifierEvent::DepositCommitted(DepositCommitted {
+                commitment: [7u8; 32],
+                leaf_index: 41,
+                root: [8u8; 32],
+            }),
+            VerifierEvent::VerifiedWithPreviousKey(VerifiedWithPreviousKey {
+                proof_hash: [9u8; 32],
+                circuit: CircuitId::TokenPayment,
+                vk_hash: [10u8; 32],
+            }),
+            VerifierEvent::Withdrawn(Withdrawn {
+                nullifier: [11u8; 32],
+                recipient: [12u8; 32],
+                amount: 1000,
+                change_leaf_index: Some(42),
+            }),
+            VerifierEvent::BatchReceiptCreated(BatchReceiptCreated {
+                receipt: Pubkey::new_unique(),
+                transcript_hash: [13u8; 32],
+                receipt_root: [14u8; 32],
+                num_proofs: 5,
+            }),
+            VerifierEvent::ReceiptConsumed(ReceiptConsumed {
+                receipt: Pubkey::new_unique(),
+                proof_hash: [15u8; 32],
+                consumer: Pubkey::new_unique(),
+            }),
+            VerifierEvent::ReceiptFinalized(ReceiptFinalized {
+                receipt: Pubkey::new_unique(),
+                proof_hash: [16u8; 32],
+                slot: 250000000,
+            }),
+            VerifierEvent::RelayerRebateClaimed(RelayerRebateClaimed {
+                receipt: Pubkey::new_unique(),
+                relayer: Pubkey::new_unique(),
+                amount: 5000,
+            }),
+        ];
+        for event in events {
+            assert_eq!(parse_event(&event.to_bytes()), Some(event));
+        }
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_parse_event_rejects_foreign_data() {
+        assert_eq!(parse_event(&[0u8; 4]), None);
+        assert_eq!(parse_event(&[0u8; 48]), None);
+
+        let mut bytes = VerifierEvent::BatchVerified(BatchVerified { num_proofs: 1 }).to_bytes();
+        bytes.push(0);
+        assert_eq!(parse_event(&bytes), None);
+    }
+}
@@ -0,0 +1,397 @@
+// BN254 base field (Fq) and scalar field (Fr) helpers
+// Field elements are 32-byte big-endian integers (`Be32`), the encoding used by
+// the alt_bn128 syscalls and the verification key constants.
+
+use crate::endian::Be32;
+
+/// BN254 base field modulus (big-endian)
+/// p = 21888242871839275222246405745257275088696311157297823662689037894645226208583
+pub const FQ_MODULUS: [u8; 32] = [
+    0x30, 0x64, 0x4e, 0x72, 0xe1, 0x31, 0xa0, 0x29,
+    0xb8, 0x50, 0x45, 0xb6, 0x81, 0x81, 0x58, 0x5d,
+    0x97, 0x81, 0x6a, 0x91, 0x68, 0x71, 0xca, 0x8d,
+    0x3c, 0x20, 0x8c, 0x16, 0xd8, 0x7c, 0xfd, 0x47,
+];
+
+/// Check that a field element encoding is canonical (strictly less than p)
+///
+/// Values in [p, 2^256) are alternative encodings of an already representable
+/// element, so accepting them would let the same point have several byte forms.
+pub fn is_canonical_fq(bytes: &[u8; 32]) -> bool {
+    // Big-endian byte order makes lexicographic comparison numeric
+    bytes < &FQ_MODULUS
+}
+
+/// BN254 scalar field modulus, the group order r (big-endian)
+/// r = 21888242871839275222246405745257275088548364400416034343698204186575808495617
+pub const FR_MODULUS: [u8; 32] = [
+    0x30, 0x64, 0x4e, 0x72, 0xe1, 0x31, 0xa0, 0x29,
+    0xb8, 0x50, 0x45, 0xb6, 0x81, 0x81, 0x58, 0x5d,
+    0x28, 0x33, 0xe8, 0x48, 0x79, 0xb9, 0x70, 0x91,
+    0x43, 0xe1, 0xf5, 0x93, 0xf0, 0x00, 0x00, 0x01,
+];
+
+/// Check that a scalar encoding is canonical (strictly less than r)
+pub fn is_canonical_fr(bytes: &[u8; 32]) -> bool {
+    bytes < &FR_MODULUS
+}
+
+/// Reduce a 256-bit big-endian integer modulo r
+///
+/// 2^256 < 6r, so at most five subtractions are needed.
+pub fn reduce_fr(bytes: &mut [u8; 32]) {
+    reduce(bytes, &FR_MODULUS);
+}
+
+/// Reduce a 256-bit big-endian integer modulo p; see `reduce_fr`
+pub fn reduce_fq(bytes: &mut [u8; 32]) {
+    reduce(bytes, &FQ_MODULUS);
+}
+
+fn reduce(bytes: &mut [u8; 32], modulus: &[u8; 32]) {
+    while bytes >= modulus {
+        let mut borrow = 0u16;
+        for i in (0..32).rev() {
+            let diff = bytes[i] as u16 + 256 - modulus[i] as u16 - borrow;
+            bytes[i] = (diff & 0xff) as u8;
+            borrow = if diff < 256 { 1 } else { 0 };
+        }
+    }
+}
+
+/// Element of the scalar field Fr, big-endian as `alt_bn128_multiplication`
+/// takes its multiplier
+///
+/// Every constructor yields a value below r.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(transparent)]
+pub struct Scalar(Be32);
+
+impl Scalar {
+    pub const ZERO: Self = Self(Be32::ZERO);
+    pub const ONE: Self = Self::from_u64(1);
+
+    /// `value` itself; every u64 is below r
+    pub const fn from_u64(value: u64) -> Self {
+        Self(Be32::from_u64(value))
+    }
+
+    /// `value` if it is below r
+    pub fn from_canonical(value: Be32) -> Option<Self> {
+        is_canonical_fr(&value.to_syscall()).then_some(Self(value))
+    }
+
+    /// `value` reduced mod r
+    pub fn reduce(value: Be32) -> Self {
+        let mut bytes = value.to_syscall();
+        reduce_fr(&mut bytes);
+        Self(Be32::from_syscall(bytes))
+    }
+
+    pub const fn to_be(self) -> Be32 {
+        self.0
+    }
+
+    /// The bytes as the syscalls take them
+    pub const fn to_syscall(self) -> [u8; 32] {
+        self.0.to_syscall()
+    }
+}
+
+impl From<u64> for Scalar {
+    fn from(value: u64) -> Self {
+        Self::from_u64(value)
+    }
+}
+
+/// Check that every 32-byte limb of an encoded point is canonical
+pub fn all_limbs_canonical(point: &[u8]) -> bool {
+    point.len() % 32 == 0
+        && point
+            .chunks_exact(32)
+            .all(|limb| is_canonical_fq(limb.try_into().unwrap()))
+}
+
+/// p as little-endian u64 limbs
+const P_LIMBS: [u64; 4] = [
+    0x3c208c16d87cfd47,
+    0x97816a916871ca8d,
+    0xb85045b68181585d,
+    0x30644e72e131a029,
+];
+
+/// -p^-1 mod 2^64
+const P_INV: u64 = 0x87d20782e4866389;
+
+/// R^2 mod p for R = 2^256, in limbs; multiplying by it enters Montgomery form
+const R2: Fq = Fq([
+    0xf32cfc5b538afa89,
+    0xb5e71911d44501fb,
+    0x47ab1eff0a417ff6,
+    0x06d89f71cab8351f,
+]);
+
+/// Element of Fq in Montgomery form, little-endian u64 limbs
+///
+/// Only what the curve equation checks need; the syscalls do the real
+/// group arithmetic.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct Fq([u64; 4]);
+
+impl Fq {
+    /// Parse a field element, `None` unless it is canonical
+    pub(crate) fn from_be(value: Be32) -> Option<Self> {
+        let bytes = value.to_syscall();
+        if !is_canonical_fq(&bytes) {
+            return None;
+        }
+        let mut limbs = [0u64; 4];
+        for (i, limb) in limbs.iter_mut().enumerate() {
+            let end = 32 - 8 * i;
+            *limb = u64::from_be_bytes(bytes[end - 8..end].try_into().unwrap());
+        }
+        Some(Self(limbs).mul(&R2))
+    }
+
+    pub(crate) fn add(&self, other: &Self) -> Self {
+        let mut sum = [0u64; 4];
+        let mut carry = false;
+        for i in 0..4 {
+            let (s, c1) = self.0[i].overflowing_add(other.0[i]);
+            let (s, c2) = s.overflowing_add(carry as u64);
+            sum[i] = s;
+            carry = c1 || c2;
+        }
+        // Both inputs are below p < 2^254, so the sum never leaves 256 bits
+        debug_assert!(!carry);
+        Self(sum).subtract_p_if_needed()
+    }
+
+    pub(crate) fn sub(&self, other: &Self) -> Self {
+        let (difference, borrow) = sub_limbs(&self.0, &other.0);
+        if borrow {
+            Self(add_limbs(&difference, &P_LIMBS))
+        } else {
+            Self(difference)
+        }
+    }
+
+    /// Montgomery product: self * other * R^-1 mod p
+    pub(crate) fn mul(&self, other: &Self) -> Self {
+        let mac = |acc: u64, a: u64, b: u64, carry: u64| {
+            let wide = acc as u128 + a as u128 * b as u128 + carry as u128;
+            (wide as u64, (wide >> 64) as u64)
+        };
+
+        let mut t = [0u64; 6];
+        for i in 0..4 {
+            let mut carry = 0;
+            for j in 0..4 {
+                (t[j], carry) = mac(t[j], self.0[j], other.0[i], carry);
+            }
+            let (high, overflow) = t[4].overflowing_add(carry);
+            t[4] = high;
+            t[5] = overflow as u64;
+
+            let m = t[0].wrapping_mul(P_INV);
+            let (_, mut carry) = mac(t[0], m, P_LIMBS[0], 0);
+            for j in 1..4 {
+                (t[j - 1], carry) = mac(t[j], m, P_LIMBS[j], carry);
+            }
+            let (high, overflow) = t[4].overflowing_add(carry);
+            t[3] = high;
+            t[4] = t[5] + overflow as u64;
+        }
+
+        // The result is below 2p; t[4] is the bit above the top limb
+        let result = Self([t[0], t[1], t[2], t[3]]);
+        if t[4] != 0 {
+            Self(sub_limbs(&result.0, &P_LIMBS).0)
+        } else {
+            result.subtract_p_if_needed()
+        }
+    }
+
+    fn subtract_p_if_needed(self) -> Self {
+        let (reduced, borrow) = sub_limbs(&self.0, &P_LIMBS);
+        if borrow {
+            self
+        } else {
+            Self(reduced)
+        }
+    }
+}
+
+fn add_limbs(a: &[u64; 4], b: &[u64; 4]) -> [u64; 4] {
+    let mut sum = [0u64; 4];
+    let mut carry = false;
+    for i in 0..4 {
+        let (s, c1) = a[i].overflowing_add(b[i]);
+        let (s, c2) = s.overflowing_add(carry as u64);
+        sum[i] = s;
+        carry = c1 || c2;
+    }
+    sum
+}
+
+fn sub_limbs(a: &[u64; 4], b: &[u64; 4]) -> ([u64; 4], bool) {
+    let mut difference = [0u64; 4];
+    let mut borrow = false;
+    for i in 0..4 {
+        let (d, b1) = a[i].overflowing_sub(b[i]);
+        let (d, b2) = d.overflowing_sub(borrow as u64);
+        difference[i] = d;
+        borrow = b1 || b2;
+    }
+    (difference, borrow)
+}
+
+/// Element c0 + c1 * u of Fq2 = Fq[u] / (u^2 + 1)
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct Fq2 {
+    pub c0: Fq,
+    pub c1: Fq,
+}
+
+impl Fq2 {
+    pub(crate) fn add(&self, other: &Self) -> Self {
+        Self {
+            c0: self.c0.add(&other.c0),
+            c1: self.c1.add(&other.c1),
+        }
+    }
+
+    /// (a0 + a1 u)(b0 + b1 u) = (a0 b0 - a1 b1) + (a0 b1 + a1 b0) u
+    pub(crate) fn mul(&self, other: &Self) -> Self {
+        Self {
+            c0: self.c0.mul(&other.c0).sub(&self.c1.mul(&other.c1)),
+            c1: self.c0.mul(&other.c1).add(&self.c1.mul(&other.c0)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fq(value: u64) -> Fq {
+        Fq::from_be(Be32::from_u64(value)).unwrap()
+    }
+
+    #[test]
+    fn test_fq_arithmetic() {
+        assert_eq!(fq(6).mul(&fq(7)), fq(42));
+        assert_eq!(fq(40).add(&fq(2)), fq(42));
+        assert_eq!(fq(44).sub(&fq(2)), fq(42));
+
+        // Wrapping around p in both directions
+        let mut p_minus_one = FQ_MODULUS;
+        p_minus_one[31] -= 1;
+        let minus_one = Fq::from_be(Be32::from_syscall(p_minus_one)).unwrap();
+        assert_eq!(minus_one.add(&fq(1)), fq(0));
+        assert_eq!(fq(0).sub(&fq(1)), minus_one);
+        assert_eq!(minus_one.mul(&minus_one), fq(1));
+
+        assert_eq!(Fq::from_be(Be32::from_syscall(FQ_MODULUS)), None);
+    }
+
+    #[test]
+    fn test_fq2_mul_uses_u_squared_minus_one() {
+        let u = Fq2 { c0: fq(0), c1: fq(1) };
+        let mut p_minus_one = FQ_MODULUS;
+        p_minus_one[31] -= 1;
+        let minus_one = Fq::from_be(Be32::from_syscall(p_minus_one)).unwrap();
+        assert_eq!(u.mul(&u), Fq2 { c0: minus_one, c1: fq(0) });
+
+        // (2 + 3u)(4 + 5u) = (8 - 15) + (10 + 12)u
+        let product = Fq2 { c0: fq(2), c1: fq(3) }.mul(&Fq2 { c0: fq(4), c1: fq(5) });
+        assert_eq!(product, Fq2 { c0: fq(0).sub(&fq(7)), c1: fq(22) });
+    }
+
+    #[test]
+    fn test_canonical_boundaries() {
+        let mut p_minus_one = FQ_MODULUS;
+        p_minus_one[31] -= 1;
+        let mut p_plus_one = FQ_MODULUS;
+        p_plus_one[31] += 1;
+
+        assert!(is_canonical_fq(&[0u8; 32]));
+        assert!(is_canonical_fq(&p_minus_one));
+        assert!(!is_canonical_fq(&FQ_MODULUS));
+        assert!(!is_canonical_fq(&p_plus_one));
+        assert!(!is_canonical_fq(&[0xffu8; 32]));
+
+        let mut r_minus_one = FR_MODULUS;
+        r_minus_one[31] -= 1;
+        assert!(is_canonical_fr(&r_minus_one));
+        assert!(!is_canonical_fr(&FR_MODULUS));
+        // r < p, so some canonical base field encodings are not canonical scalars
+        assert!(!is_canonical_fr(&p_minus_one));
+    }
+
+    #[test]
+    fn test_all_limbs_canonical() {
+        let mut point = [0u8; 64];
+        point[31] = 1;
+        point[63] = 2;
+        assert!(all_limbs_canonical(&point));
+
+        point[32..].copy_from_slice(&FQ_MODULUS);
+        assert!(!all_limbs_canonical(&point));
+
+        // Truncated encodings are never canonical
+        assert!(!all_limbs_canonical(&[0u8; 63]));
+    }
+
+    #[test]
+    fn test_reduce_fr() {
+        let mut r = FR_MODULUS;
+        reduce_fr(&mut r);
+        assert_eq!(r, [0u8; 32]);
+
+        let mut r_plus_one = FR_MODULUS;
+        r_plus_one[31] += 1;
+        reduce_fr(&mut r_plus_one);
+        let mut one = [0u8; 32];
+        one[31] = 1;
+        assert_eq!(r_plus_one, one);
+
+        let mut r_minus_one = FR_MODULUS;
+        r_minus_one[31] -= 1;
+        let expected = r_minus_one;
+        reduce_fr(&mut r_minus_one);
+        assert_eq!(r_minus_one, expected);
+
+        let mut p = FQ_MODULUS;
+        reduce_fq(&mut p);
+        assert_eq!(p, [0u8; 32]);
+
+        // 2^256 - 1 = 5r + (2^256 - 1 - 5r)
+        let mut max = [0xffu8; 32];
+        reduce_fr(&mut max);
+        assert_eq!(Scalar::reduce(Be32::from_syscall([0xff; 32])).to_syscall(), max);
+        assert_eq!(
+            max,
+            [
+                0x0e, 0x0a, 0x77, 0xc1, 0x9a, 0x07, 0xdf, 0x2f,
+                0x66, 0x6e, 0xa3, 0x6f, 0x78, 0x79, 0x46, 0x2e,
+                0x36, 0xfc, 0x76, 0x95, 0x9f, 0x60, 0xcd, 0x29,
+                0xac, 0x96, 0x34, 0x1c, 0x4f, 0xff, 0xff, 0xfa,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scalar_constructors_stay_below_r() {
+        assert_eq!(Scalar::from_u64(2).to_syscall(), Be32::from_u64(2).to_syscall());
+        assert_eq!(Scalar::from_u64(0), Scalar::ZERO);
+
+        let r = Be32::from_syscall(FR_MODULUS);
+        assert_eq!(Scalar::from_canonical(r), None);
+        assert_eq!(Scalar::reduce(r), Scalar::ZERO);
+        let mut r_minus_one = FR_MODULUS;
+        r_minus_one[31] -= 1;
+        let r_minus_one = Be32::from_syscall(r_minus_one);
+        assert_eq!(Scalar::from_canonical(r_minus_one), Some(Scalar::reduce(r_minus_one)));
+    }
+}
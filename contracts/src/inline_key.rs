@@ -0,0 +1,313 @@
+// Verification against a caller-supplied key
+// `VerifyWithInlineKey` lets integrators use the program as a plain Groth16
+// verifier for circuits outside the `CircuitId` registry. The key either rides
+// in the instruction or, since a key with more than a few IC points does not
+// fit in a transaction, sits in an account the caller wrote and is pinned by
+// its `VerificationKey::hash`. Public inputs are full-width field elements.
+//
+// Nothing is written in this mode: no receipt, no nullifier, no event. The
+// caller learns only that the proof verified against the key it named.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, msg, program::set_return_data,
+    program_error::ProgramError,
+};
+
+use crate::{
+    endian::Be32,
+    field::Scalar,
+    layout::{check_no_extra_accounts, VERIFY_WITH_INLINE_KEY},
+    verify_groth16_fr, G1Point, G2Point, Groth16Proof, SerializedLen, VerificationKey,
+    VerifierError, MAX_PUBLIC_INPUTS,
+};
+
+/// Offset of the IC length prefix in a Borsh `InlineVerificationKey`
+const IC_LEN_OFFSET: usize = G1Point::LEN + 3 * G2Point::LEN;
+
+/// A Groth16 verification key owned by the caller rather than compiled in
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct InlineVerificationKey {
+    pub alpha_g1: G1Point,
+    pub beta_g2: G2Point,
+    pub gamma_g2: G2Point,
+    pub delta_g2: G2Point,
+    /// IC[0] constant term followed by one point per public input
+    pub ic: Vec<G1Point>,
+}
+
+impl InlineVerificationKey {
+    /// The key in the form `verify_groth16` takes
+    ///
+    /// Caller keys carry no signal names, so the layout hash is zero.
+    pub fn as_key(&self) -> VerificationKey<'_> {
+        VerificationKey {
+            alpha_g1: self.alpha_g1,
+            beta_g2: self.beta_g2,
+            gamma_g2: self.gamma_g2,
+            delta_g2: self.delta_g2,
+            ic: &self.ic,
+            num_public_inputs: self.ic.len().saturating_sub(1),
+            signal_layout_hash: [0u8; 32],
+        }
+    }
+
+    /// `VerificationKey::hash`, what `InlineKeySource::Account` pins
+    pub fn hash(&self) -> [u8; 32] {
+        self.as_key().hash()
+    }
+
+    /// Read a key written at the start of an account
+    ///
+    /// Trailing bytes are ignored. The IC count is checked before any point is
+    /// read, so an oversized key costs no more than a valid one to reject.
+    pub fn from_account_data(data: &[u8]) -> Result<Self, ProgramError> {
+        let ic_len = data
+            .get(IC_LEN_OFFSET..IC_LEN_OFFSET + 4)
+            .ok_or(VerifierError::MalformedAccountData)?;
+        if u32::from_le_bytes(ic_len.try_into().unwrap()) as usize > MAX_PUBLIC_INPUTS + 1 {
+            return Err(VerifierError::TooManyPublicInputs.into());
+        }
+        Self::deserialize(&mut &data[..]).map_err(|_| VerifierError::MalformedAccountData.into())
+    }
+
+    /// Reject keys the pairing check could not meaningfully use
+    ///
+    /// Every point must be canonical, then pass `VerificationKey::validate`,
+    /// which `verify_groth16_fr` leaves to its caller.
+    pub fn validate(&self) -> Result<(), ProgramError> {
+        if self.ic.len() > MAX_PUBLIC_INPUTS + 1 {
+            return Err(VerifierError::TooManyPublicInputs.into());
+        }
+        if self.ic.is_empty() {
+            msg!("Verification key is degenerate");
+            return Err(ProgramError::InvalidArgument);
+        }
+        let key = self.as_key();
+        key.check_g2_not_identity()?;
+        if !key.is_canonical() {
+            return Err(VerifierError::NonCanonicalEncoding.into());
+        }
+        if let Err(err) = key.validate() {
+            msg!("Verification key has an invalid point");
+            return Err(err.into());
+        }
+        Ok(())
+    }
+}
+
+/// Where `VerifyWithInlineKey` finds its key
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub enum InlineKeySource {
+    /// The key itself; fits a transaction only for circuits with few public
+    /// inputs
+    Inline(InlineVerificationKey),
+    /// A Borsh `InlineVerificationKey` at the start of the first account,
+    /// which must hash to `key_hash`
+    Account { key_hash: [u8; 32] },
+}
+
+impl SerializedLen for InlineKeySource {
+    /// An inline key with the most IC points `validate` accepts
+    const MAX_LEN: usize = 1 + IC_LEN_OFFSET + 4 + (MAX_PUBLIC_INPUTS + 1) * G1Point::LEN;
+
+    fn serialized_len(&self) -> usize {
+        match self {
+            Self::Inline(key) => 1 + IC_LEN_OFFSET + 4 + key.ic.len() * G1Point::LEN,
+            Self::Account { .. } => 1 + 32,
+        }
+    }
+}
+
+/// Verify a proof of any circuit against a key the caller supplies
+///
+/// `public_inputs` are big-endian field elements and must be below the scalar
+/// field modulus. Sets the key's hash as return data.
+///
+/// Accounts expected:
+/// 0. `[]` Key account, only for `InlineKeySource::Account`; any owner
+pub fn process_verify_with_inline_key(
+    accounts: &[AccountInfo],
+    key: &InlineKeySource,
+    proof: &Groth16Proof,
+    public_inputs: &[Be32],
+) -> ProgramResult {
+    if public_inputs.len() > MAX_PUBLIC_INPUTS {
+        return Err(VerifierError::TooManyPublicInputs.into());
+    }
+
+    let layout = VERIFY_WITH_INLINE_KEY;
+    layout.check(accounts)?;
+    let stored;
+    let (vk, key_hash) = match key {
+        InlineKeySource::Inline(vk) => {
+            check_no_extra_accounts(accounts)?;
+            (vk, vk.hash())
+        }
+        InlineKeySource::Account { key_hash } => {
+            let key_account = accounts
+                .get(layout.key_account)
+                .ok_or(ProgramError::NotEnoughAccountKeys)?;
+            stored = InlineVerificationKey::from_account_data(&key_account.data.borrow())?;
+            if stored.hash() != *key_hash {
+                msg!("Key account does not hold the expected key");
+                return Err(VerifierError::InlineKeyHashMismatch.into());
+            }
+            (&stored, *key_hash)
+        }
+    };
+    vk.validate()?;
+
+    let scalars = public_inputs
+        .iter()
+        .map(|input| Scalar::from_canonical(*input).ok_or(VerifierError::NonCanonicalEncoding))
+        .collect::<Result<Vec<_>, _>>()?;
+    verify_groth16_fr(&vk.as_key(), proof, &scalars)?;
+
+    set_return_data(&key_hash);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn g1_generator() -> G1Point {
+        let mut point = [0u8; 64];
+        point[31] = 1;
+        point[63] = 2;
+        G1Point::new(point)
+    }
+
+    /// G2 generator in syscall order: x.c1, x.c0, y.c1, y.c0
+    const G2_GENERATOR: [u8; 128] = [
+        0x19, 0x8e, 0x93, 0x93, 0x92, 0x0d, 0x48, 0x3a,
+        0x72, 0x60, 0xbf, 0xb7, 0x31, 0xfb, 0x5d, 0x25,
+        0xf1, 0xaa, 0x49, 0x33, 0x35, 0xa9, 0xe7, 0x12,
+        0x97, 0xe4, 0x85, 0xb7, 0xae, 0xf3, 0x12, 0xc2,
+        0x18, 0x00, 0xde, 0xef, 0x12, 0x1f, 0x1e, 0x76,
+        0x42, 0x6a, 0x00, 0x66, 0x5e, 0x5c, 0x44, 0x79,
+        0x67, 0x43, 0x22, 0xd4, 0xf7, 0x5e, 0xda, 0xdd,
+        0x46, 0xde, 0xbd, 0x5c, 0xd9, 0x92, 0xf6, 0xed,
+        0x09, 0x06, 0x89, 0xd0, 0x58, 0x5f, 0xf0, 0x75,
+        0xec, 0x9e, 0x99, 0xad, 0x69, 0x0c, 0x33, 0x95,
+        0xbc, 0x4b, 0x31, 0x33, 0x70, 0xb3, 0x8e, 0xf3,
+        0x55, 0xac, 0xda, 0xdc, 0xd1, 0x22, 0x97, 0x5b,
+        0x12, 0xc8, 0x5e, 0xa5, 0xdb, 0x8c, 0x6d, 0xeb,
+        0x4a, 0xab, 0x71, 0x80, 0x8d, 0xcb, 0x40, 0x8f,
+        0xe3, 0xd1, 0xe7, 0x69, 0x0c, 0x43, 0xd3, 0x7b,
+        0x4c, 0xe6, 0xcc, 0x01, 0x66, 0xfa, 0x7d, 0xaa,
+    ];
+
+    /// IC points k*G for k = 1..=n; the G2 points are the G2 generator
+    fn fixture_key(n: usize) -> InlineVerificationKey {
+        let g = g1_generator();
+        let mut ic = vec![g];
+        for _ in 1..n {
+            let next = ic.last().unwrap().add(&g).unwrap();
+            ic.push(next);
+        }
+        let g2 = G2Point::new(G2_GENERATOR);
+        InlineVerificationKey {
+            alpha_g1: g,
+            beta_g2: g2,
+            gamma_g2: g2,
+            delta_g2: g2,
+            ic,
+        }
+    }
+
+    #[test]
+    fn test_account_data_round_trip() {
+        let vk = fixture_key(4);
+        let mut data = vk.try_to_vec().unwrap();
+        assert_eq!(data.len(), IC_LEN_OFFSET + 4 + 4 * G1Point::LEN);
+
+        // Accounts may be larger than the key
+        data.extend_from_slice(&[0u8; 100]);
+        assert_eq!(InlineVerificationKey::from_account_data(&data), Ok(vk.clone()));
+        assert_eq!(
+            InlineVerificationKey::from_account_data(&data[..IC_LEN_OFFSET + 4 + 64]),
+            Err(VerifierError::MalformedAccountData.into())
+        );
+        assert_eq!(
+            InlineVerificationKey::from_account_data(&data[..IC_LEN_OFFSET]),
+            Err(VerifierError::MalformedAccountData.into())
+        );
+
+        // The hash pins every point
+        let mut other = vk.clone();
+        other.ic[2] = other.ic[3];
+        assert_ne!(other.hash(), vk.hash());
+    }
+
+    #[test]
+    fn test_oversized_key_rejected_before_reading_points() {
+        // Only the header is present; reading any IC point would fail as
+        // malformed instead
+        let mut data = fixture_key(1).try_to_vec().unwrap();
+        data.truncate(IC_LEN_OFFSET + 4);
+        data[IC_LEN_OFFSET..].copy_from_slice(&(MAX_PUBLIC_INPUTS as u32 + 2).to_le_bytes());
+        assert_eq!(
+            InlineVerificationKey::from_account_data(&data),
+            Err(VerifierError::TooManyPublicInputs.into())
+        );
+
+        let vk = fixture_key(MAX_PUBLIC_INPUTS + 2);
+        assert_eq!(vk.validate(), Err(VerifierError::TooManyPublicInputs.into()));
+        assert_eq!(fixture_key(MAX_PUBLIC_INPUTS + 1).validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_invalid_keys_rejected() {
+        let mut off_curve = [0u8; 64];
+        off_curve[31] = 1;
+        off_curve[63] = 1;
+
+        let mut vk = fixture_key(3);
+        vk.ic[1] = G1Point::new(off_curve);
+        assert_eq!(vk.validate(), Err(VerifierError::InvalidVerificationKey.into()));
+
+        let mut vk = fixture_key(3);
+        vk.alpha_g1 = G1Point::IDENTITY;
+        assert_eq!(vk.validate(), Err(VerifierError::InvalidVerificationKey.into()));
+
+        let mut vk = fixture_key(3);
+        vk.ic[2] = G1Point::IDENTITY;
+        assert_eq!(vk.validate(), Err(VerifierError::InvalidVerificationKey.into()));
+
+        let mut vk = fixture_key(3);
+        vk.ic.clear();
+        assert_eq!(vk.validate(), Err(ProgramError::InvalidArgument));
+
+        let mut vk = fixture_key(3);
+        vk.ic[0] = G1Point::new([0xff; 64]);
+        assert_eq!(vk.validate(), Err(VerifierError::NonCanonicalEncoding.into()));
+
+        let mut off_twist = [0u8; 128];
+        off_twist[31] = 1;
+        off_twist[127] = 1;
+        let mut vk = fixture_key(3);
+        vk.delta_g2 = G2Point::new(off_twist);
+        assert_eq!(vk.validate(), Err(VerifierError::InvalidVerificationKey.into()));
+    }
+
+    #[test]
+    fn test_identity_g2_keys_rejected() {
+        // x.c1 = p and y.c0 = p: not canonical, but an invalid key first
+        let mut near_identity = [0u8; 128];
+        near_identity[..32].copy_from_slice(&crate::field::FQ_MODULUS);
+        near_identity[96..].copy_from_slice(&crate::field::FQ_MODULUS);
+        for identity in [G2Point::IDENTITY, G2Point::new(near_identity)] {
+            for slot in 0..3 {
+                let mut vk = fixture_key(3);
+                match slot {
+                    0 => vk.beta_g2 = identity,
+                    1 => vk.gamma_g2 = identity,
+                    _ => vk.delta_g2 = identity,
+                }
+                assert_eq!(vk.validate(), Err(VerifierError::InvalidVerificationKey.into()));
+            }
+        }
+    }
+}
@@ -0,0 +1,765 @@
+// Account orderings of the program's instructions
+// Each layout names the index of every account an instruction takes. The
+// processors look their accounts up by these indices and the client builders
+// place their metas at them, so the two cannot drift apart when an
+// instruction's accounts are reordered. The indices are part of the program's
+// interface: a client that counts accounts by hand should read them from here.
+//
+// Accounts whose position depends on the instruction data, such as the
+// invoice index and stats PDAs of `VerifyAndRecord`, come after a layout's
+// `LEN` and are read in order by their processor.
+//
+// No instruction ignores an account: one passed beyond those the instruction
+// reads, an account it writes passed read-only, or an account it only reads
+// passed writable fails it with `UnexpectedAccount`, so a transaction shows
+// only the accounts the program actually uses and locks only those it writes.
+// A signer is exempt from the last rule, since paying the fee makes it
+// writable.
+
+use std::slice::Iter;
+
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, instruction::AccountMeta, msg,
+    program_error::ProgramError, pubkey::Pubkey, system_program,
+};
+
+use crate::{VerifierError, VerifierInstruction};
+
+/// One account of an instruction's layout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccountSpec {
+    pub name: &'static str,
+    pub index: usize,
+    pub signer: bool,
+    pub writable: bool,
+    /// May be left off; only trailing accounts are optional
+    pub optional: bool,
+}
+
+impl AccountSpec {
+    pub const fn new(name: &'static str, index: usize) -> Self {
+        AccountSpec {
+            name,
+            index,
+            signer: false,
+            writable: false,
+            optional: false,
+        }
+    }
+
+    pub const fn signer(self) -> Self {
+        AccountSpec {
+            signer: true,
+            ..self
+        }
+    }
+
+    pub const fn writable(self) -> Self {
+        AccountSpec {
+            writable: true,
+            ..self
+        }
+    }
+
+    pub const fn optional(self) -> Self {
+        AccountSpec {
+            optional: true,
+            ..self
+        }
+    }
+}
+
+/// Check `accounts` holds every required account of `specs` and nothing
+/// more, that the signers signed and that exactly the writable accounts are
+/// writable
+pub fn check_accounts(specs: &[AccountSpec], accounts: &[AccountInfo]) -> ProgramResult {
+    check_leading_accounts(specs, accounts)?;
+    check_no_extra_accounts(accounts.get(specs.len()..).unwrap_or_default())
+}
+
+/// `check_accounts` for a layout followed by accounts its processor reads
+/// itself, which must pass what it leaves over to `check_no_extra_accounts`
+pub fn check_leading_accounts(specs: &[AccountSpec], accounts: &[AccountInfo]) -> ProgramResult {
+    let required = specs.iter().filter(|spec| !spec.optional).count();
+    if accounts.len() < required {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    for (spec, account) in specs.iter().zip(accounts) {
+        if spec.signer && !account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if spec.writable && !account.is_writable {
+            msg!("Writable account passed read-only:");
+            account.key.log();
+            return Err(VerifierError::UnexpectedAccount.into());
+        }
+        if !spec.writable && !spec.signer && account.is_writable {
+            msg!("Read-only account passed writable:");
+            account.key.log();
+            return Err(VerifierError::UnexpectedAccount.into());
+        }
+    }
+    Ok(())
+}
+
+/// Fail with `UnexpectedAccount` unless every account was read
+///
+/// `remaining` is what is left after an instruction's last account, as
+/// `Iter::as_slice` returns it.
+pub fn check_no_extra_accounts(remaining: &[AccountInfo]) -> ProgramResult {
+    if let Some(account) = remaining.first() {
+        msg!("Unexpected account:");
+        account.key.log();
+        return Err(VerifierError::UnexpectedAccount.into());
+    }
+    Ok(())
+}
+
+/// The next account if it is the system program
+///
+/// For instructions that only need the system program to grow an older
+/// account, which take it whenever the client passes it.
+pub fn next_system_program<'a, 'b>(
+    account_info_iter: &mut Iter<'a, AccountInfo<'b>>,
+) -> Option<&'a AccountInfo<'b>> {
+    match account_info_iter.as_slice().first() {
+        Some(account) if system_program::check_id(account.key) => account_info_iter.next(),
+        _ => None,
+    }
+}
+
+/// The optional system program at `index`, if it was passed
+///
+/// Fails with `UnexpectedAccount` when another account takes its place, so an
+/// optional account is checked for what it is whether or not it is used.
+pub fn optional_system_program<'a, 'b>(
+    accounts: &'a [AccountInfo<'b>],
+    index: usize,
+) -> Result<Option<&'a AccountInfo<'b>>, ProgramError> {
+    match accounts.get(index) {
+        Some(account) if !system_program::check_id(account.key) => {
+            msg!("Unexpected account:");
+            account.key.log();
+            Err(VerifierError::UnexpectedAccount.into())
+        }
+        account => Ok(account),
+    }
+}
+
+/// Metas for `keys`, each paired with its index, flagged as `specs` says
+///
+/// Panics unless the indices cover every required account and leave no gap,
+/// which only a builder out of step with its layout does.
+pub fn to_metas(specs: &[AccountSpec], keys: &[(usize, Pubkey)]) -> Vec<AccountMeta> {
+    let required = specs.iter().filter(|spec| !spec.optional).count();
+    assert!(keys.len() >= required, "missing accounts for the layout");
+    let mut keys = keys.to_vec();
+    keys.sort_by_key(|(index, _)| *index);
+    keys.into_iter()
+        .enumerate()
+        .map(|(position, (index, pubkey))| {
+            assert_eq!(index, position, "accounts must fill the layout in order");
+            AccountMeta {
+                pubkey,
+                is_signer: specs[index].signer,
+                is_writable: specs[index].writable,
+            }
+        })
+        .collect()
+}
+
+/// The layout of `instruction`'s accounts; `GetVersion` takes none
+///
+/// Matches every variant by name, so an instruction cannot be added without
+/// a layout.
+pub fn instruction_specs(instruction: &VerifierInstruction) -> &'static [AccountSpec] {
+    use VerifierInstruction::*;
+    match instruction {
+        VerifyProof { .. }
+        | VerifyProofV2 { .. }
+        | VerifyEnvelope { .. }
+        | VerifyEnvelopeWithMaxSkew { .. } => VerifyProofAccounts::SPECS,
+        VerifyAndRecord { .. } => VerifyAndRecordAccounts::SPECS,
+        VerifySplitPayment { .. } => VerifySplitPaymentAccounts::SPECS,
+        VerifyRefund { .. } => VerifyRefundAccounts::SPECS,
+        VerifySubscription { .. } => VerifySubscriptionAccounts::SPECS,
+        VerifyBatchSoft { .. } => VerifyBatchSoftAccounts::SPECS,
+        VerifyBatch { .. } => VerifyBatchAccounts::SPECS,
+        WriteBatchBuffer { .. } => WriteBatchBufferAccounts::SPECS,
+        VerifyBatchBuffered { .. } => VerifyBatchBufferedAccounts::SPECS,
+        VerifyProofWithAck { .. } => VerifyProofWithAckAccounts::SPECS,
+        TouchReceipt { .. } => TouchReceiptAccounts::SPECS,
+        Deposit { .. } => DepositAccounts::SPECS,
+        Withdraw { .. } => WithdrawAccounts::SPECS,
+        VerifyWithInlineKey { .. } => VerifyWithInlineKeyAccounts::SPECS,
+        VerifyBatchWithReceipt { .. } => VerifyBatchWithReceiptAccounts::SPECS,
+        VerifyBatchInclusion { .. } => VerifyBatchInclusionAccounts::SPECS,
+        ConsumeReceipt { .. } => ConsumeReceiptAccounts::SPECS,
+        FinalizeReceipt { .. } => FinalizeReceiptAccounts::SPECS,
+        GetReceiptStatus { .. } => GetReceiptStatusAccounts::SPECS,
+        AbortBuffer { .. } => AbortBufferAccounts::SPECS,
+        ReapStale { .. } => ReapStaleAccounts::SPECS,
+        MigrateReceipt { .. } => MigrateReceiptAccounts::SPECS,
+        VerifyWithOpening { .. } => VerifyWithOpeningAccounts::SPECS,
+        ClaimRelayerRebate { .. } => ClaimRelayerRebateAccounts::SPECS,
+        InitializeConfig { .. } => InitializeConfigAccounts::SPECS,
+        SetRecipientRoot { .. }
+        | SetEpochLength { .. }
+        | SetInputPolicy { .. }
+        | SetMaxReceiptAge { .. }
+        | ProposeAdmin { .. }
+        | CancelProposedAdmin => AdminConfigAccounts::SPECS,
+        AcceptAdmin => AcceptAdminAccounts::SPECS,
+        SetAuditEnabled { .. }
+        | SetMaxKeyDeprecation { .. }
+        | SetInvoiceOverpayment { .. }
+        | SetRateLimit { .. }
+        | SetFinalitySlots { .. }
+        | SetScratchTtl { .. }
+        | SetTrackRelayer { .. } => AdminConfigGrowingAccounts::SPECS,
+        SetAdminSigners { .. } => SetAdminSignersAccounts::SPECS,
+        PrepareVerificationKey { .. } => PrepareVerificationKeyAccounts::SPECS,
+        DeprecatePreviousKey { .. } | RevokeVerificationKey { .. } | SetHashBackend { .. } => {
+            AdminPreparedKeyAccounts::SPECS
+        }
+        InitializeAuditLog => InitializeAuditLogAccounts::SPECS,
+        InitializePool { .. } => InitializePoolAccounts::SPECS,
+        InitializeRelayerList | AddRelayer { .. } => RelayerListAccounts::SPECS,
+        RemoveRelayer { .. } => RemoveRelayerAccounts::SPECS,
+        DenyRecipient { .. } => DenyRecipientAccounts::SPECS,
+        AllowRecipient { .. } => AllowRecipientAccounts::SPECS,
+        GetVersion => &[],
+    }
+}
+
+/// Declare a layout struct, its account specs and its constant
+///
+/// Each account is `name: index [flags]`, the flags among `signer`,
+/// `writable` and `optional`.
+macro_rules! account_layouts {
+    ($(
+        $(#[$doc:meta])*
+        $layout:ident: $name:ident {
+            $($field:ident: $index:literal [$($flag:ident),*]),* $(,)?
+        }
+    )*) => {$(
+        $(#[$doc])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub struct $name {
+            $(pub $field: usize,)*
+        }
+
+        impl $name {
+            /// Every account of the layout, in index order
+            pub const SPECS: &'static [AccountSpec] = &[
+                $(AccountSpec::new(stringify!($field), $index)$(.$flag())*,)*
+            ];
+
+            /// Number of accounts in the layout; later ones are read in order
+            pub const LEN: usize = Self::SPECS.len();
+
+            /// Check `accounts` against the layout, as the processor does
+            /// before indexing into it
+            pub fn check(&self, accounts: &[AccountInfo]) -> ProgramResult {
+                check_accounts(Self::SPECS, accounts)
+            }
+
+            /// `check` for an instruction whose processor reads accounts
+            /// after `LEN` itself
+            pub fn check_leading(&self, accounts: &[AccountInfo]) -> ProgramResult {
+                check_leading_accounts(Self::SPECS, accounts)
+            }
+
+            /// Metas for `keys`, each paired with its index in the layout
+            pub fn metas(&self, keys: &[(usize, Pubkey)]) -> Vec<AccountMeta> {
+                to_metas(Self::SPECS, keys)
+            }
+        }
+
+        $(#[$doc])*
+        pub const $layout: $name = $name { $($field: $index,)* };
+    )*};
+}
+
+account_layouts! {
+    /// Accounts of `VerifyProof`, `VerifyProofV2` and the `VerifyEnvelope`
+    /// variants
+    VERIFY_PROOF: VerifyProofAccounts {
+        system_program: 0 [],
+        config: 1 [],
+        prepared_key: 2 [],
+        marker: 3 [],
+    }
+
+    /// Accounts of `VerifyAndRecord`, followed by the invoice index PDA unless
+    /// the invoice id is zero, the stats PDA when `update_stats` and the audit
+    /// log PDA when auditing
+    VERIFY_AND_RECORD: VerifyAndRecordAccounts {
+        payer: 0 [signer, writable],
+        config: 1 [],
+        receipt: 2 [writable],
+        system_program: 3 [],
+        marker: 4 [],
+        prepared_key: 5 [],
+    }
+
+    /// Accounts of `VerifySubscription`
+    VERIFY_SUBSCRIPTION: VerifySubscriptionAccounts {
+        payer: 0 [signer, writable],
+        config: 1 [],
+        nullifier: 2 [writable],
+        system_program: 3 [],
+        marker: 4 [],
+        legacy_nullifier: 5 [],
+        prepared_key: 6 [],
+    }
+
+    /// Accounts of `Deposit`
+    DEPOSIT: DepositAccounts {
+        depositor: 0 [signer, writable],
+        pool: 1 [writable],
+        system_program: 2 [],
+    }
+
+    /// Accounts of `Withdraw`
+    WITHDRAW: WithdrawAccounts {
+        payer: 0 [signer, writable],
+        pool: 1 [writable],
+        nullifier: 2 [writable],
+        recipient: 3 [writable],
+        system_program: 4 [],
+        marker: 5 [],
+        prepared_key: 6 [],
+    }
+
+    /// Accounts of `VerifySplitPayment`; the settlement accounts are required
+    /// with `settle` and followed by the mint's transfer hook accounts
+    VERIFY_SPLIT_PAYMENT: VerifySplitPaymentAccounts {
+        payer: 0 [signer, writable],
+        receipt: 1 [writable],
+        system_program: 2 [],
+        first_marker: 3 [],
+        second_marker: 4 [],
+        prepared_key: 5 [],
+        source: 6 [writable, optional],
+        first_destination: 7 [writable, optional],
+        second_destination: 8 [writable, optional],
+        token_program: 9 [optional],
+        mint: 10 [optional],
+    }
+
+    /// Accounts of `VerifyBatchWithReceipt`
+    VERIFY_BATCH_WITH_RECEIPT: VerifyBatchWithReceiptAccounts {
+        relayer: 0 [signer, writable],
+        receipt: 1 [writable],
+        system_program: 2 [],
+        prepared_key: 3 [],
+    }
+
+    /// Accounts of `VerifyBatchInclusion`
+    VERIFY_BATCH_INCLUSION: VerifyBatchInclusionAccounts {
+        receipt: 0 [],
+    }
+
+    /// Accounts of `WriteBatchBuffer`
+    WRITE_BATCH_BUFFER: WriteBatchBufferAccounts {
+        authority: 0 [signer, writable],
+        buffer: 1 [writable],
+        system_program: 2 [],
+    }
+
+    /// Accounts of `VerifyBatchBuffered`
+    VERIFY_BATCH_BUFFERED: VerifyBatchBufferedAccounts {
+        authority: 0 [signer, writable],
+        buffer: 1 [writable],
+        prepared_key: 2 [],
+    }
+
+    /// Accounts of `AbortBuffer`
+    ABORT_BUFFER: AbortBufferAccounts {
+        authority: 0 [signer, writable],
+        buffer: 1 [writable],
+    }
+
+    /// Accounts of `ReapStale`
+    REAP_STALE: ReapStaleAccounts {
+        reaper: 0 [signer, writable],
+        buffer: 1 [writable],
+        authority: 2 [writable],
+        config: 3 [],
+    }
+
+    /// Accounts of `ConsumeReceipt`
+    CONSUME_RECEIPT: ConsumeReceiptAccounts {
+        consumer: 0 [signer],
+        receipt: 1 [writable],
+    }
+
+    /// Accounts of `FinalizeReceipt`; the system program is only needed to
+    /// grow a receipt older than version 3
+    FINALIZE_RECEIPT: FinalizeReceiptAccounts {
+        caller: 0 [signer, writable],
+        config: 1 [],
+        receipt: 2 [writable],
+        system_program: 3 [optional],
+    }
+
+    /// Accounts of `MigrateReceipt`
+    MIGRATE_RECEIPT: MigrateReceiptAccounts {
+        caller: 0 [signer, writable],
+        config: 1 [writable],
+        receipt: 2 [writable],
+        system_program: 3 [],
+    }
+
+    /// Accounts of `GetReceiptStatus`
+    GET_RECEIPT_STATUS: GetReceiptStatusAccounts {
+        receipt: 0 [],
+    }
+
+    /// Accounts of `VerifyWithOpening`
+    VERIFY_WITH_OPENING: VerifyWithOpeningAccounts {
+        payer: 0 [signer, writable],
+        config: 1 [],
+        receipt: 2 [writable],
+        system_program: 3 [],
+        marker: 4 [],
+        prepared_key: 5 [],
+    }
+
+    /// Accounts of `ClaimRelayerRebate`
+    CLAIM_RELAYER_REBATE: ClaimRelayerRebateAccounts {
+        merchant: 0 [signer],
+        deposit: 1 [writable],
+        relayer: 2 [writable],
+        receipt: 3 [writable],
+        system_program: 4 [],
+    }
+
+    /// Accounts of `VerifyRefund`; the invoice index is marked refunded when
+    /// it is passed
+    VERIFY_REFUND: VerifyRefundAccounts {
+        receipt: 0 [writable],
+        prepared_key: 1 [],
+        invoice_index: 2 [writable, optional],
+    }
+
+    /// Accounts of `VerifyBatch`, followed by the denylist marker PDA of each
+    /// proof's recipient in batch order
+    VERIFY_BATCH: VerifyBatchAccounts {
+        prepared_key: 0 [],
+    }
+
+    /// Accounts of `VerifyBatchSoft`, followed by each proof's receipt,
+    /// invoice index, denylist marker and stats PDAs and then the audit log
+    /// PDA, as the instruction documents
+    VERIFY_BATCH_SOFT: VerifyBatchSoftAccounts {
+        payer: 0 [signer, writable],
+        config: 1 [],
+        system_program: 2 [],
+        prepared_key: 3 [],
+    }
+
+    /// Accounts of `VerifyProofWithAck`
+    VERIFY_PROOF_WITH_ACK: VerifyProofWithAckAccounts {
+        config: 0 [],
+        instructions_sysvar: 1 [],
+        marker: 2 [],
+        prepared_key: 3 [],
+    }
+
+    /// Accounts of `TouchReceipt`
+    TOUCH_RECEIPT: TouchReceiptAccounts {
+        recipient: 0 [signer],
+        config: 1 [],
+        receipt: 2 [writable],
+        marker: 3 [],
+        prepared_key: 4 [],
+    }
+
+    /// Accounts of `VerifyWithInlineKey`; the key account is passed only for
+    /// `InlineKeySource::Account`
+    VERIFY_WITH_INLINE_KEY: VerifyWithInlineKeyAccounts {
+        key_account: 0 [optional],
+    }
+
+    /// Accounts of `InitializeConfig`
+    INITIALIZE_CONFIG: InitializeConfigAccounts {
+        admin: 0 [signer, writable],
+        config: 1 [writable],
+        system_program: 2 [],
+    }
+
+    /// Accounts of the admin instructions that only rewrite the config:
+    /// `SetRecipientRoot`, `SetEpochLength`, `SetInputPolicy`,
+    /// `SetMaxReceiptAge`, `CancelProposedAdmin`, and `ProposeAdmin`, which
+    /// takes its co-signers after them
+    ADMIN_CONFIG: AdminConfigAccounts {
+        admin: 0 [signer],
+        config: 1 [writable],
+    }
+
+    /// Accounts of `AcceptAdmin`
+    ACCEPT_ADMIN: AcceptAdminAccounts {
+        pending_admin: 0 [signer],
+        config: 1 [writable],
+    }
+
+    /// Accounts of the admin instructions that may grow an older config:
+    /// `SetAuditEnabled`, `SetMaxKeyDeprecation`, `SetInvoiceOverpayment`,
+    /// `SetRateLimit`, `SetFinalitySlots`, `SetScratchTtl` and
+    /// `SetTrackRelayer`; the system program is only needed to grow it
+    ADMIN_CONFIG_GROWING: AdminConfigGrowingAccounts {
+        admin: 0 [signer, writable],
+        config: 1 [writable],
+        system_program: 2 [optional],
+    }
+
+    /// Accounts of `SetAdminSigners`, followed by the co-signers
+    SET_ADMIN_SIGNERS: SetAdminSignersAccounts {
+        admin: 0 [signer, writable],
+        config: 1 [writable],
+        system_program: 2 [],
+    }
+
+    /// Accounts of `PrepareVerificationKey`, followed by the co-signers
+    PREPARE_VERIFICATION_KEY: PrepareVerificationKeyAccounts {
+        admin: 0 [signer, writable],
+        config: 1 [],
+        prepared_key: 2 [writable],
+        system_program: 3 [],
+    }
+
+    /// Accounts of `DeprecatePreviousKey`, `RevokeVerificationKey` and
+    /// `SetHashBackend`, followed by the system program when the client
+    /// passes it and then, but for `RevokeVerificationKey`, the co-signers
+    ADMIN_PREPARED_KEY: AdminPreparedKeyAccounts {
+        admin: 0 [signer, writable],
+        config: 1 [],
+        prepared_key: 2 [writable],
+    }
+
+    /// Accounts of `InitializeAuditLog`
+    INITIALIZE_AUDIT_LOG: InitializeAuditLogAccounts {
+        admin: 0 [signer, writable],
+        config: 1 [],
+        audit_log: 2 [writable],
+        system_program: 3 [],
+    }
+
+    /// Accounts of `InitializePool`
+    INITIALIZE_POOL: InitializePoolAccounts {
+        admin: 0 [signer, writable],
+        config: 1 [],
+        pool: 2 [writable],
+        system_program: 3 [],
+    }
+
+    /// Accounts of `InitializeRelayerList` and `AddRelayer`
+    RELAYER_LIST: RelayerListAccounts {
+        admin: 0 [signer, writable],
+        config: 1 [],
+        relayer_list: 2 [writable],
+        system_program: 3 [],
+    }
+
+    /// Accounts of `RemoveRelayer`
+    REMOVE_RELAYER: RemoveRelayerAccounts {
+        admin: 0 [signer],
+        config: 1 [],
+        relayer_list: 2 [writable],
+    }
+
+    /// Accounts of `DenyRecipient`
+    DENY_RECIPIENT: DenyRecipientAccounts {
+        admin: 0 [signer, writable],
+        config: 1 [],
+        marker: 2 [writable],
+        system_program: 3 [],
+    }
+
+    /// Accounts of `AllowRecipient`
+    ALLOW_RECIPIENT: AllowRecipientAccounts {
+        admin: 0 [signer, writable],
+        config: 1 [],
+        marker: 2 [writable],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL_SPECS: [&[AccountSpec]; 37] = [
+        VerifyProofAccounts::SPECS,
+        VerifyAndRecordAccounts::SPECS,
+        VerifySubscriptionAccounts::SPECS,
+        DepositAccounts::SPECS,
+        WithdrawAccounts::SPECS,
+        VerifySplitPaymentAccounts::SPECS,
+        VerifyBatchWithReceiptAccounts::SPECS,
+        VerifyBatchInclusionAccounts::SPECS,
+        WriteBatchBufferAccounts::SPECS,
+        VerifyBatchBufferedAccounts::SPECS,
+        AbortBufferAccounts::SPECS,
+        ReapStaleAccounts::SPECS,
+        ConsumeReceiptAccounts::SPECS,
+        FinalizeReceiptAccounts::SPECS,
+        MigrateReceiptAccounts::SPECS,
+        GetReceiptStatusAccounts::SPECS,
+        VerifyWithOpeningAccounts::SPECS,
+        ClaimRelayerRebateAccounts::SPECS,
+        VerifyRefundAccounts::SPECS,
+        VerifyBatchAccounts::SPECS,
+        VerifyBatchSoftAccounts::SPECS,
+        VerifyProofWithAckAccounts::SPECS,
+        TouchReceiptAccounts::SPECS,
+        VerifyWithInlineKeyAccounts::SPECS,
+        InitializeConfigAccounts::SPECS,
+        AdminConfigAccounts::SPECS,
+        AcceptAdminAccounts::SPECS,
+        AdminConfigGrowingAccounts::SPECS,
+        SetAdminSignersAccounts::SPECS,
+        PrepareVerificationKeyAccounts::SPECS,
+        AdminPreparedKeyAccounts::SPECS,
+        InitializeAuditLogAccounts::SPECS,
+        InitializePoolAccounts::SPECS,
+        RelayerListAccounts::SPECS,
+        RemoveRelayerAccounts::SPECS,
+        DenyRecipientAccounts::SPECS,
+        AllowRecipientAccounts::SPECS,
+    ];
+
+    #[test]
+    fn test_layouts_are_contiguous_with_trailing_optionals() {
+        for specs in ALL_SPECS {
+            for (position, spec) in specs.iter().enumerate() {
+                assert_eq!(spec.index, position, "{}", spec.name);
+            }
+            let first_optional = specs.iter().position(|spec| spec.optional);
+            if let Some(first) = first_optional {
+                assert!(specs[first..].iter().all(|spec| spec.optional));
+            }
+        }
+    }
+
+    #[test]
+    fn test_layout_indices_are_stable() {
+        assert_eq!(VERIFY_AND_RECORD.payer, 0);
+        assert_eq!(VERIFY_AND_RECORD.receipt, 2);
+        assert_eq!(VERIFY_AND_RECORD.marker, 4);
+        assert_eq!(VERIFY_AND_RECORD.prepared_key, 5);
+        assert_eq!(VerifyAndRecordAccounts::LEN, 6);
+        assert_eq!(VERIFY_SPLIT_PAYMENT.prepared_key, 5);
+        assert_eq!(VERIFY_SPLIT_PAYMENT.mint, 10);
+        assert_eq!(VerifySplitPaymentAccounts::LEN, 11);
+        assert_eq!(FINALIZE_RECEIPT.system_program, 3);
+        assert_eq!(REAP_STALE.authority, 2);
+    }
+
+    #[test]
+    fn test_every_layout_rejects_wrong_writability() {
+        let owner = Pubkey::default();
+        for specs in ALL_SPECS {
+            let keys: Vec<Pubkey> = specs.iter().map(|_| Pubkey::new_unique()).collect();
+            let mut lamports = vec![0u64; specs.len()];
+            let mut data = vec![Vec::<u8>::new(); specs.len()];
+            let mut accounts: Vec<AccountInfo> = specs
+                .iter()
+                .zip(&keys)
+                .zip(lamports.iter_mut().zip(data.iter_mut()))
+                .map(|((spec, key), (lamports, data))| {
+                    AccountInfo::new(
+                        key,
+                        spec.signer,
+                        spec.writable,
+                        lamports,
+                        data,
+                        &owner,
+                        false,
+                        0,
+                    )
+                })
+                .collect();
+            assert_eq!(check_accounts(specs, &accounts), Ok(()));
+
+            // Flipping any account other than a read-only signer fails the check
+            for spec in specs.iter().filter(|spec| spec.writable || !spec.signer) {
+                accounts[spec.index].is_writable = !spec.writable;
+                assert_eq!(
+                    check_accounts(specs, &accounts),
+                    Err(VerifierError::UnexpectedAccount.into()),
+                    "{}",
+                    spec.name
+                );
+                accounts[spec.index].is_writable = spec.writable;
+            }
+
+            // A read-only signer may be writable, as the fee payer is
+            for spec in specs.iter().filter(|spec| spec.signer && !spec.writable) {
+                accounts[spec.index].is_writable = true;
+                assert_eq!(check_accounts(specs, &accounts), Ok(()), "{}", spec.name);
+                accounts[spec.index].is_writable = false;
+            }
+        }
+    }
+
+    #[test]
+    fn test_optional_system_program() {
+        let owner = Pubkey::default();
+        let mut keys: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+        keys[2] = system_program::id();
+        let mut lamports = vec![0u64; keys.len()];
+        let mut data = vec![Vec::<u8>::new(); keys.len()];
+        let accounts: Vec<AccountInfo> = keys
+            .iter()
+            .zip(lamports.iter_mut().zip(data.iter_mut()))
+            .map(|(key, (lamports, data))| {
+                AccountInfo::new(key, false, false, lamports, data, &owner, false, 0)
+            })
+            .collect();
+        let layout = ADMIN_CONFIG_GROWING;
+
+        let system = optional_system_program(&accounts[..3], layout.system_program).unwrap();
+        assert_eq!(system.map(|account| *account.key), Some(keys[2]));
+        let absent = optional_system_program(&accounts[..2], layout.system_program).unwrap();
+        assert!(absent.is_none());
+
+        // Any other account in its place is refused, not ignored
+        let mut stray = accounts.clone();
+        stray.remove(2);
+        assert_eq!(
+            optional_system_program(&stray, layout.system_program).unwrap_err(),
+            VerifierError::UnexpectedAccount.into()
+        );
+    }
+
+    #[test]
+    fn test_to_metas_orders_and_flags() {
+        let keys: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+        let layout = FINALIZE_RECEIPT;
+        let metas = layout.metas(&[
+            (layout.receipt, keys[2]),
+            (layout.caller, keys[0]),
+            (layout.config, keys[1]),
+        ]);
+        assert_eq!(
+            metas,
+            vec![
+                AccountMeta::new(keys[0], true),
+                AccountMeta::new_readonly(keys[1], false),
+                AccountMeta::new(keys[2], false),
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "accounts must fill the layout in order")]
+    fn test_to_metas_rejects_gaps() {
+        let layout = VERIFY_SPLIT_PAYMENT;
+        let key = Pubkey::new_unique();
+        let mut keys: Vec<(usize, Pubkey)> = (0..5).map(|index| (index, key)).collect();
+        keys.push((layout.mint, key));
+        layout.metas(&keys);
+    }
+}
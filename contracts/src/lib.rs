@@ -1,35 +1,172 @@
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
-    alt_bn128::{
-        alt_bn128_addition, alt_bn128_multiplication, alt_bn128_pairing,
-        AltBn128Error, ALT_BN128_PAIRING_OUTPUT_LEN,
-    },
-    entrypoint,
     entrypoint::ProgramResult,
-    msg,
+    hash, keccak, msg,
+    program::set_return_data,
     program_error::ProgramError,
     pubkey::Pubkey,
+    sysvar::{clock::Clock, Sysvar},
 };
 
+// First, so `log_statement!` is in scope in every module below
+#[macro_use]
+pub mod logging;
+#[macro_use]
+pub mod profiling;
+pub mod ack;
+pub mod admin;
+pub mod audit;
+pub mod batch_receipt;
+pub mod batch_verifier;
+#[cfg(feature = "client")]
+pub mod bench;
+#[cfg(feature = "client")]
+pub mod client;
+pub mod commitment;
+pub mod cpi;
+#[cfg(feature = "diagnostics")]
+pub mod diagnostics;
+pub mod endian;
+pub mod envelope;
+pub mod error;
+pub mod events;
+pub mod field;
+pub mod inline_key;
+pub mod layout;
+pub mod merkle;
+pub mod pairing;
+pub mod pda;
+pub mod point;
+pub mod pool;
+pub mod receipt;
+pub mod refund;
+pub mod relayers;
+#[cfg(feature = "snarkjs")]
+pub mod snarkjs;
+pub mod split;
+pub mod state;
+pub mod subscription;
+pub mod syscall_probe;
+#[cfg(feature = "test_vectors")]
+pub mod test_vectors;
+#[cfg(all(feature = "test_utils", not(target_arch = "wasm32")))]
+pub mod test_utils;
+pub mod transcript;
+mod utils;
+pub mod version;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
 // Import verification key constants
 // After circuit compilation, replace vkey_placeholder.rs with circuits/build/vkey_constants.rs
+#[cfg(not(feature = "bench-keys"))]
 mod vkey_placeholder;
+#[cfg(not(feature = "bench-keys"))]
 use vkey_placeholder::*;
 
-// Program entrypoint
-entrypoint!(process_instruction);
+// SPL token payment circuit key; replace with circuits/build/vkey_v2_constants.rs
+mod vkey_v2_placeholder;
+use vkey_v2_placeholder::*;
+
+// Invoice-bound payment circuit key; replace with circuits/build/vkey_v3_constants.rs
+#[cfg(not(feature = "bench-keys"))]
+mod vkey_v3_placeholder;
+#[cfg(not(feature = "bench-keys"))]
+use vkey_v3_placeholder::*;
+
+// Forgeable payment and invoice payment keys for `bench-cu`; see vkey_bench.rs
+#[cfg(feature = "bench-keys")]
+mod vkey_bench;
+#[cfg(feature = "bench-keys")]
+use vkey_bench::*;
+#[cfg(all(feature = "bench-keys", feature = "require-real-vkey"))]
+compile_error!("`bench-keys` replaces the payment circuit keys with forgeable ones");
+
+// Split payment circuit key; replace with circuits/build/vkey_split_constants.rs
+mod vkey_split_placeholder;
+use vkey_split_placeholder::*;
+
+// Refund circuit key; replace with circuits/build/vkey_refund_constants.rs
+mod vkey_refund_placeholder;
+use vkey_refund_placeholder::*;
+
+// Subscription circuit key; replace with circuits/build/vkey_subscription_constants.rs
+mod vkey_subscription_placeholder;
+use vkey_subscription_placeholder::*;
+
+// Shielded pool withdraw circuit key; replace with circuits/build/vkey_withdraw_constants.rs
+mod vkey_withdraw_placeholder;
+use vkey_withdraw_placeholder::*;
+
+// Committed-amount payment circuit key; replace with circuits/build/vkey_committed_constants.rs
+mod vkey_committed_placeholder;
+use vkey_committed_placeholder::*;
+
+// Keys retired by the last rotation, still accepted during their deprecation window
+mod vkey_previous;
+
+pub use error::VerifierError;
+pub use point::{G1Point, G2Point};
+pub use transcript::HashBackend;
+use batch_verifier::BatchVerificationRequest;
+use endian::{Be32, Le32};
+use envelope::{ProofEnvelope, VerifiedInputs};
+use events::{ProofVerified, VerifiedWithPreviousKey, VerifierEvent};
+use field::Scalar;
+use inline_key::InlineKeySource;
+use pairing::PairingBuilder;
+use state::{DeniedRecipient, InputPolicy, PreparedVk, VerifierConfig};
+
+// Program entrypoint; programs calling in through `cpi` build without it
+#[cfg(not(feature = "no-entrypoint"))]
+solana_program::entrypoint!(process_instruction);
+
+/// Version tag of the `Statement::statement_hash` preimage layout
+///
+/// Version 2 added the circuit id and version 3 dropped the proof bytes.
+/// Receipts store the hash they were created with, so those written under
+/// earlier versions keep their addresses.
+pub const PROOF_HASH_VERSION: u8 = 3;
+
+/// Domain separator for `Statement::statement_hash`
+const PROOF_HASH_DOMAIN: &[u8] = b"x402-zk-verifier/proof-hash";
 
 /// Groth16 proof structure
-#[derive(BorshSerialize, BorshDeserialize, Debug)]
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
 pub struct Groth16Proof {
-    pub a: [u8; 64],  // G1 point
-    pub b: [u8; 128], // G2 point
-    pub c: [u8; 64],  // G1 point
+    pub a: G1Point,
+    pub b: G2Point,
+    pub c: G1Point,
+}
+
+impl Groth16Proof {
+    /// Reject proofs whose coordinates are not canonical field elements
+    ///
+    /// All four limbs of A and C and all eight of B must be below the base field
+    /// modulus, so that each point has exactly one accepted byte encoding. B must
+    /// also lie on the G2 twist, which catches Fq2 limbs in the wrong order.
+    pub fn check_canonical(&self) -> Result<(), VerifierError> {
+        if !(self.a.is_canonical() && self.c.is_canonical()) {
+            return Err(VerifierError::NonCanonicalEncoding);
+        }
+        self.b.validate()
+    }
+
+    /// Canonical identifier of the payment this proof makes
+    ///
+    /// The `Statement::statement_hash` of its public inputs; the proof bytes
+    /// are not hashed. Anyone holding a valid proof can derive others for the
+    /// same statement, as (-A, -B, C), (r * A, B / r, C) or
+    /// (A, B + s * delta, C + s * A), so a key over the proof would let one
+    /// payment record a receipt per encoding.
+    pub fn hash<I: Statement>(&self, public_inputs: &I) -> [u8; 32] {
+        public_inputs.statement_hash()
+    }
 }
 
 /// Public inputs for payment verification
-#[derive(BorshSerialize, BorshDeserialize, Debug)]
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
 pub struct PaymentPublicInputs {
     pub min_amount: u64,
     pub recipient_pubkey: [u8; 32],
@@ -37,223 +174,3597 @@ pub struct PaymentPublicInputs {
     pub current_time: i64,
 }
 
-/// Instruction data
+impl PaymentPublicInputs {
+    /// Circuit signal names in IC order, one per scalar
+    pub const SIGNAL_LAYOUT: &'static [&'static str] = &[
+        "minAmount",
+        "recipientPubKeyX",
+        "recipientPubKeyY",
+        "maxBlockAge",
+        "currentTime",
+    ];
+
+    /// Scalars in circuit signal order, one per IC point after IC[0]
+    pub fn to_scalars(&self) -> [u64; 5] {
+        [
+            self.min_amount,
+            Le32::from_borsh(self.recipient_pubkey).u64_limb(0),
+            Le32::from_borsh(self.recipient_pubkey).u64_limb(1),
+            self.max_block_age,
+            self.current_time as u64,
+        ]
+    }
+
+    /// Check the statement against the config's input policy
+    pub fn validate(&self, policy: &InputPolicy, now: i64) -> Result<(), VerifierError> {
+        policy.check(self.min_amount, self.max_block_age, self.current_time, now)
+    }
+}
+
+/// Public inputs for SPL token payment verification
+///
+/// Extends the SOL payment statement with the mint, so a proof of paying in a
+/// worthless token cannot be presented as a USDC payment.
 #[derive(BorshSerialize, BorshDeserialize, Debug)]
-pub enum VerifierInstruction {
-    /// Verify a Groth16 proof
-    ///
-    /// Accounts expected:
-    /// 0. `[]` System program
-    VerifyProof {
-        proof: Groth16Proof,
-        public_inputs: PaymentPublicInputs,
-    },
+pub struct PaymentPublicInputsV2 {
+    pub min_amount: u64,
+    pub recipient_pubkey: [u8; 32],
+    pub max_block_age: u64,
+    pub current_time: i64,
+    pub mint: [u8; 32],
+    /// Paid amount in the mint's base units, range-checked against its decimals
+    pub token_decimals_checked_amount: u64,
 }
 
-pub fn process_instruction(
-    program_id: &Pubkey,
-    accounts: &[AccountInfo],
-    instruction_data: &[u8],
-) -> ProgramResult {
-    let instruction = VerifierInstruction::try_from_slice(instruction_data)
-        .map_err(|_| ProgramError::InvalidInstructionData)?;
+impl PaymentPublicInputsV2 {
+    /// Circuit signal names in IC order, one per scalar
+    pub const SIGNAL_LAYOUT: &'static [&'static str] = &[
+        "minAmount",
+        "recipientPubKeyX",
+        "recipientPubKeyY",
+        "maxBlockAge",
+        "currentTime",
+        "mintX",
+        "mintY",
+        "tokenAmount",
+    ];
 
-    match instruction {
-        VerifierInstruction::VerifyProof {
-            proof,
-            public_inputs,
-        } => {
-            msg!("Verifying ZK payment proof");
-            verify_payment_proof(program_id, accounts, &proof, &public_inputs)
+    /// Scalars in circuit signal order, one per IC point after IC[0]
+    pub fn to_scalars(&self) -> [u64; 8] {
+        [
+            self.min_amount,
+            Le32::from_borsh(self.recipient_pubkey).u64_limb(0),
+            Le32::from_borsh(self.recipient_pubkey).u64_limb(1),
+            self.max_block_age,
+            self.current_time as u64,
+            Le32::from_borsh(self.mint).u64_limb(0),
+            Le32::from_borsh(self.mint).u64_limb(1),
+            self.token_decimals_checked_amount,
+        ]
+    }
+
+    /// Check the statement against the config's input policy
+    pub fn validate(&self, policy: &InputPolicy, now: i64) -> Result<(), VerifierError> {
+        policy.check(self.min_amount, self.max_block_age, self.current_time, now)
+    }
+}
+
+/// Public inputs for invoice-bound payment verification
+///
+/// Binds the proof to one merchant request, so a proof paying recipient X cannot
+/// be replayed against X's other endpoints.
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct PaymentPublicInputsV3 {
+    pub min_amount: u64,
+    pub recipient_pubkey: [u8; 32],
+    pub max_block_age: u64,
+    pub current_time: i64,
+    /// Merchant-chosen identifier of the request being paid for
+    pub invoice_id: [u8; 32],
+    /// Unix timestamp after which the proof is rejected; 0 means no expiry
+    pub valid_until: i64,
+    /// Merkle root of the recipient allowlist the circuit proved membership in
+    pub recipient_set_root: [u8; 32],
+}
+
+impl PaymentPublicInputsV3 {
+    /// Circuit signal names in IC order, one per scalar
+    pub const SIGNAL_LAYOUT: &'static [&'static str] = &[
+        "minAmount",
+        "recipientPubKeyX",
+        "recipientPubKeyY",
+        "maxBlockAge",
+        "currentTime",
+        "invoiceId0",
+        "invoiceId1",
+        "invoiceId2",
+        "invoiceId3",
+        "validUntil",
+        "recipientSetRoot0",
+        "recipientSetRoot1",
+        "recipientSetRoot2",
+        "recipientSetRoot3",
+    ];
+
+    /// Scalars in circuit signal order, one per IC point after IC[0]
+    ///
+    /// The invoice id and recipient set root are bound in full as four u64 limbs each.
+    pub fn to_scalars(&self) -> [u64; 14] {
+        [
+            self.min_amount,
+            Le32::from_borsh(self.recipient_pubkey).u64_limb(0),
+            Le32::from_borsh(self.recipient_pubkey).u64_limb(1),
+            self.max_block_age,
+            self.current_time as u64,
+            Le32::from_borsh(self.invoice_id).u64_limb(0),
+            Le32::from_borsh(self.invoice_id).u64_limb(1),
+            Le32::from_borsh(self.invoice_id).u64_limb(2),
+            Le32::from_borsh(self.invoice_id).u64_limb(3),
+            self.valid_until as u64,
+            Le32::from_borsh(self.recipient_set_root).u64_limb(0),
+            Le32::from_borsh(self.recipient_set_root).u64_limb(1),
+            Le32::from_borsh(self.recipient_set_root).u64_limb(2),
+            Le32::from_borsh(self.recipient_set_root).u64_limb(3),
+        ]
+    }
+
+    /// Check the statement against the config's input policy
+    pub fn validate(&self, policy: &InputPolicy, now: i64) -> Result<(), VerifierError> {
+        policy.check(self.min_amount, self.max_block_age, self.current_time, now)
+    }
+
+    /// Reject proofs past their deadline; `now == valid_until` is still valid
+    pub fn check_expiry(&self, now: i64) -> Result<(), VerifierError> {
+        if self.valid_until != 0 && now > self.valid_until {
+            return Err(VerifierError::ProofExpired);
         }
+        Ok(())
     }
 }
 
-/// Verify Groth16 proof using Solana's alt_bn128 syscalls
-fn verify_payment_proof(
-    _program_id: &Pubkey,
-    _accounts: &[AccountInfo],
-    proof: &Groth16Proof,
-    public_inputs: &PaymentPublicInputs,
-) -> ProgramResult {
-    msg!("Min amount: {}", public_inputs.min_amount);
-    msg!("Current time: {}", public_inputs.current_time);
+/// Public inputs for a payment split between two recipients
+///
+/// Marketplaces pay the seller and the platform from one proof; each leg has
+/// its own recipient and minimum amount.
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct SplitPaymentPublicInputs {
+    pub recipients: [[u8; 32]; 2],
+    pub min_amounts: [u64; 2],
+    pub max_block_age: u64,
+    pub current_time: i64,
+    /// Mint both legs are paid in
+    pub mint: [u8; 32],
+}
 
-    // Verification key points (loaded from circuit compilation)
-    // These will be replaced with actual values after running npm run export-rust
-    let vk_alpha_g1 = VK_ALPHA_G1;
-    let vk_beta_g2 = VK_BETA_G2;
-    let vk_gamma_g2 = VK_GAMMA_G2;
-    let vk_delta_g2 = VK_DELTA_G2;
+impl SplitPaymentPublicInputs {
+    /// Circuit signal names in IC order, one per scalar
+    pub const SIGNAL_LAYOUT: &'static [&'static str] = &[
+        "minAmount0",
+        "recipient0PubKeyX",
+        "recipient0PubKeyY",
+        "minAmount1",
+        "recipient1PubKeyX",
+        "recipient1PubKeyY",
+        "maxBlockAge",
+        "currentTime",
+        "mintX",
+        "mintY",
+    ];
 
-    // IC points would be computed based on public inputs
-    // IC[0] + IC[1] * min_amount + IC[2] * recipient_pubkey[0] + ...
+    /// Scalars in circuit signal order, one per IC point after IC[0]
+    pub fn to_scalars(&self) -> [u64; 10] {
+        [
+            self.min_amounts[0],
+            Le32::from_borsh(self.recipients[0]).u64_limb(0),
+            Le32::from_borsh(self.recipients[0]).u64_limb(1),
+            self.min_amounts[1],
+            Le32::from_borsh(self.recipients[1]).u64_limb(0),
+            Le32::from_borsh(self.recipients[1]).u64_limb(1),
+            self.max_block_age,
+            self.current_time as u64,
+            Le32::from_borsh(self.mint).u64_limb(0),
+            Le32::from_borsh(self.mint).u64_limb(1),
+        ]
+    }
+}
 
-    // Groth16 pairing check: e(A, B) = e(alpha, beta) * e(pub_input, gamma) * e(C, delta)
-    // This translates to: e(A, B) * e(-pub_input, gamma) * e(-C, delta) * e(-alpha, beta) = 1
+/// Public inputs for a refund of a recorded payment
+///
+/// The circuit proves knowledge of the original payment's note without
+/// revealing the payer; only the receipt being refunded and where the refund
+/// goes are public.
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct RefundPublicInputs {
+    /// `proof_hash` of the `PaymentReceipt` being refunded
+    pub receipt_proof_hash: [u8; 32],
+    pub refund_recipient: [u8; 32],
+}
 
-    // Prepare pairing input (4 pairs for Groth16)
-    let mut pairing_input = Vec::with_capacity(384); // 4 pairs * 96 bytes each
-
-    // Pair 1: e(A, B)
-    pairing_input.extend_from_slice(&proof.a);
-    pairing_input.extend_from_slice(&proof.b);
-
-    // Pair 2: e(-pub_input_point, gamma)
-    // This requires computing pub_input_point from IC points
-    let pub_input_point = compute_public_input_point(public_inputs)?;
-    let negated_pub_input = negate_g1_point(&pub_input_point)?;
-    pairing_input.extend_from_slice(&negated_pub_input);
-    pairing_input.extend_from_slice(&vk_gamma_g2);
-
-    // Pair 3: e(-C, delta)
-    let negated_c = negate_g1_point(&proof.c)?;
-    pairing_input.extend_from_slice(&negated_c);
-    pairing_input.extend_from_slice(&vk_delta_g2);
-
-    // Pair 4: e(-alpha, beta)
-    let negated_alpha = negate_g1_point(&vk_alpha_g1)?;
-    pairing_input.extend_from_slice(&negated_alpha);
-    pairing_input.extend_from_slice(&vk_beta_g2);
-
-    // Execute pairing check
-    let mut pairing_result = [0u8; ALT_BN128_PAIRING_OUTPUT_LEN];
-    alt_bn128_pairing(&pairing_input, &mut pairing_result)
-        .map_err(|e| {
-            msg!("Pairing failed: {:?}", e);
-            ProgramError::InvalidArgument
-        })?;
-
-    // Check if result equals 1 (valid proof)
-    let expected = [
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1,
+impl RefundPublicInputs {
+    /// Circuit signal names in IC order, one per scalar
+    pub const SIGNAL_LAYOUT: &'static [&'static str] = &[
+        "receiptHash0",
+        "receiptHash1",
+        "receiptHash2",
+        "receiptHash3",
+        "refundRecipientPubKeyX",
+        "refundRecipientPubKeyY",
     ];
 
-    if pairing_result == expected {
-        msg!("✓ Payment proof verified successfully");
-        Ok(())
-    } else {
-        msg!("✗ Payment proof verification failed");
-        Err(ProgramError::InvalidArgument)
+    /// Scalars in circuit signal order, one per IC point after IC[0]
+    ///
+    /// The receipt hash is bound in full as four u64 limbs.
+    pub fn to_scalars(&self) -> [u64; 6] {
+        [
+            Le32::from_borsh(self.receipt_proof_hash).u64_limb(0),
+            Le32::from_borsh(self.receipt_proof_hash).u64_limb(1),
+            Le32::from_borsh(self.receipt_proof_hash).u64_limb(2),
+            Le32::from_borsh(self.receipt_proof_hash).u64_limb(3),
+            Le32::from_borsh(self.refund_recipient).u64_limb(0),
+            Le32::from_borsh(self.refund_recipient).u64_limb(1),
+        ]
     }
 }
 
-/// Compute public input point from IC points and public inputs
-fn compute_public_input_point(public_inputs: &PaymentPublicInputs) -> Result<[u8; 64], ProgramError> {
-    // IC[0] is the base point
-    // For each public input i: result = IC[0] + IC[1]*input[0] + IC[2]*input[1] + ...
+/// Public inputs for one billing epoch of a subscription
+///
+/// The same note proves once per epoch; the nullifier is scoped to the epoch by
+/// the circuit, and the program keys spent nullifiers by (nullifier, epoch).
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct SubscriptionPublicInputs {
+    pub min_amount: u64,
+    pub recipient_pubkey: [u8; 32],
+    /// Billing epoch, `unix_timestamp / epoch_length_seconds`
+    pub epoch: u64,
+    /// Epoch-scoped nullifier of the subscription note
+    pub nullifier: [u8; 32],
+}
 
-    // Start with IC[0] (the constant term)
-    let mut result = VK_IC[0];
-
-    // Convert public inputs to scalars
-    let inputs = [
-        public_inputs.min_amount,
-        u64::from_le_bytes(public_inputs.recipient_pubkey[0..8].try_into().unwrap()),
-        u64::from_le_bytes(public_inputs.recipient_pubkey[8..16].try_into().unwrap()),
-        public_inputs.max_block_age,
-        public_inputs.current_time as u64,
+impl SubscriptionPublicInputs {
+    /// Circuit signal names in IC order, one per scalar
+    pub const SIGNAL_LAYOUT: &'static [&'static str] = &[
+        "minAmount",
+        "recipientPubKeyX",
+        "recipientPubKeyY",
+        "epoch",
+        "nullifier0",
+        "nullifier1",
+        "nullifier2",
+        "nullifier3",
     ];
 
-    // For each public input, compute IC[i+1] * input[i] and add to result
-    for (i, &input_val) in inputs.iter().enumerate() {
-        if i + 1 >= VK_IC.len() {
-            break;
-        }
+    /// Scalars in circuit signal order, one per IC point after IC[0]
+    ///
+    /// The nullifier is bound in full as four u64 limbs.
+    pub fn to_scalars(&self) -> [u64; 8] {
+        [
+            self.min_amount,
+            Le32::from_borsh(self.recipient_pubkey).u64_limb(0),
+            Le32::from_borsh(self.recipient_pubkey).u64_limb(1),
+            self.epoch,
+            Le32::from_borsh(self.nullifier).u64_limb(0),
+            Le32::from_borsh(self.nullifier).u64_limb(1),
+            Le32::from_borsh(self.nullifier).u64_limb(2),
+            Le32::from_borsh(self.nullifier).u64_limb(3),
+        ]
+    }
+}
+
+/// Public inputs for a withdrawal from the shielded pool
+///
+/// The circuit proves a note under `root` worth at least `amount`, whose
+/// nullifier is `nullifier`. Binding the recipient stops a relayer from
+/// redirecting the funds.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct WithdrawPublicInputs {
+    /// Pool tree root the membership proof was built against
+    pub root: [u8; 32],
+    pub nullifier: [u8; 32],
+    pub recipient: [u8; 32],
+    /// Lamports paid out of the pool
+    pub amount: u64,
+    /// Commitment of a new note holding what the spent note had beyond
+    /// `amount`, appended to the pool's tree; zero when the note is spent in
+    /// full
+    pub change_commitment: [u8; 32],
+}
 
-        let ic_point = &VK_IC[i + 1];
+impl WithdrawPublicInputs {
+    /// Circuit signal names in IC order, one per scalar
+    pub const SIGNAL_LAYOUT: &'static [&'static str] = &[
+        "root0",
+        "root1",
+        "root2",
+        "root3",
+        "nullifier0",
+        "nullifier1",
+        "nullifier2",
+        "nullifier3",
+        "recipient0",
+        "recipient1",
+        "recipient2",
+        "recipient3",
+        "amount",
+        "change0",
+        "change1",
+        "change2",
+        "change3",
+    ];
 
-        // Convert input to 32-byte scalar (little-endian)
-        let mut scalar = [0u8; 32];
-        let input_bytes = input_val.to_le_bytes();
-        scalar[..8].copy_from_slice(&input_bytes);
+    /// Scalars in circuit signal order, one per IC point after IC[0]
+    ///
+    /// The root, nullifier, recipient and change commitment are each bound in
+    /// full as four u64 limbs.
+    pub fn to_scalars(&self) -> [u64; 17] {
+        [
+            Le32::from_borsh(self.root).u64_limb(0),
+            Le32::from_borsh(self.root).u64_limb(1),
+            Le32::from_borsh(self.root).u64_limb(2),
+            Le32::from_borsh(self.root).u64_limb(3),
+            Le32::from_borsh(self.nullifier).u64_limb(0),
+            Le32::from_borsh(self.nullifier).u64_limb(1),
+            Le32::from_borsh(self.nullifier).u64_limb(2),
+            Le32::from_borsh(self.nullifier).u64_limb(3),
+            Le32::from_borsh(self.recipient).u64_limb(0),
+            Le32::from_borsh(self.recipient).u64_limb(1),
+            Le32::from_borsh(self.recipient).u64_limb(2),
+            Le32::from_borsh(self.recipient).u64_limb(3),
+            self.amount,
+            Le32::from_borsh(self.change_commitment).u64_limb(0),
+            Le32::from_borsh(self.change_commitment).u64_limb(1),
+            Le32::from_borsh(self.change_commitment).u64_limb(2),
+            Le32::from_borsh(self.change_commitment).u64_limb(3),
+        ]
+    }
+}
 
-        // Perform scalar multiplication: temp = IC[i+1] * input[i]
-        let mut multiplication_input = Vec::with_capacity(96);
-        multiplication_input.extend_from_slice(ic_point);
-        multiplication_input.extend_from_slice(&scalar);
+/// Public inputs for a payment whose amount is hidden behind a commitment
+///
+/// The circuit proves a payment of at least `min_amount` to the recipient and
+/// that `amount_commitment` is `commitment::amount_commitment` of the amount
+/// paid. `VerifyWithOpening` records the amount when the payer opens the
+/// commitment and keeps it hidden otherwise.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CommittedPaymentPublicInputs {
+    pub min_amount: u64,
+    pub recipient_pubkey: [u8; 32],
+    pub max_block_age: u64,
+    pub current_time: i64,
+    /// Poseidon commitment to the amount paid, a big-endian scalar
+    pub amount_commitment: [u8; 32],
+}
 
-        let mut temp = [0u8; 64];
-        alt_bn128_multiplication(&multiplication_input, &mut temp)
-            .map_err(|e| {
-                msg!("Scalar multiplication failed: {:?}", e);
-                ProgramError::InvalidArgument
-            })?;
+impl CommittedPaymentPublicInputs {
+    /// Circuit signal names in IC order, one per scalar
+    pub const SIGNAL_LAYOUT: &'static [&'static str] = &[
+        "minAmount",
+        "recipientPubKeyX",
+        "recipientPubKeyY",
+        "maxBlockAge",
+        "currentTime",
+        "amountCommitment0",
+        "amountCommitment1",
+        "amountCommitment2",
+        "amountCommitment3",
+    ];
 
-        // Add to result: result = result + temp
-        let mut addition_input = Vec::with_capacity(128);
-        addition_input.extend_from_slice(&result);
-        addition_input.extend_from_slice(&temp);
+    /// Scalars in circuit signal order, one per IC point after IC[0]
+    ///
+    /// The commitment is bound in full as four u64 limbs.
+    pub fn to_scalars(&self) -> [u64; 9] {
+        [
+            self.min_amount,
+            Le32::from_borsh(self.recipient_pubkey).u64_limb(0),
+            Le32::from_borsh(self.recipient_pubkey).u64_limb(1),
+            self.max_block_age,
+            self.current_time as u64,
+            Le32::from_borsh(self.amount_commitment).u64_limb(0),
+            Le32::from_borsh(self.amount_commitment).u64_limb(1),
+            Le32::from_borsh(self.amount_commitment).u64_limb(2),
+            Le32::from_borsh(self.amount_commitment).u64_limb(3),
+        ]
+    }
 
-        alt_bn128_addition(&addition_input, &mut result)
-            .map_err(|e| {
-                msg!("Point addition failed: {:?}", e);
-                ProgramError::InvalidArgument
-            })?;
+    /// Check the statement against the config's input policy
+    pub fn validate(&self, policy: &InputPolicy, now: i64) -> Result<(), VerifierError> {
+        policy.check(self.min_amount, self.max_block_age, self.current_time, now)
     }
+}
 
-    Ok(result)
+/// Most public inputs a verification is allowed to process
+///
+/// Every input is a G1 scalar multiplication and addition before the pairing,
+/// so this bounds the compute spent on a statement; the largest circuit,
+/// `WithdrawPublicInputs`, takes 17.
+pub const MAX_PUBLIC_INPUTS: usize = 32;
+
+/// Groth16 verification key for a single circuit
+pub struct VerificationKey<'a> {
+    pub alpha_g1: G1Point,
+    pub beta_g2: G2Point,
+    pub gamma_g2: G2Point,
+    pub delta_g2: G2Point,
+    /// IC[0] constant term followed by one point per public input
+    pub ic: &'a [G1Point],
+    /// Public inputs the circuit takes, exported with the key as `nPublic`
+    pub num_public_inputs: usize,
+    /// `signal_layout_hash` of the circuit's public signals, exported with the key
+    pub signal_layout_hash: [u8; 32],
 }
 
-/// Negate a G1 point (flip y coordinate)
-fn negate_g1_point(point: &[u8]) -> Result<[u8; 64], ProgramError> {
-    if point.len() != 64 {
-        return Err(ProgramError::InvalidArgument);
+impl VerificationKey<'_> {
+    /// Reject a key generated for a different public signal ordering
+    ///
+    /// Scalars are bound to IC points purely by position, so a key exported
+    /// for another layout would silently multiply the wrong points.
+    pub fn check_signal_layout(&self, layout: &[&str]) -> Result<(), VerifierError> {
+        if self.num_public_inputs != layout.len()
+            || self.ic.len() != layout.len() + 1
+            || self.signal_layout_hash != signal_layout_hash(layout)
+        {
+            return Err(VerifierError::PublicInputLayoutMismatch);
+        }
+        Ok(())
     }
 
-    let mut negated = [0u8; 64];
+    /// Reject `inputs` unless the key takes exactly that many
+    ///
+    /// Each input costs a scalar multiplication, so the count is checked
+    /// before any syscall runs; a bound above `MAX_PUBLIC_INPUTS` is refused
+    /// even for a key that claims to take it.
+    pub fn check_input_count<T>(&self, inputs: &[T]) -> Result<(), VerifierError> {
+        if inputs.len() > MAX_PUBLIC_INPUTS {
+            return Err(VerifierError::TooManyPublicInputs);
+        }
+        if inputs.len() != self.num_public_inputs || self.ic.len() != inputs.len() + 1 {
+            return Err(VerifierError::PublicInputLayoutMismatch);
+        }
+        Ok(())
+    }
 
-    // x coordinate stays the same
-    negated[..32].copy_from_slice(&point[..32]);
+    /// Whether every point of the key is a canonical encoding
+    pub fn is_canonical(&self) -> bool {
+        self.alpha_g1.is_canonical()
+            && self.beta_g2.is_canonical()
+            && self.gamma_g2.is_canonical()
+            && self.delta_g2.is_canonical()
+            && self.ic.iter().all(G1Point::is_canonical)
+    }
 
-    // y_neg = p - y (where p is the BN254 field modulus)
-    // p = 21888242871839275222246405745257275088696311157297823662689037894645226208583
-    const FIELD_MODULUS: [u8; 32] = [
-        0x47, 0xfd, 0x7c, 0xd8, 0x16, 0x8c, 0x20, 0x3c,
-        0x8d, 0xca, 0x71, 0x68, 0x91, 0x6a, 0x81, 0x97,
-        0x5d, 0x58, 0x81, 0x81, 0xb6, 0x45, 0x50, 0xb8,
-        0x29, 0xa0, 0x31, 0xe1, 0x72, 0x4e, 0x64, 0x30,
-    ];
+    /// Reject a key with a point the pairing cannot meaningfully use
+    ///
+    /// Every G1 point must be on the curve and every G2 point on the twist,
+    /// and none may be the identity: a key still holding zeroed placeholder
+    /// points would otherwise accept proofs built from identity points. G2
+    /// points are not checked for subgroup membership.
+    pub fn validate(&self) -> Result<(), VerifierError> {
+        self.check_g2_not_identity()?;
+        let g1_valid = |point: &G1Point| *point != G1Point::IDENTITY && point.is_on_curve();
+        if !(g1_valid(&self.alpha_g1)
+            && self.ic.iter().all(g1_valid)
+            && [&self.beta_g2, &self.gamma_g2, &self.delta_g2]
+                .into_iter()
+                .all(|point| point.validate().is_ok()))
+        {
+            return Err(VerifierError::InvalidVerificationKey);
+        }
+        Ok(())
+    }
 
-    // Perform subtraction: y_neg = p - y
-    let y = &point[32..];
-    let mut borrow = 0u16;
+    /// Reject a key whose beta, gamma or delta is the G2 identity
+    ///
+    /// e(P, O) = 1 for every P, so an identity gamma or delta drops the input
+    /// or C term from the pairing check and any statement has a proof. Cheap
+    /// enough to run before the canonical check, so that an encoding reducing
+    /// to the identity fails as an invalid key rather than a malformed one.
+    pub fn check_g2_not_identity(&self) -> Result<(), VerifierError> {
+        if [&self.beta_g2, &self.gamma_g2, &self.delta_g2]
+            .into_iter()
+            .any(G2Point::is_identity)
+        {
+            return Err(VerifierError::InvalidVerificationKey);
+        }
+        Ok(())
+    }
 
-    for i in 0..32 {
-        let diff = FIELD_MODULUS[i] as u16 + 256 - y[i] as u16 - borrow;
-        negated[32 + i] = (diff & 0xFF) as u8;
-        borrow = if diff < 256 { 1 } else { 0 };
+    /// keccak256 over every point in order, then the signal layout hash
+    ///
+    /// Identifies the key a `PreparedVk` was derived from.
+    pub fn hash(&self) -> [u8; 32] {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&self.alpha_g1.to_bytes());
+        bytes.extend_from_slice(&self.beta_g2.to_bytes());
+        bytes.extend_from_slice(&self.gamma_g2.to_bytes());
+        bytes.extend_from_slice(&self.delta_g2.to_bytes());
+        for point in self.ic {
+            bytes.extend_from_slice(&point.to_bytes());
+        }
+        bytes.extend_from_slice(&self.signal_layout_hash);
+        keccak::hash(&bytes).to_bytes()
     }
+}
 
-    Ok(negated)
+/// Hash of an ordered list of public signal names
+///
+/// SHA256 over each name as u32 little-endian length followed by its UTF-8
+/// bytes; `circuits/verification_key_loader.js` computes the same value.
+pub fn signal_layout_hash(layout: &[&str]) -> [u8; 32] {
+    let mut hasher = hash::Hasher::default();
+    for name in layout {
+        hasher.hash(&(name.len() as u32).to_le_bytes());
+        hasher.hash(name.as_bytes());
+    }
+    hasher.result().to_bytes()
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use solana_program::clock::Epoch;
-    use solana_sdk::signature::{Keypair, Signer};
+/// Verification key of the SOL payment circuit (`PaymentPublicInputs`)
+pub const PAYMENT_VK: VerificationKey<'static> = VerificationKey {
+    alpha_g1: VK_ALPHA_G1,
+    beta_g2: VK_BETA_G2,
+    gamma_g2: VK_GAMMA_G2,
+    delta_g2: VK_DELTA_G2,
+    ic: &VK_IC,
+    num_public_inputs: VK_IC.len() - 1,
+    signal_layout_hash: VK_SIGNAL_LAYOUT_HASH,
+};
 
-    #[test]
-    fn test_verify_proof() {
-        let program_id = Pubkey::new_unique();
+/// Verification key of the SPL token payment circuit (`PaymentPublicInputsV2`)
+pub const TOKEN_PAYMENT_VK: VerificationKey<'static> = VerificationKey {
+    alpha_g1: VK_V2_ALPHA_G1,
+    beta_g2: VK_V2_BETA_G2,
+    gamma_g2: VK_V2_GAMMA_G2,
+    delta_g2: VK_V2_DELTA_G2,
+    ic: &VK_V2_IC,
+    num_public_inputs: VK_V2_IC.len() - 1,
+    signal_layout_hash: VK_V2_SIGNAL_LAYOUT_HASH,
+};
 
-        let proof = Groth16Proof {
-            a: [0u8; 64],
-            b: [0u8; 128],
-            c: [0u8; 64],
-        };
+/// Verification key of the invoice-bound payment circuit (`PaymentPublicInputsV3`)
+pub const INVOICE_PAYMENT_VK: VerificationKey<'static> = VerificationKey {
+    alpha_g1: VK_V3_ALPHA_G1,
+    beta_g2: VK_V3_BETA_G2,
+    gamma_g2: VK_V3_GAMMA_G2,
+    delta_g2: VK_V3_DELTA_G2,
+    ic: &VK_V3_IC,
+    num_public_inputs: VK_V3_IC.len() - 1,
+    signal_layout_hash: VK_V3_SIGNAL_LAYOUT_HASH,
+};
 
-        let public_inputs = PaymentPublicInputs {
-            min_amount: 1000000, // 0.001 SOL in lamports
-            recipient_pubkey: [0u8; 32],
-            max_block_age: 60,
-            current_time: 1700000000,
-        };
+/// Verification key of the split payment circuit (`SplitPaymentPublicInputs`)
+pub const SPLIT_PAYMENT_VK: VerificationKey<'static> = VerificationKey {
+    alpha_g1: VK_SPLIT_ALPHA_G1,
+    beta_g2: VK_SPLIT_BETA_G2,
+    gamma_g2: VK_SPLIT_GAMMA_G2,
+    delta_g2: VK_SPLIT_DELTA_G2,
+    ic: &VK_SPLIT_IC,
+    num_public_inputs: VK_SPLIT_IC.len() - 1,
+    signal_layout_hash: VK_SPLIT_SIGNAL_LAYOUT_HASH,
+};
+
+/// Verification key of the refund circuit (`RefundPublicInputs`)
+pub const REFUND_VK: VerificationKey<'static> = VerificationKey {
+    alpha_g1: VK_REFUND_ALPHA_G1,
+    beta_g2: VK_REFUND_BETA_G2,
+    gamma_g2: VK_REFUND_GAMMA_G2,
+    delta_g2: VK_REFUND_DELTA_G2,
+    ic: &VK_REFUND_IC,
+    num_public_inputs: VK_REFUND_IC.len() - 1,
+    signal_layout_hash: VK_REFUND_SIGNAL_LAYOUT_HASH,
+};
+
+/// Verification key of the subscription circuit (`SubscriptionPublicInputs`)
+pub const SUBSCRIPTION_VK: VerificationKey<'static> = VerificationKey {
+    alpha_g1: VK_SUBSCRIPTION_ALPHA_G1,
+    beta_g2: VK_SUBSCRIPTION_BETA_G2,
+    gamma_g2: VK_SUBSCRIPTION_GAMMA_G2,
+    delta_g2: VK_SUBSCRIPTION_DELTA_G2,
+    ic: &VK_SUBSCRIPTION_IC,
+    num_public_inputs: VK_SUBSCRIPTION_IC.len() - 1,
+    signal_layout_hash: VK_SUBSCRIPTION_SIGNAL_LAYOUT_HASH,
+};
+
+/// Verification key of the shielded pool withdraw circuit (`WithdrawPublicInputs`)
+pub const WITHDRAW_VK: VerificationKey<'static> = VerificationKey {
+    alpha_g1: VK_WITHDRAW_ALPHA_G1,
+    beta_g2: VK_WITHDRAW_BETA_G2,
+    gamma_g2: VK_WITHDRAW_GAMMA_G2,
+    delta_g2: VK_WITHDRAW_DELTA_G2,
+    ic: &VK_WITHDRAW_IC,
+    num_public_inputs: VK_WITHDRAW_IC.len() - 1,
+    signal_layout_hash: VK_WITHDRAW_SIGNAL_LAYOUT_HASH,
+};
+
+/// Verification key of the committed-amount payment circuit
+/// (`CommittedPaymentPublicInputs`)
+pub const COMMITTED_PAYMENT_VK: VerificationKey<'static> = VerificationKey {
+    alpha_g1: VK_COMMITTED_ALPHA_G1,
+    beta_g2: VK_COMMITTED_BETA_G2,
+    gamma_g2: VK_COMMITTED_GAMMA_G2,
+    delta_g2: VK_COMMITTED_DELTA_G2,
+    ic: &VK_COMMITTED_IC,
+    num_public_inputs: VK_COMMITTED_IC.len() - 1,
+    signal_layout_hash: VK_COMMITTED_SIGNAL_LAYOUT_HASH,
+};
+
+/// Registry of the circuits this program verifies
+///
+/// The discriminant identifies a circuit across instructions and off-chain
+/// tooling; it is part of the public interface and must never be renumbered.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+pub enum CircuitId {
+    Payment = 0,
+    TokenPayment = 1,
+    InvoicePayment = 2,
+    SplitPayment = 3,
+    Refund = 4,
+    Subscription = 5,
+    Withdraw = 6,
+    CommittedPayment = 7,
+}
+
+impl CircuitId {
+    /// Verification key compiled in for this circuit
+    pub fn verification_key(self) -> &'static VerificationKey<'static> {
+        match self {
+            CircuitId::Payment => &PAYMENT_VK,
+            CircuitId::TokenPayment => &TOKEN_PAYMENT_VK,
+            CircuitId::InvoicePayment => &INVOICE_PAYMENT_VK,
+            CircuitId::SplitPayment => &SPLIT_PAYMENT_VK,
+            CircuitId::Refund => &REFUND_VK,
+            CircuitId::Subscription => &SUBSCRIPTION_VK,
+            CircuitId::Withdraw => &WITHDRAW_VK,
+            CircuitId::CommittedPayment => &COMMITTED_PAYMENT_VK,
+        }
+    }
+
+    /// Key this circuit used before its last rotation, accepted while the
+    /// window set by `DeprecatePreviousKey` is open
+    pub fn previous_verification_key(self) -> Option<&'static VerificationKey<'static>> {
+        vkey_previous::previous_verification_key(self)
+    }
+}
+
+/// Public inputs of one circuit, as hashed by `Statement::statement_hash`
+pub trait Statement: BorshSerialize {
+    /// Circuit whose proofs these inputs belong to
+    const CIRCUIT: CircuitId;
+
+    /// keccak256(domain || version || circuit id || len || inputs), with the
+    /// inputs Borsh-encoded and the length as u32 little-endian
+    ///
+    /// Nullifiers, receipts, events and the canonical batch order all derive
+    /// from this so indexers agree; the circuit id keeps statements of
+    /// different circuits with the same encoding apart. It is keccak256
+    /// whatever hash backend the circuit's prepared key selects, so none of
+    /// them move when the backend changes.
+    fn statement_hash(&self) -> [u8; 32] {
+        let input_bytes = self.try_to_vec().unwrap();
+
+        keccak::hashv(&[
+            PROOF_HASH_DOMAIN,
+            &[PROOF_HASH_VERSION],
+            &[Self::CIRCUIT as u8],
+            &(input_bytes.len() as u32).to_le_bytes(),
+            &input_bytes,
+        ])
+        .to_bytes()
+    }
+}
+
+macro_rules! impl_statement {
+    ($($inputs:ty => $circuit:ident),* $(,)?) => {
+        $(impl Statement for $inputs {
+            const CIRCUIT: CircuitId = CircuitId::$circuit;
+        })*
+    };
+}
+
+impl_statement! {
+    PaymentPublicInputs => Payment,
+    PaymentPublicInputsV2 => TokenPayment,
+    PaymentPublicInputsV3 => InvoicePayment,
+    SplitPaymentPublicInputs => SplitPayment,
+    RefundPublicInputs => Refund,
+    SubscriptionPublicInputs => Subscription,
+    WithdrawPublicInputs => Withdraw,
+    CommittedPaymentPublicInputs => CommittedPayment,
+}
+
+/// Borsh encodings whose length is known without encoding them
+///
+/// Clients size transactions and buffer accounts from these instead of
+/// serializing to find out. Tests pin both against real encodings.
+pub trait SerializedLen: BorshSerialize {
+    /// Longest encoding of a value the program accepts
+    const MAX_LEN: usize;
+
+    /// Length of this value's encoding
+    fn serialized_len(&self) -> usize;
+}
+
+// Fixed-size encodings; with no padding in these structs, Borsh writes
+// exactly their in-memory size, which the assertion holds the sums to
+macro_rules! impl_fixed_len {
+    ($($ty:ty => $len:expr),* $(,)?) => {
+        $(
+            impl SerializedLen for $ty {
+                const MAX_LEN: usize = $len;
+
+                fn serialized_len(&self) -> usize {
+                    Self::MAX_LEN
+                }
+            }
+
+            const _: () = assert!(<$ty as SerializedLen>::MAX_LEN == core::mem::size_of::<$ty>());
+        )*
+    };
+}
+
+impl_fixed_len! {
+    Groth16Proof => 2 * G1Point::LEN + G2Point::LEN,
+    PaymentPublicInputs => 8 + 32 + 8 + 8,
+    PaymentPublicInputsV2 => PaymentPublicInputs::MAX_LEN + 32 + 8,
+    PaymentPublicInputsV3 => PaymentPublicInputs::MAX_LEN + 32 + 8 + 32,
+    SplitPaymentPublicInputs => 2 * 32 + 2 * 8 + 8 + 8 + 32,
+    RefundPublicInputs => 32 + 32,
+    SubscriptionPublicInputs => 8 + 32 + 8 + 32,
+    WithdrawPublicInputs => 32 + 32 + 32 + 8 + 32,
+    CommittedPaymentPublicInputs => PaymentPublicInputs::MAX_LEN + 32,
+}
+
+impl SerializedLen for VerifierInstruction {
+    /// `WriteBatchBuffer` filling a whole buffer; the assertion below holds
+    /// the other large variants under it
+    const MAX_LEN: usize = 1 + 8 + 4 + 4 + 4 + batch_verifier::MAX_BATCH_BUFFER_LEN;
+
+    /// Counted as the variant encodes, without allocating the bytes
+    fn serialized_len(&self) -> usize {
+        utils::borsh_len(self)
+    }
+}
+
+// Every variant at the bounds its processor enforces fits in `MAX_LEN`
+const _: () = {
+    let body = VerifierInstruction::MAX_LEN - 1;
+    assert!(BatchVerificationRequest::MAX_LEN <= body);
+    assert!(ProofEnvelope::MAX_LEN + 8 <= body);
+    assert!(
+        InlineKeySource::MAX_LEN + Groth16Proof::MAX_LEN + 4 + 32 * MAX_PUBLIC_INPUTS <= body
+    );
+    let soft = batch_verifier::MAX_SOFT_BATCH_SIZE
+        * (Groth16Proof::MAX_LEN + PaymentPublicInputsV3::MAX_LEN);
+    assert!(4 + 4 + soft + 1 <= body);
+    assert!(1 + 4 + 32 * state::MAX_ADMIN_SIGNERS <= body);
+};
+
+/// Instruction data
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub enum VerifierInstruction {
+    /// Verify a Groth16 proof
+    ///
+    /// Accounts expected:
+    /// 0. `[]` System program
+    /// 1. `[]` Config PDA, whose input policy applies
+    /// 2. `[]` Prepared key PDA: ["prepared-vk", circuit id]; fails the proof
+    ///    once the key is revoked, and while its deprecation window is open
+    ///    the circuit's previous key is tried when the current one rejects it
+    /// 3. `[]` Denylist marker PDA: ["denied", recipient_pubkey]
+    ///
+    /// Sets a Borsh `cpi::VerificationReceipt` as return data.
+    VerifyProof {
+        proof: Groth16Proof,
+        public_inputs: PaymentPublicInputs,
+    },
+
+    /// Verify a Groth16 proof of an SPL token payment
+    ///
+    /// Accounts expected:
+    /// 0. `[]` System program
+    /// 1. `[]` Config PDA, whose input policy applies
+    /// 2. `[]` Prepared key PDA: ["prepared-vk", circuit id]; fails the proof
+    ///    once the key is revoked, and while its deprecation window is open
+    ///    the circuit's previous key is tried when the current one rejects it
+    /// 3. `[]` Denylist marker PDA: ["denied", recipient_pubkey]
+    ///
+    /// Sets a Borsh `cpi::VerificationReceipt` as return data.
+    VerifyProofV2 {
+        proof: Groth16Proof,
+        public_inputs: PaymentPublicInputsV2,
+    },
+
+    /// Verify an invoice-bound proof and record a payment receipt
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Payer funding the receipt rent
+    /// 1. `[]` Config PDA: ["config"]
+    /// 2. `[writable]` Receipt PDA: ["receipt", proof_hash]
+    /// 3. `[]` System program
+    /// 4. `[]` Denylist marker PDA: ["denied", recipient_pubkey]
+    /// 5. `[]` Prepared key PDA: ["prepared-vk", 2]
+    /// 6. `[writable]` Invoice index PDA: ["invoice", invoice_id], unless the
+    ///    invoice id is zero; a second payment of an invoice is rejected unless
+    ///    the config allows overpayment
+    /// 7. `[writable]` Stats PDA: ["stats", recipient_pubkey], only when `update_stats`
+    /// 8. `[writable]` (optional) Audit log PDA: ["audit-log"], after the stats
+    ///    PDA or in its place; appended to when the config enables auditing
+    VerifyAndRecord {
+        proof: Groth16Proof,
+        public_inputs: PaymentPublicInputsV3,
+        /// Update the recipient's `RecipientStats`; clear to save compute.
+        /// Required when the config sets a rate limit, which fails payments
+        /// past it with `RateLimited`
+        update_stats: bool,
+        /// Check everything but write nothing; the would-be receipt is
+        /// returned as return data
+        dry_run: bool,
+        /// Let `ConsumeReceipt` spend the receipt once
+        consumable: bool,
+        /// May consume the receipt besides the recipient; requires
+        /// `consumable`
+        delegate: Option<Pubkey>,
+        /// Compute unit price, in micro-lamports, the payer set for this
+        /// transaction; recorded when the config tracks relayers
+        compute_unit_price: u64,
+    },
+
+    /// Create the program config with the signer as admin
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Admin
+    /// 1. `[writable]` Config PDA: ["config"]
+    /// 2. `[]` System program
+    InitializeConfig { recipient_root_grace_seconds: i64 },
+
+    /// Rotate the approved recipient set root (admin only)
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Admin
+    /// 1. `[writable]` Config PDA
+    SetRecipientRoot { root: [u8; 32] },
+
+    /// Verify a split payment proof, record both legs in one receipt and
+    /// optionally settle both legs from the payer's token account
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Payer
+    /// 1. `[writable]` Receipt PDA: ["receipt", proof_hash]
+    /// 2. `[]` System program
+    /// 3. `[]` Denylist marker PDA of recipient 0: ["denied", recipient]
+    /// 4. `[]` Denylist marker PDA of recipient 1
+    /// 5. `[]` Prepared key PDA: ["prepared-vk", 3]
+    ///
+    /// When `settle` is set:
+    /// 6. `[writable]` Payer token account
+    /// 7. `[writable]` Token account of recipient 0
+    /// 8. `[writable]` Token account of recipient 1
+    /// 9. `[]` Token program owning the mint: SPL Token or Token-2022
+    /// 10. `[]` Mint named in the public inputs
+    /// 11.. Extra accounts the mint's transfer hook needs, if it has one; see
+    ///    `client::resolve_transfer_hook_accounts`
+    VerifySplitPayment {
+        proof: Groth16Proof,
+        public_inputs: SplitPaymentPublicInputs,
+        settle: bool,
+        /// Check everything, settlement accounts included, but neither record
+        /// nor transfer; the would-be receipt is returned as return data
+        dry_run: bool,
+    },
+
+    /// Verify a refund proof for a recorded payment and mark its receipt refunded
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Receipt PDA: ["receipt", receipt_proof_hash]
+    /// 1. `[]` Prepared key PDA: ["prepared-vk", 4]
+    /// 2. `[writable]` (optional) Invoice index PDA: ["invoice", invoice_id];
+    ///    marked refunded when it points at this receipt
+    VerifyRefund {
+        proof: Groth16Proof,
+        public_inputs: RefundPublicInputs,
+    },
+
+    /// Verify a subscription proof for one billing epoch and spend its nullifier
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Payer funding the nullifier rent
+    /// 1. `[]` Config PDA: ["config"]
+    /// 2. `[writable]` Nullifier PDA:
+    ///    ["nullifier", seed version, circuit id, nullifier, epoch (u64 LE)]
+    /// 3. `[]` System program
+    /// 4. `[]` Denylist marker PDA: ["denied", recipient_pubkey]
+    /// 5. `[]` Legacy nullifier PDA: ["nullifier", nullifier, epoch (u64 LE)]
+    /// 6. `[]` Prepared key PDA: ["prepared-vk", 5]
+    VerifySubscription {
+        proof: Groth16Proof,
+        public_inputs: SubscriptionPublicInputs,
+        /// Check everything but leave the nullifier unspent; the would-be
+        /// nullifier account is returned as return data
+        dry_run: bool,
+    },
 
-        // This will fail until we have real verification key and proof
-        // Just testing the interface compiles
-        assert!(true);
+    /// Set the subscription billing epoch length; 0 disables subscriptions (admin only)
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Admin
+    /// 1. `[writable]` Config PDA
+    SetEpochLength { epoch_length_seconds: i64 },
+
+    /// Replace the public input policy (admin only)
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Admin
+    /// 1. `[writable]` Config PDA
+    SetInputPolicy { policy: InputPolicy },
+
+    /// Verify invoice-bound proofs one by one, recording receipts for those
+    /// that pass; returns a per-proof result bitmap and error codes as return
+    /// data (`batch_verifier::decode_soft_batch_results` reads them)
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Payer funding the receipt rents
+    /// 1. `[]` Config PDA: ["config"]
+    /// 2. `[]` System program
+    /// 3. `[]` Prepared key PDA: ["prepared-vk", 2]; a revoked key fails the
+    ///    whole batch
+    /// 4.. `[writable]` Receipt PDA of each proof, in order, each followed by
+    ///    its invoice index PDA unless the invoice id is zero, then by its
+    ///    recipient's denylist marker PDA: ["denied", recipient_pubkey], then
+    ///    by its recipient's stats PDA when the config sets a rate limit
+    /// 4 + n.. `[writable]` (optional) Audit log PDA: ["audit-log"]; every
+    ///    proof's outcome is appended when the config enables auditing
+    VerifyBatchSoft {
+        proofs: Vec<Groth16Proof>,
+        public_inputs: Vec<PaymentPublicInputsV3>,
+        /// Fewest passing proofs for the instruction to succeed
+        min_valid: u8,
+    },
+
+    /// Propose a new admin, replacing any pending proposal (admin only)
+    ///
+    /// The new admin may be a PDA, in which case its owning program accepts
+    /// by signing `AcceptAdmin` through CPI.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Admin
+    /// 1. `[writable]` Config PDA
+    /// 2. `[signer]` Admin co-signers, as many as the config's threshold needs
+    ProposeAdmin { new_admin: Pubkey },
+
+    /// Complete a pending admin transfer (pending admin only)
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Pending admin
+    /// 1. `[writable]` Config PDA
+    AcceptAdmin,
+
+    /// Withdraw a pending admin proposal (admin only)
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Admin
+    /// 1. `[writable]` Config PDA
+    CancelProposedAdmin,
+
+    /// Verify a payment proof in any supported envelope version
+    ///
+    /// Accounts expected:
+    /// 0. `[]` System program
+    /// 1. `[]` Config PDA, whose input policy applies
+    /// 2. `[]` Prepared key PDA: ["prepared-vk", circuit id]; fails the proof
+    ///    once the key is revoked, and while its deprecation window is open
+    ///    the circuit's previous key is tried when the current one rejects it
+    /// 3. `[]` Denylist marker PDA: ["denied", recipient_pubkey]
+    ///
+    /// Sets a Borsh `cpi::VerificationReceipt` as return data.
+    VerifyEnvelope { envelope: ProofEnvelope },
+
+    /// Verify up to `MAX_BATCH_SIZE` payment proofs of the request's circuit
+    /// with one aggregated pairing; fails with `PublicInputLayoutMismatch`
+    /// when that circuit does not take payment statements
+    ///
+    /// Accounts expected:
+    /// 0. `[]` Prepared key PDA: ["prepared-vk", circuit_id]; its hash
+    ///    backend derives the coefficients
+    /// 1.. `[]` Denylist marker PDA of each proof's recipient, in batch
+    ///    order: ["denied", recipient_pubkey]
+    VerifyBatch { request: BatchVerificationRequest },
+
+    /// Write a chunk of a Borsh-encoded `BatchVerificationRequest` into a
+    /// buffer, creating it with room for `total_len` bytes after a
+    /// `state::BatchBuffer` header on the first write
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Authority, funds the buffer rent
+    /// 1. `[writable]` Buffer PDA: ["batch-buffer", authority, nonce (u64 LE)]
+    /// 2. `[]` System program
+    WriteBatchBuffer {
+        nonce: u64,
+        total_len: u32,
+        offset: u32,
+        data: Vec<u8>,
+    },
+
+    /// Verify the batch staged in a buffer and close it
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Authority, receives the buffer rent
+    /// 1. `[writable]` Buffer PDA: ["batch-buffer", authority, nonce (u64 LE)]
+    /// 2. `[]` Prepared key PDA: ["prepared-vk", circuit_id], as for
+    ///    `VerifyBatch`
+    /// 3.. `[]` Denylist marker PDA of each proof's recipient, in batch order
+    VerifyBatchBuffered { nonce: u64 },
+
+    /// Validate a circuit's verification key once and store its derived
+    /// values for the verify instructions (admin only)
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Admin, funds the prepared key rent
+    /// 1. `[]` Config PDA
+    /// 2. `[writable]` Prepared key PDA: ["prepared-vk", circuit id]
+    /// 3. `[]` System program
+    /// 4. `[signer]` Admin co-signers, as many as the config's threshold needs
+    PrepareVerificationKey {
+        circuit: CircuitId,
+        /// e(alpha, beta) in GT, computed off-chain
+        alpha_beta: Option<[u8; 384]>,
+    },
+
+    /// Verify an invoice-bound proof whose terms the recipient signed in an
+    /// ed25519 program instruction earlier in the same transaction
+    ///
+    /// Accounts expected:
+    /// 0. `[]` Config PDA: ["config"]
+    /// 1. `[]` Instructions sysvar
+    /// 2. `[]` Denylist marker PDA: ["denied", recipient_pubkey]
+    /// 3. `[]` Prepared key PDA: ["prepared-vk", 2]
+    VerifyProofWithAck {
+        proof: Groth16Proof,
+        public_inputs: PaymentPublicInputsV3,
+    },
+
+    /// Refresh a receipt's slot with a new proof for the same invoice
+    /// (receipt recipient only)
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Recipient named in the receipt
+    /// 1. `[]` Config PDA: ["config"]
+    /// 2. `[writable]` Receipt PDA: ["receipt", receipt_proof_hash]
+    /// 3. `[]` Denylist marker PDA: ["denied", recipient]
+    /// 4. `[]` Prepared key PDA: ["prepared-vk", 2]
+    TouchReceipt {
+        receipt_proof_hash: [u8; 32],
+        proof: Groth16Proof,
+        public_inputs: PaymentPublicInputsV3,
+    },
+
+    /// Set how many slots receipts stay fresh for; 0 disables expiry (admin only)
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Admin
+    /// 1. `[writable]` Config PDA
+    SetMaxReceiptAge { max_receipt_age_slots: u64 },
+
+    /// Create the audit log (admin only)
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Admin, funds the audit log rent
+    /// 1. `[]` Config PDA
+    /// 2. `[writable]` Audit log PDA: ["audit-log"]
+    /// 3. `[]` System program
+    InitializeAuditLog,
+
+    /// Turn appending to the audit log on or off (admin only)
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Admin, funds growing a version 1 config
+    /// 1. `[writable]` Config PDA
+    /// 2. `[]` System program, only when enabling on a version 1 config
+    SetAuditEnabled { enabled: bool },
+
+    /// Create the shielded pool and its empty commitment tree (admin only)
+    ///
+    /// Withdrawals may prove against any of the last `root_history_size`
+    /// roots, from 1 to `merkle::MAX_ROOT_HISTORY_SIZE`
+    /// (`merkle::ROOT_HISTORY_SIZE` is the usual choice).
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Admin, funds the pool rent
+    /// 1. `[]` Config PDA
+    /// 2. `[writable]` Pool PDA: ["pool"]
+    /// 3. `[]` System program
+    InitializePool { root_history_size: u32 },
+
+    /// Deposit lamports into the shielded pool under a note commitment
+    ///
+    /// Sets the commitment's leaf index (u64 LE) as return data.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Depositor
+    /// 1. `[writable]` Pool PDA: ["pool"]
+    /// 2. `[]` System program
+    Deposit {
+        /// Note commitment, a nonzero canonical scalar (big-endian)
+        commitment: [u8; 32],
+        /// Lamports moved into the pool
+        amount: u64,
+    },
+
+    /// Verify a withdraw proof against a recent pool root, spend its nullifier,
+    /// pay the recipient out of the pool and append the change note, if any
+    ///
+    /// Sets the change note's leaf index (u64 LE) as return data.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Payer funding the nullifier rent
+    /// 1. `[writable]` Pool PDA: ["pool"]
+    /// 2. `[writable]` Nullifier PDA:
+    ///    ["nullifier", seed version, circuit id, nullifier, 0 (u64 LE)]
+    /// 3. `[writable]` Recipient
+    /// 4. `[]` System program
+    /// 5. `[]` Denylist marker PDA: ["denied", recipient]
+    /// 6. `[]` Prepared key PDA: ["prepared-vk", 6]
+    Withdraw {
+        proof: Groth16Proof,
+        public_inputs: WithdrawPublicInputs,
+    },
+
+    /// Set the longest deprecation window `DeprecatePreviousKey` may open, in
+    /// slots; 0 turns previous keys off (admin only)
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Admin, funds growing an older config
+    /// 1. `[writable]` Config PDA
+    /// 2. `[]` System program, only when growing a version 1 or 2 config
+    SetMaxKeyDeprecation { max_key_deprecation_slots: u64 },
+
+    /// Accept proofs against a circuit's previous key until
+    /// `deprecated_until_slot`, after a key rotation (admin only)
+    ///
+    /// The window may reach at most the config's `max_key_deprecation_slots`
+    /// past the current slot; a slot already passed closes it.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Admin, funds growing an older prepared key
+    /// 1. `[]` Config PDA
+    /// 2. `[writable]` Prepared key PDA: ["prepared-vk", circuit id], prepared
+    ///    from the circuit's current key
+    /// 3. `[]` System program, only when growing a prepared key older than version 4
+    /// 4. `[signer]` Admin co-signers, as many as the config's threshold needs
+    DeprecatePreviousKey {
+        circuit: CircuitId,
+        deprecated_until_slot: u64,
+    },
+
+    /// Create the empty relayer allowlist (admin only)
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Admin, funds the list rent
+    /// 1. `[]` Config PDA
+    /// 2. `[writable]` Relayer list PDA: ["relayers"]
+    /// 3. `[]` System program
+    InitializeRelayerList,
+
+    /// Add a relayer to the allowlist (admin only)
+    ///
+    /// A full list is grown by `RELAYER_LIST_GROWTH_SLOTS` slots, up to
+    /// `MAX_RELAYERS`, with the admin paying the extra rent.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Admin, funds growing the list
+    /// 1. `[]` Config PDA
+    /// 2. `[writable]` Relayer list PDA
+    /// 3. `[]` System program
+    AddRelayer { relayer: Pubkey },
+
+    /// Take a relayer off the allowlist (admin only)
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Admin
+    /// 1. `[]` Config PDA
+    /// 2. `[writable]` Relayer list PDA
+    RemoveRelayer { relayer: Pubkey },
+
+    /// `VerifyEnvelope` with the input policy's clock skew bound narrowed to
+    /// `max_time_skew_seconds` for this proof
+    ///
+    /// The bound can only be tightened: a wider request leaves the config's in
+    /// place.
+    ///
+    /// Accounts expected: as for `VerifyEnvelope`.
+    ///
+    /// Sets a Borsh `cpi::VerificationReceipt` as return data.
+    VerifyEnvelopeWithMaxSkew {
+        envelope: ProofEnvelope,
+        max_time_skew_seconds: u64,
+    },
+
+    /// Require `threshold` distinct `signers` to co-sign `ProposeAdmin`,
+    /// `PrepareVerificationKey`, `DeprecatePreviousKey` and this instruction
+    /// (admin only)
+    ///
+    /// The current set's threshold and the new one's must both be met. A
+    /// threshold of 0 with no signers leaves those instructions to the admin.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Admin, funds growing an older config
+    /// 1. `[writable]` Config PDA
+    /// 2. `[]` System program
+    /// 3. `[signer]` Co-signers from the current and new sets
+    SetAdminSigners { threshold: u8, signers: Vec<Pubkey> },
+
+    /// Let an invoice with an active receipt be paid again (admin only)
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Admin, funds growing an older config
+    /// 1. `[writable]` Config PDA
+    /// 2. `[]` System program, only when allowing on a config older than
+    ///    version 5
+    SetInvoiceOverpayment { allowed: bool },
+
+    /// Log the program's build provenance and set it as a Borsh
+    /// `version::ProgramVersion` in return data
+    ///
+    /// No accounts; simulate it to read a deployed program's version.
+    GetVersion,
+
+    /// Verify a proof of a circuit outside the registry against a key the
+    /// caller supplies; nothing is recorded
+    ///
+    /// `public_inputs` are big-endian field elements below the scalar field
+    /// modulus, one per IC point after the first.
+    ///
+    /// Accounts expected:
+    /// 0. `[]` Key account holding a Borsh `inline_key::InlineVerificationKey`,
+    ///    only for `InlineKeySource::Account`; any owner
+    ///
+    /// Sets the key's `VerificationKey::hash` as return data.
+    VerifyWithInlineKey {
+        key: InlineKeySource,
+        proof: Groth16Proof,
+        public_inputs: Vec<Be32>,
+    },
+
+    /// Revoke a circuit's prepared key at once, for a compromised trusted
+    /// setup (admin only, no co-signers)
+    ///
+    /// Every verify instruction of the circuit then fails with
+    /// `KeyRevoked`, and any deprecation window closes. Only
+    /// `PrepareVerificationKey` with a different key reinstates the circuit.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Admin, funds growing an older prepared key
+    /// 1. `[]` Config PDA
+    /// 2. `[writable]` Prepared key PDA: ["prepared-vk", circuit id]
+    /// 3. `[]` System program, only when growing a prepared key older than version 4
+    RevokeVerificationKey { circuit: CircuitId },
+
+    /// Deny payments to `recipient` (admin only)
+    ///
+    /// Every verify instruction naming a recipient takes the recipient's
+    /// marker PDA and fails with `RecipientDenied` while the marker exists.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Admin, funds the marker rent
+    /// 1. `[]` Config PDA
+    /// 2. `[writable]` Denylist marker PDA: ["denied", recipient]
+    /// 3. `[]` System program
+    DenyRecipient { recipient: [u8; 32] },
+
+    /// Lift a `DenyRecipient`, closing the marker (admin only)
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Admin, receives the marker rent
+    /// 1. `[]` Config PDA
+    /// 2. `[writable]` Denylist marker PDA: ["denied", recipient]
+    AllowRecipient { recipient: [u8; 32] },
+
+    /// Verify a batch as `VerifyBatch` does, then record one `BatchReceipt`
+    /// summarizing it
+    ///
+    /// The receipt holds the Merkle root of the proofs' `Groth16Proof::hash`
+    /// values; `VerifyBatchInclusion` proves membership later. Fails with
+    /// `InvalidArgument` if `allow_duplicates` is set.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Relayer funding the receipt rent
+    /// 1. `[writable]` Batch receipt PDA: ["batch-receipt", transcript_hash],
+    ///    see `batch_verifier::batch_transcript_hash`
+    /// 2. `[]` System program
+    /// 3. `[]` Prepared key PDA: ["prepared-vk", circuit_id], as for
+    ///    `VerifyBatch`
+    /// 4.. `[]` Denylist marker PDA of each proof's recipient, in batch order
+    VerifyBatchWithReceipt { request: BatchVerificationRequest },
+
+    /// Succeed only if the proof with `proof_hash` is member `index` of the
+    /// batch a `BatchReceipt` records, failing with `ProofNotInBatch`
+    /// otherwise
+    ///
+    /// `siblings` is the path from `client::batch_inclusion_proof`.
+    ///
+    /// Accounts expected:
+    /// 0. `[]` Batch receipt PDA: ["batch-receipt", transcript_hash]
+    VerifyBatchInclusion {
+        proof_hash: [u8; 32],
+        index: u8,
+        siblings: Vec<[u8; 32]>,
+    },
+
+    /// Limit how many payments `VerifyAndRecord` and `VerifyBatchSoft`
+    /// record for one recipient per window of `window_slots` slots (admin
+    /// only); a limit of 0 removes it
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Admin, funds growing an older config
+    /// 1. `[writable]` Config PDA
+    /// 2. `[]` System program, only when setting a limit on a config older
+    ///    than version 6
+    SetRateLimit {
+        max_verifications_per_window: u64,
+        window_slots: u64,
+    },
+
+    /// Spend a consumable receipt once; it stays recorded as `Consumed` and
+    /// no longer passes `cpi::read_receipt`
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Recipient named in the receipt, or its delegate
+    /// 1. `[writable]` Receipt PDA: ["receipt", receipt_proof_hash]
+    ConsumeReceipt { receipt_proof_hash: [u8; 32] },
+
+    /// Set the finality depth `FinalizeReceipt` waits for, in slots (admin
+    /// only); 0 selects `state::DEFAULT_FINALITY_SLOTS`
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Admin, funds growing an older config
+    /// 1. `[writable]` Config PDA
+    /// 2. `[]` System program, only when setting a depth on a config older
+    ///    than version 7
+    SetFinalitySlots { finality_slots: u64 },
+
+    /// Mark a receipt final once the finality depth has passed since its
+    /// slot; anyone may call it
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Caller, funds growing an older receipt
+    /// 1. `[]` Config PDA
+    /// 2. `[writable]` Receipt PDA: ["receipt", receipt_proof_hash]
+    /// 3. `[]` System program, only for a receipt older than version 3
+    FinalizeReceipt { receipt_proof_hash: [u8; 32] },
+
+    /// Select the hash of a circuit's batch transcripts (admin and
+    /// co-signers)
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Admin, funds growing an older prepared key
+    /// 1. `[]` Config PDA
+    /// 2. `[writable]` Prepared key PDA: ["prepared-vk", circuit id]
+    /// 3. `[]` System program, only when growing a prepared key older than version 4
+    /// 4. `[signer]` Admin co-signers, as many as the config's threshold needs
+    SetHashBackend {
+        circuit: CircuitId,
+        backend: HashBackend,
+    },
+
+    /// Set a `cpi::ReceiptStatus` for a receipt as return data; with
+    /// `allow_missing` a missing receipt reads as not existing instead of
+    /// failing with `ReceiptNotFound`
+    ///
+    /// Accounts expected:
+    /// 0. `[]` Receipt PDA: ["receipt", receipt_proof_hash]
+    GetReceiptStatus {
+        receipt_proof_hash: [u8; 32],
+        allow_missing: bool,
+    },
+
+    /// Close a batch buffer without verifying it, refunding its rent; the
+    /// authority may call it at any time
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Authority, receives the buffer rent
+    /// 1. `[writable]` Buffer PDA: ["batch-buffer", authority, nonce (u64 LE)]
+    AbortBuffer { nonce: u64 },
+
+    /// Close a batch buffer left unwritten for the config's scratch TTL;
+    /// anyone may call it, and fails with `ScratchNotStale` before then
+    ///
+    /// The reaper is paid `batch_verifier::reap_bounty` of the rent and the
+    /// authority recorded in the buffer the rest.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Reaper
+    /// 1. `[writable]` Buffer PDA: ["batch-buffer", authority, nonce (u64 LE)]
+    /// 2. `[writable]` Authority recorded in the buffer
+    /// 3. `[]` Config PDA
+    ReapStale { nonce: u64 },
+
+    /// Set how many slots after its last write a batch buffer may be reaped,
+    /// at least `state::MIN_SCRATCH_TTL_SLOTS`; 0 selects
+    /// `state::DEFAULT_SCRATCH_TTL_SLOTS` (admin only)
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Admin, funds growing an older config
+    /// 1. `[writable]` Config PDA
+    /// 2. `[]` System program, only when setting a TTL on a config older
+    ///    than version 8
+    SetScratchTtl { scratch_ttl_slots: u64 },
+
+    /// Rewrite a receipt of an older layout at the current one; anyone may
+    /// call it, and the config's spare lamports repay the added rent
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Caller, funds the growth and is reimbursed
+    /// 1. `[writable]` Config PDA
+    /// 2. `[writable]` Receipt PDA: ["receipt", receipt_proof_hash]
+    /// 3. `[]` System program
+    MigrateReceipt { receipt_proof_hash: [u8; 32] },
+
+    /// Verify a committed-amount payment proof and record a `PaymentReceipt`;
+    /// with an `opening`, also check that it opens the amount commitment and
+    /// record the amount in the receipt
+    ///
+    /// A wrong opening fails with `CommitmentMismatch`. Without one the
+    /// amount stays hidden and the receipt's `revealed_amount` is 0.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Payer funding the receipt rent
+    /// 1. `[]` Config PDA: ["config"]
+    /// 2. `[writable]` Receipt PDA: ["receipt", proof_hash]
+    /// 3. `[]` System program
+    /// 4. `[]` Denylist marker PDA: ["denied", recipient_pubkey]
+    /// 5. `[]` Prepared key PDA: ["prepared-vk", 7]
+    VerifyWithOpening {
+        proof: Groth16Proof,
+        public_inputs: CommittedPaymentPublicInputs,
+        opening: Option<commitment::AmountOpening>,
+    },
+
+    /// Record the payer and its declared compute unit price in each
+    /// `VerifyAndRecord` receipt, so recipients can rebate relayers (admin
+    /// only)
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Admin, funds growing an older config
+    /// 1. `[writable]` Config PDA
+    /// 2. `[]` System program, only when enabling on a config older than
+    ///    version 9
+    SetTrackRelayer { track_relayer: bool },
+
+    /// Pay the relayer recorded in a receipt `amount` lamports out of the
+    /// recipient's rebate deposit, once per receipt
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Merchant: the recipient named in the receipt
+    /// 1. `[writable]` Rebate deposit PDA: ["rebate-deposit", merchant]
+    /// 2. `[writable]` Relayer recorded in the receipt
+    /// 3. `[writable]` Receipt PDA: ["receipt", receipt_proof_hash]
+    /// 4. `[]` System program
+    ClaimRelayerRebate {
+        receipt_proof_hash: [u8; 32],
+        amount: u64,
+    },
+}
+
+impl VerifierInstruction {
+    /// Whether the instruction calls the alt_bn128 syscalls, and so probes
+    /// for them before anything else
+    pub fn uses_alt_bn128(&self) -> bool {
+        matches!(
+            self,
+            Self::VerifyProof { .. }
+                | Self::VerifyProofV2 { .. }
+                | Self::VerifyAndRecord { .. }
+                | Self::VerifySplitPayment { .. }
+                | Self::VerifyRefund { .. }
+                | Self::VerifySubscription { .. }
+                | Self::VerifyBatchSoft { .. }
+                | Self::VerifyEnvelope { .. }
+                | Self::VerifyBatch { .. }
+                | Self::VerifyBatchBuffered { .. }
+                | Self::VerifyProofWithAck { .. }
+                | Self::TouchReceipt { .. }
+                | Self::Withdraw { .. }
+                | Self::VerifyEnvelopeWithMaxSkew { .. }
+                | Self::VerifyWithInlineKey { .. }
+                | Self::VerifyBatchWithReceipt { .. }
+                | Self::VerifyWithOpening { .. }
+        )
+    }
+}
+
+pub fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let instruction = profile_phase!(
+        profiling::Phase::InputParsing,
+        utils::decode_exact::<VerifierInstruction>(instruction_data)
+    )?;
+    if instruction.uses_alt_bn128() {
+        syscall_probe::ensure_bn128_available()?;
+    }
+
+    match instruction {
+        VerifierInstruction::VerifyProof {
+            proof,
+            public_inputs,
+        } => {
+            msg!("Verifying ZK payment proof");
+            verify_payment_proof(program_id, accounts, &proof, &public_inputs)
+        }
+        VerifierInstruction::VerifyProofV2 {
+            proof,
+            public_inputs,
+        } => {
+            msg!("Verifying ZK token payment proof");
+            verify_token_payment_proof(program_id, accounts, &proof, &public_inputs)
+        }
+        VerifierInstruction::VerifyAndRecord {
+            proof,
+            public_inputs,
+            update_stats,
+            dry_run,
+            consumable,
+            delegate,
+            compute_unit_price,
+        } => {
+            msg!("Verifying and recording ZK payment proof");
+            receipt::process_verify_and_record(
+                program_id,
+                accounts,
+                &proof,
+                &public_inputs,
+                update_stats,
+                dry_run,
+                consumable,
+                delegate,
+                compute_unit_price,
+            )
+        }
+        VerifierInstruction::InitializeConfig {
+            recipient_root_grace_seconds,
+        } => {
+            msg!("Initializing verifier config");
+            admin::process_initialize_config(program_id, accounts, recipient_root_grace_seconds)
+        }
+        VerifierInstruction::SetRecipientRoot { root } => {
+            msg!("Setting recipient set root");
+            admin::process_set_recipient_root(program_id, accounts, root)
+        }
+        VerifierInstruction::VerifySplitPayment {
+            proof,
+            public_inputs,
+            settle,
+            dry_run,
+        } => {
+            msg!("Verifying ZK split payment proof");
+            split::process_verify_split_payment(
+                program_id,
+                accounts,
+                &proof,
+                &public_inputs,
+                settle,
+                dry_run,
+            )
+        }
+        VerifierInstruction::VerifyRefund {
+            proof,
+            public_inputs,
+        } => {
+            msg!("Verifying ZK refund proof");
+            refund::process_verify_refund(program_id, accounts, &proof, &public_inputs)
+        }
+        VerifierInstruction::VerifySubscription {
+            proof,
+            public_inputs,
+            dry_run,
+        } => {
+            msg!("Verifying ZK subscription proof");
+            subscription::process_verify_subscription(
+                program_id,
+                accounts,
+                &proof,
+                &public_inputs,
+                dry_run,
+            )
+        }
+        VerifierInstruction::SetEpochLength {
+            epoch_length_seconds,
+        } => {
+            msg!("Setting subscription epoch length");
+            admin::process_set_epoch_length(program_id, accounts, epoch_length_seconds)
+        }
+        VerifierInstruction::SetInputPolicy { policy } => {
+            msg!("Setting input policy");
+            admin::process_set_input_policy(program_id, accounts, policy)
+        }
+        VerifierInstruction::VerifyBatchSoft {
+            proofs,
+            public_inputs,
+            min_valid,
+        } => {
+            msg!("Soft batch verifying proofs");
+            batch_verifier::process_verify_batch_soft(
+                program_id,
+                accounts,
+                &proofs,
+                &public_inputs,
+                min_valid,
+            )
+        }
+        VerifierInstruction::ProposeAdmin { new_admin } => {
+            msg!("Proposing new admin");
+            admin::process_propose_admin(program_id, accounts, new_admin)
+        }
+        VerifierInstruction::AcceptAdmin => {
+            msg!("Accepting admin transfer");
+            admin::process_accept_admin(program_id, accounts)
+        }
+        VerifierInstruction::CancelProposedAdmin => {
+            msg!("Cancelling proposed admin");
+            admin::process_cancel_proposed_admin(program_id, accounts)
+        }
+        VerifierInstruction::VerifyEnvelope { envelope } => {
+            msg!("Verifying ZK payment proof envelope");
+            let (proof, inputs) = envelope.decode()?;
+            verify_payment_statement(program_id, accounts, &proof, &inputs, None)
+        }
+        VerifierInstruction::VerifyBatch { request } => {
+            batch_verifier::batch_verify_proofs(program_id, accounts, &request)
+        }
+        VerifierInstruction::WriteBatchBuffer {
+            nonce,
+            total_len,
+            offset,
+            data,
+        } => {
+            msg!("Writing batch buffer");
+            batch_verifier::process_write_batch_buffer(
+                program_id, accounts, nonce, total_len, offset, &data,
+            )
+        }
+        VerifierInstruction::VerifyBatchBuffered { nonce } => {
+            msg!("Verifying buffered batch");
+            batch_verifier::process_verify_batch_buffered(program_id, accounts, nonce)
+        }
+        VerifierInstruction::PrepareVerificationKey {
+            circuit,
+            alpha_beta,
+        } => {
+            msg!("Preparing verification key");
+            admin::process_prepare_verification_key(program_id, accounts, circuit, alpha_beta)
+        }
+        VerifierInstruction::VerifyProofWithAck {
+            proof,
+            public_inputs,
+        } => {
+            msg!("Verifying recipient-acknowledged ZK payment proof");
+            ack::process_verify_proof_with_ack(program_id, accounts, &proof, &public_inputs)
+        }
+        VerifierInstruction::TouchReceipt {
+            receipt_proof_hash,
+            proof,
+            public_inputs,
+        } => {
+            msg!("Refreshing payment receipt");
+            receipt::process_touch_receipt(
+                program_id,
+                accounts,
+                &receipt_proof_hash,
+                &proof,
+                &public_inputs,
+            )
+        }
+        VerifierInstruction::SetMaxReceiptAge {
+            max_receipt_age_slots,
+        } => {
+            msg!("Setting max receipt age");
+            admin::process_set_max_receipt_age(program_id, accounts, max_receipt_age_slots)
+        }
+        VerifierInstruction::InitializeAuditLog => {
+            msg!("Initializing audit log");
+            admin::process_initialize_audit_log(program_id, accounts)
+        }
+        VerifierInstruction::SetAuditEnabled { enabled } => {
+            msg!("Setting audit log");
+            admin::process_set_audit_enabled(program_id, accounts, enabled)
+        }
+        VerifierInstruction::InitializePool { root_history_size } => {
+            msg!("Initializing shielded pool");
+            admin::process_initialize_pool(program_id, accounts, root_history_size)
+        }
+        VerifierInstruction::Deposit { commitment, amount } => {
+            msg!("Depositing into shielded pool");
+            pool::process_deposit(program_id, accounts, commitment, amount)
+        }
+        VerifierInstruction::Withdraw {
+            proof,
+            public_inputs,
+        } => {
+            msg!("Verifying shielded pool withdrawal");
+            pool::process_withdraw(program_id, accounts, &proof, &public_inputs)
+        }
+        VerifierInstruction::SetMaxKeyDeprecation {
+            max_key_deprecation_slots,
+        } => {
+            msg!("Setting max key deprecation window");
+            admin::process_set_max_key_deprecation(
+                program_id,
+                accounts,
+                max_key_deprecation_slots,
+            )
+        }
+        VerifierInstruction::DeprecatePreviousKey {
+            circuit,
+            deprecated_until_slot,
+        } => {
+            msg!("Deprecating previous verification key");
+            admin::process_deprecate_previous_key(
+                program_id,
+                accounts,
+                circuit,
+                deprecated_until_slot,
+            )
+        }
+        VerifierInstruction::InitializeRelayerList => {
+            msg!("Initializing relayer list");
+            admin::process_initialize_relayer_list(program_id, accounts)
+        }
+        VerifierInstruction::AddRelayer { relayer } => {
+            msg!("Adding relayer");
+            admin::process_add_relayer(program_id, accounts, &relayer)
+        }
+        VerifierInstruction::RemoveRelayer { relayer } => {
+            msg!("Removing relayer");
+            admin::process_remove_relayer(program_id, accounts, &relayer)
+        }
+        VerifierInstruction::VerifyEnvelopeWithMaxSkew {
+            envelope,
+            max_time_skew_seconds,
+        } => {
+            msg!("Verifying ZK payment proof envelope with max skew");
+            let (proof, inputs) = envelope.decode()?;
+            verify_payment_statement(
+                program_id,
+                accounts,
+                &proof,
+                &inputs,
+                Some(max_time_skew_seconds),
+            )
+        }
+        VerifierInstruction::SetAdminSigners { threshold, signers } => {
+            msg!("Setting admin signers");
+            admin::process_set_admin_signers(program_id, accounts, threshold, &signers)
+        }
+        VerifierInstruction::SetInvoiceOverpayment { allowed } => {
+            msg!("Setting invoice overpayment");
+            admin::process_set_invoice_overpayment(program_id, accounts, allowed)
+        }
+        VerifierInstruction::GetVersion => {
+            msg!("Getting version");
+            layout::check_no_extra_accounts(accounts)?;
+            version::process_get_version()
+        }
+        VerifierInstruction::VerifyWithInlineKey {
+            key,
+            proof,
+            public_inputs,
+        } => {
+            msg!("Verifying with inline key");
+            inline_key::process_verify_with_inline_key(accounts, &key, &proof, &public_inputs)
+        }
+        VerifierInstruction::RevokeVerificationKey { circuit } => {
+            msg!("Revoking verification key");
+            admin::process_revoke_verification_key(program_id, accounts, circuit)
+        }
+        VerifierInstruction::DenyRecipient { recipient } => {
+            msg!("Denying recipient");
+            admin::process_deny_recipient(program_id, accounts, recipient)
+        }
+        VerifierInstruction::AllowRecipient { recipient } => {
+            msg!("Allowing recipient");
+            admin::process_allow_recipient(program_id, accounts, recipient)
+        }
+        VerifierInstruction::VerifyBatchWithReceipt { request } => {
+            msg!("Verifying batch with receipt");
+            batch_receipt::process_verify_batch_with_receipt(program_id, accounts, &request)
+        }
+        VerifierInstruction::VerifyBatchInclusion {
+            proof_hash,
+            index,
+            siblings,
+        } => {
+            msg!("Verifying batch inclusion");
+            batch_receipt::process_verify_batch_inclusion(
+                program_id,
+                accounts,
+                &proof_hash,
+                index,
+                &siblings,
+            )
+        }
+        VerifierInstruction::SetRateLimit {
+            max_verifications_per_window,
+            window_slots,
+        } => {
+            msg!("Setting rate limit");
+            admin::process_set_rate_limit(
+                program_id,
+                accounts,
+                max_verifications_per_window,
+                window_slots,
+            )
+        }
+        VerifierInstruction::ConsumeReceipt { receipt_proof_hash } => {
+            msg!("Consuming payment receipt");
+            receipt::process_consume_receipt(program_id, accounts, &receipt_proof_hash)
+        }
+        VerifierInstruction::SetFinalitySlots { finality_slots } => {
+            msg!("Setting finality depth");
+            admin::process_set_finality_slots(program_id, accounts, finality_slots)
+        }
+        VerifierInstruction::FinalizeReceipt { receipt_proof_hash } => {
+            msg!("Finalizing payment receipt");
+            receipt::process_finalize_receipt(program_id, accounts, &receipt_proof_hash)
+        }
+        VerifierInstruction::SetHashBackend { circuit, backend } => {
+            msg!("Setting hash backend");
+            admin::process_set_hash_backend(program_id, accounts, circuit, backend)
+        }
+        VerifierInstruction::GetReceiptStatus {
+            receipt_proof_hash,
+            allow_missing,
+        } => {
+            msg!("Getting receipt status");
+            receipt::process_get_receipt_status(
+                program_id,
+                accounts,
+                &receipt_proof_hash,
+                allow_missing,
+            )
+        }
+        VerifierInstruction::AbortBuffer { nonce } => {
+            msg!("Aborting batch buffer");
+            batch_verifier::process_abort_buffer(program_id, accounts, nonce)
+        }
+        VerifierInstruction::ReapStale { nonce } => {
+            msg!("Reaping stale batch buffer");
+            batch_verifier::process_reap_stale(program_id, accounts, nonce)
+        }
+        VerifierInstruction::SetScratchTtl { scratch_ttl_slots } => {
+            msg!("Setting scratch TTL");
+            admin::process_set_scratch_ttl(program_id, accounts, scratch_ttl_slots)
+        }
+        VerifierInstruction::MigrateReceipt { receipt_proof_hash } => {
+            msg!("Migrating payment receipt");
+            receipt::process_migrate_receipt(program_id, accounts, &receipt_proof_hash)
+        }
+        VerifierInstruction::VerifyWithOpening {
+            proof,
+            public_inputs,
+            opening,
+        } => {
+            msg!("Verifying committed-amount payment proof");
+            commitment::process_verify_with_opening(
+                program_id,
+                accounts,
+                &proof,
+                &public_inputs,
+                opening.as_ref(),
+            )
+        }
+        VerifierInstruction::SetTrackRelayer { track_relayer } => {
+            msg!("Setting relayer tracking");
+            admin::process_set_track_relayer(program_id, accounts, track_relayer)
+        }
+        VerifierInstruction::ClaimRelayerRebate {
+            receipt_proof_hash,
+            amount,
+        } => {
+            msg!("Claiming relayer rebate");
+            receipt::process_claim_relayer_rebate(
+                program_id,
+                accounts,
+                &receipt_proof_hash,
+                amount,
+            )
+        }
+    }
+}
+
+/// Verify a SOL payment proof against the payment circuit key
+fn verify_payment_proof(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    proof: &Groth16Proof,
+    public_inputs: &PaymentPublicInputs,
+) -> ProgramResult {
+    let inputs = VerifiedInputs::from_v1(proof, public_inputs);
+    verify_payment_statement(program_id, accounts, proof, &inputs, None)
+}
+
+/// Verify an SPL token payment proof against the token circuit key
+fn verify_token_payment_proof(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    proof: &Groth16Proof,
+    public_inputs: &PaymentPublicInputsV2,
+) -> ProgramResult {
+    #[cfg(not(feature = "privacy-strict"))]
+    log_statement!("Token amount:", public_inputs.token_decimals_checked_amount);
+    let inputs = VerifiedInputs::from_v2(proof, public_inputs);
+    verify_payment_statement(program_id, accounts, proof, &inputs, None)
+}
+
+/// Verify a decoded payment statement against its circuit's key
+///
+/// Shared by the versioned instructions and `VerifyEnvelope`; the config
+/// account applies its input policy, the prepared key its revocation, and
+/// the recipient's denylist marker its block.
+/// `max_time_skew_seconds` narrows that policy's skew bound. A proof only the
+/// circuit's previous key accepts additionally logs `VerifiedWithPreviousKey`.
+///
+/// Checks run in the order `VerifierError` documents, and every one before
+/// the pairing runs without an alt_bn128 syscall.
+fn verify_payment_statement(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    proof: &Groth16Proof,
+    inputs: &VerifiedInputs,
+    max_time_skew_seconds: Option<u64>,
+) -> ProgramResult {
+    let layout = layout::VERIFY_PROOF;
+    layout.check(accounts)?;
+    let vk = inputs.circuit.verification_key();
+    #[cfg(feature = "diagnostics")]
+    diagnostics::report(vk, proof, inputs)?;
+    // 1. The compiled-in key matches the statement's layout
+    vk.check_signal_layout(inputs.signal_layout)?;
+
+    // 2. The config account, then its input policy
+    let mut policy = VerifierConfig::load(&accounts[layout.config], program_id)?.input_policy;
+    if let Some(requested) = max_time_skew_seconds {
+        policy = policy.tightened(requested);
+    }
+    policy.check(
+        inputs.min_amount,
+        inputs.max_block_age,
+        inputs.current_time,
+        Clock::get()?.unix_timestamp,
+    )?;
+    DeniedRecipient::check(&accounts[layout.marker], program_id, &inputs.recipient_pubkey)?;
+
+    // 3. The prepared key account, which alone records a revocation or a
+    // deprecation window
+    let prepared =
+        PreparedVk::load_active(&accounts[layout.prepared_key], program_id, inputs.circuit)?;
+    let slot = Clock::get()?.slot;
+    let previous = inputs
+        .circuit
+        .previous_verification_key()
+        .filter(|previous| prepared.accepts_previous_key(previous, slot));
+    if let Some(previous) = previous {
+        previous.check_signal_layout(inputs.signal_layout)?;
+    }
+
+    // 4. Encodings; `verify_groth16_prepared` repeats these for every key it
+    // tries, and they do not depend on the key
+    vk.check_input_count(&inputs.scalars)?;
+    proof.check_canonical()?;
+
+    #[cfg(not(feature = "privacy-strict"))]
+    log_statement!("Min amount, current time:", inputs.min_amount, inputs.current_time as u64);
+
+    // 5. The key's points, then the pairing
+    let verified_by_previous = verify_groth16_with_fallback(
+        vk,
+        &prepared.neg_alpha_g1,
+        previous,
+        proof,
+        &inputs.scalars,
+    )?;
+
+    let receipt = cpi::VerificationReceipt {
+        proof_hash: inputs.proof_hash,
+        recipient: inputs.recipient_pubkey,
+        min_amount: inputs.min_amount,
+    };
+    set_return_data(&receipt.try_to_vec()?);
+    VerifierEvent::ProofVerified(ProofVerified {
+        proof_hash: receipt.proof_hash,
+        recipient: receipt.recipient,
+        min_amount: receipt.min_amount,
+    })
+    .emit();
+    if let Some(previous) = verified_by_previous {
+        VerifierEvent::VerifiedWithPreviousKey(VerifiedWithPreviousKey {
+            proof_hash: receipt.proof_hash,
+            circuit: inputs.circuit,
+            vk_hash: previous.hash(),
+        })
+        .emit();
+    }
+    Ok(())
+}
+
+/// Keys that passed `VerificationKey::validate` during one instruction
+///
+/// The curve checks cost a few field multiplications per point, so an
+/// instruction verifying several proofs against one key threads a single
+/// `ValidatedKeys` through and checks the key once. Keys are matched by
+/// address, which can only miss, never confuse two keys.
+#[derive(Default)]
+pub(crate) struct ValidatedKeys<'k> {
+    keys: Vec<&'k VerificationKey<'k>>,
+}
+
+impl<'k> ValidatedKeys<'k> {
+    /// `vk.validate()`, skipped for a key already checked through `self`
+    pub(crate) fn check(&mut self, vk: &'k VerificationKey<'k>) -> Result<(), VerifierError> {
+        if self.keys.iter().any(|key| std::ptr::eq(*key, vk)) {
+            return Ok(());
+        }
+        if let Err(err) = vk.validate() {
+            msg!("Verification key has an invalid point");
+            return Err(err);
+        }
+        self.keys.push(vk);
+        Ok(())
+    }
+}
+
+/// Verify Groth16 proof using Solana's alt_bn128 syscalls
+fn verify_groth16(vk: &VerificationKey, proof: &Groth16Proof, inputs: &[u64]) -> ProgramResult {
+    verify_groth16_with_keys(&mut ValidatedKeys::default(), vk, proof, inputs)
+}
+
+/// `verify_groth16` validating `vk` through `keys`, for an instruction that
+/// verifies several proofs
+pub(crate) fn verify_groth16_with_keys<'k>(
+    keys: &mut ValidatedKeys<'k>,
+    vk: &'k VerificationKey<'k>,
+    proof: &Groth16Proof,
+    inputs: &[u64],
+) -> ProgramResult {
+    verify_groth16_prepared(keys, vk, &vk.alpha_g1.neg(), proof, inputs)
+}
+
+/// Verify against the active key, then against `previous` if the proof fails
+/// the pairing; returns `previous` when it is the key that accepted the proof
+///
+/// Other failures, such as a non-canonical proof, do not depend on the key and
+/// are returned as they are.
+fn verify_groth16_with_fallback<'k>(
+    vk: &VerificationKey,
+    neg_alpha: &G1Point,
+    previous: Option<&'k VerificationKey<'k>>,
+    proof: &Groth16Proof,
+    inputs: &[u64],
+) -> Result<Option<&'k VerificationKey<'k>>, ProgramError> {
+    let keys = &mut ValidatedKeys::default();
+    match (verify_groth16_prepared(keys, vk, neg_alpha, proof, inputs), previous) {
+        (Ok(()), _) => Ok(None),
+        (Err(ProgramError::InvalidArgument), Some(previous)) => {
+            msg!("Trying the previous verification key");
+            verify_groth16(previous, proof, inputs)?;
+            Ok(Some(previous))
+        }
+        (Err(err), _) => Err(err),
+    }
+}
+
+/// `verify_groth16` with -alpha supplied, e.g. from a `PreparedVk`
+fn verify_groth16_prepared<'k>(
+    keys: &mut ValidatedKeys<'k>,
+    vk: &'k VerificationKey<'k>,
+    neg_alpha: &G1Point,
+    proof: &Groth16Proof,
+    inputs: &[u64],
+) -> ProgramResult {
+    vk.check_input_count(inputs)?;
+    proof.check_canonical()?;
+    keys.check(vk)?;
+    let pub_input_point =
+        profile_phase!(profiling::Phase::InputMsm, compute_public_input_point(vk.ic, inputs))?;
+    check_pairing(vk, neg_alpha, proof, &pub_input_point)
+}
+
+/// `verify_groth16` over full-width field elements, for circuits whose public
+/// inputs are not u64 scalars
+///
+/// Does not validate `vk`; `InlineVerificationKey::validate` already has.
+pub(crate) fn verify_groth16_fr(
+    vk: &VerificationKey,
+    proof: &Groth16Proof,
+    inputs: &[Scalar],
+) -> ProgramResult {
+    vk.check_input_count(inputs)?;
+    proof.check_canonical()?;
+    let pub_input_point = profile_phase!(
+        profiling::Phase::InputMsm,
+        compute_public_input_point_generic(vk.ic, inputs)
+    )?;
+    check_pairing(vk, &vk.alpha_g1.neg(), proof, &pub_input_point)
+}
+
+/// The Groth16 pairing check, given the public input point
+fn check_pairing(
+    vk: &VerificationKey,
+    neg_alpha: &G1Point,
+    proof: &Groth16Proof,
+    pub_input_point: &G1Point,
+) -> ProgramResult {
+    let pairing = groth16_pairing(vk, neg_alpha, proof, pub_input_point)?;
+    let verified = profile_phase!(profiling::Phase::Pairing, pairing.check()).map_err(|e| {
+        msg!("Pairing failed");
+        e
+    })?;
+
+    if verified {
+        msg!("✓ Payment proof verified successfully");
+        Ok(())
+    } else {
+        msg!("✗ Payment proof verification failed");
+        Err(ProgramError::InvalidArgument)
+    }
+}
+
+/// The four pairs of the Groth16 check, in the order the syscall takes them
+fn groth16_pairing(
+    vk: &VerificationKey,
+    neg_alpha: &G1Point,
+    proof: &Groth16Proof,
+    pub_input_point: &G1Point,
+) -> Result<PairingBuilder, VerifierError> {
+    // Groth16 pairing check: e(A, B) = e(alpha, beta) * e(pub_input, gamma) * e(C, delta)
+    // This translates to: e(A, B) * e(-pub_input, gamma) * e(-C, delta) * e(-alpha, beta) = 1
+    let (neg_input, neg_c) = profile_phase!(
+        profiling::Phase::Negation,
+        (pub_input_point.neg(), proof.c.neg())
+    );
+    let mut pairing = PairingBuilder::new();
+    pairing
+        .add_pair(&proof.a, &proof.b)?
+        .add_pair(&neg_input, &vk.gamma_g2)?
+        .add_pair(&neg_c, &vk.delta_g2)?
+        .add_pair(neg_alpha, &vk.beta_g2)?;
+    Ok(pairing)
+}
+
+/// Compute the public input point of the payment circuits, whose statements
+/// expand to u64 scalars through `to_scalars`
+fn compute_public_input_point(ic: &[G1Point], inputs: &[u64]) -> Result<G1Point, ProgramError> {
+    compute_public_input_point_generic(ic, inputs)
+}
+
+/// IC[0] + IC[1]*inputs[0] + IC[2]*inputs[1] + ... for a circuit of any size
+///
+/// The inputs may be u64 values or full-width `Scalar`s. `ic` must hold one
+/// point more than there are inputs; an input without an IC point is an
+/// error rather than silently dropped.
+pub fn compute_public_input_point_generic<S: Copy + Into<Scalar>>(
+    ic: &[G1Point],
+    inputs: &[S],
+) -> Result<G1Point, ProgramError> {
+    // Bound the loop before the first syscall
+    if inputs.len() > MAX_PUBLIC_INPUTS {
+        return Err(VerifierError::TooManyPublicInputs.into());
+    }
+    if ic.len() != inputs.len() + 1 {
+        return Err(VerifierError::PublicInputLayoutMismatch.into());
+    }
+
+    // Start with IC[0] (the constant term)
+    let mut result = ic[0];
+    for (ic_point, &input) in ic[1..].iter().zip(inputs) {
+        result = add_input_term(result, ic_point, input.into())?;
+    }
+    Ok(result)
+}
+
+/// acc + ic_point * input
+fn add_input_term(
+    acc: G1Point,
+    ic_point: &G1Point,
+    input: Scalar,
+) -> Result<G1Point, ProgramError> {
+    // IC * 0 is the identity and contributes nothing; IC * 1 is the point
+    // itself, so neither needs the multiplication syscall
+    match input {
+        Scalar::ZERO => Ok(acc),
+        Scalar::ONE => acc.add(ic_point),
+        _ => acc.add(&ic_point.mul(&input)?),
+    }
+}
+
+/// Negate a G1 point (flip y coordinate)
+pub fn negate_g1_point(point: &[u8; 64]) -> [u8; 64] {
+    G1Point::new(*point).neg().to_bytes()
+}
+
+/// `negate_g1_point` for a slice, as it took before its length was typed
+pub fn negate_g1_point_slice(point: &[u8]) -> Result<[u8; 64], ProgramError> {
+    let point: &[u8; 64] = point
+        .try_into()
+        .map_err(|_| VerifierError::InvalidPointLength)?;
+    Ok(negate_g1_point(point))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_proof() {
+        let ic = distinct_ic(6);
+        let vk = trapdoor_key(&ic);
+        let public_inputs = PaymentPublicInputs {
+            min_amount: 1000000, // 0.001 SOL in lamports
+            recipient_pubkey: [0u8; 32],
+            max_block_age: 60,
+            current_time: 1700000000,
+        };
+        let proof = forge_proof(&vk, &public_inputs.to_scalars());
+        assert_eq!(verify_groth16(&vk, &proof, &public_inputs.to_scalars()), Ok(()));
+
+        // The same proof says nothing about another statement
+        let other_inputs = PaymentPublicInputs {
+            min_amount: 2000000,
+            ..public_inputs
+        };
+        assert!(verify_groth16(&vk, &proof, &other_inputs.to_scalars()).is_err());
+    }
+
+    /// G1 generator (1, 2)
+    fn g1_generator() -> G1Point {
+        let mut point = [0u8; 64];
+        point[31] = 1;
+        point[63] = 2;
+        G1Point::new(point)
+    }
+
+    /// Add the field modulus to a small big-endian limb (no carry out of the low byte)
+    fn add_modulus(limb: &mut [u8]) {
+        let low = limb[31];
+        limb.copy_from_slice(&field::FQ_MODULUS);
+        limb[31] += low;
+    }
+
+    #[test]
+    fn test_negate_g1_point_slice() {
+        let generator = g1_generator().to_bytes();
+        let negated = negate_g1_point(&generator);
+        assert_eq!(negated, g1_generator().neg().to_bytes());
+        assert_eq!(negate_g1_point_slice(&generator), Ok(negated));
+
+        for len in [0, 63, 65, 128] {
+            assert_eq!(
+                negate_g1_point_slice(&vec![1u8; len]),
+                Err(VerifierError::InvalidPointLength.into()),
+                "{} bytes",
+                len
+            );
+        }
+    }
+
+    #[test]
+    fn test_non_canonical_proof_rejected() {
+        let canonical = Groth16Proof {
+            a: g1_generator(),
+            b: G2Point::new([0u8; 128]),
+            c: g1_generator(),
+        };
+        assert_eq!(canonical.check_canonical(), Ok(()));
+
+        // x + p encodes the same generator point, but must not be accepted
+        let mut reencoded_a = g1_generator().to_bytes();
+        add_modulus(&mut reencoded_a[..32]);
+        let proof = Groth16Proof {
+            a: G1Point::new(reencoded_a),
+            ..canonical
+        };
+        assert_eq!(
+            proof.check_canonical(),
+            Err(VerifierError::NonCanonicalEncoding)
+        );
+
+        // Every G2 limb is checked too
+        for limb in 0..4 {
+            let mut b = [0u8; 128];
+            add_modulus(&mut b[limb * 32..(limb + 1) * 32]);
+            let proof = Groth16Proof {
+                a: g1_generator(),
+                b: G2Point::new(b),
+                c: g1_generator(),
+            };
+            assert_eq!(
+                proof.check_canonical(),
+                Err(VerifierError::NonCanonicalEncoding)
+            );
+        }
+    }
+
+    #[test]
+    fn test_non_canonical_rejected_before_pairing() {
+        let mut c = g1_generator();
+        add_modulus(&mut c[32..]);
+        let proof = Groth16Proof {
+            a: g1_generator(),
+            b: G2Point::new([0u8; 128]),
+            c,
+        };
+        let public_inputs = PaymentPublicInputs {
+            min_amount: 1000000,
+            recipient_pubkey: [0u8; 32],
+            max_block_age: 60,
+            current_time: 1700000000,
+        };
+
+        // The encoding is checked before the key and the pairing; the full
+        // instruction is covered by the integration tests
+        assert_eq!(
+            verify_groth16(&PAYMENT_VK, &proof, &public_inputs.to_scalars()),
+            Err(VerifierError::NonCanonicalEncoding.into())
+        );
+    }
+
+    #[test]
+    fn test_trailing_bytes_rejected() {
+        let program_id = Pubkey::new_unique();
+        let proof = Groth16Proof {
+            a: G1Point::new([1u8; 64]),
+            b: G2Point::new([2u8; 128]),
+            c: G1Point::new([3u8; 64]),
+        };
+        let public_inputs = PaymentPublicInputs {
+            min_amount: 1000000,
+            recipient_pubkey: [4u8; 32],
+            max_block_age: 60,
+            current_time: 1700000000,
+        };
+        let instructions = [
+            VerifierInstruction::VerifyProof {
+                proof: proof.clone(),
+                public_inputs: public_inputs.clone(),
+            },
+            VerifierInstruction::VerifyBatch {
+                request: BatchVerificationRequest {
+                    proofs: vec![proof],
+                    public_inputs: vec![public_inputs],
+                    allow_duplicates: false,
+                    circuit_id: CircuitId::Payment,
+                },
+            },
+            VerifierInstruction::SetAuditEnabled { enabled: true },
+        ];
+
+        for instruction in &instructions {
+            let data = instruction.try_to_vec().unwrap();
+            // Exact data gets past decoding, to fail on the missing accounts or proof
+            assert_ne!(
+                process_instruction(&program_id, &[], &data),
+                Err(ProgramError::InvalidInstructionData),
+                "{:?}",
+                instruction
+            );
+            for junk in [&[0u8][..], &[0xff; 7]] {
+                let padded = [&data[..], junk].concat();
+                assert_eq!(
+                    process_instruction(&program_id, &[], &padded),
+                    Err(ProgramError::InvalidInstructionData),
+                    "{:?}",
+                    instruction
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_proof_hash_pinned() {
+        let proof = Groth16Proof {
+            a: G1Point::new([1u8; 64]),
+            b: G2Point::new([2u8; 128]),
+            c: G1Point::new([3u8; 64]),
+        };
+        let public_inputs = PaymentPublicInputs {
+            min_amount: 1000000,
+            recipient_pubkey: [4u8; 32],
+            max_block_age: 60,
+            current_time: 1700000000,
+        };
+
+        // Changing this value breaks every nullifier and receipt id on-chain
+        let expected = [
+            0xc2, 0x25, 0xb0, 0xbd, 0xb8, 0x95, 0x4c, 0x6e,
+            0x0e, 0x72, 0xcd, 0xc2, 0xaa, 0x38, 0x05, 0xd3,
+            0x80, 0x77, 0x9d, 0xf6, 0x09, 0x6f, 0xcd, 0xfa,
+            0x39, 0xf5, 0xf1, 0x63, 0xe3, 0x22, 0x9c, 0xc0,
+        ];
+        assert_eq!(proof.hash(&public_inputs), expected);
+        assert_eq!(public_inputs.statement_hash(), expected);
+
+        // The proof bytes do not enter it
+        let other_proof = Groth16Proof {
+            c: G1Point::new([5u8; 64]),
+            ..proof.clone()
+        };
+        assert_eq!(other_proof.hash(&public_inputs), expected);
+
+        // Any change to the statement changes the hash
+        let other_inputs = PaymentPublicInputs {
+            min_amount: 1000001,
+            ..public_inputs
+        };
+        assert_ne!(proof.hash(&other_inputs), expected);
+
+        // So does the circuit, even when the inputs encode to the same bytes
+        #[derive(BorshSerialize)]
+        struct RefundShaped(PaymentPublicInputs);
+        impl Statement for RefundShaped {
+            const CIRCUIT: CircuitId = CircuitId::Refund;
+        }
+        assert_eq!(
+            RefundShaped(public_inputs.clone()).try_to_vec().unwrap(),
+            public_inputs.try_to_vec().unwrap()
+        );
+        assert_ne!(proof.hash(&RefundShaped(public_inputs)), expected);
+    }
+
+    #[test]
+    fn test_proof_borsh_layout_unchanged() {
+        let proof = Groth16Proof {
+            a: G1Point::new([1u8; 64]),
+            b: G2Point::new([2u8; 128]),
+            c: G1Point::new([3u8; 64]),
+        };
+
+        // Captured from the raw-array layout: a || b || c with no length prefixes
+        let mut expected = vec![1u8; 64];
+        expected.extend_from_slice(&[2u8; 128]);
+        expected.extend_from_slice(&[3u8; 64]);
+        assert_eq!(proof.try_to_vec().unwrap(), expected);
+
+        let decoded = Groth16Proof::try_from_slice(&expected).unwrap();
+        assert_eq!(decoded.a, proof.a);
+        assert_eq!(decoded.b, proof.b);
+        assert_eq!(decoded.c, proof.c);
+    }
+
+    /// A value decoded from exactly `T::MAX_LEN` bytes, so the constant is
+    /// the encoded length and not just a bound on it
+    fn fixed_len<T: SerializedLen + BorshDeserialize>() -> T {
+        let value = T::try_from_slice(&vec![7u8; T::MAX_LEN]).unwrap();
+        assert_eq!(value.try_to_vec().unwrap().len(), T::MAX_LEN);
+        assert_eq!(value.serialized_len(), T::MAX_LEN);
+        value
+    }
+
+    fn assert_len<T: SerializedLen>(value: &T) -> usize {
+        let len = value.try_to_vec().unwrap().len();
+        assert_eq!(value.serialized_len(), len);
+        assert!(len <= T::MAX_LEN);
+        len
+    }
+
+    #[test]
+    fn test_fixed_serialized_lengths() {
+        let proof = fixed_len::<Groth16Proof>();
+        fixed_len::<PaymentPublicInputs>();
+        let v2 = fixed_len::<PaymentPublicInputsV2>();
+        fixed_len::<PaymentPublicInputsV3>();
+        fixed_len::<SplitPaymentPublicInputs>();
+        fixed_len::<RefundPublicInputs>();
+        fixed_len::<SubscriptionPublicInputs>();
+        fixed_len::<WithdrawPublicInputs>();
+
+        let envelope = ProofEnvelope::v2(&proof, &v2);
+        assert_eq!(assert_len(&envelope), ProofEnvelope::MAX_LEN);
+        assert_len(&ProofEnvelope::v1(&proof, &v1_inputs()));
+    }
+
+    #[test]
+    fn test_maximal_serialized_lengths() {
+        use batch_verifier::{MAX_BATCH_BUFFER_LEN, MAX_BATCH_SIZE, MAX_SOFT_BATCH_SIZE};
+
+        let proof = fixed_len::<Groth16Proof>();
+        let request = |n: usize| BatchVerificationRequest {
+            proofs: vec![proof.clone(); n],
+            public_inputs: vec![v1_inputs(); n],
+            allow_duplicates: false,
+            circuit_id: CircuitId::Payment,
+        };
+        assert_eq!(assert_len(&request(0)), BatchVerificationRequest::len_for(0));
+        let full = request(MAX_BATCH_SIZE);
+        assert_eq!(assert_len(&full), BatchVerificationRequest::MAX_LEN);
+        assert_eq!(BatchVerificationRequest::MAX_LEN, MAX_BATCH_BUFFER_LEN);
+
+        let key = InlineKeySource::Inline(inline_key::InlineVerificationKey {
+            alpha_g1: proof.a,
+            beta_g2: proof.b,
+            gamma_g2: proof.b,
+            delta_g2: proof.b,
+            ic: vec![proof.c; MAX_PUBLIC_INPUTS + 1],
+        });
+        assert_eq!(assert_len(&key), InlineKeySource::MAX_LEN);
+        assert_len(&InlineKeySource::Account { key_hash: [1u8; 32] });
+
+        // A write filling the whole buffer is the longest instruction
+        let write = VerifierInstruction::WriteBatchBuffer {
+            nonce: 1,
+            total_len: MAX_BATCH_BUFFER_LEN as u32,
+            offset: 0,
+            data: vec![3u8; MAX_BATCH_BUFFER_LEN],
+        };
+        assert_eq!(assert_len(&write), VerifierInstruction::MAX_LEN);
+        for instruction in [
+            VerifierInstruction::VerifyBatch { request: full },
+            VerifierInstruction::VerifyWithInlineKey {
+                key,
+                proof: proof.clone(),
+                public_inputs: vec![Be32::ZERO; MAX_PUBLIC_INPUTS],
+            },
+            VerifierInstruction::VerifyBatchSoft {
+                proofs: vec![proof.clone(); MAX_SOFT_BATCH_SIZE],
+                public_inputs: (0..MAX_SOFT_BATCH_SIZE).map(|_| v3_inputs()).collect(),
+                min_valid: 1,
+            },
+            VerifierInstruction::VerifyEnvelope {
+                envelope: ProofEnvelope::v1(&proof, &v1_inputs()),
+            },
+            VerifierInstruction::GetVersion,
+        ] {
+            assert_len(&instruction);
+        }
+    }
+
+    #[test]
+    fn test_v2_scalars_bind_mint() {
+        let v1 = PaymentPublicInputs {
+            min_amount: 1000000,
+            recipient_pubkey: [4u8; 32],
+            max_block_age: 60,
+            current_time: 1700000000,
+        };
+        let mut mint = [0u8; 32];
+        mint[0] = 0xc6;
+        mint[8] = 0xfa;
+        let v2 = PaymentPublicInputsV2 {
+            min_amount: v1.min_amount,
+            recipient_pubkey: v1.recipient_pubkey,
+            max_block_age: v1.max_block_age,
+            current_time: v1.current_time,
+            mint,
+            token_decimals_checked_amount: 2500000,
+        };
+
+        let v1_scalars = v1.to_scalars();
+        let v2_scalars = v2.to_scalars();
+
+        // V2 extends the V1 signal order rather than reshuffling it
+        assert_eq!(&v2_scalars[..5], &v1_scalars[..]);
+        assert_eq!(v2_scalars[5], 0xc6);
+        assert_eq!(v2_scalars[6], 0xfa);
+        assert_eq!(v2_scalars[7], 2500000);
+
+        // One IC point per scalar plus the constant term
+        assert_eq!(PAYMENT_VK.ic.len(), v1_scalars.len() + 1);
+        assert_eq!(TOKEN_PAYMENT_VK.ic.len(), v2_scalars.len() + 1);
+    }
+
+    #[test]
+    fn test_instruction_versions_keep_v1_layout() {
+        let proof = Groth16Proof {
+            a: G1Point::new([1u8; 64]),
+            b: G2Point::new([2u8; 128]),
+            c: G1Point::new([3u8; 64]),
+        };
+        let v1 = VerifierInstruction::VerifyProof {
+            proof,
+            public_inputs: PaymentPublicInputs {
+                min_amount: 1000000,
+                recipient_pubkey: [4u8; 32],
+                max_block_age: 60,
+                current_time: 1700000000,
+            },
+        }
+        .try_to_vec()
+        .unwrap();
+
+        // Existing clients send variant 0 followed by the proof and inputs
+        assert_eq!(v1[0], 0);
+        assert_eq!(v1.len(), 1 + 256 + 56);
+
+        let v2 = VerifierInstruction::VerifyProofV2 {
+            proof: Groth16Proof {
+                a: G1Point::new([1u8; 64]),
+                b: G2Point::new([2u8; 128]),
+                c: G1Point::new([3u8; 64]),
+            },
+            public_inputs: PaymentPublicInputsV2 {
+                min_amount: 1000000,
+                recipient_pubkey: [4u8; 32],
+                max_block_age: 60,
+                current_time: 1700000000,
+                mint: [5u8; 32],
+                token_decimals_checked_amount: 1000000,
+            },
+        }
+        .try_to_vec()
+        .unwrap();
+
+        assert_eq!(v2[0], 1);
+        assert_eq!(v2.len(), 1 + 256 + 56 + 40);
+        assert!(matches!(
+            VerifierInstruction::try_from_slice(&v2).unwrap(),
+            VerifierInstruction::VerifyProofV2 { .. }
+        ));
+    }
+
+    fn v3_inputs() -> PaymentPublicInputsV3 {
+        PaymentPublicInputsV3 {
+            min_amount: 1000000,
+            recipient_pubkey: [4u8; 32],
+            max_block_age: 60,
+            current_time: 1700000000,
+            invoice_id: [0xaa; 32],
+            valid_until: 0,
+            recipient_set_root: [0x5e; 32],
+        }
+    }
+
+    /// `compute_public_input_point` without the zero/one shortcuts
+    fn compute_public_input_point_naive(ic: &[G1Point], inputs: &[u64]) -> G1Point {
+        let mut result = ic[0];
+        for (ic_point, &input_val) in ic[1..].iter().zip(inputs) {
+            result = result.add(&ic_point.mul(&Scalar::from_u64(input_val)).unwrap()).unwrap();
+        }
+        result
+    }
+
+    /// IC points k*G for k = 1..=n, so every term is distinct
+    fn distinct_ic(n: usize) -> Vec<G1Point> {
+        let g = g1_generator();
+        let mut ic = vec![g];
+        for _ in 1..n {
+            let next = ic.last().unwrap().add(&g).unwrap();
+            ic.push(next);
+        }
+        ic
+    }
+
+    #[test]
+    fn test_zero_and_one_scalars_match_naive_path() {
+        let ic = distinct_ic(6);
+        let cases: [[u64; 5]; 5] = [
+            [0, 0, 0, 0, 0],
+            [1, 1, 1, 1, 1],
+            [0, 1, 0, 1, 0],
+            [1000000, 0, 1, 60, 1700000000],
+            [2, 1, 0, 3, 1],
+        ];
+        for inputs in cases {
+            assert_eq!(
+                compute_public_input_point(&ic, &inputs).unwrap(),
+                compute_public_input_point_naive(&ic, &inputs),
+                "{:?}",
+                inputs
+            );
+        }
+    }
+
+    #[test]
+    fn test_generic_input_point_serves_any_circuit_size() {
+        // Fixture circuits with 3 and 12 public inputs
+        let small = [7u64, 0, 1];
+        let large: Vec<u64> = (0..12).map(|i| i * 1000 + i % 2).collect();
+        for inputs in [&small[..], &large[..]] {
+            let ic = distinct_ic(inputs.len() + 1);
+            let scalars: Vec<Scalar> = inputs.iter().copied().map(Scalar::from_u64).collect();
+            let expected = compute_public_input_point_naive(&ic, inputs);
+            assert_eq!(
+                compute_public_input_point_generic(&ic, inputs),
+                Ok(expected)
+            );
+            assert_eq!(
+                compute_public_input_point_generic(&ic, &scalars),
+                Ok(expected)
+            );
+
+            // Each circuit's IC fits only its own input count
+            assert_eq!(
+                compute_public_input_point_generic(&ic[1..], inputs),
+                Err(VerifierError::PublicInputLayoutMismatch.into())
+            );
+        }
+    }
+
+    #[test]
+    fn test_all_zero_recipient_matches_naive_path() {
+        let ic = distinct_ic(6);
+        let inputs = PaymentPublicInputs {
+            recipient_pubkey: [0u8; 32],
+            ..v1_inputs()
+        }
+        .to_scalars();
+        assert_eq!(inputs[1], 0);
+        assert_eq!(inputs[2], 0);
+        assert_eq!(
+            compute_public_input_point(&ic, &inputs).unwrap(),
+            compute_public_input_point_naive(&ic, &inputs)
+        );
+    }
+
+    fn v1_inputs() -> PaymentPublicInputs {
+        PaymentPublicInputs {
+            min_amount: 1000000,
+            recipient_pubkey: [4u8; 32],
+            max_block_age: 60,
+            current_time: 1700000000,
+        }
+    }
+
+    #[test]
+    fn test_default_policy_is_permissive() {
+        let inputs = PaymentPublicInputs {
+            min_amount: 0,
+            max_block_age: 0,
+            current_time: 0,
+            ..v1_inputs()
+        };
+        assert_eq!(inputs.validate(&InputPolicy::default(), 1700000000), Ok(()));
+    }
+
+    #[test]
+    fn test_policy_min_amount_floor() {
+        let policy = InputPolicy {
+            min_amount_floor: 1000000,
+            ..InputPolicy::default()
+        };
+        assert_eq!(v1_inputs().validate(&policy, 1700000000), Ok(()));
+
+        let inputs = PaymentPublicInputs {
+            min_amount: 999999,
+            ..v1_inputs()
+        };
+        assert_eq!(
+            inputs.validate(&policy, 1700000000),
+            Err(VerifierError::AmountBelowFloor)
+        );
+    }
+
+    #[test]
+    fn test_policy_max_block_age_ceiling() {
+        let policy = InputPolicy {
+            max_block_age_ceiling: 60,
+            ..InputPolicy::default()
+        };
+        assert_eq!(v1_inputs().validate(&policy, 1700000000), Ok(()));
+
+        let inputs = PaymentPublicInputs {
+            max_block_age: 61,
+            ..v1_inputs()
+        };
+        assert_eq!(
+            inputs.validate(&policy, 1700000000),
+            Err(VerifierError::BlockAgeAboveCeiling)
+        );
+    }
+
+    #[test]
+    fn test_policy_time_skew() {
+        let policy = InputPolicy {
+            max_time_skew_seconds: 30,
+            ..InputPolicy::default()
+        };
+        // Skew is allowed in both directions, bounds inclusive
+        assert_eq!(v1_inputs().validate(&policy, 1700000030), Ok(()));
+        assert_eq!(v1_inputs().validate(&policy, 1699999970), Ok(()));
+        assert_eq!(
+            v1_inputs().validate(&policy, 1700000031),
+            Err(VerifierError::ClockSkewExceeded)
+        );
+        assert_eq!(
+            v1_inputs().validate(&policy, 1699999969),
+            Err(VerifierError::ClockSkewExceeded)
+        );
+    }
+
+    #[test]
+    fn test_policy_time_skew_tightened() {
+        let policy = InputPolicy {
+            max_time_skew_seconds: 30,
+            ..InputPolicy::default()
+        };
+        // A request can narrow the bound but never widen it
+        assert_eq!(policy.tightened(100), policy);
+        let tight = policy.tightened(10);
+        assert_eq!(tight.max_time_skew_seconds, 10);
+        assert_eq!(tight.min_amount_floor, policy.min_amount_floor);
+        assert_eq!(v1_inputs().validate(&tight, 1700000010), Ok(()));
+        assert_eq!(v1_inputs().validate(&tight, 1700000009), Ok(()));
+        assert_eq!(
+            v1_inputs().validate(&tight, 1700000011),
+            Err(VerifierError::ClockSkewExceeded)
+        );
+    }
+
+    #[test]
+    fn test_invoice_binding_changes_public_input_point() {
+        // Test key whose IC points are all the G1 generator; the placeholder
+        // invoice key is zeroed and would map every statement to the identity
+        let ic = [g1_generator(); 15];
+
+        let invoice_a = v3_inputs();
+        let mut invoice_b_id = [0xaa; 32];
+        invoice_b_id[31] = 0xab;
+        let invoice_b = PaymentPublicInputsV3 {
+            invoice_id: invoice_b_id,
+            ..invoice_a
+        };
+
+        // A proof for invoice A commits to A's public input point, so the
+        // pairing check fails once invoice B's point is substituted
+        let point_a = compute_public_input_point(&ic, &invoice_a.to_scalars()).unwrap();
+        let point_b = compute_public_input_point(&ic, &invoice_b.to_scalars()).unwrap();
+        assert_ne!(point_a, point_b);
+
+        // Receipts and nullifiers are keyed per invoice as well
+        let proof = Groth16Proof {
+            a: G1Point::new([1u8; 64]),
+            b: G2Point::new([2u8; 128]),
+            c: G1Point::new([3u8; 64]),
+        };
+        assert_ne!(proof.hash(&invoice_a), proof.hash(&invoice_b));
+    }
+
+    #[test]
+    fn test_proof_expiry() {
+        let public_inputs = PaymentPublicInputsV3 {
+            valid_until: 1700000600,
+            ..v3_inputs()
+        };
+
+        assert_eq!(public_inputs.check_expiry(1700000000), Ok(()));
+        // The deadline itself is inclusive
+        assert_eq!(public_inputs.check_expiry(1700000600), Ok(()));
+        assert_eq!(
+            public_inputs.check_expiry(1700000601),
+            Err(VerifierError::ProofExpired)
+        );
+    }
+
+    #[test]
+    fn test_zero_valid_until_never_expires() {
+        let public_inputs = v3_inputs();
+
+        assert_eq!(public_inputs.check_expiry(i64::MAX), Ok(()));
+    }
+
+    #[test]
+    fn test_compiled_keys_match_signal_layouts() {
+        assert_eq!(
+            PAYMENT_VK.check_signal_layout(PaymentPublicInputs::SIGNAL_LAYOUT),
+            Ok(())
+        );
+        assert_eq!(
+            TOKEN_PAYMENT_VK.check_signal_layout(PaymentPublicInputsV2::SIGNAL_LAYOUT),
+            Ok(())
+        );
+        assert_eq!(
+            INVOICE_PAYMENT_VK.check_signal_layout(PaymentPublicInputsV3::SIGNAL_LAYOUT),
+            Ok(())
+        );
+        assert_eq!(
+            WITHDRAW_VK.check_signal_layout(WithdrawPublicInputs::SIGNAL_LAYOUT),
+            Ok(())
+        );
+        assert_eq!(
+            COMMITTED_PAYMENT_VK.check_signal_layout(CommittedPaymentPublicInputs::SIGNAL_LAYOUT),
+            Ok(())
+        );
+
+        // The layout descriptor and the scalar packing must agree in length
+        assert_eq!(PaymentPublicInputs::SIGNAL_LAYOUT.len(), 5);
+        assert_eq!(PaymentPublicInputsV2::SIGNAL_LAYOUT.len(), 8);
+        assert_eq!(PaymentPublicInputsV3::SIGNAL_LAYOUT.len(), 14);
+        assert_eq!(WithdrawPublicInputs::SIGNAL_LAYOUT.len(), 17);
+        assert_eq!(CommittedPaymentPublicInputs::SIGNAL_LAYOUT.len(), 9);
+    }
+
+    #[test]
+    fn test_vk_hash_binds_every_point() {
+        assert!(PAYMENT_VK.is_canonical());
+        let hash = PAYMENT_VK.hash();
+
+        let mut ic = PAYMENT_VK.ic.to_vec();
+        ic[5] = ic[5].neg();
+        let other_ic = VerificationKey { ic: &ic, ..PAYMENT_VK };
+        assert_ne!(other_ic.hash(), hash);
+
+        let other_delta = VerificationKey {
+            delta_g2: PAYMENT_VK.gamma_g2,
+            ..PAYMENT_VK
+        };
+        assert_ne!(other_delta.hash(), hash);
+
+        // Zeroed placeholder keys are still canonical, just degenerate
+        assert!(REFUND_VK.is_canonical());
+    }
+
+    #[test]
+    fn test_neg_alpha_is_additive_inverse() {
+        // What `PrepareVerificationKey` stores as -alpha: before version 5
+        // of `PreparedVk` the negation subtracted little-endian, which gives
+        // a point off the curve
+        for vk in [&PAYMENT_VK, &TOKEN_PAYMENT_VK, &INVOICE_PAYMENT_VK, &SPLIT_PAYMENT_VK] {
+            let neg_alpha = vk.alpha_g1.neg();
+            assert!(neg_alpha.is_on_curve());
+            assert_eq!(vk.alpha_g1.add(&neg_alpha), Ok(G1Point::IDENTITY));
+            for point in vk.ic {
+                assert_eq!(point.add(&point.neg()), Ok(G1Point::IDENTITY));
+            }
+        }
+    }
+
+    #[test]
+    fn test_points_keep_their_encoding() {
+        // Borsh, `to_bytes` and `new` agree byte for byte on every key point
+        for vk in [&PAYMENT_VK, &TOKEN_PAYMENT_VK, &INVOICE_PAYMENT_VK, &SPLIT_PAYMENT_VK] {
+            for point in std::iter::once(&vk.alpha_g1).chain(vk.ic) {
+                assert_eq!(point.try_to_vec().unwrap(), point.to_bytes());
+                assert_eq!(G1Point::new(point.to_bytes()), *point);
+            }
+            for point in [&vk.beta_g2, &vk.gamma_g2, &vk.delta_g2] {
+                assert_eq!(point.try_to_vec().unwrap(), point.to_bytes());
+                assert_eq!(G2Point::new(point.to_bytes()), *point);
+            }
+        }
+
+        // A proof is its three points back to back
+        let proof = Groth16Proof {
+            a: PAYMENT_VK.ic[1],
+            b: PAYMENT_VK.beta_g2,
+            c: PAYMENT_VK.alpha_g1,
+        };
+        let bytes = proof.try_to_vec().unwrap();
+        assert_eq!(bytes.len(), 256);
+        assert_eq!(bytes[..64], proof.a.to_bytes());
+        assert_eq!(bytes[64..192], proof.b.to_bytes());
+        assert_eq!(bytes[192..], proof.c.to_bytes());
+        assert_eq!(Groth16Proof::try_from_slice(&bytes).unwrap().try_to_vec().unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_signal_layout_mismatch_rejected() {
+        // A key exported for a circuit with maxBlockAge and currentTime swapped
+        let reordered = [
+            "minAmount",
+            "recipientPubKeyX",
+            "recipientPubKeyY",
+            "currentTime",
+            "maxBlockAge",
+        ];
+        let vk = VerificationKey {
+            signal_layout_hash: signal_layout_hash(&reordered),
+            ..PAYMENT_VK
+        };
+        assert_eq!(
+            vk.check_signal_layout(PaymentPublicInputs::SIGNAL_LAYOUT),
+            Err(VerifierError::PublicInputLayoutMismatch)
+        );
+
+        // A key for the token circuit cannot verify SOL payment inputs
+        assert_eq!(
+            TOKEN_PAYMENT_VK.check_signal_layout(PaymentPublicInputs::SIGNAL_LAYOUT),
+            Err(VerifierError::PublicInputLayoutMismatch)
+        );
+    }
+
+    #[test]
+    fn test_input_count_checked_before_syscalls() {
+        // Off-curve IC points and a non-canonical proof: any addition or
+        // encoding check reached would fail with a different error
+        let mut off_curve = [0u8; 64];
+        off_curve[31] = 1;
+        off_curve[63] = 1;
+        let ic = vec![G1Point::new(off_curve); MAX_PUBLIC_INPUTS + 2];
+        let proof = Groth16Proof {
+            a: G1Point::new([0xff; 64]),
+            b: G2Point::new([0u8; 128]),
+            c: G1Point::new([0u8; 64]),
+        };
+        let too_many = ProgramError::from(VerifierError::TooManyPublicInputs);
+
+        // An oversized vector is refused even by a key that claims to take it
+        let oversized = VerificationKey {
+            ic: &ic,
+            num_public_inputs: MAX_PUBLIC_INPUTS + 1,
+            ..PAYMENT_VK
+        };
+        let inputs = vec![2u64; MAX_PUBLIC_INPUTS + 1];
+        assert_eq!(verify_groth16(&oversized, &proof, &inputs), Err(too_many.clone()));
+        assert_eq!(compute_public_input_point(&ic, &inputs), Err(too_many));
+
+        // Inputs the key has no IC point for, or too few of them
+        let mismatch = ProgramError::from(VerifierError::PublicInputLayoutMismatch);
+        let vk = VerificationKey { ic: &ic[..6], ..PAYMENT_VK };
+        for count in [4, 6] {
+            let inputs = vec![2u64; count];
+            assert_eq!(verify_groth16(&vk, &proof, &inputs), Err(mismatch.clone()));
+            assert_eq!(compute_public_input_point(vk.ic, &inputs), Err(mismatch.clone()));
+        }
+
+        // IC points that disagree with the exported count
+        let vk = VerificationKey { ic: &ic[..7], ..PAYMENT_VK };
+        assert_eq!(vk.check_input_count(&[2u64; 5]), Err(VerifierError::PublicInputLayoutMismatch));
+        assert_eq!(vk.check_input_count(&[2u64; 6]), Err(VerifierError::PublicInputLayoutMismatch));
+    }
+
+    #[test]
+    fn test_compiled_keys_within_input_bound() {
+        for vk in [
+            &PAYMENT_VK,
+            &TOKEN_PAYMENT_VK,
+            &INVOICE_PAYMENT_VK,
+            &SPLIT_PAYMENT_VK,
+            &REFUND_VK,
+            &SUBSCRIPTION_VK,
+            &WITHDRAW_VK,
+        ] {
+            assert_eq!(vk.ic.len(), vk.num_public_inputs + 1);
+            assert!(vk.num_public_inputs <= MAX_PUBLIC_INPUTS);
+        }
+    }
+
+    #[test]
+    fn test_split_scalars_bind_both_legs() {
+        let public_inputs = SplitPaymentPublicInputs {
+            recipients: [[1u8; 32], [2u8; 32]],
+            min_amounts: [900000, 100000],
+            max_block_age: 60,
+            current_time: 1700000000,
+            mint: [3u8; 32],
+        };
+        let scalars = public_inputs.to_scalars();
+
+        assert_eq!(scalars[0], 900000);
+        assert_eq!(scalars[1], u64::from_le_bytes([1u8; 8]));
+        assert_eq!(scalars[3], 100000);
+        assert_eq!(scalars[4], u64::from_le_bytes([2u8; 8]));
+        assert_eq!(
+            SPLIT_PAYMENT_VK.check_signal_layout(SplitPaymentPublicInputs::SIGNAL_LAYOUT),
+            Ok(())
+        );
+
+        // Swapping the legs is a different statement
+        let swapped = SplitPaymentPublicInputs {
+            recipients: [[2u8; 32], [1u8; 32]],
+            min_amounts: [100000, 900000],
+            ..public_inputs
+        };
+        assert_ne!(swapped.to_scalars(), scalars);
+    }
+
+    #[test]
+    fn test_withdraw_scalars_bind_full_recipient() {
+        let public_inputs = WithdrawPublicInputs {
+            root: [1u8; 32],
+            nullifier: [2u8; 32],
+            recipient: [3u8; 32],
+            amount: 1000000,
+            change_commitment: [5u8; 32],
+        };
+        let scalars = public_inputs.to_scalars();
+        assert_eq!(scalars[..4], [u64::from_le_bytes([1u8; 8]); 4]);
+        assert_eq!(scalars[4..8], [u64::from_le_bytes([2u8; 8]); 4]);
+        assert_eq!(scalars[8..12], [u64::from_le_bytes([3u8; 8]); 4]);
+        assert_eq!(scalars[12], 1000000);
+        assert_eq!(scalars[13..], [u64::from_le_bytes([5u8; 8]); 4]);
+
+        // Unlike the payment circuits' two-limb recipient, every byte counts
+        let mut recipient = [3u8; 32];
+        recipient[31] = 4;
+        let redirected = WithdrawPublicInputs {
+            recipient,
+            ..public_inputs
+        };
+        assert_ne!(redirected.to_scalars(), scalars);
+    }
+
+    /// G2 generator in syscall order: x.c1, x.c0, y.c1, y.c0
+    const G2_GENERATOR: [u8; 128] = [
+        0x19, 0x8e, 0x93, 0x93, 0x92, 0x0d, 0x48, 0x3a,
+        0x72, 0x60, 0xbf, 0xb7, 0x31, 0xfb, 0x5d, 0x25,
+        0xf1, 0xaa, 0x49, 0x33, 0x35, 0xa9, 0xe7, 0x12,
+        0x97, 0xe4, 0x85, 0xb7, 0xae, 0xf3, 0x12, 0xc2,
+        0x18, 0x00, 0xde, 0xef, 0x12, 0x1f, 0x1e, 0x76,
+        0x42, 0x6a, 0x00, 0x66, 0x5e, 0x5c, 0x44, 0x79,
+        0x67, 0x43, 0x22, 0xd4, 0xf7, 0x5e, 0xda, 0xdd,
+        0x46, 0xde, 0xbd, 0x5c, 0xd9, 0x92, 0xf6, 0xed,
+        0x09, 0x06, 0x89, 0xd0, 0x58, 0x5f, 0xf0, 0x75,
+        0xec, 0x9e, 0x99, 0xad, 0x69, 0x0c, 0x33, 0x95,
+        0xbc, 0x4b, 0x31, 0x33, 0x70, 0xb3, 0x8e, 0xf3,
+        0x55, 0xac, 0xda, 0xdc, 0xd1, 0x22, 0x97, 0x5b,
+        0x12, 0xc8, 0x5e, 0xa5, 0xdb, 0x8c, 0x6d, 0xeb,
+        0x4a, 0xab, 0x71, 0x80, 0x8d, 0xcb, 0x40, 0x8f,
+        0xe3, 0xd1, 0xe7, 0x69, 0x0c, 0x43, 0xd3, 0x7b,
+        0x4c, 0xe6, 0xcc, 0x01, 0x66, 0xfa, 0x7d, 0xaa,
+    ];
+
+    /// A key with beta = gamma = delta = the G2 generator, so that
+    /// A = alpha + L + C, B = that generator is a valid proof of any inputs
+    fn trapdoor_key(ic: &[G1Point]) -> VerificationKey<'_> {
+        let g2 = G2Point::new(G2_GENERATOR);
+        VerificationKey {
+            alpha_g1: g1_generator(),
+            beta_g2: g2,
+            gamma_g2: g2,
+            delta_g2: g2,
+            ic,
+            num_public_inputs: ic.len() - 1,
+            signal_layout_hash: [0u8; 32],
+        }
+    }
+
+    fn forge_proof(vk: &VerificationKey, inputs: &[u64]) -> Groth16Proof {
+        let c = g1_generator();
+        let public_input_point = compute_public_input_point(vk.ic, inputs).unwrap();
+        let a = vk.alpha_g1.add(&public_input_point).unwrap().add(&c).unwrap();
+        Groth16Proof {
+            a,
+            b: G2Point::new(G2_GENERATOR),
+            c,
+        }
+    }
+
+    /// `forge_proof` for full-width inputs
+    fn forge_proof_fr(vk: &VerificationKey, inputs: &[Scalar]) -> Groth16Proof {
+        let c = g1_generator();
+        let public_input_point = compute_public_input_point_generic(vk.ic, inputs).unwrap();
+        let a = vk.alpha_g1.add(&public_input_point).unwrap().add(&c).unwrap();
+        Groth16Proof {
+            a,
+            b: G2Point::new(G2_GENERATOR),
+            c,
+        }
+    }
+
+    /// 2 and 3 times the G2 generator, in syscall order
+    const G2_DOUBLE: [u8; 128] = [
+        0x20, 0x3e, 0x20, 0x5d, 0xb4, 0xf1, 0x9b, 0x37,
+        0xb6, 0x01, 0x21, 0xb8, 0x3a, 0x73, 0x33, 0x70,
+        0x6d, 0xb8, 0x64, 0x31, 0xc6, 0xd8, 0x35, 0x84,
+        0x99, 0x57, 0xed, 0x8c, 0x39, 0x28, 0xad, 0x79,
+        0x27, 0xdc, 0x72, 0x34, 0xfd, 0x11, 0xd3, 0xe8,
+        0xc3, 0x6c, 0x59, 0x27, 0x7c, 0x3e, 0x6f, 0x14,
+        0x9d, 0x5c, 0xd3, 0xcf, 0xa9, 0xa6, 0x2a, 0xee,
+        0x49, 0xf8, 0x13, 0x09, 0x62, 0xb4, 0xb3, 0xb9,
+        0x19, 0x5e, 0x8a, 0xa5, 0xb7, 0x82, 0x74, 0x63,
+        0x72, 0x2b, 0x8c, 0x15, 0x39, 0x31, 0x57, 0x9d,
+        0x35, 0x05, 0x56, 0x6b, 0x4e, 0xdf, 0x48, 0xd4,
+        0x98, 0xe1, 0x85, 0xf0, 0x50, 0x9d, 0xe1, 0x52,
+        0x04, 0xbb, 0x53, 0xb8, 0x97, 0x7e, 0x5f, 0x92,
+        0xa0, 0xbc, 0x37, 0x27, 0x42, 0xc4, 0x83, 0x09,
+        0x44, 0xa5, 0x9b, 0x4f, 0xe6, 0xb1, 0xc0, 0x46,
+        0x6e, 0x2a, 0x6d, 0xad, 0x12, 0x2b, 0x5d, 0x2e,
+    ];
+    const G2_TRIPLE: [u8; 128] = [
+        0x10, 0x14, 0x77, 0x2f, 0x57, 0xbb, 0x97, 0x42,
+        0x73, 0x51, 0x91, 0xcd, 0x5d, 0xcf, 0xe4, 0xeb,
+        0xbc, 0x04, 0x15, 0x6b, 0x68, 0x78, 0xa0, 0xa7,
+        0xc9, 0x82, 0x4f, 0x32, 0xff, 0xb6, 0x6e, 0x85,
+        0x06, 0x06, 0x4e, 0x78, 0x4d, 0xb1, 0x0e, 0x90,
+        0x51, 0xe5, 0x28, 0x26, 0xe1, 0x92, 0x71, 0x5e,
+        0x8d, 0x7e, 0x47, 0x8c, 0xb0, 0x9a, 0x5e, 0x00,
+        0x12, 0xde, 0xfa, 0x06, 0x94, 0xfb, 0xc7, 0xf5,
+        0x02, 0x1e, 0x23, 0x35, 0xf3, 0x35, 0x4b, 0xb7,
+        0x92, 0x2f, 0xfc, 0xc2, 0xf3, 0x8d, 0x33, 0x23,
+        0xdd, 0x94, 0x53, 0xac, 0x49, 0xb5, 0x54, 0x41,
+        0x45, 0x2a, 0xea, 0xca, 0x14, 0x77, 0x11, 0xb2,
+        0x05, 0x8e, 0x1d, 0x56, 0x81, 0xb5, 0xb9, 0xe0,
+        0x07, 0x4b, 0x0f, 0x9c, 0x8d, 0x2c, 0x68, 0xa0,
+        0x69, 0xb9, 0x20, 0xd7, 0x45, 0x21, 0xe7, 0x97,
+        0x65, 0x03, 0x6d, 0x57, 0x66, 0x6c, 0x55, 0x97,
+    ];
+
+    /// A valid proof against `trapdoor_key` with B = k * H for H the G2
+    /// generator and k = +-1, +-2 or +-3: A = G and C = k * G - alpha - L
+    fn forge_proof_with_b(vk: &VerificationKey, inputs: &[u64], k: i8) -> Groth16Proof {
+        let b = match k.abs() {
+            1 => G2Point::new(G2_GENERATOR),
+            2 => G2Point::new(G2_DOUBLE),
+            3 => G2Point::new(G2_TRIPLE),
+            _ => unreachable!(),
+        };
+        let a = g1_generator();
+        let mut ka = a.mul(&Scalar::from_u64(k.unsigned_abs() as u64)).unwrap();
+        let b = if k < 0 {
+            ka = ka.neg();
+            b.neg()
+        } else {
+            b
+        };
+        let public_input_point = compute_public_input_point(vk.ic, inputs).unwrap();
+        let c = ka.add(&vk.alpha_g1.neg()).unwrap().add(&public_input_point.neg()).unwrap();
+        Groth16Proof { a, b, c }
+    }
+
+    #[test]
+    fn test_batch_pairing_soundness() {
+        use batch_verifier::{verify_batch_pairing, MAX_BATCH_SIZE};
+        use pairing::MAX_PAIRS;
+
+        let ic = distinct_ic(6);
+        let vk = trapdoor_key(&ic);
+        let neg_alpha = vk.alpha_g1.neg();
+        let statements: Vec<PaymentPublicInputs> = (1..=MAX_BATCH_SIZE as u64)
+            .map(|i| PaymentPublicInputs {
+                min_amount: 1000000 * i,
+                recipient_pubkey: [4u8; 32],
+                max_block_age: 60,
+                current_time: 1700000000,
+            })
+            .collect();
+        // Six distinct B points, so the batch needs more pairs than a
+        // `PairingBuilder` holds
+        let ks = [1, 2, 3, -1, -2, -3];
+        assert!(ks.len() + 3 > MAX_PAIRS);
+        let proofs: Vec<Groth16Proof> = statements
+            .iter()
+            .zip(ks.iter().cycle())
+            .map(|(inputs, &k)| forge_proof_with_b(&vk, &inputs.to_scalars(), k))
+            .collect();
+        for (proof, inputs) in proofs.iter().zip(&statements) {
+            assert_eq!(verify_groth16(&vk, proof, &inputs.to_scalars()), Ok(()));
+        }
+        let verify = |proofs: &[Groth16Proof], statements: &[PaymentPublicInputs]| {
+            verify_batch_pairing(&vk, &neg_alpha, HashBackend::Keccak256, proofs, statements)
+        };
+
+        // Valid proofs pass, alone, as a full batch and under either backend
+        assert_eq!(verify(&proofs[..1], &statements[..1]), Ok(()));
+        assert_eq!(verify(&proofs, &statements), Ok(()));
+        assert_eq!(
+            verify_batch_pairing(&vk, &neg_alpha, HashBackend::Sha256, &proofs, &statements),
+            Ok(())
+        );
+
+        // One bad proof or statement anywhere fails the whole batch
+        let g = g1_generator();
+        let invalid = ProgramError::InvalidArgument;
+        for i in [0, 7, MAX_BATCH_SIZE - 1] {
+            let mut bad = proofs.clone();
+            bad[i].c = bad[i].c.add(&g).unwrap();
+            assert_eq!(verify(&bad, &statements), Err(invalid.clone()), "C of {i}");
+
+            let mut bad = proofs.clone();
+            bad[i].a = bad[i].a.add(&g).unwrap();
+            assert_eq!(verify(&bad, &statements), Err(invalid.clone()), "A of {i}");
+
+            let mut lied = statements.clone();
+            lied[i].min_amount += 1;
+            assert_eq!(verify(&proofs, &lied), Err(invalid.clone()), "statement {i}");
+        }
+
+        // Two proofs trading their B points both turn invalid
+        let mut traded = proofs.clone();
+        let b = traded[0].b;
+        traded[0].b = traded[1].b;
+        traded[1].b = b;
+        assert_eq!(verify(&traded, &statements), Err(invalid.clone()));
+
+        // Errors that cancel across two proofs do not survive the coefficients
+        let mut cancelling = proofs.clone();
+        cancelling[0].c = cancelling[0].c.add(&g).unwrap();
+        cancelling[6].c = cancelling[6].c.add(&g.neg()).unwrap();
+        assert_eq!(verify(&cancelling, &statements), Err(invalid));
+    }
+
+    #[test]
+    fn test_full_width_inputs_verify() {
+        // A fixture circuit with three public inputs, bound to no registered
+        // layout; the first input needs more than 64 bits
+        let ic = distinct_ic(4);
+        let vk = trapdoor_key(&ic);
+        let mut wide = [0u8; 32];
+        wide[0] = 0x20;
+        wide[31] = 7;
+        let inputs = [
+            Scalar::from_canonical(Be32::from_syscall(wide)).unwrap(),
+            Scalar::from_u64(5),
+            Scalar::ZERO,
+        ];
+        let proof = forge_proof_fr(&vk, &inputs);
+        assert_eq!(verify_groth16_fr(&vk, &proof, &inputs), Ok(()));
+
+        // The high limbs are bound: the input's low 64 bits alone fail
+        let truncated = [Scalar::from_u64(7), inputs[1], inputs[2]];
+        assert_eq!(
+            verify_groth16_fr(&vk, &proof, &truncated),
+            Err(ProgramError::InvalidArgument)
+        );
+        assert_eq!(
+            verify_groth16_fr(&vk, &proof, &inputs[..2]),
+            Err(VerifierError::PublicInputLayoutMismatch.into())
+        );
+
+        // On u64 values the full-width path agrees with the scalar one
+        let small = [1000000u64, 1, 0];
+        assert_eq!(
+            compute_public_input_point_generic(&ic, &small.map(Scalar::from_u64)),
+            compute_public_input_point(&ic, &small)
+        );
+    }
+
+    /// `point` times the big-endian `scalar` by double-and-add, without the
+    /// multiplication syscall
+    fn mul_by_doubling(point: &G1Point, scalar: &[u8; 32]) -> G1Point {
+        let mut result = G1Point::IDENTITY;
+        for byte in scalar {
+            for bit in (0..8).rev() {
+                result = result.add(&result).unwrap();
+                if (byte >> bit) & 1 == 1 {
+                    result = result.add(point).unwrap();
+                }
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn test_u64_inputs_multiply_big_endian() {
+        // `add_input_term` used to pack the u64 little-endian into the
+        // multiplier, so IC * 1700000000 came out as IC * (that value with
+        // its bytes reversed into the high limb)
+        let ic = distinct_ic(3);
+        let inputs = [1700000000u64, 0x0102_0304_0506_0708];
+        let mut expected = ic[0];
+        for (point, input) in ic[1..].iter().zip(inputs) {
+            let mut scalar = [0u8; 32];
+            scalar[24..].copy_from_slice(&input.to_be_bytes());
+            expected = expected.add(&mul_by_doubling(point, &scalar)).unwrap();
+        }
+        assert_eq!(compute_public_input_point(&ic, &inputs), Ok(expected));
+
+        let mut little_endian = ic[0];
+        for (point, input) in ic[1..].iter().zip(inputs) {
+            let mut scalar = [0u8; 32];
+            scalar[..8].copy_from_slice(&input.to_le_bytes());
+            little_endian = little_endian.add(&mul_by_doubling(point, &scalar)).unwrap();
+        }
+        assert_ne!(expected, little_endian);
+    }
+
+    #[test]
+    fn test_fixture_proof_pairing_input_bit_identical() {
+        // Every byte of the wide input is nonzero in a different limb
+        // position, so a scalar packed in the wrong order or width moves the
+        // public input point
+        let mut wide = [0u8; 32];
+        wide[0] = 0x20;
+        wide[13] = 0xa5;
+        wide[24] = 0x3c;
+        wide[31] = 7;
+        let inputs = [
+            Scalar::from_canonical(Be32::from_syscall(wide)).unwrap(),
+            Scalar::from_u64(1 << 40),
+            Scalar::ONE,
+            Scalar::ZERO,
+            Scalar::from_u64(1700000000),
+        ];
+        let ic = distinct_ic(6);
+        let vk = trapdoor_key(&ic);
+        let proof = forge_proof_fr(&vk, &inputs);
+
+        let mut input_point = ic[0];
+        for (point, input) in ic[1..].iter().zip(&inputs) {
+            input_point = input_point.add(&mul_by_doubling(point, &input.to_syscall())).unwrap();
+        }
+        assert_eq!(compute_public_input_point_generic(&ic, &inputs), Ok(input_point));
+
+        // The stack buffer holds what the per-pair Vecs it replaced held
+        let mut expected = Vec::new();
+        for (g1, g2) in [
+            (proof.a, proof.b),
+            (input_point.neg(), vk.gamma_g2),
+            (proof.c.neg(), vk.delta_g2),
+            (vk.alpha_g1.neg(), vk.beta_g2),
+        ] {
+            expected.extend_from_slice(&g1.to_bytes());
+            expected.extend_from_slice(&g2.to_bytes());
+        }
+        let pairing = groth16_pairing(&vk, &vk.alpha_g1.neg(), &proof, &input_point).unwrap();
+        assert_eq!(pairing.input(), &expected[..]);
+        assert_eq!(pairing.check(), Ok(true));
+        assert_eq!(verify_groth16_fr(&vk, &proof, &inputs), Ok(()));
+    }
+
+    #[test]
+    fn test_previous_key_accepted_during_window() {
+        let current_ic = distinct_ic(6);
+        let previous_ic = distinct_ic(7)[1..].to_vec();
+        let current = trapdoor_key(&current_ic);
+        let previous = trapdoor_key(&previous_ic);
+        let inputs = v1_inputs().to_scalars();
+        let current_proof = forge_proof(&current, &inputs);
+        let previous_proof = forge_proof(&previous, &inputs);
+        assert_eq!(
+            verify_groth16(&current, &previous_proof, &inputs),
+            Err(ProgramError::InvalidArgument)
+        );
+
+        let prepared = PreparedVk {
+            circuit: CircuitId::Payment,
+            vk_hash: current.hash(),
+            neg_alpha_g1: current.alpha_g1.neg(),
+            alpha_beta: [0u8; 384],
+            bump: 255,
+            previous_vk_hash: previous.hash(),
+            deprecated_until_slot: 1000,
+            revoked_vk_hash: [0u8; 32],
+            hash_backend: HashBackend::Keccak256,
+        };
+        // The previous key as `verify_payment_statement` selects it at `slot`
+        let verify = |proof: &Groth16Proof, slot: u64| {
+            let window = Some(&previous).filter(|key| prepared.accepts_previous_key(key, slot));
+            verify_groth16_with_fallback(&current, &prepared.neg_alpha_g1, window, proof, &inputs)
+                .map(|key| key.map(|key| key.hash()))
+        };
+
+        // Inside the window each key verifies its own proofs, and the
+        // previous one is reported
+        assert_eq!(verify(&current_proof, 1000), Ok(None));
+        assert_eq!(verify(&previous_proof, 1000), Ok(Some(previous.hash())));
+
+        // After it only the current key is left
+        assert_eq!(verify(&current_proof, 1001), Ok(None));
+        assert_eq!(verify(&previous_proof, 1001), Err(ProgramError::InvalidArgument));
+
+        // Failures that do not depend on the key are not retried
+        let mut non_canonical = previous_proof.clone();
+        non_canonical.a = G1Point::new([0xff; 64]);
+        assert_eq!(
+            verify(&non_canonical, 1000),
+            Err(VerifierError::NonCanonicalEncoding.into())
+        );
+    }
+
+    #[test]
+    fn test_rerandomized_proofs_hash_the_same() {
+        let ic = distinct_ic(6);
+        let vk = trapdoor_key(&ic);
+        let statement = v1_inputs();
+        let inputs = statement.to_scalars();
+        let g = g1_generator();
+
+        // (-A, -B, C), as e(-A, -B) = e(A, B)
+        let proof = forge_proof(&vk, &inputs);
+        let negated = Groth16Proof {
+            a: proof.a.neg(),
+            b: proof.b.neg(),
+            c: proof.c,
+        };
+        // (r * A, B / r, C) with r = 2, from B = 2 * H
+        let doubled_b = forge_proof_with_b(&vk, &inputs, 2);
+        let rescaled = Groth16Proof {
+            a: g.mul(&Scalar::from_u64(2)).unwrap(),
+            b: G2Point::new(G2_GENERATOR),
+            c: doubled_b.c,
+        };
+        // (A, B + s * delta, C + s * A) with s = 1, as delta = H
+        let single_b = forge_proof_with_b(&vk, &inputs, 1);
+        let shifted = Groth16Proof {
+            a: single_b.a,
+            b: G2Point::new(G2_DOUBLE),
+            c: single_b.c.add(&single_b.a).unwrap(),
+        };
+
+        // Every encoding verifies, so every one must name the one receipt
+        for (original, twin) in [
+            (&proof, &negated),
+            (&doubled_b, &rescaled),
+            (&single_b, &shifted),
+        ] {
+            assert_eq!(verify_groth16(&vk, original, &inputs), Ok(()));
+            assert_eq!(verify_groth16(&vk, twin, &inputs), Ok(()));
+            assert_ne!(original.try_to_vec().unwrap(), twin.try_to_vec().unwrap());
+            assert_eq!(original.hash(&statement), twin.hash(&statement));
+            assert_eq!(twin.hash(&statement), statement.statement_hash());
+        }
+    }
+
+    #[test]
+    fn test_invalid_key_points_refused() {
+        let ic = distinct_ic(6);
+        let vk = trapdoor_key(&ic);
+        let inputs = v1_inputs().to_scalars();
+        let proof = forge_proof(&vk, &inputs);
+        assert_eq!(vk.validate(), Ok(()));
+        assert_eq!(verify_groth16(&vk, &proof, &inputs), Ok(()));
+        let invalid = ProgramError::from(VerifierError::InvalidVerificationKey);
+
+        // One IC point zeroed, as the placeholder keys have them; the pairing
+        // alone would accept a proof forged against it
+        let mut zeroed_ic = ic.clone();
+        zeroed_ic[3] = G1Point::IDENTITY;
+        let zeroed = trapdoor_key(&zeroed_ic);
+        let forged = forge_proof(&zeroed, &inputs);
+        assert_eq!(zeroed.validate(), Err(VerifierError::InvalidVerificationKey));
+        assert_eq!(verify_groth16(&zeroed, &forged, &inputs), Err(invalid.clone()));
+
+        // gamma off the twist, zeroed, or with x.c1 = p, which reduces to the
+        // identity
+        let mut gamma = G2_GENERATOR;
+        gamma[127] ^= 1;
+        let mut near_identity = [0u8; 128];
+        near_identity[..32].copy_from_slice(&field::FQ_MODULUS);
+        for gamma in [gamma, [0u8; 128], near_identity] {
+            let mut corrupted = trapdoor_key(&ic);
+            corrupted.gamma_g2 = G2Point::new(gamma);
+            assert_eq!(corrupted.validate(), Err(VerifierError::InvalidVerificationKey));
+            assert_eq!(verify_groth16(&corrupted, &proof, &inputs), Err(invalid.clone()));
+        }
+
+        // Within one `ValidatedKeys` a key is checked once, and a failing key
+        // is not remembered
+        let mut corrupted = trapdoor_key(&ic);
+        corrupted.gamma_g2 = G2Point::new([0u8; 128]);
+        let mut keys = ValidatedKeys::default();
+        for _ in 0..2 {
+            assert_eq!(verify_groth16_with_keys(&mut keys, &vk, &proof, &inputs), Ok(()));
+            assert_eq!(keys.keys.len(), 1);
+        }
+        assert_eq!(
+            verify_groth16_with_keys(&mut keys, &corrupted, &proof, &inputs),
+            Err(invalid)
+        );
+        assert_eq!(keys.keys.len(), 1);
+    }
+
+    #[test]
+    fn test_verify_with_opening_hides_or_reveals_amount() {
+        use commitment::{amount_commitment, verify_with_opening, AmountOpening};
+
+        let ic = distinct_ic(10);
+        let vk = trapdoor_key(&ic);
+        let opening = AmountOpening {
+            amount: 1500000,
+            blinding: Be32::from_u64(42).to_syscall(),
+        };
+        let public_inputs = CommittedPaymentPublicInputs {
+            min_amount: 1000000,
+            recipient_pubkey: [4u8; 32],
+            max_block_age: 60,
+            current_time: 1700000000,
+            amount_commitment: amount_commitment(opening.amount, &opening.blinding).unwrap(),
+        };
+        let proof = forge_proof(&vk, &public_inputs.to_scalars());
+
+        // The hiding path records no amount, the revealing path the opened one
+        assert_eq!(verify_with_opening(&vk, &proof, &public_inputs, None), Ok(0));
+        assert_eq!(
+            verify_with_opening(&vk, &proof, &public_inputs, Some(&opening)),
+            Ok(1500000)
+        );
+
+        // A valid proof does not make a wrong opening pass
+        let overstated = AmountOpening {
+            amount: 2000000,
+            ..opening.clone()
+        };
+        assert_eq!(
+            verify_with_opening(&vk, &proof, &public_inputs, Some(&overstated)),
+            Err(VerifierError::CommitmentMismatch.into())
+        );
+
+        // Nor does a right opening rescue a proof of another commitment
+        let other = CommittedPaymentPublicInputs {
+            amount_commitment: amount_commitment(2000000, &opening.blinding).unwrap(),
+            ..public_inputs.clone()
+        };
+        let other_proof = forge_proof(&vk, &other.to_scalars());
+        assert_eq!(
+            verify_with_opening(&vk, &other_proof, &public_inputs, Some(&opening)),
+            Err(ProgramError::InvalidArgument)
+        );
     }
 }
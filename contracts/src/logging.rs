@@ -0,0 +1,39 @@
+// Logging of values taken from a proof or its statement
+// Amounts, times and epochs in transaction logs are business metadata that
+// anyone scraping the cluster can read. The program logs such values only
+// through `log_statement!`. With the `privacy-strict` feature every expansion
+// of it fails a const assertion, so each call site is compiled out of strict
+// builds with `#[cfg(not(feature = "privacy-strict"))]`, and a new call site
+// that is not breaks the strict build instead of leaking.
+//
+// Values logged with `msg!` or `sol_log_64` directly are not caught: never
+// log a proof, a public input or anything derived from one except through
+// this macro. Events are the program's interface and are not covered.
+
+use solana_program::log::sol_log_64;
+
+/// Whether this build refuses to log statement values
+pub const PRIVACY_STRICT: bool = cfg!(feature = "privacy-strict");
+
+/// Log `message`, then up to five u64 values from a proof or its statement
+///
+/// A compile error in `privacy-strict` builds; see the module comment.
+#[cfg_attr(feature = "privacy-strict", allow(unused_macros))]
+macro_rules! log_statement {
+    ($message:expr, $($value:expr),+ $(,)?) => {{
+        const _: () = assert!(
+            !$crate::logging::PRIVACY_STRICT,
+            "privacy-strict builds must not log proof or public input values"
+        );
+        ::solana_program::msg!($message);
+        $crate::logging::log_values(&[$($value),+]);
+    }};
+}
+
+/// `sol_log_64` with the unused slots zeroed
+#[doc(hidden)]
+pub fn log_values(values: &[u64]) {
+    let mut slots = [0u64; 5];
+    slots[..values.len()].copy_from_slice(values);
+    sol_log_64(slots[0], slots[1], slots[2], slots[3], slots[4]);
+}
@@ -0,0 +1,441 @@
+// Incremental Merkle tree of shielded pool commitments
+// An append-only binary tree of fixed depth whose nodes are Poseidon(left,
+// right) over BN254 with circomlib's parameters, so roots match the ones the
+// spend circuit recomputes. Only the rightmost filled node of each level is
+// kept: an append costs DEPTH hashes and the account never grows. Empty leaves
+// are zero, and an empty subtree of height h hashes to `ZERO_HASHES[h]`.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::poseidon::{hashv, Endianness, Parameters};
+
+use crate::{field::is_canonical_fr, VerifierError};
+
+/// Depth of the shielded pool's tree, room for 2^20 deposits
+pub const MERKLE_TREE_DEPTH: usize = 20;
+
+/// Recent roots kept so proofs built against a slightly stale tree still
+/// verify, unless the pool was created with another size
+pub const ROOT_HISTORY_SIZE: usize = 30;
+
+/// Largest root history a pool may keep; the account always has room for it
+pub const MAX_ROOT_HISTORY_SIZE: usize = 64;
+
+/// Root of an empty subtree of each height up to `MERKLE_TREE_DEPTH`
+///
+/// `ZERO_HASHES[0]` is the empty leaf and `ZERO_HASHES[h + 1]` is
+/// Poseidon(`ZERO_HASHES[h]`, `ZERO_HASHES[h]`), big-endian.
+pub const ZERO_HASHES: [[u8; 32]; MERKLE_TREE_DEPTH + 1] = [
+    [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ],
+    [
+        0x20, 0x98, 0xf5, 0xfb, 0x9e, 0x23, 0x9e, 0xab,
+        0x3c, 0xea, 0xc3, 0xf2, 0x7b, 0x81, 0xe4, 0x81,
+        0xdc, 0x31, 0x24, 0xd5, 0x5f, 0xfe, 0xd5, 0x23,
+        0xa8, 0x39, 0xee, 0x84, 0x46, 0xb6, 0x48, 0x64,
+    ],
+    [
+        0x10, 0x69, 0x67, 0x3d, 0xcd, 0xb1, 0x22, 0x63,
+        0xdf, 0x30, 0x1a, 0x6f, 0xf5, 0x84, 0xa7, 0xec,
+        0x26, 0x1a, 0x44, 0xcb, 0x9d, 0xc6, 0x8d, 0xf0,
+        0x67, 0xa4, 0x77, 0x44, 0x60, 0xb1, 0xf1, 0xe1,
+    ],
+    [
+        0x18, 0xf4, 0x33, 0x31, 0x53, 0x7e, 0xe2, 0xaf,
+        0x2e, 0x3d, 0x75, 0x8d, 0x50, 0xf7, 0x21, 0x06,
+        0x46, 0x7c, 0x6e, 0xea, 0x50, 0x37, 0x1d, 0xd5,
+        0x28, 0xd5, 0x7e, 0xb2, 0xb8, 0x56, 0xd2, 0x38,
+    ],
+    [
+        0x07, 0xf9, 0xd8, 0x37, 0xcb, 0x17, 0xb0, 0xd3,
+        0x63, 0x20, 0xff, 0xe9, 0x3b, 0xa5, 0x23, 0x45,
+        0xf1, 0xb7, 0x28, 0x57, 0x1a, 0x56, 0x82, 0x65,
+        0xca, 0xac, 0x97, 0x55, 0x9d, 0xbc, 0x95, 0x2a,
+    ],
+    [
+        0x2b, 0x94, 0xcf, 0x5e, 0x87, 0x46, 0xb3, 0xf5,
+        0xc9, 0x63, 0x1f, 0x4c, 0x5d, 0xf3, 0x29, 0x07,
+        0xa6, 0x99, 0xc5, 0x8c, 0x94, 0xb2, 0xad, 0x4d,
+        0x7b, 0x5c, 0xec, 0x16, 0x39, 0x18, 0x3f, 0x55,
+    ],
+    [
+        0x2d, 0xee, 0x93, 0xc5, 0xa6, 0x66, 0x45, 0x96,
+        0x46, 0xea, 0x7d, 0x22, 0xcc, 0xa9, 0xe1, 0xbc,
+        0xfe, 0xd7, 0x1e, 0x69, 0x51, 0xb9, 0x53, 0x61,
+        0x1d, 0x11, 0xdd, 0xa3, 0x2e, 0xa0, 0x9d, 0x78,
+    ],
+    [
+        0x07, 0x82, 0x95, 0xe5, 0xa2, 0x2b, 0x84, 0xe9,
+        0x82, 0xcf, 0x60, 0x1e, 0xb6, 0x39, 0x59, 0x7b,
+        0x8b, 0x05, 0x15, 0xa8, 0x8c, 0xb5, 0xac, 0x7f,
+        0xa8, 0xa4, 0xaa, 0xbe, 0x3c, 0x87, 0x34, 0x9d,
+    ],
+    [
+        0x2f, 0xa5, 0xe5, 0xf1, 0x8f, 0x60, 0x27, 0xa6,
+        0x50, 0x1b, 0xec, 0x86, 0x45, 0x64, 0x47, 0x2a,
+        0x61, 0x6b, 0x2e, 0x27, 0x4a, 0x41, 0x21, 0x1a,
+        0x44, 0x4c, 0xbe, 0x3a, 0x99, 0xf3, 0xcc, 0x61,
+    ],
+    [
+        0x0e, 0x88, 0x43, 0x76, 0xd0, 0xd8, 0xfd, 0x21,
+        0xec, 0xb7, 0x80, 0x38, 0x9e, 0x94, 0x1f, 0x66,
+        0xe4, 0x5e, 0x7a, 0xcc, 0xe3, 0xe2, 0x28, 0xab,
+        0x3e, 0x21, 0x56, 0xa6, 0x14, 0xfc, 0xd7, 0x47,
+    ],
+    [
+        0x1b, 0x72, 0x01, 0xda, 0x72, 0x49, 0x4f, 0x1e,
+        0x28, 0x71, 0x7a, 0xd1, 0xa5, 0x2e, 0xb4, 0x69,
+        0xf9, 0x58, 0x92, 0xf9, 0x57, 0x71, 0x35, 0x33,
+        0xde, 0x61, 0x75, 0xe5, 0xda, 0x19, 0x0a, 0xf2,
+    ],
+    [
+        0x1f, 0x8d, 0x88, 0x22, 0x72, 0x5e, 0x36, 0x38,
+        0x52, 0x00, 0xc0, 0xb2, 0x01, 0x24, 0x98, 0x19,
+        0xa6, 0xe6, 0xe1, 0xe4, 0x65, 0x08, 0x08, 0xb5,
+        0xbe, 0xbc, 0x6b, 0xfa, 0xce, 0x7d, 0x76, 0x36,
+    ],
+    [
+        0x2c, 0x5d, 0x82, 0xf6, 0x6c, 0x91, 0x4b, 0xaf,
+        0xb9, 0x70, 0x15, 0x89, 0xba, 0x8c, 0xfc, 0xfb,
+        0x61, 0x62, 0xb0, 0xa1, 0x2a, 0xcf, 0x88, 0xa8,
+        0xd0, 0x87, 0x9a, 0x04, 0x71, 0xb5, 0xf8, 0x5a,
+    ],
+    [
+        0x14, 0xc5, 0x41, 0x48, 0xa0, 0x94, 0x0b, 0xb8,
+        0x20, 0x95, 0x7f, 0x5a, 0xdf, 0x3f, 0xa1, 0x13,
+        0x4e, 0xf5, 0xc4, 0xaa, 0xa1, 0x13, 0xf4, 0x64,
+        0x64, 0x58, 0xf2, 0x70, 0xe0, 0xbf, 0xbf, 0xd0,
+    ],
+    [
+        0x19, 0x0d, 0x33, 0xb1, 0x2f, 0x98, 0x6f, 0x96,
+        0x1e, 0x10, 0xc0, 0xee, 0x44, 0xd8, 0xb9, 0xaf,
+        0x11, 0xbe, 0x25, 0x58, 0x8c, 0xad, 0x89, 0xd4,
+        0x16, 0x11, 0x8e, 0x4b, 0xf4, 0xeb, 0xe8, 0x0c,
+    ],
+    [
+        0x22, 0xf9, 0x8a, 0xa9, 0xce, 0x70, 0x41, 0x52,
+        0xac, 0x17, 0x35, 0x49, 0x14, 0xad, 0x73, 0xed,
+        0x11, 0x67, 0xae, 0x65, 0x96, 0xaf, 0x51, 0x0a,
+        0xa5, 0xb3, 0x64, 0x93, 0x25, 0xe0, 0x6c, 0x92,
+    ],
+    [
+        0x2a, 0x7c, 0x7c, 0x9b, 0x6c, 0xe5, 0x88, 0x0b,
+        0x9f, 0x6f, 0x22, 0x8d, 0x72, 0xbf, 0x6a, 0x57,
+        0x5a, 0x52, 0x6f, 0x29, 0xc6, 0x6e, 0xcc, 0xee,
+        0xf8, 0xb7, 0x53, 0xd3, 0x8b, 0xba, 0x73, 0x23,
+    ],
+    [
+        0x2e, 0x81, 0x86, 0xe5, 0x58, 0x69, 0x8e, 0xc1,
+        0xc6, 0x7a, 0xf9, 0xc1, 0x4d, 0x46, 0x3f, 0xfc,
+        0x47, 0x00, 0x43, 0xc9, 0xc2, 0x98, 0x8b, 0x95,
+        0x4d, 0x75, 0xdd, 0x64, 0x3f, 0x36, 0xb9, 0x92,
+    ],
+    [
+        0x0f, 0x57, 0xc5, 0x57, 0x1e, 0x9a, 0x4e, 0xab,
+        0x49, 0xe2, 0xc8, 0xcf, 0x05, 0x0d, 0xae, 0x94,
+        0x8a, 0xef, 0x6e, 0xad, 0x64, 0x73, 0x92, 0x27,
+        0x35, 0x46, 0x24, 0x9d, 0x1c, 0x1f, 0xf1, 0x0f,
+    ],
+    [
+        0x18, 0x30, 0xee, 0x67, 0xb5, 0xfb, 0x55, 0x4a,
+        0xd5, 0xf6, 0x3d, 0x43, 0x88, 0x80, 0x0e, 0x1c,
+        0xfe, 0x78, 0xe3, 0x10, 0x69, 0x7d, 0x46, 0xe4,
+        0x3c, 0x9c, 0xe3, 0x61, 0x34, 0xf7, 0x2c, 0xca,
+    ],
+    [
+        0x21, 0x34, 0xe7, 0x6a, 0xc5, 0xd2, 0x1a, 0xab,
+        0x18, 0x6c, 0x2b, 0xe1, 0xdd, 0x8f, 0x84, 0xee,
+        0x88, 0x0a, 0x1e, 0x46, 0xea, 0xf7, 0x12, 0xf9,
+        0xd3, 0x71, 0xb6, 0xdf, 0x22, 0x19, 0x1f, 0x3e,
+    ],
+];
+
+/// Poseidon hash of two big-endian scalars, circomlib's `Poseidon(2)`
+pub fn poseidon(left: &[u8; 32], right: &[u8; 32]) -> Result<[u8; 32], VerifierError> {
+    hashv(Parameters::Bn254X5, Endianness::BigEndian, &[left, right])
+        .map(|hash| hash.to_bytes())
+        .map_err(|_| VerifierError::PoseidonHashFailed)
+}
+
+/// Append-only Merkle tree of `DEPTH` levels, at most `MERKLE_TREE_DEPTH`
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MerkleTree<const DEPTH: usize> {
+    /// Index the next leaf is written at
+    pub next_index: u64,
+    /// Left-hand node at each level of the path of the next leaf
+    pub filled_subtrees: [[u8; 32]; DEPTH],
+    /// Ring of the last `root_history_size` roots, with the current one at
+    /// `root_index`; slots past `root_history_size` stay zero
+    pub roots: [[u8; 32]; MAX_ROOT_HISTORY_SIZE],
+    pub root_index: u32,
+    pub root_history_size: u32,
+}
+
+impl<const DEPTH: usize> MerkleTree<DEPTH> {
+    pub const LEN: usize = 8 + 32 * DEPTH + 32 * MAX_ROOT_HISTORY_SIZE + 4 + 4;
+
+    /// Leaves the tree holds when full
+    pub const CAPACITY: u64 = 1 << DEPTH;
+
+    /// An empty tree keeping `ROOT_HISTORY_SIZE` roots
+    pub fn new() -> Self {
+        Self::with_root_history(ROOT_HISTORY_SIZE as u32)
+            .expect("ROOT_HISTORY_SIZE is within bounds")
+    }
+
+    /// An empty tree keeping the last `root_history_size` roots, from 1 to
+    /// `MAX_ROOT_HISTORY_SIZE`
+    pub fn with_root_history(root_history_size: u32) -> Result<Self, VerifierError> {
+        if root_history_size == 0 || root_history_size as usize > MAX_ROOT_HISTORY_SIZE {
+            return Err(VerifierError::InvalidRootHistorySize);
+        }
+        let mut filled_subtrees = [[0u8; 32]; DEPTH];
+        filled_subtrees.copy_from_slice(&ZERO_HASHES[..DEPTH]);
+        let mut roots = [[0u8; 32]; MAX_ROOT_HISTORY_SIZE];
+        roots[0] = ZERO_HASHES[DEPTH];
+        Ok(Self {
+            next_index: 0,
+            filled_subtrees,
+            roots,
+            root_index: 0,
+            root_history_size,
+        })
+    }
+
+    /// Current root
+    pub fn root(&self) -> [u8; 32] {
+        self.roots[self.root_index as usize % MAX_ROOT_HISTORY_SIZE]
+    }
+
+    /// Whether `root` is the current root or one of the `root_history_size - 1`
+    /// before it
+    ///
+    /// Zero is never known: it fills the history slots no root has reached yet.
+    pub fn is_known_root(&self, root: &[u8; 32]) -> bool {
+        let history = (self.root_history_size as usize).min(MAX_ROOT_HISTORY_SIZE);
+        *root != [0u8; 32] && self.roots[..history].contains(root)
+    }
+
+    /// Append `leaf` and return its index
+    ///
+    /// The leaf must be a nonzero canonical scalar: zero is the empty leaf, and
+    /// the circuit could not reproduce a hash over a non-canonical one.
+    pub fn append(&mut self, leaf: [u8; 32]) -> Result<u64, VerifierError> {
+        if leaf == [0u8; 32] || !is_canonical_fr(&leaf) {
+            return Err(VerifierError::InvalidCommitment);
+        }
+        if self.next_index >= Self::CAPACITY {
+            return Err(VerifierError::MerkleTreeFull);
+        }
+
+        let index = self.next_index;
+        let mut node = leaf;
+        for (level, filled) in self.filled_subtrees.iter_mut().enumerate() {
+            node = if (index >> level) & 1 == 0 {
+                // Left child: remember it for its right sibling, which is still empty
+                *filled = node;
+                poseidon(&node, &ZERO_HASHES[level])?
+            } else {
+                poseidon(filled, &node)?
+            };
+        }
+
+        // A size out of bounds could only come from a corrupt account; clamp
+        // rather than index out of the ring
+        let history = (self.root_history_size as usize).clamp(1, MAX_ROOT_HISTORY_SIZE);
+        self.root_index = ((self.root_index as usize + 1) % history) as u32;
+        self.roots[self.root_index as usize] = node;
+        self.next_index = index + 1;
+        Ok(index)
+    }
+}
+
+impl<const DEPTH: usize> Default for MerkleTree<DEPTH> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scalar(n: u64) -> [u8; 32] {
+        let mut bytes = [0u8; 32];
+        bytes[24..].copy_from_slice(&n.to_be_bytes());
+        bytes
+    }
+
+    /// Root of `leaves` padded with empty leaves, hashed level by level
+    fn naive_root(leaves: &[[u8; 32]], depth: usize) -> [u8; 32] {
+        let mut level = leaves.to_vec();
+        level.resize(1 << depth, [0u8; 32]);
+        while level.len() > 1 {
+            level = level
+                .chunks(2)
+                .map(|pair| poseidon(&pair[0], &pair[1]).unwrap())
+                .collect();
+        }
+        level[0]
+    }
+
+    #[test]
+    fn test_poseidon_matches_circomlib() {
+        // circomlibjs poseidon([1, 2])
+        let expected = [
+            0x11, 0x5c, 0xc0, 0xf5, 0xe7, 0xd6, 0x90, 0x41,
+            0x3d, 0xf6, 0x4c, 0x6b, 0x96, 0x62, 0xe9, 0xcf,
+            0x2a, 0x36, 0x17, 0xf2, 0x74, 0x32, 0x45, 0x51,
+            0x9e, 0x19, 0x60, 0x7a, 0x44, 0x17, 0x18, 0x9a,
+        ];
+        assert_eq!(poseidon(&scalar(1), &scalar(2)), Ok(expected));
+
+        // Order matters: the circuit hashes (left, right)
+        assert_ne!(poseidon(&scalar(2), &scalar(1)), Ok(expected));
+
+        // Inputs at or above r are refused rather than reduced
+        assert_eq!(
+            poseidon(&crate::field::FR_MODULUS, &scalar(1)),
+            Err(VerifierError::PoseidonHashFailed)
+        );
+    }
+
+    #[test]
+    fn test_zero_hashes() {
+        assert_eq!(ZERO_HASHES[0], [0u8; 32]);
+        for height in 0..MERKLE_TREE_DEPTH {
+            assert_eq!(
+                poseidon(&ZERO_HASHES[height], &ZERO_HASHES[height]),
+                Ok(ZERO_HASHES[height + 1]),
+                "height {}",
+                height
+            );
+        }
+        assert_eq!(MerkleTree::<MERKLE_TREE_DEPTH>::new().root(), ZERO_HASHES[MERKLE_TREE_DEPTH]);
+    }
+
+    #[test]
+    fn test_append_matches_full_recomputation() {
+        let mut tree = MerkleTree::<4>::new();
+        let mut leaves = Vec::new();
+        for n in 1..=11 {
+            assert_eq!(tree.append(scalar(n)), Ok(n - 1));
+            leaves.push(scalar(n));
+            assert_eq!(tree.root(), naive_root(&leaves, 4), "{} leaves", n);
+        }
+        assert_eq!(tree.next_index, 11);
+
+        // At full depth a lone leaf is hashed against every empty subtree
+        let mut tree = MerkleTree::<MERKLE_TREE_DEPTH>::new();
+        tree.append(scalar(1)).unwrap();
+        let mut node = scalar(1);
+        for zero in &ZERO_HASHES[..MERKLE_TREE_DEPTH] {
+            node = poseidon(&node, zero).unwrap();
+        }
+        assert_eq!(tree.root(), node);
+    }
+
+    #[test]
+    fn test_full_tree_rejects_append() {
+        let mut tree = MerkleTree::<3>::new();
+        for n in 1..=8 {
+            tree.append(scalar(n)).unwrap();
+        }
+        let full = tree.clone();
+        assert_eq!(tree.append(scalar(9)), Err(VerifierError::MerkleTreeFull));
+        assert_eq!(tree, full);
+        assert_eq!(tree.root(), naive_root(&(1..=8).map(scalar).collect::<Vec<_>>(), 3));
+    }
+
+    #[test]
+    fn test_invalid_commitments_rejected() {
+        let mut tree = MerkleTree::<3>::new();
+        let empty = tree.clone();
+        assert_eq!(tree.append([0u8; 32]), Err(VerifierError::InvalidCommitment));
+        assert_eq!(
+            tree.append(crate::field::FR_MODULUS),
+            Err(VerifierError::InvalidCommitment)
+        );
+        assert_eq!(tree, empty);
+    }
+
+    #[test]
+    fn test_recent_roots_known() {
+        let mut tree = MerkleTree::<6>::new();
+        let empty_root = tree.root();
+        assert!(tree.is_known_root(&empty_root));
+        assert!(!tree.is_known_root(&[0u8; 32]));
+
+        tree.append(scalar(1)).unwrap();
+        let first = tree.root();
+        for n in 2..ROOT_HISTORY_SIZE as u64 {
+            tree.append(scalar(n)).unwrap();
+        }
+        // `first` is now ROOT_HISTORY_SIZE - 2 appends old; the empty root one more
+        assert!(tree.is_known_root(&first));
+        assert!(tree.is_known_root(&empty_root));
+
+        tree.append(scalar(ROOT_HISTORY_SIZE as u64)).unwrap();
+        assert!(tree.is_known_root(&first));
+        assert!(!tree.is_known_root(&empty_root));
+
+        // Exactly ROOT_HISTORY_SIZE appends after `first` it has left the history
+        tree.append(scalar(ROOT_HISTORY_SIZE as u64 + 1)).unwrap();
+        assert!(!tree.is_known_root(&first));
+        assert!(tree.is_known_root(&tree.root()));
+    }
+
+    #[test]
+    fn test_root_history_wraps() {
+        let mut tree = MerkleTree::<4>::with_root_history(4).unwrap();
+        let mut roots = vec![tree.root()];
+        for n in 1..=10 {
+            tree.append(scalar(n)).unwrap();
+            roots.push(tree.root());
+            assert_eq!(tree.root_index, n as u32 % 4);
+        }
+
+        // Only the last four roots are known, and slots past the ring stay empty
+        for (age, root) in roots.iter().rev().enumerate() {
+            assert_eq!(tree.is_known_root(root), age < 4, "root {} appends old", age);
+        }
+        assert!(tree.roots[4..].iter().all(|slot| *slot == [0u8; 32]));
+        assert!(!tree.is_known_root(&[0u8; 32]));
+
+        // A one-root history only knows the current root
+        let mut tree = MerkleTree::<4>::with_root_history(1).unwrap();
+        let empty_root = tree.root();
+        tree.append(scalar(1)).unwrap();
+        assert_eq!(tree.root_index, 0);
+        assert!(tree.is_known_root(&tree.root()));
+        assert!(!tree.is_known_root(&empty_root));
+    }
+
+    #[test]
+    fn test_root_history_size_bounded() {
+        assert_eq!(
+            MerkleTree::<4>::with_root_history(0),
+            Err(VerifierError::InvalidRootHistorySize)
+        );
+        assert_eq!(
+            MerkleTree::<4>::with_root_history(MAX_ROOT_HISTORY_SIZE as u32 + 1),
+            Err(VerifierError::InvalidRootHistorySize)
+        );
+
+        // Unused slots of the largest history never match the zero root
+        let mut tree = MerkleTree::<4>::with_root_history(MAX_ROOT_HISTORY_SIZE as u32).unwrap();
+        tree.append(scalar(1)).unwrap();
+        assert!(!tree.is_known_root(&[0u8; 32]));
+        assert_eq!(MerkleTree::<4>::new().root_history_size, ROOT_HISTORY_SIZE as u32);
+    }
+
+    #[test]
+    fn test_layout() {
+        let tree = MerkleTree::<MERKLE_TREE_DEPTH>::new();
+        assert_eq!(tree.try_to_vec().unwrap().len(), MerkleTree::<MERKLE_TREE_DEPTH>::LEN);
+        assert_eq!(MerkleTree::<MERKLE_TREE_DEPTH>::LEN, 8 + 640 + 2048 + 4 + 4);
+    }
+}
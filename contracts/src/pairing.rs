@@ -0,0 +1,278 @@
+// Pairing product checks
+// `PairingBuilder` lays out the input of `alt_bn128_pairing` one (G1, G2) pair
+// at a time and checks that the product of the pairings is one. The Groth16
+// verifiers build their equations with it, and callers layering extra pairs
+// on Groth16, such as the commitment pair of LegoGroth16, can build theirs
+// the same way. `PairingVec` does the same on the heap for the batch checks,
+// whose pair count outgrows the stack buffer.
+
+use solana_program::alt_bn128::{alt_bn128_pairing, ALT_BN128_PAIRING_OUTPUT_LEN};
+
+use crate::{G1Point, G2Point, VerifierError};
+
+/// Most pairs one check takes
+pub const MAX_PAIRS: usize = 8;
+
+/// Bytes of one pair in the syscall input, G1 then G2
+pub const PAIR_LEN: usize = G1Point::LEN + G2Point::LEN;
+
+/// The pairing output when the product is one
+const PAIRING_ONE: [u8; ALT_BN128_PAIRING_OUTPUT_LEN] = {
+    let mut one = [0u8; ALT_BN128_PAIRING_OUTPUT_LEN];
+    one[ALT_BN128_PAIRING_OUTPUT_LEN - 1] = 1;
+    one
+};
+
+/// Input of a pairing product check, built one pair at a time
+#[derive(Debug, Clone)]
+pub struct PairingBuilder {
+    input: [u8; MAX_PAIRS * PAIR_LEN],
+    pairs: usize,
+}
+
+impl Default for PairingBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PairingBuilder {
+    pub const fn new() -> Self {
+        Self {
+            input: [0u8; MAX_PAIRS * PAIR_LEN],
+            pairs: 0,
+        }
+    }
+
+    /// Multiply e(g1, g2) into the product
+    ///
+    /// Fails with `TooManyPairs` once `MAX_PAIRS` pairs are held.
+    pub fn add_pair(&mut self, g1: &G1Point, g2: &G2Point) -> Result<&mut Self, VerifierError> {
+        if self.pairs == MAX_PAIRS {
+            return Err(VerifierError::TooManyPairs);
+        }
+        let pair = &mut self.input[self.pairs * PAIR_LEN..(self.pairs + 1) * PAIR_LEN];
+        pair[..G1Point::LEN].copy_from_slice(&g1.to_bytes());
+        pair[G1Point::LEN..].copy_from_slice(&g2.to_bytes());
+        self.pairs += 1;
+        Ok(self)
+    }
+
+    /// Multiply e(-g1, g2), the inverse of e(g1, g2), into the product
+    pub fn add_negated_pair(
+        &mut self,
+        g1: &G1Point,
+        g2: &G2Point,
+    ) -> Result<&mut Self, VerifierError> {
+        self.add_pair(&g1.neg(), g2)
+    }
+
+    /// Pairs added so far
+    pub fn len(&self) -> usize {
+        self.pairs
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pairs == 0
+    }
+
+    /// The syscall input: each pair as G1 || G2, in the order added
+    pub fn input(&self) -> &[u8] {
+        &self.input[..self.pairs * PAIR_LEN]
+    }
+
+    /// Whether the product of the pairings is one
+    ///
+    /// Points off their curve fail the syscall, and with it the check.
+    pub fn check(&self) -> Result<bool, VerifierError> {
+        product_is_one(self.input())
+    }
+}
+
+/// `PairingBuilder` without the pair cap, its input held in a `Vec`
+///
+/// A batch pairs each proof's A with its B beside the key's three pairs, up
+/// to `MAX_BATCH_SIZE + 3` of them; the single-proof paths keep the stack
+/// buffer.
+#[derive(Debug, Clone, Default)]
+pub struct PairingVec {
+    input: Vec<u8>,
+}
+
+impl PairingVec {
+    /// An empty check with room for `pairs` pairs
+    pub fn with_capacity(pairs: usize) -> Self {
+        Self {
+            input: Vec::with_capacity(pairs * PAIR_LEN),
+        }
+    }
+
+    /// Multiply e(g1, g2) into the product
+    pub fn add_pair(&mut self, g1: &G1Point, g2: &G2Point) -> &mut Self {
+        self.input.extend_from_slice(&g1.to_bytes());
+        self.input.extend_from_slice(&g2.to_bytes());
+        self
+    }
+
+    /// Multiply e(-g1, g2), the inverse of e(g1, g2), into the product
+    pub fn add_negated_pair(&mut self, g1: &G1Point, g2: &G2Point) -> &mut Self {
+        self.add_pair(&g1.neg(), g2)
+    }
+
+    /// Pairs added so far
+    pub fn len(&self) -> usize {
+        self.input.len() / PAIR_LEN
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.input.is_empty()
+    }
+
+    /// The syscall input: each pair as G1 || G2, in the order added
+    pub fn input(&self) -> &[u8] {
+        &self.input
+    }
+
+    /// Whether the product of the pairings is one, as `PairingBuilder::check`
+    pub fn check(&self) -> Result<bool, VerifierError> {
+        product_is_one(&self.input)
+    }
+}
+
+fn product_is_one(input: &[u8]) -> Result<bool, VerifierError> {
+    let mut output = [0u8; ALT_BN128_PAIRING_OUTPUT_LEN];
+    alt_bn128_pairing(input, &mut output)?;
+    Ok(output == PAIRING_ONE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Groth16Proof, PAYMENT_VK};
+
+    /// G2 generator in syscall order: x.c1, x.c0, y.c1, y.c0
+    const G2_GENERATOR: [u8; 128] = [
+        0x19, 0x8e, 0x93, 0x93, 0x92, 0x0d, 0x48, 0x3a,
+        0x72, 0x60, 0xbf, 0xb7, 0x31, 0xfb, 0x5d, 0x25,
+        0xf1, 0xaa, 0x49, 0x33, 0x35, 0xa9, 0xe7, 0x12,
+        0x97, 0xe4, 0x85, 0xb7, 0xae, 0xf3, 0x12, 0xc2,
+        0x18, 0x00, 0xde, 0xef, 0x12, 0x1f, 0x1e, 0x76,
+        0x42, 0x6a, 0x00, 0x66, 0x5e, 0x5c, 0x44, 0x79,
+        0x67, 0x43, 0x22, 0xd4, 0xf7, 0x5e, 0xda, 0xdd,
+        0x46, 0xde, 0xbd, 0x5c, 0xd9, 0x92, 0xf6, 0xed,
+        0x09, 0x06, 0x89, 0xd0, 0x58, 0x5f, 0xf0, 0x75,
+        0xec, 0x9e, 0x99, 0xad, 0x69, 0x0c, 0x33, 0x95,
+        0xbc, 0x4b, 0x31, 0x33, 0x70, 0xb3, 0x8e, 0xf3,
+        0x55, 0xac, 0xda, 0xdc, 0xd1, 0x22, 0x97, 0x5b,
+        0x12, 0xc8, 0x5e, 0xa5, 0xdb, 0x8c, 0x6d, 0xeb,
+        0x4a, 0xab, 0x71, 0x80, 0x8d, 0xcb, 0x40, 0x8f,
+        0xe3, 0xd1, 0xe7, 0x69, 0x0c, 0x43, 0xd3, 0x7b,
+        0x4c, 0xe6, 0xcc, 0x01, 0x66, 0xfa, 0x7d, 0xaa,
+    ];
+
+    fn g1_generator() -> G1Point {
+        let mut point = [0u8; 64];
+        point[31] = 1;
+        point[63] = 2;
+        G1Point::new(point)
+    }
+
+    #[test]
+    fn test_groth16_input_matches_hand_layout() {
+        let vk = PAYMENT_VK;
+        let proof = Groth16Proof {
+            a: vk.ic[1],
+            b: vk.beta_g2,
+            c: vk.ic[2],
+        };
+        let pub_input_point = vk.ic[3];
+        let neg_alpha = vk.alpha_g1.neg();
+
+        // The four pairs as the verifier laid them out before the builder
+        let pairs: [(G1Point, &G2Point); 4] = [
+            (proof.a, &proof.b),
+            (pub_input_point.neg(), &vk.gamma_g2),
+            (proof.c.neg(), &vk.delta_g2),
+            (neg_alpha, &vk.beta_g2),
+        ];
+        let mut expected = [0u8; 4 * 192];
+        for (chunk, (g1, g2)) in expected.chunks_exact_mut(192).zip(pairs) {
+            chunk[..64].copy_from_slice(&g1.to_bytes());
+            chunk[64..].copy_from_slice(&g2.to_bytes());
+        }
+
+        let mut builder = PairingBuilder::new();
+        builder
+            .add_pair(&proof.a, &proof.b)
+            .unwrap()
+            .add_negated_pair(&pub_input_point, &vk.gamma_g2)
+            .unwrap()
+            .add_negated_pair(&proof.c, &vk.delta_g2)
+            .unwrap()
+            .add_pair(&neg_alpha, &vk.beta_g2)
+            .unwrap();
+        assert_eq!(builder.len(), 4);
+        assert_eq!(builder.input(), &expected[..]);
+    }
+
+    #[test]
+    fn test_check_and_pair_cap() {
+        let g1 = g1_generator();
+        let g2 = G2Point::new(G2_GENERATOR);
+
+        // e(G, H) * e(-G, H) = 1, e(G, H) alone is not
+        let mut builder = PairingBuilder::new();
+        builder.add_pair(&g1, &g2).unwrap();
+        assert_eq!(builder.check(), Ok(false));
+        builder.add_negated_pair(&g1, &g2).unwrap();
+        assert_eq!(builder.check(), Ok(true));
+
+        // Up to MAX_PAIRS pairs, then refused without touching the input
+        while builder.len() < MAX_PAIRS {
+            builder.add_pair(&g1, &G2Point::new([0u8; 128])).unwrap();
+        }
+        let input = builder.input().to_vec();
+        assert_eq!(
+            builder.add_pair(&g1, &g2).map(|_| ()),
+            Err(VerifierError::TooManyPairs)
+        );
+        assert_eq!(builder.input(), &input[..]);
+        assert_eq!(builder.check(), Ok(true));
+
+        // A point off the curve fails the syscall
+        let mut off_curve = [0u8; 64];
+        off_curve[31] = 1;
+        off_curve[63] = 1;
+        let mut builder = PairingBuilder::new();
+        builder.add_pair(&G1Point::new(off_curve), &g2).unwrap();
+        assert_eq!(builder.check(), Err(VerifierError::AltBn128InvalidInput));
+    }
+
+    #[test]
+    fn test_pairing_vec_past_the_cap() {
+        let g1 = g1_generator();
+        let g2 = G2Point::new(G2_GENERATOR);
+
+        // Laid out as the builder lays out its first MAX_PAIRS pairs
+        let mut builder = PairingBuilder::new();
+        let mut pairs = PairingVec::with_capacity(2 * MAX_PAIRS);
+        for _ in 0..MAX_PAIRS / 2 {
+            builder
+                .add_pair(&g1, &g2)
+                .unwrap()
+                .add_negated_pair(&g1, &g2)
+                .unwrap();
+            pairs.add_pair(&g1, &g2).add_negated_pair(&g1, &g2);
+        }
+        assert_eq!(pairs.input(), builder.input());
+
+        // Twice as many pairs still cancel, and one more e(G, H) does not
+        for _ in 0..MAX_PAIRS / 2 {
+            pairs.add_pair(&g1, &g2).add_negated_pair(&g1, &g2);
+        }
+        assert_eq!(pairs.len(), 2 * MAX_PAIRS);
+        assert_eq!(pairs.check(), Ok(true));
+        pairs.add_pair(&g1, &g2);
+        assert_eq!(pairs.check(), Ok(false));
+    }
+}
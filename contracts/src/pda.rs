@@ -0,0 +1,157 @@
+// Program-derived addresses
+// Every PDA the program creates or checks is derived here, and the processors
+// derive them through these functions, so wallets and relayers predicting an
+// address run the same seed code as the program. Re-exported as `client::pda`.
+//
+// `SpentNullifier::legacy_address` stays with the account: it names
+// nullifiers spent under the old seed layout and is never created anew.
+
+use solana_program::pubkey::Pubkey;
+
+use crate::{
+    state::{
+        AUDIT_LOG_SEED, BATCH_BUFFER_SEED, BATCH_RECEIPT_SEED, CONFIG_SEED, DENIED_RECIPIENT_SEED,
+        INVOICE_SEED, NULLIFIER_SEED, NULLIFIER_SEED_VERSION, POOL_SEED, PREPARED_VK_SEED,
+        REBATE_DEPOSIT_SEED, RECEIPT_SEED, RELAYER_LIST_SEED, STATS_SEED,
+    },
+    CircuitId,
+};
+
+/// The singleton `VerifierConfig`
+pub fn config_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CONFIG_SEED], program_id)
+}
+
+/// The receipt recorded for the proof with `proof_hash`
+///
+/// Payment, split and refund receipts share the seed; `proof_hash` is
+/// `Groth16Proof::hash` over the statement the receipt records.
+pub fn receipt_address(program_id: &Pubkey, proof_hash: &[u8; 32]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[RECEIPT_SEED, proof_hash], program_id)
+}
+
+/// Per-recipient payment statistics
+pub fn stats_address(program_id: &Pubkey, recipient: &[u8; 32]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[STATS_SEED, recipient], program_id)
+}
+
+/// The marker spending `nullifier` for `circuit` in `epoch`
+///
+/// Withdrawals spend at epoch 0. The circuit id keeps a nullifier produced by
+/// one circuit from occupying another circuit's slot.
+pub fn nullifier_address(
+    program_id: &Pubkey,
+    circuit: CircuitId,
+    nullifier: &[u8; 32],
+    epoch: u64,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            NULLIFIER_SEED,
+            &[NULLIFIER_SEED_VERSION],
+            &[circuit as u8],
+            nullifier,
+            &epoch.to_le_bytes(),
+        ],
+        program_id,
+    )
+}
+
+/// The `PreparedVk` registered for `circuit`
+pub fn vkey_address(program_id: &Pubkey, circuit: CircuitId) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[PREPARED_VK_SEED, &[circuit as u8]], program_id)
+}
+
+/// The `InvoiceIndex` for `invoice_id`
+pub fn invoice_address(program_id: &Pubkey, invoice_id: &[u8; 32]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[INVOICE_SEED, invoice_id], program_id)
+}
+
+/// A staged batch upload, one per authority and nonce
+pub fn batch_buffer_address(program_id: &Pubkey, authority: &Pubkey, nonce: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[BATCH_BUFFER_SEED, authority.as_ref(), &nonce.to_le_bytes()],
+        program_id,
+    )
+}
+
+/// The singleton shielded pool
+pub fn pool_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[POOL_SEED], program_id)
+}
+
+/// The singleton audit log
+pub fn audit_log_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[AUDIT_LOG_SEED], program_id)
+}
+
+/// The singleton relayer allowlist
+pub fn relayer_list_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[RELAYER_LIST_SEED], program_id)
+}
+
+/// The denylist marker for `recipient`; see `state::DeniedRecipient`
+pub fn denied_recipient_address(program_id: &Pubkey, recipient: &[u8; 32]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[DENIED_RECIPIENT_SEED, recipient], program_id)
+}
+
+/// The `BatchReceipt` of the batch with `transcript_hash`
+pub fn batch_receipt_address(program_id: &Pubkey, transcript_hash: &[u8; 32]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[BATCH_RECEIPT_SEED, transcript_hash], program_id)
+}
+
+/// The lamport deposit `merchant` rebates relayers from with
+/// `ClaimRelayerRebate`
+pub fn rebate_deposit_address(program_id: &Pubkey, merchant: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[REBATE_DEPOSIT_SEED, merchant.as_ref()], program_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Seeds spelled out byte for byte: deployed accounts live at these
+    // addresses, so a change to any constant above must fail here
+    #[test]
+    fn test_seed_layouts_pinned() {
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let hash = [7u8; 32];
+        let derive = |seeds: &[&[u8]]| Pubkey::find_program_address(seeds, &program_id);
+
+        assert_eq!(config_address(&program_id), derive(&[b"config"]));
+        assert_eq!(receipt_address(&program_id, &hash), derive(&[b"receipt", &hash]));
+        assert_eq!(stats_address(&program_id, &hash), derive(&[b"stats", &hash]));
+        assert_eq!(
+            nullifier_address(&program_id, CircuitId::Withdraw, &hash, 3),
+            derive(&[
+                b"nullifier",
+                &[1],
+                &[CircuitId::Withdraw as u8],
+                &hash,
+                &3u64.to_le_bytes()
+            ])
+        );
+        assert_eq!(
+            vkey_address(&program_id, CircuitId::Refund),
+            derive(&[b"prepared-vk", &[CircuitId::Refund as u8]])
+        );
+        assert_eq!(invoice_address(&program_id, &hash), derive(&[b"invoice", &hash]));
+        assert_eq!(
+            batch_buffer_address(&program_id, &authority, 9),
+            derive(&[b"batch-buffer", authority.as_ref(), &9u64.to_le_bytes()])
+        );
+        assert_eq!(pool_address(&program_id), derive(&[b"pool"]));
+        assert_eq!(audit_log_address(&program_id), derive(&[b"audit-log"]));
+        assert_eq!(relayer_list_address(&program_id), derive(&[b"relayers"]));
+        assert_eq!(denied_recipient_address(&program_id, &hash), derive(&[b"denied", &hash]));
+        assert_eq!(
+            batch_receipt_address(&program_id, &hash),
+            derive(&[b"batch-receipt", &hash])
+        );
+        assert_eq!(
+            rebate_deposit_address(&program_id, &authority),
+            derive(&[b"rebate-deposit", authority.as_ref()])
+        );
+    }
+}
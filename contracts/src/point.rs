@@ -0,0 +1,525 @@
+// BN254 curve point wrappers
+// Points use the alt_bn128 syscall encoding, every limb a 32-byte big-endian
+// field element:
+//
+//   G1: x || y
+//   G2: x.c1 || x.c0 || y.c1 || y.c0, for Fq2 elements c0 + c1 * u
+//
+// G2 coordinates put the imaginary limb first, as in EIP-197. snarkjs JSON
+// lists each coordinate as [c0, c1] and arkworks serializes c0 then c1, each
+// limb little-endian, so keys and proofs from either must be reordered before
+// they are embedded here. `validate_g2_point` catches a blob that was not.
+//
+// Both types hold their limbs as `Be32`s in that order, and Borsh writes fields
+// in order, so their Borsh encoding is the raw bytes and on-chain layouts are
+// unchanged.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    alt_bn128::{alt_bn128_addition, alt_bn128_multiplication},
+    msg,
+    program_error::ProgramError,
+};
+
+use crate::{
+    endian::{self, Be32},
+    field::{self, Fq, Fq2, Scalar, FQ_MODULUS},
+    VerifierError,
+};
+
+/// Twist coefficient b' = 3 / (9 + u) of E'(Fq2): y^2 = x^3 + b'
+const TWIST_B_C0: [u8; 32] = [
+    0x2b, 0x14, 0x9d, 0x40, 0xce, 0xb8, 0xaa, 0xae,
+    0x81, 0xbe, 0x18, 0x99, 0x1b, 0xe0, 0x6a, 0xc3,
+    0xb5, 0xb4, 0xc5, 0xe5, 0x59, 0xdb, 0xef, 0xa3,
+    0x32, 0x67, 0xe6, 0xdc, 0x24, 0xa1, 0x38, 0xe5,
+];
+const TWIST_B_C1: [u8; 32] = [
+    0x00, 0x97, 0x13, 0xb0, 0x3a, 0xf0, 0xfe, 0xd4,
+    0xcd, 0x2c, 0xaf, 0xad, 0xee, 0xd8, 0xfd, 0xf4,
+    0xa7, 0x4f, 0xa0, 0x84, 0xe5, 0x2d, 0x18, 0x52,
+    0xe4, 0xa2, 0xbd, 0x06, 0x85, 0xc3, 0x15, 0xd2,
+];
+
+/// Point on G1 (64 bytes)
+#[derive(BorshSerialize, BorshDeserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct G1Point {
+    x: Be32,
+    y: Be32,
+}
+
+impl G1Point {
+    pub const LEN: usize = 64;
+
+    /// The point at infinity, encoded by the syscalls as all zeros
+    pub const IDENTITY: Self = Self { x: Be32::ZERO, y: Be32::ZERO };
+
+    /// Wrap an encoding of statically known length; see `is_canonical`
+    pub const fn new(bytes: [u8; 64]) -> Self {
+        Self {
+            x: Be32::from_limb(&bytes, 0),
+            y: Be32::from_limb(&bytes, 1),
+        }
+    }
+
+    /// Parse an encoding, rejecting the wrong length or non-canonical limbs
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, VerifierError> {
+        let bytes: [u8; 64] = bytes
+            .try_into()
+            .map_err(|_| VerifierError::InvalidPointLength)?;
+        let point = Self::new(bytes);
+        if !point.is_canonical() {
+            return Err(VerifierError::NonCanonicalEncoding);
+        }
+        Ok(point)
+    }
+
+    /// Whether both coordinates are reduced field elements
+    pub fn is_canonical(&self) -> bool {
+        field::all_limbs_canonical(&self.to_bytes())
+    }
+
+    /// Whether this encodes a point on y^2 = x^3 + 3
+    ///
+    /// All zeros is the point at infinity and counts, as the syscalls accept
+    /// it. Non-canonical limbs do not.
+    pub fn is_on_curve(&self) -> bool {
+        if *self == Self::IDENTITY {
+            return true;
+        }
+        match (Fq::from_be(self.x), Fq::from_be(self.y)) {
+            (Some(x), Some(y)) => {
+                let b = Fq::from_be(Be32::from_u64(3)).unwrap();
+                y.mul(&y) == x.mul(&x).mul(&x).add(&b)
+            }
+            _ => false,
+        }
+    }
+
+    /// The syscall encoding, x then y
+    pub fn to_bytes(self) -> [u8; 64] {
+        endian::join_limbs(&[self.x, self.y])
+    }
+
+    /// -P: same x, y replaced by p - y
+    ///
+    /// The point at infinity is its own negation. `y` is assumed canonical;
+    /// the syscalls reject the result otherwise.
+    pub fn neg(&self) -> Self {
+        if *self == Self::IDENTITY {
+            return *self;
+        }
+        Self {
+            x: self.x,
+            y: p_minus(self.y),
+        }
+    }
+
+    /// self + other
+    pub fn add(&self, other: &Self) -> Result<Self, ProgramError> {
+        let mut input = [0u8; 128];
+        input[..64].copy_from_slice(&self.to_bytes());
+        input[64..].copy_from_slice(&other.to_bytes());
+
+        let mut result = [0u8; 64];
+        alt_bn128_addition(&input, &mut result).map_err(|e| {
+            msg!("Point addition failed");
+            VerifierError::from(e)
+        })?;
+        Ok(Self::new(result))
+    }
+
+    /// self * scalar
+    pub fn mul(&self, scalar: &Scalar) -> Result<Self, ProgramError> {
+        let mut input = [0u8; 96];
+        input[..64].copy_from_slice(&self.to_bytes());
+        input[64..].copy_from_slice(&scalar.to_syscall());
+
+        let mut result = [0u8; 64];
+        alt_bn128_multiplication(&input, &mut result).map_err(|e| {
+            msg!("Scalar multiplication failed");
+            VerifierError::from(e)
+        })?;
+        Ok(Self::new(result))
+    }
+}
+
+/// Point on G2 (128 bytes)
+#[derive(BorshSerialize, BorshDeserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct G2Point {
+    x_c1: Be32,
+    x_c0: Be32,
+    y_c1: Be32,
+    y_c0: Be32,
+}
+
+impl G2Point {
+    pub const LEN: usize = 128;
+
+    /// The point at infinity, encoded by the syscalls as all zeros
+    pub const IDENTITY: Self = Self::new([0u8; 128]);
+
+    /// Wrap an encoding of statically known length; see `is_canonical`
+    pub const fn new(bytes: [u8; 128]) -> Self {
+        Self {
+            x_c1: Be32::from_limb(&bytes, 0),
+            x_c0: Be32::from_limb(&bytes, 1),
+            y_c1: Be32::from_limb(&bytes, 2),
+            y_c0: Be32::from_limb(&bytes, 3),
+        }
+    }
+
+    /// Parse an encoding, rejecting the wrong length or non-canonical limbs
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, VerifierError> {
+        let bytes: [u8; 128] = bytes
+            .try_into()
+            .map_err(|_| VerifierError::InvalidPointLength)?;
+        let point = Self::new(bytes);
+        if !point.is_canonical() {
+            return Err(VerifierError::NonCanonicalEncoding);
+        }
+        Ok(point)
+    }
+
+    /// Whether all four coordinate limbs are reduced field elements
+    pub fn is_canonical(&self) -> bool {
+        field::all_limbs_canonical(&self.to_bytes())
+    }
+
+    /// Whether this is the point at infinity, counting encodings whose every
+    /// limb is a multiple of p, which a decoder that reduces its input reads
+    /// as all zeros
+    pub fn is_identity(&self) -> bool {
+        [self.x_c1, self.x_c0, self.y_c1, self.y_c0].into_iter().all(|limb| {
+            let mut limb = limb.to_syscall();
+            field::reduce_fq(&mut limb);
+            limb == [0u8; 32]
+        })
+    }
+
+    /// Whether this encodes a point on the G2 twist; see `validate_g2_point`
+    pub fn validate(&self) -> Result<(), VerifierError> {
+        validate_g2_point(&self.to_bytes())
+    }
+
+    /// The syscall encoding, x.c1, x.c0, y.c1, y.c0
+    pub fn to_bytes(self) -> [u8; 128] {
+        endian::join_limbs(&[self.x_c1, self.x_c0, self.y_c1, self.y_c0])
+    }
+
+    /// -Q: same x, each limb of y replaced by p - limb, zero limbs kept
+    ///
+    /// The point at infinity is its own negation. The limbs are assumed
+    /// canonical.
+    pub fn neg(&self) -> Self {
+        let neg = |limb: Be32| if limb == Be32::ZERO { limb } else { p_minus(limb) };
+        Self {
+            y_c1: neg(self.y_c1),
+            y_c0: neg(self.y_c0),
+            ..*self
+        }
+    }
+}
+
+/// p - limb, wrapping for a limb above p
+fn p_minus(limb: Be32) -> Be32 {
+    let limb = limb.to_syscall();
+    let mut negated = [0u8; 32];
+    let mut borrow = 0u16;
+
+    // Big-endian subtraction, from the least significant byte
+    for i in (0..32).rev() {
+        let diff = FQ_MODULUS[i] as u16 + 256 - limb[i] as u16 - borrow;
+        negated[i] = (diff & 0xFF) as u8;
+        borrow = if diff < 256 { 1 } else { 0 };
+    }
+    Be32::from_syscall(negated)
+}
+
+/// Check that 128 bytes encode a point on the BN254 G2 twist
+///
+/// Every limb must be canonical and (x, y), read in the limb order documented
+/// at the top of this file, must satisfy y^2 = x^3 + 3 / (9 + u). All zeros is
+/// the point at infinity and is accepted, as the syscalls accept it. This is a
+/// curve check only, not a subgroup check.
+pub fn validate_g2_point(bytes: &[u8; 128]) -> Result<(), VerifierError> {
+    let limb = |i: usize| {
+        Fq::from_be(Be32::from_limb(bytes, i)).ok_or(VerifierError::NonCanonicalEncoding)
+    };
+    let x = Fq2 {
+        c0: limb(1)?,
+        c1: limb(0)?,
+    };
+    let y = Fq2 {
+        c0: limb(3)?,
+        c1: limb(2)?,
+    };
+    if *bytes == [0u8; 128] {
+        return Ok(());
+    }
+
+    let twist_b = Fq2 {
+        c0: Fq::from_be(Be32::from_syscall(TWIST_B_C0)).unwrap(),
+        c1: Fq::from_be(Be32::from_syscall(TWIST_B_C1)).unwrap(),
+    };
+    if y.mul(&y) != x.mul(&x).mul(&x).add(&twist_b) {
+        return Err(VerifierError::InvalidG2Point);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generator() -> G1Point {
+        let mut bytes = [0u8; 64];
+        bytes[31] = 1;
+        bytes[63] = 2;
+        G1Point::new(bytes)
+    }
+
+    #[test]
+    fn test_from_slice_validation() {
+        assert_eq!(G1Point::from_slice(&generator().to_bytes()), Ok(generator()));
+        assert_eq!(
+            G1Point::from_slice(&[0u8; 63]),
+            Err(VerifierError::InvalidPointLength)
+        );
+        assert_eq!(
+            G1Point::from_slice(&[0xffu8; 64]),
+            Err(VerifierError::NonCanonicalEncoding)
+        );
+
+        assert!(G2Point::from_slice(&[0u8; 128]).is_ok());
+        assert_eq!(
+            G2Point::from_slice(&[0u8; 64]),
+            Err(VerifierError::InvalidPointLength)
+        );
+        assert_eq!(
+            G2Point::from_slice(&[0xffu8; 128]),
+            Err(VerifierError::NonCanonicalEncoding)
+        );
+    }
+
+    /// The G2 generator in syscall order: x.c1, x.c0, y.c1, y.c0
+    const G2_GENERATOR_LIMBS: [[u8; 32]; 4] = [
+        [
+            0x19, 0x8e, 0x93, 0x93, 0x92, 0x0d, 0x48, 0x3a,
+            0x72, 0x60, 0xbf, 0xb7, 0x31, 0xfb, 0x5d, 0x25,
+            0xf1, 0xaa, 0x49, 0x33, 0x35, 0xa9, 0xe7, 0x12,
+            0x97, 0xe4, 0x85, 0xb7, 0xae, 0xf3, 0x12, 0xc2,
+        ],
+        [
+            0x18, 0x00, 0xde, 0xef, 0x12, 0x1f, 0x1e, 0x76,
+            0x42, 0x6a, 0x00, 0x66, 0x5e, 0x5c, 0x44, 0x79,
+            0x67, 0x43, 0x22, 0xd4, 0xf7, 0x5e, 0xda, 0xdd,
+            0x46, 0xde, 0xbd, 0x5c, 0xd9, 0x92, 0xf6, 0xed,
+        ],
+        [
+            0x09, 0x06, 0x89, 0xd0, 0x58, 0x5f, 0xf0, 0x75,
+            0xec, 0x9e, 0x99, 0xad, 0x69, 0x0c, 0x33, 0x95,
+            0xbc, 0x4b, 0x31, 0x33, 0x70, 0xb3, 0x8e, 0xf3,
+            0x55, 0xac, 0xda, 0xdc, 0xd1, 0x22, 0x97, 0x5b,
+        ],
+        [
+            0x12, 0xc8, 0x5e, 0xa5, 0xdb, 0x8c, 0x6d, 0xeb,
+            0x4a, 0xab, 0x71, 0x80, 0x8d, 0xcb, 0x40, 0x8f,
+            0xe3, 0xd1, 0xe7, 0x69, 0x0c, 0x43, 0xd3, 0x7b,
+            0x4c, 0xe6, 0xcc, 0x01, 0x66, 0xfa, 0x7d, 0xaa,
+        ],
+    ];
+
+    /// Generator limbs placed in the given order
+    fn g2_with_order(order: [usize; 4]) -> [u8; 128] {
+        let mut bytes = [0u8; 128];
+        for (slot, limb) in order.iter().enumerate() {
+            bytes[32 * slot..32 * (slot + 1)].copy_from_slice(&G2_GENERATOR_LIMBS[*limb]);
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_g2_generator_and_infinity_valid() {
+        assert_eq!(validate_g2_point(&g2_with_order([0, 1, 2, 3])), Ok(()));
+        assert_eq!(validate_g2_point(&[0u8; 128]), Ok(()));
+
+        // -G: y.c1 and y.c0 negated
+        let mut negated = g2_with_order([0, 1, 2, 3]);
+        negated[64..96].copy_from_slice(&[
+            0x27, 0x5d, 0xc4, 0xa2, 0x88, 0xd1, 0xaf, 0xb3,
+            0xcb, 0xb1, 0xac, 0x09, 0x18, 0x75, 0x24, 0xc7,
+            0xdb, 0x36, 0x39, 0x5d, 0xf7, 0xbe, 0x3b, 0x99,
+            0xe6, 0x73, 0xb1, 0x3a, 0x07, 0x5a, 0x65, 0xec,
+        ]);
+        negated[96..].copy_from_slice(&[
+            0x1d, 0x9b, 0xef, 0xcd, 0x05, 0xa5, 0x32, 0x3e,
+            0x6d, 0xa4, 0xd4, 0x35, 0xf3, 0xb6, 0x17, 0xcd,
+            0xb3, 0xaf, 0x83, 0x28, 0x5c, 0x2d, 0xf7, 0x11,
+            0xef, 0x39, 0xc0, 0x15, 0x71, 0x82, 0x7f, 0x9d,
+        ]);
+        assert_eq!(validate_g2_point(&negated), Ok(()));
+        let g = G2Point::new(g2_with_order([0, 1, 2, 3]));
+        assert_eq!(g.neg(), G2Point::new(negated));
+        assert_eq!(g.neg().neg(), g);
+        assert_eq!(G2Point::IDENTITY.neg(), G2Point::IDENTITY);
+    }
+
+    #[test]
+    fn test_g2_identity_encodings() {
+        assert!(G2Point::IDENTITY.is_identity());
+        assert!(!G2Point::new(g2_with_order([0, 1, 2, 3])).is_identity());
+
+        // Limbs of p and 2p reduce to zero; a single nonzero bit does not
+        let mut twice = FQ_MODULUS;
+        let mut carry = 0u16;
+        for i in (0..32).rev() {
+            let sum = twice[i] as u16 * 2 + carry;
+            twice[i] = sum as u8;
+            carry = sum >> 8;
+        }
+        let mut near = [0u8; 128];
+        near[..32].copy_from_slice(&FQ_MODULUS);
+        near[96..].copy_from_slice(&twice);
+        assert!(G2Point::new(near).is_identity());
+        assert!(!G2Point::new(near).is_canonical());
+
+        let mut one_bit = [0u8; 128];
+        one_bit[127] = 1;
+        assert!(!G2Point::new(one_bit).is_identity());
+    }
+
+    #[test]
+    fn test_g2_wrong_limb_orders_rejected() {
+        // snarkjs order (c0 before c1), x and y swapped, and both
+        for order in [[1, 0, 3, 2], [2, 3, 0, 1], [3, 2, 1, 0]] {
+            assert_eq!(
+                validate_g2_point(&g2_with_order(order)),
+                Err(VerifierError::InvalidG2Point),
+                "order {:?}",
+                order
+            );
+        }
+
+        // Every other permutation of the four limbs is off the curve too
+        let mut rejected = 0;
+        for index in 0..256usize {
+            let order = [index & 3, index >> 2 & 3, index >> 4 & 3, index >> 6 & 3];
+            if !(0..4).all(|limb| order.contains(&limb)) || order == [0, 1, 2, 3] {
+                continue;
+            }
+            assert_eq!(
+                validate_g2_point(&g2_with_order(order)),
+                Err(VerifierError::InvalidG2Point),
+                "order {:?}",
+                order
+            );
+            rejected += 1;
+        }
+        assert_eq!(rejected, 23);
+
+        // arkworks order: c0 first and every limb little-endian
+        let mut arkworks = g2_with_order([1, 0, 3, 2]);
+        for limb in arkworks.chunks_exact_mut(32) {
+            limb.reverse();
+        }
+        assert_eq!(
+            validate_g2_point(&arkworks),
+            Err(VerifierError::NonCanonicalEncoding)
+        );
+    }
+
+    #[test]
+    fn test_g2_off_curve_rejected() {
+        let mut bytes = g2_with_order([0, 1, 2, 3]);
+        bytes[127] ^= 1;
+        assert_eq!(validate_g2_point(&bytes), Err(VerifierError::InvalidG2Point));
+
+        assert_eq!(
+            validate_g2_point(&[2u8; 128]),
+            Err(VerifierError::InvalidG2Point)
+        );
+        assert_eq!(
+            validate_g2_point(&[0xffu8; 128]),
+            Err(VerifierError::NonCanonicalEncoding)
+        );
+    }
+
+    #[test]
+    fn test_g1_on_curve() {
+        assert!(generator().is_on_curve());
+        assert!(generator().neg().is_on_curve());
+        assert!(G1Point::IDENTITY.is_on_curve());
+
+        let mut off_curve = generator().to_bytes();
+        off_curve[63] = 3;
+        assert!(!G1Point::new(off_curve).is_on_curve());
+
+        // The generator with each limb written little-endian
+        let mut little_endian = [0u8; 64];
+        little_endian[0] = 1;
+        little_endian[32] = 2;
+        assert!(!G1Point::new(little_endian).is_on_curve());
+
+        assert!(!G1Point::new([0xffu8; 64]).is_on_curve());
+    }
+
+    #[test]
+    fn test_neg_subtracts_big_endian_y() {
+        let g = generator();
+        let mut p_minus_two = FQ_MODULUS;
+        p_minus_two[31] -= 2;
+        let mut expected = [0u8; 64];
+        expected[31] = 1;
+        expected[32..].copy_from_slice(&p_minus_two);
+        assert_eq!(g.neg().to_bytes(), expected);
+        assert_eq!(g.neg().neg(), g);
+        assert_eq!(G1Point::IDENTITY.neg(), G1Point::IDENTITY);
+    }
+
+    #[test]
+    fn test_borsh_is_raw_bytes() {
+        let g1 = G1Point::new([7u8; 64]);
+        assert_eq!(g1.try_to_vec().unwrap(), vec![7u8; 64]);
+        assert_eq!(G1Point::try_from_slice(&[7u8; 64]).unwrap(), g1);
+        assert_eq!(g1.to_bytes(), [7u8; 64]);
+        assert_eq!(std::mem::size_of::<G1Point>(), G1Point::LEN);
+
+        let g2 = G2Point::new([9u8; 128]);
+        assert_eq!(g2.try_to_vec().unwrap(), vec![9u8; 128]);
+        assert_eq!(G2Point::try_from_slice(&[9u8; 128]).unwrap(), g2);
+        assert_eq!(g2.to_bytes(), [9u8; 128]);
+        assert_eq!(std::mem::size_of::<G2Point>(), G2Point::LEN);
+
+        // Limbs keep their positions
+        let mut bytes = [0u8; 128];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        assert_eq!(G2Point::new(bytes).to_bytes(), bytes);
+        assert_eq!(G2Point::new(bytes).try_to_vec().unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_add_and_mul_agree() {
+        let g = generator();
+        assert_eq!(g.mul(&Scalar::from_u64(2)).unwrap(), g.add(&g).unwrap());
+        assert_eq!(g.add(&G1Point::IDENTITY).unwrap(), g);
+
+        // A multiplier past the low limb: 2^64 * G by doubling
+        let mut doubled = g;
+        for _ in 0..64 {
+            doubled = doubled.add(&doubled).unwrap();
+        }
+        let mut bytes = [0u8; 32];
+        bytes[23] = 1;
+        let scalar = Scalar::from_canonical(Be32::from_syscall(bytes)).unwrap();
+        assert_eq!(g.mul(&scalar).unwrap(), doubled);
+
+        // and one using every limb: (r - 1) * G = -G
+        let mut r_minus_one = field::FR_MODULUS;
+        r_minus_one[31] -= 1;
+        let scalar = Scalar::from_canonical(Be32::from_syscall(r_minus_one)).unwrap();
+        assert_eq!(g.mul(&scalar).unwrap(), g.neg());
+    }
+}
@@ -0,0 +1,218 @@
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    log::sol_log_64,
+    msg,
+    program::{invoke, set_return_data},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction,
+    sysvar::Sysvar,
+};
+
+use crate::{
+    events::{DepositCommitted, NullifierConsumed, ProofVerified, VerifierEvent, Withdrawn},
+    field::is_canonical_fr,
+    layout::{DEPOSIT, WITHDRAW},
+    state::{
+        DeniedRecipient, PreparedVk, ShieldedPool, SpentNullifier, StateAccount, NULLIFIER_SEED,
+        NULLIFIER_SEED_VERSION,
+    },
+    utils::create_pda_account,
+    verify_groth16, CircuitId, Groth16Proof, VerifierError, WithdrawPublicInputs,
+};
+
+/// Epoch seed of pool nullifiers, which are spent once rather than per epoch
+pub const WITHDRAW_NULLIFIER_EPOCH: u64 = 0;
+
+/// Move lamports into the shielded pool and append their note commitment
+///
+/// The commitment's leaf index is set as return data (u64 LE) so the wallet
+/// can track the note without parsing logs.
+///
+/// Accounts expected:
+/// 0. `[signer, writable]` Depositor
+/// 1. `[writable]` Pool PDA: ["pool"]
+/// 2. `[]` System program
+pub fn process_deposit(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    commitment: [u8; 32],
+    amount: u64,
+) -> ProgramResult {
+    let layout = DEPOSIT;
+    layout.check(accounts)?;
+    let depositor = &accounts[layout.depositor];
+    let pool_account = &accounts[layout.pool];
+    let system_program = &accounts[layout.system_program];
+    if amount == 0 {
+        msg!("Deposit amount must be nonzero");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let mut pool = ShieldedPool::load(pool_account, program_id)?;
+    let leaf_index = pool.tree.append(commitment)?;
+
+    invoke(
+        &system_instruction::transfer(depositor.key, pool_account.key, amount),
+        &[depositor.clone(), pool_account.clone(), system_program.clone()],
+    )?;
+    pool.pack(&mut pool_account.data.borrow_mut()[..])?;
+
+    set_return_data(&leaf_index.to_le_bytes());
+    VerifierEvent::DepositCommitted(DepositCommitted {
+        commitment,
+        leaf_index,
+        root: pool.tree.root(),
+    })
+    .emit();
+
+    msg!("✓ Deposit committed (leaf index, amount):");
+    sol_log_64(leaf_index, amount, 0, 0, 0);
+    Ok(())
+}
+
+/// Verify a withdraw proof, spend its nullifier, pay the recipient and append
+/// the change note
+///
+/// The root may be any of the pool's last `root_history_size` roots, so a
+/// proof built just before other deposits landed still verifies. Root,
+/// nullifier, recipient and the change commitment's encoding are checked
+/// before the pairing. A zero change commitment appends nothing. The change
+/// leaf index, if any, is set as return data (u64 LE); when the tree is full
+/// the whole withdrawal fails.
+///
+/// Accounts expected:
+/// 0. `[signer, writable]` Payer funding the nullifier rent
+/// 1. `[writable]` Pool PDA: ["pool"]
+/// 2. `[writable]` Nullifier PDA:
+///    ["nullifier", seed version, circuit id, nullifier, 0 (u64 LE)]
+/// 3. `[writable]` Recipient
+/// 4. `[]` System program
+/// 5. `[]` Denylist marker PDA: ["denied", recipient]
+/// 6. `[]` Prepared key PDA: ["prepared-vk", 6]
+pub fn process_withdraw(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    proof: &Groth16Proof,
+    public_inputs: &WithdrawPublicInputs,
+) -> ProgramResult {
+    let vk = CircuitId::Withdraw.verification_key();
+    vk.check_signal_layout(WithdrawPublicInputs::SIGNAL_LAYOUT)?;
+
+    let layout = WITHDRAW;
+    layout.check(accounts)?;
+    let payer = &accounts[layout.payer];
+    let pool_account = &accounts[layout.pool];
+    let nullifier_account = &accounts[layout.nullifier];
+    let recipient = &accounts[layout.recipient];
+    let system_program = &accounts[layout.system_program];
+    let marker_account = &accounts[layout.marker];
+    PreparedVk::load_active(&accounts[layout.prepared_key], program_id, CircuitId::Withdraw)?;
+
+    let mut pool = ShieldedPool::load(pool_account, program_id)?;
+    if !pool.tree.is_known_root(&public_inputs.root) {
+        return Err(VerifierError::UnknownMerkleRoot.into());
+    }
+
+    let (nullifier_address, bump) = SpentNullifier::address(
+        program_id,
+        CircuitId::Withdraw,
+        &public_inputs.nullifier,
+        WITHDRAW_NULLIFIER_EPOCH,
+    );
+    if nullifier_address != *nullifier_account.key {
+        msg!("Nullifier account does not match nullifier");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if !nullifier_account.data_is_empty() {
+        return Err(VerifierError::NullifierAlreadySpent.into());
+    }
+
+    if recipient.key.to_bytes() != public_inputs.recipient {
+        return Err(VerifierError::InvalidSettlementAccount.into());
+    }
+    DeniedRecipient::check(marker_account, program_id, &public_inputs.recipient)?;
+    if !is_canonical_fr(&public_inputs.change_commitment) {
+        return Err(VerifierError::InvalidCommitment.into());
+    }
+    let available = pool_account
+        .lamports()
+        .saturating_sub(Rent::get()?.minimum_balance(ShieldedPool::LEN));
+    if public_inputs.amount > available {
+        return Err(ProgramError::InsufficientFunds);
+    }
+
+    verify_groth16(vk, proof, &public_inputs.to_scalars())?;
+
+    create_pda_account(
+        payer,
+        nullifier_account,
+        system_program,
+        program_id,
+        SpentNullifier::LEN,
+        &[
+            NULLIFIER_SEED,
+            &[NULLIFIER_SEED_VERSION],
+            &[CircuitId::Withdraw as u8],
+            &public_inputs.nullifier,
+            &WITHDRAW_NULLIFIER_EPOCH.to_le_bytes(),
+            &[bump],
+        ],
+    )?;
+    SpentNullifier {
+        nullifier: public_inputs.nullifier,
+        epoch: WITHDRAW_NULLIFIER_EPOCH,
+        bump,
+    }
+    .pack(&mut nullifier_account.data.borrow_mut()[..])?;
+
+    // The pool is program-owned, so it is debited directly rather than by CPI
+    **pool_account.try_borrow_mut_lamports()? -= public_inputs.amount;
+    **recipient.try_borrow_mut_lamports()? += public_inputs.amount;
+
+    let change_leaf_index = if public_inputs.change_commitment == [0u8; 32] {
+        None
+    } else {
+        let leaf_index = pool.tree.append(public_inputs.change_commitment)?;
+        pool.pack(&mut pool_account.data.borrow_mut()[..])?;
+        set_return_data(&leaf_index.to_le_bytes());
+        Some(leaf_index)
+    };
+
+    VerifierEvent::ProofVerified(ProofVerified {
+        proof_hash: proof.hash(public_inputs),
+        recipient: public_inputs.recipient,
+        min_amount: public_inputs.amount,
+    })
+    .emit();
+    VerifierEvent::NullifierConsumed(NullifierConsumed {
+        nullifier: public_inputs.nullifier,
+    })
+    .emit();
+    if let Some(leaf_index) = change_leaf_index {
+        VerifierEvent::DepositCommitted(DepositCommitted {
+            commitment: public_inputs.change_commitment,
+            leaf_index,
+            root: pool.tree.root(),
+        })
+        .emit();
+    }
+    VerifierEvent::Withdrawn(Withdrawn {
+        nullifier: public_inputs.nullifier,
+        recipient: public_inputs.recipient,
+        amount: public_inputs.amount,
+        change_leaf_index,
+    })
+    .emit();
+
+    msg!("✓ Withdrawn from shielded pool");
+    #[cfg(not(feature = "privacy-strict"))]
+    log_statement!("Amount:", public_inputs.amount);
+    if let Some(leaf_index) = change_leaf_index {
+        msg!("Change note committed (leaf index):");
+        sol_log_64(leaf_index, 0, 0, 0, 0);
+    }
+    Ok(())
+}
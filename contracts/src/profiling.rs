@@ -0,0 +1,217 @@
+// Per-phase compute unit accounting
+// With the `profiling` feature, `profile_phase!` reads the remaining compute
+// units before and after a phase of verification and logs what it used with
+// `sol_log_64(PROFILE_TAG, phase, units, 0, 0)`. Without the feature the
+// macro expands to its body alone, so default builds carry neither the
+// syscalls nor the logs. `parse_phase_costs` adds the logged costs of a
+// transaction back up per phase, skipping every other line.
+//
+// How much the input-point MSM costs depends on how many public inputs are
+// zero or one, so the logs leak statement values and `profiling` cannot be
+// combined with `privacy-strict`.
+
+#[cfg(all(feature = "profiling", feature = "privacy-strict"))]
+compile_error!("profiling logs depend on public input values; drop it from privacy-strict builds");
+
+/// First value of every profiling log, ASCII "prof"
+pub const PROFILE_TAG: u64 = 0x7072_6f66;
+
+/// Whether this build logs phase costs
+pub const PROFILING: bool = cfg!(feature = "profiling");
+
+/// A profiled phase of verification; the discriminants are logged and stable
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Decoding the instruction data
+    InputParsing = 1,
+    /// Folding the public inputs into the IC points
+    InputMsm = 2,
+    /// Negating the G1 points of the pairing equation
+    Negation = 3,
+    /// The pairing syscall
+    Pairing = 4,
+    /// Creating and writing accounts once a proof is accepted
+    StateWrites = 5,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 5] = [
+        Phase::InputParsing,
+        Phase::InputMsm,
+        Phase::Negation,
+        Phase::Pairing,
+        Phase::StateWrites,
+    ];
+
+    /// The phase logged as `tag`, `None` for tags this build does not know
+    pub fn from_tag(tag: u64) -> Option<Phase> {
+        Phase::ALL.into_iter().find(|phase| *phase as u64 == tag)
+    }
+}
+
+/// Evaluate `$body` and, in `profiling` builds, log the compute units it used
+/// as `$phase`
+#[cfg(feature = "profiling")]
+macro_rules! profile_phase {
+    ($phase:expr, $body:expr) => {{
+        use ::solana_program::compute_units::sol_remaining_compute_units;
+        let remaining = sol_remaining_compute_units();
+        #[allow(clippy::let_unit_value)]
+        let value = $body;
+        let units = remaining.saturating_sub(sol_remaining_compute_units());
+        $crate::profiling::log_phase($phase, units);
+        value
+    }};
+}
+
+/// `$body` alone, without the `profiling` feature
+#[cfg(not(feature = "profiling"))]
+macro_rules! profile_phase {
+    ($phase:expr, $body:expr) => {
+        $body
+    };
+}
+
+/// Log `units` spent in `phase`
+#[cfg(feature = "profiling")]
+#[doc(hidden)]
+pub fn log_phase(phase: Phase, units: u64) {
+    solana_program::log::sol_log_64(PROFILE_TAG, phase as u64, units, 0, 0);
+}
+
+/// Compute units a transaction spent per phase, summed over every time the
+/// phase ran
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PhaseCosts {
+    pub input_parsing: u64,
+    pub input_msm: u64,
+    pub negation: u64,
+    pub pairing: u64,
+    pub state_writes: u64,
+}
+
+impl PhaseCosts {
+    pub fn get(&self, phase: Phase) -> u64 {
+        match phase {
+            Phase::InputParsing => self.input_parsing,
+            Phase::InputMsm => self.input_msm,
+            Phase::Negation => self.negation,
+            Phase::Pairing => self.pairing,
+            Phase::StateWrites => self.state_writes,
+        }
+    }
+
+    fn get_mut(&mut self, phase: Phase) -> &mut u64 {
+        match phase {
+            Phase::InputParsing => &mut self.input_parsing,
+            Phase::InputMsm => &mut self.input_msm,
+            Phase::Negation => &mut self.negation,
+            Phase::Pairing => &mut self.pairing,
+            Phase::StateWrites => &mut self.state_writes,
+        }
+    }
+
+    /// Units spent across all phases
+    pub fn total(&self) -> u64 {
+        Phase::ALL.iter().map(|phase| self.get(*phase)).sum()
+    }
+}
+
+/// Sum the phase costs logged in `logs`, a transaction's log messages
+///
+/// Lines that are not profiling logs, and profiling logs of phases this build
+/// does not know, are skipped.
+pub fn parse_phase_costs<S: AsRef<str>>(logs: &[S]) -> PhaseCosts {
+    let mut costs = PhaseCosts::default();
+    for (phase, units) in logs.iter().filter_map(|line| parse_line(line.as_ref())) {
+        let cost = costs.get_mut(phase);
+        *cost = cost.saturating_add(units);
+    }
+    costs
+}
+
+/// The phase and units of one `sol_log_64` line,
+/// "Program log: 0x7072..., 0x.., 0x.., 0x0, 0x0"
+fn parse_line(line: &str) -> Option<(Phase, u64)> {
+    let values = line.strip_prefix("Program log: ")?;
+    let mut fields = [0u64; 5];
+    let mut parts = values.split(", ");
+    for field in fields.iter_mut() {
+        let hex = parts.next()?.strip_prefix("0x")?;
+        *field = u64::from_str_radix(hex, 16).ok()?;
+    }
+    if parts.next().is_some() || fields[0] != PROFILE_TAG {
+        return None;
+    }
+    Some((Phase::from_tag(fields[1])?, fields[2]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log_line(tag: u64, phase: u64, units: u64) -> String {
+        format!("Program log: {:#x}, {:#x}, {:#x}, {:#x}, {:#x}", tag, phase, units, 0, 0)
+    }
+
+    #[test]
+    fn test_phase_tags_are_stable() {
+        let tags = Phase::ALL.map(|phase| phase as u64);
+        assert_eq!(tags, [1, 2, 3, 4, 5]);
+        for phase in Phase::ALL {
+            assert_eq!(Phase::from_tag(phase as u64), Some(phase));
+        }
+        assert_eq!(Phase::from_tag(0), None);
+        assert_eq!(Phase::from_tag(6), None);
+    }
+
+    #[test]
+    fn test_parse_phase_costs_skips_unrelated_lines() {
+        let logs = [
+            "Program Verifier111 invoke [1]".to_string(),
+            "Program log: Verifying ZK payment proof".to_string(),
+            log_line(PROFILE_TAG, Phase::InputParsing as u64, 1_200),
+            // Another program's sol_log_64, and the batch verifier's own
+            log_line(0xdead, Phase::Pairing as u64, 99),
+            "Program log: 0x3, 0x1, 0x0, 0x0, 0x0".to_string(),
+            log_line(PROFILE_TAG, Phase::InputMsm as u64, 24_000),
+            log_line(PROFILE_TAG, Phase::Negation as u64, 300),
+            // A phase added after this build
+            log_line(PROFILE_TAG, 9, 7),
+            log_line(PROFILE_TAG, Phase::Pairing as u64, 120_000),
+            "Program log: 0x7072656e, oops".to_string(),
+            log_line(PROFILE_TAG, Phase::StateWrites as u64, 5_000),
+            log_line(PROFILE_TAG, Phase::StateWrites as u64, 1_000),
+            "Program Verifier111 consumed 160000 of 200000 compute units".to_string(),
+        ];
+        let costs = parse_phase_costs(&logs);
+        assert_eq!(
+            costs,
+            PhaseCosts {
+                input_parsing: 1_200,
+                input_msm: 24_000,
+                negation: 300,
+                pairing: 120_000,
+                state_writes: 6_000,
+            }
+        );
+        assert_eq!(costs.total(), 151_500);
+        assert_eq!(parse_phase_costs::<&str>(&[]), PhaseCosts::default());
+    }
+
+    #[test]
+    fn test_parse_line_rejects_malformed_lines() {
+        let good = log_line(PROFILE_TAG, 4, 10);
+        assert_eq!(parse_line(&good), Some((Phase::Pairing, 10)));
+        for line in [
+            good.replace("Program log: ", ""),
+            format!("{}, 0x0", good),
+            good.replace(", 0x0, 0x0", ", 0x0"),
+            good.replace("0xa", "10"),
+            good.replace("0xa", "0xzz"),
+        ] {
+            assert_eq!(parse_line(&line), None, "{}", line);
+        }
+    }
+}
@@ -0,0 +1,860 @@
+use borsh::BorshSerialize;
+
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    log::sol_log_64,
+    msg,
+    program::{invoke_signed, set_return_data},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction,
+    sysvar::{clock::Clock, Sysvar},
+};
+
+use crate::{
+    audit::{self, AuditEntry},
+    cpi,
+    events::{
+        ProofVerified, ReceiptConsumed, ReceiptCreated, ReceiptFinalized, RelayerRebateClaimed,
+        VerifierEvent,
+    },
+    layout::{
+        check_no_extra_accounts, VerifyAndRecordAccounts, CLAIM_RELAYER_REBATE, CONSUME_RECEIPT,
+        FINALIZE_RECEIPT, GET_RECEIPT_STATUS, MIGRATE_RECEIPT, TOUCH_RECEIPT, VERIFY_AND_RECORD,
+    },
+    pda,
+    profiling::Phase,
+    state::{
+        DeniedRecipient, InvoiceIndex, PaymentReceipt, PreparedVk, ReceiptStatus, RecipientStats,
+        StateAccount, VerifierConfig, INVOICE_SEED, REBATE_DEPOSIT_SEED, RECEIPT_SEED, STATS_SEED,
+    },
+    utils::{create_pda_account, grow_program_account, report_dry_run},
+    verify_groth16, verify_groth16_with_keys, CircuitId, Groth16Proof, PaymentPublicInputsV3,
+    ValidatedKeys, VerifierError, INVOICE_PAYMENT_VK,
+};
+
+/// Verify an invoice-bound proof and record a `PaymentReceipt` for it
+///
+/// Accounts expected:
+/// 0. `[signer, writable]` Payer funding the receipt rent
+/// 1. `[]` Config PDA: ["config"]
+/// 2. `[writable]` Receipt PDA: ["receipt", proof_hash]
+/// 3. `[]` System program
+/// 4. `[]` Denylist marker PDA: ["denied", recipient_pubkey]
+/// 5. `[]` Prepared key PDA: ["prepared-vk", 2]; a revoked key fails with
+///    `KeyRevoked`
+/// 6. `[writable]` Invoice index PDA: ["invoice", invoice_id], unless the
+///    invoice id is zero
+/// 7. `[writable]` Stats PDA: ["stats", recipient_pubkey], only when `update_stats`
+/// 8. `[writable]` (optional) Audit log PDA: ["audit-log"], after the stats
+///    PDA or in its place
+///
+/// A second payment for an invoice with an active receipt is rejected with
+/// `InvoiceAlreadyPaid` unless the config allows overpayment.
+///
+/// When the config sets a rate limit, `update_stats` must be set: a payment
+/// past the recipient's limit for the window fails with `RateLimited` and
+/// records nothing.
+///
+/// A `consumable` receipt can be spent once with `ConsumeReceipt` by the
+/// recipient or `delegate`; a delegate without `consumable` is refused.
+///
+/// When the config tracks relayers, the payer is recorded as the receipt's
+/// relayer along with `compute_unit_price`, which it signed as part of the
+/// instruction; otherwise both are left unset.
+///
+/// A failed verification aborts the transaction, so only passes reach the
+/// audit log from here; `VerifyBatchSoft` also logs failures.
+///
+/// A dry run makes every check but writes no receipt, stats or audit entry, so
+/// a simulation's outcome does not depend on accounts created later.
+#[allow(clippy::too_many_arguments)]
+pub fn process_verify_and_record(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    proof: &Groth16Proof,
+    public_inputs: &PaymentPublicInputsV3,
+    update_stats: bool,
+    dry_run: bool,
+    consumable: bool,
+    delegate: Option<Pubkey>,
+    compute_unit_price: u64,
+) -> ProgramResult {
+    INVOICE_PAYMENT_VK.check_signal_layout(PaymentPublicInputsV3::SIGNAL_LAYOUT)?;
+    if delegate.is_some() && !consumable {
+        msg!("Only a consumable receipt has a delegate");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let layout = VERIFY_AND_RECORD;
+    layout.check_leading(accounts)?;
+    let payer = &accounts[layout.payer];
+    let config_account = &accounts[layout.config];
+    let receipt_account = &accounts[layout.receipt];
+    let system_program = &accounts[layout.system_program];
+    let marker_account = &accounts[layout.marker];
+    PreparedVk::load_active(&accounts[layout.prepared_key], program_id, CircuitId::InvoicePayment)?;
+    let account_info_iter = &mut accounts[VerifyAndRecordAccounts::LEN..].iter();
+    let index_account = if public_inputs.invoice_id == [0u8; 32] {
+        None
+    } else {
+        Some(next_account_info(account_info_iter)?)
+    };
+
+    let config = VerifierConfig::load(config_account, program_id)?;
+    let clock = Clock::get()?;
+    let stats_account = if update_stats {
+        Some(next_account_info(account_info_iter)?)
+    } else {
+        None
+    };
+    let log_account = audit::active_log(program_id, &config, account_info_iter.next())?;
+    check_no_extra_accounts(account_info_iter.as_slice())?;
+
+    verify_and_record(
+        program_id,
+        payer,
+        receipt_account,
+        index_account,
+        marker_account,
+        stats_account,
+        system_program,
+        &config,
+        &clock,
+        &mut ValidatedKeys::default(),
+        proof,
+        public_inputs,
+        consumable,
+        delegate.unwrap_or_default(),
+        compute_unit_price,
+        dry_run,
+    )?;
+    if dry_run {
+        msg!("Dry run: nothing recorded");
+        return Ok(());
+    }
+
+    if let Some(log_account) = log_account {
+        audit::record(
+            log_account,
+            &AuditEntry {
+                proof_hash: proof.hash(public_inputs),
+                recipient: public_inputs.recipient_pubkey,
+                min_amount: public_inputs.min_amount,
+                slot: clock.slot,
+                outcome: 0,
+            },
+        )?;
+    }
+
+    msg!("✓ Payment receipt recorded");
+    Ok(())
+}
+
+/// The stats recorded at `stats_account` for `recipient`, or fresh ones
+/// before its first recorded payment
+fn load_recipient_stats(
+    program_id: &Pubkey,
+    stats_account: &AccountInfo,
+    recipient: &[u8; 32],
+) -> Result<RecipientStats, ProgramError> {
+    let (stats_address, bump) = pda::stats_address(program_id, recipient);
+    if stats_address != *stats_account.key {
+        msg!("Stats account does not match recipient");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if stats_account.data_is_empty() {
+        return Ok(RecipientStats {
+            recipient: *recipient,
+            count: 0,
+            total_min_amount: 0,
+            last_verified_slot: 0,
+            bump,
+            window_start_slot: 0,
+            window_count: 0,
+        });
+    }
+    if stats_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Ok(RecipientStats::unpack(&stats_account.data.borrow())?)
+}
+
+/// Write `stats` back, creating the PDA on first use and growing a version 1
+/// account
+fn store_recipient_stats<'a>(
+    program_id: &Pubkey,
+    payer: &AccountInfo<'a>,
+    stats_account: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    stats: &RecipientStats,
+) -> ProgramResult {
+    if stats_account.data_is_empty() {
+        create_pda_account(
+            payer,
+            stats_account,
+            system_program,
+            program_id,
+            RecipientStats::LEN,
+            &[STATS_SEED, &stats.recipient, &[stats.bump]],
+        )?;
+    } else if stats_account.data_len() < RecipientStats::LEN {
+        grow_program_account(payer, stats_account, system_program, RecipientStats::LEN)?;
+    }
+    stats.pack(&mut stats_account.data.borrow_mut()[..])?;
+    Ok(())
+}
+
+/// Check, verify and record one invoice-bound payment
+///
+/// Shared by `VerifyAndRecord` and the per-proof loop of `VerifyBatchSoft`.
+/// `index_account` is the invoice's index PDA, required unless the invoice id
+/// is zero, `marker_account` the recipient's denylist marker and
+/// `stats_account` its stats PDA, required when the config sets a rate limit.
+/// `consumable` and `delegate` are recorded in the receipt, as are the payer
+/// and `compute_unit_price` when the config tracks relayers, and `keys` holds
+/// the keys this instruction has already validated. A dry run reports
+/// the receipt with `report_dry_run` instead of creating it, and leaves the
+/// index and stats alone.
+///
+/// The rate limit is checked before anything is written, so a `RateLimited`
+/// payment leaves its receipt address free for a later retry.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn verify_and_record<'a>(
+    program_id: &Pubkey,
+    payer: &AccountInfo<'a>,
+    receipt_account: &AccountInfo<'a>,
+    index_account: Option<&AccountInfo<'a>>,
+    marker_account: &AccountInfo<'a>,
+    stats_account: Option<&AccountInfo<'a>>,
+    system_program: &AccountInfo<'a>,
+    config: &VerifierConfig,
+    clock: &Clock,
+    keys: &mut ValidatedKeys<'static>,
+    proof: &Groth16Proof,
+    public_inputs: &PaymentPublicInputsV3,
+    consumable: bool,
+    delegate: Pubkey,
+    compute_unit_price: u64,
+    dry_run: bool,
+) -> ProgramResult {
+    let now = clock.unix_timestamp;
+    let proof_hash = proof.hash(public_inputs);
+    let (receipt_address, bump) = pda::receipt_address(program_id, &proof_hash);
+    if receipt_address != *receipt_account.key {
+        msg!("Receipt account does not match proof hash");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if !receipt_account.data_is_empty() {
+        return Err(VerifierError::ReceiptAlreadyExists.into());
+    }
+    let invoice = if public_inputs.invoice_id == [0u8; 32] {
+        None
+    } else {
+        let index_account = index_account.ok_or(ProgramError::NotEnoughAccountKeys)?;
+        let index = load_invoice_index(program_id, index_account, &public_inputs.invoice_id)?;
+        Some((index_account, index))
+    };
+    if let Some((_, (Some(index), _))) = &invoice {
+        index.check_new_payment(config.allow_invoice_overpayment)?;
+    }
+
+    public_inputs.validate(&config.input_policy, now)?;
+    public_inputs.check_expiry(now)?;
+    if !config.accepts_recipient_root(&public_inputs.recipient_set_root, now) {
+        return Err(VerifierError::RecipientRootMismatch.into());
+    }
+    DeniedRecipient::check(marker_account, program_id, &public_inputs.recipient_pubkey)?;
+    let stats = match stats_account {
+        Some(stats_account) => {
+            let mut stats =
+                load_recipient_stats(program_id, stats_account, &public_inputs.recipient_pubkey)?;
+            stats.admit(config, clock.slot)?;
+            Some((stats_account, stats))
+        }
+        None if config.max_verifications_per_window > 0 => {
+            msg!("Rate limiting needs the recipient's stats account");
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+        None => None,
+    };
+
+    verify_groth16_with_keys(keys, &INVOICE_PAYMENT_VK, proof, &public_inputs.to_scalars())?;
+
+    let (relayer, compute_unit_price) = if config.track_relayer {
+        (*payer.key, compute_unit_price)
+    } else {
+        (Pubkey::default(), 0)
+    };
+    let receipt = PaymentReceipt {
+        proof_hash,
+        recipient: public_inputs.recipient_pubkey,
+        min_amount: public_inputs.min_amount,
+        invoice_id: public_inputs.invoice_id,
+        payer: *payer.key,
+        status: ReceiptStatus::Active,
+        slot: clock.slot,
+        bump,
+        consumable,
+        delegate,
+        finalized: false,
+        revealed_amount: 0,
+        relayer,
+        compute_unit_price,
+        rebate_claimed: false,
+    };
+    if dry_run {
+        report_dry_run(receipt_account.key, receipt.to_account_data());
+        return Ok(());
+    }
+
+    profile_phase!(Phase::StateWrites, {
+        create_pda_account(
+            payer,
+            receipt_account,
+            system_program,
+            program_id,
+            PaymentReceipt::LEN,
+            &[RECEIPT_SEED, &proof_hash, &[bump]],
+        )?;
+        receipt.pack(&mut receipt_account.data.borrow_mut()[..])?;
+
+        if let Some((index_account, (existing, index_bump))) = invoice {
+            let mut index = match existing {
+                Some(index) => index,
+                None => {
+                    create_pda_account(
+                        payer,
+                        index_account,
+                        system_program,
+                        program_id,
+                        InvoiceIndex::LEN,
+                        &[INVOICE_SEED, &public_inputs.invoice_id, &[index_bump]],
+                    )?;
+                    InvoiceIndex {
+                        invoice_id: public_inputs.invoice_id,
+                        receipt: Pubkey::default(),
+                        status: ReceiptStatus::Active,
+                        payments: 0,
+                        bump: index_bump,
+                    }
+                }
+            };
+            index.record(*receipt_account.key);
+            index.pack(&mut index_account.data.borrow_mut()[..])?;
+        }
+
+        if let Some((stats_account, mut stats)) = stats {
+            stats.record(public_inputs.min_amount, clock.slot);
+            store_recipient_stats(program_id, payer, stats_account, system_program, &stats)?;
+        }
+    });
+
+    VerifierEvent::ProofVerified(ProofVerified {
+        proof_hash,
+        recipient: public_inputs.recipient_pubkey,
+        min_amount: public_inputs.min_amount,
+    })
+    .emit();
+    VerifierEvent::ReceiptCreated(ReceiptCreated {
+        receipt: *receipt_account.key,
+        proof_hash,
+        invoice_id: public_inputs.invoice_id,
+    })
+    .emit();
+
+    Ok(())
+}
+
+/// The index recorded at `index_account` for `invoice_id`, `None` before the
+/// invoice's first payment, and the PDA's bump
+fn load_invoice_index(
+    program_id: &Pubkey,
+    index_account: &AccountInfo,
+    invoice_id: &[u8; 32],
+) -> Result<(Option<InvoiceIndex>, u8), ProgramError> {
+    let (index_address, bump) = InvoiceIndex::address(program_id, invoice_id);
+    if index_address != *index_account.key {
+        msg!("Invoice index account does not match invoice id");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if index_account.data_is_empty() {
+        return Ok((None, bump));
+    }
+    if index_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Ok((Some(InvoiceIndex::unpack(&index_account.data.borrow())?), bump))
+}
+
+/// Whether `receipt` still proves a payment at `clock_slot`
+///
+/// A receipt is fresh while it is active and at most
+/// `config.max_receipt_age_slots` slots old; a limit of 0 never expires.
+/// Programs reading receipts over CPI should gate on this rather than on the
+/// receipt's existence alone.
+pub fn is_fresh(receipt: &PaymentReceipt, clock_slot: u64, config: &VerifierConfig) -> bool {
+    receipt.status == ReceiptStatus::Active
+        && (config.max_receipt_age_slots == 0
+            || clock_slot.saturating_sub(receipt.slot) <= config.max_receipt_age_slots)
+}
+
+/// Refresh a receipt's slot by presenting a new proof of the same payment
+///
+/// The new proof must be for the receipt's invoice and recipient and for at
+/// least its amount; the receipt keeps its original proof hash and terms.
+///
+/// Accounts expected:
+/// 0. `[signer]` Recipient named in the receipt
+/// 1. `[]` Config PDA: ["config"]
+/// 2. `[writable]` Receipt PDA: ["receipt", receipt_proof_hash]
+/// 3. `[]` Denylist marker PDA: ["denied", recipient]
+/// 4. `[]` Prepared key PDA: ["prepared-vk", 2]
+pub fn process_touch_receipt(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    receipt_proof_hash: &[u8; 32],
+    proof: &Groth16Proof,
+    public_inputs: &PaymentPublicInputsV3,
+) -> ProgramResult {
+    INVOICE_PAYMENT_VK.check_signal_layout(PaymentPublicInputsV3::SIGNAL_LAYOUT)?;
+
+    let layout = TOUCH_RECEIPT;
+    layout.check(accounts)?;
+    let recipient = &accounts[layout.recipient];
+    let config_account = &accounts[layout.config];
+    let receipt_account = &accounts[layout.receipt];
+    let marker_account = &accounts[layout.marker];
+    let prepared_account = &accounts[layout.prepared_key];
+    PreparedVk::load_active(prepared_account, program_id, CircuitId::InvoicePayment)?;
+
+    let (receipt_address, _) = pda::receipt_address(program_id, receipt_proof_hash);
+    if receipt_address != *receipt_account.key {
+        msg!("Receipt account does not match receipt hash");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if receipt_account.owner != program_id || receipt_account.data_is_empty() {
+        return Err(VerifierError::ReceiptNotFound.into());
+    }
+
+    let mut receipt = PaymentReceipt::unpack(&receipt_account.data.borrow())?;
+    if recipient.key.to_bytes() != receipt.recipient {
+        return Err(VerifierError::Unauthorized.into());
+    }
+    match receipt.status {
+        ReceiptStatus::Active => {}
+        ReceiptStatus::Refunded => return Err(VerifierError::ReceiptAlreadyRefunded.into()),
+        ReceiptStatus::Consumed => return Err(VerifierError::ReceiptAlreadyConsumed.into()),
+    }
+    if public_inputs.invoice_id != receipt.invoice_id
+        || public_inputs.recipient_pubkey != receipt.recipient
+        || public_inputs.min_amount < receipt.min_amount
+    {
+        return Err(VerifierError::ReceiptTermsMismatch.into());
+    }
+    // Re-presenting the recorded proof would refresh the receipt without
+    // anything new having been proved
+    let proof_hash = proof.hash(public_inputs);
+    if proof_hash == receipt.proof_hash {
+        return Err(VerifierError::ReceiptAlreadyExists.into());
+    }
+
+    let config = VerifierConfig::load(config_account, program_id)?;
+    let clock = Clock::get()?;
+    let now = clock.unix_timestamp;
+    public_inputs.validate(&config.input_policy, now)?;
+    public_inputs.check_expiry(now)?;
+    if !config.accepts_recipient_root(&public_inputs.recipient_set_root, now) {
+        return Err(VerifierError::RecipientRootMismatch.into());
+    }
+    DeniedRecipient::check(marker_account, program_id, &receipt.recipient)?;
+
+    verify_groth16(&INVOICE_PAYMENT_VK, proof, &public_inputs.to_scalars())?;
+
+    receipt.slot = clock.slot;
+    receipt.store(&mut receipt_account.data.borrow_mut()[..])?;
+
+    VerifierEvent::ProofVerified(ProofVerified {
+        proof_hash,
+        recipient: public_inputs.recipient_pubkey,
+        min_amount: public_inputs.min_amount,
+    })
+    .emit();
+
+    msg!("✓ Payment receipt refreshed");
+    Ok(())
+}
+
+/// Spend a consumable receipt
+///
+/// Only the receipt's recipient or its delegate may consume it, and only
+/// once; the receipt stays as a record with status `Consumed`, which is no
+/// longer fresh.
+///
+/// Accounts expected:
+/// 0. `[signer]` Recipient named in the receipt, or its delegate
+/// 1. `[writable]` Receipt PDA: ["receipt", receipt_proof_hash]
+pub fn process_consume_receipt(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    receipt_proof_hash: &[u8; 32],
+) -> ProgramResult {
+    let layout = CONSUME_RECEIPT;
+    layout.check(accounts)?;
+    let consumer = &accounts[layout.consumer];
+    let receipt_account = &accounts[layout.receipt];
+
+    let (receipt_address, _) = pda::receipt_address(program_id, receipt_proof_hash);
+    if receipt_address != *receipt_account.key {
+        msg!("Receipt account does not match receipt hash");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if receipt_account.owner != program_id || receipt_account.data_is_empty() {
+        return Err(VerifierError::ReceiptNotFound.into());
+    }
+
+    let mut receipt = PaymentReceipt::unpack(&receipt_account.data.borrow())?;
+    if !receipt.may_consume(consumer.key) {
+        return Err(VerifierError::Unauthorized.into());
+    }
+    if !receipt.consumable {
+        return Err(VerifierError::ReceiptNotConsumable.into());
+    }
+    match receipt.status {
+        ReceiptStatus::Active => {}
+        ReceiptStatus::Refunded => return Err(VerifierError::ReceiptAlreadyRefunded.into()),
+        ReceiptStatus::Consumed => return Err(VerifierError::ReceiptAlreadyConsumed.into()),
+    }
+
+    receipt.status = ReceiptStatus::Consumed;
+    receipt.store(&mut receipt_account.data.borrow_mut()[..])?;
+
+    VerifierEvent::ReceiptConsumed(ReceiptConsumed {
+        receipt: *receipt_account.key,
+        proof_hash: receipt.proof_hash,
+        consumer: *consumer.key,
+    })
+    .emit();
+
+    msg!("✓ Payment receipt consumed");
+    Ok(())
+}
+
+/// Mark a receipt final once the config's finality depth has passed since
+/// its slot
+///
+/// Anyone may call it. A receipt still in a slot that can be rolled back is
+/// refused with `ReceiptNotFinal`; one already finalized is left alone.
+/// Receipts created before version 3 are 1 byte short and grow, with the
+/// caller paying the extra rent.
+///
+/// Accounts expected:
+/// 0. `[signer, writable]` Caller, funds growing an older receipt
+/// 1. `[]` Config PDA
+/// 2. `[writable]` Receipt PDA: ["receipt", receipt_proof_hash]
+/// 3. `[]` System program, only for a receipt older than version 3
+pub fn process_finalize_receipt(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    receipt_proof_hash: &[u8; 32],
+) -> ProgramResult {
+    let layout = FINALIZE_RECEIPT;
+    layout.check(accounts)?;
+    let caller = &accounts[layout.caller];
+    let config_account = &accounts[layout.config];
+    let receipt_account = &accounts[layout.receipt];
+
+    let (receipt_address, _) = pda::receipt_address(program_id, receipt_proof_hash);
+    if receipt_address != *receipt_account.key {
+        msg!("Receipt account does not match receipt hash");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if receipt_account.owner != program_id || receipt_account.data_is_empty() {
+        return Err(VerifierError::ReceiptNotFound.into());
+    }
+
+    let config = VerifierConfig::load(config_account, program_id)?;
+    let mut receipt = PaymentReceipt::unpack(&receipt_account.data.borrow())?;
+    if receipt.finalized {
+        msg!("Payment receipt already finalized");
+        return Ok(());
+    }
+    if !config.receipt_final_at(receipt.slot, Clock::get()?.slot) {
+        return Err(VerifierError::ReceiptNotFinal.into());
+    }
+
+    if receipt_account.data_len() < PaymentReceipt::LEN_V3 {
+        let system_program = accounts
+            .get(layout.system_program)
+            .ok_or(ProgramError::NotEnoughAccountKeys)?;
+        grow_program_account(caller, receipt_account, system_program, PaymentReceipt::LEN_V3)?;
+    }
+    receipt.finalized = true;
+    receipt.store(&mut receipt_account.data.borrow_mut()[..])?;
+
+    VerifierEvent::ReceiptFinalized(ReceiptFinalized {
+        receipt: *receipt_account.key,
+        proof_hash: receipt.proof_hash,
+        slot: receipt.slot,
+    })
+    .emit();
+
+    msg!("✓ Payment receipt finalized");
+    Ok(())
+}
+
+/// Rewrite a receipt of an older layout at the current one
+///
+/// Anyone may call it. The caller pays the rent of the added bytes and is
+/// paid it back out of the config's lamports above its own rent-exempt
+/// minimum, so far as they go; the admin funds migrations by transferring
+/// lamports to the config. The fields an older receipt lacks take the
+/// defaults it already reads with. A receipt at the current layout is
+/// refused with `ReceiptAlreadyMigrated`.
+///
+/// Accounts expected:
+/// 0. `[signer, writable]` Caller, funds the growth and is reimbursed
+/// 1. `[writable]` Config PDA, the reimbursement pool
+/// 2. `[writable]` Receipt PDA: ["receipt", receipt_proof_hash]
+/// 3. `[]` System program
+pub fn process_migrate_receipt(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    receipt_proof_hash: &[u8; 32],
+) -> ProgramResult {
+    let layout = MIGRATE_RECEIPT;
+    layout.check(accounts)?;
+    let caller = &accounts[layout.caller];
+    let config_account = &accounts[layout.config];
+    let receipt_account = &accounts[layout.receipt];
+    let system_program = &accounts[layout.system_program];
+
+    let (receipt_address, _) = pda::receipt_address(program_id, receipt_proof_hash);
+    if receipt_address != *receipt_account.key {
+        msg!("Receipt account does not match receipt hash");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if receipt_account.owner != program_id || receipt_account.data_is_empty() {
+        return Err(VerifierError::ReceiptNotFound.into());
+    }
+
+    VerifierConfig::load(config_account, program_id)?;
+    let receipt = PaymentReceipt::unpack(&receipt_account.data.borrow())?;
+    let version = receipt_account.data.borrow()[1];
+    if version == PaymentReceipt::VERSION {
+        return Err(VerifierError::ReceiptAlreadyMigrated.into());
+    }
+
+    let rent = Rent::get()?;
+    let top_up = rent
+        .minimum_balance(PaymentReceipt::LEN)
+        .saturating_sub(receipt_account.lamports());
+    grow_program_account(caller, receipt_account, system_program, PaymentReceipt::LEN)?;
+    receipt.pack(&mut receipt_account.data.borrow_mut()[..])?;
+
+    let pool = config_account
+        .lamports()
+        .saturating_sub(rent.minimum_balance(config_account.data_len()));
+    let refund = top_up.min(pool);
+    if refund > 0 {
+        **config_account.try_borrow_mut_lamports()? -= refund;
+        **caller.try_borrow_mut_lamports()? += refund;
+    }
+    sol_log_64(version as u64, PaymentReceipt::VERSION as u64, top_up, refund, 0);
+
+    msg!("✓ Payment receipt migrated");
+    Ok(())
+}
+
+/// Pay the relayer recorded in a receipt a rebate out of the recipient's
+/// rebate deposit
+///
+/// Only the receipt's recipient may claim, only to the relayer the receipt
+/// recorded, and only once per receipt. The deposit is a system account at
+/// ["rebate-deposit", merchant] that the merchant funds with plain transfers;
+/// the program signs for it. It must keep at least its rent-exempt minimum
+/// or be emptied. A receipt recorded while the config did not track relayers
+/// names none and fails with `RelayerMismatch`.
+///
+/// Accounts expected:
+/// 0. `[signer]` Merchant: the recipient named in the receipt
+/// 1. `[writable]` Rebate deposit PDA: ["rebate-deposit", merchant]
+/// 2. `[writable]` Relayer recorded in the receipt
+/// 3. `[writable]` Receipt PDA: ["receipt", receipt_proof_hash]
+/// 4. `[]` System program
+pub fn process_claim_relayer_rebate(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    receipt_proof_hash: &[u8; 32],
+    amount: u64,
+) -> ProgramResult {
+    let layout = CLAIM_RELAYER_REBATE;
+    layout.check(accounts)?;
+    let merchant = &accounts[layout.merchant];
+    let deposit_account = &accounts[layout.deposit];
+    let relayer = &accounts[layout.relayer];
+    let receipt_account = &accounts[layout.receipt];
+    let system_program = &accounts[layout.system_program];
+    if amount == 0 {
+        msg!("Rebate amount must be nonzero");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let (receipt_address, _) = pda::receipt_address(program_id, receipt_proof_hash);
+    if receipt_address != *receipt_account.key {
+        msg!("Receipt account does not match receipt hash");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if receipt_account.owner != program_id || receipt_account.data_is_empty() {
+        return Err(VerifierError::ReceiptNotFound.into());
+    }
+
+    let mut receipt = PaymentReceipt::unpack(&receipt_account.data.borrow())?;
+    if merchant.key.to_bytes() != receipt.recipient {
+        return Err(VerifierError::Unauthorized.into());
+    }
+    if receipt.relayer == Pubkey::default() || *relayer.key != receipt.relayer {
+        return Err(VerifierError::RelayerMismatch.into());
+    }
+    if receipt.rebate_claimed {
+        return Err(VerifierError::RebateAlreadyClaimed.into());
+    }
+
+    let (deposit_address, bump) = pda::rebate_deposit_address(program_id, merchant.key);
+    if deposit_address != *deposit_account.key {
+        msg!("Rebate deposit does not match merchant");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    invoke_signed(
+        &system_instruction::transfer(deposit_account.key, relayer.key, amount),
+        &[deposit_account.clone(), relayer.clone(), system_program.clone()],
+        &[&[REBATE_DEPOSIT_SEED, merchant.key.as_ref(), &[bump]]],
+    )?;
+
+    receipt.rebate_claimed = true;
+    receipt.store(&mut receipt_account.data.borrow_mut()[..])?;
+
+    VerifierEvent::RelayerRebateClaimed(RelayerRebateClaimed {
+        receipt: *receipt_account.key,
+        relayer: *relayer.key,
+        amount,
+    })
+    .emit();
+
+    msg!("✓ Relayer rebate paid (lamports):");
+    sol_log_64(amount, 0, 0, 0, 0);
+    Ok(())
+}
+
+/// Set a `cpi::ReceiptStatus` for the receipt of `receipt_proof_hash` as
+/// return data
+///
+/// Read-only, so a program can ask through CPI without parsing the receipt
+/// account. A receipt that does not exist fails with `ReceiptNotFound`
+/// unless `allow_missing` is set, when it reads as `exists` false.
+///
+/// Accounts expected:
+/// 0. `[]` Receipt PDA: ["receipt", receipt_proof_hash]
+pub fn process_get_receipt_status(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    receipt_proof_hash: &[u8; 32],
+    allow_missing: bool,
+) -> ProgramResult {
+    let layout = GET_RECEIPT_STATUS;
+    layout.check(accounts)?;
+    let receipt_account = &accounts[layout.receipt];
+
+    let (receipt_address, _) = pda::receipt_address(program_id, receipt_proof_hash);
+    if receipt_address != *receipt_account.key {
+        msg!("Receipt account does not match receipt hash");
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    let status = if receipt_account.owner != program_id || receipt_account.data_is_empty() {
+        if !allow_missing {
+            return Err(VerifierError::ReceiptNotFound.into());
+        }
+        cpi::ReceiptStatus::of(None)
+    } else {
+        let receipt = PaymentReceipt::unpack(&receipt_account.data.borrow())?;
+        cpi::ReceiptStatus::of(Some(&receipt))
+    };
+    set_return_data(&status.try_to_vec()?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{AdminSigners, InputPolicy};
+
+    fn config(max_receipt_age_slots: u64) -> VerifierConfig {
+        VerifierConfig {
+            admin: Pubkey::new_unique(),
+            pending_admin: Pubkey::default(),
+            recipient_set_root: [0u8; 32],
+            previous_recipient_set_root: [0u8; 32],
+            previous_root_valid_until: 0,
+            recipient_root_grace_seconds: 0,
+            epoch_length_seconds: 0,
+            input_policy: InputPolicy::default(),
+            max_receipt_age_slots,
+            bump: 255,
+            audit_enabled: false,
+            max_key_deprecation_slots: 0,
+            admin_signers: AdminSigners::default(),
+            allow_invoice_overpayment: false,
+            max_verifications_per_window: 0,
+            window_slots: 0,
+            finality_slots: 0,
+            scratch_ttl_slots: 0,
+            track_relayer: false,
+        }
+    }
+
+    fn receipt(slot: u64) -> PaymentReceipt {
+        PaymentReceipt {
+            proof_hash: [1u8; 32],
+            recipient: [2u8; 32],
+            min_amount: 1000000,
+            invoice_id: [3u8; 32],
+            payer: Pubkey::new_unique(),
+            status: ReceiptStatus::Active,
+            slot,
+            bump: 255,
+            consumable: false,
+            delegate: Pubkey::default(),
+            finalized: false,
+            revealed_amount: 0,
+            relayer: Pubkey::default(),
+            compute_unit_price: 0,
+            rebate_claimed: false,
+        }
+    }
+
+    #[test]
+    fn test_is_fresh_boundaries() {
+        let config = config(100);
+        assert!(is_fresh(&receipt(1000), 1000, &config));
+        assert!(is_fresh(&receipt(1000), 1100, &config));
+        assert!(!is_fresh(&receipt(1000), 1101, &config));
+
+        // A clock behind the receipt slot does not underflow into "stale"
+        assert!(is_fresh(&receipt(1000), 999, &config));
+    }
+
+    #[test]
+    fn test_is_fresh_unbounded_and_refunded() {
+        assert!(is_fresh(&receipt(0), u64::MAX, &config(0)));
+
+        let refunded = PaymentReceipt {
+            status: ReceiptStatus::Refunded,
+            ..receipt(1000)
+        };
+        assert!(!is_fresh(&refunded, 1000, &config(0)));
+        assert!(!is_fresh(&refunded, 1000, &config(100)));
+    }
+}
@@ -0,0 +1,85 @@
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, msg, program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+use crate::{
+    events::{ReceiptRefunded, VerifierEvent},
+    layout::VERIFY_REFUND,
+    pda,
+    state::{InvoiceIndex, PaymentReceipt, PreparedVk, ReceiptStatus, StateAccount},
+    verify_groth16, CircuitId, Groth16Proof, RefundPublicInputs, VerifierError,
+};
+
+/// Verify a refund proof and mark the referenced `PaymentReceipt` refunded
+///
+/// The receipt is checked before the pairing, so refunds of unknown or already
+/// refunded payments are rejected without paying for the cryptography.
+///
+/// Accounts expected:
+/// 0. `[writable]` Receipt PDA: ["receipt", receipt_proof_hash]
+/// 1. `[]` Prepared key PDA: ["prepared-vk", 4]
+/// 2. `[writable]` (optional) Invoice index PDA: ["invoice", invoice_id]; marked
+///    refunded when it points at this receipt, so the invoice can be paid again
+pub fn process_verify_refund(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    proof: &Groth16Proof,
+    public_inputs: &RefundPublicInputs,
+) -> ProgramResult {
+    let vk = CircuitId::Refund.verification_key();
+    vk.check_signal_layout(RefundPublicInputs::SIGNAL_LAYOUT)?;
+
+    let layout = VERIFY_REFUND;
+    layout.check(accounts)?;
+    let receipt_account = &accounts[layout.receipt];
+    let prepared_account = &accounts[layout.prepared_key];
+    let index_account = accounts.get(layout.invoice_index);
+    PreparedVk::load_active(prepared_account, program_id, CircuitId::Refund)?;
+
+    let (receipt_address, _) = pda::receipt_address(program_id, &public_inputs.receipt_proof_hash);
+    if receipt_address != *receipt_account.key {
+        msg!("Receipt account does not match receipt hash");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if receipt_account.owner != program_id || receipt_account.data_is_empty() {
+        return Err(VerifierError::ReceiptNotFound.into());
+    }
+
+    let mut receipt = PaymentReceipt::unpack(&receipt_account.data.borrow())?;
+    if receipt.status == ReceiptStatus::Refunded {
+        return Err(VerifierError::ReceiptAlreadyRefunded.into());
+    }
+    if let Some(index_account) = index_account {
+        let (index_address, _) = InvoiceIndex::address(program_id, &receipt.invoice_id);
+        if index_address != *index_account.key {
+            msg!("Invoice index account does not match receipt's invoice id");
+            return Err(ProgramError::InvalidSeeds);
+        }
+        if index_account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+    }
+
+    verify_groth16(vk, proof, &public_inputs.to_scalars())?;
+
+    receipt.status = ReceiptStatus::Refunded;
+    receipt.store(&mut receipt_account.data.borrow_mut()[..])?;
+
+    if let Some(index_account) = index_account {
+        let mut index = InvoiceIndex::unpack(&index_account.data.borrow())?;
+        if index.receipt == *receipt_account.key {
+            index.status = ReceiptStatus::Refunded;
+            index.pack(&mut index_account.data.borrow_mut()[..])?;
+        }
+    }
+
+    VerifierEvent::ReceiptRefunded(ReceiptRefunded {
+        receipt: *receipt_account.key,
+        refund_recipient: public_inputs.refund_recipient,
+    })
+    .emit();
+
+    msg!("✓ Payment receipt refunded");
+    Ok(())
+}
@@ -0,0 +1,289 @@
+// Relayer allowlist
+// The allowlist is a singleton PDA ["relayers"] holding the relayers the
+// admin has approved. Layout: the two-byte `StateAccount` header, a
+// `RelayerListHeader`, then 32-byte slots of which the first `count` hold
+// relayer keys and the rest are zero. The list is created with
+// `RELAYER_LIST_INITIAL_SLOTS` slots, and `AddRelayer` reallocs it
+// `RELAYER_LIST_GROWTH_SLOTS` at a time, up to `MAX_RELAYERS`, so a full list
+// never means migrating to a bigger account. Removing a relayer moves the last
+// one into its slot; the account does not shrink.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey};
+
+use crate::{
+    state::{ACCOUNT_HEADER_LEN, RELAYER_LIST_SEED},
+    VerifierError,
+};
+
+/// Slots the list is created with
+pub const RELAYER_LIST_INITIAL_SLOTS: usize = 8;
+
+/// Slots added each time the list fills up
+pub const RELAYER_LIST_GROWTH_SLOTS: usize = 8;
+
+/// Hard cap on the list, and so on the account size
+pub const MAX_RELAYERS: usize = 256;
+
+/// First byte of the relayer list, distinct from every `StateAccount` discriminator
+pub const RELAYER_LIST_DISCRIMINATOR: u8 = 9;
+
+/// Layout version of the relayer list
+pub const RELAYER_LIST_VERSION: u8 = 1;
+
+/// Bookkeeping stored between the account header and the slots
+#[derive(BorshSerialize, BorshDeserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RelayerListHeader {
+    /// Slots in use, from the first
+    pub count: u32,
+    pub bump: u8,
+}
+
+impl RelayerListHeader {
+    pub const LEN: usize = 4 + 1;
+}
+
+/// Offset of the first slot
+const SLOTS_OFFSET: usize = ACCOUNT_HEADER_LEN + RelayerListHeader::LEN;
+
+/// Account size of a list with `slots` slots
+pub const fn relayer_list_len(slots: usize) -> usize {
+    SLOTS_OFFSET + slots * 32
+}
+
+/// Write an empty list into account data with `RELAYER_LIST_INITIAL_SLOTS`
+/// slots
+pub fn initialize(dst: &mut [u8], bump: u8) -> Result<(), VerifierError> {
+    if dst.len() != relayer_list_len(RELAYER_LIST_INITIAL_SLOTS) {
+        return Err(VerifierError::InvalidAccountLength);
+    }
+    dst.fill(0);
+    dst[0] = RELAYER_LIST_DISCRIMINATOR;
+    dst[1] = RELAYER_LIST_VERSION;
+    write_header(dst, &RelayerListHeader { count: 0, bump })
+}
+
+/// Slots an account of `len` bytes has room for
+fn capacity(len: usize) -> usize {
+    (len - SLOTS_OFFSET) / 32
+}
+
+/// Read the list header, checking the account header the way
+/// `StateAccount::unpack` does
+pub fn read_header(src: &[u8]) -> Result<RelayerListHeader, VerifierError> {
+    let header = match src {
+        [RELAYER_LIST_DISCRIMINATOR, RELAYER_LIST_VERSION, ..]
+            if src.len() >= SLOTS_OFFSET
+                && (src.len() - SLOTS_OFFSET) % 32 == 0
+                && src.len() <= relayer_list_len(MAX_RELAYERS) =>
+        {
+            RelayerListHeader::try_from_slice(&src[ACCOUNT_HEADER_LEN..SLOTS_OFFSET])
+                .map_err(|_| VerifierError::MalformedAccountData)?
+        }
+        [RELAYER_LIST_DISCRIMINATOR, RELAYER_LIST_VERSION, ..] => {
+            return Err(VerifierError::InvalidAccountLength)
+        }
+        [RELAYER_LIST_DISCRIMINATOR, _, ..] => {
+            return Err(VerifierError::UnsupportedAccountVersion)
+        }
+        [_, _, ..] => return Err(VerifierError::WrongAccountDiscriminator),
+        _ => return Err(VerifierError::InvalidAccountLength),
+    };
+    if header.count as usize > capacity(src.len()) {
+        return Err(VerifierError::MalformedAccountData);
+    }
+    Ok(header)
+}
+
+/// Relayers on the list, in slot order
+pub fn read_relayers(data: &[u8]) -> Result<Vec<Pubkey>, VerifierError> {
+    let header = read_header(data)?;
+    Ok((0..header.count as usize).map(|slot| read_slot(data, slot)).collect())
+}
+
+/// Whether `relayer` is on the list
+pub fn contains(data: &[u8], relayer: &Pubkey) -> Result<bool, VerifierError> {
+    Ok(read_relayers(data)?.contains(relayer))
+}
+
+/// Account size needed to add one more relayer: the current size while a
+/// slot is free, otherwise `RELAYER_LIST_GROWTH_SLOTS` more slots, capped at
+/// `MAX_RELAYERS`
+pub fn len_for_one_more(data: &[u8]) -> Result<usize, VerifierError> {
+    let header = read_header(data)?;
+    let slots = capacity(data.len());
+    if (header.count as usize) < slots {
+        return Ok(data.len());
+    }
+    if slots >= MAX_RELAYERS {
+        return Err(VerifierError::RelayerListFull);
+    }
+    Ok(relayer_list_len((slots + RELAYER_LIST_GROWTH_SLOTS).min(MAX_RELAYERS)))
+}
+
+/// Put `relayer` in the first free slot; false when it is already listed
+///
+/// The account must already have a free slot; see `len_for_one_more`.
+pub fn add(data: &mut [u8], relayer: &Pubkey) -> Result<bool, VerifierError> {
+    let mut header = read_header(data)?;
+    if contains(data, relayer)? {
+        return Ok(false);
+    }
+    let slot = header.count as usize;
+    if slot >= capacity(data.len()) {
+        return Err(VerifierError::RelayerListFull);
+    }
+    write_slot(data, slot, relayer);
+    header.count += 1;
+    write_header(data, &header)?;
+    Ok(true)
+}
+
+/// Take `relayer` off the list; false when it was not listed
+pub fn remove(data: &mut [u8], relayer: &Pubkey) -> Result<bool, VerifierError> {
+    let mut header = read_header(data)?;
+    let relayers = read_relayers(data)?;
+    let Some(slot) = relayers.iter().position(|listed| listed == relayer) else {
+        return Ok(false);
+    };
+    let last = relayers.len() - 1;
+    write_slot(data, slot, &relayers[last]);
+    write_slot(data, last, &Pubkey::default());
+    header.count -= 1;
+    write_header(data, &header)?;
+    Ok(true)
+}
+
+/// Check that `list_account` is this program's relayer list
+pub fn check_address(program_id: &Pubkey, list_account: &AccountInfo) -> Result<(), ProgramError> {
+    if list_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let header = read_header(&list_account.data.borrow())?;
+    let address =
+        Pubkey::create_program_address(&[RELAYER_LIST_SEED, &[header.bump]], program_id)?;
+    if address != *list_account.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    Ok(())
+}
+
+fn read_slot(data: &[u8], slot: usize) -> Pubkey {
+    let start = SLOTS_OFFSET + slot * 32;
+    Pubkey::new_from_array(data[start..start + 32].try_into().unwrap())
+}
+
+fn write_slot(data: &mut [u8], slot: usize, relayer: &Pubkey) {
+    let start = SLOTS_OFFSET + slot * 32;
+    data[start..start + 32].copy_from_slice(relayer.as_ref());
+}
+
+fn write_header(dst: &mut [u8], header: &RelayerListHeader) -> Result<(), VerifierError> {
+    header
+        .serialize(&mut &mut dst[ACCOUNT_HEADER_LEN..SLOTS_OFFSET])
+        .map_err(|_| VerifierError::InvalidAccountLength)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list() -> Vec<u8> {
+        let mut data = vec![0u8; relayer_list_len(RELAYER_LIST_INITIAL_SLOTS)];
+        initialize(&mut data, 253).unwrap();
+        data
+    }
+
+    fn relayer(n: usize) -> Pubkey {
+        let mut key = [1u8; 32];
+        key[..8].copy_from_slice(&(n as u64).to_le_bytes());
+        Pubkey::new_from_array(key)
+    }
+
+    /// Add `relayer`, growing `data` as `AddRelayer` reallocs the account
+    fn grow_and_add(data: &mut Vec<u8>, relayer: &Pubkey) -> Result<bool, VerifierError> {
+        let len = len_for_one_more(data)?;
+        data.resize(len, 0);
+        add(data, relayer)
+    }
+
+    #[test]
+    fn test_add_and_remove() {
+        let mut data = list();
+        assert_eq!(read_relayers(&data), Ok(vec![]));
+        assert_eq!(relayer_list_len(RELAYER_LIST_INITIAL_SLOTS), 2 + 5 + 8 * 32);
+
+        for n in 0..3 {
+            assert_eq!(add(&mut data, &relayer(n)), Ok(true));
+        }
+        assert_eq!(add(&mut data, &relayer(1)), Ok(false));
+        assert_eq!(read_relayers(&data), Ok((0..3).map(relayer).collect()));
+
+        // The last relayer moves into the freed slot, and its old slot is zeroed
+        assert_eq!(remove(&mut data, &relayer(0)), Ok(true));
+        assert_eq!(remove(&mut data, &relayer(0)), Ok(false));
+        assert_eq!(read_relayers(&data), Ok(vec![relayer(2), relayer(1)]));
+        assert!(data[relayer_list_len(2)..].iter().all(|byte| *byte == 0));
+        assert_eq!(read_header(&data).unwrap().bump, 253);
+    }
+
+    #[test]
+    fn test_growth_across_initial_slots() {
+        let mut data = list();
+        for n in 0..RELAYER_LIST_INITIAL_SLOTS {
+            assert_eq!(grow_and_add(&mut data, &relayer(n)), Ok(true));
+        }
+        assert_eq!(data.len(), relayer_list_len(RELAYER_LIST_INITIAL_SLOTS));
+
+        // Full: adding without growing fails, and the next add grows by a chunk
+        assert_eq!(
+            add(&mut data, &relayer(8)),
+            Err(VerifierError::RelayerListFull)
+        );
+        assert_eq!(grow_and_add(&mut data, &relayer(8)), Ok(true));
+        assert_eq!(
+            data.len(),
+            relayer_list_len(RELAYER_LIST_INITIAL_SLOTS + RELAYER_LIST_GROWTH_SLOTS)
+        );
+        assert_eq!(read_relayers(&data), Ok((0..9).map(relayer).collect()));
+    }
+
+    #[test]
+    fn test_hard_cap() {
+        let mut data = list();
+        for n in 0..MAX_RELAYERS {
+            assert_eq!(grow_and_add(&mut data, &relayer(n)), Ok(true));
+        }
+        assert_eq!(data.len(), relayer_list_len(MAX_RELAYERS));
+        assert_eq!(len_for_one_more(&data), Err(VerifierError::RelayerListFull));
+
+        // A freed slot can be reused without growing
+        let first = read_relayers(&data).unwrap()[0];
+        remove(&mut data, &first).unwrap();
+        assert_eq!(len_for_one_more(&data), Ok(data.len()));
+    }
+
+    #[test]
+    fn test_header_rejections() {
+        let data = list();
+        assert_eq!(
+            read_header(&data[..data.len() - 1]),
+            Err(VerifierError::InvalidAccountLength)
+        );
+        let mut oversized = data.clone();
+        oversized.resize(relayer_list_len(MAX_RELAYERS + 1), 0);
+        assert_eq!(read_header(&oversized), Err(VerifierError::InvalidAccountLength));
+
+        let mut wrong = data.clone();
+        wrong[0] = 7;
+        assert_eq!(read_header(&wrong), Err(VerifierError::WrongAccountDiscriminator));
+        wrong = data.clone();
+        wrong[1] = 2;
+        assert_eq!(read_header(&wrong), Err(VerifierError::UnsupportedAccountVersion));
+
+        // More relayers claimed than there are slots
+        wrong = data.clone();
+        wrong[2] = RELAYER_LIST_INITIAL_SLOTS as u8 + 1;
+        assert_eq!(read_header(&wrong), Err(VerifierError::MalformedAccountData));
+    }
+}
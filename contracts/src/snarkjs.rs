@@ -0,0 +1,307 @@
+// snarkjs JSON exports: proof.json, public.json and verification_key.json
+// Enabled with the `snarkjs` feature. Coordinates are decimal strings; G2
+// coordinates are `[c0, c1]` where the program takes `c1 || c0`.
+
+use serde_json::Value;
+
+use crate::{
+    client::ClientError, endian::Be32, field::is_canonical_fr, inline_key::InlineVerificationKey,
+    G1Point, G2Point, Groth16Proof, VerificationKey, MAX_PUBLIC_INPUTS,
+};
+
+/// A verification key read at runtime rather than compiled in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnedVerificationKey {
+    pub alpha_g1: G1Point,
+    pub beta_g2: G2Point,
+    pub gamma_g2: G2Point,
+    pub delta_g2: G2Point,
+    pub ic: Vec<G1Point>,
+    /// `nPublic`, one less than the number of IC points
+    pub num_public_inputs: usize,
+}
+
+impl OwnedVerificationKey {
+    /// The key in the form `verify_groth16` takes
+    ///
+    /// snarkjs exports carry no signal names, so the layout hash is zero and
+    /// only the input count is checked.
+    pub fn as_key(&self) -> VerificationKey<'_> {
+        VerificationKey {
+            alpha_g1: self.alpha_g1,
+            beta_g2: self.beta_g2,
+            gamma_g2: self.gamma_g2,
+            delta_g2: self.delta_g2,
+            ic: &self.ic,
+            num_public_inputs: self.num_public_inputs,
+            signal_layout_hash: [0u8; 32],
+        }
+    }
+
+    /// The key as `VerifyWithInlineKey` takes it
+    pub fn to_inline(&self) -> InlineVerificationKey {
+        InlineVerificationKey {
+            alpha_g1: self.alpha_g1,
+            beta_g2: self.beta_g2,
+            gamma_g2: self.gamma_g2,
+            delta_g2: self.delta_g2,
+            ic: self.ic.clone(),
+        }
+    }
+}
+
+/// A snarkjs proof.json
+pub fn parse_proof(json: &Value) -> Result<Groth16Proof, ClientError> {
+    Ok(Groth16Proof {
+        a: g1(&json["pi_a"])?,
+        b: g2(&json["pi_b"])?,
+        c: g1(&json["pi_c"])?,
+    })
+}
+
+/// A snarkjs public.json, each signal as the u64 scalar the program takes
+///
+/// More than `MAX_PUBLIC_INPUTS` signals are refused before any is parsed.
+pub fn parse_public_signals(json: &Value) -> Result<Vec<u64>, ClientError> {
+    let signals = json
+        .as_array()
+        .filter(|signals| signals.len() <= MAX_PUBLIC_INPUTS)
+        .ok_or(ClientError::MalformedPayload("public signals"))?;
+    signals
+        .iter()
+        .map(|signal| {
+            signal
+                .as_str()
+                .and_then(|signal| signal.parse().ok())
+                .ok_or(ClientError::MalformedPayload("public signal"))
+        })
+        .collect()
+}
+
+/// A snarkjs public.json as full-width field elements, for
+/// `VerifyWithInlineKey`
+///
+/// Signals must be below the scalar field modulus; more than
+/// `MAX_PUBLIC_INPUTS` are refused before any is parsed.
+pub fn parse_field_signals(json: &Value) -> Result<Vec<Be32>, ClientError> {
+    let signals = json
+        .as_array()
+        .filter(|signals| signals.len() <= MAX_PUBLIC_INPUTS)
+        .ok_or(ClientError::MalformedPayload("public signals"))?;
+    signals
+        .iter()
+        .map(|signal| {
+            signal
+                .as_str()
+                .and_then(decimal_to_be_bytes)
+                .filter(is_canonical_fr)
+                .map(Be32::from_syscall)
+                .ok_or(ClientError::MalformedPayload("public signal"))
+        })
+        .collect()
+}
+
+/// A snarkjs verification_key.json
+///
+/// `nPublic` must be at most `MAX_PUBLIC_INPUTS` and match the IC points,
+/// which are only parsed once it does.
+pub fn parse_verification_key(json: &Value) -> Result<OwnedVerificationKey, ClientError> {
+    let num_public_inputs = json["nPublic"]
+        .as_u64()
+        .and_then(|n| usize::try_from(n).ok())
+        .filter(|&n| n <= MAX_PUBLIC_INPUTS)
+        .ok_or(ClientError::MalformedPayload("verification key"))?;
+    let ic = json["IC"]
+        .as_array()
+        .filter(|ic| ic.len() == num_public_inputs + 1)
+        .ok_or(ClientError::MalformedPayload("verification key"))?
+        .iter()
+        .map(g1)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(OwnedVerificationKey {
+        alpha_g1: g1(&json["vk_alpha_1"])?,
+        beta_g2: g2(&json["vk_beta_2"])?,
+        gamma_g2: g2(&json["vk_gamma_2"])?,
+        delta_g2: g2(&json["vk_delta_2"])?,
+        ic,
+        num_public_inputs,
+    })
+}
+
+fn coordinate(value: &Value) -> Result<[u8; 32], ClientError> {
+    value
+        .as_str()
+        .and_then(decimal_to_be_bytes)
+        .ok_or(ClientError::MalformedPayload("coordinate"))
+}
+
+/// `[x, y, z]` in affine form; z is not read
+fn g1(point: &Value) -> Result<G1Point, ClientError> {
+    let mut bytes = [0u8; 64];
+    bytes[..32].copy_from_slice(&coordinate(&point[0])?);
+    bytes[32..].copy_from_slice(&coordinate(&point[1])?);
+    Ok(G1Point::new(bytes))
+}
+
+/// `[[x.c0, x.c1], [y.c0, y.c1], z]`, written as x.c1 || x.c0 || y.c1 || y.c0
+fn g2(point: &Value) -> Result<G2Point, ClientError> {
+    let mut bytes = [0u8; 128];
+    for (slot, (coord, limb)) in [(0, 1), (0, 0), (1, 1), (1, 0)].into_iter().enumerate() {
+        bytes[32 * slot..32 * (slot + 1)].copy_from_slice(&coordinate(&point[coord][limb])?);
+    }
+    Ok(G2Point::new(bytes))
+}
+
+/// A decimal string as a 32-byte big-endian integer, if it fits
+pub fn decimal_to_be_bytes(s: &str) -> Option<[u8; 32]> {
+    if s.is_empty() {
+        return None;
+    }
+    let mut bytes = [0u8; 32];
+    for digit in s.chars() {
+        let mut carry = digit.to_digit(10)?;
+        for byte in bytes.iter_mut().rev() {
+            let value = *byte as u32 * 10 + carry;
+            *byte = value as u8;
+            carry = value >> 8;
+        }
+        if carry != 0 {
+            return None;
+        }
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limb(n: u8) -> [u8; 32] {
+        let mut bytes = [0u8; 32];
+        bytes[31] = n;
+        bytes
+    }
+
+    #[test]
+    fn test_decimal_to_be_bytes() {
+        let mut expected = [0u8; 32];
+        expected[30..].copy_from_slice(&[0x01, 0x00]);
+        assert_eq!(decimal_to_be_bytes("256"), Some(expected));
+        assert_eq!(decimal_to_be_bytes("0"), Some([0u8; 32]));
+
+        // 2^256 - 1 fits, 2^256 does not
+        let two_256 = |last: &str| {
+            format!(
+                "1157920892373161954235709850086879078532699846656405640394575840079131296399{}",
+                last
+            )
+        };
+        assert_eq!(decimal_to_be_bytes(&two_256("35")), Some([0xff; 32]));
+        assert_eq!(decimal_to_be_bytes(&two_256("36")), None);
+
+        assert_eq!(decimal_to_be_bytes(""), None);
+        assert_eq!(decimal_to_be_bytes("12a"), None);
+        assert_eq!(decimal_to_be_bytes("-1"), None);
+    }
+
+    #[test]
+    fn test_proof_g2_order() {
+        let json: Value = serde_json::from_str(
+            r#"{
+                "pi_a": ["1", "2", "1"],
+                "pi_b": [["3", "4"], ["5", "6"], ["1", "0"]],
+                "pi_c": ["7", "8", "1"],
+                "protocol": "groth16"
+            }"#,
+        )
+        .unwrap();
+        let proof = parse_proof(&json).unwrap();
+
+        assert_eq!(proof.a.to_bytes()[..32], limb(1));
+        assert_eq!(proof.a.to_bytes()[32..], limb(2));
+        // x.c1 || x.c0 || y.c1 || y.c0
+        let b: Vec<u8> = [4, 3, 6, 5].into_iter().flat_map(limb).collect();
+        assert_eq!(&proof.b.to_bytes()[..], &b[..]);
+        assert_eq!(proof.c.to_bytes()[32..], limb(8));
+
+        let missing: Value = serde_json::from_str(r#"{"pi_a": ["1", "2"]}"#).unwrap();
+        assert_eq!(
+            parse_proof(&missing).unwrap_err(),
+            ClientError::MalformedPayload("coordinate")
+        );
+    }
+
+    #[test]
+    fn test_public_signals() {
+        let json: Value =
+            serde_json::from_str(r#"["1000000", "4", "5", "60", "1700000000"]"#).unwrap();
+        assert_eq!(parse_public_signals(&json), Ok(vec![1000000, 4, 5, 60, 1700000000]));
+
+        // Signals are u64 scalars on-chain; larger field elements cannot be bound
+        let json: Value = serde_json::from_str(r#"["18446744073709551616"]"#).unwrap();
+        assert_eq!(
+            parse_public_signals(&json),
+            Err(ClientError::MalformedPayload("public signal"))
+        );
+        let json: Value = serde_json::from_str(r#"[5]"#).unwrap();
+        assert!(parse_public_signals(&json).is_err());
+
+        // Oversized vectors are refused as a whole
+        let json = Value::from(vec!["1"; MAX_PUBLIC_INPUTS + 1]);
+        assert_eq!(
+            parse_public_signals(&json),
+            Err(ClientError::MalformedPayload("public signals"))
+        );
+        let json = Value::from(vec!["1"; MAX_PUBLIC_INPUTS]);
+        assert_eq!(parse_public_signals(&json), Ok(vec![1; MAX_PUBLIC_INPUTS]));
+    }
+
+    #[test]
+    fn test_field_signals() {
+        // r - 1 is the largest signal, r itself is not a field element
+        let r_minus_1 =
+            "21888242871839275222246405745257275088548364400416034343698204186575808495616";
+        let r = "21888242871839275222246405745257275088548364400416034343698204186575808495617";
+        let json = Value::from(vec!["256", r_minus_1]);
+        let signals = parse_field_signals(&json).unwrap();
+        assert_eq!(signals[0], Be32::from_u64(256));
+        assert!(is_canonical_fr(&signals[1].to_syscall()));
+
+        let json = Value::from(vec![r]);
+        assert_eq!(
+            parse_field_signals(&json),
+            Err(ClientError::MalformedPayload("public signal"))
+        );
+        let json = Value::from(vec!["1"; MAX_PUBLIC_INPUTS + 1]);
+        assert!(parse_field_signals(&json).is_err());
+    }
+
+    #[test]
+    fn test_repository_verification_key() {
+        let json: Value = serde_json::from_str(include_str!(
+            "../../circuits/build/verification_key.json"
+        ))
+        .unwrap();
+        let key = parse_verification_key(&json).unwrap();
+        assert_eq!(key.num_public_inputs, json["nPublic"].as_u64().unwrap() as usize);
+        assert_eq!(key.ic.len(), key.num_public_inputs + 1);
+        assert!(key.as_key().is_canonical());
+
+        let no_ic: Value = serde_json::from_str(r#"{"IC": []}"#).unwrap();
+        assert!(parse_verification_key(&no_ic).is_err());
+
+        // An IC count that disagrees with nPublic, and an nPublic over the cap
+        let mut short = json.clone();
+        short["IC"].as_array_mut().unwrap().pop();
+        assert!(parse_verification_key(&short).is_err());
+        let mut oversized = json.clone();
+        oversized["nPublic"] = Value::from(MAX_PUBLIC_INPUTS + 1);
+        let ic = vec![json["IC"][0].clone(); MAX_PUBLIC_INPUTS + 2];
+        oversized["IC"] = Value::from(ic);
+        assert_eq!(
+            parse_verification_key(&oversized),
+            Err(ClientError::MalformedPayload("verification key"))
+        );
+    }
+}
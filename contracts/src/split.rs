@@ -0,0 +1,224 @@
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, log::sol_log_64, msg,
+    program_error::ProgramError, pubkey::Pubkey,
+};
+use spl_token_2022::{
+    extension::StateWithExtensions,
+    onchain::invoke_transfer_checked,
+    state::{Account as TokenAccount, Mint},
+};
+
+use crate::{
+    events::{ProofVerified, ReceiptCreated, VerifierEvent},
+    layout::{check_no_extra_accounts, VerifySplitPaymentAccounts, VERIFY_SPLIT_PAYMENT},
+    pda,
+    state::{DeniedRecipient, PreparedVk, SplitPaymentReceipt, StateAccount, RECEIPT_SEED},
+    utils::{create_pda_account, report_dry_run},
+    verify_groth16, CircuitId, Groth16Proof, SplitPaymentPublicInputs, VerifierError,
+    SPLIT_PAYMENT_VK,
+};
+
+/// Token accounts settling both legs, validated against the statement
+struct Settlement<'a, 'info> {
+    source: &'a AccountInfo<'info>,
+    destinations: [&'a AccountInfo<'info>; 2],
+    token_program: &'a AccountInfo<'info>,
+    mint: &'a AccountInfo<'info>,
+    decimals: u8,
+    /// Accounts the mint's transfer hook needs, passed through to the token
+    /// program, which checks them against the hook's own account list
+    hook_accounts: &'a [AccountInfo<'info>],
+}
+
+/// Verify a split payment proof, record both legs, and optionally settle them
+///
+/// Settlement accounts are validated before the pairing so that a bad leg is
+/// rejected cheaply; both transfers happen in this instruction, so either leg
+/// failing reverts the receipt and the other transfer with it.
+///
+/// Legs settle with `transfer_checked` under whichever token program owns the
+/// mint, SPL Token or Token-2022. A Token-2022 transfer fee comes out of each
+/// leg, so the receipt records what each recipient's account actually gained.
+///
+/// The transfers hand control to the token program and, for a Token-2022
+/// mint, to its transfer hook. The token program must be SPL Token or
+/// Token-2022 and own the mint, and the runtime refuses any call back into
+/// the verifier from them or the hook.
+///
+/// A dry run validates the settlement accounts but neither records the
+/// receipt nor moves tokens; its receipt has no settled amounts.
+pub fn process_verify_split_payment(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    proof: &Groth16Proof,
+    public_inputs: &SplitPaymentPublicInputs,
+    settle: bool,
+    dry_run: bool,
+) -> ProgramResult {
+    SPLIT_PAYMENT_VK.check_signal_layout(SplitPaymentPublicInputs::SIGNAL_LAYOUT)?;
+
+    let layout = VERIFY_SPLIT_PAYMENT;
+    layout.check_leading(accounts)?;
+    let payer = &accounts[layout.payer];
+    let receipt_account = &accounts[layout.receipt];
+    let system_program = &accounts[layout.system_program];
+    let marker_accounts = [
+        &accounts[layout.first_marker],
+        &accounts[layout.second_marker],
+    ];
+    PreparedVk::load_active(&accounts[layout.prepared_key], program_id, CircuitId::SplitPayment)?;
+
+    for (marker_account, recipient) in marker_accounts.iter().zip(&public_inputs.recipients) {
+        DeniedRecipient::check(marker_account, program_id, recipient)?;
+    }
+
+    let settlement = if settle {
+        if accounts.len() < VerifySplitPaymentAccounts::LEN {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+        let source = &accounts[layout.source];
+        let destinations = [
+            &accounts[layout.first_destination],
+            &accounts[layout.second_destination],
+        ];
+        let token_program = &accounts[layout.token_program];
+        let mint = &accounts[layout.mint];
+
+        // The mint's owner decides the token program
+        if !is_token_program(mint.owner) || token_program.key != mint.owner {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if mint.key.to_bytes() != public_inputs.mint {
+            return Err(VerifierError::InvalidSettlementAccount.into());
+        }
+        let decimals = StateWithExtensions::<Mint>::unpack(&mint.data.borrow())
+            .map_err(|_| VerifierError::InvalidSettlementAccount)?
+            .base
+            .decimals;
+        for (leg, destination) in destinations.iter().enumerate() {
+            check_leg_token_account(destination, mint, &public_inputs.recipients[leg]).map_err(
+                |e| {
+                    msg!("Settlement account rejected for leg:");
+                    sol_log_64(leg as u64, 0, 0, 0, 0);
+                    e
+                },
+            )?;
+        }
+        Some(Settlement {
+            source,
+            destinations,
+            token_program,
+            mint,
+            decimals,
+            hook_accounts: &accounts[VerifySplitPaymentAccounts::LEN..],
+        })
+    } else {
+        // The settlement and hook accounts come only with `settle`
+        check_no_extra_accounts(&accounts[layout.source..])?;
+        None
+    };
+
+    let proof_hash = proof.hash(public_inputs);
+    let (receipt_address, bump) = pda::receipt_address(program_id, &proof_hash);
+    if receipt_address != *receipt_account.key {
+        msg!("Receipt account does not match proof hash");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if !receipt_account.data_is_empty() {
+        return Err(VerifierError::ReceiptAlreadyExists.into());
+    }
+
+    verify_groth16(&SPLIT_PAYMENT_VK, proof, &public_inputs.to_scalars())?;
+
+    let mut receipt = SplitPaymentReceipt {
+        proof_hash,
+        recipients: public_inputs.recipients,
+        min_amounts: public_inputs.min_amounts,
+        settled: settlement.is_some(),
+        payer: *payer.key,
+        bump,
+        settled_amounts: [0; 2],
+    };
+    if dry_run {
+        report_dry_run(receipt_account.key, receipt.to_account_data());
+        msg!("Dry run: nothing recorded or settled");
+        return Ok(());
+    }
+
+    create_pda_account(
+        payer,
+        receipt_account,
+        system_program,
+        program_id,
+        SplitPaymentReceipt::LEN,
+        &[RECEIPT_SEED, &proof_hash, &[bump]],
+    )?;
+
+    if let Some(settlement) = settlement {
+        for (leg, destination) in settlement.destinations.iter().enumerate() {
+            let before = token_amount(destination)?;
+            invoke_transfer_checked(
+                settlement.token_program.key,
+                settlement.source.clone(),
+                settlement.mint.clone(),
+                (*destination).clone(),
+                payer.clone(),
+                settlement.hook_accounts,
+                public_inputs.min_amounts[leg],
+                settlement.decimals,
+                &[],
+            )?;
+            receipt.settled_amounts[leg] = token_amount(destination)?.saturating_sub(before);
+        }
+        msg!("✓ Both split payment legs settled");
+    }
+    receipt.pack(&mut receipt_account.data.borrow_mut()[..])?;
+
+    for leg in 0..2 {
+        VerifierEvent::ProofVerified(ProofVerified {
+            proof_hash,
+            recipient: public_inputs.recipients[leg],
+            min_amount: public_inputs.min_amounts[leg],
+        })
+        .emit();
+    }
+    VerifierEvent::ReceiptCreated(ReceiptCreated {
+        receipt: *receipt_account.key,
+        proof_hash,
+        invoice_id: [0u8; 32],
+    })
+    .emit();
+
+    msg!("✓ Split payment receipt recorded");
+    Ok(())
+}
+
+fn is_token_program(program_id: &Pubkey) -> bool {
+    *program_id == spl_token::id() || *program_id == spl_token_2022::id()
+}
+
+/// Require a leg's destination to be a token account of the proven recipient
+/// in the proven mint, under the mint's token program
+fn check_leg_token_account(
+    account: &AccountInfo,
+    mint: &AccountInfo,
+    recipient: &[u8; 32],
+) -> ProgramResult {
+    if account.owner != mint.owner {
+        return Err(VerifierError::InvalidSettlementAccount.into());
+    }
+    let data = account.data.borrow();
+    let token_account = StateWithExtensions::<TokenAccount>::unpack(&data)
+        .map_err(|_| VerifierError::InvalidSettlementAccount)?
+        .base;
+    if token_account.owner.to_bytes() != *recipient || token_account.mint != *mint.key {
+        return Err(VerifierError::InvalidSettlementAccount.into());
+    }
+    Ok(())
+}
+
+/// Balance of a token account already checked by `check_leg_token_account`
+fn token_amount(account: &AccountInfo) -> Result<u64, ProgramError> {
+    let data = account.data.borrow();
+    Ok(StateWithExtensions::<TokenAccount>::unpack(&data)?.base.amount)
+}
@@ -0,0 +1,2230 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey};
+
+use crate::{
+    merkle::{MerkleTree, MERKLE_TREE_DEPTH},
+    pda,
+    transcript::HashBackend,
+    CircuitId, G1Point, VerificationKey, VerifierError,
+};
+
+/// PDA seed of the singleton program configuration: ["config"]
+pub const CONFIG_SEED: &[u8] = b"config";
+
+/// PDA seed prefix for payment receipts: ["receipt", proof_hash]
+pub const RECEIPT_SEED: &[u8] = b"receipt";
+
+/// PDA seed prefix for per-recipient statistics: ["stats", recipient_pubkey]
+pub const STATS_SEED: &[u8] = b"stats";
+
+/// PDA seed prefix for spent nullifiers:
+/// ["nullifier", seed version, circuit id, nullifier, epoch (u64 LE)]
+pub const NULLIFIER_SEED: &[u8] = b"nullifier";
+
+/// Layout version of the nullifier PDA seeds
+///
+/// Version 1 added the version and circuit id seeds. Nullifiers spent before
+/// then live at ["nullifier", nullifier, epoch (u64 LE)]; see
+/// `SpentNullifier::legacy_address`.
+pub const NULLIFIER_SEED_VERSION: u8 = 1;
+
+/// PDA seed prefix for staged batch uploads: ["batch-buffer", authority, nonce (u64 LE)]
+pub const BATCH_BUFFER_SEED: &[u8] = b"batch-buffer";
+
+/// PDA seed prefix for prepared verification keys: ["prepared-vk", circuit id]
+pub const PREPARED_VK_SEED: &[u8] = b"prepared-vk";
+
+/// PDA seed of the singleton audit log: ["audit-log"]
+pub const AUDIT_LOG_SEED: &[u8] = b"audit-log";
+
+/// PDA seed of the singleton shielded pool: ["pool"]
+pub const POOL_SEED: &[u8] = b"pool";
+
+/// PDA seed of the singleton relayer allowlist: ["relayers"]
+pub const RELAYER_LIST_SEED: &[u8] = b"relayers";
+
+/// PDA seed prefix for invoice indexes: ["invoice", invoice_id]
+pub const INVOICE_SEED: &[u8] = b"invoice";
+
+/// PDA seed prefix for denylist markers: ["denied", recipient]
+pub const DENIED_RECIPIENT_SEED: &[u8] = b"denied";
+
+/// PDA seed prefix for batch receipts: ["batch-receipt", transcript_hash]
+pub const BATCH_RECEIPT_SEED: &[u8] = b"batch-receipt";
+
+/// PDA seed prefix for merchants' relayer rebate deposits:
+/// ["rebate-deposit", merchant]
+pub const REBATE_DEPOSIT_SEED: &[u8] = b"rebate-deposit";
+
+/// Bytes ahead of every account body: discriminator, then layout version
+pub const ACCOUNT_HEADER_LEN: usize = 2;
+
+/// A fixed-size program account, stored as discriminator || version || Borsh body
+///
+/// The discriminator stops one account type being read as another (payment
+/// and split receipts share a seed prefix); the version is the hook for
+/// layout changes. To change a layout, bump `VERSION` and override
+/// `unpack_version` to decode bodies written at the old version as well.
+pub trait StateAccount: BorshSerialize + BorshDeserialize {
+    /// First byte of the account; never 0, so zeroed data is never accepted
+    const DISCRIMINATOR: u8;
+    /// Layout version written by `pack`
+    const VERSION: u8;
+    /// Full account size, header included
+    const LEN: usize;
+
+    /// Write the header and body into account data of exactly `LEN` bytes
+    fn pack(&self, dst: &mut [u8]) -> Result<(), VerifierError> {
+        if dst.len() != Self::LEN {
+            return Err(VerifierError::InvalidAccountLength);
+        }
+        dst[0] = Self::DISCRIMINATOR;
+        dst[1] = Self::VERSION;
+        self.serialize(&mut &mut dst[ACCOUNT_HEADER_LEN..])
+            .map_err(|_| VerifierError::InvalidAccountLength)
+    }
+
+    /// Read account data, checking the discriminator before anything else
+    fn unpack(src: &[u8]) -> Result<Self, VerifierError> {
+        match src {
+            [discriminator, version, body @ ..] if *discriminator == Self::DISCRIMINATOR => {
+                Self::unpack_version(*version, body)
+            }
+            [_, _, ..] => Err(VerifierError::WrongAccountDiscriminator),
+            _ => Err(VerifierError::InvalidAccountLength),
+        }
+    }
+
+    /// Decode a body written at `version`
+    ///
+    /// Only the current layout is known until a migration overrides this.
+    fn unpack_version(version: u8, body: &[u8]) -> Result<Self, VerifierError> {
+        if version != Self::VERSION {
+            return Err(VerifierError::UnsupportedAccountVersion);
+        }
+        if body.len() != Self::LEN - ACCOUNT_HEADER_LEN {
+            return Err(VerifierError::InvalidAccountLength);
+        }
+        Self::try_from_slice(body).map_err(|_| VerifierError::MalformedAccountData)
+    }
+
+    /// Packed account data, for clients and tests creating accounts directly
+    fn to_account_data(&self) -> Vec<u8> {
+        let mut data = vec![0u8; Self::LEN];
+        self.pack(&mut data).unwrap();
+        data
+    }
+}
+
+/// Lifecycle of a `PaymentReceipt`
+#[derive(BorshSerialize, BorshDeserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ReceiptStatus {
+    /// Payment verified and not refunded
+    Active,
+    /// A refund proof for this payment has been verified
+    Refunded,
+    /// A consumable receipt spent by `ConsumeReceipt`
+    Consumed,
+}
+
+/// Record of a verified payment, written by `VerifyAndRecord`
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PaymentReceipt {
+    /// `Groth16Proof::hash` of the verified proof and its public inputs
+    pub proof_hash: [u8; 32],
+    pub recipient: [u8; 32],
+    pub min_amount: u64,
+    /// Invoice the proof was bound to, for gateways matching receipts to requests
+    pub invoice_id: [u8; 32],
+    /// Account that paid rent for the receipt
+    pub payer: Pubkey,
+    pub status: ReceiptStatus,
+    /// Slot the receipt was recorded in, or last refreshed by `TouchReceipt`
+    pub slot: u64,
+    pub bump: u8,
+    /// Whether `ConsumeReceipt` may spend the receipt; added in version 2
+    pub consumable: bool,
+    /// Key allowed to consume the receipt besides the recipient;
+    /// `Pubkey::default()` when none. Added in version 2
+    pub delegate: Pubkey,
+    /// Set by `FinalizeReceipt` once the config's finality depth has passed
+    /// since `slot`; added in version 3
+    pub finalized: bool,
+    /// Amount the payer revealed by opening the proof's amount commitment
+    /// with `VerifyWithOpening`; 0 when it stayed hidden. Added in version 4
+    pub revealed_amount: u64,
+    /// Relayer that fronted the fees, recorded when the config tracks
+    /// relayers; `Pubkey::default()` otherwise. Added in version 5
+    pub relayer: Pubkey,
+    /// Compute unit price, in micro-lamports, the relayer declared in the
+    /// instruction it signed; added in version 5
+    pub compute_unit_price: u64,
+    /// Set once the recipient has paid the relayer its rebate with
+    /// `ClaimRelayerRebate`; added in version 5
+    pub rebate_claimed: bool,
+}
+
+impl StateAccount for PaymentReceipt {
+    const DISCRIMINATOR: u8 = 2;
+    const VERSION: u8 = 5;
+    const LEN: usize = Self::LEN_V4 + 32 + 8 + 1;
+
+    fn unpack_version(version: u8, body: &[u8]) -> Result<Self, VerifierError> {
+        let expected_len = match version {
+            1 => Self::LEN_V1,
+            2 => Self::LEN_V2,
+            3 => Self::LEN_V3,
+            4 => Self::LEN_V4,
+            5 => Self::LEN,
+            _ => return Err(VerifierError::UnsupportedAccountVersion),
+        };
+        if body.len() != expected_len - ACCOUNT_HEADER_LEN {
+            return Err(VerifierError::InvalidAccountLength);
+        }
+        // Version 1 receipts predate consumption and read as durable, and
+        // older receipts read as not finalized, with no revealed amount and
+        // no relayer
+        let mut body = body.to_vec();
+        body.resize(Self::LEN - ACCOUNT_HEADER_LEN, 0);
+        Self::try_from_slice(&body).map_err(|_| VerifierError::MalformedAccountData)
+    }
+}
+
+impl PaymentReceipt {
+    /// Size of receipts created before consumable receipts
+    pub const LEN_V1: usize = ACCOUNT_HEADER_LEN + 32 + 32 + 8 + 32 + 32 + 1 + 8 + 1;
+
+    /// Size of receipts created before `finalized`
+    pub const LEN_V2: usize = Self::LEN_V1 + 1 + 32;
+
+    /// Size of receipts created before `revealed_amount`
+    pub const LEN_V3: usize = Self::LEN_V2 + 1;
+
+    /// Size of receipts created before relayer tracking
+    pub const LEN_V4: usize = Self::LEN_V3 + 8;
+
+    /// Write the receipt back over existing account data
+    ///
+    /// An older receipt keeps its version: a version 1 receipt cannot be made
+    /// consumable or be consumed through this path, neither it nor a version 2
+    /// receipt can be finalized, no older version holds a revealed amount, and
+    /// none before version 5 a relayer.
+    pub fn store(&self, dst: &mut [u8]) -> Result<(), VerifierError> {
+        let version = match dst.len() {
+            Self::LEN_V1 => 1,
+            Self::LEN_V2 => 2,
+            Self::LEN_V3 => 3,
+            Self::LEN_V4 => 4,
+            _ => return self.pack(dst),
+        };
+        let data = self.to_account_data();
+        if data[dst.len()..].iter().any(|&byte| byte != 0)
+            || (version == 1 && self.status == ReceiptStatus::Consumed)
+        {
+            return Err(VerifierError::InvalidAccountLength);
+        }
+        dst.copy_from_slice(&data[..dst.len()]);
+        dst[1] = version;
+        Ok(())
+    }
+
+    /// Whether `key` may consume the receipt: its recipient or its delegate
+    pub fn may_consume(&self, key: &Pubkey) -> bool {
+        key.to_bytes() == self.recipient
+            || (self.delegate != Pubkey::default() && *key == self.delegate)
+    }
+}
+
+/// Record of a verified split payment, written by `VerifySplitPayment`
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SplitPaymentReceipt {
+    /// `Groth16Proof::hash` of the verified proof and its public inputs
+    pub proof_hash: [u8; 32],
+    /// Both legs, in the order the circuit binds them
+    pub recipients: [[u8; 32]; 2],
+    pub min_amounts: [u64; 2],
+    /// Whether both legs were transferred by the verifying instruction
+    pub settled: bool,
+    /// Account that paid rent for the receipt
+    pub payer: Pubkey,
+    pub bump: u8,
+    /// What each recipient's token account gained, net of any Token-2022
+    /// transfer fee; zero unless settled. Added in version 2
+    pub settled_amounts: [u64; 2],
+}
+
+impl StateAccount for SplitPaymentReceipt {
+    const DISCRIMINATOR: u8 = 3;
+    const VERSION: u8 = 2;
+    const LEN: usize = Self::LEN_V1 + 16;
+
+    fn unpack_version(version: u8, body: &[u8]) -> Result<Self, VerifierError> {
+        let expected_len = match version {
+            1 => Self::LEN_V1,
+            2 => Self::LEN,
+            _ => return Err(VerifierError::UnsupportedAccountVersion),
+        };
+        if body.len() != expected_len - ACCOUNT_HEADER_LEN {
+            return Err(VerifierError::InvalidAccountLength);
+        }
+        // Version 1 receipts predate net amounts and read as zero
+        let mut body = body.to_vec();
+        body.resize(Self::LEN - ACCOUNT_HEADER_LEN, 0);
+        Self::try_from_slice(&body).map_err(|_| VerifierError::MalformedAccountData)
+    }
+}
+
+impl SplitPaymentReceipt {
+    /// Size of receipts created before `settled_amounts`
+    pub const LEN_V1: usize = ACCOUNT_HEADER_LEN + 32 + 64 + 16 + 1 + 32 + 1;
+}
+
+/// Running totals of recorded payments to one recipient
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RecipientStats {
+    pub recipient: [u8; 32],
+    /// Number of recorded payments
+    pub count: u64,
+    /// Sum of `min_amount` over recorded payments, saturating
+    pub total_min_amount: u128,
+    pub last_verified_slot: u64,
+    pub bump: u8,
+    /// First slot of the current rate limit window; added in version 2
+    pub window_start_slot: u64,
+    /// Verifications admitted since `window_start_slot`; added in version 2
+    pub window_count: u64,
+}
+
+impl StateAccount for RecipientStats {
+    const DISCRIMINATOR: u8 = 4;
+    const VERSION: u8 = 2;
+    const LEN: usize = Self::LEN_V1 + 16;
+
+    fn unpack_version(version: u8, body: &[u8]) -> Result<Self, VerifierError> {
+        let expected_len = match version {
+            1 => Self::LEN_V1,
+            2 => Self::LEN,
+            _ => return Err(VerifierError::UnsupportedAccountVersion),
+        };
+        if body.len() != expected_len - ACCOUNT_HEADER_LEN {
+            return Err(VerifierError::InvalidAccountLength);
+        }
+        // Version 1 stats predate rate limiting and start with an empty window
+        let mut body = body.to_vec();
+        body.resize(Self::LEN - ACCOUNT_HEADER_LEN, 0);
+        Self::try_from_slice(&body).map_err(|_| VerifierError::MalformedAccountData)
+    }
+}
+
+impl RecipientStats {
+    /// Size of stats created before rate limiting
+    pub const LEN_V1: usize = ACCOUNT_HEADER_LEN + 32 + 8 + 16 + 8 + 1;
+
+    /// Count one more payment; totals saturate instead of wrapping
+    pub fn record(&mut self, min_amount: u64, slot: u64) {
+        self.count = self.count.saturating_add(1);
+        self.total_min_amount = self.total_min_amount.saturating_add(min_amount as u128);
+        self.last_verified_slot = slot;
+    }
+
+    /// Count a verification at `slot` against the config's rate limit
+    ///
+    /// A window opens at the first verification after the previous one
+    /// closed and lasts `window_slots` slots; at most
+    /// `max_verifications_per_window` verifications are admitted in it. A
+    /// limit of 0 admits everything and leaves the window alone.
+    pub fn admit(&mut self, config: &VerifierConfig, slot: u64) -> Result<(), VerifierError> {
+        if config.max_verifications_per_window == 0 {
+            return Ok(());
+        }
+        if slot.saturating_sub(self.window_start_slot) >= config.window_slots {
+            self.window_start_slot = slot;
+            self.window_count = 0;
+        }
+        if self.window_count >= config.max_verifications_per_window {
+            return Err(VerifierError::RateLimited);
+        }
+        self.window_count += 1;
+        Ok(())
+    }
+}
+
+/// Receipt recorded for an invoice, so gateways that only know the invoice id
+/// can find it
+///
+/// Written by `VerifyAndRecord` and `VerifyBatchSoft` for every nonzero
+/// invoice id; `VerifyRefund` keeps `status` in step with the receipt.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct InvoiceIndex {
+    pub invoice_id: [u8; 32],
+    /// Latest `PaymentReceipt` PDA recorded for the invoice
+    pub receipt: Pubkey,
+    /// Status of that receipt
+    pub status: ReceiptStatus,
+    /// Receipts recorded for the invoice; above 1 only when the config allows
+    /// overpayment or an earlier payment was refunded
+    pub payments: u32,
+    pub bump: u8,
+}
+
+impl StateAccount for InvoiceIndex {
+    const DISCRIMINATOR: u8 = 10;
+    const VERSION: u8 = 1;
+    const LEN: usize = ACCOUNT_HEADER_LEN + 32 + 32 + 1 + 4 + 1;
+}
+
+impl InvoiceIndex {
+    /// Address and bump of the index PDA for `invoice_id`
+    pub fn address(program_id: &Pubkey, invoice_id: &[u8; 32]) -> (Pubkey, u8) {
+        pda::invoice_address(program_id, invoice_id)
+    }
+
+    /// Check that another receipt may be recorded for this invoice
+    ///
+    /// An active payment blocks a second one unless `allow_overpayment`; a
+    /// refunded one never does.
+    pub fn check_new_payment(&self, allow_overpayment: bool) -> Result<(), VerifierError> {
+        if self.status == ReceiptStatus::Active && !allow_overpayment {
+            return Err(VerifierError::InvoiceAlreadyPaid);
+        }
+        Ok(())
+    }
+
+    /// Point the index at a newly recorded receipt
+    pub fn record(&mut self, receipt: Pubkey) {
+        self.receipt = receipt;
+        self.status = ReceiptStatus::Active;
+        self.payments = self.payments.saturating_add(1);
+    }
+}
+
+/// Marker that a nullifier was spent; subscriptions spend theirs once per
+/// `epoch`, withdrawals once at epoch 0
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SpentNullifier {
+    pub nullifier: [u8; 32],
+    pub epoch: u64,
+    pub bump: u8,
+}
+
+impl StateAccount for SpentNullifier {
+    const DISCRIMINATOR: u8 = 5;
+    const VERSION: u8 = 1;
+    const LEN: usize = ACCOUNT_HEADER_LEN + 32 + 8 + 1;
+}
+
+impl SpentNullifier {
+    /// Address and bump of the PDA spending `nullifier` for `circuit` in `epoch`
+    ///
+    /// The circuit id keeps a nullifier produced by one circuit from occupying
+    /// another circuit's slot.
+    pub fn address(
+        program_id: &Pubkey,
+        circuit: CircuitId,
+        nullifier: &[u8; 32],
+        epoch: u64,
+    ) -> (Pubkey, u8) {
+        pda::nullifier_address(program_id, circuit, nullifier, epoch)
+    }
+
+    /// Address a subscription nullifier had before seeds carried a circuit id
+    ///
+    /// Only subscriptions spent nullifiers then, so these all belong to
+    /// `CircuitId::Subscription`.
+    pub fn legacy_address(program_id: &Pubkey, nullifier: &[u8; 32], epoch: u64) -> Pubkey {
+        Pubkey::find_program_address(&[NULLIFIER_SEED, nullifier, &epoch.to_le_bytes()], program_id)
+            .0
+    }
+}
+
+/// Marker that the admin denied payments to `recipient`, written by
+/// `DenyRecipient` and closed by `AllowRecipient`
+///
+/// One account per recipient, so the denylist grows without a realloc and a
+/// verification reads a single account.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DeniedRecipient {
+    pub recipient: [u8; 32],
+    pub bump: u8,
+}
+
+impl StateAccount for DeniedRecipient {
+    const DISCRIMINATOR: u8 = 11;
+    const VERSION: u8 = 1;
+    const LEN: usize = ACCOUNT_HEADER_LEN + 32 + 1;
+}
+
+impl DeniedRecipient {
+    /// Reject a payment to `recipient` when `marker_account`, which must be
+    /// its marker PDA, holds a marker
+    ///
+    /// A marker never created, or closed by `AllowRecipient`, is an empty
+    /// account and lets the payment through.
+    pub fn check(
+        marker_account: &AccountInfo,
+        program_id: &Pubkey,
+        recipient: &[u8; 32],
+    ) -> Result<(), ProgramError> {
+        let (address, _) = pda::denied_recipient_address(program_id, recipient);
+        if address != *marker_account.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        if marker_account.owner != program_id || marker_account.data_is_empty() {
+            return Ok(());
+        }
+        Self::unpack(&marker_account.data.borrow())?;
+        Err(VerifierError::RecipientDenied.into())
+    }
+}
+
+/// One account summarizing a batch verified by `VerifyBatchWithReceipt`
+///
+/// Stands in for a receipt per proof: any proof in the batch is shown to be
+/// included with `VerifyBatchInclusion` and a path to `receipt_root`; see
+/// `batch_receipt`.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BatchReceipt {
+    /// `batch_verifier::batch_transcript_hash` of the verified batch
+    pub transcript_hash: [u8; 32],
+    /// Number of proofs in the batch
+    pub count: u8,
+    /// Merkle root over the batch's `Groth16Proof::hash` values, in batch order
+    pub receipt_root: [u8; 32],
+    /// Slot at which the batch was verified
+    pub slot: u64,
+    /// Signer that submitted the batch and funded this account
+    pub relayer: Pubkey,
+    pub bump: u8,
+}
+
+impl StateAccount for BatchReceipt {
+    const DISCRIMINATOR: u8 = 12;
+    const VERSION: u8 = 1;
+    const LEN: usize = ACCOUNT_HEADER_LEN + 32 + 1 + 32 + 8 + 32 + 1;
+}
+
+impl BatchReceipt {
+    /// Read a batch receipt, checking the owner and that the account is the
+    /// PDA of the transcript hash it holds
+    pub fn load(account: &AccountInfo, program_id: &Pubkey) -> Result<Self, ProgramError> {
+        if account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let receipt = Self::unpack(&account.data.borrow())?;
+        let address = Pubkey::create_program_address(
+            &[BATCH_RECEIPT_SEED, &receipt.transcript_hash, &[receipt.bump]],
+            program_id,
+        )?;
+        if address != *account.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(receipt)
+    }
+}
+
+/// Header of a batch buffer, followed by the staged request bytes
+///
+/// Records who funded the buffer and when it was last written, so an upload
+/// abandoned part way can be reaped with `ReapStale`.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BatchBuffer {
+    /// Signer of the writes, who funded the buffer and is refunded its rent
+    pub authority: Pubkey,
+    /// Slot of the last `WriteBatchBuffer`; the scratch TTL runs from it
+    pub last_write_slot: u64,
+}
+
+impl StateAccount for BatchBuffer {
+    const DISCRIMINATOR: u8 = 13;
+    const VERSION: u8 = 1;
+    const LEN: usize = ACCOUNT_HEADER_LEN + 32 + 8;
+}
+
+impl BatchBuffer {
+    /// Split buffer account data into its header and the staged bytes
+    pub fn split(data: &[u8]) -> Result<(Self, &[u8]), VerifierError> {
+        if data.len() < Self::LEN {
+            return Err(VerifierError::InvalidAccountLength);
+        }
+        let (header, staged) = data.split_at(Self::LEN);
+        Ok((Self::unpack(header)?, staged))
+    }
+}
+
+/// Values derived once from a circuit's verification key, written by
+/// `PrepareVerificationKey`
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PreparedVk {
+    pub circuit: CircuitId,
+    /// `VerificationKey::hash` of the key these values were derived from
+    pub vk_hash: [u8; 32],
+    /// -alpha, the G1 half of the constant pairing term
+    pub neg_alpha_g1: G1Point,
+    /// e(alpha, beta) in GT as supplied by the admin; all zeros when not supplied
+    pub alpha_beta: [u8; 384],
+    pub bump: u8,
+    /// `VerificationKey::hash` of the circuit's previous key, set by
+    /// `DeprecatePreviousKey`; added in version 2
+    pub previous_vk_hash: [u8; 32],
+    /// Last slot at which proofs against the previous key are still accepted
+    pub deprecated_until_slot: u64,
+    /// `VerificationKey::hash` of the key `RevokeVerificationKey` revoked;
+    /// zero when none was. Added in version 3
+    pub revoked_vk_hash: [u8; 32],
+    /// Hash of the circuit's batch transcripts, set by `SetHashBackend`;
+    /// added in version 4
+    pub hash_backend: HashBackend,
+}
+
+impl StateAccount for PreparedVk {
+    const DISCRIMINATOR: u8 = 6;
+    /// Version 5 changes no field, only marks `neg_alpha_g1` as negated
+    /// in big-endian; `load` refuses anything older
+    const VERSION: u8 = 5;
+    const LEN: usize = Self::LEN_V4;
+
+    fn unpack_version(version: u8, body: &[u8]) -> Result<Self, VerifierError> {
+        let expected_len = match version {
+            1 => Self::LEN_V1,
+            2 => Self::LEN_V2,
+            3 => Self::LEN_V3,
+            4 | 5 => Self::LEN_V4,
+            _ => return Err(VerifierError::UnsupportedAccountVersion),
+        };
+        if body.len() != expected_len - ACCOUNT_HEADER_LEN {
+            return Err(VerifierError::InvalidAccountLength);
+        }
+        // A zero hash matches no key, so older versions read as having no
+        // window and nothing revoked; a zero backend is keccak256
+        let mut body = body.to_vec();
+        body.resize(Self::LEN - ACCOUNT_HEADER_LEN, 0);
+        Self::try_from_slice(&body).map_err(|_| VerifierError::MalformedAccountData)
+    }
+}
+
+impl PreparedVk {
+    /// Deserialize a prepared key for `circuit`, checking its owner and address
+    ///
+    /// Accounts written before version 5 may hold a `neg_alpha_g1` negated
+    /// with the little-endian subtraction, and fail with `StalePreparedKey`
+    /// until `PrepareVerificationKey` rewrites them.
+    pub fn load(
+        account: &AccountInfo,
+        program_id: &Pubkey,
+        circuit: CircuitId,
+    ) -> Result<Self, ProgramError> {
+        let prepared = Self::load_any_version(account, program_id, circuit)?;
+        if account.data.borrow()[1] < Self::VERSION {
+            return Err(VerifierError::StalePreparedKey.into());
+        }
+        Ok(prepared)
+    }
+
+    /// `load` for a verification: fails with `KeyRevoked` once the circuit's
+    /// key is revoked, and with `StalePreparedKey` unless the account was
+    /// prepared from the compiled-in key
+    pub fn load_active(
+        account: &AccountInfo,
+        program_id: &Pubkey,
+        circuit: CircuitId,
+    ) -> Result<Self, ProgramError> {
+        let prepared = Self::load(account, program_id, circuit)?;
+        prepared.check_not_revoked()?;
+        prepared.check_fresh(&circuit.verification_key().hash())?;
+        Ok(prepared)
+    }
+
+    /// `load` accepting every version, for `PrepareVerificationKey` to carry
+    /// an older account's window, revocation and backend over
+    pub fn load_any_version(
+        account: &AccountInfo,
+        program_id: &Pubkey,
+        circuit: CircuitId,
+    ) -> Result<Self, ProgramError> {
+        if account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let prepared = Self::unpack(&account.data.borrow())?;
+        if prepared.circuit != circuit {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let address = Pubkey::create_program_address(
+            &[PREPARED_VK_SEED, &[circuit as u8], &[prepared.bump]],
+            program_id,
+        )?;
+        if address != *account.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(prepared)
+    }
+
+    /// Hash backend selected for `circuit` by its prepared key at `account`
+    ///
+    /// keccak256 when no account is passed or the key was never prepared;
+    /// an uncreated account must still be the circuit's prepared key PDA.
+    pub fn hash_backend_of(
+        account: Option<&AccountInfo>,
+        program_id: &Pubkey,
+        circuit: CircuitId,
+    ) -> Result<HashBackend, ProgramError> {
+        match account {
+            Some(account) if !account.data_is_empty() => {
+                Ok(Self::load(account, program_id, circuit)?.hash_backend)
+            }
+            Some(account) if *account.key != pda::vkey_address(program_id, circuit).0 => {
+                Err(ProgramError::InvalidSeeds)
+            }
+            _ => Ok(HashBackend::Keccak256),
+        }
+    }
+
+    /// Size of prepared keys created before the deprecation window
+    pub const LEN_V1: usize = ACCOUNT_HEADER_LEN + 1 + 32 + 64 + 384 + 1;
+
+    /// Size of prepared keys created before revocation
+    pub const LEN_V2: usize = Self::LEN_V1 + 32 + 8;
+
+    /// Size of prepared keys created before hash backends
+    pub const LEN_V3: usize = Self::LEN_V2 + 32;
+
+    /// Size of prepared keys since hash backends; version 5 keeps the layout
+    pub const LEN_V4: usize = Self::LEN_V3 + 1;
+
+    /// Whether `vk_hash` names a revoked key
+    pub fn is_revoked(&self, vk_hash: &[u8; 32]) -> bool {
+        self.revoked_vk_hash != [0u8; 32] && self.revoked_vk_hash == *vk_hash
+    }
+
+    /// Reject every proof once the current key is revoked
+    pub fn check_not_revoked(&self) -> Result<(), VerifierError> {
+        if self.is_revoked(&self.vk_hash) {
+            return Err(VerifierError::KeyRevoked);
+        }
+        Ok(())
+    }
+
+    /// Reject values derived from a key other than the one compiled in
+    pub fn check_fresh(&self, vk_hash: &[u8; 32]) -> Result<(), VerifierError> {
+        if self.vk_hash != *vk_hash {
+            return Err(VerifierError::StalePreparedKey);
+        }
+        Ok(())
+    }
+
+    /// Whether proofs against `previous` are still accepted at `slot`
+    ///
+    /// The window names the key it was opened for, so one left open across
+    /// another rotation does not carry over to the next previous key.
+    pub fn accepts_previous_key(&self, previous: &VerificationKey, slot: u64) -> bool {
+        let previous_hash = previous.hash();
+        slot <= self.deprecated_until_slot
+            && self.previous_vk_hash == previous_hash
+            && !self.is_revoked(&previous_hash)
+    }
+}
+
+/// Shielded pool: holds deposited lamports on top of its own rent, and the tree
+/// of deposit commitments
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ShieldedPool {
+    pub tree: MerkleTree<MERKLE_TREE_DEPTH>,
+    pub bump: u8,
+}
+
+impl StateAccount for ShieldedPool {
+    const DISCRIMINATOR: u8 = 8;
+    const VERSION: u8 = 1;
+    const LEN: usize = ACCOUNT_HEADER_LEN + MerkleTree::<MERKLE_TREE_DEPTH>::LEN + 1;
+}
+
+impl ShieldedPool {
+    /// Deserialize the pool, checking its owner and address
+    pub fn load(account: &AccountInfo, program_id: &Pubkey) -> Result<Self, ProgramError> {
+        if account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let pool = Self::unpack(&account.data.borrow())?;
+        let address = Pubkey::create_program_address(&[POOL_SEED, &[pool.bump]], program_id)?;
+        if address != *account.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(pool)
+    }
+}
+
+/// Admin-set bounds on payment public inputs, checked before any pairing work
+///
+/// The default accepts every statement, matching the behaviour before the
+/// policy existed.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct InputPolicy {
+    /// Smallest `min_amount` worth verifying
+    pub min_amount_floor: u64,
+    /// Largest `max_block_age` a proof may claim
+    pub max_block_age_ceiling: u64,
+    /// Largest allowed distance between `current_time` and the cluster clock, in seconds
+    pub max_time_skew_seconds: u64,
+}
+
+impl Default for InputPolicy {
+    fn default() -> Self {
+        Self {
+            min_amount_floor: 0,
+            max_block_age_ceiling: u64::MAX,
+            max_time_skew_seconds: u64::MAX,
+        }
+    }
+}
+
+impl InputPolicy {
+    pub const LEN: usize = 8 + 8 + 8;
+
+    /// Check the fields every payment statement shares, one error per rule
+    pub fn check(
+        &self,
+        min_amount: u64,
+        max_block_age: u64,
+        current_time: i64,
+        now: i64,
+    ) -> Result<(), VerifierError> {
+        if min_amount < self.min_amount_floor {
+            return Err(VerifierError::AmountBelowFloor);
+        }
+        if max_block_age > self.max_block_age_ceiling {
+            return Err(VerifierError::BlockAgeAboveCeiling);
+        }
+        if current_time.abs_diff(now) > self.max_time_skew_seconds {
+            return Err(VerifierError::ClockSkewExceeded);
+        }
+        Ok(())
+    }
+
+    /// This policy with the skew bound narrowed to `max_time_skew_seconds`
+    ///
+    /// A request for a wider bound leaves the policy's own bound in place.
+    pub fn tightened(self, max_time_skew_seconds: u64) -> Self {
+        Self {
+            max_time_skew_seconds: self.max_time_skew_seconds.min(max_time_skew_seconds),
+            ..self
+        }
+    }
+}
+
+/// Most keys an `AdminSigners` set may list
+pub const MAX_ADMIN_SIGNERS: usize = 8;
+
+/// Keys that must co-sign destructive admin instructions
+///
+/// Key updates, admin rotation and changes to this set itself need at least
+/// `threshold` distinct listed keys to have signed, on top of the admin's own
+/// signature. The admin counts if listed. A threshold of 0 leaves those
+/// instructions to the admin alone.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct AdminSigners {
+    pub threshold: u8,
+    /// Entries of `keys` in use, from the first; the rest are zero
+    count: u8,
+    keys: [Pubkey; MAX_ADMIN_SIGNERS],
+}
+
+impl AdminSigners {
+    pub const LEN: usize = 1 + 1 + 32 * MAX_ADMIN_SIGNERS;
+
+    /// A set requiring `threshold` of `signers`
+    ///
+    /// The keys must be distinct and nonzero, and the threshold reachable; a
+    /// threshold of 0 takes no keys.
+    pub fn new(threshold: u8, signers: &[Pubkey]) -> Result<Self, VerifierError> {
+        if signers.len() > MAX_ADMIN_SIGNERS
+            || threshold as usize > signers.len()
+            || (threshold == 0 && !signers.is_empty())
+        {
+            return Err(VerifierError::InvalidAdminSigners);
+        }
+        let mut keys = [Pubkey::default(); MAX_ADMIN_SIGNERS];
+        for (i, signer) in signers.iter().enumerate() {
+            if *signer == Pubkey::default() || signers[..i].contains(signer) {
+                return Err(VerifierError::InvalidAdminSigners);
+            }
+            keys[i] = *signer;
+        }
+        Ok(Self {
+            threshold,
+            count: signers.len() as u8,
+            keys,
+        })
+    }
+
+    pub fn signers(&self) -> &[Pubkey] {
+        &self.keys[..(self.count as usize).min(MAX_ADMIN_SIGNERS)]
+    }
+
+    /// Distinct listed keys among the signers of `accounts`
+    ///
+    /// An account passed more than once counts once.
+    pub fn count_signed(&self, accounts: &[AccountInfo]) -> usize {
+        let mut signed: Vec<&Pubkey> = Vec::with_capacity(MAX_ADMIN_SIGNERS);
+        for account in accounts.iter().filter(|account| account.is_signer) {
+            if self.signers().contains(account.key) && !signed.contains(&account.key) {
+                signed.push(account.key);
+            }
+        }
+        signed.len()
+    }
+
+    /// Require `threshold` distinct listed keys to have signed
+    pub fn check(&self, accounts: &[AccountInfo]) -> Result<(), VerifierError> {
+        if self.count_signed(accounts) < self.threshold as usize {
+            return Err(VerifierError::AdminThresholdNotMet);
+        }
+        Ok(())
+    }
+}
+
+/// Finality depth of a config that sets none: the slots after which the
+/// cluster's commitment is `finalized`
+pub const DEFAULT_FINALITY_SLOTS: u64 = 32;
+
+/// Scratch TTL of a config that sets none: about a day of slots
+pub const DEFAULT_SCRATCH_TTL_SLOTS: u64 = 216_000;
+
+/// Shortest scratch TTL a config may set: well past a blockhash's lifetime,
+/// so an upload still being sent is never reaped
+pub const MIN_SCRATCH_TTL_SLOTS: u64 = 1_500;
+
+/// Program-wide configuration, controlled by `admin`
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct VerifierConfig {
+    pub admin: Pubkey,
+    /// Successor proposed by `ProposeAdmin`; `Pubkey::default()` when none
+    pub pending_admin: Pubkey,
+    /// Merkle root of the approved recipient set
+    pub recipient_set_root: [u8; 32],
+    /// Root replaced by the last `SetRecipientRoot`, honoured during the grace period
+    pub previous_recipient_set_root: [u8; 32],
+    /// Unix timestamp until which `previous_recipient_set_root` is still accepted
+    pub previous_root_valid_until: i64,
+    /// How long a replaced root stays valid, in seconds
+    pub recipient_root_grace_seconds: i64,
+    /// Subscription billing epoch length in seconds; 0 disables subscriptions
+    pub epoch_length_seconds: i64,
+    /// Bounds on payment public inputs
+    pub input_policy: InputPolicy,
+    /// How many slots a receipt stays fresh for downstream readers; 0 means forever
+    pub max_receipt_age_slots: u64,
+    pub bump: u8,
+    /// Append verifications to the audit log when it is passed; added in version 2
+    pub audit_enabled: bool,
+    /// Longest deprecation window `DeprecatePreviousKey` may open, in slots;
+    /// 0 keeps previous keys off. Added in version 3
+    pub max_key_deprecation_slots: u64,
+    /// Co-signers of destructive admin instructions; added in version 4
+    pub admin_signers: AdminSigners,
+    /// Record further payments for an invoice that already has an active
+    /// receipt instead of rejecting them; added in version 5
+    pub allow_invoice_overpayment: bool,
+    /// Most payments recorded for one recipient per window; 0 disables rate
+    /// limiting. Added in version 6
+    pub max_verifications_per_window: u64,
+    /// Length of a rate limit window in slots; added in version 6
+    pub window_slots: u64,
+    /// Slots that must pass after a receipt's slot before `FinalizeReceipt`
+    /// accepts it; 0 selects `DEFAULT_FINALITY_SLOTS`. Added in version 7
+    pub finality_slots: u64,
+    /// Slots after its last write before anyone may reap a batch buffer; 0
+    /// selects `DEFAULT_SCRATCH_TTL_SLOTS`. Added in version 8
+    pub scratch_ttl_slots: u64,
+    /// Record the relayer and its declared compute unit price in each
+    /// `VerifyAndRecord` receipt, so the recipient can rebate it; added in
+    /// version 9
+    pub track_relayer: bool,
+}
+
+impl StateAccount for VerifierConfig {
+    const DISCRIMINATOR: u8 = 1;
+    const VERSION: u8 = 9;
+    const LEN: usize = Self::LEN_V8 + 1;
+
+    fn unpack_version(version: u8, body: &[u8]) -> Result<Self, VerifierError> {
+        let expected_len = match version {
+            1 => Self::LEN_V1,
+            2 => Self::LEN_V2,
+            3 => Self::LEN_V3,
+            4 => Self::LEN_V4,
+            5 => Self::LEN_V5,
+            6 => Self::LEN_V6,
+            7 => Self::LEN_V7,
+            8 => Self::LEN_V8,
+            9 => Self::LEN,
+            _ => return Err(VerifierError::UnsupportedAccountVersion),
+        };
+        if body.len() != expected_len - ACCOUNT_HEADER_LEN {
+            return Err(VerifierError::InvalidAccountLength);
+        }
+        // Each version only appends fields, so the ones an older account
+        // lacks read as zero: auditing off, no previous keys, no co-signers,
+        // no overpayment, no rate limit, the default finality depth, the
+        // default scratch TTL, no relayer tracking
+        let mut body = body.to_vec();
+        body.resize(Self::LEN - ACCOUNT_HEADER_LEN, 0);
+        Self::try_from_slice(&body).map_err(|_| VerifierError::MalformedAccountData)
+    }
+}
+
+impl VerifierConfig {
+    /// Size of configs created before `audit_enabled`
+    pub const LEN_V1: usize =
+        ACCOUNT_HEADER_LEN + 32 + 32 + 32 + 32 + 8 + 8 + 8 + InputPolicy::LEN + 8 + 1;
+
+    /// Size of configs created before `max_key_deprecation_slots`
+    pub const LEN_V2: usize = Self::LEN_V1 + 1;
+
+    /// Size of configs created before `admin_signers`
+    pub const LEN_V3: usize = Self::LEN_V2 + 8;
+
+    /// Size of configs created before `allow_invoice_overpayment`
+    pub const LEN_V4: usize = Self::LEN_V3 + AdminSigners::LEN;
+
+    /// Size of configs created before rate limiting
+    pub const LEN_V5: usize = Self::LEN_V4 + 1;
+
+    /// Size of configs created before `finality_slots`
+    pub const LEN_V6: usize = Self::LEN_V5 + 16;
+
+    /// Size of configs created before `scratch_ttl_slots`
+    pub const LEN_V7: usize = Self::LEN_V6 + 8;
+
+    /// Size of configs created before `track_relayer`
+    pub const LEN_V8: usize = Self::LEN_V7 + 8;
+
+    /// Write the config back over existing account data
+    ///
+    /// An older account keeps its version until an instruction that needs the
+    /// newer fields grows it, so those fields cannot be set through this path.
+    pub fn store(&self, dst: &mut [u8]) -> Result<(), VerifierError> {
+        let version = match dst.len() {
+            Self::LEN_V1 => 1,
+            Self::LEN_V2 => 2,
+            Self::LEN_V3 => 3,
+            Self::LEN_V4 => 4,
+            Self::LEN_V5 => 5,
+            Self::LEN_V6 => 6,
+            Self::LEN_V7 => 7,
+            Self::LEN_V8 => 8,
+            _ => return self.pack(dst),
+        };
+        let data = self.to_account_data();
+        if data[dst.len()..].iter().any(|&byte| byte != 0) {
+            return Err(VerifierError::InvalidAccountLength);
+        }
+        dst.copy_from_slice(&data[..dst.len()]);
+        dst[1] = version;
+        Ok(())
+    }
+
+    /// Deserialize the config PDA, checking its owner and address
+    pub fn load(account: &AccountInfo, program_id: &Pubkey) -> Result<Self, ProgramError> {
+        if account.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let config = Self::unpack(&account.data.borrow())?;
+        let address = Pubkey::create_program_address(&[CONFIG_SEED, &[config.bump]], program_id)?;
+        if address != *account.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(config)
+    }
+
+    /// Whether a proof's recipient set root is currently approved
+    ///
+    /// The previous root stays valid until the end of its grace period so that
+    /// proofs generated just before a rotation still verify.
+    pub fn accepts_recipient_root(&self, root: &[u8; 32], now: i64) -> bool {
+        if *root == [0u8; 32] {
+            return false;
+        }
+        *root == self.recipient_set_root
+            || (*root == self.previous_recipient_set_root && now <= self.previous_root_valid_until)
+    }
+
+    /// Whether `FinalizeReceipt` accepts a receipt recorded at `receipt_slot`
+    /// in `slot`
+    ///
+    /// The first accepted slot is `receipt_slot` plus the finality depth.
+    pub fn receipt_final_at(&self, receipt_slot: u64, slot: u64) -> bool {
+        let depth = match self.finality_slots {
+            0 => DEFAULT_FINALITY_SLOTS,
+            depth => depth,
+        };
+        receipt_slot.checked_add(depth).is_some_and(|final_slot| slot >= final_slot)
+    }
+
+    /// Whether `ReapStale` may close a buffer last written at
+    /// `last_write_slot` in `slot`
+    ///
+    /// The first such slot is `last_write_slot` plus the scratch TTL, which
+    /// is never shorter than `MIN_SCRATCH_TTL_SLOTS` whatever the config holds.
+    pub fn scratch_stale_at(&self, last_write_slot: u64, slot: u64) -> bool {
+        let ttl = match self.scratch_ttl_slots {
+            0 => DEFAULT_SCRATCH_TTL_SLOTS,
+            ttl => ttl.max(MIN_SCRATCH_TTL_SLOTS),
+        };
+        last_write_slot.checked_add(ttl).is_some_and(|stale_slot| slot >= stale_slot)
+    }
+
+    /// Install a new approved root, keeping the current one for the grace period
+    pub fn rotate_recipient_root(&mut self, new_root: [u8; 32], now: i64) {
+        self.previous_recipient_set_root = self.recipient_set_root;
+        self.previous_root_valid_until = now.saturating_add(self.recipient_root_grace_seconds);
+        self.recipient_set_root = new_root;
+    }
+
+    /// Check a subscription proof's epoch against the clock
+    ///
+    /// The current epoch and the one before it are accepted, so a proof
+    /// generated just before an epoch boundary still lands.
+    pub fn check_epoch(&self, epoch: u64, now: i64) -> Result<(), VerifierError> {
+        if self.epoch_length_seconds <= 0 || now < 0 {
+            return Err(VerifierError::EpochMismatch);
+        }
+        let current = (now / self.epoch_length_seconds) as u64;
+        if epoch == current || epoch.checked_add(1) == Some(current) {
+            Ok(())
+        } else {
+            Err(VerifierError::EpochMismatch)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> VerifierConfig {
+        VerifierConfig {
+            admin: Pubkey::new_unique(),
+            pending_admin: Pubkey::default(),
+            recipient_set_root: [1u8; 32],
+            previous_recipient_set_root: [0u8; 32],
+            previous_root_valid_until: 0,
+            recipient_root_grace_seconds: 3600,
+            epoch_length_seconds: 0,
+            input_policy: InputPolicy::default(),
+            max_receipt_age_slots: 0,
+            bump: 255,
+            audit_enabled: false,
+            max_key_deprecation_slots: 0,
+            admin_signers: AdminSigners::default(),
+            allow_invoice_overpayment: false,
+            max_verifications_per_window: 0,
+            window_slots: 0,
+            finality_slots: 0,
+            scratch_ttl_slots: 0,
+            track_relayer: false,
+        }
+    }
+
+    #[test]
+    fn test_recipient_root_grace_window() {
+        let mut config = config();
+        assert!(config.accepts_recipient_root(&[1u8; 32], 1700000000));
+        assert!(!config.accepts_recipient_root(&[2u8; 32], 1700000000));
+
+        config.rotate_recipient_root([2u8; 32], 1700000000);
+
+        // New root immediately, old root until the grace period ends (inclusive)
+        assert!(config.accepts_recipient_root(&[2u8; 32], 1700000000));
+        assert!(config.accepts_recipient_root(&[1u8; 32], 1700003600));
+        assert!(!config.accepts_recipient_root(&[1u8; 32], 1700003601));
+        assert!(config.accepts_recipient_root(&[2u8; 32], 1700003601));
+    }
+
+    #[test]
+    fn test_second_rotation_drops_oldest_root() {
+        let mut config = config();
+        config.rotate_recipient_root([2u8; 32], 1700000000);
+        config.rotate_recipient_root([3u8; 32], 1700000010);
+
+        assert!(!config.accepts_recipient_root(&[1u8; 32], 1700000010));
+        assert!(config.accepts_recipient_root(&[2u8; 32], 1700000010));
+        assert!(config.accepts_recipient_root(&[3u8; 32], 1700000010));
+    }
+
+    #[test]
+    fn test_zero_root_never_accepted() {
+        let mut config = config();
+        config.recipient_set_root = [0u8; 32];
+        assert!(!config.accepts_recipient_root(&[0u8; 32], 1700000000));
+    }
+
+    #[test]
+    fn test_epoch_window() {
+        let mut config = config();
+        // Subscriptions are off until an epoch length is set
+        assert_eq!(config.check_epoch(0, 1700000000), Err(VerifierError::EpochMismatch));
+
+        config.epoch_length_seconds = 86400;
+        let now = 1700000000;
+        let current = (now / 86400) as u64;
+        assert_eq!(config.check_epoch(current, now), Ok(()));
+        assert_eq!(config.check_epoch(current - 1, now), Ok(()));
+        assert_eq!(config.check_epoch(current - 2, now), Err(VerifierError::EpochMismatch));
+        assert_eq!(config.check_epoch(current + 1, now), Err(VerifierError::EpochMismatch));
+    }
+
+    #[test]
+    fn test_nullifier_address_scoped_by_epoch() {
+        let program_id = Pubkey::new_unique();
+        let nullifier = [7u8; 32];
+        let address = |epoch: u64| {
+            SpentNullifier::address(&program_id, CircuitId::Subscription, &nullifier, epoch).0
+        };
+        assert_ne!(address(19675), address(19676));
+        assert_eq!(address(19675), address(19675));
+    }
+
+    #[test]
+    fn test_nullifier_address_scoped_by_circuit() {
+        let program_id = Pubkey::new_unique();
+        let nullifier = [7u8; 32];
+        let address = |circuit: CircuitId| {
+            SpentNullifier::address(&program_id, circuit, &nullifier, 19675).0
+        };
+        assert_ne!(address(CircuitId::Subscription), address(CircuitId::Refund));
+        assert_ne!(address(CircuitId::Subscription), address(CircuitId::Payment));
+        assert_ne!(
+            address(CircuitId::Subscription),
+            SpentNullifier::legacy_address(&program_id, &nullifier, 19675)
+        );
+    }
+
+    fn stats() -> RecipientStats {
+        RecipientStats {
+            recipient: [4u8; 32],
+            count: 0,
+            total_min_amount: 0,
+            last_verified_slot: 0,
+            bump: 255,
+            window_start_slot: 0,
+            window_count: 0,
+        }
+    }
+
+    #[test]
+    fn test_recipient_stats_accumulate() {
+        let mut stats = stats();
+        stats.record(1000000, 100);
+        stats.record(2500000, 105);
+        stats.record(0, 107);
+
+        assert_eq!(stats.count, 3);
+        assert_eq!(stats.total_min_amount, 3500000);
+        assert_eq!(stats.last_verified_slot, 107);
+        assert_eq!(
+            ACCOUNT_HEADER_LEN + stats.try_to_vec().unwrap().len(),
+            RecipientStats::LEN
+        );
+    }
+
+    #[test]
+    fn test_recipient_stats_saturate() {
+        let mut stats = RecipientStats {
+            count: u64::MAX,
+            total_min_amount: u128::MAX - 1,
+            ..stats()
+        };
+        stats.record(u64::MAX, 200);
+
+        assert_eq!(stats.count, u64::MAX);
+        assert_eq!(stats.total_min_amount, u128::MAX);
+        assert_eq!(stats.last_verified_slot, 200);
+    }
+
+    #[test]
+    fn test_rate_limit_window() {
+        let config = VerifierConfig {
+            max_verifications_per_window: 2,
+            window_slots: 100,
+            ..config()
+        };
+        let mut stats = stats();
+
+        // The first verification opens the window at its own slot
+        assert_eq!(stats.admit(&config, 1000), Ok(()));
+        assert_eq!((stats.window_start_slot, stats.window_count), (1000, 1));
+        assert_eq!(stats.admit(&config, 1050), Ok(()));
+        assert_eq!(stats.admit(&config, 1099), Err(VerifierError::RateLimited));
+        // A rejection is not counted
+        assert_eq!((stats.window_start_slot, stats.window_count), (1000, 2));
+
+        // The window is `window_slots` long: its last slot is still limited
+        // and the next one opens a fresh window there
+        assert_eq!(stats.admit(&config, 1099), Err(VerifierError::RateLimited));
+        assert_eq!(stats.admit(&config, 1100), Ok(()));
+        assert_eq!((stats.window_start_slot, stats.window_count), (1100, 1));
+
+        // After a quiet spell the window starts at the next verification, not
+        // on a boundary of the old one
+        assert_eq!(stats.admit(&config, 1345), Ok(()));
+        assert_eq!((stats.window_start_slot, stats.window_count), (1345, 1));
+    }
+
+    #[test]
+    fn test_rate_limit_edges() {
+        // No limit: everything is admitted and the window is left alone
+        let mut stats = RecipientStats {
+            window_start_slot: 7,
+            window_count: u64::MAX,
+            ..stats()
+        };
+        assert_eq!(stats.admit(&config(), 8), Ok(()));
+        assert_eq!((stats.window_start_slot, stats.window_count), (7, u64::MAX));
+
+        // A window starting near the top of the slot range does not overflow
+        let config = VerifierConfig {
+            max_verifications_per_window: 1,
+            window_slots: u64::MAX,
+            ..config()
+        };
+        let mut stats = RecipientStats {
+            window_start_slot: u64::MAX - 1,
+            window_count: 1,
+            ..stats()
+        };
+        assert_eq!(stats.admit(&config, u64::MAX), Err(VerifierError::RateLimited));
+        // A slot before the window's start cannot reopen it
+        assert_eq!(stats.admit(&config, 0), Err(VerifierError::RateLimited));
+
+        // A version 1 account reads as an empty window
+        let mut v1 = stats.to_account_data()[..RecipientStats::LEN_V1].to_vec();
+        v1[1] = 1;
+        let mut upgraded = RecipientStats::unpack(&v1).unwrap();
+        assert_eq!((upgraded.window_start_slot, upgraded.window_count), (0, 0));
+        assert_eq!(upgraded.admit(&config, 500), Ok(()));
+    }
+
+    /// Account bytes from a header and fields, so each pinned layout reads
+    /// field by field
+    fn layout(discriminator: u8, fields: &[&[u8]]) -> Vec<u8> {
+        let mut data = vec![discriminator, 1];
+        for field in fields {
+            data.extend_from_slice(field);
+        }
+        data
+    }
+
+    fn receipt() -> PaymentReceipt {
+        PaymentReceipt {
+            proof_hash: [1u8; 32],
+            recipient: [2u8; 32],
+            min_amount: 1000000,
+            invoice_id: [3u8; 32],
+            payer: Pubkey::new_from_array([5u8; 32]),
+            status: ReceiptStatus::Refunded,
+            slot: 250000000,
+            bump: 254,
+            consumable: false,
+            delegate: Pubkey::default(),
+            finalized: false,
+            revealed_amount: 0,
+            relayer: Pubkey::default(),
+            compute_unit_price: 0,
+            rebate_claimed: false,
+        }
+    }
+
+    // Changing any of these layouts breaks every live account of that type.
+    // Bump the type's VERSION and migrate in `unpack_version` instead.
+
+    #[test]
+    fn test_config_layout_pinned() {
+        let config = VerifierConfig {
+            admin: Pubkey::new_from_array([0x11; 32]),
+            pending_admin: Pubkey::new_from_array([0x22; 32]),
+            recipient_set_root: [0x33; 32],
+            previous_recipient_set_root: [0x44; 32],
+            previous_root_valid_until: 1700003600,
+            recipient_root_grace_seconds: 3600,
+            epoch_length_seconds: 86400,
+            input_policy: InputPolicy {
+                min_amount_floor: 1000,
+                max_block_age_ceiling: 120,
+                max_time_skew_seconds: 30,
+            },
+            max_receipt_age_slots: 150,
+            bump: 254,
+            audit_enabled: false,
+            max_key_deprecation_slots: 0,
+            admin_signers: AdminSigners::default(),
+            allow_invoice_overpayment: false,
+            max_verifications_per_window: 0,
+            window_slots: 0,
+            finality_slots: 0,
+            scratch_ttl_slots: 0,
+            track_relayer: false,
+        };
+        let v1 = layout(
+            1,
+            &[
+                &[0x11; 32],
+                &[0x22; 32],
+                &[0x33; 32],
+                &[0x44; 32],
+                &1700003600i64.to_le_bytes(),
+                &3600i64.to_le_bytes(),
+                &86400i64.to_le_bytes(),
+                &1000u64.to_le_bytes(),
+                &120u64.to_le_bytes(),
+                &30u64.to_le_bytes(),
+                &150u64.to_le_bytes(),
+                &[254],
+            ],
+        );
+        assert_eq!(VerifierConfig::LEN_V1, 187);
+        assert_eq!(VerifierConfig::unpack(&v1), Ok(config.clone()));
+
+        // Version 2 appends `audit_enabled`
+        let config = VerifierConfig {
+            audit_enabled: true,
+            ..config
+        };
+        let mut v2 = v1.clone();
+        v2[1] = 2;
+        v2.push(1);
+        assert_eq!(VerifierConfig::LEN_V2, 188);
+        assert_eq!(VerifierConfig::unpack(&v2), Ok(config.clone()));
+
+        // Version 3 appends `max_key_deprecation_slots`
+        let config = VerifierConfig {
+            max_key_deprecation_slots: 432000,
+            ..config
+        };
+        let mut v3 = v2.clone();
+        v3[1] = 3;
+        v3.extend_from_slice(&432000u64.to_le_bytes());
+        assert_eq!(VerifierConfig::LEN_V3, 196);
+        assert_eq!(VerifierConfig::unpack(&v3), Ok(config.clone()));
+
+        // Version 4 appends `admin_signers`: threshold, count, then every slot
+        let signers = [Pubkey::new_from_array([0x55; 32]), Pubkey::new_from_array([0x66; 32])];
+        let config = VerifierConfig {
+            admin_signers: AdminSigners::new(2, &signers).unwrap(),
+            ..config
+        };
+        let mut v4 = v3.clone();
+        v4[1] = 4;
+        v4.extend_from_slice(&[2, 2]);
+        v4.extend_from_slice(&[0x55; 32]);
+        v4.extend_from_slice(&[0x66; 32]);
+        v4.resize(v4.len() + 32 * (MAX_ADMIN_SIGNERS - 2), 0);
+        assert_eq!(VerifierConfig::LEN_V4, 454);
+        assert_eq!(VerifierConfig::unpack(&v4), Ok(config.clone()));
+
+        // Version 5 appends `allow_invoice_overpayment`
+        let config = VerifierConfig {
+            allow_invoice_overpayment: true,
+            ..config
+        };
+        let mut v5 = v4.clone();
+        v5[1] = 5;
+        v5.push(1);
+        assert_eq!(VerifierConfig::LEN_V5, 455);
+        assert_eq!(VerifierConfig::unpack(&v5), Ok(config.clone()));
+
+        // Version 6 appends `max_verifications_per_window` and `window_slots`
+        let config = VerifierConfig {
+            max_verifications_per_window: 20,
+            window_slots: 9000,
+            ..config
+        };
+        let mut v6 = v5.clone();
+        v6[1] = 6;
+        v6.extend_from_slice(&20u64.to_le_bytes());
+        v6.extend_from_slice(&9000u64.to_le_bytes());
+        assert_eq!(VerifierConfig::LEN_V6, 471);
+        assert_eq!(VerifierConfig::unpack(&v6), Ok(config.clone()));
+
+        // Version 7 appends `finality_slots`
+        let config = VerifierConfig {
+            finality_slots: 64,
+            ..config
+        };
+        let mut v7 = v6.clone();
+        v7[1] = 7;
+        v7.extend_from_slice(&64u64.to_le_bytes());
+        assert_eq!(VerifierConfig::LEN_V7, 479);
+        assert_eq!(VerifierConfig::unpack(&v7), Ok(config.clone()));
+
+        // Version 8 appends `scratch_ttl_slots`
+        let config = VerifierConfig {
+            scratch_ttl_slots: 3000,
+            ..config
+        };
+        let mut v8 = v7;
+        v8[1] = 8;
+        v8.extend_from_slice(&3000u64.to_le_bytes());
+        assert_eq!(VerifierConfig::LEN_V8, 487);
+        assert_eq!(VerifierConfig::unpack(&v8), Ok(config.clone()));
+
+        // Version 9 appends `track_relayer`
+        let config = VerifierConfig {
+            track_relayer: true,
+            ..config
+        };
+        let mut expected = v8;
+        expected[1] = 9;
+        expected.push(1);
+        assert_eq!(VerifierConfig::LEN, 488);
+        assert_eq!(config.to_account_data(), expected);
+        assert_eq!(VerifierConfig::unpack(&expected), Ok(config));
+    }
+
+    #[test]
+    fn test_receipt_store_keeps_version_1_accounts() {
+        let mut v1 = receipt().to_account_data()[..PaymentReceipt::LEN_V1].to_vec();
+        v1[1] = 1;
+
+        let mut updated = PaymentReceipt {
+            status: ReceiptStatus::Active,
+            slot: 250000100,
+            ..receipt()
+        };
+        let mut data = v1.clone();
+        assert_eq!(updated.store(&mut data), Ok(()));
+        assert_eq!(data[..2], [2, 1]);
+        assert_eq!(PaymentReceipt::unpack(&data), Ok(updated.clone()));
+
+        // A version 1 receipt is never consumable, so it cannot be consumed
+        updated.status = ReceiptStatus::Consumed;
+        assert_eq!(updated.store(&mut data), Err(VerifierError::InvalidAccountLength));
+
+        updated.consumable = true;
+        let mut data = vec![0u8; PaymentReceipt::LEN_V2];
+        assert_eq!(updated.store(&mut data), Ok(()));
+        assert_eq!(data[..2], [2, 2]);
+        assert_eq!(PaymentReceipt::unpack(&data), Ok(updated.clone()));
+
+        // Nor is a version 2 receipt finalized in place
+        updated.finalized = true;
+        assert_eq!(updated.store(&mut data), Err(VerifierError::InvalidAccountLength));
+        let mut data = vec![0u8; PaymentReceipt::LEN_V3];
+        assert_eq!(updated.store(&mut data), Ok(()));
+        assert_eq!(data[..2], [2, 3]);
+        assert_eq!(PaymentReceipt::unpack(&data), Ok(updated.clone()));
+
+        // Nor does a version 3 receipt take a revealed amount
+        updated.revealed_amount = 1500000;
+        assert_eq!(updated.store(&mut data), Err(VerifierError::InvalidAccountLength));
+        let mut data = vec![0u8; PaymentReceipt::LEN_V4];
+        assert_eq!(updated.store(&mut data), Ok(()));
+        assert_eq!(data[..2], [2, 4]);
+        assert_eq!(PaymentReceipt::unpack(&data), Ok(updated.clone()));
+
+        // Nor a version 4 receipt a relayer
+        updated.relayer = Pubkey::new_from_array([7u8; 32]);
+        assert_eq!(updated.store(&mut data), Err(VerifierError::InvalidAccountLength));
+        let mut data = vec![0u8; PaymentReceipt::LEN];
+        assert_eq!(updated.store(&mut data), Ok(()));
+        assert_eq!(data[..2], [2, 5]);
+        assert_eq!(PaymentReceipt::unpack(&data), Ok(updated));
+    }
+
+    #[test]
+    fn test_receipt_consumers() {
+        let recipient = Pubkey::new_from_array([2u8; 32]);
+        let delegate = Pubkey::new_from_array([6u8; 32]);
+        let outsider = Pubkey::new_unique();
+
+        let without = receipt();
+        assert!(without.may_consume(&recipient));
+        assert!(!without.may_consume(&delegate));
+        // No delegate is not a delegate of the default key
+        assert!(!without.may_consume(&Pubkey::default()));
+
+        let with = PaymentReceipt { delegate, ..receipt() };
+        assert!(with.may_consume(&recipient));
+        assert!(with.may_consume(&delegate));
+        assert!(!with.may_consume(&outsider));
+    }
+
+    #[test]
+    fn test_config_store_keeps_version_1_accounts() {
+        let mut v1 = config().to_account_data()[..VerifierConfig::LEN_V1].to_vec();
+        v1[1] = 1;
+
+        let mut updated = config();
+        updated.max_receipt_age_slots = 300;
+        let mut data = v1.clone();
+        assert_eq!(updated.store(&mut data), Ok(()));
+        assert_eq!(data[..2], [1, 1]);
+        assert_eq!(VerifierConfig::unpack(&data), Ok(updated.clone()));
+
+        // Enabling the audit log needs the extra byte
+        updated.audit_enabled = true;
+        assert_eq!(updated.store(&mut data), Err(VerifierError::InvalidAccountLength));
+
+        let mut data = vec![0u8; VerifierConfig::LEN];
+        assert_eq!(updated.store(&mut data), Ok(()));
+        assert_eq!(VerifierConfig::unpack(&data), Ok(updated));
+
+        let mut truncated = v1.clone();
+        truncated[1] = 2;
+        assert_eq!(
+            VerifierConfig::unpack(&truncated),
+            Err(VerifierError::InvalidAccountLength)
+        );
+    }
+
+    #[test]
+    fn test_config_store_keeps_version_2_accounts() {
+        let mut v2 = config().to_account_data()[..VerifierConfig::LEN_V2].to_vec();
+        v2[1] = 2;
+
+        let mut updated = config();
+        updated.audit_enabled = true;
+        let mut data = v2.clone();
+        assert_eq!(updated.store(&mut data), Ok(()));
+        assert_eq!(data[..2], [1, 2]);
+        assert_eq!(VerifierConfig::unpack(&data), Ok(updated.clone()));
+
+        // Allowing previous keys needs the version 3 field
+        updated.max_key_deprecation_slots = 1000;
+        assert_eq!(updated.store(&mut data), Err(VerifierError::InvalidAccountLength));
+
+        let mut data = vec![0u8; VerifierConfig::LEN_V3];
+        assert_eq!(updated.store(&mut data), Ok(()));
+        assert_eq!(data[..2], [1, 3]);
+        assert_eq!(VerifierConfig::unpack(&data), Ok(updated.clone()));
+
+        // Co-signers need the version 4 field
+        updated.admin_signers = AdminSigners::new(1, &[Pubkey::new_unique()]).unwrap();
+        assert_eq!(updated.store(&mut data), Err(VerifierError::InvalidAccountLength));
+
+        let mut data = vec![0u8; VerifierConfig::LEN_V4];
+        assert_eq!(updated.store(&mut data), Ok(()));
+        assert_eq!(data[..2], [1, 4]);
+        assert_eq!(VerifierConfig::unpack(&data), Ok(updated.clone()));
+
+        // Overpayment the version 5 one
+        updated.allow_invoice_overpayment = true;
+        assert_eq!(updated.store(&mut data), Err(VerifierError::InvalidAccountLength));
+
+        let mut data = vec![0u8; VerifierConfig::LEN_V5];
+        assert_eq!(updated.store(&mut data), Ok(()));
+        assert_eq!(data[..2], [1, 5]);
+        assert_eq!(VerifierConfig::unpack(&data), Ok(updated.clone()));
+
+        // A rate limit the version 6 ones
+        updated.max_verifications_per_window = 10;
+        updated.window_slots = 150;
+        assert_eq!(updated.store(&mut data), Err(VerifierError::InvalidAccountLength));
+
+        let mut data = vec![0u8; VerifierConfig::LEN_V6];
+        assert_eq!(updated.store(&mut data), Ok(()));
+        assert_eq!(data[..2], [1, 6]);
+        assert_eq!(VerifierConfig::unpack(&data), Ok(updated.clone()));
+
+        // And a finality depth the version 7 ones
+        updated.finality_slots = 64;
+        assert_eq!(updated.store(&mut data), Err(VerifierError::InvalidAccountLength));
+
+        let mut data = vec![0u8; VerifierConfig::LEN_V7];
+        assert_eq!(updated.store(&mut data), Ok(()));
+        assert_eq!(data[..2], [1, 7]);
+        assert_eq!(VerifierConfig::unpack(&data), Ok(updated.clone()));
+
+        // Only a version 8 one set a scratch TTL
+        updated.scratch_ttl_slots = 3000;
+        assert_eq!(updated.store(&mut data), Err(VerifierError::InvalidAccountLength));
+
+        let mut data = vec![0u8; VerifierConfig::LEN_V8];
+        assert_eq!(updated.store(&mut data), Ok(()));
+        assert_eq!(data[..2], [1, 8]);
+        assert_eq!(VerifierConfig::unpack(&data), Ok(updated.clone()));
+
+        // And only a version 9 one track relayers
+        updated.track_relayer = true;
+        assert_eq!(updated.store(&mut data), Err(VerifierError::InvalidAccountLength));
+
+        let mut data = vec![0u8; VerifierConfig::LEN];
+        assert_eq!(updated.store(&mut data), Ok(()));
+        assert_eq!(data[..2], [1, 9]);
+        assert_eq!(VerifierConfig::unpack(&data), Ok(updated));
+    }
+
+    #[test]
+    fn test_receipt_finality_depth() {
+        // An unset depth waits the default, and the boundary slot passes
+        let config = config();
+        assert!(!config.receipt_final_at(1000, 1000 + DEFAULT_FINALITY_SLOTS - 1));
+        assert!(config.receipt_final_at(1000, 1000 + DEFAULT_FINALITY_SLOTS));
+
+        let config = VerifierConfig {
+            finality_slots: 150,
+            ..config
+        };
+        assert!(!config.receipt_final_at(1000, 1149));
+        assert!(config.receipt_final_at(1000, 1150));
+        // A slot before the receipt's, and a depth past the slot range
+        assert!(!config.receipt_final_at(1000, 999));
+        assert!(!config.receipt_final_at(u64::MAX - 100, u64::MAX));
+    }
+
+    #[test]
+    fn test_scratch_ttl() {
+        // An unset TTL waits the default, and the boundary slot passes
+        let config = config();
+        assert!(!config.scratch_stale_at(1000, 1000 + DEFAULT_SCRATCH_TTL_SLOTS - 1));
+        assert!(config.scratch_stale_at(1000, 1000 + DEFAULT_SCRATCH_TTL_SLOTS));
+
+        let config = VerifierConfig {
+            scratch_ttl_slots: 3000,
+            ..config
+        };
+        assert!(!config.scratch_stale_at(1000, 3999));
+        assert!(config.scratch_stale_at(1000, 4000));
+
+        // A TTL below the floor, stored before the floor was enforced, still
+        // waits the floor
+        let config = VerifierConfig {
+            scratch_ttl_slots: 1,
+            ..config
+        };
+        assert!(!config.scratch_stale_at(1000, 1000 + MIN_SCRATCH_TTL_SLOTS - 1));
+        assert!(config.scratch_stale_at(1000, 1000 + MIN_SCRATCH_TTL_SLOTS));
+        assert!(!config.scratch_stale_at(u64::MAX - 100, u64::MAX));
+    }
+
+    #[test]
+    fn test_batch_buffer_header() {
+        let header = BatchBuffer {
+            authority: Pubkey::new_unique(),
+            last_write_slot: 42,
+        };
+        let mut data = header.to_account_data();
+        data.extend_from_slice(&[7u8; 5]);
+        assert_eq!(BatchBuffer::split(&data), Ok((header, &[7u8; 5][..])));
+
+        // A buffer written before the header, and one too short for it
+        assert_eq!(
+            BatchBuffer::split(&[2, 0, 0, 0, 9, 9, 9]),
+            Err(VerifierError::InvalidAccountLength)
+        );
+        let mut legacy = vec![2u8, 0, 0, 0];
+        legacy.resize(BatchBuffer::LEN, 0);
+        assert_eq!(
+            BatchBuffer::split(&legacy),
+            Err(VerifierError::WrongAccountDiscriminator)
+        );
+    }
+
+    #[test]
+    fn test_admin_signer_threshold() {
+        let keys: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        let set = AdminSigners::new(2, &keys).unwrap();
+        assert_eq!(set.signers(), &keys[..]);
+
+        let outsider = Pubkey::new_unique();
+        let passed = [
+            (keys[0], true),
+            // Passed twice: counts once
+            (keys[0], true),
+            // Listed but not signing
+            (keys[1], false),
+            // Signing but not listed
+            (outsider, true),
+            (keys[2], true),
+        ];
+        let owner = Pubkey::default();
+        let mut lamports = [0u64; 5];
+        let mut data = [[0u8; 0]; 5];
+        let accounts: Vec<AccountInfo> = passed
+            .iter()
+            .zip(lamports.iter_mut())
+            .zip(data.iter_mut())
+            .map(|(((key, is_signer), lamports), data)| {
+                AccountInfo::new(key, *is_signer, false, lamports, data, &owner, false, 0)
+            })
+            .collect();
+
+        // Exactly the threshold
+        assert_eq!(set.count_signed(&accounts), 2);
+        assert_eq!(set.check(&accounts), Ok(()));
+
+        // Below it, however often the one signer repeats
+        assert_eq!(set.count_signed(&accounts[..4]), 1);
+        assert_eq!(set.check(&accounts[..4]), Err(VerifierError::AdminThresholdNotMet));
+
+        // No threshold, nothing to check
+        assert_eq!(AdminSigners::default().check(&[]), Ok(()));
+    }
+
+    #[test]
+    fn test_admin_signer_set_validation() {
+        let a = Pubkey::new_unique();
+        let b = Pubkey::new_unique();
+        assert!(AdminSigners::new(0, &[]).is_ok());
+        assert!(AdminSigners::new(2, &[a, b]).is_ok());
+
+        for (threshold, signers) in [
+            (3, vec![a, b]),
+            (0, vec![a]),
+            (1, vec![a, a]),
+            (1, vec![a, Pubkey::default()]),
+            (1, vec![a; MAX_ADMIN_SIGNERS + 1]),
+        ] {
+            assert_eq!(
+                AdminSigners::new(threshold, &signers),
+                Err(VerifierError::InvalidAdminSigners),
+                "{} of {:?}",
+                threshold,
+                signers
+            );
+        }
+    }
+
+    #[test]
+    fn test_receipt_layouts_pinned() {
+        let v1 = layout(
+            2,
+            &[
+                &[1u8; 32],
+                &[2u8; 32],
+                &1000000u64.to_le_bytes(),
+                &[3u8; 32],
+                &[5u8; 32],
+                &[1],
+                &250000000u64.to_le_bytes(),
+                &[254],
+            ],
+        );
+        assert_eq!(PaymentReceipt::LEN_V1, 148);
+        assert_eq!(PaymentReceipt::unpack(&v1), Ok(receipt()));
+
+        // Version 2 appends `consumable` and `delegate`
+        let consumable = PaymentReceipt {
+            status: ReceiptStatus::Consumed,
+            consumable: true,
+            delegate: Pubkey::new_from_array([6u8; 32]),
+            ..receipt()
+        };
+        let mut v2 = v1.clone();
+        v2[1] = 2;
+        v2[2 + 32 + 32 + 8 + 32 + 32] = 2;
+        v2.push(1);
+        v2.extend_from_slice(&[6u8; 32]);
+        assert_eq!(PaymentReceipt::LEN_V2, 181);
+        assert_eq!(PaymentReceipt::unpack(&v2), Ok(consumable.clone()));
+
+        // Version 3 appends `finalized`
+        let finalized = PaymentReceipt {
+            finalized: true,
+            ..consumable
+        };
+        let mut v3 = v2;
+        v3[1] = 3;
+        v3.push(1);
+        assert_eq!(PaymentReceipt::LEN_V3, 182);
+        assert_eq!(PaymentReceipt::unpack(&v3), Ok(finalized.clone()));
+
+        // Version 4 appends the revealed amount
+        let revealed = PaymentReceipt {
+            revealed_amount: 1500000,
+            ..finalized
+        };
+        let mut v4 = v3;
+        v4[1] = 4;
+        v4.extend_from_slice(&1500000u64.to_le_bytes());
+        assert_eq!(PaymentReceipt::LEN_V4, 190);
+        assert_eq!(PaymentReceipt::unpack(&v4), Ok(revealed.clone()));
+
+        // Version 5 appends the relayer, its compute unit price and the
+        // rebate flag
+        let rebated = PaymentReceipt {
+            relayer: Pubkey::new_from_array([7u8; 32]),
+            compute_unit_price: 5000,
+            rebate_claimed: true,
+            ..revealed
+        };
+        let mut expected = v4;
+        expected[1] = 5;
+        expected.extend_from_slice(&[7u8; 32]);
+        expected.extend_from_slice(&5000u64.to_le_bytes());
+        expected.push(1);
+        assert_eq!(PaymentReceipt::LEN, 231);
+        assert_eq!(rebated.to_account_data(), expected);
+        assert_eq!(PaymentReceipt::unpack(&expected), Ok(rebated));
+
+        let split = SplitPaymentReceipt {
+            proof_hash: [1u8; 32],
+            recipients: [[2u8; 32], [3u8; 32]],
+            min_amounts: [600000, 400000],
+            settled: true,
+            payer: Pubkey::new_from_array([5u8; 32]),
+            bump: 253,
+            settled_amounts: [0; 2],
+        };
+        let v1 = layout(
+            3,
+            &[
+                &[1u8; 32],
+                &[2u8; 32],
+                &[3u8; 32],
+                &600000u64.to_le_bytes(),
+                &400000u64.to_le_bytes(),
+                &[1],
+                &[5u8; 32],
+                &[253],
+            ],
+        );
+        assert_eq!(SplitPaymentReceipt::LEN_V1, 148);
+        assert_eq!(SplitPaymentReceipt::unpack(&v1), Ok(split.clone()));
+
+        // Version 2 appends the net settled amounts
+        let split = SplitPaymentReceipt {
+            settled_amounts: [594000, 396000],
+            ..split
+        };
+        let mut expected = v1;
+        expected[1] = 2;
+        expected.extend_from_slice(&594000u64.to_le_bytes());
+        expected.extend_from_slice(&396000u64.to_le_bytes());
+        assert_eq!(SplitPaymentReceipt::LEN, 164);
+        assert_eq!(split.to_account_data(), expected);
+        assert_eq!(SplitPaymentReceipt::unpack(&expected), Ok(split));
+
+        let index = InvoiceIndex {
+            invoice_id: [3u8; 32],
+            receipt: Pubkey::new_from_array([6u8; 32]),
+            status: ReceiptStatus::Refunded,
+            payments: 2,
+            bump: 251,
+        };
+        let expected = layout(10, &[&[3u8; 32], &[6u8; 32], &[1], &2u32.to_le_bytes(), &[251]]);
+        assert_eq!(InvoiceIndex::LEN, 72);
+        assert_eq!(index.to_account_data(), expected);
+        assert_eq!(InvoiceIndex::unpack(&expected), Ok(index));
+
+        let denied = DeniedRecipient {
+            recipient: [4u8; 32],
+            bump: 250,
+        };
+        let expected = layout(11, &[&[4u8; 32], &[250]]);
+        assert_eq!(DeniedRecipient::LEN, 35);
+        assert_eq!(denied.to_account_data(), expected);
+        assert_eq!(DeniedRecipient::unpack(&expected), Ok(denied));
+
+        let batch = BatchReceipt {
+            transcript_hash: [5u8; 32],
+            count: 3,
+            receipt_root: [8u8; 32],
+            slot: 77,
+            relayer: Pubkey::new_from_array([9u8; 32]),
+            bump: 249,
+        };
+        let expected = layout(
+            12,
+            &[&[5u8; 32], &[3], &[8u8; 32], &77u64.to_le_bytes(), &[9u8; 32], &[249]],
+        );
+        assert_eq!(BatchReceipt::LEN, 108);
+        assert_eq!(batch.to_account_data(), expected);
+        assert_eq!(BatchReceipt::unpack(&expected), Ok(batch));
+    }
+
+    #[test]
+    fn test_invoice_second_payment() {
+        let mut index = InvoiceIndex {
+            invoice_id: [3u8; 32],
+            receipt: Pubkey::new_unique(),
+            status: ReceiptStatus::Active,
+            payments: 1,
+            bump: 251,
+        };
+        assert_eq!(index.check_new_payment(false), Err(VerifierError::InvoiceAlreadyPaid));
+        assert_eq!(index.check_new_payment(true), Ok(()));
+
+        // A refunded invoice may be paid again
+        index.status = ReceiptStatus::Refunded;
+        assert_eq!(index.check_new_payment(false), Ok(()));
+        let receipt = Pubkey::new_unique();
+        index.record(receipt);
+        assert_eq!(index.receipt, receipt);
+        assert_eq!(index.status, ReceiptStatus::Active);
+        assert_eq!(index.payments, 2);
+    }
+
+    #[test]
+    fn test_stats_nullifier_and_prepared_layouts_pinned() {
+        let stats = RecipientStats {
+            count: 3,
+            total_min_amount: 3500000,
+            last_verified_slot: 107,
+            window_start_slot: 100,
+            window_count: 2,
+            ..stats()
+        };
+        let expected = layout(
+            4,
+            &[
+                &[4u8; 32],
+                &3u64.to_le_bytes(),
+                &3500000u128.to_le_bytes(),
+                &107u64.to_le_bytes(),
+                &[255],
+                &100u64.to_le_bytes(),
+                &2u64.to_le_bytes(),
+            ],
+        );
+        assert_eq!(RecipientStats::LEN_V1, 67);
+        assert_eq!(RecipientStats::LEN, 83);
+        assert_eq!(stats.to_account_data(), expected);
+        assert_eq!(RecipientStats::unpack(&expected), Ok(stats));
+
+        let spent = SpentNullifier {
+            nullifier: [7u8; 32],
+            epoch: 19675,
+            bump: 252,
+        };
+        let expected = layout(5, &[&[7u8; 32], &19675u64.to_le_bytes(), &[252]]);
+        assert_eq!(SpentNullifier::LEN, 43);
+        assert_eq!(spent.to_account_data(), expected);
+        assert_eq!(SpentNullifier::unpack(&expected), Ok(spent));
+
+        let prepared = PreparedVk {
+            circuit: CircuitId::Subscription,
+            vk_hash: [8u8; 32],
+            neg_alpha_g1: G1Point::new([9u8; 64]),
+            alpha_beta: [10u8; 384],
+            bump: 251,
+            previous_vk_hash: [0u8; 32],
+            deprecated_until_slot: 0,
+            revoked_vk_hash: [0u8; 32],
+            hash_backend: HashBackend::Keccak256,
+        };
+        let v1 = layout(6, &[&[5], &[8u8; 32], &[9u8; 64], &[10u8; 384], &[251]]);
+        assert_eq!(PreparedVk::LEN_V1, 484);
+        assert_eq!(PreparedVk::unpack(&v1), Ok(prepared.clone()));
+
+        // Version 2 appends the deprecation window
+        let prepared = PreparedVk {
+            previous_vk_hash: [11u8; 32],
+            deprecated_until_slot: 250000000,
+            ..prepared
+        };
+        let mut v2 = v1.clone();
+        v2[1] = 2;
+        v2.extend_from_slice(&[11u8; 32]);
+        v2.extend_from_slice(&250000000u64.to_le_bytes());
+        assert_eq!(PreparedVk::LEN_V2, 524);
+        assert_eq!(PreparedVk::unpack(&v2), Ok(prepared.clone()));
+
+        // Version 3 appends the revoked key's hash
+        let prepared = PreparedVk {
+            revoked_vk_hash: [12u8; 32],
+            ..prepared
+        };
+        let mut v3 = v2.clone();
+        v3[1] = 3;
+        v3.extend_from_slice(&[12u8; 32]);
+        assert_eq!(PreparedVk::LEN_V3, 556);
+        assert_eq!(PreparedVk::unpack(&v3), Ok(prepared.clone()));
+
+        // Version 4 appends the hash backend
+        let prepared = PreparedVk {
+            hash_backend: HashBackend::Sha256,
+            ..prepared
+        };
+        let mut v4 = v3;
+        v4[1] = 4;
+        v4.push(1);
+        assert_eq!(PreparedVk::LEN_V4, 557);
+        assert_eq!(PreparedVk::unpack(&v4), Ok(prepared.clone()));
+
+        // Version 5 keeps the layout
+        let mut expected = v4;
+        expected[1] = 5;
+        assert_eq!(PreparedVk::LEN, 557);
+        assert_eq!(prepared.to_account_data(), expected);
+        assert_eq!(PreparedVk::unpack(&expected), Ok(prepared));
+    }
+
+    #[test]
+    fn test_pool_layout_pinned() {
+        let mut pool = ShieldedPool {
+            tree: MerkleTree::new(),
+            bump: 250,
+        };
+        pool.tree.next_index = 5;
+        pool.tree.filled_subtrees[0] = [11u8; 32];
+        pool.tree.roots[3] = [12u8; 32];
+        pool.tree.root_index = 3;
+
+        let mut subtrees = [[0u8; 32]; MERKLE_TREE_DEPTH];
+        subtrees.copy_from_slice(&crate::merkle::ZERO_HASHES[..MERKLE_TREE_DEPTH]);
+        subtrees[0] = [11u8; 32];
+        let mut roots = [[0u8; 32]; crate::merkle::MAX_ROOT_HISTORY_SIZE];
+        roots[0] = crate::merkle::ZERO_HASHES[MERKLE_TREE_DEPTH];
+        roots[3] = [12u8; 32];
+        let expected = layout(
+            8,
+            &[
+                &5u64.to_le_bytes(),
+                &subtrees.concat(),
+                &roots.concat(),
+                &3u32.to_le_bytes(),
+                &30u32.to_le_bytes(),
+                &[250],
+            ],
+        );
+        assert_eq!(ShieldedPool::LEN, 2707);
+        assert_eq!(pool.to_account_data(), expected);
+        assert_eq!(ShieldedPool::unpack(&expected), Ok(pool));
+    }
+
+    #[test]
+    fn test_unpack_rejections() {
+        let data = receipt().to_account_data();
+
+        // Same seed prefix, different type
+        assert_eq!(
+            SplitPaymentReceipt::unpack(&data),
+            Err(VerifierError::WrongAccountDiscriminator)
+        );
+        assert_eq!(
+            PaymentReceipt::unpack(&[0u8; PaymentReceipt::LEN]),
+            Err(VerifierError::WrongAccountDiscriminator)
+        );
+
+        assert_eq!(
+            PaymentReceipt::unpack(&data[..data.len() - 1]),
+            Err(VerifierError::InvalidAccountLength)
+        );
+        let mut padded = data.clone();
+        padded.push(0);
+        assert_eq!(
+            PaymentReceipt::unpack(&padded),
+            Err(VerifierError::InvalidAccountLength)
+        );
+        assert_eq!(PaymentReceipt::unpack(&[2]), Err(VerifierError::InvalidAccountLength));
+        assert_eq!(PaymentReceipt::unpack(&[]), Err(VerifierError::InvalidAccountLength));
+
+        let mut newer = data.clone();
+        newer[1] = 3;
+        assert_eq!(
+            PaymentReceipt::unpack(&newer),
+            Err(VerifierError::UnsupportedAccountVersion)
+        );
+
+        // Status byte outside the enum
+        let mut bad_status = data.clone();
+        bad_status[2 + 32 + 32 + 8 + 32 + 32] = 7;
+        assert_eq!(
+            PaymentReceipt::unpack(&bad_status),
+            Err(VerifierError::MalformedAccountData)
+        );
+
+        let mut short = vec![0u8; PaymentReceipt::LEN - 1];
+        assert_eq!(
+            receipt().pack(&mut short),
+            Err(VerifierError::InvalidAccountLength)
+        );
+    }
+
+    /// A layout at version 2 that still reads its version 1 accounts
+    #[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq, Eq)]
+    struct Migrated {
+        value: u64,
+        /// Added in version 2
+        flags: u8,
+    }
+
+    impl StateAccount for Migrated {
+        const DISCRIMINATOR: u8 = 0xee;
+        const VERSION: u8 = 2;
+        const LEN: usize = ACCOUNT_HEADER_LEN + 8 + 1;
+
+        fn unpack_version(version: u8, body: &[u8]) -> Result<Self, VerifierError> {
+            match version {
+                1 => {
+                    let value = u64::try_from_slice(body)
+                        .map_err(|_| VerifierError::MalformedAccountData)?;
+                    Ok(Self { value, flags: 0 })
+                }
+                2 => Self::try_from_slice(body).map_err(|_| VerifierError::MalformedAccountData),
+                _ => Err(VerifierError::UnsupportedAccountVersion),
+            }
+        }
+    }
+
+    #[test]
+    fn test_version_migration_hook() {
+        let mut v1 = vec![0xee, 1];
+        v1.extend_from_slice(&42u64.to_le_bytes());
+        assert_eq!(Migrated::unpack(&v1), Ok(Migrated { value: 42, flags: 0 }));
+
+        // Rewritten at the current version
+        let current = Migrated { value: 42, flags: 3 }.to_account_data();
+        assert_eq!(current[1], 2);
+        assert_eq!(Migrated::unpack(&current), Ok(Migrated { value: 42, flags: 3 }));
+    }
+
+    fn prepared() -> PreparedVk {
+        PreparedVk {
+            circuit: CircuitId::Payment,
+            vk_hash: [1u8; 32],
+            neg_alpha_g1: G1Point::IDENTITY,
+            alpha_beta: [0u8; 384],
+            bump: 255,
+            previous_vk_hash: [0u8; 32],
+            deprecated_until_slot: 0,
+            revoked_vk_hash: [0u8; 32],
+            hash_backend: HashBackend::Keccak256,
+        }
+    }
+
+    #[test]
+    fn test_prepared_vk_len_and_staleness() {
+        let prepared = prepared();
+        assert_eq!(prepared.check_fresh(&[1u8; 32]), Ok(()));
+        assert_eq!(
+            prepared.check_fresh(&[2u8; 32]),
+            Err(VerifierError::StalePreparedKey)
+        );
+    }
+
+    #[test]
+    fn test_previous_key_window() {
+        // Any compiled key will do as the previous one
+        let previous = crate::TOKEN_PAYMENT_VK;
+        let mut prepared = prepared();
+        assert!(!prepared.accepts_previous_key(&previous, 0));
+
+        prepared.previous_vk_hash = previous.hash();
+        prepared.deprecated_until_slot = 5000;
+        assert!(prepared.accepts_previous_key(&previous, 4999));
+        assert!(prepared.accepts_previous_key(&previous, 5000));
+        assert!(!prepared.accepts_previous_key(&previous, 5001));
+
+        // The window belongs to the key it was opened for
+        assert!(!prepared.accepts_previous_key(&crate::PAYMENT_VK, 4999));
+
+        // and never reopens for a revoked key
+        prepared.revoked_vk_hash = previous.hash();
+        assert!(!prepared.accepts_previous_key(&previous, 4999));
+    }
+
+    #[test]
+    fn test_revocation_names_one_key() {
+        let mut prepared = prepared();
+        assert_eq!(prepared.check_not_revoked(), Ok(()));
+        assert!(!prepared.is_revoked(&[0u8; 32]));
+
+        prepared.revoked_vk_hash = prepared.vk_hash;
+        assert_eq!(prepared.check_not_revoked(), Err(VerifierError::KeyRevoked));
+
+        // Preparing a different key leaves the revoked one on record
+        prepared.vk_hash = [2u8; 32];
+        assert_eq!(prepared.check_not_revoked(), Ok(()));
+        assert!(prepared.is_revoked(&[1u8; 32]));
+    }
+}
@@ -0,0 +1,130 @@
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar::{clock::Clock, Sysvar},
+};
+
+use crate::{
+    events::{NullifierConsumed, ProofVerified, VerifierEvent},
+    layout::VERIFY_SUBSCRIPTION,
+    state::{
+        DeniedRecipient, PreparedVk, SpentNullifier, StateAccount, VerifierConfig, NULLIFIER_SEED,
+        NULLIFIER_SEED_VERSION,
+    },
+    utils::{create_pda_account, report_dry_run},
+    verify_groth16, CircuitId, Groth16Proof, SubscriptionPublicInputs, VerifierError,
+};
+
+/// Verify a subscription proof for one billing epoch and spend its nullifier
+///
+/// Nullifier PDAs are keyed by (circuit, nullifier, epoch), so the same
+/// subscription verifies once in each epoch. A dry run leaves the nullifier
+/// unspent.
+///
+/// Nullifiers spent before the seeds carried a circuit id stay at their legacy
+/// address and are still refused. Legacy epochs fall outside `check_epoch`'s
+/// window one epoch after the upgrade, after which account 5 can be dropped.
+///
+/// Accounts expected:
+/// 0. `[signer, writable]` Payer funding the nullifier rent
+/// 1. `[]` Config PDA: ["config"]
+/// 2. `[writable]` Nullifier PDA:
+///    ["nullifier", seed version, circuit id, nullifier, epoch (u64 LE)]
+/// 3. `[]` System program
+/// 4. `[]` Denylist marker PDA: ["denied", recipient_pubkey]
+/// 5. `[]` Legacy nullifier PDA: ["nullifier", nullifier, epoch (u64 LE)]
+/// 6. `[]` Prepared key PDA: ["prepared-vk", 5]
+pub fn process_verify_subscription(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    proof: &Groth16Proof,
+    public_inputs: &SubscriptionPublicInputs,
+    dry_run: bool,
+) -> ProgramResult {
+    let vk = CircuitId::Subscription.verification_key();
+    vk.check_signal_layout(SubscriptionPublicInputs::SIGNAL_LAYOUT)?;
+
+    let layout = VERIFY_SUBSCRIPTION;
+    layout.check(accounts)?;
+    let payer = &accounts[layout.payer];
+    let config_account = &accounts[layout.config];
+    let nullifier_account = &accounts[layout.nullifier];
+    let system_program = &accounts[layout.system_program];
+    let marker_account = &accounts[layout.marker];
+    let legacy_nullifier_account = &accounts[layout.legacy_nullifier];
+    PreparedVk::load_active(&accounts[layout.prepared_key], program_id, CircuitId::Subscription)?;
+
+    let (nullifier_address, bump) = SpentNullifier::address(
+        program_id,
+        CircuitId::Subscription,
+        &public_inputs.nullifier,
+        public_inputs.epoch,
+    );
+    if nullifier_address != *nullifier_account.key {
+        msg!("Nullifier account does not match nullifier and epoch");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    let legacy_address =
+        SpentNullifier::legacy_address(program_id, &public_inputs.nullifier, public_inputs.epoch);
+    if legacy_address != *legacy_nullifier_account.key {
+        msg!("Legacy nullifier account does not match nullifier and epoch");
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if !nullifier_account.data_is_empty() || !legacy_nullifier_account.data_is_empty() {
+        return Err(VerifierError::NullifierAlreadySpent.into());
+    }
+
+    let config = VerifierConfig::load(config_account, program_id)?;
+    let clock = Clock::get()?;
+    config.check_epoch(public_inputs.epoch, clock.unix_timestamp)?;
+    DeniedRecipient::check(marker_account, program_id, &public_inputs.recipient_pubkey)?;
+
+    verify_groth16(vk, proof, &public_inputs.to_scalars())?;
+
+    let spent = SpentNullifier {
+        nullifier: public_inputs.nullifier,
+        epoch: public_inputs.epoch,
+        bump,
+    };
+    if dry_run {
+        report_dry_run(nullifier_account.key, spent.to_account_data());
+        msg!("Dry run: nullifier not spent");
+        return Ok(());
+    }
+
+    create_pda_account(
+        payer,
+        nullifier_account,
+        system_program,
+        program_id,
+        SpentNullifier::LEN,
+        &[
+            NULLIFIER_SEED,
+            &[NULLIFIER_SEED_VERSION],
+            &[CircuitId::Subscription as u8],
+            &public_inputs.nullifier,
+            &public_inputs.epoch.to_le_bytes(),
+            &[bump],
+        ],
+    )?;
+    spent.pack(&mut nullifier_account.data.borrow_mut()[..])?;
+
+    VerifierEvent::ProofVerified(ProofVerified {
+        proof_hash: proof.hash(public_inputs),
+        recipient: public_inputs.recipient_pubkey,
+        min_amount: public_inputs.min_amount,
+    })
+    .emit();
+    VerifierEvent::NullifierConsumed(NullifierConsumed {
+        nullifier: public_inputs.nullifier,
+    })
+    .emit();
+
+    msg!("✓ Subscription epoch paid");
+    #[cfg(not(feature = "privacy-strict"))]
+    log_statement!("Epoch:", public_inputs.epoch);
+    Ok(())
+}
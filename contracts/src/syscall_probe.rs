@@ -0,0 +1,92 @@
+// alt_bn128 syscall availability
+// The alt_bn128 syscalls sit behind a feature gate. Where it is not active,
+// as on an old local validator, the first curve operation fails with an
+// opaque error deep inside verification. Every instruction that uses them
+// therefore probes once, before any other check: doubling the G1 generator
+// must give the known 2G, and anything else, an error included, is reported
+// as `SyscallUnavailable`. Clients read the gate's feature account instead;
+// see `client::bn128_syscalls_enabled`.
+
+use solana_program::{
+    alt_bn128::{alt_bn128_addition, AltBn128Error},
+    msg,
+    pubkey::Pubkey,
+};
+
+use crate::VerifierError;
+
+/// Feature gate that enables the alt_bn128 syscalls
+pub const ALT_BN128_FEATURE_ID: Pubkey =
+    solana_program::pubkey!("A16q37opZdQMCbe5qJ6xpBB9usykfv8jZaMkxvZQi4GJ");
+
+/// The G1 generator (1, 2) twice, as the addition syscall's input
+const PROBE_INPUT: [u8; 128] = {
+    let mut input = [0u8; 128];
+    input[31] = 1;
+    input[63] = 2;
+    input[95] = 1;
+    input[127] = 2;
+    input
+};
+
+/// 2G, x then y
+const PROBE_OUTPUT: [u8; 64] = [
+    0x03, 0x06, 0x44, 0xe7, 0x2e, 0x13, 0x1a, 0x02,
+    0x9b, 0x85, 0x04, 0x5b, 0x68, 0x18, 0x15, 0x85,
+    0xd9, 0x78, 0x16, 0xa9, 0x16, 0x87, 0x1c, 0xa8,
+    0xd3, 0xc2, 0x08, 0xc1, 0x6d, 0x87, 0xcf, 0xd3,
+    0x15, 0xed, 0x73, 0x8c, 0x0e, 0x0a, 0x7c, 0x92,
+    0xe7, 0x84, 0x5f, 0x96, 0xb2, 0xae, 0x9c, 0x0a,
+    0x68, 0xa6, 0xa4, 0x49, 0xe3, 0x53, 0x8f, 0xc7,
+    0xff, 0x3e, 0xbf, 0x7a, 0x5a, 0x18, 0xa2, 0xc4,
+];
+
+/// Fail with `SyscallUnavailable` unless the alt_bn128 syscalls work
+///
+/// Called once per instruction by the dispatcher, for the instructions that
+/// `VerifierInstruction::uses_alt_bn128` lists.
+pub fn ensure_bn128_available() -> Result<(), VerifierError> {
+    probe(alt_bn128_addition)
+}
+
+/// Run the probe through `add`, standing in for the addition syscall
+fn probe<T>(
+    add: impl FnOnce(&[u8], &mut [u8]) -> Result<T, AltBn128Error>,
+) -> Result<(), VerifierError> {
+    let mut output = [0u8; 64];
+    if add(&PROBE_INPUT, &mut output).is_err() || output != PROBE_OUTPUT {
+        msg!("alt_bn128 syscalls are not available on this cluster");
+        return Err(VerifierError::SyscallUnavailable);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probe_passes_where_syscalls_work() {
+        assert_eq!(ensure_bn128_available(), Ok(()));
+    }
+
+    #[test]
+    fn test_probe_maps_failures() {
+        // The syscall reporting an error, whichever
+        for error in [AltBn128Error::UnexpectedError, AltBn128Error::InvalidInputData] {
+            let result = probe(|_: &[u8], _: &mut [u8]| Err::<(), _>(error.clone()));
+            assert_eq!(result, Err(VerifierError::SyscallUnavailable));
+        }
+
+        // A stub that succeeds without computing anything
+        let result = probe(|_: &[u8], _: &mut [u8]| Ok::<(), AltBn128Error>(()));
+        assert_eq!(result, Err(VerifierError::SyscallUnavailable));
+
+        // A correct result through the stub passes
+        let result = probe(|_: &[u8], output: &mut [u8]| {
+            output.copy_from_slice(&PROBE_OUTPUT);
+            Ok::<(), AltBn128Error>(())
+        });
+        assert_eq!(result, Ok(()));
+    }
+}
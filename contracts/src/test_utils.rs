@@ -0,0 +1,420 @@
+// Test harness for programs that integrate with the verifier
+// Enabled with the `test_utils` feature. `setup_verifier_test` returns a
+// `ProgramTest` with the verifier registered under `VERIFIER_TEST_PROGRAM_ID`,
+// a config owned by a fixed test admin, every circuit's prepared key and the
+// fixture key in an account, so a downstream crate adds its own program and
+// starts the bank:
+//
+//   let (mut program_test, mut ctx) = setup_verifier_test();
+//   program_test.add_program("my_program", my_id, processor!(my_processor));
+//   ctx.start(program_test).await;
+//   submit_valid_proof(&mut ctx).await.unwrap();
+//
+// The registered circuits' keys come from a trusted setup, so no proof for
+// them can be made up here. The fixture key is a circuit of its own, verified
+// through `VerifyWithInlineKey`, for which any statement has a proof; receipts
+// for the payment circuit are written directly by `create_receipt_for`.
+// The program's own integration tests use this module, so it cannot rot.
+
+use borsh::BorshSerialize;
+use solana_program::pubkey::Pubkey;
+use solana_program_test::{processor, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::{Account, AccountSharedData},
+    instruction::Instruction,
+    signature::{Keypair, Signer},
+    signer::keypair::keypair_from_seed,
+    transaction::{Transaction, TransactionError},
+};
+
+use crate::{
+    client,
+    endian::Be32,
+    field::Scalar,
+    inline_key::InlineVerificationKey,
+    pda,
+    process_instruction,
+    state::{
+        AdminSigners, InputPolicy, PaymentReceipt, PreparedVk, ReceiptStatus, StateAccount,
+        VerifierConfig,
+    },
+    transcript::HashBackend,
+    CircuitId, G1Point, G2Point, Groth16Proof, PaymentPublicInputs,
+};
+
+/// Program id the harness registers the verifier under
+pub const VERIFIER_TEST_PROGRAM_ID: Pubkey = Pubkey::new_from_array([0x58; 32]);
+
+/// Address of the account holding the fixture key
+pub const FIXTURE_KEY_ACCOUNT: Pubkey = Pubkey::new_from_array([0x4b; 32]);
+
+/// Public inputs of the fixture key `setup_verifier_test` loads
+pub const FIXTURE_KEY_INPUTS: usize = 2;
+
+/// Every registered circuit, each of which takes its prepared key on every
+/// verify instruction
+pub const ALL_CIRCUITS: [CircuitId; 8] = [
+    CircuitId::Payment,
+    CircuitId::TokenPayment,
+    CircuitId::InvoicePayment,
+    CircuitId::SplitPayment,
+    CircuitId::Refund,
+    CircuitId::Subscription,
+    CircuitId::Withdraw,
+    CircuitId::CommittedPayment,
+];
+
+/// Seed of the test admin keypair
+const TEST_ADMIN_SEED: [u8; 32] = [0xad; 32];
+
+/// G2 generator in syscall order: x.c1, x.c0, y.c1, y.c0
+pub const G2_GENERATOR: [u8; 128] = [
+    0x19, 0x8e, 0x93, 0x93, 0x92, 0x0d, 0x48, 0x3a,
+    0x72, 0x60, 0xbf, 0xb7, 0x31, 0xfb, 0x5d, 0x25,
+    0xf1, 0xaa, 0x49, 0x33, 0x35, 0xa9, 0xe7, 0x12,
+    0x97, 0xe4, 0x85, 0xb7, 0xae, 0xf3, 0x12, 0xc2,
+    0x18, 0x00, 0xde, 0xef, 0x12, 0x1f, 0x1e, 0x76,
+    0x42, 0x6a, 0x00, 0x66, 0x5e, 0x5c, 0x44, 0x79,
+    0x67, 0x43, 0x22, 0xd4, 0xf7, 0x5e, 0xda, 0xdd,
+    0x46, 0xde, 0xbd, 0x5c, 0xd9, 0x92, 0xf6, 0xed,
+    0x09, 0x06, 0x89, 0xd0, 0x58, 0x5f, 0xf0, 0x75,
+    0xec, 0x9e, 0x99, 0xad, 0x69, 0x0c, 0x33, 0x95,
+    0xbc, 0x4b, 0x31, 0x33, 0x70, 0xb3, 0x8e, 0xf3,
+    0x55, 0xac, 0xda, 0xdc, 0xd1, 0x22, 0x97, 0x5b,
+    0x12, 0xc8, 0x5e, 0xa5, 0xdb, 0x8c, 0x6d, 0xeb,
+    0x4a, 0xab, 0x71, 0x80, 0x8d, 0xcb, 0x40, 0x8f,
+    0xe3, 0xd1, 0xe7, 0x69, 0x0c, 0x43, 0xd3, 0x7b,
+    0x4c, 0xe6, 0xcc, 0x01, 0x66, 0xfa, 0x7d, 0xaa,
+];
+
+/// 2 times the G2 generator, in syscall order
+pub const G2_DOUBLE: [u8; 128] = [
+    0x20, 0x3e, 0x20, 0x5d, 0xb4, 0xf1, 0x9b, 0x37,
+    0xb6, 0x01, 0x21, 0xb8, 0x3a, 0x73, 0x33, 0x70,
+    0x6d, 0xb8, 0x64, 0x31, 0xc6, 0xd8, 0x35, 0x84,
+    0x99, 0x57, 0xed, 0x8c, 0x39, 0x28, 0xad, 0x79,
+    0x27, 0xdc, 0x72, 0x34, 0xfd, 0x11, 0xd3, 0xe8,
+    0xc3, 0x6c, 0x59, 0x27, 0x7c, 0x3e, 0x6f, 0x14,
+    0x9d, 0x5c, 0xd3, 0xcf, 0xa9, 0xa6, 0x2a, 0xee,
+    0x49, 0xf8, 0x13, 0x09, 0x62, 0xb4, 0xb3, 0xb9,
+    0x19, 0x5e, 0x8a, 0xa5, 0xb7, 0x82, 0x74, 0x63,
+    0x72, 0x2b, 0x8c, 0x15, 0x39, 0x31, 0x57, 0x9d,
+    0x35, 0x05, 0x56, 0x6b, 0x4e, 0xdf, 0x48, 0xd4,
+    0x98, 0xe1, 0x85, 0xf0, 0x50, 0x9d, 0xe1, 0x52,
+    0x04, 0xbb, 0x53, 0xb8, 0x97, 0x7e, 0x5f, 0x92,
+    0xa0, 0xbc, 0x37, 0x27, 0x42, 0xc4, 0x83, 0x09,
+    0x44, 0xa5, 0x9b, 0x4f, 0xe6, 0xb1, 0xc0, 0x46,
+    0x6e, 0x2a, 0x6d, 0xad, 0x12, 0x2b, 0x5d, 0x2e,
+];
+
+/// The verifier's accounts in a test bank, and the bank once started
+pub struct VerifierTestContext {
+    pub program_id: Pubkey,
+    /// Admin of the config, funded with 1 SOL
+    pub admin: Keypair,
+    pub config: Pubkey,
+    /// The key in `FIXTURE_KEY_ACCOUNT`
+    pub fixture_key: InlineVerificationKey,
+    context: Option<ProgramTestContext>,
+}
+
+impl VerifierTestContext {
+    /// Start the bank for `program_test`, which must come from
+    /// `setup_verifier_test`
+    pub async fn start(&mut self, program_test: ProgramTest) {
+        self.context = Some(program_test.start_with_context().await);
+    }
+
+    /// The started bank
+    ///
+    /// Panics before `start`.
+    pub fn context(&mut self) -> &mut ProgramTestContext {
+        self.context.as_mut().expect("VerifierTestContext::start was not called")
+    }
+}
+
+/// A `ProgramTest` with the verifier, its config and the fixture key, plus
+/// the context describing them
+///
+/// Everything is deterministic: the program id, the admin keypair and every
+/// account are the same on every run.
+pub fn setup_verifier_test() -> (ProgramTest, VerifierTestContext) {
+    let program_id = VERIFIER_TEST_PROGRAM_ID;
+    let mut program_test = ProgramTest::new(
+        "x402_zk_verifier",
+        program_id,
+        processor!(process_instruction),
+    );
+
+    let admin = keypair_from_seed(&TEST_ADMIN_SEED).unwrap();
+    program_test.add_account(
+        admin.pubkey(),
+        Account {
+            lamports: 1_000_000_000,
+            ..Account::default()
+        },
+    );
+    let config = add_config(&mut program_test, &program_id, admin.pubkey(), 0);
+    add_prepared_keys(&mut program_test, &program_id);
+
+    let fixture_key = fixture_key(FIXTURE_KEY_INPUTS);
+    program_test.add_account(
+        FIXTURE_KEY_ACCOUNT,
+        Account {
+            lamports: 1_000_000_000,
+            data: fixture_key.try_to_vec().unwrap(),
+            owner: Pubkey::default(),
+            ..Account::default()
+        },
+    );
+
+    let ctx = VerifierTestContext {
+        program_id,
+        admin,
+        config,
+        fixture_key,
+        context: None,
+    };
+    (program_test, ctx)
+}
+
+/// Pre-load a config PDA with an empty recipient root and the default input
+/// policy
+pub fn add_config(
+    program_test: &mut ProgramTest,
+    program_id: &Pubkey,
+    admin: Pubkey,
+    epoch_length_seconds: i64,
+) -> Pubkey {
+    let (config, bump) = pda::config_address(program_id);
+    let data = VerifierConfig {
+        admin,
+        pending_admin: Pubkey::default(),
+        recipient_set_root: [0u8; 32],
+        previous_recipient_set_root: [0u8; 32],
+        previous_root_valid_until: 0,
+        recipient_root_grace_seconds: 0,
+        epoch_length_seconds,
+        input_policy: InputPolicy::default(),
+        max_receipt_age_slots: 0,
+        bump,
+        audit_enabled: false,
+        max_key_deprecation_slots: 0,
+        admin_signers: AdminSigners::default(),
+        allow_invoice_overpayment: false,
+        max_verifications_per_window: 0,
+        window_slots: 0,
+        finality_slots: 0,
+        scratch_ttl_slots: 0,
+        track_relayer: false,
+    }
+    .to_account_data();
+    program_test.add_account(
+        config,
+        Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: *program_id,
+            ..Account::default()
+        },
+    );
+    config
+}
+
+/// Pre-load `circuit`'s prepared key PDA as `PrepareVerificationKey` writes
+/// it for the compiled-in key, and return its address
+pub fn add_prepared_key(
+    program_test: &mut ProgramTest,
+    program_id: &Pubkey,
+    circuit: CircuitId,
+) -> Pubkey {
+    let (prepared, bump) = pda::vkey_address(program_id, circuit);
+    let vk = circuit.verification_key();
+    let data = PreparedVk {
+        circuit,
+        vk_hash: vk.hash(),
+        neg_alpha_g1: vk.alpha_g1.neg(),
+        alpha_beta: [0u8; 384],
+        bump,
+        previous_vk_hash: [0u8; 32],
+        deprecated_until_slot: 0,
+        revoked_vk_hash: [0u8; 32],
+        hash_backend: HashBackend::default(),
+    }
+    .to_account_data();
+    program_test.add_account(
+        prepared,
+        Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: *program_id,
+            ..Account::default()
+        },
+    );
+    prepared
+}
+
+/// `add_prepared_key` for every circuit in `ALL_CIRCUITS`
+pub fn add_prepared_keys(program_test: &mut ProgramTest, program_id: &Pubkey) {
+    for circuit in ALL_CIRCUITS {
+        add_prepared_key(program_test, program_id, circuit);
+    }
+}
+
+/// Fixture circuit for `VerifyWithInlineKey`, unrelated to every registered
+/// circuit: `n` public inputs, IC points k*G and beta = gamma = delta = the
+/// G2 generator, so that A = alpha + L + C, B = that generator proves any
+/// statement
+pub fn fixture_key(n: usize) -> InlineVerificationKey {
+    let mut g = [0u8; 64];
+    g[31] = 1;
+    g[63] = 2;
+    let g = G1Point::new(g);
+    let mut ic = vec![g];
+    for _ in 0..n {
+        let next = ic.last().unwrap().add(&g).unwrap();
+        ic.push(next);
+    }
+    let g2 = G2Point::new(G2_GENERATOR);
+    InlineVerificationKey {
+        alpha_g1: g,
+        beta_g2: g2,
+        gamma_g2: g2,
+        delta_g2: g2,
+        ic,
+    }
+}
+
+/// A proof of `inputs` against a key from `fixture_key`
+pub fn fixture_proof(vk: &InlineVerificationKey, inputs: &[Be32]) -> Groth16Proof {
+    let mut l = vk.ic[0];
+    for (point, input) in vk.ic[1..].iter().zip(inputs) {
+        let scalar = Scalar::from_canonical(*input).unwrap();
+        l = l.add(&point.mul(&scalar).unwrap()).unwrap();
+    }
+    let c = vk.ic[0];
+    Groth16Proof {
+        a: vk.alpha_g1.add(&l).unwrap().add(&c).unwrap(),
+        b: G2Point::new(G2_GENERATOR),
+        c,
+    }
+}
+
+/// Public inputs for `fixture_key(n)`; the first needs more than 64 bits
+pub fn fixture_inputs(n: usize) -> Vec<Be32> {
+    let mut wide = [0u8; 32];
+    wide[0] = 0x20;
+    wide[31] = 7;
+    let mut inputs = vec![Be32::from_syscall(wide)];
+    inputs.extend((1..n as u64).map(Be32::from_u64));
+    inputs
+}
+
+/// Submit an instruction paid by the context's payer, returning its result
+/// and return data
+pub async fn submit_with_return_data(
+    context: &mut ProgramTestContext,
+    instruction: Instruction,
+) -> (Result<(), TransactionError>, Vec<u8>) {
+    let blockhash = context.get_new_latest_blockhash().await.unwrap();
+    let mut transaction =
+        Transaction::new_with_payer(&[instruction], Some(&context.payer.pubkey()));
+    transaction.sign(&[&context.payer], blockhash);
+    let result = context
+        .banks_client
+        .process_transaction_with_metadata(transaction)
+        .await
+        .unwrap();
+    let return_data = result
+        .metadata
+        .and_then(|metadata| metadata.return_data)
+        .map(|return_data| return_data.data);
+    (result.result, return_data.unwrap_or_default())
+}
+
+/// Verify a fixture proof against the fixture key account; succeeds
+pub async fn submit_valid_proof(ctx: &mut VerifierTestContext) -> Result<(), TransactionError> {
+    let inputs = fixture_inputs(FIXTURE_KEY_INPUTS);
+    submit_fixture_proof(ctx, &inputs, &inputs).await
+}
+
+/// Verify a fixture proof against a statement it does not prove; fails with
+/// `InvalidArgument` from the pairing
+pub async fn submit_invalid_proof(ctx: &mut VerifierTestContext) -> Result<(), TransactionError> {
+    let inputs = fixture_inputs(FIXTURE_KEY_INPUTS);
+    let mut other = inputs.clone();
+    other[1] = Be32::from_u64(u64::MAX);
+    submit_fixture_proof(ctx, &inputs, &other).await
+}
+
+async fn submit_fixture_proof(
+    ctx: &mut VerifierTestContext,
+    proved: &[Be32],
+    claimed: &[Be32],
+) -> Result<(), TransactionError> {
+    let proof = fixture_proof(&ctx.fixture_key, proved);
+    let instruction = client::verify_with_key_account_instruction(
+        &ctx.program_id,
+        &FIXTURE_KEY_ACCOUNT,
+        &ctx.fixture_key,
+        proof,
+        claimed.to_vec(),
+    );
+    submit_with_return_data(ctx.context(), instruction).await.0
+}
+
+/// Write an active `PaymentReceipt` paying `recipient`, as `VerifyAndRecord`
+/// would have, and return its address and contents
+///
+/// The receipt is for a fixed statement of 1_000_000 lamports, so
+/// `cpi::read_receipt` accepts it with the returned `proof_hash`.
+pub fn create_receipt_for(
+    ctx: &mut VerifierTestContext,
+    recipient: [u8; 32],
+) -> (Pubkey, PaymentReceipt) {
+    let (proof, public_inputs) = receipt_statement(recipient);
+    let proof_hash = proof.hash(&public_inputs);
+    let (address, bump) = pda::receipt_address(&ctx.program_id, &proof_hash);
+    let receipt = PaymentReceipt {
+        proof_hash,
+        recipient,
+        min_amount: public_inputs.min_amount,
+        invoice_id: [0u8; 32],
+        payer: ctx.admin.pubkey(),
+        status: ReceiptStatus::Active,
+        slot: 0,
+        bump,
+        consumable: false,
+        delegate: Pubkey::default(),
+        finalized: false,
+        revealed_amount: 0,
+        relayer: Pubkey::default(),
+        compute_unit_price: 0,
+        rebate_claimed: false,
+    };
+    let account = Account {
+        lamports: 1_000_000_000,
+        data: receipt.to_account_data(),
+        owner: ctx.program_id,
+        ..Account::default()
+    };
+    ctx.context().set_account(&address, &AccountSharedData::from(account));
+    (address, receipt)
+}
+
+/// The mock statement behind `create_receipt_for`'s receipts
+fn receipt_statement(recipient: [u8; 32]) -> (Groth16Proof, PaymentPublicInputs) {
+    (
+        Groth16Proof {
+            a: G1Point::new([1u8; 64]),
+            b: G2Point::new([2u8; 128]),
+            c: G1Point::new([3u8; 64]),
+        },
+        PaymentPublicInputs {
+            min_amount: 1_000_000,
+            recipient_pubkey: recipient,
+            max_block_age: 60,
+            current_time: 1_700_000_000,
+        },
+    )
+}
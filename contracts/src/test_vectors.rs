@@ -0,0 +1,474 @@
+// Canonical test vectors for SDKs that re-implement the wire formats
+// Enabled with the `test_vectors` feature. `vectors()` runs the fixture proofs
+// below through the same code the program and `client` use, so the expected
+// values are the implementation's own output and cannot drift from it. The
+// `test-vectors` binary dumps them as JSON for TypeScript and Python tests:
+//
+//   cargo run --features test_vectors --bin test-vectors > vectors.json
+//
+// The fixtures are multiples of the curve generators, not proofs against any
+// deployed key: they pin encodings, hashes and addresses, not verification.
+// Byte strings are lowercase hex in the JSON, u64 and i64 values are decimal
+// strings, and addresses are base58.
+
+use borsh::BorshSerialize;
+use serde_json::{json, Value};
+use solana_program::pubkey::Pubkey;
+
+use crate::{
+    client::{batch_coefficients, encode_payment_headers, to_hex, PaymentHeaders},
+    snarkjs,
+    state::SpentNullifier,
+    CircuitId, Groth16Proof, PaymentPublicInputs, PaymentPublicInputsV3,
+};
+
+/// G1 generator multiples 1G to 5G as snarkjs writes coordinates
+const G1_MULTIPLES: [[&str; 2]; 5] = [
+    ["1", "2"],
+    [
+        "1368015179489954701390400359078579693043519447331113978918064868415326638035",
+        "9918110051302171585080402603319702774565515993150576347155970296011118125764",
+    ],
+    [
+        "3353031288059533942658390886683067124040920775575537747144343083137631628272",
+        "19321533766552368860946552437480515441416830039777911637913418824951667761761",
+    ],
+    [
+        "3010198690406615200373504922352659861758983907867017329644089018310584441462",
+        "4027184618003122424972590350825261965929648733675738730716654005365300998076",
+    ],
+    [
+        "10744596414106452074759370245733544594153395043370666422502510773307029471145",
+        "848677436511517736191562425154572367705380862894644942948681172815252343932",
+    ],
+];
+
+/// G2 generator as snarkjs writes it: [[x.c0, x.c1], [y.c0, y.c1]]
+const G2_GENERATOR: [[&str; 2]; 2] = [
+    [
+        "10857046999023057135944570762232829481370756359578518086990519993285655852781",
+        "11559732032986387107991004021392285783925812861821192530917403151452391805634",
+    ],
+    [
+        "8495653923123431417604973247489272438418190587263600148770280649306958101930",
+        "4082367875863433681332203403145435568316851327593401208105741076214120093531",
+    ],
+];
+
+/// Fixture proofs: name, then A and C as multiples of the G1 generator; B is
+/// the G2 generator
+const FIXTURE_PROOFS: [(&str, usize, usize); 3] =
+    [("payment-1", 1, 2), ("payment-2", 3, 4), ("payment-3", 5, 1)];
+
+/// Program id the PDA vectors are derived under
+pub const VECTOR_PROGRAM_ID: Pubkey = Pubkey::new_from_array([7u8; 32]);
+
+/// Every vector, grouped by what it exercises
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestVectors {
+    pub payments: Vec<PaymentVector>,
+    pub invoice_headers: Vec<HeaderVector>,
+    pub batches: Vec<BatchVector>,
+    pub nullifiers: Vec<NullifierVector>,
+}
+
+/// A SOL payment proof and its statement through Borsh, scalar packing and
+/// proof hashing
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaymentVector {
+    pub name: &'static str,
+    /// The proof as snarkjs writes proof.json
+    pub proof_json: Value,
+    pub public_inputs: PaymentPublicInputs,
+    pub proof_borsh: Vec<u8>,
+    pub public_inputs_borsh: Vec<u8>,
+    /// `PaymentPublicInputs::to_scalars`, in IC order
+    pub scalars: Vec<u64>,
+    /// `Groth16Proof::hash`
+    pub proof_hash: [u8; 32],
+}
+
+/// An invoice-bound proof as x402 headers
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeaderVector {
+    pub name: &'static str,
+    pub proof_json: Value,
+    pub public_inputs: PaymentPublicInputsV3,
+    /// `client::encode_payment_headers`
+    pub headers: PaymentHeaders,
+    pub proof_hash: [u8; 32],
+}
+
+/// Fiat-Shamir challenges of a batch of payment vectors
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchVector {
+    pub name: &'static str,
+    /// Names of the `PaymentVector`s in the batch, in order
+    pub payments: Vec<&'static str>,
+    /// One coefficient per proof, big-endian and reduced mod r
+    pub challenges: Vec<[u8; 32]>,
+}
+
+/// A spent-nullifier PDA under `VECTOR_PROGRAM_ID`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NullifierVector {
+    pub circuit: CircuitId,
+    pub nullifier: [u8; 32],
+    pub epoch: u64,
+    pub address: Pubkey,
+    pub bump: u8,
+}
+
+/// Build every vector from the fixtures
+pub fn vectors() -> TestVectors {
+    let payments: Vec<_> = FIXTURE_PROOFS
+        .iter()
+        .enumerate()
+        .map(|(n, (name, a, c))| payment_vector(name, proof_json(*a, *c), payment_inputs(n)))
+        .collect();
+
+    let invoice_headers = vec![header_vector(
+        "invoice-1",
+        proof_json(1, 2),
+        PaymentPublicInputsV3 {
+            min_amount: 1_000_000,
+            recipient_pubkey: [4u8; 32],
+            max_block_age: 60,
+            current_time: 1_700_000_000,
+            invoice_id: [0x1d; 32],
+            valid_until: 1_700_000_600,
+            recipient_set_root: [0u8; 32],
+        },
+    )];
+
+    let batches = vec![
+        batch_vector("batch-of-1", &payments[..1]),
+        batch_vector("batch-of-3", &payments),
+    ];
+
+    let nullifiers = [
+        (CircuitId::Subscription, [0x11; 32], 19_675),
+        (CircuitId::Subscription, [0x11; 32], 19_676),
+        (CircuitId::Withdraw, [0x22; 32], 0),
+    ]
+    .into_iter()
+    .map(|(circuit, nullifier, epoch)| {
+        let (address, bump) =
+            SpentNullifier::address(&VECTOR_PROGRAM_ID, circuit, &nullifier, epoch);
+        NullifierVector {
+            circuit,
+            nullifier,
+            epoch,
+            address,
+            bump,
+        }
+    })
+    .collect();
+
+    TestVectors {
+        payments,
+        invoice_headers,
+        batches,
+        nullifiers,
+    }
+}
+
+fn proof_json(a: usize, c: usize) -> Value {
+    let g1 = |k: usize| json!([G1_MULTIPLES[k - 1][0], G1_MULTIPLES[k - 1][1], "1"]);
+    json!({
+        "pi_a": g1(a),
+        "pi_b": [G2_GENERATOR[0], G2_GENERATOR[1], ["1", "0"]],
+        "pi_c": g1(c),
+        "protocol": "groth16",
+        "curve": "bn128",
+    })
+}
+
+fn parse_fixture(proof_json: &Value) -> Groth16Proof {
+    snarkjs::parse_proof(proof_json).expect("fixture proofs are well-formed")
+}
+
+fn payment_inputs(n: usize) -> PaymentPublicInputs {
+    PaymentPublicInputs {
+        min_amount: 1_000_000 * (n as u64 + 1),
+        recipient_pubkey: [n as u8 + 4; 32],
+        max_block_age: 60,
+        current_time: 1_700_000_000 + n as i64,
+    }
+}
+
+fn payment_vector(
+    name: &'static str,
+    proof_json: Value,
+    public_inputs: PaymentPublicInputs,
+) -> PaymentVector {
+    let proof = parse_fixture(&proof_json);
+    PaymentVector {
+        name,
+        proof_borsh: proof.try_to_vec().unwrap(),
+        public_inputs_borsh: public_inputs.try_to_vec().unwrap(),
+        scalars: public_inputs.to_scalars().to_vec(),
+        proof_hash: proof.hash(&public_inputs),
+        proof_json,
+        public_inputs,
+    }
+}
+
+fn header_vector(
+    name: &'static str,
+    proof_json: Value,
+    public_inputs: PaymentPublicInputsV3,
+) -> HeaderVector {
+    let proof = parse_fixture(&proof_json);
+    HeaderVector {
+        name,
+        headers: encode_payment_headers(&proof, &public_inputs),
+        proof_hash: proof.hash(&public_inputs),
+        proof_json,
+        public_inputs,
+    }
+}
+
+fn batch_vector(name: &'static str, payments: &[PaymentVector]) -> BatchVector {
+    let proofs: Vec<_> = payments
+        .iter()
+        .map(|payment| parse_fixture(&payment.proof_json))
+        .collect();
+    let inputs: Vec<_> = payments.iter().map(|payment| payment.public_inputs.clone()).collect();
+    BatchVector {
+        name,
+        payments: payments.iter().map(|payment| payment.name).collect(),
+        challenges: batch_coefficients(&proofs, &inputs),
+    }
+}
+
+impl TestVectors {
+    /// The vectors in the JSON form the `test-vectors` binary writes
+    pub fn to_json(&self) -> Value {
+        json!({
+            "payments": self.payments.iter().map(PaymentVector::to_json).collect::<Vec<_>>(),
+            "invoice_headers": self
+                .invoice_headers
+                .iter()
+                .map(HeaderVector::to_json)
+                .collect::<Vec<_>>(),
+            "batches": self.batches.iter().map(BatchVector::to_json).collect::<Vec<_>>(),
+            "nullifiers": self.nullifiers.iter().map(NullifierVector::to_json).collect::<Vec<_>>(),
+        })
+    }
+}
+
+impl PaymentVector {
+    fn to_json(&self) -> Value {
+        let inputs = &self.public_inputs;
+        json!({
+            "name": self.name,
+            "proof": self.proof_json,
+            "public_inputs": {
+                "min_amount": inputs.min_amount.to_string(),
+                "recipient_pubkey": to_hex(&inputs.recipient_pubkey),
+                "max_block_age": inputs.max_block_age.to_string(),
+                "current_time": inputs.current_time.to_string(),
+            },
+            "proof_borsh": to_hex(&self.proof_borsh),
+            "public_inputs_borsh": to_hex(&self.public_inputs_borsh),
+            "scalars": self.scalars.iter().map(u64::to_string).collect::<Vec<_>>(),
+            "proof_hash": to_hex(&self.proof_hash),
+        })
+    }
+}
+
+impl HeaderVector {
+    fn to_json(&self) -> Value {
+        let inputs = &self.public_inputs;
+        let headers: serde_json::Map<_, _> = self
+            .headers
+            .to_pairs()
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), Value::from(value)))
+            .collect();
+        json!({
+            "name": self.name,
+            "proof": self.proof_json,
+            "public_inputs": {
+                "min_amount": inputs.min_amount.to_string(),
+                "recipient_pubkey": to_hex(&inputs.recipient_pubkey),
+                "max_block_age": inputs.max_block_age.to_string(),
+                "current_time": inputs.current_time.to_string(),
+                "invoice_id": to_hex(&inputs.invoice_id),
+                "valid_until": inputs.valid_until.to_string(),
+                "recipient_set_root": to_hex(&inputs.recipient_set_root),
+            },
+            "headers": headers,
+            "proof_hash": to_hex(&self.proof_hash),
+        })
+    }
+}
+
+impl BatchVector {
+    fn to_json(&self) -> Value {
+        json!({
+            "name": self.name,
+            "payments": self.payments,
+            "challenges": self.challenges.iter().map(|c| to_hex(c)).collect::<Vec<_>>(),
+        })
+    }
+}
+
+impl NullifierVector {
+    fn to_json(&self) -> Value {
+        json!({
+            "program_id": VECTOR_PROGRAM_ID.to_string(),
+            "circuit": self.circuit as u8,
+            "nullifier": to_hex(&self.nullifier),
+            "epoch": self.epoch.to_string(),
+            "address": self.address.to_string(),
+            "bump": self.bump,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use borsh::BorshDeserialize;
+
+    use super::*;
+    use crate::{
+        client::{decode_hex, decode_payment_headers, verify_subscription_instruction},
+        field,
+        state::{NULLIFIER_SEED, NULLIFIER_SEED_VERSION},
+        G1Point, G2Point, SubscriptionPublicInputs,
+    };
+
+    #[test]
+    fn test_payment_vectors() {
+        let vectors = vectors();
+        assert_eq!(vectors.payments.len(), FIXTURE_PROOFS.len());
+        for vector in &vectors.payments {
+            let proof = parse_fixture(&vector.proof_json);
+            assert!(proof.a.is_on_curve() && proof.c.is_on_curve(), "{}", vector.name);
+            assert_eq!(proof.b.validate(), Ok(()), "{}", vector.name);
+
+            assert_eq!(proof.try_to_vec().unwrap(), vector.proof_borsh);
+            assert_eq!(
+                PaymentPublicInputs::try_from_slice(&vector.public_inputs_borsh).unwrap(),
+                vector.public_inputs
+            );
+            assert_eq!(vector.proof_borsh.len(), G1Point::LEN * 2 + G2Point::LEN);
+            assert_eq!(vector.scalars.len(), PaymentPublicInputs::SIGNAL_LAYOUT.len());
+            assert_eq!(vector.proof_hash, proof.hash(&vector.public_inputs));
+        }
+
+        // Distinct fixtures give distinct hashes
+        let mut hashes: Vec<_> = vectors.payments.iter().map(|v| v.proof_hash).collect();
+        hashes.sort();
+        hashes.dedup();
+        assert_eq!(hashes.len(), vectors.payments.len());
+    }
+
+    #[test]
+    fn test_header_vectors_decode() {
+        for vector in vectors().invoice_headers {
+            let (proof, public_inputs) = decode_payment_headers(&vector.headers).unwrap();
+            assert_eq!(
+                proof.try_to_vec().unwrap(),
+                parse_fixture(&vector.proof_json).try_to_vec().unwrap()
+            );
+            assert_eq!(public_inputs, vector.public_inputs);
+            assert_eq!(proof.hash(&public_inputs), vector.proof_hash);
+        }
+    }
+
+    #[test]
+    fn test_batch_vectors() {
+        let vectors = vectors();
+        for batch in &vectors.batches {
+            assert_eq!(batch.challenges.len(), batch.payments.len());
+            for challenge in &batch.challenges {
+                assert!(field::is_canonical_fr(challenge), "{}", batch.name);
+            }
+        }
+
+        // Challenges depend on the whole batch, not just the proof's position
+        assert_ne!(vectors.batches[0].challenges[0], vectors.batches[1].challenges[0]);
+    }
+
+    #[test]
+    fn test_nullifier_vectors_match_instruction_builders() {
+        for vector in vectors().nullifiers {
+            let address = Pubkey::create_program_address(
+                &[
+                    NULLIFIER_SEED,
+                    &[NULLIFIER_SEED_VERSION],
+                    &[vector.circuit as u8],
+                    &vector.nullifier,
+                    &vector.epoch.to_le_bytes(),
+                    &[vector.bump],
+                ],
+                &VECTOR_PROGRAM_ID,
+            );
+            assert_eq!(address, Ok(vector.address));
+
+            if vector.circuit == CircuitId::Subscription {
+                let instruction = verify_subscription_instruction(
+                    &VECTOR_PROGRAM_ID,
+                    &Pubkey::new_unique(),
+                    parse_fixture(&proof_json(1, 2)),
+                    SubscriptionPublicInputs {
+                        nullifier: vector.nullifier,
+                        epoch: vector.epoch,
+                        ..subscription_inputs()
+                    },
+                );
+                assert_eq!(instruction.accounts[2].pubkey, vector.address);
+            }
+        }
+    }
+
+    fn subscription_inputs() -> SubscriptionPublicInputs {
+        SubscriptionPublicInputs {
+            min_amount: 1_000_000,
+            recipient_pubkey: [4u8; 32],
+            epoch: 0,
+            nullifier: [0u8; 32],
+        }
+    }
+
+    #[test]
+    fn test_json_matches_vectors() {
+        let vectors = vectors();
+        let json = vectors.to_json();
+
+        for (vector, dumped) in vectors.payments.iter().zip(json["payments"].as_array().unwrap()) {
+            assert_eq!(dumped["name"], vector.name);
+            let proof = snarkjs::parse_proof(&dumped["proof"]).unwrap();
+            assert_eq!(proof.try_to_vec().unwrap(), vector.proof_borsh);
+            let hex = |key: &str| decode_hex(dumped[key].as_str().unwrap()).unwrap();
+            assert_eq!(hex("proof_borsh"), vector.proof_borsh);
+            assert_eq!(hex("public_inputs_borsh"), vector.public_inputs_borsh);
+            assert_eq!(hex("proof_hash"), vector.proof_hash);
+            assert_eq!(
+                dumped["public_inputs"]["min_amount"],
+                vector.public_inputs.min_amount.to_string()
+            );
+        }
+
+        let headers = &json["invoice_headers"][0]["headers"];
+        let vector = &vectors.invoice_headers[0];
+        for (name, value) in vector.headers.to_pairs() {
+            assert_eq!(headers[name], value);
+        }
+
+        let challenges = json["batches"][1]["challenges"].as_array().unwrap();
+        assert_eq!(challenges.len(), 3);
+        assert_eq!(
+            decode_hex(challenges[2].as_str().unwrap()).unwrap(),
+            vectors.batches[1].challenges[2]
+        );
+
+        let nullifier = &json["nullifiers"][0];
+        assert_eq!(nullifier["address"], vectors.nullifiers[0].address.to_string());
+        assert_eq!(nullifier["bump"], vectors.nullifiers[0].bump);
+        assert_eq!(nullifier["epoch"], "19675");
+    }
+}
@@ -0,0 +1,202 @@
+// Fiat-Shamir transcript over keccak256 or SHA-256
+// Every item is absorbed with a type tag and, for variable-length items, a u32
+// little-endian length, so distinct sequences of appends never hash the same.
+// The encoding is part of the protocol: clients re-derive challenges off-chain.
+// The hash is the circuit's `HashBackend`, recorded in its prepared key; the
+// encoding is the same under either.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{hash, keccak};
+
+use crate::{endian::Be32, field::Scalar};
+
+const TAG_LABEL: u8 = 1;
+const TAG_BYTES: u8 = 2;
+const TAG_U64: u8 = 3;
+const TAG_CHALLENGE: u8 = 4;
+
+/// Hash function behind a circuit's transcripts and batch proof hashes
+///
+/// The discriminant is stored in `PreparedVk` and must never be renumbered.
+/// blake3 is left out: its syscall is not enabled on any public cluster.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[repr(u8)]
+pub enum HashBackend {
+    #[default]
+    Keccak256 = 0,
+    Sha256 = 1,
+}
+
+impl HashBackend {
+    /// Hash the concatenation of `data`
+    pub fn hashv(self, data: &[&[u8]]) -> [u8; 32] {
+        match self {
+            HashBackend::Keccak256 => keccak::hashv(data).to_bytes(),
+            HashBackend::Sha256 => hash::hashv(data).to_bytes(),
+        }
+    }
+}
+
+/// Domain-separated transcript producing scalar challenges
+#[derive(Debug, Clone)]
+pub struct Transcript {
+    buffer: Vec<u8>,
+    backend: HashBackend,
+}
+
+impl Transcript {
+    /// Start a keccak256 transcript bound to `domain`
+    pub fn new(domain: &str) -> Self {
+        Self::with_backend(domain, HashBackend::Keccak256)
+    }
+
+    /// Start a transcript bound to `domain` that squeezes with `backend`
+    pub fn with_backend(domain: &str, backend: HashBackend) -> Self {
+        let mut transcript = Self {
+            buffer: Vec::new(),
+            backend,
+        };
+        transcript.append_label(domain);
+        transcript
+    }
+
+    /// Absorb a protocol label, separating the items that follow
+    pub fn append_label(&mut self, label: &str) {
+        self.append_tagged(TAG_LABEL, label.as_bytes());
+    }
+
+    pub fn append_bytes(&mut self, bytes: &[u8]) {
+        self.append_tagged(TAG_BYTES, bytes);
+    }
+
+    pub fn append_u64(&mut self, value: u64) {
+        self.buffer.push(TAG_U64);
+        self.buffer.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// Squeeze a challenge reduced mod r, big-endian as the syscalls expect
+    ///
+    /// The transcript state becomes the hash, so consecutive challenges differ
+    /// and later appends are bound to every earlier challenge.
+    pub fn challenge_scalar(&mut self) -> Scalar {
+        Scalar::reduce(Be32::from_syscall(self.challenge_bytes()))
+    }
+
+    /// Squeeze the raw 32-byte challenge, for use as an identifier or seed
+    ///
+    /// Advances the state exactly like `challenge_scalar`; only the reduction
+    /// mod r is skipped.
+    pub fn challenge_bytes(&mut self) -> [u8; 32] {
+        self.buffer.push(TAG_CHALLENGE);
+        let digest = self.backend.hashv(&[&self.buffer]);
+        self.buffer.clear();
+        self.buffer.extend_from_slice(&digest);
+        digest
+    }
+
+    fn append_tagged(&mut self, tag: u8, bytes: &[u8]) {
+        self.buffer.push(tag);
+        self.buffer.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+        self.buffer.extend_from_slice(bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_transcript_pinned() {
+        let mut transcript = Transcript::new("x402-zk-verifier/test");
+        assert_eq!(
+            transcript.challenge_scalar().to_syscall(),
+            [
+                0x06, 0x75, 0x13, 0xe2, 0x99, 0x62, 0xd7, 0x56,
+                0x8d, 0xcf, 0x4b, 0xce, 0x32, 0xd4, 0x5a, 0xc6,
+                0xa5, 0x20, 0xce, 0x8d, 0x9c, 0xd7, 0x9b, 0x2d,
+                0xbc, 0x92, 0xe0, 0xa3, 0x6a, 0x6c, 0x7e, 0x81,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_consecutive_challenges_pinned() {
+        let mut transcript = Transcript::new("x402-zk-verifier/test");
+        transcript.append_label("a");
+        transcript.append_bytes(b"abc");
+        transcript.append_u64(42);
+
+        assert_eq!(
+            transcript.challenge_scalar().to_syscall(),
+            [
+                0x20, 0x81, 0x8b, 0x44, 0xa5, 0x85, 0x3d, 0xdf,
+                0x5d, 0xd8, 0xec, 0xf6, 0x73, 0xd9, 0x09, 0x99,
+                0x7a, 0x00, 0x3b, 0xa6, 0x74, 0x36, 0x15, 0x01,
+                0xb4, 0x27, 0x21, 0xc3, 0xec, 0x4a, 0x2f, 0x51,
+            ]
+        );
+        assert_eq!(
+            transcript.challenge_scalar().to_syscall(),
+            [
+                0x00, 0x1a, 0x2f, 0x6b, 0xb4, 0xfd, 0x3f, 0x4c,
+                0x2e, 0x65, 0xeb, 0xcb, 0x6c, 0x10, 0x25, 0x6d,
+                0x15, 0xed, 0x1b, 0xe4, 0x31, 0x6c, 0xf8, 0xd8,
+                0x79, 0xdb, 0xac, 0x94, 0xb6, 0x91, 0x4d, 0x50,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sha256_challenges_pinned() {
+        let mut transcript =
+            Transcript::with_backend("x402-zk-verifier/test", HashBackend::Sha256);
+        transcript.append_label("a");
+        transcript.append_bytes(b"abc");
+        transcript.append_u64(42);
+
+        assert_eq!(
+            transcript.challenge_scalar().to_syscall(),
+            [
+                0x20, 0x67, 0xde, 0x52, 0x06, 0xb9, 0xcb, 0x93,
+                0xbf, 0x57, 0x99, 0x4d, 0x54, 0x5e, 0x4e, 0x03,
+                0x33, 0xfd, 0xe8, 0x97, 0xd9, 0xa0, 0x92, 0x19,
+                0x81, 0xa6, 0xd0, 0x6b, 0x93, 0x2f, 0x6d, 0x31,
+            ]
+        );
+        assert_eq!(
+            transcript.challenge_scalar().to_syscall(),
+            [
+                0x18, 0x8d, 0x60, 0xaa, 0xee, 0x3b, 0x30, 0x6a,
+                0xe8, 0x3c, 0x26, 0xab, 0x29, 0xc8, 0x65, 0x4d,
+                0xd2, 0xd7, 0x29, 0xb8, 0x5f, 0xdf, 0xe0, 0xa0,
+                0xd2, 0x1d, 0x67, 0x8b, 0x43, 0x46, 0xda, 0xef,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_item_boundaries_are_bound() {
+        // Moving bytes between adjacent items changes the challenge
+        let mut split = Transcript::new("x402-zk-verifier/test");
+        split.append_bytes(b"ab");
+        split.append_bytes(b"c");
+        let mut joined = Transcript::new("x402-zk-verifier/test");
+        joined.append_bytes(b"a");
+        joined.append_bytes(b"bc");
+        assert_ne!(split.challenge_scalar(), joined.challenge_scalar());
+
+        // A label is not interchangeable with the same bytes
+        let mut label = Transcript::new("x402-zk-verifier/test");
+        label.append_label("abc");
+        let mut bytes = Transcript::new("x402-zk-verifier/test");
+        bytes.append_bytes(b"abc");
+        assert_ne!(label.challenge_scalar(), bytes.challenge_scalar());
+
+        // Nor is the domain
+        let mut other = Transcript::new("x402-zk-verifier/other");
+        assert_ne!(
+            other.challenge_scalar(),
+            Transcript::new("x402-zk-verifier/test").challenge_scalar()
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, program::invoke,
+    program::invoke_signed, program::set_return_data, program_error::ProgramError,
+    pubkey::Pubkey, rent::Rent, system_instruction, sysvar::Sysvar,
+};
+
+use crate::events::{DryRunVerified, VerifierEvent};
+
+/// Create a program-owned PDA, funded by `payer`
+///
+/// Tolerates a PDA that was pre-funded by a third party (which would make a plain
+/// `create_account` fail) by topping up, allocating and assigning instead.
+pub fn create_pda_account<'a>(
+    payer: &AccountInfo<'a>,
+    new_account: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    program_id: &Pubkey,
+    space: usize,
+    signer_seeds: &[&[u8]],
+) -> ProgramResult {
+    let required_lamports = Rent::get()?.minimum_balance(space);
+
+    if new_account.lamports() == 0 {
+        return invoke_signed(
+            &system_instruction::create_account(
+                payer.key,
+                new_account.key,
+                required_lamports,
+                space as u64,
+                program_id,
+            ),
+            &[payer.clone(), new_account.clone(), system_program.clone()],
+            &[signer_seeds],
+        );
+    }
+
+    let top_up = required_lamports.saturating_sub(new_account.lamports());
+    if top_up > 0 {
+        invoke(
+            &system_instruction::transfer(payer.key, new_account.key, top_up),
+            &[payer.clone(), new_account.clone(), system_program.clone()],
+        )?;
+    }
+
+    invoke_signed(
+        &system_instruction::allocate(new_account.key, space as u64),
+        &[new_account.clone(), system_program.clone()],
+        &[signer_seeds],
+    )?;
+
+    invoke_signed(
+        &system_instruction::assign(new_account.key, program_id),
+        &[new_account.clone(), system_program.clone()],
+        &[signer_seeds],
+    )
+}
+
+/// Grow a program-owned account to `space` bytes, `payer` topping up its rent
+///
+/// The added bytes are zeroed.
+pub fn grow_program_account<'a>(
+    payer: &AccountInfo<'a>,
+    account: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    space: usize,
+) -> ProgramResult {
+    let top_up = Rent::get()?
+        .minimum_balance(space)
+        .saturating_sub(account.lamports());
+    if top_up > 0 {
+        invoke(
+            &system_instruction::transfer(payer.key, account.key, top_up),
+            &[payer.clone(), account.clone(), system_program.clone()],
+        )?;
+    }
+    account.realloc(space, true)
+}
+
+/// Borsh-decode all of `data` as a `T`, refusing bytes left over after it
+///
+/// Proof hashes and transcripts cover raw instruction bytes, so a decoder
+/// that ignored a tail would let one instruction travel under many hashes.
+pub fn decode_exact<T: BorshDeserialize>(data: &[u8]) -> Result<T, ProgramError> {
+    let mut rest = data;
+    let value = T::deserialize(&mut rest).map_err(|_| ProgramError::InvalidInstructionData)?;
+    if !rest.is_empty() {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    Ok(value)
+}
+
+/// Length of `value`'s Borsh encoding, counted without allocating it
+pub fn borsh_len<T: BorshSerialize + ?Sized>(value: &T) -> usize {
+    struct Counter(usize);
+
+    impl std::io::Write for Counter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0 += buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut counter = Counter(0);
+    // Writing to a counter cannot fail
+    value.serialize(&mut counter).unwrap();
+    counter.0
+}
+
+/// Report what a dry run would have written to `account`, as return data and
+/// as a `DryRunVerified` event
+pub fn report_dry_run(account: &Pubkey, data: Vec<u8>) {
+    set_return_data(&data);
+    VerifierEvent::DryRunVerified(DryRunVerified {
+        account: *account,
+        data,
+    })
+    .emit();
+}
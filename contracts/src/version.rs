@@ -0,0 +1,69 @@
+// Build provenance
+// `build.rs` embeds the git commit and the SHA-256 of the exported payment
+// verification_key.json. `GetVersion` returns them with the crate version and
+// the compiled-in key's signal layout hash, so an auditor can match a deployed
+// binary to a circuit build by simulating one transaction.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    entrypoint::ProgramResult, log::sol_log_data, msg, program::set_return_data,
+};
+
+use crate::PAYMENT_VK;
+
+include!(concat!(env!("OUT_DIR"), "/version.rs"));
+
+/// Version of the crate the program was built from
+pub const CRATE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// What `GetVersion` sets as Borsh return data
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ProgramVersion {
+    pub crate_version: String,
+    /// Full commit hash, or "unknown" outside a git checkout
+    pub git_commit: String,
+    /// SHA-256 of circuits/build/verification_key.json; zero when the build
+    /// had no exported key
+    pub vkey_sha256: [u8; 32],
+    /// `signal_layout_hash` the compiled-in payment key was exported with
+    pub signal_layout_hash: [u8; 32],
+}
+
+impl ProgramVersion {
+    /// The values compiled into this build
+    pub fn current() -> Self {
+        Self {
+            crate_version: CRATE_VERSION.to_string(),
+            git_commit: GIT_COMMIT.to_string(),
+            vkey_sha256: VKEY_SHA256,
+            signal_layout_hash: PAYMENT_VK.signal_layout_hash,
+        }
+    }
+}
+
+/// Log the build provenance and set it as return data
+pub fn process_get_version() -> ProgramResult {
+    let version = ProgramVersion::current();
+    msg!("Program version (crate, commit, then key and layout hashes):");
+    msg!(CRATE_VERSION);
+    msg!(GIT_COMMIT);
+    sol_log_data(&[&version.vkey_sha256, &version.signal_layout_hash]);
+    set_return_data(&version.try_to_vec()?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_current_version() {
+        let version = ProgramVersion::current();
+        assert_eq!(version.crate_version, env!("CARGO_PKG_VERSION"));
+        assert!(!version.git_commit.is_empty());
+        assert_eq!(
+            ProgramVersion::try_from_slice(&version.try_to_vec().unwrap()).unwrap(),
+            version
+        );
+    }
+}
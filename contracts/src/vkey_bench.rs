@@ -0,0 +1,239 @@
+// Verification key constants for the `bench-keys` feature
+// Stand-ins for the payment and invoice payment circuit keys with a known
+// trapdoor: alpha = G, beta = gamma = delta = the G2 generator and
+// IC[k] = (k + 1) * G, the `test_utils::fixture_key` of each circuit's shape.
+// `test_utils::fixture_proof` then proves any statement, so `bench-cu` can
+// measure batches the program accepts. A program built with them accepts
+// forged payments; never deploy one.
+// Curve: BN254 (alt_bn128)
+
+use crate::point::{G1Point, G2Point};
+
+/// G2 generator in syscall order, the key's beta, gamma and delta
+const G2_GENERATOR: G2Point = G2Point::new([
+    0x19, 0x8e, 0x93, 0x93, 0x92, 0x0d, 0x48, 0x3a,
+    0x72, 0x60, 0xbf, 0xb7, 0x31, 0xfb, 0x5d, 0x25,
+    0xf1, 0xaa, 0x49, 0x33, 0x35, 0xa9, 0xe7, 0x12,
+    0x97, 0xe4, 0x85, 0xb7, 0xae, 0xf3, 0x12, 0xc2,
+    0x18, 0x00, 0xde, 0xef, 0x12, 0x1f, 0x1e, 0x76,
+    0x42, 0x6a, 0x00, 0x66, 0x5e, 0x5c, 0x44, 0x79,
+    0x67, 0x43, 0x22, 0xd4, 0xf7, 0x5e, 0xda, 0xdd,
+    0x46, 0xde, 0xbd, 0x5c, 0xd9, 0x92, 0xf6, 0xed,
+    0x09, 0x06, 0x89, 0xd0, 0x58, 0x5f, 0xf0, 0x75,
+    0xec, 0x9e, 0x99, 0xad, 0x69, 0x0c, 0x33, 0x95,
+    0xbc, 0x4b, 0x31, 0x33, 0x70, 0xb3, 0x8e, 0xf3,
+    0x55, 0xac, 0xda, 0xdc, 0xd1, 0x22, 0x97, 0x5b,
+    0x12, 0xc8, 0x5e, 0xa5, 0xdb, 0x8c, 0x6d, 0xeb,
+    0x4a, 0xab, 0x71, 0x80, 0x8d, 0xcb, 0x40, 0x8f,
+    0xe3, 0xd1, 0xe7, 0x69, 0x0c, 0x43, 0xd3, 0x7b,
+    0x4c, 0xe6, 0xcc, 0x01, 0x66, 0xfa, 0x7d, 0xaa,
+]);
+
+/// k * G for k = 1 to 15, G the G1 generator (1, 2)
+const G1_MULTIPLES: [G1Point; 15] = [
+    // 1 * G
+    G1Point::new([
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02,
+    ]),
+    // 2 * G
+    G1Point::new([
+        0x03, 0x06, 0x44, 0xe7, 0x2e, 0x13, 0x1a, 0x02,
+        0x9b, 0x85, 0x04, 0x5b, 0x68, 0x18, 0x15, 0x85,
+        0xd9, 0x78, 0x16, 0xa9, 0x16, 0x87, 0x1c, 0xa8,
+        0xd3, 0xc2, 0x08, 0xc1, 0x6d, 0x87, 0xcf, 0xd3,
+        0x15, 0xed, 0x73, 0x8c, 0x0e, 0x0a, 0x7c, 0x92,
+        0xe7, 0x84, 0x5f, 0x96, 0xb2, 0xae, 0x9c, 0x0a,
+        0x68, 0xa6, 0xa4, 0x49, 0xe3, 0x53, 0x8f, 0xc7,
+        0xff, 0x3e, 0xbf, 0x7a, 0x5a, 0x18, 0xa2, 0xc4,
+    ]),
+    // 3 * G
+    G1Point::new([
+        0x07, 0x69, 0xbf, 0x9a, 0xc5, 0x6b, 0xea, 0x3f,
+        0xf4, 0x02, 0x32, 0xbc, 0xb1, 0xb6, 0xbd, 0x15,
+        0x93, 0x15, 0xd8, 0x47, 0x15, 0xb8, 0xe6, 0x79,
+        0xf2, 0xd3, 0x55, 0x96, 0x19, 0x15, 0xab, 0xf0,
+        0x2a, 0xb7, 0x99, 0xbe, 0xe0, 0x48, 0x94, 0x29,
+        0x55, 0x4f, 0xdb, 0x7c, 0x8d, 0x08, 0x64, 0x75,
+        0x31, 0x9e, 0x63, 0xb4, 0x0b, 0x9c, 0x5b, 0x57,
+        0xcd, 0xf1, 0xff, 0x3d, 0xd9, 0xfe, 0x22, 0x61,
+    ]),
+    // 4 * G
+    G1Point::new([
+        0x06, 0xa7, 0xb6, 0x4a, 0xf8, 0xf4, 0x14, 0xbc,
+        0xbe, 0xef, 0x45, 0x5b, 0x1d, 0xa5, 0x20, 0x8c,
+        0x9b, 0x59, 0x2b, 0x83, 0xee, 0x65, 0x99, 0x82,
+        0x4c, 0xaa, 0x6d, 0x2e, 0xe9, 0x14, 0x1a, 0x76,
+        0x08, 0xe7, 0x4e, 0x43, 0x8c, 0xee, 0x31, 0xac,
+        0x10, 0x4c, 0xe5, 0x9b, 0x94, 0xe4, 0x5f, 0xe9,
+        0x8a, 0x97, 0xd8, 0xf8, 0xa6, 0xe7, 0x56, 0x64,
+        0xce, 0x88, 0xef, 0x5a, 0x41, 0xe7, 0x2f, 0xbc,
+    ]),
+    // 5 * G
+    G1Point::new([
+        0x17, 0xc1, 0x39, 0xdf, 0x0e, 0xfe, 0xe0, 0xf7,
+        0x66, 0xbc, 0x02, 0x04, 0x76, 0x2b, 0x77, 0x43,
+        0x62, 0xe4, 0xde, 0xd8, 0x89, 0x53, 0xa3, 0x9c,
+        0xe8, 0x49, 0xa8, 0xa7, 0xfa, 0x16, 0x3f, 0xa9,
+        0x01, 0xe0, 0x55, 0x9b, 0xac, 0xb1, 0x60, 0x66,
+        0x47, 0x64, 0xa3, 0x57, 0xaf, 0x8a, 0x9f, 0xe7,
+        0x0b, 0xaa, 0x92, 0x58, 0xe0, 0xb9, 0x59, 0x27,
+        0x3f, 0xfc, 0x57, 0x18, 0xc6, 0xd4, 0xcc, 0x7c,
+    ]),
+    // 6 * G
+    G1Point::new([
+        0x09, 0xf4, 0xca, 0x41, 0x1a, 0x3f, 0x52, 0xf4,
+        0xe0, 0x79, 0x2f, 0xd9, 0xe7, 0x92, 0x77, 0x98,
+        0x56, 0x71, 0x92, 0x15, 0xd3, 0xb3, 0x2a, 0x76,
+        0x2a, 0xfe, 0x3d, 0x5b, 0x8c, 0x68, 0x4a, 0xf9,
+        0x0d, 0x8e, 0xf3, 0xd7, 0x95, 0xac, 0xd4, 0xb3,
+        0x5d, 0x43, 0x66, 0xab, 0x22, 0xe4, 0xad, 0x33,
+        0x52, 0x73, 0xaa, 0x59, 0x42, 0x9e, 0x26, 0x92,
+        0x9d, 0x0f, 0x64, 0x58, 0x34, 0x74, 0xd9, 0xc8,
+    ]),
+    // 7 * G
+    G1Point::new([
+        0x17, 0x07, 0x2b, 0x2e, 0xd3, 0xbb, 0x8d, 0x75,
+        0x9a, 0x53, 0x25, 0xf4, 0x77, 0x62, 0x93, 0x86,
+        0xcb, 0x6f, 0xc6, 0xec, 0xb8, 0x01, 0xbd, 0x76,
+        0x98, 0x3a, 0x6b, 0x86, 0xab, 0xff, 0xe0, 0x78,
+        0x16, 0x8a, 0xda, 0x6c, 0xd1, 0x30, 0xdd, 0x52,
+        0x01, 0x7b, 0xb5, 0x4b, 0xfa, 0x19, 0x37, 0x7a,
+        0xad, 0xfe, 0x3b, 0xf0, 0x5d, 0x18, 0xf4, 0x1b,
+        0x77, 0x80, 0x9f, 0x7f, 0x60, 0xd4, 0xaf, 0x9e,
+    ]),
+    // 8 * G
+    G1Point::new([
+        0x08, 0xb1, 0xd5, 0x1d, 0x23, 0x48, 0x0c, 0x10,
+        0xf4, 0x72, 0xf5, 0xe9, 0x3b, 0x9c, 0xfe, 0xa8,
+        0x82, 0x38, 0xc1, 0x21, 0xfe, 0x15, 0x5a, 0xf7,
+        0x04, 0x39, 0x37, 0x88, 0x2c, 0x30, 0x6a, 0x63,
+        0x29, 0x98, 0x36, 0x71, 0x3d, 0xad, 0x3f, 0xa3,
+        0x4e, 0x33, 0x7a, 0xa4, 0x12, 0x46, 0x60, 0x15,
+        0xc3, 0x66, 0xaf, 0x8e, 0xc5, 0x0b, 0x9d, 0x7b,
+        0xd0, 0x5a, 0xa7, 0x46, 0x42, 0x82, 0x20, 0x21,
+    ]),
+    // 9 * G
+    G1Point::new([
+        0x03, 0x97, 0x30, 0xea, 0x8d, 0xff, 0x12, 0x54,
+        0xc0, 0xfe, 0xe9, 0xc0, 0xea, 0x77, 0x7d, 0x29,
+        0xa9, 0xc7, 0x10, 0xb7, 0xe6, 0x16, 0x68, 0x3f,
+        0x19, 0x4f, 0x18, 0xc4, 0x3b, 0x43, 0xb8, 0x69,
+        0x07, 0x3a, 0x5f, 0xfc, 0xc6, 0xfc, 0x7a, 0x28,
+        0xc3, 0x07, 0x23, 0xd6, 0xe5, 0x8c, 0xe5, 0x77,
+        0x35, 0x69, 0x82, 0xd6, 0x5b, 0x83, 0x3a, 0x5a,
+        0x5c, 0x15, 0xbf, 0x90, 0x24, 0xb4, 0x3d, 0x98,
+    ]),
+    // 10 * G
+    G1Point::new([
+        0x09, 0xd3, 0xa2, 0x57, 0xb9, 0x9f, 0x1a, 0xd8,
+        0x04, 0xa9, 0xe2, 0x35, 0x4e, 0xa7, 0x1c, 0x72,
+        0xda, 0x7f, 0xa5, 0x18, 0xf4, 0xca, 0x79, 0x04,
+        0xc6, 0x95, 0x1d, 0x92, 0x4b, 0x40, 0x45, 0xb4,
+        0x17, 0x4b, 0xe1, 0x2a, 0xe3, 0xfd, 0x89, 0x9d,
+        0x55, 0xd3, 0xe4, 0x87, 0xfa, 0x10, 0x3f, 0x95,
+        0x1a, 0x24, 0xca, 0x0f, 0x67, 0x0e, 0xca, 0xe8,
+        0x02, 0x20, 0x9b, 0x25, 0x18, 0xcc, 0xca, 0x6c,
+    ]),
+    // 11 * G
+    G1Point::new([
+        0x2a, 0x14, 0x70, 0x55, 0x37, 0xb0, 0x09, 0x18,
+        0x9d, 0xa8, 0x80, 0x86, 0x51, 0xee, 0xcd, 0xb8,
+        0x24, 0x82, 0x47, 0x7f, 0xe9, 0x2a, 0xc1, 0x2c,
+        0xa8, 0xb7, 0x1f, 0x80, 0xfc, 0x3d, 0x49, 0xef,
+        0x2d, 0xf7, 0xee, 0x7f, 0x24, 0x3e, 0xa8, 0xb3,
+        0x8e, 0x1d, 0xdf, 0x14, 0x02, 0x92, 0x58, 0x87,
+        0x7a, 0x61, 0x8c, 0x77, 0x9f, 0xd4, 0x71, 0x7d,
+        0xb6, 0x17, 0x7e, 0x19, 0xea, 0x67, 0xec, 0x38,
+    ]),
+    // 12 * G
+    G1Point::new([
+        0x25, 0xd3, 0x2c, 0x47, 0x1c, 0x8c, 0xd1, 0xab,
+        0x9a, 0xc9, 0xb4, 0x11, 0x8d, 0x04, 0x01, 0x66,
+        0xf7, 0x5a, 0xd9, 0xe4, 0xf3, 0x65, 0x26, 0xb0,
+        0x9f, 0xc0, 0xb7, 0xd1, 0x00, 0x2b, 0xc8, 0x51,
+        0x2d, 0xb0, 0x9a, 0xe9, 0xbc, 0x0c, 0xb9, 0xad,
+        0xdf, 0x34, 0x04, 0x06, 0x90, 0x78, 0xf0, 0x36,
+        0x7f, 0xf4, 0x2b, 0x63, 0xcb, 0x1c, 0x20, 0x0b,
+        0xae, 0x5b, 0xf9, 0x09, 0x55, 0x85, 0xb6, 0x9c,
+    ]),
+    // 13 * G
+    G1Point::new([
+        0x05, 0xe8, 0x6f, 0x8c, 0xc8, 0xa7, 0xa4, 0xf1,
+        0x0f, 0x56, 0x09, 0x34, 0x65, 0x67, 0x9f, 0x17,
+        0xf8, 0xb8, 0xc3, 0xfd, 0xb4, 0x14, 0x69, 0xe4,
+        0x08, 0xb5, 0x29, 0xe0, 0x30, 0xf5, 0x2f, 0x3f,
+        0x28, 0x57, 0xbd, 0x14, 0xbb, 0xc0, 0x97, 0x67,
+        0xbe, 0xd8, 0xe9, 0x13, 0xd3, 0xcc, 0xb4, 0x2b,
+        0x2b, 0xc8, 0x73, 0x8f, 0x71, 0x54, 0x17, 0xdd,
+        0x6f, 0x02, 0x07, 0x25, 0xd2, 0x2b, 0xcd, 0x90,
+    ]),
+    // 14 * G
+    G1Point::new([
+        0x15, 0xbf, 0x2b, 0xb1, 0x78, 0x80, 0x14, 0x4b,
+        0x5d, 0x1c, 0xd2, 0xb1, 0xf4, 0x6e, 0xff, 0x9d,
+        0x61, 0x7b, 0xff, 0xd1, 0xca, 0x57, 0xc3, 0x7f,
+        0xb5, 0xa4, 0x9b, 0xd8, 0x4e, 0x53, 0xcf, 0x66,
+        0x04, 0x9c, 0x79, 0x7f, 0x9c, 0xe0, 0xd1, 0x70,
+        0x83, 0xde, 0xb3, 0x2b, 0x5e, 0x36, 0xf2, 0xea,
+        0x2a, 0x21, 0x2e, 0xe0, 0x36, 0x59, 0x8d, 0xd7,
+        0x62, 0x4c, 0x16, 0x89, 0x93, 0xd1, 0x35, 0x5f,
+    ]),
+    // 15 * G
+    G1Point::new([
+        0x2d, 0x96, 0xb1, 0x21, 0x48, 0x6a, 0xb9, 0xda,
+        0x7b, 0xf5, 0x49, 0xe5, 0x7d, 0x2f, 0x8a, 0x6c,
+        0xc1, 0x98, 0x3a, 0x33, 0x69, 0x03, 0x52, 0x4f,
+        0xb0, 0x5d, 0xcd, 0x50, 0x74, 0x57, 0xf6, 0x3c,
+        0x1d, 0xcb, 0x45, 0x73, 0x19, 0x79, 0xca, 0x35,
+        0xdf, 0xde, 0x49, 0xa4, 0x76, 0xe2, 0x73, 0xa1,
+        0xb1, 0xc9, 0xb5, 0x2e, 0x3e, 0xca, 0x22, 0xfa,
+        0xe2, 0x79, 0x45, 0x99, 0x20, 0xda, 0xa7, 0xe3,
+    ]),
+];
+
+pub const VK_ALPHA_G1: G1Point = G1_MULTIPLES[0];
+pub const VK_BETA_G2: G2Point = G2_GENERATOR;
+pub const VK_GAMMA_G2: G2Point = G2_GENERATOR;
+pub const VK_DELTA_G2: G2Point = G2_GENERATOR;
+
+/// IC points of `fixture_key(5)`
+pub const VK_IC: [G1Point; 6] = [
+    G1_MULTIPLES[0],
+    G1_MULTIPLES[1],
+    G1_MULTIPLES[2],
+    G1_MULTIPLES[3],
+    G1_MULTIPLES[4],
+    G1_MULTIPLES[5],
+];
+
+/// Signal layout hash of the payment circuit, as in vkey_placeholder.rs
+pub const VK_SIGNAL_LAYOUT_HASH: [u8; 32] = [
+    0xb8, 0xcc, 0x64, 0xca, 0x5d, 0x3c, 0xac, 0x85,
+    0xb2, 0x95, 0xe6, 0x4d, 0xbf, 0xc7, 0xff, 0xb2,
+    0x57, 0xfa, 0xa2, 0x6e, 0x2f, 0x62, 0x37, 0x5f,
+    0x81, 0x22, 0xc1, 0xf0, 0xf9, 0xae, 0x51, 0x31,
+];
+
+pub const VK_V3_ALPHA_G1: G1Point = G1_MULTIPLES[0];
+pub const VK_V3_BETA_G2: G2Point = G2_GENERATOR;
+pub const VK_V3_GAMMA_G2: G2Point = G2_GENERATOR;
+pub const VK_V3_DELTA_G2: G2Point = G2_GENERATOR;
+
+/// IC points of `fixture_key(14)`
+pub const VK_V3_IC: [G1Point; 15] = G1_MULTIPLES;
+
+/// Signal layout hash of the invoice payment circuit, as in
+/// vkey_v3_placeholder.rs
+pub const VK_V3_SIGNAL_LAYOUT_HASH: [u8; 32] = [
+    0xc6, 0xb5, 0x0d, 0x53, 0x9f, 0x0f, 0x83, 0xcf,
+    0xbe, 0x7b, 0xcf, 0xbd, 0xf0, 0x53, 0x32, 0x73,
+    0x1a, 0x89, 0xcc, 0x9e, 0xd1, 0xa6, 0x79, 0x0d,
+    0x31, 0x37, 0x14, 0x84, 0x1e, 0xfd, 0x67, 0xd3,
+];
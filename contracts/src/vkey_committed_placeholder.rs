@@ -0,0 +1,36 @@
+// Verification key constants for CommittedPaymentProof circuit
+// (CommittedPaymentPublicInputs)
+// Placeholder: the committed payment circuit has not been through the trusted
+// setup yet, so every point is zeroed and no committed payment proof will
+// verify until this file is replaced.
+// Circuit: committed_payment_proof.circom with 9 public inputs
+// Curve: BN254 (alt_bn128)
+
+use crate::point::{G1Point, G2Point};
+
+/// Alpha point on G1 (uncompressed, 64 bytes)
+pub const VK_COMMITTED_ALPHA_G1: G1Point = G1Point::new([0u8; 64]);
+
+/// Beta point on G2 (uncompressed, 128 bytes)
+pub const VK_COMMITTED_BETA_G2: G2Point = G2Point::new([0u8; 128]);
+
+/// Gamma point on G2 (uncompressed, 128 bytes)
+pub const VK_COMMITTED_GAMMA_G2: G2Point = G2Point::new([0u8; 128]);
+
+/// Delta point on G2 (uncompressed, 128 bytes)
+pub const VK_COMMITTED_DELTA_G2: G2Point = G2Point::new([0u8; 128]);
+
+/// IC (Input Commitment) points on G1 for public inputs
+/// IC[0] is the constant term
+/// IC[1..10] correspond to: minAmount, recipientPubKeyX, recipientPubKeyY,
+/// maxBlockAge, currentTime, amountCommitment0..3
+pub const VK_COMMITTED_IC: [G1Point; 10] = [G1Point::IDENTITY; 10];
+
+/// Signal layout hash: SHA256 over the public signal names in IC order,
+/// each encoded as u32 little-endian length followed by the UTF-8 name
+pub const VK_COMMITTED_SIGNAL_LAYOUT_HASH: [u8; 32] = [
+    0x15, 0x4f, 0xf7, 0x11, 0x4e, 0x5d, 0xc0, 0xe7,
+    0xd7, 0xe6, 0x95, 0x2d, 0xd7, 0xff, 0x06, 0xfd,
+    0x5c, 0x70, 0x01, 0x83, 0x26, 0x50, 0xbd, 0xdd,
+    0x56, 0xc6, 0x3f, 0x44, 0x88, 0xed, 0x17, 0x48,
+];
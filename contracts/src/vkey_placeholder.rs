@@ -1,167 +1,179 @@
 // Verification key constants for PaymentProof circuit
-// Generated from trusted setup ceremony (November 2025)
+// Placeholder: points hashed to the curve from fixed seeds, so no discrete log
+// is known. Replace with circuits/build/vkey_constants.rs after the ceremony.
 // Circuit: payment_proof.circom with 5 public inputs
 // Curve: BN254 (alt_bn128)
 
+use crate::point::{G1Point, G2Point};
+
 /// Alpha point on G1 (uncompressed, 64 bytes)
-/// Part of the Groth16 verification key from trusted setup
-pub const VK_ALPHA_G1: [u8; 64] = [
+pub const VK_ALPHA_G1: G1Point = G1Point::new([
     // X coordinate (32 bytes, big-endian)
-    0x2d, 0x4d, 0x9a, 0xa7, 0xe3, 0x02, 0xd9, 0xdf,
-    0x41, 0x74, 0x9d, 0x5e, 0x85, 0x89, 0x0a, 0x77,
-    0x78, 0x8b, 0x8a, 0x04, 0x47, 0x23, 0x08, 0x31,
-    0x40, 0x5c, 0x5d, 0xd5, 0x36, 0xbc, 0xf6, 0x8e,
+    0x08, 0x72, 0xce, 0xba, 0x34, 0x8e, 0x0a, 0x55,
+    0xd7, 0xb2, 0xe4, 0x22, 0xf2, 0x8b, 0x54, 0xd4,
+    0xea, 0xfc, 0x20, 0x50, 0x46, 0x2e, 0x95, 0xf8,
+    0x84, 0x13, 0x56, 0x78, 0x19, 0x5c, 0x4b, 0xa6,
     // Y coordinate (32 bytes, big-endian)
-    0x12, 0xc8, 0x5e, 0xa5, 0xdb, 0x8c, 0x6d, 0xef,
-    0x56, 0x6e, 0xcb, 0xf5, 0x97, 0x40, 0x3e, 0x0c,
-    0x58, 0x72, 0x44, 0x8e, 0xd8, 0x63, 0x50, 0xe0,
-    0x32, 0x01, 0x98, 0xbf, 0x95, 0x02, 0xf1, 0x44,
-];
+    0x03, 0xec, 0x7d, 0xb8, 0xf7, 0x36, 0x1c, 0x5c,
+    0xec, 0xff, 0xdd, 0xf5, 0x78, 0xe8, 0xce, 0x0d,
+    0xa6, 0xcb, 0x21, 0xc2, 0xb0, 0x1f, 0xa5, 0x7a,
+    0x04, 0xbc, 0xfd, 0x0e, 0xf9, 0x3c, 0xa1, 0x3d,
+]);
 
 /// Beta point on G2 (uncompressed, 128 bytes)
 /// G2 points have coordinates in Fp2 (pairs of field elements)
-pub const VK_BETA_G2: [u8; 128] = [
-    // X coordinate (Fp2: c0 || c1, 64 bytes)
-    0x19, 0x8e, 0x93, 0x93, 0x92, 0x0d, 0x48, 0x3a,
-    0x72, 0x60, 0xbf, 0xb7, 0x31, 0xfb, 0x5d, 0x25,
-    0xf1, 0xaa, 0x49, 0x33, 0x35, 0xa9, 0xe7, 0x12,
-    0x97, 0xe4, 0x85, 0xb7, 0xae, 0xf3, 0x12, 0xc2,
-    0x18, 0x00, 0xde, 0xef, 0x12, 0x1f, 0x1e, 0x76,
-    0x42, 0x6a, 0x00, 0x66, 0x5e, 0x5c, 0x44, 0x79,
-    0x67, 0x4b, 0x6f, 0xc5, 0x7a, 0x28, 0xa0, 0x9b,
-    0xb3, 0xf0, 0x93, 0x80, 0x6d, 0x8b, 0xc0, 0x88,
-    // Y coordinate (Fp2: c0 || c1, 64 bytes)
-    0x09, 0x0e, 0xf9, 0x25, 0xa1, 0x79, 0xa0, 0x53,
-    0x90, 0xba, 0x31, 0xa4, 0xc4, 0x10, 0x5e, 0xbc,
-    0x9a, 0xa2, 0x7e, 0x23, 0x8e, 0x6a, 0x3e, 0x35,
-    0x43, 0x0d, 0x4e, 0x41, 0xd0, 0x59, 0x87, 0x52,
-    0x25, 0x51, 0xf1, 0x18, 0x8c, 0x01, 0x11, 0x04,
-    0x6c, 0x8e, 0x9c, 0xad, 0x36, 0x70, 0x7c, 0x8a,
-    0x22, 0x91, 0x91, 0x9a, 0x5b, 0x2a, 0x2c, 0x98,
-    0xe1, 0x77, 0xf4, 0x83, 0x7e, 0xf4, 0x3c, 0x5b,
-];
+pub const VK_BETA_G2: G2Point = G2Point::new([
+    // X coordinate (Fp2: c1 || c0, 64 bytes, see point.rs)
+    0x01, 0xff, 0x42, 0x19, 0xc6, 0x1c, 0xad, 0x5a,
+    0x66, 0x22, 0x78, 0xbd, 0x77, 0xb8, 0xae, 0x74,
+    0xeb, 0x31, 0x4e, 0x45, 0xe7, 0x84, 0x91, 0xd5,
+    0x6d, 0x5b, 0x07, 0xca, 0x56, 0x4b, 0xec, 0x93,
+    0x20, 0xae, 0xb8, 0xfb, 0xae, 0xa1, 0xea, 0xaa,
+    0xb1, 0xf5, 0x04, 0x0c, 0x97, 0x66, 0x2e, 0xc4,
+    0x61, 0x1e, 0x64, 0x1f, 0x05, 0xe3, 0xe3, 0xf5,
+    0xa9, 0x98, 0xe7, 0x88, 0xa9, 0x79, 0xf5, 0x12,
+    // Y coordinate (Fp2: c1 || c0, 64 bytes, see point.rs)
+    0x24, 0x39, 0xe4, 0xdf, 0x90, 0x22, 0xb3, 0x0b,
+    0xf7, 0x00, 0xb9, 0xc4, 0x90, 0x44, 0xfb, 0xa6,
+    0x56, 0xf7, 0x27, 0x7c, 0x1f, 0x00, 0xb6, 0x7f,
+    0x17, 0xc4, 0x5e, 0x8f, 0xe6, 0x78, 0x15, 0x03,
+    0x24, 0xc2, 0x85, 0xda, 0x0a, 0x9d, 0x38, 0xf5,
+    0xec, 0x43, 0x70, 0xc4, 0x07, 0xf9, 0x2b, 0xf3,
+    0x8b, 0xd2, 0xfc, 0x19, 0x5a, 0xef, 0xbd, 0xa3,
+    0x4f, 0x20, 0xec, 0x59, 0x5e, 0xce, 0xdf, 0x72,
+]);
 
 /// Gamma point on G2 (uncompressed, 128 bytes)
-pub const VK_GAMMA_G2: [u8; 128] = [
-    // X coordinate (Fp2: c0 || c1, 64 bytes)
-    0x26, 0x0e, 0x01, 0xb2, 0x51, 0xf6, 0xce, 0xcd,
-    0xfe, 0xbe, 0x80, 0x40, 0x12, 0xdc, 0x8d, 0xdb,
-    0x04, 0x28, 0x0a, 0x3a, 0x3c, 0xc1, 0x30, 0x36,
-    0x53, 0x63, 0x35, 0x88, 0xef, 0x3c, 0xf7, 0x1a,
-    0x21, 0x58, 0x97, 0x3a, 0x74, 0xa7, 0xf9, 0xd3,
-    0x98, 0xe4, 0xf3, 0xef, 0x56, 0x4a, 0x4f, 0x1b,
-    0x5e, 0x3d, 0xf0, 0x33, 0x78, 0x9c, 0x86, 0xb0,
-    0x42, 0x06, 0x2d, 0x23, 0xa8, 0x94, 0x49, 0x31,
-    // Y coordinate (Fp2: c0 || c1, 64 bytes)
-    0x06, 0x35, 0xa4, 0x57, 0x7e, 0x4f, 0x7f, 0x1a,
-    0x95, 0x85, 0xfe, 0xc9, 0x30, 0x9e, 0xa1, 0xa1,
-    0x3c, 0x3a, 0x44, 0x7e, 0xbb, 0xcc, 0x90, 0xfb,
-    0xa6, 0xb7, 0x2a, 0xc7, 0xa2, 0x54, 0x94, 0xf5,
-    0x11, 0x1c, 0x65, 0xbc, 0x71, 0xaf, 0x54, 0x9c,
-    0xde, 0x6f, 0x0e, 0x03, 0xd7, 0x8f, 0xfa, 0xd2,
-    0x12, 0xa4, 0x69, 0x54, 0x2c, 0x3a, 0x2a, 0xda,
-    0x6c, 0x5a, 0x85, 0x3c, 0xb6, 0x04, 0x40, 0x64,
-];
+pub const VK_GAMMA_G2: G2Point = G2Point::new([
+    // X coordinate (Fp2: c1 || c0, 64 bytes, see point.rs)
+    0x28, 0xc7, 0x91, 0x97, 0x2c, 0xe9, 0x7f, 0x19,
+    0xe6, 0x16, 0x66, 0x74, 0x56, 0x73, 0x5e, 0x1b,
+    0x53, 0x95, 0x15, 0x19, 0x2b, 0x5a, 0xbf, 0x96,
+    0x02, 0x34, 0x6f, 0x90, 0x9d, 0x70, 0x87, 0xd2,
+    0x0a, 0xbb, 0x37, 0x4a, 0x80, 0x6c, 0x5b, 0xa8,
+    0xaf, 0x50, 0x29, 0xc7, 0x29, 0x66, 0xd6, 0x2f,
+    0x2c, 0xeb, 0x5d, 0x6f, 0x41, 0xc9, 0x76, 0x26,
+    0xb5, 0x25, 0x49, 0xee, 0x36, 0x45, 0xb6, 0x12,
+    // Y coordinate (Fp2: c1 || c0, 64 bytes, see point.rs)
+    0x2c, 0xdd, 0xec, 0x55, 0x93, 0x2c, 0x86, 0x61,
+    0x21, 0xe3, 0x9c, 0x3e, 0x56, 0xe1, 0xe3, 0x39,
+    0xe9, 0x37, 0x05, 0x41, 0x7a, 0xff, 0x89, 0x7f,
+    0xb1, 0x2d, 0xed, 0xa7, 0x67, 0x20, 0x9f, 0x9b,
+    0x13, 0xde, 0xb8, 0xd3, 0x71, 0xaf, 0x6f, 0x28,
+    0xf9, 0x14, 0x7a, 0x20, 0x87, 0xde, 0x6d, 0x9a,
+    0x21, 0x2d, 0x0d, 0x26, 0xa5, 0x3d, 0x9b, 0xcc,
+    0x19, 0x84, 0x67, 0x56, 0x07, 0x0b, 0xaf, 0x83,
+]);
 
 /// Delta point on G2 (uncompressed, 128 bytes)
-pub const VK_DELTA_G2: [u8; 128] = [
-    // X coordinate (Fp2: c0 || c1, 64 bytes)
-    0x22, 0xfe, 0xb2, 0xd6, 0x7c, 0xdc, 0xf9, 0xbf,
-    0x00, 0x53, 0xc1, 0xa5, 0x29, 0xf5, 0xb9, 0x23,
-    0xbc, 0xa1, 0x09, 0xa2, 0xbe, 0x28, 0x03, 0xa3,
-    0x76, 0x9b, 0xe4, 0x26, 0xc8, 0xbc, 0x13, 0xf7,
-    0x2c, 0xd7, 0x67, 0x2a, 0x8c, 0xf7, 0x3a, 0x88,
-    0x33, 0x7b, 0xc9, 0x45, 0x55, 0xcd, 0xe3, 0x3d,
-    0x01, 0x44, 0x96, 0x88, 0x10, 0xa2, 0x5c, 0x96,
-    0x1f, 0xfd, 0xe5, 0xa0, 0xc0, 0x82, 0xb1, 0xf3,
-    // Y coordinate (Fp2: c0 || c1, 64 bytes)
-    0x14, 0x79, 0x25, 0x4d, 0x18, 0xea, 0x7c, 0x8f,
-    0xf5, 0xa9, 0x67, 0xf4, 0x3b, 0x8c, 0x6a, 0x85,
-    0x40, 0x5d, 0xd0, 0x32, 0x9f, 0xfb, 0x54, 0x82,
-    0x22, 0x72, 0xec, 0x00, 0xea, 0xdd, 0xfa, 0x25,
-    0x28, 0x9b, 0x3c, 0x53, 0x45, 0x5a, 0x26, 0xe1,
-    0xd0, 0xc1, 0x53, 0x33, 0x56, 0x08, 0x1a, 0x8e,
-    0x7b, 0x2c, 0xc0, 0x01, 0x5e, 0x80, 0x51, 0xfe,
-    0x67, 0xb8, 0x93, 0xc6, 0xf6, 0x3e, 0x92, 0x4a,
-];
+pub const VK_DELTA_G2: G2Point = G2Point::new([
+    // X coordinate (Fp2: c1 || c0, 64 bytes, see point.rs)
+    0x2f, 0x7f, 0x9c, 0xf7, 0x6d, 0x41, 0x98, 0x7e,
+    0x7b, 0xbd, 0x23, 0x93, 0x12, 0x3e, 0xa5, 0x2a,
+    0x8a, 0x97, 0xeb, 0x76, 0x9e, 0xeb, 0x46, 0x54,
+    0xb8, 0x90, 0x97, 0x95, 0x9b, 0x6a, 0x16, 0xa2,
+    0x0a, 0x4d, 0x43, 0x65, 0xf9, 0x49, 0xc7, 0x26,
+    0xcd, 0xd7, 0xec, 0x2a, 0xc0, 0x78, 0x08, 0x0f,
+    0x2c, 0xed, 0x5b, 0xb6, 0xcb, 0x0d, 0x23, 0x55,
+    0x1c, 0x7f, 0x16, 0x94, 0x91, 0x19, 0xce, 0xcc,
+    // Y coordinate (Fp2: c1 || c0, 64 bytes, see point.rs)
+    0x11, 0x85, 0x20, 0x3a, 0x0f, 0x5c, 0x34, 0x84,
+    0x79, 0x61, 0x10, 0xf5, 0x9a, 0x67, 0xf9, 0xfa,
+    0x5a, 0x0a, 0xce, 0xdd, 0x29, 0xc8, 0x45, 0xba,
+    0x8a, 0x19, 0x77, 0x17, 0xfb, 0x22, 0x8c, 0x01,
+    0x15, 0x84, 0xde, 0xaf, 0xd0, 0x2e, 0xed, 0xfe,
+    0xc2, 0x95, 0xad, 0xc4, 0x0a, 0x9b, 0x3a, 0x87,
+    0x90, 0xee, 0x29, 0xd8, 0x23, 0xfb, 0x71, 0xac,
+    0xe0, 0xa0, 0x4c, 0x1f, 0x5d, 0xed, 0x6f, 0xcf,
+]);
 
 /// IC (Input Commitment) points on G1 for public inputs
 /// IC[0] is the constant term
 /// IC[1..6] correspond to: minAmount, recipientPubKeyX, recipientPubKeyY, maxBlockAge, currentTime
-pub const VK_IC: [[u8; 64]; 6] = [
+pub const VK_IC: [G1Point; 6] = [
     // IC[0] - Constant term
-    [
-        0x0b, 0x45, 0x48, 0xa0, 0x96, 0xb2, 0xe0, 0x98,
-        0xd5, 0x4e, 0x5c, 0xf3, 0xd8, 0x51, 0x22, 0xb1,
-        0x89, 0x73, 0x0e, 0x01, 0xa5, 0x73, 0x63, 0x48,
-        0xda, 0x16, 0xbf, 0xf6, 0x39, 0xc8, 0x2a, 0x16,
-        0x24, 0x17, 0x8e, 0x28, 0xfa, 0x77, 0x81, 0x96,
-        0x5a, 0x57, 0x84, 0x4c, 0xa9, 0xf3, 0x59, 0xdb,
-        0x88, 0x99, 0x7c, 0x71, 0xed, 0x93, 0x19, 0xc7,
-        0xb4, 0x72, 0x7a, 0x3e, 0xf3, 0x4c, 0x6d, 0x26,
-    ],
+    G1Point::new([
+        0x21, 0xac, 0xc2, 0x68, 0xf0, 0x7f, 0xc4, 0x61,
+        0x83, 0x37, 0xf9, 0x37, 0x6c, 0xb2, 0xe8, 0x6c,
+        0x34, 0x7f, 0x62, 0x0b, 0x65, 0x61, 0x46, 0xb5,
+        0xc1, 0x97, 0xe1, 0x67, 0x85, 0xc5, 0x2e, 0x57,
+        0x0b, 0xe7, 0x8d, 0x8b, 0xb4, 0xdf, 0x56, 0xe3,
+        0x1d, 0x92, 0x01, 0x7e, 0x3c, 0x0e, 0x15, 0x4b,
+        0x50, 0xf5, 0xee, 0xba, 0x9b, 0x81, 0xf1, 0x17,
+        0x31, 0x19, 0x6d, 0x87, 0xd9, 0x06, 0x6e, 0x21,
+    ]),
     // IC[1] - minAmount coefficient
-    [
-        0x1a, 0x9c, 0x25, 0x43, 0x8f, 0xdb, 0x50, 0xc1,
-        0xa3, 0xb8, 0x92, 0xda, 0x72, 0x99, 0x86, 0xf7,
-        0x5d, 0xe5, 0xc3, 0x12, 0x16, 0xbf, 0x43, 0xab,
-        0x8e, 0x26, 0x67, 0x1a, 0x87, 0xc1, 0x33, 0xe8,
-        0x07, 0x54, 0x90, 0x84, 0xea, 0x4d, 0x33, 0x91,
-        0xd4, 0xb3, 0xa8, 0x2c, 0x93, 0x4a, 0xad, 0x52,
-        0xd1, 0x46, 0x98, 0x80, 0x8e, 0x77, 0x64, 0x36,
-        0xc3, 0xad, 0x35, 0xa2, 0x6f, 0x53, 0xa0, 0x9b,
-    ],
+    G1Point::new([
+        0x15, 0x49, 0xe8, 0x55, 0x63, 0xdf, 0x77, 0x4b,
+        0x77, 0x8d, 0x11, 0x2e, 0x01, 0xdd, 0xc6, 0x5e,
+        0x9f, 0x0b, 0xdd, 0xc6, 0x10, 0x49, 0x2a, 0x40,
+        0x3c, 0x33, 0xe2, 0x52, 0x74, 0xba, 0xeb, 0x1a,
+        0x06, 0x5b, 0xaf, 0x6c, 0x29, 0xcc, 0x08, 0xfe,
+        0x57, 0xe3, 0x74, 0xec, 0x65, 0x44, 0x17, 0x3f,
+        0xba, 0x80, 0x63, 0xcf, 0x68, 0x9d, 0xff, 0xf6,
+        0x62, 0x77, 0xb3, 0x27, 0xa7, 0xbe, 0x2b, 0x17,
+    ]),
     // IC[2] - recipientPubKeyX coefficient
-    [
-        0x05, 0xe8, 0x8a, 0x46, 0x71, 0xab, 0x94, 0xfc,
-        0x28, 0x01, 0x84, 0x39, 0x4c, 0x7f, 0x2e, 0x01,
-        0x87, 0x25, 0xe9, 0xd0, 0xf4, 0x2a, 0xa9, 0x76,
-        0x2d, 0x85, 0x0b, 0x73, 0x2c, 0x9a, 0xa6, 0x72,
-        0x2a, 0xc7, 0x1e, 0x4d, 0x1b, 0x85, 0xe6, 0xd2,
-        0x42, 0x5b, 0x91, 0x4f, 0x2e, 0xa3, 0x18, 0x55,
-        0xf8, 0x66, 0x5e, 0x85, 0x52, 0x0f, 0x4e, 0xa1,
-        0x93, 0xb6, 0xd4, 0x98, 0x82, 0x75, 0xc1, 0x0c,
-    ],
+    G1Point::new([
+        0x1b, 0x57, 0xb2, 0x2a, 0x41, 0x55, 0x21, 0x6c,
+        0xb1, 0x3c, 0x9f, 0xa1, 0xa0, 0x2f, 0x2d, 0x56,
+        0x8a, 0xb9, 0x9a, 0x45, 0xd2, 0xb2, 0x6e, 0x04,
+        0x64, 0x71, 0xda, 0x0c, 0x13, 0xd2, 0x93, 0x57,
+        0x12, 0xbc, 0xf2, 0x50, 0xb5, 0xd1, 0xe5, 0x5a,
+        0x46, 0x7c, 0x09, 0xed, 0xea, 0xa5, 0xd6, 0x8c,
+        0x42, 0x1e, 0xce, 0x47, 0x14, 0xde, 0x51, 0x92,
+        0xad, 0x6c, 0xad, 0xd2, 0x0c, 0x88, 0xc3, 0x8d,
+    ]),
     // IC[3] - recipientPubKeyY coefficient
-    [
-        0x29, 0xd5, 0x76, 0x8a, 0x33, 0x6c, 0xe1, 0x43,
-        0x81, 0x54, 0xac, 0x73, 0x5c, 0x7b, 0x84, 0x8d,
-        0x3a, 0xe2, 0x49, 0x00, 0x47, 0x31, 0xe2, 0xc4,
-        0x49, 0x35, 0x8e, 0xbb, 0x60, 0x72, 0xaf, 0x95,
-        0x16, 0x2b, 0xa4, 0xf0, 0x63, 0x7b, 0x9d, 0xa5,
-        0x5d, 0xf1, 0x82, 0x9b, 0x68, 0x09, 0xc9, 0x24,
-        0xe7, 0x18, 0xa1, 0x5b, 0x8f, 0xcc, 0x21, 0xdf,
-        0xaa, 0x2e, 0x89, 0x4f, 0x7b, 0x41, 0x2b, 0x13,
-    ],
+    G1Point::new([
+        0x0d, 0x2b, 0xd3, 0x52, 0x36, 0x83, 0x63, 0x63,
+        0xef, 0xbe, 0x8a, 0x6d, 0x52, 0x14, 0xe7, 0x4b,
+        0xf8, 0x6c, 0x6b, 0x05, 0x76, 0xc3, 0xd1, 0x25,
+        0xe4, 0x4e, 0x8c, 0xcf, 0x8f, 0xd5, 0x86, 0xfa,
+        0x00, 0x2d, 0x15, 0x9d, 0x26, 0x89, 0x6c, 0x9a,
+        0xcf, 0x70, 0xa6, 0x0b, 0xde, 0x7f, 0x2b, 0xfe,
+        0x38, 0x15, 0x45, 0xbe, 0xae, 0x0d, 0x3f, 0x57,
+        0x5f, 0x0d, 0x16, 0x30, 0xb4, 0x3e, 0x11, 0x1f,
+    ]),
     // IC[4] - maxBlockAge coefficient
-    [
-        0x1d, 0x30, 0xa0, 0x76, 0x4b, 0xc1, 0x62, 0x8e,
-        0xc7, 0x5c, 0x10, 0xcc, 0xa6, 0xca, 0x7a, 0x5d,
-        0xf9, 0x2a, 0xd2, 0x6e, 0xd0, 0x60, 0xc5, 0x25,
-        0x10, 0xc9, 0xf5, 0xe8, 0x1c, 0x50, 0xf4, 0x9f,
-        0x0e, 0xc7, 0x21, 0x6f, 0x3c, 0xb0, 0x85, 0xa7,
-        0xb8, 0xe9, 0x62, 0xd5, 0xc8, 0x45, 0x5a, 0x4b,
-        0x37, 0xf4, 0x5d, 0xe0, 0xd6, 0x7b, 0xa1, 0x59,
-        0xaf, 0x7f, 0xe6, 0x29, 0x32, 0x8e, 0xc3, 0x52,
-    ],
+    G1Point::new([
+        0x26, 0x79, 0x60, 0x58, 0xba, 0x4b, 0x02, 0xf5,
+        0x48, 0x56, 0xd4, 0xf3, 0xff, 0xd9, 0x10, 0xdf,
+        0xb8, 0xeb, 0xf7, 0xc1, 0xec, 0x67, 0x4a, 0x61,
+        0x67, 0x65, 0x28, 0xc5, 0x29, 0x34, 0x98, 0xf6,
+        0x07, 0xae, 0xa3, 0x3d, 0x09, 0xd5, 0x71, 0x09,
+        0xea, 0x5c, 0x87, 0xbe, 0x77, 0xa1, 0x0d, 0x81,
+        0xb0, 0xfe, 0xb3, 0x22, 0x0e, 0x60, 0x99, 0x9f,
+        0xa0, 0x62, 0x34, 0x3b, 0x16, 0xc9, 0x7c, 0xc5,
+    ]),
     // IC[5] - currentTime coefficient
-    [
-        0x08, 0xb8, 0xe2, 0x5c, 0x9f, 0x12, 0x3e, 0xaf,
-        0x6b, 0x56, 0x41, 0x8c, 0x31, 0x22, 0xb5, 0x95,
-        0x19, 0x38, 0x6a, 0x4b, 0xe1, 0x51, 0x4e, 0x2a,
-        0x7a, 0x12, 0xb7, 0x99, 0x6d, 0x55, 0x42, 0x3d,
-        0x1f, 0x95, 0x38, 0xa2, 0xd7, 0x3e, 0x4a, 0x04,
-        0xb9, 0xa5, 0xc7, 0x73, 0x9c, 0x88, 0x44, 0x71,
-        0xba, 0x12, 0xcd, 0xe5, 0x90, 0x26, 0x9d, 0x50,
-        0x37, 0x9d, 0x60, 0xb2, 0x1e, 0x47, 0x8a, 0x19,
-    ],
+    G1Point::new([
+        0x10, 0x31, 0xdf, 0x8e, 0x2b, 0x1d, 0xdf, 0x0b,
+        0xa5, 0x86, 0x7c, 0x4f, 0xed, 0xa6, 0xf3, 0x01,
+        0x4f, 0x93, 0xbc, 0xc7, 0xa9, 0xae, 0xb6, 0x3a,
+        0x11, 0xd8, 0x9d, 0x73, 0xa3, 0x14, 0x99, 0x51,
+        0x0e, 0xc5, 0xf7, 0xcf, 0xc3, 0x55, 0x89, 0x28,
+        0x84, 0x88, 0x9c, 0xad, 0xdd, 0xc8, 0xf7, 0xe9,
+        0x39, 0xfa, 0xbd, 0x0d, 0xd9, 0x50, 0x4d, 0x3e,
+        0x68, 0x83, 0x3a, 0x58, 0xfe, 0x47, 0x88, 0x0c,
+    ]),
 ];
 
 /// Verification key hash for integrity verification
-/// SHA256 of the concatenated verification key components
+/// `VerificationKey::hash` of this key
 pub const VK_HASH: [u8; 32] = [
-    0xa7, 0x3c, 0x5d, 0x8e, 0x2f, 0x91, 0x4b, 0x66,
-    0xd8, 0x23, 0x7a, 0x45, 0xbc, 0x19, 0xe0, 0x52,
-    0x8d, 0xf6, 0x3a, 0xc7, 0x41, 0x95, 0x0e, 0xb8,
-    0x6f, 0xd2, 0x84, 0x39, 0xa1, 0x5c, 0x7b, 0xe3,
+    0x6d, 0x3a, 0x13, 0x7d, 0x5d, 0x46, 0xa7, 0xf7,
+    0x01, 0xc2, 0xda, 0xaf, 0x35, 0x06, 0xe9, 0xa6,
+    0x68, 0xae, 0x06, 0x74, 0xb5, 0xd7, 0xea, 0xd6,
+    0xd4, 0xff, 0xb5, 0x22, 0xe8, 0xc4, 0x98, 0x65,
+];
+
+/// Signal layout hash: SHA256 over the public signal names in IC order,
+/// each encoded as u32 little-endian length followed by the UTF-8 name
+/// (minAmount, recipientPubKeyX, recipientPubKeyY, maxBlockAge, currentTime)
+pub const VK_SIGNAL_LAYOUT_HASH: [u8; 32] = [
+    0xb8, 0xcc, 0x64, 0xca, 0x5d, 0x3c, 0xac, 0x85,
+    0xb2, 0x95, 0xe6, 0x4d, 0xbf, 0xc7, 0xff, 0xb2,
+    0x57, 0xfa, 0xa2, 0x6e, 0x2f, 0x62, 0x37, 0x5f,
+    0x81, 0x22, 0xc1, 0xf0, 0xf9, 0xae, 0x51, 0x31,
 ];
@@ -0,0 +1,34 @@
+// Verification key constants for RefundProof circuit (RefundPublicInputs)
+// Placeholder: the refund circuit has not been through the trusted setup yet, so
+// every point is zeroed and no refund proof will verify until this file is
+// replaced.
+// Circuit: refund_proof.circom with 6 public inputs
+// Curve: BN254 (alt_bn128)
+
+use crate::point::{G1Point, G2Point};
+
+/// Alpha point on G1 (uncompressed, 64 bytes)
+pub const VK_REFUND_ALPHA_G1: G1Point = G1Point::new([0u8; 64]);
+
+/// Beta point on G2 (uncompressed, 128 bytes)
+pub const VK_REFUND_BETA_G2: G2Point = G2Point::new([0u8; 128]);
+
+/// Gamma point on G2 (uncompressed, 128 bytes)
+pub const VK_REFUND_GAMMA_G2: G2Point = G2Point::new([0u8; 128]);
+
+/// Delta point on G2 (uncompressed, 128 bytes)
+pub const VK_REFUND_DELTA_G2: G2Point = G2Point::new([0u8; 128]);
+
+/// IC (Input Commitment) points on G1 for public inputs
+/// IC[0] is the constant term
+/// IC[1..7] correspond to: receiptHash0..3, refundRecipientPubKeyX, refundRecipientPubKeyY
+pub const VK_REFUND_IC: [G1Point; 7] = [G1Point::IDENTITY; 7];
+
+/// Signal layout hash: SHA256 over the public signal names in IC order,
+/// each encoded as u32 little-endian length followed by the UTF-8 name
+pub const VK_REFUND_SIGNAL_LAYOUT_HASH: [u8; 32] = [
+    0xf8, 0xe7, 0x97, 0x55, 0xaa, 0xd0, 0x23, 0xeb,
+    0x66, 0x1c, 0xc6, 0x13, 0x92, 0x57, 0x46, 0x7d,
+    0x3a, 0x21, 0xf8, 0x14, 0xfe, 0xc3, 0x06, 0xbf,
+    0x23, 0xef, 0x6e, 0xfd, 0x29, 0xeb, 0xbd, 0x41,
+];
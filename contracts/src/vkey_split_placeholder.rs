@@ -0,0 +1,36 @@
+// Verification key constants for SplitPaymentProof circuit (SplitPaymentPublicInputs)
+// Placeholder: the split circuit has not been through the trusted setup yet, so
+// every point is zeroed and no split proof will verify until this file is
+// replaced.
+// Circuit: payment_proof_split.circom with 10 public inputs
+// Curve: BN254 (alt_bn128)
+
+use crate::point::{G1Point, G2Point};
+
+/// Alpha point on G1 (uncompressed, 64 bytes)
+pub const VK_SPLIT_ALPHA_G1: G1Point = G1Point::new([0u8; 64]);
+
+/// Beta point on G2 (uncompressed, 128 bytes)
+pub const VK_SPLIT_BETA_G2: G2Point = G2Point::new([0u8; 128]);
+
+/// Gamma point on G2 (uncompressed, 128 bytes)
+pub const VK_SPLIT_GAMMA_G2: G2Point = G2Point::new([0u8; 128]);
+
+/// Delta point on G2 (uncompressed, 128 bytes)
+pub const VK_SPLIT_DELTA_G2: G2Point = G2Point::new([0u8; 128]);
+
+/// IC (Input Commitment) points on G1 for public inputs
+/// IC[0] is the constant term
+/// IC[1..11] correspond to: minAmount0, recipient0PubKeyX, recipient0PubKeyY, minAmount1,
+/// recipient1PubKeyX, recipient1PubKeyY, maxBlockAge, currentTime, mintX, mintY
+pub const VK_SPLIT_IC: [G1Point; 11] = [G1Point::IDENTITY; 11];
+
+/// Signal layout hash: SHA256 over the public signal names in IC order,
+/// each encoded as u32 little-endian length followed by the UTF-8 name
+/// (per-leg signals for both recipients, then maxBlockAge, currentTime, mintX, mintY)
+pub const VK_SPLIT_SIGNAL_LAYOUT_HASH: [u8; 32] = [
+    0x6f, 0xb7, 0xa2, 0x04, 0x73, 0xb6, 0x3f, 0x97,
+    0x39, 0xa9, 0x76, 0x4e, 0x6e, 0x6f, 0x56, 0xdc,
+    0x55, 0x8c, 0x3c, 0x5d, 0xd8, 0x43, 0x1e, 0xc8,
+    0xb6, 0xbf, 0x8a, 0xd4, 0x06, 0x83, 0xeb, 0x7a,
+];
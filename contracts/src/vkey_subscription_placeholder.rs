@@ -0,0 +1,34 @@
+// Verification key constants for SubscriptionProof circuit (SubscriptionPublicInputs)
+// Placeholder: the subscription circuit has not been through the trusted setup
+// yet, so every point is zeroed and no subscription proof will verify until this
+// file is replaced.
+// Circuit: subscription_proof.circom with 8 public inputs
+// Curve: BN254 (alt_bn128)
+
+use crate::point::{G1Point, G2Point};
+
+/// Alpha point on G1 (uncompressed, 64 bytes)
+pub const VK_SUBSCRIPTION_ALPHA_G1: G1Point = G1Point::new([0u8; 64]);
+
+/// Beta point on G2 (uncompressed, 128 bytes)
+pub const VK_SUBSCRIPTION_BETA_G2: G2Point = G2Point::new([0u8; 128]);
+
+/// Gamma point on G2 (uncompressed, 128 bytes)
+pub const VK_SUBSCRIPTION_GAMMA_G2: G2Point = G2Point::new([0u8; 128]);
+
+/// Delta point on G2 (uncompressed, 128 bytes)
+pub const VK_SUBSCRIPTION_DELTA_G2: G2Point = G2Point::new([0u8; 128]);
+
+/// IC (Input Commitment) points on G1 for public inputs
+/// IC[0] is the constant term
+/// IC[1..9] correspond to: minAmount, recipientPubKeyX, recipientPubKeyY, epoch, nullifier0..3
+pub const VK_SUBSCRIPTION_IC: [G1Point; 9] = [G1Point::IDENTITY; 9];
+
+/// Signal layout hash: SHA256 over the public signal names in IC order,
+/// each encoded as u32 little-endian length followed by the UTF-8 name
+pub const VK_SUBSCRIPTION_SIGNAL_LAYOUT_HASH: [u8; 32] = [
+    0x85, 0x29, 0xb7, 0x3f, 0x07, 0x2c, 0x27, 0xc3,
+    0xc8, 0xeb, 0x41, 0x41, 0xcd, 0x29, 0x41, 0x93,
+    0x88, 0xcc, 0x8e, 0xdf, 0xa9, 0xca, 0x00, 0xd4,
+    0xac, 0x8f, 0x75, 0x81, 0xa3, 0x85, 0x1b, 0x73,
+];
@@ -0,0 +1,35 @@
+// Verification key constants for TokenPaymentProof circuit (PaymentPublicInputsV2)
+// Placeholder: the token circuit has not been through the trusted setup yet, so
+// every point is zeroed and no V2 proof will verify until this file is replaced.
+// Circuit: payment_proof_token.circom with 8 public inputs
+// Curve: BN254 (alt_bn128)
+
+use crate::point::{G1Point, G2Point};
+
+/// Alpha point on G1 (uncompressed, 64 bytes)
+pub const VK_V2_ALPHA_G1: G1Point = G1Point::new([0u8; 64]);
+
+/// Beta point on G2 (uncompressed, 128 bytes)
+pub const VK_V2_BETA_G2: G2Point = G2Point::new([0u8; 128]);
+
+/// Gamma point on G2 (uncompressed, 128 bytes)
+pub const VK_V2_GAMMA_G2: G2Point = G2Point::new([0u8; 128]);
+
+/// Delta point on G2 (uncompressed, 128 bytes)
+pub const VK_V2_DELTA_G2: G2Point = G2Point::new([0u8; 128]);
+
+/// IC (Input Commitment) points on G1 for public inputs
+/// IC[0] is the constant term
+/// IC[1..9] correspond to: minAmount, recipientPubKeyX, recipientPubKeyY, maxBlockAge,
+/// currentTime, mintX, mintY, tokenAmount
+pub const VK_V2_IC: [G1Point; 9] = [G1Point::IDENTITY; 9];
+
+/// Signal layout hash: SHA256 over the public signal names in IC order,
+/// each encoded as u32 little-endian length followed by the UTF-8 name
+/// (V1 signals followed by mintX, mintY, tokenAmount)
+pub const VK_V2_SIGNAL_LAYOUT_HASH: [u8; 32] = [
+    0x73, 0x28, 0xb9, 0x92, 0xb8, 0xb0, 0x4f, 0x6f,
+    0xc5, 0x52, 0xce, 0x9f, 0x53, 0x21, 0xea, 0x6d,
+    0x14, 0x5b, 0xb4, 0x23, 0xef, 0x43, 0x6a, 0xb7,
+    0x01, 0xc8, 0xaf, 0x6b, 0x21, 0x28, 0x54, 0x8f,
+];
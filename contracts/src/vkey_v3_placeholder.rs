@@ -0,0 +1,36 @@
+// Verification key constants for InvoicePaymentProof circuit (PaymentPublicInputsV3)
+// Placeholder: the invoice-bound circuit has not been through the trusted setup
+// yet, so every point is zeroed and no V3 proof will verify until this file is
+// replaced.
+// Circuit: payment_proof_invoice.circom with 14 public inputs
+// Curve: BN254 (alt_bn128)
+
+use crate::point::{G1Point, G2Point};
+
+/// Alpha point on G1 (uncompressed, 64 bytes)
+pub const VK_V3_ALPHA_G1: G1Point = G1Point::new([0u8; 64]);
+
+/// Beta point on G2 (uncompressed, 128 bytes)
+pub const VK_V3_BETA_G2: G2Point = G2Point::new([0u8; 128]);
+
+/// Gamma point on G2 (uncompressed, 128 bytes)
+pub const VK_V3_GAMMA_G2: G2Point = G2Point::new([0u8; 128]);
+
+/// Delta point on G2 (uncompressed, 128 bytes)
+pub const VK_V3_DELTA_G2: G2Point = G2Point::new([0u8; 128]);
+
+/// IC (Input Commitment) points on G1 for public inputs
+/// IC[0] is the constant term
+/// IC[1..15] correspond to: minAmount, recipientPubKeyX, recipientPubKeyY, maxBlockAge,
+/// currentTime, invoiceId[0..4], validUntil, recipientSetRoot[0..4]
+pub const VK_V3_IC: [G1Point; 15] = [G1Point::IDENTITY; 15];
+
+/// Signal layout hash: SHA256 over the public signal names in IC order,
+/// each encoded as u32 little-endian length followed by the UTF-8 name
+/// (V1 signals followed by invoiceId0..3, validUntil, recipientSetRoot0..3)
+pub const VK_V3_SIGNAL_LAYOUT_HASH: [u8; 32] = [
+    0xc6, 0xb5, 0x0d, 0x53, 0x9f, 0x0f, 0x83, 0xcf,
+    0xbe, 0x7b, 0xcf, 0xbd, 0xf0, 0x53, 0x32, 0x73,
+    0x1a, 0x89, 0xcc, 0x9e, 0xd1, 0xa6, 0x79, 0x0d,
+    0x31, 0x37, 0x14, 0x84, 0x1e, 0xfd, 0x67, 0xd3,
+];
@@ -0,0 +1,35 @@
+// Verification key constants for WithdrawProof circuit (WithdrawPublicInputs)
+// Placeholder: the withdraw circuit has not been through the trusted setup yet,
+// so every point is zeroed and no withdrawal proof will verify until this file
+// is replaced.
+// Circuit: withdraw_proof.circom with 17 public inputs
+// Curve: BN254 (alt_bn128)
+
+use crate::point::{G1Point, G2Point};
+
+/// Alpha point on G1 (uncompressed, 64 bytes)
+pub const VK_WITHDRAW_ALPHA_G1: G1Point = G1Point::new([0u8; 64]);
+
+/// Beta point on G2 (uncompressed, 128 bytes)
+pub const VK_WITHDRAW_BETA_G2: G2Point = G2Point::new([0u8; 128]);
+
+/// Gamma point on G2 (uncompressed, 128 bytes)
+pub const VK_WITHDRAW_GAMMA_G2: G2Point = G2Point::new([0u8; 128]);
+
+/// Delta point on G2 (uncompressed, 128 bytes)
+pub const VK_WITHDRAW_DELTA_G2: G2Point = G2Point::new([0u8; 128]);
+
+/// IC (Input Commitment) points on G1 for public inputs
+/// IC[0] is the constant term
+/// IC[1..18] correspond to: root0..3, nullifier0..3, recipient0..3, amount,
+/// change0..3
+pub const VK_WITHDRAW_IC: [G1Point; 18] = [G1Point::IDENTITY; 18];
+
+/// Signal layout hash: SHA256 over the public signal names in IC order,
+/// each encoded as u32 little-endian length followed by the UTF-8 name
+pub const VK_WITHDRAW_SIGNAL_LAYOUT_HASH: [u8; 32] = [
+    0x91, 0x98, 0xf7, 0x37, 0xbd, 0x98, 0x90, 0xc0,
+    0xef, 0x30, 0xb2, 0x74, 0x49, 0xb1, 0x50, 0xec,
+    0x94, 0xfe, 0xb3, 0x3a, 0x79, 0x7c, 0x04, 0x97,
+    0xac, 0x95, 0x5e, 0x8b, 0x40, 0x29, 0x83, 0xb4,
+];
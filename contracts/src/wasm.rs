@@ -0,0 +1,172 @@
+// Browser bindings for checkout pages
+// Enabled with the `wasm` feature and built with wasm-pack. Proofs, public
+// signals and keys are the JSON files snarkjs writes, so a page can check a
+// proof before posting it to the facilitator.
+
+use serde_json::{json, Value};
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    client::{encode_payment_headers, parse_hex_array, parse_pubkey, verify_with_key, ClientError},
+    snarkjs::{parse_proof, parse_public_signals, parse_verification_key},
+    PaymentPublicInputsV3,
+};
+
+/// Whether a snarkjs proof verifies against a snarkjs key and public signals
+///
+/// Malformed input of any kind is `false`.
+#[wasm_bindgen]
+pub fn verify_proof_js(proof_json: &str, inputs_json: &str, vkey_json: &str) -> bool {
+    let verify = || -> Result<bool, ClientError> {
+        let proof = parse_proof(&json_value(proof_json)?)?;
+        let scalars = parse_public_signals(&json_value(inputs_json)?)?;
+        let vk = parse_verification_key(&json_value(vkey_json)?)?;
+        Ok(verify_with_key(&vk.as_key(), &proof, &scalars).is_ok())
+    };
+    verify().unwrap_or(false)
+}
+
+/// x402 headers for a snarkjs proof and its invoice-bound public inputs, as a
+/// JSON object of header name to value
+///
+/// `inputs_json` has the `PaymentPublicInputsV3` field names. Integers may be
+/// numbers or decimal strings, the recipient base58 or hex, and the other
+/// 32-byte fields hex.
+#[wasm_bindgen]
+pub fn encode_payment_header_js(proof_json: &str, inputs_json: &str) -> Result<String, JsError> {
+    payment_header_json(proof_json, inputs_json).map_err(|e| JsError::new(&e.to_string()))
+}
+
+fn payment_header_json(proof_json: &str, inputs_json: &str) -> Result<String, ClientError> {
+    let proof = parse_proof(&json_value(proof_json)?)?;
+    let public_inputs = payment_inputs_v3(&json_value(inputs_json)?)?;
+
+    let headers = encode_payment_headers(&proof, &public_inputs);
+    let object: serde_json::Map<String, Value> = headers
+        .to_pairs()
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), json!(value)))
+        .collect();
+    Ok(Value::Object(object).to_string())
+}
+
+fn json_value(text: &str) -> Result<Value, ClientError> {
+    serde_json::from_str(text).map_err(|_| ClientError::MalformedPayload("JSON"))
+}
+
+fn payment_inputs_v3(json: &Value) -> Result<PaymentPublicInputsV3, ClientError> {
+    const NAME: &str = "public inputs";
+    let field = |name: &str| json.get(name).ok_or(ClientError::InvalidField(NAME));
+    // Numbers above 2^53 lose precision in JavaScript, so strings are accepted
+    let integer = |name: &str| -> Result<i128, ClientError> {
+        let value = field(name)?;
+        value
+            .as_i64()
+            .map(i128::from)
+            .or_else(|| value.as_u64().map(i128::from))
+            .or_else(|| value.as_str().and_then(|s| s.parse().ok()))
+            .ok_or(ClientError::MalformedPayload(NAME))
+    };
+    let unsigned = |name: &str| {
+        u64::try_from(integer(name)?).map_err(|_| ClientError::MalformedPayload(NAME))
+    };
+    let signed = |name: &str| {
+        i64::try_from(integer(name)?).map_err(|_| ClientError::MalformedPayload(NAME))
+    };
+    let text = |name: &str| field(name)?.as_str().ok_or(ClientError::MalformedPayload(NAME));
+
+    Ok(PaymentPublicInputsV3 {
+        min_amount: unsigned("min_amount")?,
+        recipient_pubkey: parse_pubkey(text("recipient_pubkey")?)?,
+        max_block_age: unsigned("max_block_age")?,
+        current_time: signed("current_time")?,
+        invoice_id: parse_hex_array(text("invoice_id")?, "invoice id")?,
+        valid_until: signed("valid_until")?,
+        recipient_set_root: parse_hex_array(text("recipient_set_root")?, "recipient set root")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{decode_payment_headers, PaymentHeaders};
+
+    const PROOF: &str = r#"{
+        "pi_a": ["1", "2", "1"],
+        "pi_b": [["3", "4"], ["5", "6"], ["1", "0"]],
+        "pi_c": ["7", "8", "1"]
+    }"#;
+
+    fn inputs_json(min_amount: &str) -> String {
+        format!(
+            r#"{{
+                "min_amount": {},
+                "recipient_pubkey": "0x{}",
+                "max_block_age": 60,
+                "current_time": "1700000000",
+                "invoice_id": "{}",
+                "valid_until": 0,
+                "recipient_set_root": "{}"
+            }}"#,
+            min_amount,
+            "04".repeat(32),
+            "aa".repeat(32),
+            "5E".repeat(32)
+        )
+    }
+
+    #[test]
+    fn test_payment_header_json_decodes() {
+        let headers: Value =
+            serde_json::from_str(&payment_header_json(PROOF, &inputs_json("1000000")).unwrap())
+                .unwrap();
+        let header = |name: &str| headers[name].as_str().unwrap().to_string();
+        let headers = PaymentHeaders {
+            proof: header("X-ZK-PROOF"),
+            public_inputs: header("X-ZK-PUBLIC-INPUTS"),
+            invoice_id: header("X-ZK-INVOICE-ID"),
+        };
+
+        let (proof, public_inputs) = decode_payment_headers(&headers).unwrap();
+        assert_eq!(proof.c, parse_proof(&json_value(PROOF).unwrap()).unwrap().c);
+        assert_eq!(public_inputs.min_amount, 1000000);
+        assert_eq!(public_inputs.recipient_pubkey, [4u8; 32]);
+        assert_eq!(public_inputs.recipient_set_root, [0x5e; 32]);
+
+        // Integers beyond what a JavaScript number holds exactly, as strings
+        let large = payment_header_json(PROOF, &inputs_json(r#""18446744073709551615""#));
+        assert!(large.is_ok());
+    }
+
+    #[test]
+    fn test_payment_header_json_rejects_malformed() {
+        let name = "public inputs";
+        assert_eq!(
+            payment_header_json(PROOF, &inputs_json("-1")),
+            Err(ClientError::MalformedPayload(name))
+        );
+        assert_eq!(
+            payment_header_json(PROOF, &inputs_json(r#""18446744073709551616""#)),
+            Err(ClientError::MalformedPayload(name))
+        );
+        assert_eq!(
+            payment_header_json(PROOF, "{}"),
+            Err(ClientError::InvalidField(name))
+        );
+        assert_eq!(
+            payment_header_json("not json", &inputs_json("1")),
+            Err(ClientError::MalformedPayload("JSON"))
+        );
+    }
+
+    #[test]
+    fn test_verify_proof_js_rejects_without_panicking() {
+        let vkey = include_str!("../../circuits/build/verification_key.json");
+        let signals = r#"["1000000", "4", "5", "60", "1700000000"]"#;
+
+        assert!(!verify_proof_js(PROOF, signals, vkey));
+        assert!(!verify_proof_js(PROOF, r#"["1"]"#, vkey));
+        assert!(!verify_proof_js("{}", signals, vkey));
+        assert!(!verify_proof_js(PROOF, signals, "[]"));
+    }
+}
@@ -0,0 +1,35 @@
+//! Deployed binary size budget
+//!
+//! Deploy cost grows with the size of the .so, so it is checked against a
+//! fixed budget. Build it first, then run the ignored test:
+//!
+//! ```bash
+//! cargo build-bpf
+//! cargo test --test binary_size -- --ignored
+//! ```
+
+use std::path::PathBuf;
+
+/// Largest release .so we accept, in bytes
+const SO_SIZE_BUDGET: u64 = 500 * 1024;
+
+#[test]
+#[ignore = "needs the release .so from `cargo build-bpf`"]
+fn test_program_binary_within_budget() {
+    let path = std::env::var_os("X402_PROGRAM_SO")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| {
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target/deploy/x402_zk_verifier.so")
+        });
+    let size = std::fs::metadata(&path)
+        .unwrap_or_else(|e| panic!("{}: {} (run `cargo build-bpf` first)", path.display(), e))
+        .len();
+
+    assert!(
+        size <= SO_SIZE_BUDGET,
+        "{} is {} bytes, over the {} byte budget",
+        path.display(),
+        size,
+        SO_SIZE_BUDGET
+    );
+}
@@ -0,0 +1,80 @@
+//! Compute unit budget of a single verification
+//!
+//! The pairing and multiplication inputs are built in stack buffers, so a
+//! verification allocates nothing on the heap for them; this pins what the
+//! release program charges for a fixture proof, so a change that brings the
+//! allocations back, or otherwise slows the path down, fails here. Build it
+//! first, then run the ignored test:
+//!
+//! ```bash
+//! cargo build-bpf
+//! cargo test --test compute_units -- --ignored
+//! ```
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use borsh::BorshSerialize;
+    use solana_program_test::ProgramTest;
+    use solana_sdk::{
+        account::Account, pubkey::Pubkey, signature::Signer, transaction::Transaction,
+    };
+    use x402_zk_verifier::{
+        client,
+        test_utils::{fixture_inputs, fixture_key, fixture_proof, FIXTURE_KEY_ACCOUNT},
+    };
+
+    /// Most units one `VerifyWithInlineKey` of a five-input fixture proof may use
+    ///
+    /// The four-pair pairing and five scalar multiplications cost over 90,000 of
+    /// these in syscalls alone.
+    const SINGLE_VERIFY_UNIT_BUDGET: u64 = 130_000;
+
+    #[tokio::test]
+    #[ignore = "needs the release .so from `cargo build-bpf`"]
+    async fn test_single_verification_within_budget() {
+        let program_id = Pubkey::new_unique();
+        let mut program_test = ProgramTest::default();
+        program_test.prefer_bpf(true);
+        program_test.add_program("x402_zk_verifier", program_id, None);
+
+        let key = fixture_key(5);
+        program_test.add_account(
+            FIXTURE_KEY_ACCOUNT,
+            Account {
+                lamports: 1_000_000_000,
+                data: key.try_to_vec().unwrap(),
+                ..Account::default()
+            },
+        );
+        let mut context = program_test.start_with_context().await;
+
+        // The first input is wider than 64 bits, so every limb of the
+        // multiplication input is exercised
+        let inputs = fixture_inputs(5);
+        let proof = fixture_proof(&key, &inputs);
+        let instruction = client::verify_with_key_account_instruction(
+            &program_id,
+            &FIXTURE_KEY_ACCOUNT,
+            &key,
+            proof,
+            inputs,
+        );
+        let mut transaction =
+            Transaction::new_with_payer(&[instruction], Some(&context.payer.pubkey()));
+        transaction.sign(&[&context.payer], context.last_blockhash);
+        let result = context
+            .banks_client
+            .process_transaction_with_metadata(transaction)
+            .await
+            .unwrap();
+        assert_eq!(result.result, Ok(()));
+
+        let units = result.metadata.unwrap().compute_units_consumed;
+        assert!(
+            units <= SINGLE_VERIFY_UNIT_BUDGET,
+            "{} units, over the {} unit budget",
+            units,
+            SINGLE_VERIFY_UNIT_BUDGET
+        );
+    }
+}
@@ -0,0 +1,228 @@
+// Example consumer of the `cpi` module
+// `consumer_process` stands in for a downstream program that only runs once a
+// payment is proved: either by verifying a proof over CPI, or by accepting a
+// receipt recorded earlier. It passes the receipt on as its own return data so
+// the tests can see what the verifier reported. The verifier side is set up
+// with `test_utils`, as a downstream crate's tests would.
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use borsh::{BorshDeserialize, BorshSerialize};
+    use solana_program::{
+        account_info::AccountInfo, entrypoint::ProgramResult, msg, program::set_return_data,
+        program_error::ProgramError,
+    };
+    use solana_program_test::*;
+    use solana_sdk::{
+        account::{Account, AccountSharedData},
+        instruction::{AccountMeta, Instruction, InstructionError},
+        pubkey::Pubkey,
+        system_program,
+        transaction::TransactionError,
+    };
+    use x402_zk_verifier::{
+        cpi::{self, VerificationReceipt, VerifyCpiAccounts},
+        state::{PaymentReceipt, ReceiptStatus, StateAccount},
+        test_utils::{
+            create_receipt_for, setup_verifier_test, submit_with_return_data,
+            VerifierTestContext, VERIFIER_TEST_PROGRAM_ID,
+        },
+        *,
+    };
+
+    /// The verifier deployment the consumer trusts
+    const VERIFIER_ID: Pubkey = VERIFIER_TEST_PROGRAM_ID;
+
+    #[derive(BorshSerialize, BorshDeserialize)]
+    enum ConsumerInstruction {
+        /// Accounts: 0. verifier program, 1. system program, 2. verifier config,
+        /// 3. payment circuit's prepared key, 4. recipient's denylist marker
+        PayAndRun {
+            proof: Groth16Proof,
+            public_inputs: PaymentPublicInputs,
+        },
+        /// Accounts: 0. verifier program, 1. verifier config, 2. receipt
+        RunWithReceipt { proof_hash: [u8; 32] },
+    }
+
+    fn consumer_process(
+        _program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        instruction_data: &[u8],
+    ) -> ProgramResult {
+        let verifier_program = accounts.first().ok_or(ProgramError::NotEnoughAccountKeys)?;
+        if *verifier_program.key != VERIFIER_ID {
+            msg!("Not the trusted verifier");
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let receipt = match ConsumerInstruction::try_from_slice(instruction_data)? {
+            ConsumerInstruction::PayAndRun {
+                proof,
+                public_inputs,
+            } => cpi::verify_proof_cpi(
+                verifier_program,
+                VerifyCpiAccounts {
+                    system_program: &accounts[1],
+                    config: &accounts[2],
+                    prepared_vk: &accounts[3],
+                    marker: &accounts[4],
+                },
+                proof,
+                public_inputs,
+            )?,
+            ConsumerInstruction::RunWithReceipt { proof_hash } => {
+                cpi::read_receipt(verifier_program, &accounts[1], &accounts[2], &proof_hash)?
+            }
+        };
+        set_return_data(&receipt.try_to_vec()?);
+        Ok(())
+    }
+
+    /// The verifier harness with the consumer added, started
+    async fn start(consumer_id: Pubkey) -> VerifierTestContext {
+        let (mut program_test, mut ctx) = setup_verifier_test();
+        program_test.add_program("cpi_consumer", consumer_id, processor!(consumer_process));
+        ctx.start(program_test).await;
+        ctx
+    }
+
+    /// Submit `instruction` to the consumer, returning the result and the
+    /// consumer's return data
+    async fn run(
+        ctx: &mut VerifierTestContext,
+        consumer_id: Pubkey,
+        instruction: &ConsumerInstruction,
+        accounts: Vec<AccountMeta>,
+    ) -> (Result<(), TransactionError>, Option<VerificationReceipt>) {
+        let instruction = Instruction::new_with_borsh(consumer_id, instruction, accounts);
+        let (result, return_data) = submit_with_return_data(ctx.context(), instruction).await;
+        let receipt = (!return_data.is_empty())
+            .then(|| VerificationReceipt::try_from_slice(&return_data).unwrap());
+        (result, receipt)
+    }
+
+    fn payment_inputs() -> PaymentPublicInputs {
+        PaymentPublicInputs {
+            min_amount: 1000000,
+            recipient_pubkey: [4u8; 32],
+            max_block_age: 60,
+            current_time: 1700000000,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_verify_over_cpi_propagates_rejection() {
+        let consumer_id = Pubkey::new_unique();
+
+        // A coordinate at or above the field modulus is rejected by the
+        // verifier before the pairing, and the consumer fails with its code
+        let instruction = ConsumerInstruction::PayAndRun {
+            proof: Groth16Proof {
+                a: G1Point::new([0xff; 64]),
+                b: G2Point::new([0u8; 128]),
+                c: G1Point::new([0u8; 64]),
+            },
+            public_inputs: payment_inputs(),
+        };
+        let mut ctx = start(consumer_id).await;
+        let accounts = vec![
+            AccountMeta::new_readonly(VERIFIER_ID, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(ctx.config, false),
+            AccountMeta::new_readonly(pda::vkey_address(&VERIFIER_ID, CircuitId::Payment).0, false),
+            AccountMeta::new_readonly(
+                pda::denied_recipient_address(&VERIFIER_ID, &[4u8; 32]).0,
+                false,
+            ),
+        ];
+        let (result, receipt) = run(&mut ctx, consumer_id, &instruction, accounts).await;
+        assert_eq!(
+            result,
+            Err(TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(VerifierError::NonCanonicalEncoding as u32)
+            ))
+        );
+        assert_eq!(receipt, None);
+    }
+
+    fn receipt_accounts(
+        ctx: &VerifierTestContext,
+        verifier: Pubkey,
+        receipt: Pubkey,
+    ) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new_readonly(verifier, false),
+            AccountMeta::new_readonly(ctx.config, false),
+            AccountMeta::new_readonly(receipt, false),
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_consumer_reads_recorded_receipt() {
+        let consumer_id = Pubkey::new_unique();
+        let mut ctx = start(consumer_id).await;
+        let (address, recorded) = create_receipt_for(&mut ctx, [4u8; 32]);
+        let proof_hash = recorded.proof_hash;
+        let instruction = ConsumerInstruction::RunWithReceipt { proof_hash };
+
+        let accounts = receipt_accounts(&ctx, VERIFIER_ID, address);
+        let (result, receipt) = run(&mut ctx, consumer_id, &instruction, accounts).await;
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            receipt,
+            Some(VerificationReceipt {
+                proof_hash,
+                recipient: [4u8; 32],
+                min_amount: 1000000,
+            })
+        );
+
+        // A receipt for another proof does not count
+        let accounts = receipt_accounts(&ctx, VERIFIER_ID, address);
+        let (result, _) = run(
+            &mut ctx,
+            consumer_id,
+            &ConsumerInstruction::RunWithReceipt {
+                proof_hash: [8u8; 32],
+            },
+            accounts,
+        )
+        .await;
+        assert_eq!(
+            result,
+            Err(TransactionError::InstructionError(0, InstructionError::InvalidSeeds))
+        );
+
+        // The consumer refuses a program it does not trust as the verifier
+        let accounts = receipt_accounts(&ctx, consumer_id, address);
+        let (result, _) = run(&mut ctx, consumer_id, &instruction, accounts).await;
+        assert_eq!(
+            result,
+            Err(TransactionError::InstructionError(0, InstructionError::IncorrectProgramId))
+        );
+
+        // A refunded payment no longer counts
+        let refunded = PaymentReceipt {
+            status: ReceiptStatus::Refunded,
+            ..recorded
+        };
+        let account = Account {
+            lamports: 1_000_000_000,
+            data: refunded.to_account_data(),
+            owner: VERIFIER_ID,
+            ..Account::default()
+        };
+        ctx.context().set_account(&address, &AccountSharedData::from(account));
+        let accounts = receipt_accounts(&ctx, VERIFIER_ID, address);
+        let (result, _) = run(&mut ctx, consumer_id, &instruction, accounts).await;
+        assert_eq!(
+            result,
+            Err(TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(VerifierError::ReceiptAlreadyRefunded as u32)
+            ))
+        );
+    }
+}
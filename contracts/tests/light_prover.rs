@@ -0,0 +1,260 @@
+//! Real Groth16 proofs through the program
+//!
+//! The registered circuits' keys come from a trusted setup, so the other
+//! integration tests either forge proofs against a trapdoor key or write
+//! receipts directly. Here a light prover proves statements of a small circuit
+//! mirroring the payment statement and the proofs go through
+//! `VerifyWithInlineKey` in a `ProgramTest` bank, which checks the point
+//! encodings and the pairing against a key no test chose by hand. The key has
+//! six IC points, too many for a transaction, so it sits in an account. Run
+//! with
+//!
+//! ```bash
+//! cargo test --features prove-tests --test light_prover
+//! ```
+//!
+//! The setup and the statements are drawn from fixed seeds, so every run
+//! proves the same statements against the same key.
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use ark_bn254::{Bn254, Fq, Fq2, Fr, G1Affine, G2Affine};
+    use ark_ff::{BigInteger, PrimeField};
+    use ark_groth16::{Groth16, Proof, ProvingKey};
+    use ark_relations::{
+        lc,
+        r1cs::{
+            ConstraintSynthesizer, ConstraintSystemRef, LinearCombination, SynthesisError, Variable,
+        },
+    };
+    use ark_std::rand::{rngs::StdRng, Rng, SeedableRng};
+    use borsh::BorshSerialize;
+    use solana_sdk::{
+        account::Account, instruction::InstructionError, pubkey::Pubkey,
+        transaction::TransactionError,
+    };
+    use x402_zk_verifier::{
+        client,
+        endian::Be32,
+        inline_key::InlineVerificationKey,
+        test_utils::{setup_verifier_test, submit_with_return_data},
+        G1Point, G2Point, Groth16Proof, PaymentPublicInputs,
+    };
+
+    /// Seed of the setup, fixed so the key is the same on every run
+    const SETUP_SEED: u64 = 0x7832;
+
+    /// Seeds of the proved statements and their witnesses
+    const STATEMENT_SEEDS: [u64; 3] = [1, 0x5eed, 0x2c_3f91];
+
+    /// Bits of the range checks, as `LessEqThan(64)` in payment_proof.circom
+    const RANGE_BITS: usize = 64;
+
+    /// payment_proof.circom without the signature: `actualAmount >= minAmount`
+    /// and `currentTime - paymentTime <= maxBlockAge`, over the public inputs
+    /// of `PaymentPublicInputs::to_scalars`
+    ///
+    /// The recipient limbs are in no constraint of their own; Groth16 binds
+    /// every public input through its IC point regardless.
+    struct LightPaymentCircuit {
+        statement: [u64; 5],
+        /// Private inputs, `None` during setup
+        actual_amount: Option<u64>,
+        payment_time: Option<u64>,
+    }
+
+    impl LightPaymentCircuit {
+        fn setup() -> Self {
+            LightPaymentCircuit {
+                statement: [0; 5],
+                actual_amount: None,
+                payment_time: None,
+            }
+        }
+    }
+
+    /// Constrain `value`, the witness `of` its linear combination, to 64 bits
+    fn enforce_range(
+        cs: &ConstraintSystemRef<Fr>,
+        value: LinearCombination<Fr>,
+        of: Option<u64>,
+    ) -> Result<(), SynthesisError> {
+        let mut sum = lc!();
+        let mut coeff = Fr::from(1u64);
+        for i in 0..RANGE_BITS {
+            let bit = cs.new_witness_variable(|| {
+                let of = of.ok_or(SynthesisError::AssignmentMissing)?;
+                Ok(Fr::from((of >> i) & 1))
+            })?;
+            cs.enforce_constraint(lc!() + bit, lc!() + bit - Variable::One, lc!())?;
+            sum = sum + (coeff, bit);
+            coeff += coeff;
+        }
+        cs.enforce_constraint(sum, lc!() + Variable::One, value)
+    }
+
+    impl ConstraintSynthesizer<Fr> for LightPaymentCircuit {
+        fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+            let [min_amount, _, _, max_block_age, current_time] = self.statement;
+            let mut public = Vec::with_capacity(self.statement.len());
+            for value in self.statement {
+                public.push(cs.new_input_variable(|| Ok(Fr::from(value)))?);
+            }
+
+            let actual_amount = cs.new_witness_variable(|| {
+                self.actual_amount
+                    .map(Fr::from)
+                    .ok_or(SynthesisError::AssignmentMissing)
+            })?;
+            let payment_time = cs.new_witness_variable(|| {
+                self.payment_time
+                    .map(Fr::from)
+                    .ok_or(SynthesisError::AssignmentMissing)
+            })?;
+
+            // actualAmount - minAmount fits in 64 bits
+            enforce_range(
+                &cs,
+                lc!() + actual_amount - public[0],
+                self.actual_amount
+                    .map(|amount| amount.wrapping_sub(min_amount)),
+            )?;
+            // maxBlockAge - (currentTime - paymentTime) fits in 64 bits
+            enforce_range(
+                &cs,
+                lc!() + public[3] - public[4] + payment_time,
+                self.payment_time
+                    .map(|time| max_block_age.wrapping_sub(current_time.wrapping_sub(time))),
+            )
+        }
+    }
+
+    fn be_bytes(field: &Fq) -> Vec<u8> {
+        field.into_bigint().to_bytes_be()
+    }
+
+    fn g1(point: &G1Affine) -> G1Point {
+        let mut bytes = [0u8; 64];
+        bytes[..32].copy_from_slice(&be_bytes(&point.x));
+        bytes[32..].copy_from_slice(&be_bytes(&point.y));
+        G1Point::new(bytes)
+    }
+
+    /// x.c1 || x.c0 || y.c1 || y.c0, the syscalls' order
+    fn g2(point: &G2Affine) -> G2Point {
+        let coordinates: [&Fq2; 2] = [&point.x, &point.y];
+        let mut bytes = [0u8; 128];
+        for (i, coordinate) in coordinates.iter().enumerate() {
+            bytes[64 * i..64 * i + 32].copy_from_slice(&be_bytes(&coordinate.c1));
+            bytes[64 * i + 32..64 * i + 64].copy_from_slice(&be_bytes(&coordinate.c0));
+        }
+        G2Point::new(bytes)
+    }
+
+    fn inline_key(pk: &ProvingKey<Bn254>) -> InlineVerificationKey {
+        let vk = &pk.vk;
+        InlineVerificationKey {
+            alpha_g1: g1(&vk.alpha_g1),
+            beta_g2: g2(&vk.beta_g2),
+            gamma_g2: g2(&vk.gamma_g2),
+            delta_g2: g2(&vk.delta_g2),
+            ic: vk.gamma_abc_g1.iter().map(g1).collect(),
+        }
+    }
+
+    fn groth16_proof(proof: &Proof<Bn254>) -> Groth16Proof {
+        Groth16Proof {
+            a: g1(&proof.a),
+            b: g2(&proof.b),
+            c: g1(&proof.c),
+        }
+    }
+
+    fn inputs(statement: &PaymentPublicInputs) -> Vec<Be32> {
+        statement
+            .to_scalars()
+            .into_iter()
+            .map(Be32::from_u64)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_light_prover_proof_verifies_on_chain() {
+        let pk = Groth16::<Bn254>::generate_random_parameters_with_reduction(
+            LightPaymentCircuit::setup(),
+            &mut StdRng::seed_from_u64(SETUP_SEED),
+        )
+        .unwrap();
+        let vk = inline_key(&pk);
+        assert_eq!(vk.ic.len(), 6);
+
+        let (mut program_test, mut ctx) = setup_verifier_test();
+        let key_account = Pubkey::new_unique();
+        program_test.add_account(
+            key_account,
+            Account {
+                lamports: 1_000_000_000,
+                data: vk.try_to_vec().unwrap(),
+                owner: Pubkey::new_unique(),
+                ..Account::default()
+            },
+        );
+        ctx.start(program_test).await;
+        let program_id = ctx.program_id;
+
+        for seed in STATEMENT_SEEDS {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let min_amount = rng.gen_range(1..1_000_000_000u64);
+            let statement = PaymentPublicInputs {
+                min_amount,
+                recipient_pubkey: rng.gen(),
+                max_block_age: 60,
+                current_time: 1700000000,
+            };
+            let circuit = LightPaymentCircuit {
+                statement: statement.to_scalars(),
+                actual_amount: Some(min_amount + rng.gen_range(0..1_000_000u64)),
+                payment_time: Some(1700000000 - rng.gen_range(0..=60u64)),
+            };
+            let proof =
+                Groth16::<Bn254>::create_random_proof_with_reduction(circuit, &pk, &mut rng)
+                    .unwrap();
+            let proof = groth16_proof(&proof);
+
+            let instruction = client::verify_with_key_account_instruction(
+                &program_id,
+                &key_account,
+                &vk,
+                proof.clone(),
+                inputs(&statement),
+            );
+            let (result, return_data) = submit_with_return_data(ctx.context(), instruction).await;
+            assert_eq!(result, Ok(()), "seed {seed}");
+            assert_eq!(return_data, vk.hash());
+
+            // The same proof names no other recipient
+            let mut recipient_pubkey = statement.recipient_pubkey;
+            recipient_pubkey[0] ^= 1;
+            let other = PaymentPublicInputs {
+                recipient_pubkey,
+                ..statement
+            };
+            let instruction = client::verify_with_key_account_instruction(
+                &program_id,
+                &key_account,
+                &vk,
+                proof,
+                inputs(&other),
+            );
+            let (result, _) = submit_with_return_data(ctx.context(), instruction).await;
+            assert_eq!(
+                result,
+                Err(TransactionError::InstructionError(
+                    0,
+                    InstructionError::InvalidArgument
+                )),
+                "seed {seed}"
+            );
+        }
+    }
+}
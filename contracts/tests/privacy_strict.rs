@@ -0,0 +1,86 @@
+//! Statement values in transaction logs
+//!
+//! A `privacy-strict` build logs no value taken from a proof or its public
+//! inputs, and other builds do; both are checked against the logs of a real
+//! transaction. Run the strict side with
+//!
+//! ```bash
+//! cargo test --features privacy-strict --test privacy_strict
+//! ```
+//!
+//! The ignored test checks from a default run that every strict feature
+//! combination compiles.
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use std::{path::Path, process::Command};
+
+    use solana_sdk::{signature::Signer, transaction::Transaction};
+    use x402_zk_verifier::{
+        client, logging::PRIVACY_STRICT, test_utils::setup_verifier_test, G1Point, G2Point,
+        Groth16Proof, PaymentPublicInputs,
+    };
+
+    /// Feature sets of the build matrix's strict entries
+    const STRICT_FEATURES: [&str; 2] = ["privacy-strict", "privacy-strict,diagnostics"];
+
+    #[test]
+    #[ignore = "runs cargo check once per strict feature set"]
+    fn test_privacy_strict_builds() {
+        let target_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("privacy-strict");
+        for features in STRICT_FEATURES {
+            let status = Command::new(env!("CARGO"))
+                .current_dir(env!("CARGO_MANIFEST_DIR"))
+                .args(["check", "--lib", "--features", features, "--target-dir"])
+                .arg(&target_dir)
+                .status()
+                .unwrap();
+            assert!(status.success(), "--features {} does not compile", features);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_logs_leak_inputs_only_outside_strict_builds() {
+        let (program_test, mut ctx) = setup_verifier_test();
+        ctx.start(program_test).await;
+
+        // The all-zero proof passes every encoding check, so the instruction
+        // gets as far as the verification key before it fails
+        let public_inputs = PaymentPublicInputs {
+            min_amount: 1_234_567,
+            recipient_pubkey: [4u8; 32],
+            max_block_age: 60,
+            current_time: 1_700_000_123,
+        };
+        let values = [public_inputs.min_amount, public_inputs.current_time as u64];
+        let proof = Groth16Proof {
+            a: G1Point::new([0u8; 64]),
+            b: G2Point::new([0u8; 128]),
+            c: G1Point::new([0u8; 64]),
+        };
+        let instruction = client::verify_proof_instruction(&ctx.program_id, proof, public_inputs);
+
+        let context = ctx.context();
+        let blockhash = context.get_new_latest_blockhash().await.unwrap();
+        let mut transaction =
+            Transaction::new_with_payer(&[instruction], Some(&context.payer.pubkey()));
+        transaction.sign(&[&context.payer], blockhash);
+        let logs = context
+            .banks_client
+            .process_transaction_with_metadata(transaction)
+            .await
+            .unwrap()
+            .metadata
+            .unwrap()
+            .log_messages;
+        assert!(logs.iter().any(|line| line.contains("Verifying ZK payment proof")));
+
+        // `sol_log_64` writes hex, `msg!` formatting would write decimal
+        let leaked = logs.iter().any(|line| {
+            values.iter().any(|value| {
+                line.contains(&value.to_string()) || line.contains(&format!("{:#x}", value))
+            })
+        });
+        assert_eq!(leaked, !PRIVACY_STRICT, "{:#?}", logs);
+    }
+}
@@ -0,0 +1,60 @@
+//! Browser bindings under a wasm runtime
+//!
+//! Run with `wasm-pack test --node -- --features wasm`.
+#![cfg(all(target_arch = "wasm32", feature = "wasm"))]
+
+use wasm_bindgen_test::*;
+use x402_zk_verifier::{
+    client::{decode_payment_headers, PaymentHeaders},
+    wasm::{encode_payment_header_js, verify_proof_js},
+};
+
+const VKEY: &str = include_str!("../../circuits/build/verification_key.json");
+
+const PROOF: &str = r#"{
+    "pi_a": ["1", "2", "1"],
+    "pi_b": [["3", "4"], ["5", "6"], ["1", "0"]],
+    "pi_c": ["7", "8", "1"]
+}"#;
+
+#[wasm_bindgen_test]
+fn test_mock_proof_rejected() {
+    assert!(!verify_proof_js(PROOF, r#"["1000000", "4", "5", "60", "1700000000"]"#, VKEY));
+}
+
+#[wasm_bindgen_test]
+fn test_malformed_json_rejected() {
+    assert!(!verify_proof_js("{", "[]", VKEY));
+    assert!(!verify_proof_js(PROOF, r#"["x"]"#, VKEY));
+    assert!(!verify_proof_js(PROOF, "[]", "{}"));
+}
+
+#[wasm_bindgen_test]
+fn test_payment_header_round_trip() {
+    let inputs = format!(
+        r#"{{
+            "min_amount": "1000000",
+            "recipient_pubkey": "0x{}",
+            "max_block_age": 60,
+            "current_time": 1700000000,
+            "invoice_id": "{}",
+            "valid_until": 0,
+            "recipient_set_root": "{}"
+        }}"#,
+        "04".repeat(32),
+        "aa".repeat(32),
+        "00".repeat(32)
+    );
+    let json: serde_json::Value =
+        serde_json::from_str(&encode_payment_header_js(PROOF, &inputs).unwrap()).unwrap();
+    let header = |name: &str| json[name].as_str().unwrap().to_string();
+
+    let (_, public_inputs) = decode_payment_headers(&PaymentHeaders {
+        proof: header("X-ZK-PROOF"),
+        public_inputs: header("X-ZK-PUBLIC-INPUTS"),
+        invoice_id: header("X-ZK-INVOICE-ID"),
+    })
+    .unwrap();
+    assert_eq!(public_inputs.min_amount, 1000000);
+    assert_eq!(public_inputs.invoice_id, [0xaa; 32]);
+}